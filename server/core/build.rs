@@ -30,6 +30,7 @@ fn main() {
         .file("vips_wrapper/thumbnail.c")
         .file("vips_wrapper/image_info.c")
         .file("vips_wrapper/image_conversion.c")
+        .file("vips_wrapper/sharpness.c")
         .warnings_into_errors(true);
     for flag in flags {
         if !flag.is_empty() {
@@ -0,0 +1 @@
+ALTER TABLE Asset DROP COLUMN missing_since;
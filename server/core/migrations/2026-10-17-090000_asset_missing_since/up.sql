@@ -0,0 +1,4 @@
+-- Set when the Asset's file was found to no longer exist,
+-- UTC timestamp in milliseconds since UNIX epoch.
+-- Missing assets are hidden from the timeline and purged after a grace period.
+ALTER TABLE Asset ADD COLUMN missing_since INTEGER;
@@ -0,0 +1,2 @@
+ALTER TABLE Asset DROP COLUMN file_size;
+ALTER TABLE Asset DROP COLUMN file_mtime;
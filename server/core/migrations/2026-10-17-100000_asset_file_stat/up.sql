@@ -0,0 +1,4 @@
+-- Modification time (UTC timestamp in milliseconds since UNIX epoch) and size in bytes
+-- of the file when it was last indexed, used to notice files that were changed in place
+ALTER TABLE Asset ADD COLUMN file_mtime INTEGER;
+ALTER TABLE Asset ADD COLUMN file_size INTEGER;
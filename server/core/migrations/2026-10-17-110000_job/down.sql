@@ -0,0 +1,2 @@
+DROP INDEX job_state_index;
+DROP TABLE Job;
@@ -0,0 +1,25 @@
+-- Work done by the actors (indexing, thumbnails, video packaging, image conversion),
+-- kept after the job is done so failures stay visible across restarts.
+-- No foreign keys: jobs outlive the Assets, Albums and AssetRootDirs they refer to.
+CREATE TABLE Job (
+  job_id INTEGER PRIMARY KEY NOT NULL,
+  -- see model::JobKind for values
+  kind INTEGER NOT NULL,
+  -- 1: queued, 2: running, 3: finished, 4: failed, 5: cancelled
+  state INTEGER NOT NULL,
+  -- what the job is working on, depending on kind
+  asset_id INTEGER,
+  album_id INTEGER,
+  asset_root_dir_id INTEGER,
+  -- unit depends on kind: files scanned for indexing, milliseconds of output for ffmpeg
+  progress_done INTEGER,
+  -- NULL if not known (yet)
+  progress_total INTEGER,
+  error TEXT,
+  -- milliseconds since UNIX epoch
+  created_at INTEGER NOT NULL,
+  started_at INTEGER,
+  finished_at INTEGER
+) STRICT;
+
+CREATE INDEX job_state_index ON Job(state, job_id);
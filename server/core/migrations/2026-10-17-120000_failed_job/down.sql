@@ -0,0 +1,38 @@
+DROP TABLE ToolVersion;
+
+CREATE TABLE FailedThumbnailJob (
+  asset_id INTEGER PRIMARY KEY NOT NULL,
+  file_hash BLOB NOT NULL,
+  -- milliseconds since UNIX epoch
+  date INTEGER NOT NULL,
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id)
+) STRICT;
+
+CREATE TABLE FailedFFmpeg (
+  asset_id INTEGER PRIMARY KEY NOT NULL,
+  file_hash BLOB NOT NULL,
+  -- milliseconds since UNIX epoch
+  date INTEGER NOT NULL,
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id)
+);
+
+CREATE TABLE FailedShakaPackager (
+  asset_id INTEGER PRIMARY KEY NOT NULL,
+  file_hash BLOB NOT NULL,
+  -- milliseconds since UNIX epoch
+  date INTEGER NOT NULL,
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id)
+);
+
+INSERT INTO FailedThumbnailJob (asset_id, file_hash, date)
+SELECT asset_id, file_hash, last_failed_at FROM FailedJob WHERE kind = 1 AND file_hash IS NOT NULL;
+
+INSERT INTO FailedFFmpeg (asset_id, file_hash, date)
+SELECT asset_id, file_hash, last_failed_at FROM FailedJob WHERE kind = 2 AND tool = 1 AND file_hash IS NOT NULL;
+
+-- mpd_generator and errors not caused by a tool were part of packaging before
+INSERT INTO FailedShakaPackager (asset_id, file_hash, date)
+SELECT asset_id, file_hash, last_failed_at FROM FailedJob
+WHERE kind = 2 AND tool IS NOT 1 AND file_hash IS NOT NULL;
+
+DROP TABLE FailedJob;
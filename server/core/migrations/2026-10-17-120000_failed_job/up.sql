@@ -0,0 +1,50 @@
+-- Replaces FailedThumbnailJob, FailedFFmpeg and FailedShakaPackager,
+-- which only recorded that something failed but not why.
+CREATE TABLE FailedJob (
+  failed_job_id INTEGER PRIMARY KEY NOT NULL,
+  asset_id INTEGER NOT NULL,
+  -- 1: thumbnail, 2: video packaging
+  kind INTEGER NOT NULL,
+  -- Asset.hash at the time of the failure, the job is retried once the file changes
+  file_hash BLOB,
+  -- external program that failed, NULL if the error did not come from one
+  -- 1: ffmpeg, 2: shaka packager, 3: mpd_generator
+  tool INTEGER,
+  command TEXT,
+  -- end of the output of the failed program
+  stderr TEXT,
+  error TEXT NOT NULL,
+  attempts INTEGER NOT NULL,
+  -- milliseconds since UNIX epoch
+  first_failed_at INTEGER NOT NULL,
+  last_failed_at INTEGER NOT NULL,
+  -- not retried automatically before this
+  retry_after INTEGER NOT NULL,
+  -- never retried automatically unless the file changes
+  is_ignored INTEGER NOT NULL,
+  UNIQUE (asset_id, kind),
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id)
+) STRICT;
+
+INSERT INTO FailedJob (asset_id, kind, file_hash, error, attempts, first_failed_at, last_failed_at, retry_after, is_ignored)
+SELECT asset_id, 1, file_hash, 'unknown error', 1, date, date, date, 0 FROM FailedThumbnailJob;
+
+INSERT INTO FailedJob (asset_id, kind, file_hash, tool, error, attempts, first_failed_at, last_failed_at, retry_after, is_ignored)
+SELECT asset_id, 2, file_hash, 1, 'unknown error', 1, date, date, date, 0 FROM FailedFFmpeg;
+
+-- packaging runs after ffmpeg, a video can only have one FailedJob of each kind
+INSERT INTO FailedJob (asset_id, kind, file_hash, tool, error, attempts, first_failed_at, last_failed_at, retry_after, is_ignored)
+SELECT asset_id, 2, file_hash, 2, 'unknown error', 1, date, date, date, 0 FROM FailedShakaPackager
+WHERE asset_id NOT IN (SELECT asset_id FROM FailedFFmpeg);
+
+DROP TABLE FailedThumbnailJob;
+DROP TABLE FailedFFmpeg;
+DROP TABLE FailedShakaPackager;
+
+-- Versions of external programs seen at the last startup, failures caused by a program
+-- are retried when its version changes.
+CREATE TABLE ToolVersion (
+  -- same values as FailedJob.tool
+  tool INTEGER PRIMARY KEY NOT NULL,
+  version TEXT NOT NULL
+) STRICT;
@@ -0,0 +1,2 @@
+DROP TABLE UserToken;
+DROP TABLE User;
@@ -0,0 +1,30 @@
+CREATE TABLE User (
+  user_id INTEGER PRIMARY KEY NOT NULL,
+  name TEXT UNIQUE NOT NULL,
+  -- argon2 hash in PHC string format
+  password_hash TEXT NOT NULL,
+  -- 1: admin, 2: user
+  role INTEGER NOT NULL,
+  -- milliseconds since UNIX epoch
+  created_at INTEGER NOT NULL
+) STRICT;
+
+-- Login sessions and API tokens. Only a hash of the token is stored.
+CREATE TABLE UserToken (
+  user_token_id INTEGER PRIMARY KEY NOT NULL,
+  user_id INTEGER NOT NULL,
+  -- 1: session, 2: API token
+  kind INTEGER NOT NULL,
+  -- given by the user for API tokens
+  name TEXT,
+  -- SHA-256 of the token
+  token_hash BLOB UNIQUE NOT NULL,
+  -- milliseconds since UNIX epoch
+  created_at INTEGER NOT NULL,
+  -- NULL for API tokens, which are valid until deleted
+  expires_at INTEGER,
+  last_used_at INTEGER,
+  FOREIGN KEY (user_id) REFERENCES User(user_id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX user_token_user_id_index ON UserToken(user_id);
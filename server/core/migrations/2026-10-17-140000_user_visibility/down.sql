@@ -0,0 +1,9 @@
+UPDATE Asset SET is_hidden = 1
+WHERE asset_id IN (SELECT asset_id FROM UserAsset WHERE is_hidden = 1);
+
+DROP TABLE UserAsset;
+
+ALTER TABLE TimelineGroup DROP COLUMN is_private;
+ALTER TABLE TimelineGroup DROP COLUMN owner_id;
+ALTER TABLE Album DROP COLUMN is_private;
+ALTER TABLE Album DROP COLUMN owner_id;
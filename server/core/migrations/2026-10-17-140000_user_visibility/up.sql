@@ -0,0 +1,36 @@
+-- No foreign keys on owner_id so the columns can be dropped again,
+-- deleting a User deletes their private Albums and TimelineGroups and
+-- clears owner_id of the others.
+
+-- NULL for Albums created before there were users
+ALTER TABLE Album ADD COLUMN owner_id INTEGER;
+-- private Albums are only visible to their owner, others to everyone
+ALTER TABLE Album ADD COLUMN is_private INTEGER NOT NULL DEFAULT 0 CHECK (is_private IN (0, 1));
+
+-- NULL for TimelineGroups created before there were users
+ALTER TABLE TimelineGroup ADD COLUMN owner_id INTEGER;
+-- private TimelineGroups are only shown to their owner,
+-- other users see the Assets in it like ungrouped ones
+ALTER TABLE TimelineGroup ADD COLUMN is_private INTEGER NOT NULL DEFAULT 0 CHECK (is_private IN (0, 1));
+
+-- Per user state of an Asset, only has rows for Assets with at least one flag set
+CREATE TABLE UserAsset (
+  user_id INTEGER NOT NULL,
+  asset_id INTEGER NOT NULL,
+  -- hidden Assets are not shown in the timeline or in albums
+  is_hidden INTEGER NOT NULL CHECK (is_hidden IN (0, 1)),
+  -- archived Assets are not shown in the timeline, but still in albums
+  is_archived INTEGER NOT NULL CHECK (is_archived IN (0, 1)),
+  PRIMARY KEY (user_id, asset_id),
+  FOREIGN KEY (user_id) REFERENCES User(user_id) ON DELETE CASCADE,
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX user_asset_asset_id_index ON UserAsset(asset_id);
+
+-- Asset.is_hidden is replaced by UserAsset.is_hidden and no longer set,
+-- Assets hidden so far stay hidden for all existing users and for users created later
+INSERT INTO UserAsset (user_id, asset_id, is_hidden, is_archived)
+SELECT User.user_id, Asset.asset_id, 1, 0
+FROM User, Asset
+WHERE Asset.is_hidden = 1;
@@ -0,0 +1,27 @@
+-- Links that show an Album, TimelineGroup or single Asset to people without an account.
+-- Only a hash of the token is stored.
+CREATE TABLE ShareLink (
+  share_link_id INTEGER PRIMARY KEY NOT NULL,
+  -- SHA-256 of the token
+  token_hash BLOB UNIQUE NOT NULL,
+  -- exactly one of album_id, timeline_group_id and asset_id is set
+  album_id INTEGER,
+  timeline_group_id INTEGER,
+  asset_id INTEGER,
+  created_by_id INTEGER NOT NULL,
+  -- milliseconds since UNIX epoch
+  created_at INTEGER NOT NULL,
+  -- NULL for links that are valid until deleted
+  expires_at INTEGER,
+  -- argon2 hash in PHC string format, NULL if no password is needed
+  password_hash TEXT,
+  -- if original files may be downloaded, otherwise only thumbnails and representations are served
+  allow_download INTEGER NOT NULL CHECK (allow_download IN (0, 1)),
+  FOREIGN KEY (album_id) REFERENCES Album(album_id) ON DELETE CASCADE,
+  FOREIGN KEY (timeline_group_id) REFERENCES TimelineGroup(timeline_group_id) ON DELETE CASCADE,
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id) ON DELETE CASCADE,
+  FOREIGN KEY (created_by_id) REFERENCES User(user_id) ON DELETE CASCADE,
+  CHECK ((album_id IS NOT NULL) + (timeline_group_id IS NOT NULL) + (asset_id IS NOT NULL) = 1)
+) STRICT;
+
+CREATE INDEX share_link_created_by_id_index ON ShareLink(created_by_id);
@@ -0,0 +1,2 @@
+DROP TABLE NearDuplicate;
+ALTER TABLE Asset DROP COLUMN perceptual_hash;
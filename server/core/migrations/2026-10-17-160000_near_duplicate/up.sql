@@ -0,0 +1,12 @@
+-- dHash of the image (the snapshot for videos) as 8 bytes, computed with the thumbnails
+ALTER TABLE Asset ADD COLUMN perceptual_hash BLOB;
+
+-- Groups of visually similar Assets, replaced every time near duplicates are searched
+CREATE TABLE NearDuplicate (
+  asset_id INTEGER PRIMARY KEY NOT NULL,
+  -- smallest asset_id in the group
+  group_id INTEGER NOT NULL,
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX near_duplicate_group_id_index ON NearDuplicate(group_id);
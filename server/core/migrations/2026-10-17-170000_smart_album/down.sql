@@ -0,0 +1 @@
+ALTER TABLE Album DROP COLUMN smart_filter;
@@ -0,0 +1,4 @@
+-- JSON of the saved filter of smart albums, NULL for normal albums.
+-- The AlbumItems of smart albums are the Assets matching the filter ordered by taken_date,
+-- they are rebuilt whenever Assets are indexed and not edited by hand.
+ALTER TABLE Album ADD COLUMN smart_filter TEXT;
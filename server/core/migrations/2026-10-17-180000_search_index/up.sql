@@ -0,0 +1,38 @@
+-- Full text index over names and descriptions of Assets, Albums and TimelineGroups.
+-- There are no triggers, the repository functions changing the indexed columns
+-- update it (see repository::search_index).
+-- rowid is (id << 2) | kind with kind 1 = Asset, 2 = Album, 3 = TimelineGroup
+-- so that rows can be replaced without scanning the whole table.
+CREATE VIRTUAL TABLE SearchIndex USING fts5(
+  -- path in the root dir for Assets
+  name,
+  -- ImageDescription for Assets, description and text items for Albums
+  description,
+  -- XPKeywords and IPTC Keywords of Assets
+  keywords,
+  tokenize = 'unicode61 remove_diacritics 2',
+  prefix = '2 3'
+);
+
+INSERT INTO SearchIndex (rowid, name, description, keywords)
+SELECT (asset_id << 2) | 1, file_path,
+  json_extract(exif, '$[0].EXIF.ImageDescription'),
+  IFNULL(json_extract(exif, '$[0].EXIF.XPKeywords'), '') || ' ' || IFNULL(json_extract(exif, '$[0].IPTC.Keywords'), '')
+FROM (
+  SELECT asset_id, file_path,
+    CASE WHEN json_valid(CAST(exiftool_output AS TEXT)) THEN CAST(exiftool_output AS TEXT) ELSE '[]' END AS exif
+  FROM Asset
+);
+
+INSERT INTO SearchIndex (rowid, name, description, keywords)
+SELECT (album_id << 2) | 2, name,
+  IFNULL(description, '') || ' ' || IFNULL((
+    SELECT group_concat(text, ' ') FROM AlbumItem
+    WHERE AlbumItem.album_id = Album.album_id AND AlbumItem.ty = 2
+  ), ''),
+  NULL
+FROM Album;
+
+INSERT INTO SearchIndex (rowid, name, description, keywords)
+SELECT (timeline_group_id << 2) | 3, name, NULL, NULL
+FROM TimelineGroup;
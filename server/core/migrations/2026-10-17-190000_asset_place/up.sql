@@ -0,0 +1,18 @@
+-- Place an Asset's GPS coordinates were reverse geocoded to
+CREATE TABLE AssetPlace (
+  asset_id INTEGER PRIMARY KEY NOT NULL,
+  -- Asset.gps_latitude/gps_longitude that were looked up,
+  -- the Asset is geocoded again when they no longer match
+  gps_latitude INTEGER NOT NULL,
+  gps_longitude INTEGER NOT NULL,
+  -- all NULL if the coordinates are not inside any country
+  country_code TEXT,
+  country TEXT,
+  admin_region TEXT,
+  city TEXT,
+  geocoded_at INTEGER NOT NULL,
+  FOREIGN KEY (asset_id) REFERENCES Asset(asset_id) ON DELETE CASCADE
+) STRICT;
+
+CREATE INDEX asset_place_country_code_index ON AssetPlace(country_code, admin_region, city);
+CREATE INDEX asset_place_city_index ON AssetPlace(city);
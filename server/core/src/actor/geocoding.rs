@@ -0,0 +1,235 @@
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use camino::Utf8PathBuf as PathBuf;
+use eyre::{eyre, Context, Report, Result};
+use geocode::{Coordinates, LookupResult, ReverseGeocodeError, ReverseGeocoder};
+use tokio::sync::{mpsc, oneshot, Mutex};
+use tracing::Instrument;
+
+use crate::{
+    catalog::operation::geocode_asset::{apply_geocode_asset, GeocodeAsset},
+    interact,
+    model::{
+        repository::{self, db::DbPool},
+        AssetId, AssetPlace, CreateJob, FailedJobKind, JobKind, JobSubject,
+    },
+    processing::process_control::{job_failure, ProgressSender},
+};
+
+use super::{
+    jobs::{JobTask, JobTaskResult, JobTracker},
+    simple_queue_actor::{
+        Actor, ActorOptions, MsgFrom, MsgTaskControl, QueuedActorHandle, TaskError, TaskId,
+    },
+};
+
+pub type GeocodingTaskMsg = GeocodeAsset;
+pub type GeocodingActorHandle = QueuedActorHandle<GeocodingTaskMsg>;
+pub type MsgFromGeocoding = MsgFrom<GeocodingTaskResult>;
+
+/// After a failed download of geodata no other download is attempted for this long,
+/// otherwise every queued Asset would try again
+const DOWNLOAD_RETRY_DELAY: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug)]
+pub enum GeocodingTaskResult {
+    GeocodingComplete(GeocodeAsset),
+    GeocodingError {
+        geocode_asset: GeocodeAsset,
+        report: Report,
+    },
+}
+
+impl JobTask for GeocodingTaskMsg {
+    fn job(&self) -> CreateJob {
+        CreateJob {
+            kind: JobKind::ReverseGeocode,
+            subject: JobSubject::Asset(self.asset_id),
+        }
+    }
+}
+
+impl JobTaskResult for GeocodingTaskResult {
+    fn job_error(&self) -> Option<String> {
+        match self {
+            GeocodingTaskResult::GeocodingComplete(_) => None,
+            GeocodingTaskResult::GeocodingError { report, .. } => Some(format!("{:#}", report)),
+        }
+    }
+}
+
+/// `geodata_path` is the sqlite database the geocode crate keeps its downloaded data in
+pub fn start_geocoding_actor(
+    db_pool: DbPool,
+    geodata_path: PathBuf,
+    did_shutdown_send: oneshot::Sender<()>,
+    send_from_us: mpsc::UnboundedSender<MsgFromGeocoding>,
+) -> GeocodingActorHandle {
+    let jobs = JobTracker::new(db_pool.clone());
+    let actor = GeocodingActor {
+        db_pool,
+        geocoder: Arc::new(Mutex::new(Geocoder {
+            geodata_path,
+            reverse_geocoder: None,
+            download_failed_at: None,
+        })),
+    };
+    QueuedActorHandle::new(
+        actor,
+        jobs,
+        send_from_us,
+        did_shutdown_send,
+        ActorOptions {
+            // lookups are cheap, what takes time is downloading the data of a country
+            // which every task in that country would have to wait for anyway
+            max_tasks: 1,
+            max_queue_size: 1000,
+        },
+        tracing::info_span!("geocoding"),
+    )
+}
+
+impl QueuedActorHandle<GeocodingTaskMsg> {
+    pub fn msg_geocode_asset(&self, msg: GeocodeAsset) -> Result<()> {
+        self.msg_do_task(msg)
+    }
+}
+
+struct GeocodingActor {
+    db_pool: DbPool,
+    geocoder: Arc<Mutex<Geocoder>>,
+}
+
+/// Opened on first use, downloads the data it needs as it goes
+struct Geocoder {
+    geodata_path: PathBuf,
+    reverse_geocoder: Option<ReverseGeocoder>,
+    download_failed_at: Option<Instant>,
+}
+
+impl Geocoder {
+    async fn lookup(&mut self, coordinates: Coordinates) -> Result<Option<AssetPlace>> {
+        if self.reverse_geocoder.is_none() {
+            let reverse_geocoder = ReverseGeocoder::new(&self.geodata_path)
+                .await
+                .wrap_err("error opening geodata database")?;
+            self.reverse_geocoder = Some(reverse_geocoder);
+        }
+        let reverse_geocoder = self.reverse_geocoder.as_mut().expect("was opened above");
+        if reverse_geocoder.base_data_present().await?.is_none() {
+            check_download_allowed(self.download_failed_at)?;
+            tracing::info!("Downloading country list and borders");
+            if let Err(err) = reverse_geocoder.download_base_data().await {
+                self.download_failed_at = Some(Instant::now());
+                return Err(err.wrap_err("error downloading country list and borders"));
+            }
+        }
+        let country_id = match reverse_geocoder.lookup(coordinates).await {
+            Err(ReverseGeocodeError::CountryDataNotPresent { country_id }) => country_id,
+            result => return lookup_result(result),
+        };
+        check_download_allowed(self.download_failed_at)?;
+        tracing::info!(country_id = country_id.0, "Downloading places of country");
+        if let Err(err) = reverse_geocoder.download_country_data(country_id).await {
+            self.download_failed_at = Some(Instant::now());
+            return Err(err.wrap_err(format!(
+                "error downloading places of country {}",
+                country_id.0
+            )));
+        }
+        lookup_result(reverse_geocoder.lookup(coordinates).await)
+    }
+}
+
+fn check_download_allowed(download_failed_at: Option<Instant>) -> Result<()> {
+    match download_failed_at {
+        Some(failed_at) if failed_at.elapsed() < DOWNLOAD_RETRY_DELAY => Err(eyre!(
+            "downloading geodata failed recently, not trying again yet"
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn lookup_result(
+    result: Result<Option<LookupResult>, ReverseGeocodeError>,
+) -> Result<Option<AssetPlace>> {
+    match result {
+        Ok(result) => Ok(result.map(|result| AssetPlace {
+            country_code: result.country_code,
+            country: result.country_name,
+            admin_region: result.admin1_name,
+            city: result.name,
+        })),
+        Err(ReverseGeocodeError::Other(report)) => {
+            Err(report.wrap_err("error in reverse geocoding"))
+        }
+        Err(err) => Err(eyre!("geodata missing after download: {}", err)),
+    }
+}
+
+impl Actor<GeocodingTaskMsg, GeocodingTaskResult> for GeocodingActor {
+    async fn run_task(
+        &mut self,
+        msg: GeocodingTaskMsg,
+        result_send: mpsc::UnboundedSender<(TaskId, Result<GeocodingTaskResult, TaskError>)>,
+        task_id: TaskId,
+        ctl_recv: mpsc::UnboundedReceiver<MsgTaskControl>,
+        _progress_send: ProgressSender,
+    ) {
+        let db_pool = self.db_pool.clone();
+        let geocoder = self.geocoder.clone();
+        async fn save_failure(db_pool: &DbPool, asset_id: AssetId, report: &Report) -> Result<()> {
+            let failure = job_failure(report);
+            let conn = db_pool.get().await?;
+            interact!(conn, move |conn| {
+                repository::failed_job::record_failed_job(
+                    conn,
+                    asset_id,
+                    FailedJobKind::ReverseGeocode,
+                    &failure,
+                )
+            })
+            .await??;
+            Ok(())
+        }
+        tokio::task::spawn(
+            async move {
+                let (lat, lon) = msg.lat_lon();
+                let lookup = geocoder.lock().await.lookup(Coordinates { lat, lon }).await;
+                let result = match lookup {
+                    Ok(place) => {
+                        let apply_result = match db_pool.get().await {
+                            Ok(mut conn) => apply_geocode_asset(&mut conn, &msg, place).await,
+                            Err(err) => Err(err),
+                        };
+                        match apply_result {
+                            Ok(()) => GeocodingTaskResult::GeocodingComplete(msg),
+                            Err(report) => GeocodingTaskResult::GeocodingError {
+                                geocode_asset: msg,
+                                report,
+                            },
+                        }
+                    }
+                    Err(report) => {
+                        let asset_id = msg.asset_id;
+                        if let Err(err) = save_failure(&db_pool, asset_id, &report).await {
+                            tracing::warn!(?err, %asset_id, "error saving failed geocoding");
+                        }
+                        GeocodingTaskResult::GeocodingError {
+                            geocode_asset: msg,
+                            report,
+                        }
+                    }
+                };
+                result_send
+                    .send((task_id, Ok(result)))
+                    .expect("Receiver must be alive");
+                drop(ctl_recv); // must be alive for entire task duration
+            }
+            .in_current_span(),
+        );
+    }
+}
@@ -7,12 +7,17 @@ use crate::{
         apply_convert_image, perform_side_effects_convert_image, ConvertImage,
         ImageConversionSideEffectResult,
     },
+    config,
     core::storage::Storage,
-    model::repository::db::DbPool,
+    model::{repository::db::DbPool, CreateJob, JobKind, JobSubject},
+    processing::process_control::ProgressSender,
 };
 
-use super::simple_queue_actor::{
-    Actor, ActorOptions, MsgFrom, MsgTaskControl, QueuedActorHandle, TaskError, TaskId,
+use super::{
+    jobs::{JobTask, JobTaskResult, JobTracker},
+    simple_queue_actor::{
+        Actor, ActorOptions, MsgFrom, MsgTaskControl, QueuedActorHandle, TaskError, TaskId,
+    },
 };
 
 pub type ImageConversionTaskMsg = ConvertImage;
@@ -28,19 +33,42 @@ pub enum ImageConversionTaskResult {
     },
 }
 
+impl JobTask for ImageConversionTaskMsg {
+    fn job(&self) -> CreateJob {
+        CreateJob {
+            kind: JobKind::ConvertImage,
+            subject: JobSubject::Asset(self.asset_id),
+        }
+    }
+}
+
+impl JobTaskResult for ImageConversionTaskResult {
+    fn job_error(&self) -> Option<String> {
+        match self {
+            ImageConversionTaskResult::ConversionComplete(_) => None,
+            ImageConversionTaskResult::ConversionError { report, .. } => {
+                Some(format!("{:#}", report))
+            }
+        }
+    }
+}
+
 pub fn start_image_conversion_actor(
     db_pool: DbPool,
     storage: Storage,
+    config: config::Config,
     did_shutdown_send: oneshot::Sender<()>,
     send_from_us: mpsc::UnboundedSender<MsgFromImageConversion>,
 ) -> ImageConversionActorHandle {
+    let jobs = JobTracker::new(db_pool.clone());
     let actor = ImageConversionActor { db_pool, storage };
     QueuedActorHandle::new(
         actor,
+        jobs,
         send_from_us,
         did_shutdown_send,
         ActorOptions {
-            max_tasks: 8,
+            max_tasks: config.processing.image_conversion.max_tasks,
             max_queue_size: 1000,
         },
         tracing::info_span!("image_conversion"),
@@ -65,6 +93,7 @@ impl Actor<ImageConversionTaskMsg, ImageConversionTaskResult> for ImageConversio
         result_send: mpsc::UnboundedSender<(TaskId, Result<ImageConversionTaskResult, TaskError>)>,
         task_id: TaskId,
         ctl_recv: mpsc::UnboundedReceiver<MsgTaskControl>,
+        _progress_send: ProgressSender,
     ) {
         let db_pool = self.db_pool.clone();
         let storage = self.storage.clone();
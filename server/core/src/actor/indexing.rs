@@ -1,8 +1,14 @@
-use std::collections::VecDeque;
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::Arc,
+};
 
-use camino::Utf8PathBuf as PathBuf;
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use eyre::{eyre, Context, Result};
-use tokio::sync::mpsc;
+use tokio::{
+    sync::{mpsc, Mutex, OwnedMutexGuard},
+    task::JoinSet,
+};
 use tracing::instrument;
 use walkdir::WalkDir;
 
@@ -10,9 +16,19 @@ use crate::{
     config, interact,
     model::{
         repository::{self, db::DbPool},
-        AssetId, AssetRootDir, AssetRootDirId,
+        AssetId, AssetRootDir, AssetRootDirId, CreateJob, JobId, JobKind, JobProgress, JobState,
+        JobSubject,
+    },
+    processing::{
+        indexing::{index_file, IndexFileResult},
+        process_control::ProgressSender,
+        reconcile::{reconcile_asset_root, reconcile_paths, ReconcileResult},
     },
-    processing::indexing::index_file,
+};
+
+use super::{
+    jobs::{discard_progress, JobTask, JobTracker},
+    simple_queue_actor::{MsgTaskControl, TaskError},
 };
 
 #[derive(Debug)]
@@ -23,6 +39,13 @@ pub enum MsgFromIndexing {
     },
     DroppedMessage,
     NewAsset(AssetId),
+    /// The file of an existing Asset changed and the Asset was updated
+    AssetModified(AssetId),
+    /// Checked which Assets in the root no longer have a file on disk
+    Reconciled {
+        root_dir_id: AssetRootDirId,
+        result: ReconcileResult,
+    },
     IndexingError {
         root_dir_id: AssetRootDirId,
         path: Option<PathBuf>,
@@ -39,11 +62,130 @@ enum MsgToIndexing {
     Pause,
     Resume,
     DoTask(DoTaskMsg),
+    /// Run a task for an existing Job again
+    RetryJob(JobId, DoTaskMsg),
+    CancelJob(JobId),
 }
 
 #[derive(Debug, Clone)]
 enum DoTaskMsg {
-    IndexAssetRootDir { root_dir_id: AssetRootDirId },
+    IndexAssetRootDir {
+        root_dir_id: AssetRootDirId,
+    },
+    /// Index individual files (or directories, which are walked) in an AssetRootDir
+    IndexPaths {
+        root_dir_id: AssetRootDirId,
+        paths: Vec<PathBuf>,
+    },
+    /// Check whether files at or below paths in an AssetRootDir still exist
+    ReconcilePaths {
+        root_dir_id: AssetRootDirId,
+        paths: Vec<PathBuf>,
+    },
+}
+
+impl JobTask for DoTaskMsg {
+    fn job(&self) -> CreateJob {
+        let (kind, root_dir_id) = match self {
+            DoTaskMsg::IndexAssetRootDir { root_dir_id } => (JobKind::IndexAssetRoot, root_dir_id),
+            DoTaskMsg::IndexPaths { root_dir_id, .. } => (JobKind::IndexPaths, root_dir_id),
+            DoTaskMsg::ReconcilePaths { root_dir_id, .. } => (JobKind::ReconcilePaths, root_dir_id),
+        };
+        CreateJob {
+            kind,
+            subject: JobSubject::AssetRootDir(*root_dir_id),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct QueuedTask {
+    msg: DoTaskMsg,
+    job_id: Option<JobId>,
+}
+
+/// Handed to a running task so that it can report progress and be paused or cancelled
+/// in between files
+struct RunningTask {
+    ctl_recv: mpsc::UnboundedReceiver<MsgTaskControl>,
+    is_paused: bool,
+    progress_send: ProgressSender,
+    files_scanned: i64,
+    in_flight: InFlightPaths,
+}
+
+impl RunningTask {
+    /// Returns Err(TaskError::Cancelled) if the task should stop
+    async fn file_scanned(&mut self) -> Result<(), TaskError> {
+        self.files_scanned += 1;
+        let _ = self.progress_send.send(JobProgress {
+            done: self.files_scanned,
+            total: None,
+        });
+        loop {
+            let msg = if self.is_paused {
+                self.ctl_recv.recv().await
+            } else {
+                match self.ctl_recv.try_recv() {
+                    Ok(msg) => Some(msg),
+                    Err(_) => return Ok(()),
+                }
+            };
+            match msg {
+                Some(MsgTaskControl::Pause) => self.is_paused = true,
+                Some(MsgTaskControl::Resume) => self.is_paused = false,
+                Some(MsgTaskControl::Cancel) => return Err(TaskError::Cancelled),
+                // actor is gone, nobody can resume us anymore
+                None => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Files that are being indexed right now.
+/// Tasks run concurrently and the watcher queues paths while a rescan of the same
+/// root may be running, so two tasks can get to the same file at the same time.
+/// Indexing of a path is serialized so that the second one sees the result of the first.
+#[derive(Clone, Default)]
+pub(super) struct InFlightPaths {
+    pub(super) locks: Arc<std::sync::Mutex<HashMap<PathBuf, Arc<Mutex<()>>>>>,
+}
+
+impl InFlightPaths {
+    /// Waits until no other task is indexing `path`
+    pub(super) async fn lock(&self, path: &Path) -> InFlightGuard {
+        let lock = self
+            .locks
+            .lock()
+            .unwrap()
+            .entry(path.to_owned())
+            .or_default()
+            .clone();
+        InFlightGuard {
+            paths: self.clone(),
+            path: path.to_owned(),
+            _guard: lock.lock_owned().await,
+        }
+    }
+}
+
+pub(super) struct InFlightGuard {
+    paths: InFlightPaths,
+    path: PathBuf,
+    _guard: OwnedMutexGuard<()>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        let mut locks = self.paths.locks.lock().unwrap();
+        // only the map and our guard reference the lock, nobody is waiting for it
+        if locks
+            .get(&self.path)
+            .is_some_and(|lock| Arc::strong_count(lock) == 2)
+        {
+            locks.remove(&self.path);
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -59,6 +201,8 @@ impl IndexingActorHandle {
     ) -> Self {
         let (send, recv) = mpsc::unbounded_channel();
         let actor = IndexingActor {
+            jobs: JobTracker::new(db_pool.clone()),
+            in_flight: InFlightPaths::default(),
             db_pool,
             config,
             send_from_us,
@@ -75,6 +219,47 @@ impl IndexingActorHandle {
         Ok(())
     }
 
+    pub fn msg_index_paths(&self, root_dir_id: AssetRootDirId, paths: Vec<PathBuf>) -> Result<()> {
+        self.send
+            .send(MsgToIndexing::DoTask(DoTaskMsg::IndexPaths {
+                root_dir_id,
+                paths,
+            }))?;
+        Ok(())
+    }
+
+    pub fn msg_reconcile_paths(
+        &self,
+        root_dir_id: AssetRootDirId,
+        paths: Vec<PathBuf>,
+    ) -> Result<()> {
+        self.send
+            .send(MsgToIndexing::DoTask(DoTaskMsg::ReconcilePaths {
+                root_dir_id,
+                paths,
+            }))?;
+        Ok(())
+    }
+
+    /// `job_id` must be a job of kind IndexAssetRoot
+    pub fn msg_retry_index_asset_root(
+        &self,
+        job_id: JobId,
+        root_dir_id: AssetRootDirId,
+    ) -> Result<()> {
+        self.send.send(MsgToIndexing::RetryJob(
+            job_id,
+            DoTaskMsg::IndexAssetRootDir { root_dir_id },
+        ))?;
+        Ok(())
+    }
+
+    /// Does nothing if the job is not queued or running
+    pub fn msg_cancel_job(&self, job_id: JobId) -> Result<()> {
+        self.send.send(MsgToIndexing::CancelJob(job_id))?;
+        Ok(())
+    }
+
     pub fn msg_pause_all(&self) -> Result<()> {
         self.send.send(MsgToIndexing::Pause)?;
         Ok(())
@@ -86,60 +271,196 @@ impl IndexingActorHandle {
     }
 }
 
+#[derive(Clone)]
 struct IndexingActor {
     pub db_pool: DbPool,
     pub config: config::Config,
     pub send_from_us: mpsc::UnboundedSender<MsgFromIndexing>,
+    pub jobs: JobTracker,
+    in_flight: InFlightPaths,
 }
 
 const MAX_TASKS: usize = 4;
-const MAX_QUEUE_SIZE: usize = 10;
+const MAX_QUEUE_SIZE: usize = 100;
 
 async fn run_indexing_actor(
     mut recv: mpsc::UnboundedReceiver<MsgToIndexing>,
     actor: IndexingActor,
 ) {
     let mut is_running = true;
-    let mut running_tasks: usize = 0;
-    let mut queue: VecDeque<DoTaskMsg> = Default::default();
+    let mut running_tasks: JoinSet<()> = JoinSet::new();
+    // control channels of running tasks, closed once the task is done
+    let mut task_ctl_sends: Vec<(Option<JobId>, mpsc::UnboundedSender<MsgTaskControl>)> =
+        Vec::default();
+    let mut queue: VecDeque<QueuedTask> = Default::default();
     loop {
         tokio::select! {
             Some(msg) = recv.recv() => {
+                task_ctl_sends.retain(|(_job_id, send)| !send.is_closed());
                 match msg {
                     MsgToIndexing::Pause => {
                         is_running = false;
-                        // TODO: pause currently running indexing jobs
+                        for (_job_id, send) in &task_ctl_sends {
+                            let _ = send.send(MsgTaskControl::Pause);
+                        }
                     }
                     MsgToIndexing::Resume => {
                         is_running = true;
-                        // TODO: unpause currently running indexing jobs
+                        for (_job_id, send) in &task_ctl_sends {
+                            let _ = send.send(MsgTaskControl::Resume);
+                        }
+                        while running_tasks.len() < MAX_TASKS {
+                            match queue.pop_front() {
+                                Some(task) => {
+                                    task_ctl_sends.push(actor.start_task(&mut running_tasks, task).await);
+                                }
+                                None => break,
+                            }
+                        }
                     }
                     MsgToIndexing::DoTask(task) => {
-                        if is_running && running_tasks < MAX_TASKS {
-                            running_tasks += 1;
-                            let _ = actor.send_from_us.send(MsgFromIndexing::ActivityChange {
-                                running_tasks,
-                                queued_tasks: queue.len()
-                            });
-                            actor.process_message(task).await;
-                        } else if queue.len() < MAX_QUEUE_SIZE {
-                            queue.push_back(task);
-                            let _ = actor.send_from_us.send(MsgFromIndexing::ActivityChange {
-                                running_tasks,
-                                queued_tasks: queue.len()
-                            });
-                        } else {
-                            let _ = actor.send_from_us.send(MsgFromIndexing::DroppedMessage);
+                        let accepted = actor.on_task_received(
+                            &mut running_tasks, &mut task_ctl_sends, &mut queue, is_running, task, None
+                        ).await;
+                        if !accepted {
+                            continue;
+                        }
+                    }
+                    MsgToIndexing::RetryJob(job_id, task) => {
+                        let accepted = actor.on_task_received(
+                            &mut running_tasks, &mut task_ctl_sends, &mut queue, is_running, task, Some(job_id)
+                        ).await;
+                        if !accepted {
+                            continue;
+                        }
+                    }
+                    MsgToIndexing::CancelJob(job_id) => {
+                        let queue_pos = queue.iter().position(|queued| queued.job_id == Some(job_id));
+                        if let Some(queue_pos) = queue_pos {
+                            queue.remove(queue_pos);
+                            actor.jobs.on_done(job_id, JobState::Cancelled, None).await;
+                        } else if let Some((_job_id, send)) = task_ctl_sends
+                            .iter()
+                            .find(|(running_job_id, _send)| *running_job_id == Some(job_id))
+                        {
+                            // the job is marked as cancelled once the task returns
+                            let _ = send.send(MsgTaskControl::Cancel);
                         }
                     }
                 }
+                let _ = actor.send_from_us.send(MsgFromIndexing::ActivityChange {
+                    running_tasks: running_tasks.len(),
+                    queued_tasks: queue.len()
+                });
+            }
+            Some(join_result) = running_tasks.join_next(), if !running_tasks.is_empty() => {
+                if let Err(err) = join_result {
+                    tracing::error!(%err, "indexing task panicked");
+                }
+                if is_running {
+                    if let Some(task) = queue.pop_front() {
+                        task_ctl_sends.push(actor.start_task(&mut running_tasks, task).await);
+                    }
+                }
+                let _ = actor.send_from_us.send(MsgFromIndexing::ActivityChange {
+                    running_tasks: running_tasks.len(),
+                    queued_tasks: queue.len()
+                });
+            }
+            else => {
+                break;
             }
         }
     }
 }
 
 impl IndexingActor {
-    async fn process_message(&self, msg: DoTaskMsg) {
+    /// Start or queue the task, returns false if it was dropped because the queue is full.
+    /// `retry_job_id` is set if this runs an existing Job again.
+    async fn on_task_received(
+        &self,
+        running_tasks: &mut JoinSet<()>,
+        task_ctl_sends: &mut Vec<(Option<JobId>, mpsc::UnboundedSender<MsgTaskControl>)>,
+        queue: &mut VecDeque<QueuedTask>,
+        is_running: bool,
+        msg: DoTaskMsg,
+        retry_job_id: Option<JobId>,
+    ) -> bool {
+        let can_start = is_running && running_tasks.len() < MAX_TASKS;
+        if !can_start && queue.len() >= MAX_QUEUE_SIZE {
+            if let Some(job_id) = retry_job_id {
+                self.jobs
+                    .on_done(
+                        job_id,
+                        JobState::Cancelled,
+                        Some("job queue is full".to_string()),
+                    )
+                    .await;
+            }
+            let _ = self.send_from_us.send(MsgFromIndexing::DroppedMessage);
+            return false;
+        }
+        let job_id = match retry_job_id {
+            Some(job_id) => {
+                self.jobs.on_requeued(job_id).await;
+                Some(job_id)
+            }
+            None => self.jobs.on_queued(msg.job()).await,
+        };
+        let task = QueuedTask { msg, job_id };
+        if can_start {
+            task_ctl_sends.push(self.start_task(running_tasks, task).await);
+        } else {
+            queue.push_back(task);
+        }
+        true
+    }
+
+    /// Returns the channel to control the task with
+    async fn start_task(
+        &self,
+        running_tasks: &mut JoinSet<()>,
+        task: QueuedTask,
+    ) -> (Option<JobId>, mpsc::UnboundedSender<MsgTaskControl>) {
+        let (ctl_send, ctl_recv) = mpsc::unbounded_channel();
+        let job_id = task.job_id;
+        let progress_send = match job_id {
+            Some(job_id) => {
+                self.jobs.on_started(job_id).await;
+                self.jobs.progress_sender(job_id)
+            }
+            None => discard_progress(),
+        };
+        let running_task = RunningTask {
+            ctl_recv,
+            is_paused: false,
+            progress_send,
+            files_scanned: 0,
+            in_flight: self.in_flight.clone(),
+        };
+        let actor = self.clone();
+        running_tasks.spawn(async move {
+            let jobs = actor.jobs.clone();
+            let result = actor.process_message(task.msg, running_task).await;
+            if let Some(job_id) = job_id {
+                let (state, error) = match result {
+                    Ok(()) => (JobState::Finished, None),
+                    Err(TaskError::Cancelled) => (JobState::Cancelled, None),
+                    Err(TaskError::Other(report)) => {
+                        (JobState::Failed, Some(format!("{:#}", report)))
+                    }
+                };
+                jobs.on_done(job_id, state, error).await;
+            }
+        });
+        (job_id, ctl_send)
+    }
+
+    async fn process_message(
+        self,
+        msg: DoTaskMsg,
+        mut running_task: RunningTask,
+    ) -> Result<(), TaskError> {
         match msg {
             DoTaskMsg::IndexAssetRootDir { root_dir_id } => {
                 let send_copy = self.send_from_us.clone();
@@ -149,17 +470,73 @@ impl IndexingActor {
                     send_copy,
                     self.config.bin_paths.clone(),
                     root_dir_id,
+                    &mut running_task,
                 )
                 .await;
 
-                if let Err(report) = start_result {
+                if let Err(TaskError::Other(report)) = start_result {
+                    let report = report.wrap_err("Error starting indexing job");
+                    let error = format!("{:#}", report);
                     let _ = self
                         .send_from_us
                         .send(MsgFromIndexing::FailedToStartIndexing {
                             root_dir_id,
-                            report: report.wrap_err("Error starting indexing job"),
+                            report,
                         });
+                    return Err(eyre!(error).into());
                 }
+                start_result
+            }
+            DoTaskMsg::IndexPaths { root_dir_id, paths } => {
+                let start_result = index_paths(
+                    self.db_pool.clone(),
+                    self.send_from_us.clone(),
+                    self.config.bin_paths.clone(),
+                    root_dir_id,
+                    paths,
+                    &mut running_task,
+                )
+                .await;
+
+                if let Err(TaskError::Other(report)) = start_result {
+                    let report = report.wrap_err("Error starting indexing job");
+                    let error = format!("{:#}", report);
+                    let _ = self
+                        .send_from_us
+                        .send(MsgFromIndexing::FailedToStartIndexing {
+                            root_dir_id,
+                            report,
+                        });
+                    return Err(eyre!(error).into());
+                }
+                start_result
+            }
+            DoTaskMsg::ReconcilePaths { root_dir_id, paths } => {
+                let reconcile_result =
+                    handle_reconcile_paths_message(self.db_pool.clone(), root_dir_id, paths).await;
+                let (msg, result) = match reconcile_result {
+                    Ok(result) => (
+                        MsgFromIndexing::Reconciled {
+                            root_dir_id,
+                            result,
+                        },
+                        Ok(()),
+                    ),
+                    Err(report) => {
+                        let report = report.wrap_err("Error reconciling removed paths");
+                        let error = format!("{:#}", report);
+                        (
+                            MsgFromIndexing::IndexingError {
+                                root_dir_id,
+                                path: None,
+                                report,
+                            },
+                            Err(eyre!(error).into()),
+                        )
+                    }
+                };
+                let _ = self.send_from_us.send(msg);
+                result
             }
         }
     }
@@ -170,53 +547,151 @@ async fn handle_indexing_message(
     send_result: mpsc::UnboundedSender<MsgFromIndexing>,
     bin_paths: Option<config::BinPaths>,
     root_dir_id: AssetRootDirId,
-) -> Result<()> {
+    running_task: &mut RunningTask,
+) -> Result<(), TaskError> {
+    let conn = db_pool.get().await?;
+    let asset_root = interact!(conn, move |conn| {
+        repository::asset_root_dir::get_asset_root(conn, root_dir_id)
+    })
+    .await?
+    .wrap_err("Error getting AssetRootDir from db")?;
+    index_asset_root(db_pool, send_result, bin_paths, asset_root, running_task).await
+}
+
+async fn handle_reconcile_paths_message(
+    db_pool: DbPool,
+    root_dir_id: AssetRootDirId,
+    paths: Vec<PathBuf>,
+) -> Result<ReconcileResult> {
+    let conn = db_pool.get().await?;
+    let asset_root = interact!(conn, move |conn| {
+        repository::asset_root_dir::get_asset_root(conn, root_dir_id)
+    })
+    .await?
+    .wrap_err("Error getting AssetRootDir from db")?;
+    reconcile_paths(&asset_root, &paths, &db_pool).await
+}
+
+async fn index_paths(
+    db_pool: DbPool,
+    send_result: mpsc::UnboundedSender<MsgFromIndexing>,
+    bin_paths: Option<config::BinPaths>,
+    root_dir_id: AssetRootDirId,
+    paths: Vec<PathBuf>,
+    running_task: &mut RunningTask,
+) -> Result<(), TaskError> {
     let conn = db_pool.get().await?;
     let asset_root = interact!(conn, move |conn| {
         repository::asset_root_dir::get_asset_root(conn, root_dir_id)
     })
     .await?
     .wrap_err("Error getting AssetRootDir from db")?;
-    tokio::spawn(async move {
-        index_asset_root(db_pool, send_result, bin_paths, asset_root).await;
-    });
+    for path in paths {
+        if path.is_dir() {
+            // a directory was created or moved into the root, index everything in it
+            walk_and_index(
+                &path,
+                &db_pool,
+                &send_result,
+                bin_paths.as_ref(),
+                &asset_root,
+                running_task,
+            )
+            .await?;
+        } else if path.is_file() {
+            index_and_report(
+                &path,
+                &db_pool,
+                &send_result,
+                bin_paths.as_ref(),
+                &asset_root,
+                &running_task.in_flight,
+            )
+            .await;
+            running_task.file_scanned().await?;
+        }
+    }
     Ok(())
 }
 
-#[instrument(skip(pool, send_result, bin_paths))]
+/// Errors with individual files are sent to the scheduler and don't fail the whole task,
+/// only failing to check for removed files does
+#[instrument(skip(pool, send_result, bin_paths, running_task))]
 async fn index_asset_root(
     pool: DbPool,
     send_result: mpsc::UnboundedSender<MsgFromIndexing>,
     bin_paths: Option<config::BinPaths>,
     asset_root: AssetRootDir,
-) {
+    running_task: &mut RunningTask,
+) -> Result<(), TaskError> {
     tracing::info!(path=%asset_root.path, "Start indexing");
+    let new_asset_count = walk_and_index(
+        &asset_root.path,
+        &pool,
+        &send_result,
+        bin_paths.as_ref(),
+        &asset_root,
+        running_task,
+    )
+    .await?;
+    tracing::info!(path=%asset_root.path, new_assets=new_asset_count, "Finished indexing");
+    // after indexing, so that moved files have already been matched to their Asset
+    let (msg, result) = match reconcile_asset_root(&asset_root, &pool).await {
+        Ok(result) => (
+            MsgFromIndexing::Reconciled {
+                root_dir_id: asset_root.id,
+                result,
+            },
+            Ok(()),
+        ),
+        Err(report) => {
+            let report = report.wrap_err("Error checking for removed files");
+            let error = format!("{:#}", report);
+            (
+                MsgFromIndexing::IndexingError {
+                    root_dir_id: asset_root.id,
+                    path: None,
+                    report,
+                },
+                Err(eyre!(error).into()),
+            )
+        }
+    };
+    let _ = send_result.send(msg);
+    result
+}
+
+/// Returns the number of new assets found
+async fn walk_and_index(
+    dir: &Path,
+    pool: &DbPool,
+    send_result: &mpsc::UnboundedSender<MsgFromIndexing>,
+    bin_paths: Option<&config::BinPaths>,
+    asset_root: &AssetRootDir,
+    running_task: &mut RunningTask,
+) -> Result<usize, TaskError> {
     // TODO WalkDir is synchronous
     // FIXME if a datadir is subdir of assetroot it should obviously not be indexed
     let mut new_asset_count = 0;
-    for entry in WalkDir::new(asset_root.path.as_path()).follow_links(true) {
+    for entry in WalkDir::new(dir.as_std_path()).follow_links(true) {
         match entry {
             Ok(e) => {
                 if e.file_type().is_file() {
                     let utf8_path = camino::Utf8Path::from_path(e.path());
                     if let Some(path) = utf8_path {
-                        let indexing_res =
-                            index_file(path, &asset_root, &pool, bin_paths.as_ref()).await;
-                        let msg = match indexing_res {
-                            Ok(None) => {
-                                continue;
-                            }
-                            Ok(Some(asset_id)) => {
-                                new_asset_count += 1;
-                                MsgFromIndexing::NewAsset(asset_id)
-                            }
-                            Err(report) => MsgFromIndexing::IndexingError {
-                                root_dir_id: asset_root.id,
-                                path: Some(path.to_owned()),
-                                report,
-                            },
-                        };
-                        let _ = send_result.send(msg);
+                        if index_and_report(
+                            path,
+                            pool,
+                            send_result,
+                            bin_paths,
+                            asset_root,
+                            &running_task.in_flight,
+                        )
+                        .await
+                        {
+                            new_asset_count += 1;
+                        }
+                        running_task.file_scanned().await?;
                     }
                 }
             }
@@ -233,5 +708,40 @@ async fn index_asset_root(
             }
         }
     }
-    tracing::info!(path=%asset_root.path, new_assets=new_asset_count, "Finished indexing");
+    Ok(new_asset_count)
+}
+
+/// Index a single file and send the result to the scheduler.
+/// Returns true if a new asset was created.
+async fn index_and_report(
+    path: &Path,
+    pool: &DbPool,
+    send_result: &mpsc::UnboundedSender<MsgFromIndexing>,
+    bin_paths: Option<&config::BinPaths>,
+    asset_root: &AssetRootDir,
+    in_flight: &InFlightPaths,
+) -> bool {
+    let indexing_res = {
+        let _in_flight = in_flight.lock(path).await;
+        index_file(path, asset_root, pool, bin_paths).await
+    };
+    let (msg, is_new) = match indexing_res {
+        Ok(IndexFileResult::NothingNew) => {
+            return false;
+        }
+        Ok(IndexFileResult::NewAsset(asset_id)) => (MsgFromIndexing::NewAsset(asset_id), true),
+        Ok(IndexFileResult::AssetModified(asset_id)) => {
+            (MsgFromIndexing::AssetModified(asset_id), false)
+        }
+        Err(report) => (
+            MsgFromIndexing::IndexingError {
+                root_dir_id: asset_root.id,
+                path: Some(path.to_owned()),
+                report,
+            },
+            false,
+        ),
+    };
+    let _ = send_result.send(msg);
+    is_new
 }
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use eyre::{Context, Result};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::{
+    interact,
+    model::{
+        repository::{
+            self,
+            db::{DbConn, DbPool},
+        },
+        CreateJob, JobId, JobProgress, JobState,
+    },
+    processing::process_control::ProgressSender,
+};
+
+/// Tasks that are recorded in the Job table while queued, running and after they're done
+pub trait JobTask {
+    fn job(&self) -> CreateJob;
+}
+
+/// Task results that can describe a failure without the task itself returning an error
+pub trait JobTaskResult {
+    /// None if the task succeeded
+    fn job_error(&self) -> Option<String>;
+}
+
+/// Don't write progress to the db more often than this
+const PROGRESS_WRITE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Keeps the Job table in sync with what the actors are doing.
+/// Errors are logged and otherwise ignored, failing to record a job should not stop the job.
+#[derive(Clone)]
+pub struct JobTracker {
+    db_pool: DbPool,
+}
+
+impl JobTracker {
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Returns None if the job could not be recorded
+    pub async fn on_queued(&self, create_job: CreateJob) -> Option<JobId> {
+        let result = self
+            .interact(move |conn| repository::job::create_job(conn, &create_job))
+            .await;
+        match result {
+            Ok(job_id) => Some(job_id),
+            Err(err) => {
+                tracing::warn!(?err, "error recording queued job");
+                None
+            }
+        }
+    }
+
+    /// An existing job was queued again to be retried
+    pub async fn on_requeued(&self, job_id: JobId) {
+        let result = self
+            .interact(move |conn| repository::job::set_job_queued(conn, job_id))
+            .await;
+        if let Err(err) = result {
+            tracing::warn!(%job_id, ?err, "error recording requeued job");
+        }
+    }
+
+    pub async fn on_started(&self, job_id: JobId) {
+        let result = self
+            .interact(move |conn| repository::job::set_job_running(conn, job_id))
+            .await;
+        if let Err(err) = result {
+            tracing::warn!(%job_id, ?err, "error recording started job");
+        }
+    }
+
+    pub async fn on_done(&self, job_id: JobId, state: JobState, error: Option<String>) {
+        let result = self
+            .interact(move |conn| repository::job::set_job_done(conn, job_id, state, error))
+            .await;
+        if let Err(err) = result {
+            tracing::warn!(%job_id, ?err, "error recording finished job");
+        }
+    }
+
+    /// Progress sent on the returned channel is written to the job until the sender is dropped.
+    /// Only the most recent progress is written, at most once every PROGRESS_WRITE_INTERVAL.
+    pub fn progress_sender(&self, job_id: JobId) -> ProgressSender {
+        let (send, mut recv) = mpsc::unbounded_channel::<JobProgress>();
+        let tracker = self.clone();
+        tokio::spawn(
+            async move {
+                while let Some(mut progress) = recv.recv().await {
+                    while let Ok(newer) = recv.try_recv() {
+                        progress = newer;
+                    }
+                    let result = tracker
+                        .interact(move |conn| {
+                            repository::job::set_job_progress(conn, job_id, progress)
+                        })
+                        .await;
+                    if let Err(err) = result {
+                        tracing::warn!(%job_id, ?err, "error recording job progress");
+                    }
+                    tokio::time::sleep(PROGRESS_WRITE_INTERVAL).await;
+                }
+            }
+            .in_current_span(),
+        );
+        send
+    }
+
+    async fn interact<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut DbConn) -> Result<T> + Send + 'static,
+    ) -> Result<T> {
+        let conn = self.db_pool.get().await?;
+        interact!(conn, f).await?.wrap_err("error updating Job")
+    }
+}
+
+/// A progress sender for tasks without a Job, progress sent on it goes nowhere
+pub fn discard_progress() -> ProgressSender {
+    mpsc::unbounded_channel().0
+}
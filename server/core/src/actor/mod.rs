@@ -2,7 +2,13 @@ mod misc;
 mod simple_queue_actor;
 
 pub use simple_queue_actor::TaskError;
+pub mod geocoding;
 pub mod image_conversion;
 pub mod indexing;
+pub mod jobs;
 pub mod thumbnail;
 pub mod video_packaging;
+pub mod watcher;
+
+#[cfg(test)]
+mod test;
@@ -8,6 +8,13 @@ use eyre::Result;
 use tokio::sync::{mpsc, oneshot};
 use tracing::Instrument;
 
+use crate::{
+    model::{JobId, JobState},
+    processing::process_control::ProgressSender,
+};
+
+use super::jobs::{discard_progress, JobTask, JobTaskResult, JobTracker};
+
 #[derive(Debug)]
 pub enum MsgFrom<T: Debug> {
     ActivityChange {
@@ -25,6 +32,9 @@ pub enum MsgTo<T: Debug> {
     ResumeAll,
     Shutdown,
     DoTask(T),
+    /// Run a task for an existing Job again
+    RetryJob(JobId, T),
+    CancelJob(JobId),
 }
 
 #[derive(Debug, Clone)]
@@ -47,9 +57,13 @@ pub struct QueuedActorHandle<T: Debug + Send + Sync> {
     send: mpsc::UnboundedSender<MsgTo<T>>,
 }
 
-impl<Task: Debug + Send + Sync + 'static> QueuedActorHandle<Task> {
-    pub fn new<TaskResult: Debug + Send + Sync + 'static, A: Actor<Task, TaskResult> + 'static>(
+impl<Task: Debug + Send + Sync + JobTask + 'static> QueuedActorHandle<Task> {
+    pub fn new<
+        TaskResult: Debug + Send + Sync + JobTaskResult + 'static,
+        A: Actor<Task, TaskResult> + 'static,
+    >(
         actor: A,
+        jobs: JobTracker,
         send_from_us: mpsc::UnboundedSender<MsgFrom<TaskResult>>,
         did_shutdown_send: oneshot::Sender<()>,
         opts: ActorOptions,
@@ -58,7 +72,7 @@ impl<Task: Debug + Send + Sync + 'static> QueuedActorHandle<Task> {
         let (send, recv) = mpsc::unbounded_channel::<MsgTo<Task>>();
         tokio::task::spawn(
             async move {
-                run_actor(recv, send_from_us, did_shutdown_send, actor, opts).await;
+                run_actor(recv, send_from_us, did_shutdown_send, actor, jobs, opts).await;
             }
             .instrument(span),
         );
@@ -84,6 +98,17 @@ impl<Task: Debug + Send + Sync + 'static> QueuedActorHandle<Task> {
         self.send.send(MsgTo::DoTask(msg))?;
         Ok(())
     }
+
+    pub fn msg_retry_job(&self, job_id: JobId, msg: Task) -> Result<()> {
+        self.send.send(MsgTo::RetryJob(job_id, msg))?;
+        Ok(())
+    }
+
+    /// Does nothing if the job is not queued or running in this actor
+    pub fn msg_cancel_job(&self, job_id: JobId) -> Result<()> {
+        self.send.send(MsgTo::CancelJob(job_id))?;
+        Ok(())
+    }
 }
 
 pub trait Actor<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync>: Send + Sync {
@@ -93,9 +118,16 @@ pub trait Actor<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync>: Sen
         result_send: mpsc::UnboundedSender<(TaskId, Result<TaskResult, TaskError>)>,
         task_id: TaskId,
         ctl_recv: mpsc::UnboundedReceiver<MsgTaskControl>,
+        progress_send: ProgressSender,
     ) -> impl Future<Output = ()> + Send;
 }
 
+#[derive(Debug)]
+struct QueuedTask<Task: Debug> {
+    task: Task,
+    job_id: Option<JobId>,
+}
+
 struct Runner<
     Task: Debug + Send + Sync,
     TaskResult: Debug + Send + Sync,
@@ -104,12 +136,14 @@ struct Runner<
     opts: ActorOptions,
     is_running: bool,
     active_tasks: usize,
-    queue: VecDeque<Task>,
+    queue: VecDeque<QueuedTask<Task>>,
     send_from_us: mpsc::UnboundedSender<MsgFrom<TaskResult>>,
     actor_result_send: mpsc::UnboundedSender<(TaskId, Result<TaskResult, TaskError>)>,
     actor: A,
     next_task_id: TaskId,
     task_ctl_sends: HashMap<TaskId, mpsc::UnboundedSender<MsgTaskControl>>,
+    jobs: JobTracker,
+    task_jobs: HashMap<TaskId, JobId>,
 
     did_shutdown_send: Option<oneshot::Sender<()>>,
     waiting_for_shutdown: bool,
@@ -119,16 +153,17 @@ struct Runner<
 pub struct TaskId(usize);
 
 const SEND_ERROR_MESSAGE: &str = "Receiver held by scheduler, which must be alive";
-impl<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync, A: Actor<Task, TaskResult>>
-    Runner<Task, TaskResult, A>
+impl<
+        Task: Debug + Send + Sync + JobTask,
+        TaskResult: Debug + Send + Sync + JobTaskResult,
+        A: Actor<Task, TaskResult>,
+    > Runner<Task, TaskResult, A>
 {
     #[tracing::instrument(skip(self))]
     async fn pause_all(&mut self) {
         if self.is_running {
             tracing::debug!("pausing");
-            self.task_ctl_sends.values().for_each(|send| {
-                send.send(MsgTaskControl::Pause).expect("TODO");
-            });
+            self.send_to_all_tasks(MsgTaskControl::Pause);
             self.is_running = false;
             self.signal_activity_change();
         }
@@ -138,9 +173,7 @@ impl<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync, A: Actor<Task,
     async fn resume_all(&mut self) {
         if !self.is_running {
             self.is_running = true;
-            self.task_ctl_sends.values().for_each(|send| {
-                send.send(MsgTaskControl::Resume).expect("TODO");
-            });
+            self.send_to_all_tasks(MsgTaskControl::Resume);
             self.dequeue_work_if_available().await;
             self.signal_activity_change();
         }
@@ -151,13 +184,18 @@ impl<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync, A: Actor<Task,
             self.is_running = false;
             tracing::info!("starting shutdown");
             self.waiting_for_shutdown = true;
-            self.task_ctl_sends.values().for_each(|send| {
-                send.send(MsgTaskControl::Cancel).expect("TODO");
-            });
+            self.send_to_all_tasks(MsgTaskControl::Cancel);
             self.signal_activity_change();
         }
     }
 
+    /// A task that has returned but whose result we haven't handled yet
+    /// has dropped its receiver, its sender is removed instead.
+    fn send_to_all_tasks(&mut self, msg: MsgTaskControl) {
+        self.task_ctl_sends
+            .retain(|_task_id, send| send.send(msg.clone()).is_ok());
+    }
+
     #[tracing::instrument(skip(self))]
     async fn dequeue_work_if_available(&mut self) {
         while self.active_tasks < self.opts.max_tasks {
@@ -171,7 +209,7 @@ impl<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync, A: Actor<Task,
     }
 
     #[tracing::instrument(skip(self))]
-    async fn start_task(&mut self, msg: Task) {
+    async fn start_task(&mut self, msg: QueuedTask<Task>) {
         assert!(self.is_running);
         assert!(
             self.active_tasks < self.opts.max_tasks,
@@ -184,8 +222,22 @@ impl<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync, A: Actor<Task,
             "Next TaskId already in map"
         );
         self.next_task_id.0 += 1;
+        let progress_send = match msg.job_id {
+            Some(job_id) => {
+                self.task_jobs.insert(id, job_id);
+                self.jobs.on_started(job_id).await;
+                self.jobs.progress_sender(job_id)
+            }
+            None => discard_progress(),
+        };
         self.actor
-            .run_task(msg, self.actor_result_send.clone(), id, ctl_recv)
+            .run_task(
+                msg.task,
+                self.actor_result_send.clone(),
+                id,
+                ctl_recv,
+                progress_send,
+            )
             .await;
         self.active_tasks += 1;
         self.signal_activity_change();
@@ -213,10 +265,19 @@ impl<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync, A: Actor<Task,
     }
 
     async fn on_task_finished(&mut self, task_id: TaskId, result: Result<TaskResult, TaskError>) {
-        assert!(
-            self.task_ctl_sends.remove(&task_id).is_some(),
-            "TaskId of finished task not in map"
-        );
+        // may already be gone if sending a control message found the task finished
+        self.task_ctl_sends.remove(&task_id);
+        if let Some(job_id) = self.task_jobs.remove(&task_id) {
+            let (state, error) = match &result {
+                Err(TaskError::Cancelled) => (JobState::Cancelled, None),
+                Err(TaskError::Other(report)) => (JobState::Failed, Some(format!("{:#}", report))),
+                Ok(task_result) => match task_result.job_error() {
+                    Some(error) => (JobState::Failed, Some(error)),
+                    None => (JobState::Finished, None),
+                },
+            };
+            self.jobs.on_done(job_id, state, error).await;
+        }
         self.send_from_us
             .send(MsgFrom::TaskResult(result))
             .expect(SEND_ERROR_MESSAGE);
@@ -227,16 +288,62 @@ impl<Task: Debug + Send + Sync, TaskResult: Debug + Send + Sync, A: Actor<Task,
         }
     }
 
-    async fn on_task_received(&mut self, task: Task) {
-        if self.is_running && self.active_tasks < self.opts.max_tasks {
-            self.start_task(task).await;
-        } else if self.queue.len() < self.opts.max_queue_size {
-            self.queue.push_back(task);
-            self.signal_activity_change();
-        } else {
+    /// `retry_job_id` is set if this runs an existing Job again
+    async fn on_task_received(&mut self, task: Task, retry_job_id: Option<JobId>) {
+        let can_start = self.is_running && self.active_tasks < self.opts.max_tasks;
+        if !can_start && self.queue.len() >= self.opts.max_queue_size {
+            if let Some(job_id) = retry_job_id {
+                self.jobs
+                    .on_done(
+                        job_id,
+                        JobState::Cancelled,
+                        Some("job queue is full".to_string()),
+                    )
+                    .await;
+            }
             self.send_from_us
                 .send(MsgFrom::DroppedMessage)
                 .expect(SEND_ERROR_MESSAGE);
+            return;
+        }
+        // only record jobs we actually accept so that a flood of dropped messages doesn't
+        // leave a flood of jobs behind
+        let job_id = match retry_job_id {
+            Some(job_id) => {
+                self.jobs.on_requeued(job_id).await;
+                Some(job_id)
+            }
+            None => self.jobs.on_queued(task.job()).await,
+        };
+        let task = QueuedTask { task, job_id };
+        if can_start {
+            self.start_task(task).await;
+        } else {
+            self.queue.push_back(task);
+            self.signal_activity_change();
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn cancel_job(&mut self, job_id: JobId) {
+        let queue_pos = self
+            .queue
+            .iter()
+            .position(|queued| queued.job_id == Some(job_id));
+        if let Some(queue_pos) = queue_pos {
+            self.queue.remove(queue_pos);
+            self.jobs.on_done(job_id, JobState::Cancelled, None).await;
+            self.signal_activity_change();
+            return;
+        }
+        let task_id = self
+            .task_jobs
+            .iter()
+            .find(|(_task_id, running_job_id)| **running_job_id == job_id)
+            .map(|(task_id, _)| *task_id);
+        if let Some(ctl_send) = task_id.and_then(|task_id| self.task_ctl_sends.get(&task_id)) {
+            // the job is marked as cancelled once the task returns
+            let _ = ctl_send.send(MsgTaskControl::Cancel);
         }
     }
 }
@@ -249,13 +356,14 @@ pub struct ActorOptions {
 
 #[tracing::instrument(skip_all)]
 pub async fn run_actor<
-    Task: Debug + Send + Sync + 'static,
-    TaskResult: Debug + Send + Sync + 'static,
+    Task: Debug + Send + Sync + JobTask + 'static,
+    TaskResult: Debug + Send + Sync + JobTaskResult + 'static,
 >(
     mut actor_recv: mpsc::UnboundedReceiver<MsgTo<Task>>,
     send: mpsc::UnboundedSender<MsgFrom<TaskResult>>,
     did_shutdown_send: oneshot::Sender<()>,
     actor: impl Actor<Task, TaskResult>,
+    jobs: JobTracker,
     opts: ActorOptions,
 ) {
     let (actor_result_send, mut actor_result_recv) = mpsc::unbounded_channel();
@@ -269,6 +377,8 @@ pub async fn run_actor<
         actor,
         next_task_id: TaskId(0),
         task_ctl_sends: Default::default(),
+        jobs,
+        task_jobs: Default::default(),
         did_shutdown_send: Some(did_shutdown_send),
         waiting_for_shutdown: false,
     };
@@ -286,7 +396,13 @@ pub async fn run_actor<
                         runner.shutdown().await;
                     }
                     MsgTo::DoTask(task) => {
-                        runner.on_task_received(task).await;
+                        runner.on_task_received(task, None).await;
+                    }
+                    MsgTo::RetryJob(job_id, task) => {
+                        runner.on_task_received(task, Some(job_id)).await;
+                    }
+                    MsgTo::CancelJob(job_id) => {
+                        runner.cancel_job(job_id).await;
                     }
                 }
             }
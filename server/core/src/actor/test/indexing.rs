@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+use camino::Utf8Path as Path;
+
+use crate::actor::indexing::InFlightPaths;
+
+#[tokio::test]
+async fn indexing_of_same_path_is_serialized() {
+    let in_flight = InFlightPaths::default();
+    let path = Path::new("/photos/a.jpg");
+    let guard = in_flight.lock(path).await;
+
+    // other paths are not blocked
+    let other = tokio::time::timeout(
+        Duration::from_millis(100),
+        in_flight.lock(Path::new("/photos/b.jpg")),
+    )
+    .await
+    .expect("locking a different path should not wait");
+    drop(other);
+
+    let second = tokio::spawn({
+        let in_flight = in_flight.clone();
+        async move {
+            let _guard = in_flight.lock(Path::new("/photos/a.jpg")).await;
+        }
+    });
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!second.is_finished());
+
+    drop(guard);
+    tokio::time::timeout(Duration::from_secs(1), second)
+        .await
+        .expect("second lock should be acquired once the first is released")
+        .unwrap();
+    assert!(in_flight.locks.lock().unwrap().is_empty());
+}
@@ -0,0 +1,2 @@
+pub mod indexing;
+pub mod watcher;
@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use notify::{
+    event::{CreateKind, DataChange, MetadataKind, ModifyKind, RemoveKind, RenameMode},
+    EventKind,
+};
+
+use crate::{
+    actor::watcher::{
+        changes_from_event, effective_rescan_interval, is_rescan_due, record_change, root_for_path,
+        take_settled, ChangeKind, PendingChange, WatchedRoot,
+    },
+    model::{AssetRootDir, AssetRootDirId},
+};
+
+const DEBOUNCE: Duration = Duration::from_secs(2);
+
+fn watched_root(id: i64, path: &str, ignore_prefix: Option<&str>) -> WatchedRoot {
+    WatchedRoot {
+        root: AssetRootDir {
+            id: AssetRootDirId(id),
+            path: PathBuf::from(path),
+        },
+        ignore_prefix: ignore_prefix.map(PathBuf::from),
+        rescan_interval: None,
+        last_rescan: Instant::now(),
+    }
+}
+
+fn kinds(kind: EventKind, paths: &[&str]) -> Vec<(std::path::PathBuf, Option<ChangeKind>)> {
+    changes_from_event(kind, paths.iter().map(std::path::PathBuf::from).collect())
+}
+
+#[test]
+fn event_kinds_map_to_changes() {
+    assert_eq!(
+        kinds(EventKind::Create(CreateKind::File), &["/a"]),
+        vec![("/a".into(), Some(ChangeKind::Changed))]
+    );
+    assert_eq!(
+        kinds(EventKind::Remove(RemoveKind::File), &["/a"]),
+        vec![("/a".into(), Some(ChangeKind::Removed))]
+    );
+    assert_eq!(
+        kinds(
+            EventKind::Modify(ModifyKind::Data(DataChange::Any)),
+            &["/a"]
+        ),
+        vec![("/a".into(), Some(ChangeKind::Changed))]
+    );
+    assert_eq!(
+        kinds(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            &["/from", "/to"]
+        ),
+        vec![
+            ("/from".into(), Some(ChangeKind::Removed)),
+            ("/to".into(), Some(ChangeKind::Changed))
+        ]
+    );
+    assert_eq!(
+        kinds(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Any)),
+            &["/a"]
+        ),
+        vec![("/a".into(), None)]
+    );
+    assert!(kinds(
+        EventKind::Modify(ModifyKind::Metadata(MetadataKind::Any)),
+        &["/a"]
+    )
+    .is_empty());
+}
+
+#[test]
+fn paths_are_assigned_to_innermost_root_unless_ignored() {
+    let roots = vec![
+        watched_root(1, "/photos", Some("/photos/myrti-data")),
+        watched_root(2, "/photos/phone", None),
+    ];
+    let root_id = |path: &str| root_for_path(&roots, Path::new(path)).map(|r| r.root.id);
+    assert_eq!(root_id("/photos/a.jpg"), Some(AssetRootDirId(1)));
+    assert_eq!(root_id("/photos/phone/b.jpg"), Some(AssetRootDirId(2)));
+    assert_eq!(root_id("/photos/myrti-data/thumb.avif"), None);
+    assert_eq!(root_id("/elsewhere/c.jpg"), None);
+    // prefix matching is by path component, not by string
+    assert_eq!(root_id("/photosbackup/d.jpg"), None);
+}
+
+#[test]
+fn changes_are_held_back_until_quiet_for_debounce_period() {
+    let start = Instant::now();
+    let root_dir_id = AssetRootDirId(1);
+    let mut pending: HashMap<PathBuf, PendingChange> = HashMap::default();
+    record_change(
+        &mut pending,
+        "/photos/a.jpg".into(),
+        root_dir_id,
+        ChangeKind::Changed,
+        start,
+    );
+    // still being written to
+    record_change(
+        &mut pending,
+        "/photos/a.jpg".into(),
+        root_dir_id,
+        ChangeKind::Changed,
+        start + Duration::from_secs(1),
+    );
+    record_change(
+        &mut pending,
+        "/photos/b.jpg".into(),
+        root_dir_id,
+        ChangeKind::Changed,
+        start,
+    );
+
+    let batches = take_settled(&mut pending, start + DEBOUNCE, DEBOUNCE);
+    assert_eq!(
+        batches,
+        HashMap::from([(
+            (root_dir_id, ChangeKind::Changed),
+            vec![PathBuf::from("/photos/b.jpg")]
+        )])
+    );
+    assert_eq!(pending.len(), 1);
+
+    let batches = take_settled(&mut pending, start + Duration::from_secs(3), DEBOUNCE);
+    assert_eq!(
+        batches,
+        HashMap::from([(
+            (root_dir_id, ChangeKind::Changed),
+            vec![PathBuf::from("/photos/a.jpg")]
+        )])
+    );
+    assert!(pending.is_empty());
+}
+
+#[test]
+fn last_change_to_a_path_wins() {
+    let start = Instant::now();
+    let root_dir_id = AssetRootDirId(1);
+    let mut pending: HashMap<PathBuf, PendingChange> = HashMap::default();
+    // atomic save: old file removed, new one moved into place
+    record_change(
+        &mut pending,
+        "/photos/a.jpg".into(),
+        root_dir_id,
+        ChangeKind::Removed,
+        start,
+    );
+    record_change(
+        &mut pending,
+        "/photos/a.jpg".into(),
+        root_dir_id,
+        ChangeKind::Changed,
+        start,
+    );
+    // created and deleted again before it settled
+    record_change(
+        &mut pending,
+        "/photos/tmp.jpg".into(),
+        root_dir_id,
+        ChangeKind::Changed,
+        start,
+    );
+    record_change(
+        &mut pending,
+        "/photos/tmp.jpg".into(),
+        root_dir_id,
+        ChangeKind::Removed,
+        start,
+    );
+
+    let batches = take_settled(&mut pending, start + DEBOUNCE, DEBOUNCE);
+    assert_eq!(
+        batches,
+        HashMap::from([
+            (
+                (root_dir_id, ChangeKind::Changed),
+                vec![PathBuf::from("/photos/a.jpg")]
+            ),
+            (
+                (root_dir_id, ChangeKind::Removed),
+                vec![PathBuf::from("/photos/tmp.jpg")]
+            ),
+        ])
+    );
+}
+
+#[test]
+fn unwatched_roots_fall_back_to_periodic_rescans() {
+    let fallback = Duration::from_secs(3600);
+    let configured = Duration::from_secs(60);
+    assert_eq!(effective_rescan_interval(true, None, fallback), None);
+    assert_eq!(
+        effective_rescan_interval(true, Some(configured), fallback),
+        Some(configured)
+    );
+    assert_eq!(
+        effective_rescan_interval(false, None, fallback),
+        Some(fallback)
+    );
+    assert_eq!(
+        effective_rescan_interval(false, Some(configured), fallback),
+        Some(configured)
+    );
+}
+
+#[test]
+fn rescan_is_due_after_interval() {
+    let mut root = watched_root(1, "/photos", None);
+    let start = root.last_rescan;
+    assert!(!is_rescan_due(
+        &root,
+        start + Duration::from_secs(1_000_000)
+    ));
+    root.rescan_interval = Some(Duration::from_secs(60));
+    assert!(!is_rescan_due(&root, start + Duration::from_secs(59)));
+    assert!(is_rescan_due(&root, start + Duration::from_secs(60)));
+}
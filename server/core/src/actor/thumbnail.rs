@@ -1,6 +1,5 @@
-use chrono::Utc;
 use deadpool_diesel;
-use eyre::Result;
+use eyre::{Report, Result};
 use tokio::sync::{mpsc, oneshot};
 use tracing::Instrument;
 
@@ -17,6 +16,7 @@ use crate::{
         },
         storage_key,
     },
+    config,
     core::storage::Storage,
     interact,
     model::{
@@ -24,13 +24,18 @@ use crate::{
             self,
             db::{DbPool, PooledDbConn},
         },
-        AssetId, FailedThumbnailJob, ThumbnailFormat,
+        AssetId, CreateJob, FailedJobKind, JobFailure, JobKind, JobSubject, ThumbnailFormat,
+    },
+    processing::process_control::{
+        job_failure, process_control_channel, ProcessControlReceiver, ProgressSender,
     },
-    processing::{hash::hash_file, process_control::ProcessControlReceiver},
 };
 
-use super::simple_queue_actor::{
-    Actor, ActorOptions, MsgFrom, MsgTaskControl, QueuedActorHandle, TaskError, TaskId,
+use super::{
+    jobs::{JobTask, JobTaskResult, JobTracker},
+    simple_queue_actor::{
+        Actor, ActorOptions, MsgFrom, MsgTaskControl, QueuedActorHandle, TaskError, TaskId,
+    },
 };
 
 pub type ThumbnailActorHandle = QueuedActorHandle<ThumbnailTaskMsg>;
@@ -48,19 +53,63 @@ pub enum ThumbnailTaskResult {
     Album(Result<CreateAlbumThumbnailWithPaths>),
 }
 
+impl JobTask for ThumbnailTaskMsg {
+    fn job(&self) -> CreateJob {
+        match self {
+            ThumbnailTaskMsg::CreateAssetThumbnail(create_thumbnail) => CreateJob {
+                kind: JobKind::AssetThumbnail,
+                subject: JobSubject::Asset(create_thumbnail.asset_id),
+            },
+            ThumbnailTaskMsg::CreateAlbumThumbnail(create_thumbnail) => CreateJob {
+                kind: JobKind::AlbumThumbnail,
+                subject: JobSubject::Album {
+                    album_id: create_thumbnail.album_id,
+                    asset_id: create_thumbnail.asset_id,
+                },
+            },
+        }
+    }
+}
+
+impl JobTaskResult for ThumbnailTaskResult {
+    fn job_error(&self) -> Option<String> {
+        match self {
+            ThumbnailTaskResult::Asset(Ok(result)) => {
+                let errors: Vec<String> = result
+                    .failed
+                    .iter()
+                    .map(|(thumbnail, report)| format!("{:?}: {:#}", thumbnail.ty, report))
+                    .collect();
+                (!errors.is_empty()).then(|| errors.join("\n"))
+            }
+            ThumbnailTaskResult::Album(Ok(_)) => None,
+            ThumbnailTaskResult::Asset(Err(report)) => Some(format!("{:#}", report)),
+            ThumbnailTaskResult::Album(Err(report)) => Some(format!("{:#}", report)),
+        }
+    }
+}
+
 pub fn start_thumbnail_actor(
     db_pool: DbPool,
     storage: Storage,
+    config: config::Config,
     did_shutdown_send: oneshot::Sender<()>,
     send_from_us: mpsc::UnboundedSender<MsgFromThumbnail>,
 ) -> ThumbnailActorHandle {
-    let actor = ThumbnailActor { db_pool, storage };
+    let jobs = JobTracker::new(db_pool.clone());
+    let actor_config = config.processing.thumbnail;
+    let actor = ThumbnailActor {
+        db_pool,
+        storage,
+        process_priority: actor_config.priority,
+    };
     QueuedActorHandle::new(
         actor,
+        jobs,
         send_from_us,
         did_shutdown_send,
         ActorOptions {
-            max_tasks: 8,
+            max_tasks: actor_config.max_tasks,
             max_queue_size: 1000,
         },
         tracing::info_span!("thumbnail"),
@@ -80,24 +129,28 @@ impl QueuedActorHandle<ThumbnailTaskMsg> {
 struct ThumbnailActor {
     db_pool: DbPool,
     storage: Storage,
+    process_priority: config::ProcessPriority,
 }
 
 impl Actor<ThumbnailTaskMsg, ThumbnailTaskResult> for ThumbnailActor {
-    #[tracing::instrument(skip(self, ctl_recv))]
+    #[tracing::instrument(skip(self, ctl_recv, _progress_send))]
     async fn run_task(
         &mut self,
         msg: ThumbnailTaskMsg,
         result_send: mpsc::UnboundedSender<(TaskId, Result<ThumbnailTaskResult, TaskError>)>,
         task_id: TaskId,
         mut ctl_recv: mpsc::UnboundedReceiver<MsgTaskControl>,
+        _progress_send: ProgressSender,
     ) {
-        let (process_control_send, mut process_control_recv) = mpsc::channel(1);
+        let (process_control_send, mut process_control_recv) =
+            process_control_channel(self.process_priority);
         match msg {
             ThumbnailTaskMsg::CreateAssetThumbnail(create_thumbnail) => {
                 let db_pool = self.db_pool.clone();
                 let storage = self.storage.clone();
                 tokio::task::spawn(
                     async move {
+                        let asset_id = create_thumbnail.asset_id;
                         // ugly, rewrite this with try blocks one day hopefuly
                         let result_fut = do_asset_thumbnail_side_effects(
                             db_pool.clone(),
@@ -125,7 +178,11 @@ impl Actor<ThumbnailTaskMsg, ThumbnailTaskResult> for ThumbnailActor {
                                 for (_thumbnail, report) in &result.failed {
                                     tracing::warn!(?report, %result.asset_id, "failed to create thumbnail");
                                 }
-                                save_failed_thumbnail(&mut conn, result.asset_id).await?;
+                                let failure = thumbnails_failure(&result.failed);
+                                save_failed_thumbnail(&mut conn, result.asset_id, Some(failure))
+                                    .await?;
+                            } else {
+                                save_failed_thumbnail(&mut conn, result.asset_id, None).await?;
                             }
                             for succeeded in &result.succeeded {
                                 apply_create_thumbnail(
@@ -150,6 +207,21 @@ impl Actor<ThumbnailTaskMsg, ThumbnailTaskResult> for ThumbnailActor {
                                     .expect("Receiver must be alive");
                             }
                         } else {
+                            if let Err(report) = &result {
+                                let save_result = async {
+                                    let mut conn = db_pool.get().await?;
+                                    save_failed_thumbnail(
+                                        &mut conn,
+                                        asset_id,
+                                        Some(job_failure(report)),
+                                    )
+                                    .await
+                                }
+                                .await;
+                                if let Err(err) = save_result {
+                                    tracing::warn!(?err, %asset_id, "error saving failed thumbnail");
+                                }
+                            }
                             result_send
                                 .send((task_id, Ok(ThumbnailTaskResult::Asset(result))))
                                 .expect("Receiver must be alive");
@@ -253,35 +325,7 @@ async fn do_asset_thumbnail_side_effects(
     op: CreateAssetThumbnail,
     control_recv: &mut ProcessControlReceiver,
 ) -> Result<ThumbnailSideEffectResult> {
-    let conn = db_pool.get().await?;
-    let asset_id = op.asset_id;
-    let past_failed_job = interact!(conn, move |conn| {
-        repository::failed_job::get_failed_thumbnail_job_for_asset(conn, asset_id)
-    })
-    .await??;
-    if let Some(past_failed_job) = past_failed_job {
-        let asset_path = interact!(conn, move |conn| {
-            repository::asset::get_asset_path_on_disk(conn, asset_id)
-        })
-        .await??
-        .path_on_disk();
-        let file = tokio::fs::File::open(&asset_path)
-            .await?
-            .try_into_std()
-            .expect("no operation has touched this file");
-        let current_hash = hash_file(file).await?;
-        if current_hash == past_failed_job.file_hash {
-            tracing::debug!(
-                asset_id = ?asset_id,
-                "skipping thumbnail that failed in the past"
-            );
-            // FIXME: we're not actually skipping anything here. return optional result and add
-            // flag to force retry (force if user asks to create thumbnail and on startup, don't
-            // force otherwise)
-        }
-    }
-    drop(conn); // don't hold connection over long operations that don't need it
-
+    // whether it is worth trying again after failing before is decided in the rules
     let op_resolved = resolve(&op);
     perform_side_effects_create_thumbnail(
         &storage,
@@ -319,28 +363,47 @@ async fn do_album_thumbnail_side_effects(
     Ok(op_with_paths)
 }
 
+/// Record that creating thumbnails for the Asset failed, or forget about earlier failures
+/// if `failure` is None
 #[tracing::instrument(skip(conn))]
-async fn save_failed_thumbnail(conn: &mut PooledDbConn, asset_id: AssetId) -> Result<()> {
-    let asset_path = interact!(conn, move |conn| {
-        repository::asset::get_asset_path_on_disk(conn, asset_id)
-    })
-    .await??
-    .path_on_disk();
-    let file = tokio::fs::File::open(&asset_path)
-        .await?
-        .try_into_std()
-        .expect("no operation has touched this file");
-    let hash = hash_file(file).await?;
-    interact!(conn, move |conn| {
-        repository::failed_job::insert_failed_thumbnail_job(
+async fn save_failed_thumbnail(
+    conn: &mut PooledDbConn,
+    asset_id: AssetId,
+    failure: Option<JobFailure>,
+) -> Result<()> {
+    interact!(conn, move |conn| match failure {
+        Some(failure) => repository::failed_job::record_failed_job(
             conn,
-            &FailedThumbnailJob {
-                asset_id,
-                file_hash: hash,
-                date: Utc::now(),
-            },
-        )
+            asset_id,
+            FailedJobKind::Thumbnail,
+            &failure,
+        ),
+        None => repository::failed_job::delete_failed_job(conn, asset_id, FailedJobKind::Thumbnail),
     })
     .await??;
     Ok(())
 }
+
+/// All errors, and the command of the first one that came from an external program
+fn thumbnails_failure(failed: &[(ThumbnailToCreateWithPaths, Report)]) -> JobFailure {
+    let failures: Vec<JobFailure> = failed
+        .iter()
+        .map(|(_thumbnail, report)| job_failure(report))
+        .collect();
+    let error = failed
+        .iter()
+        .zip(failures.iter())
+        .map(|((thumbnail, _), failure)| format!("{:?}: {}", thumbnail.ty, failure.error))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let from_tool = failures.into_iter().find(|failure| failure.tool.is_some());
+    match from_tool {
+        Some(failure) => JobFailure { error, ..failure },
+        None => JobFailure {
+            error,
+            tool: None,
+            command: None,
+            stderr: None,
+        },
+    }
+}
@@ -10,11 +10,17 @@ use crate::{
     },
     config,
     core::storage::Storage,
-    model::repository::db::DbPool,
+    interact,
+    model::{
+        repository::{self, db::DbPool},
+        AssetId, CreateJob, FailedJobKind, JobFailure, JobKind, JobSubject,
+    },
+    processing::process_control::{job_failure, process_control_channel, ProgressSender},
 };
 
-use super::simple_queue_actor::{
-    Actor, ActorOptions, MsgFrom, MsgTaskControl, QueuedActorHandle, TaskId,
+use super::{
+    jobs::{JobTask, JobTaskResult, JobTracker},
+    simple_queue_actor::{Actor, ActorOptions, MsgFrom, MsgTaskControl, QueuedActorHandle, TaskId},
 };
 
 pub type VideoPackagingActorHandle = QueuedActorHandle<VideoPackagingTaskMsg>;
@@ -34,6 +40,28 @@ pub enum VideoPackagingTaskResult {
     },
 }
 
+impl JobTask for VideoPackagingTaskMsg {
+    fn job(&self) -> CreateJob {
+        match self {
+            VideoPackagingTaskMsg::PackageVideo(package_video) => CreateJob {
+                kind: JobKind::PackageVideo,
+                subject: JobSubject::Asset(package_video.asset_id),
+            },
+        }
+    }
+}
+
+impl JobTaskResult for VideoPackagingTaskResult {
+    fn job_error(&self) -> Option<String> {
+        match self {
+            VideoPackagingTaskResult::PackagingComplete(_) => None,
+            VideoPackagingTaskResult::PackagingError { report, .. } => {
+                Some(format!("{:#}", report))
+            }
+        }
+    }
+}
+
 pub fn start_video_packaging_actor(
     db_pool: DbPool,
     storage: Storage,
@@ -41,6 +69,8 @@ pub fn start_video_packaging_actor(
     did_shutdown_send: oneshot::Sender<()>,
     send_from_us: mpsc::UnboundedSender<MsgFromVideoPackaging>,
 ) -> QueuedActorHandle<VideoPackagingTaskMsg> {
+    let jobs = JobTracker::new(db_pool.clone());
+    let max_tasks = config.processing.video_packaging.max_tasks;
     let actor = VideoPackagingActor {
         db_pool,
         storage,
@@ -48,10 +78,11 @@ pub fn start_video_packaging_actor(
     };
     QueuedActorHandle::new(
         actor,
+        jobs,
         send_from_us,
         did_shutdown_send,
         ActorOptions {
-            max_tasks: 1,
+            max_tasks,
             max_queue_size: 100,
         },
         tracing::info_span!("video_packaging"),
@@ -77,29 +108,59 @@ impl Actor<VideoPackagingTaskMsg, VideoPackagingTaskResult> for VideoPackagingAc
         result_send: mpsc::UnboundedSender<(TaskId, Result<VideoPackagingTaskResult, TaskError>)>,
         task_id: TaskId,
         mut ctl_recv: mpsc::UnboundedReceiver<MsgTaskControl>,
+        progress_send: ProgressSender,
     ) {
         match msg {
             VideoPackagingTaskMsg::PackageVideo(package_video) => {
                 let db_pool = self.db_pool.clone();
                 let storage = self.storage.clone();
                 let bin_paths = self.config.bin_paths.clone();
+                let process_priority = self.config.processing.video_packaging.priority;
                 async fn apply_result(
                     db_pool: DbPool,
                     result: CompletedPackageVideo,
                 ) -> Result<()> {
                     let mut conn = db_pool.get().await?;
-                    apply_package_video(&mut conn, result.clone()).await
+                    let asset_id = result.asset_id;
+                    apply_package_video(&mut conn, result.clone()).await?;
+                    interact!(conn, move |conn| {
+                        repository::failed_job::delete_failed_job(
+                            conn,
+                            asset_id,
+                            FailedJobKind::VideoPackaging,
+                        )
+                    })
+                    .await??;
+                    Ok(())
+                }
+                async fn save_failure(
+                    db_pool: &DbPool,
+                    asset_id: AssetId,
+                    failure: JobFailure,
+                ) -> Result<()> {
+                    let conn = db_pool.get().await?;
+                    interact!(conn, move |conn| {
+                        repository::failed_job::record_failed_job(
+                            conn,
+                            asset_id,
+                            FailedJobKind::VideoPackaging,
+                            &failure,
+                        )
+                    })
+                    .await??;
+                    Ok(())
                 }
                 tokio::task::spawn(
                     async move {
                         let (process_control_send, process_control_recv) =
-                            tokio::sync::mpsc::channel(1);
+                            process_control_channel(process_priority);
                         let result_fut = perform_side_effects_package_video(
                             &db_pool,
                             &storage,
                             &package_video,
                             bin_paths.as_ref(),
                             process_control_recv,
+                            progress_send,
                         );
                         let task_result =
                             task_loop(result_fut, &mut ctl_recv, process_control_send).await;
@@ -140,6 +201,12 @@ impl Actor<VideoPackagingTaskMsg, VideoPackagingTaskResult> for VideoPackagingAc
                                 }
                             }
                             Err(report) => {
+                                let asset_id = package_video.asset_id;
+                                if let Err(err) =
+                                    save_failure(&db_pool, asset_id, job_failure(&report)).await
+                                {
+                                    tracing::warn!(?err, %asset_id, "error saving failed video packaging");
+                                }
                                 result_send
                                     .send((
                                         task_id,
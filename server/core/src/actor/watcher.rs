@@ -0,0 +1,411 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use camino::Utf8PathBuf as PathBuf;
+use eyre::{Context, Result};
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+
+use crate::{
+    config, interact,
+    model::{
+        repository::{self, db::DbPool},
+        AssetRootDir, AssetRootDirId,
+    },
+};
+
+/// Filesystem changes in asset roots, debounced so that a file that is still being written
+/// or saved in multiple steps results in only one message
+#[derive(Debug)]
+pub enum MsgFromWatcher {
+    /// Files or directories were created, modified or moved into place
+    PathsChanged {
+        root_dir_id: AssetRootDirId,
+        paths: Vec<PathBuf>,
+    },
+    /// Files or directories were deleted or moved away
+    PathsRemoved {
+        root_dir_id: AssetRootDirId,
+        paths: Vec<PathBuf>,
+    },
+    /// The root should be fully rescanned, either because it is not watched
+    /// and its rescan interval elapsed or because events were lost
+    RescanRequired { root_dir_id: AssetRootDirId },
+    WatchError {
+        root_dir_id: Option<AssetRootDirId>,
+        report: eyre::Report,
+    },
+}
+
+#[derive(Debug)]
+enum MsgToWatcher {
+    /// (Re)load AssetRootDirs from the database and start watching them
+    WatchAssetRoots,
+}
+
+#[derive(Clone)]
+pub struct WatcherActorHandle {
+    send: mpsc::UnboundedSender<MsgToWatcher>,
+}
+
+impl WatcherActorHandle {
+    pub fn new(
+        db_pool: DbPool,
+        config: config::Config,
+        send_from_us: mpsc::UnboundedSender<MsgFromWatcher>,
+    ) -> Self {
+        let (send, recv) = mpsc::unbounded_channel();
+        let actor = WatcherActor {
+            db_pool,
+            config,
+            send_from_us,
+            watcher: None,
+            roots: Vec::default(),
+            pending: HashMap::default(),
+        };
+        tokio::spawn(run_watcher_actor(recv, actor).instrument(tracing::info_span!("watcher")));
+        Self { send }
+    }
+
+    pub fn msg_watch_asset_roots(&self) -> Result<()> {
+        self.send.send(MsgToWatcher::WatchAssetRoots)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum ChangeKind {
+    Changed,
+    Removed,
+}
+
+#[derive(Debug)]
+pub(super) struct PendingChange {
+    pub root_dir_id: AssetRootDirId,
+    pub kind: ChangeKind,
+    pub last_event: Instant,
+}
+
+#[derive(Debug)]
+pub(super) struct WatchedRoot {
+    pub root: AssetRootDir,
+    /// Events for paths under here are dropped, used when the data dir is inside the root
+    pub ignore_prefix: Option<PathBuf>,
+    pub rescan_interval: Option<Duration>,
+    pub last_rescan: Instant,
+}
+
+struct WatcherActor {
+    db_pool: DbPool,
+    config: config::Config,
+    send_from_us: mpsc::UnboundedSender<MsgFromWatcher>,
+    watcher: Option<RecommendedWatcher>,
+    roots: Vec<WatchedRoot>,
+    pending: HashMap<PathBuf, PendingChange>,
+}
+
+const TICK_INTERVAL: Duration = Duration::from_millis(500);
+
+async fn run_watcher_actor(
+    mut recv: mpsc::UnboundedReceiver<MsgToWatcher>,
+    mut actor: WatcherActor,
+) {
+    let (event_send, mut event_recv) = mpsc::unbounded_channel::<notify::Result<notify::Event>>();
+    let mut tick = tokio::time::interval(TICK_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tokio::select! {
+            msg = recv.recv() => {
+                match msg {
+                    Some(MsgToWatcher::WatchAssetRoots) => {
+                        if let Err(report) = actor.watch_asset_roots(event_send.clone()).await {
+                            let _ = actor.send_from_us.send(MsgFromWatcher::WatchError {
+                                root_dir_id: None,
+                                report,
+                            });
+                        }
+                    }
+                    None => {
+                        // handle dropped, nobody is listening anymore
+                        break;
+                    }
+                }
+            }
+            Some(event) = event_recv.recv() => {
+                actor.on_event(event);
+            }
+            _ = tick.tick() => {
+                actor.flush_pending();
+                actor.request_due_rescans();
+            }
+        }
+    }
+}
+
+impl WatcherActor {
+    async fn watch_asset_roots(
+        &mut self,
+        event_send: mpsc::UnboundedSender<notify::Result<notify::Event>>,
+    ) -> Result<()> {
+        let conn = self.db_pool.get().await?;
+        let asset_roots = interact!(conn, move |conn| {
+            repository::asset_root_dir::get_asset_roots(conn)
+        })
+        .await?
+        .wrap_err("error getting AssetRootDirs")?;
+
+        // drop the previous watcher first so we don't get duplicate events
+        self.watcher = None;
+        let mut watcher = notify::recommended_watcher(move |res| {
+            let _ = event_send.send(res);
+        })
+        .wrap_err("error creating filesystem watcher")?;
+
+        let data_dir_canonical = std::fs::canonicalize(&self.config.data_dir.path).ok();
+        let now = Instant::now();
+        let mut roots = Vec::default();
+        for root in asset_roots {
+            let asset_dir = self
+                .config
+                .asset_dirs
+                .iter()
+                .find(|asset_dir| asset_dir.path == root.path);
+            let should_watch = asset_dir.map(|d| d.watch).unwrap_or(true);
+            let is_watched = if should_watch {
+                let watch_result = watcher
+                    .watch(root.path.as_std_path(), RecursiveMode::Recursive)
+                    .wrap_err_with(|| format!("error watching asset root {}", root.path));
+                match watch_result {
+                    Ok(()) => {
+                        tracing::info!(path = %root.path, "Watching asset root");
+                        true
+                    }
+                    Err(report) => {
+                        // e.g. inotify watch limit reached, fall back to rescanning periodically
+                        let _ = self.send_from_us.send(MsgFromWatcher::WatchError {
+                            root_dir_id: Some(root.id),
+                            report,
+                        });
+                        false
+                    }
+                }
+            } else {
+                false
+            };
+            let rescan_interval = effective_rescan_interval(
+                is_watched,
+                asset_dir.and_then(|d| d.rescan_interval),
+                self.config.watcher.fallback_rescan_interval,
+            );
+            if let Some(interval) = rescan_interval {
+                tracing::info!(path = %root.path, ?interval, "Periodically rescanning asset root");
+            }
+            let ignore_prefix = data_dir_canonical.as_ref().and_then(|data_dir| {
+                let root_canonical = std::fs::canonicalize(&root.path).ok()?;
+                let data_dir_in_root = data_dir.strip_prefix(root_canonical).ok()?;
+                let data_dir_in_root = camino::Utf8Path::from_path(data_dir_in_root)?;
+                Some(root.path.join(data_dir_in_root))
+            });
+            roots.push(WatchedRoot {
+                root,
+                ignore_prefix,
+                rescan_interval,
+                last_rescan: now,
+            });
+        }
+        self.watcher = Some(watcher);
+        self.roots = roots;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn on_event(&mut self, event: notify::Result<notify::Event>) {
+        let event = match event {
+            Ok(event) => event,
+            Err(err) => {
+                let _ = self.send_from_us.send(MsgFromWatcher::WatchError {
+                    root_dir_id: None,
+                    report: eyre::Report::from(err).wrap_err("error from filesystem watcher"),
+                });
+                return;
+            }
+        };
+        if event.need_rescan() {
+            // the kernel queue overflowed or similar, we can't know what we missed
+            for root in &self.roots {
+                let _ = self.send_from_us.send(MsgFromWatcher::RescanRequired {
+                    root_dir_id: root.root.id,
+                });
+            }
+            return;
+        }
+        let now = Instant::now();
+        for (path, kind) in changes_from_event(event.kind, event.paths) {
+            let path = match PathBuf::from_path_buf(path) {
+                Ok(p) => p,
+                Err(path) => {
+                    tracing::warn!(?path, "ignoring non UTF-8 path");
+                    continue;
+                }
+            };
+            let Some(root) = root_for_path(&self.roots, &path) else {
+                continue;
+            };
+            let root_dir_id = root.root.id;
+            let kind = kind.unwrap_or(if path.exists() {
+                ChangeKind::Changed
+            } else {
+                ChangeKind::Removed
+            });
+            record_change(&mut self.pending, path, root_dir_id, kind, now);
+        }
+    }
+
+    fn flush_pending(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let batches = take_settled(
+            &mut self.pending,
+            Instant::now(),
+            self.config.watcher.debounce,
+        );
+        for ((root_dir_id, kind), paths) in batches {
+            tracing::debug!(%root_dir_id, ?kind, num_paths = paths.len(), "filesystem changes");
+            let msg = match kind {
+                ChangeKind::Changed => MsgFromWatcher::PathsChanged { root_dir_id, paths },
+                ChangeKind::Removed => MsgFromWatcher::PathsRemoved { root_dir_id, paths },
+            };
+            let _ = self.send_from_us.send(msg);
+        }
+    }
+
+    fn request_due_rescans(&mut self) {
+        let now = Instant::now();
+        for root in self.roots.iter_mut() {
+            if is_rescan_due(root, now) {
+                root.last_rescan = now;
+                let _ = self.send_from_us.send(MsgFromWatcher::RescanRequired {
+                    root_dir_id: root.root.id,
+                });
+            }
+        }
+    }
+}
+
+/// What happened to each path of an event, or None if it can't be told from the event
+/// and must be decided based on whether the path exists once the debounce period is over
+pub(super) fn changes_from_event(
+    kind: EventKind,
+    paths: Vec<std::path::PathBuf>,
+) -> Vec<(std::path::PathBuf, Option<ChangeKind>)> {
+    let mut paths = paths.into_iter();
+    match kind {
+        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => Vec::default(),
+        EventKind::Create(_) => paths.map(|p| (p, Some(ChangeKind::Changed))).collect(),
+        EventKind::Remove(_) => paths.map(|p| (p, Some(ChangeKind::Removed))).collect(),
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => {
+            paths.map(|p| (p, Some(ChangeKind::Removed))).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => {
+            paths.map(|p| (p, Some(ChangeKind::Changed))).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+            // paths are [from, to]
+            let from = paths.next().map(|p| (p, Some(ChangeKind::Removed)));
+            let to = paths.next().map(|p| (p, Some(ChangeKind::Changed)));
+            from.into_iter().chain(to).collect()
+        }
+        EventKind::Modify(ModifyKind::Name(_)) | EventKind::Any | EventKind::Other => {
+            paths.map(|p| (p, None)).collect()
+        }
+        EventKind::Modify(_) => paths.map(|p| (p, Some(ChangeKind::Changed))).collect(),
+    }
+}
+
+/// Innermost watched root containing path, or None if the path should be ignored
+pub(super) fn root_for_path<'a>(
+    roots: &'a [WatchedRoot],
+    path: &camino::Utf8Path,
+) -> Option<&'a WatchedRoot> {
+    let root = roots
+        .iter()
+        .filter(|root| path.starts_with(&root.root.path))
+        .max_by_key(|root| root.root.path.as_str().len())?;
+    match &root.ignore_prefix {
+        Some(ignore_prefix) if path.starts_with(ignore_prefix) => None,
+        _ => Some(root),
+    }
+}
+
+/// Add a change to the pending ones, restarting the debounce period for the path
+pub(super) fn record_change(
+    pending: &mut HashMap<PathBuf, PendingChange>,
+    path: PathBuf,
+    root_dir_id: AssetRootDirId,
+    kind: ChangeKind,
+    now: Instant,
+) {
+    // the last event wins: a file that is deleted and recreated (like editors saving
+    // atomically do) is a change, not a removal
+    pending.insert(
+        path,
+        PendingChange {
+            root_dir_id,
+            kind,
+            last_event: now,
+        },
+    );
+}
+
+/// Remove the changes that have been quiet for at least `debounce` from pending
+/// and group them by root and kind
+pub(super) fn take_settled(
+    pending: &mut HashMap<PathBuf, PendingChange>,
+    now: Instant,
+    debounce: Duration,
+) -> HashMap<(AssetRootDirId, ChangeKind), Vec<PathBuf>> {
+    let settled: Vec<PathBuf> = pending
+        .iter()
+        .filter(|(_path, change)| now.saturating_duration_since(change.last_event) >= debounce)
+        .map(|(path, _change)| path.clone())
+        .collect();
+    let mut batches: HashMap<(AssetRootDirId, ChangeKind), Vec<PathBuf>> = HashMap::default();
+    for path in settled {
+        let change = pending
+            .remove(&path)
+            .expect("path was just found in pending");
+        batches
+            .entry((change.root_dir_id, change.kind))
+            .or_default()
+            .push(path);
+    }
+    batches
+}
+
+/// Roots that are not watched, either by configuration or because watching failed,
+/// are rescanned periodically even if no rescan interval is configured
+pub(super) fn effective_rescan_interval(
+    is_watched: bool,
+    configured: Option<Duration>,
+    fallback: Duration,
+) -> Option<Duration> {
+    if is_watched {
+        configured
+    } else {
+        configured.or(Some(fallback))
+    }
+}
+
+pub(super) fn is_rescan_due(root: &WatchedRoot, now: Instant) -> bool {
+    match root.rescan_interval {
+        Some(interval) => now.saturating_duration_since(root.last_rescan) >= interval,
+        None => false,
+    }
+}
@@ -62,6 +62,9 @@ pub async fn apply_create_thumbnail(
                 format: result.format,
             },
         )?;
+        if let Some(perceptual_hash) = result.perceptual_hash {
+            repository::asset::set_perceptual_hash(conn, asset_id, perceptual_hash)?;
+        }
         Ok(())
     })
     .await??;
@@ -73,6 +76,7 @@ pub struct ThumbnailSideEffectSuccess {
     pub ty: ThumbnailType,
     pub format: ThumbnailFormat,
     pub actual_size: Size,
+    pub perceptual_hash: Option<u64>,
 }
 
 #[derive(Debug)]
@@ -121,6 +125,7 @@ pub async fn perform_side_effects_create_thumbnail(
                         ty: thumb.ty,
                         format,
                         actual_size: res.actual_size,
+                        perceptual_hash: res.perceptual_hash,
                     });
                 }
             }
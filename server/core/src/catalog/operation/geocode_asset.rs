@@ -0,0 +1,45 @@
+use eyre::{Context, Result};
+
+use crate::{
+    interact,
+    model::{
+        repository::{self, db::PooledDbConn},
+        AssetId, AssetPlace, FailedJobKind, GpsCoordinates,
+    },
+};
+
+/// Look up the country, region and city an Asset's GPS coordinates are in
+#[derive(Debug, Clone)]
+pub struct GeocodeAsset {
+    pub asset_id: AssetId,
+    pub coordinates: GpsCoordinates,
+}
+
+impl GeocodeAsset {
+    /// In degrees, as used by the geocode crate
+    pub fn lat_lon(&self) -> (f32, f32) {
+        (
+            (self.coordinates.lat as f64 / 10e8) as f32,
+            (self.coordinates.lon as f64 / 10e8) as f32,
+        )
+    }
+}
+
+/// `place` is None if the coordinates are not inside any country.
+/// If the Asset's coordinates changed in the meantime it is geocoded again later.
+#[tracing::instrument(skip(conn), level = "debug")]
+pub async fn apply_geocode_asset(
+    conn: &mut PooledDbConn,
+    op: &GeocodeAsset,
+    place: Option<AssetPlace>,
+) -> Result<()> {
+    let asset_id = op.asset_id;
+    let coordinates = op.coordinates;
+    interact!(conn, move |conn| {
+        repository::asset_place::set_asset_place(conn, asset_id, coordinates, place.as_ref())?;
+        repository::failed_job::delete_failed_job(conn, asset_id, FailedJobKind::ReverseGeocode)
+    })
+    .await?
+    .wrap_err("error saving Asset place")?;
+    Ok(())
+}
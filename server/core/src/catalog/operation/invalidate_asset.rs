@@ -0,0 +1,35 @@
+use eyre::{Context, Result};
+
+use crate::{
+    interact,
+    model::{
+        repository::{self, db::PooledDbConn},
+        AssetId,
+    },
+};
+
+use super::obsolete_files::{asset_derived_files, ObsoleteFiles};
+
+/// Forget thumbnails, representations and DASH packaging of an Asset whose file changed,
+/// so that the rules create them again from the new file
+#[derive(Debug, Clone)]
+pub struct InvalidateAssetDerivedFiles {
+    pub asset_id: AssetId,
+}
+
+/// Like `apply_purge_asset`, the database is changed first and the returned files
+/// are deleted afterwards with `delete_obsolete_files`
+#[tracing::instrument(skip(conn), level = "debug")]
+pub async fn apply_invalidate_asset_derived_files(
+    conn: &mut PooledDbConn,
+    op: &InvalidateAssetDerivedFiles,
+) -> Result<ObsoleteFiles> {
+    let asset_id = op.asset_id;
+    let files = asset_derived_files(conn, asset_id).await?;
+    interact!(conn, move |conn| {
+        repository::asset::delete_asset_derived_data(conn, asset_id)
+    })
+    .await?
+    .wrap_err("error deleting thumbnails and representations")?;
+    Ok(files)
+}
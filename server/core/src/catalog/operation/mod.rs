@@ -1,4 +1,8 @@
 pub mod convert_image;
 pub mod create_album_thumbnail;
 pub mod create_thumbnail;
+pub mod geocode_asset;
+pub mod invalidate_asset;
+pub mod obsolete_files;
 pub mod package_video;
+pub mod purge_asset;
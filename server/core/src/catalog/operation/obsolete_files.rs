@@ -0,0 +1,132 @@
+use std::collections::{BTreeSet, HashSet};
+
+use chrono::{Duration, Utc};
+use eyre::Result;
+
+use crate::{
+    catalog::storage_key,
+    core::storage::{Storage, StorageObject, StorageProvider},
+    interact,
+    model::{
+        repository::{self, db::PooledDbConn},
+        AssetId,
+    },
+};
+
+/// Files in storage that nothing in the database refers to anymore
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ObsoleteFiles {
+    pub keys: Vec<String>,
+    pub key_prefixes: Vec<String>,
+}
+
+/// Storage keys of everything created from an Asset's file:
+/// thumbnails, image representations and DASH packaging
+pub async fn asset_derived_files(
+    conn: &mut PooledDbConn,
+    asset_id: AssetId,
+) -> Result<ObsoleteFiles> {
+    let (thumbnails, image_reprs, video_reprs, audio_reprs) = interact!(conn, move |conn| {
+        let thumbnails = repository::asset::get_thumbnails_for_asset(conn, asset_id)?;
+        let image_reprs = repository::representation::get_image_representations(conn, asset_id)?;
+        let video_reprs = repository::representation::get_video_representations(conn, asset_id)?;
+        let audio_reprs = repository::representation::get_audio_representations(conn, asset_id)?;
+        Ok((thumbnails, image_reprs, video_reprs, audio_reprs))
+    })
+    .await??;
+    let keys = thumbnails
+        .into_iter()
+        .map(|thumb| storage_key::thumbnail(asset_id, thumb.ty, thumb.format))
+        .chain(image_reprs.into_iter().map(|repr| repr.file_key))
+        .chain(
+            video_reprs
+                .into_iter()
+                .flat_map(|repr| [repr.file_key, repr.media_info_key]),
+        )
+        .chain(
+            audio_reprs
+                .into_iter()
+                .flat_map(|repr| [repr.file_key, repr.media_info_key]),
+        )
+        .collect();
+    // the mpd manifest and anything else packaging left behind
+    let key_prefixes = vec![storage_key::dash_dir(asset_id)];
+    Ok(ObsoleteFiles { keys, key_prefixes })
+}
+
+/// Files younger than this may belong to a job that is still running
+/// and has not inserted its rows yet
+const ORPHANED_FILE_MIN_AGE_DAYS: i64 = 1;
+
+/// Files below `storage_key::PREFIXES` that no row in the database refers to,
+/// left behind by jobs that crashed or were interrupted, or by failing to delete them.
+/// DASH directories are kept or deleted as a whole since the mpd manifest is not in the database.
+#[tracing::instrument(skip(conn, storage))]
+pub async fn orphaned_files(conn: &mut PooledDbConn, storage: &Storage) -> Result<ObsoleteFiles> {
+    // list before querying so that files created in between are not considered
+    let mut objects: Vec<StorageObject> = Vec::default();
+    for prefix in storage_key::PREFIXES {
+        objects.extend(storage.list_prefix(prefix).await?);
+    }
+    let referenced_keys: HashSet<String> = interact!(conn, move |conn| {
+        let thumbnail_keys = repository::asset::get_all_thumbnails(conn)?
+            .into_iter()
+            .map(|thumb| storage_key::thumbnail(thumb.asset_id, thumb.ty, thumb.format));
+        let album_thumbnail_keys = repository::album_thumbnail::get_all_album_thumbnail_keys(conn)?;
+        let repr_keys = repository::representation::get_all_representation_file_keys(conn)?;
+        Ok(thumbnail_keys
+            .chain(album_thumbnail_keys)
+            .chain(repr_keys)
+            .collect())
+    })
+    .await??;
+    let referenced_dash_dirs: HashSet<&str> = referenced_keys
+        .iter()
+        .filter_map(|key| dash_dir_of_key(key))
+        .collect();
+
+    let min_modified = Utc::now() - Duration::days(ORPHANED_FILE_MIN_AGE_DAYS);
+    let mut keys: Vec<String> = Vec::default();
+    let mut dash_dirs: BTreeSet<&str> = BTreeSet::default();
+    let mut recent_dash_dirs: HashSet<&str> = HashSet::default();
+    for object in &objects {
+        match dash_dir_of_key(&object.key) {
+            Some(dash_dir) => {
+                if object.last_modified > min_modified {
+                    recent_dash_dirs.insert(dash_dir);
+                } else if !referenced_dash_dirs.contains(dash_dir) {
+                    dash_dirs.insert(dash_dir);
+                }
+            }
+            None => {
+                if object.last_modified <= min_modified && !referenced_keys.contains(&object.key) {
+                    keys.push(object.key.clone());
+                }
+            }
+        }
+    }
+    let key_prefixes = dash_dirs
+        .into_iter()
+        .filter(|dash_dir| !recent_dash_dirs.contains(dash_dir))
+        .map(|dash_dir| dash_dir.to_owned())
+        .collect();
+    Ok(ObsoleteFiles { keys, key_prefixes })
+}
+
+/// `dash/<asset_id>` for keys returned by `storage_key::dash_file`
+fn dash_dir_of_key(key: &str) -> Option<&str> {
+    let rest = key.strip_prefix("dash/")?;
+    let asset_dir_len = rest.find('/')?;
+    Some(&key[.."dash/".len() + asset_dir_len])
+}
+
+#[tracing::instrument(skip(storage), level = "debug")]
+pub async fn delete_obsolete_files(storage: &Storage, files: &ObsoleteFiles) -> Result<()> {
+    for key in &files.keys {
+        storage.delete(key).await?;
+    }
+    for prefix in &files.key_prefixes {
+        storage.delete_prefix(prefix).await?;
+    }
+    Ok(())
+}
@@ -2,7 +2,6 @@ use std::ffi::OsString;
 
 use diesel::Connection;
 use eyre::{eyre, Context, Result};
-use tokio::sync::mpsc;
 use tracing::{error, instrument};
 
 use crate::{
@@ -23,7 +22,7 @@ use crate::{
     },
     processing::{
         commands::{FFmpeg, FFmpegIntoShaka, MpdGenerator, ShakaIntoFFmpeg, ShakaPackager},
-        process_control::ProcessControl,
+        process_control::{ProcessControlReceiver, ProgressSender},
         video::{
             ffmpeg::FFmpegTrait,
             ffmpeg_into_shaka::{FFmpegIntoShakaFFmpegTrait, FFmpegIntoShakaTrait},
@@ -213,18 +212,25 @@ pub async fn apply_package_video(conn: &mut PooledDbConn, op: CompletedPackageVi
     .await?
 }
 
-#[instrument(skip(pool, storage, process_control_recv), level = "debug")]
+/// Progress of the transcode (the part that takes long) is reported on `progress_send`
+#[instrument(
+    skip(pool, storage, process_control_recv, progress_send),
+    level = "debug"
+)]
 pub async fn perform_side_effects_package_video(
     pool: &DbPool,
     storage: &Storage,
     package_video: &PackageVideo,
     bin_paths: Option<&config::BinPaths>,
-    mut process_control_recv: mpsc::Receiver<ProcessControl>,
+    mut process_control_recv: ProcessControlReceiver,
+    progress_send: ProgressSender,
 ) -> Result<CompletedPackageVideo> {
     let asset_id = package_video.asset_id;
     let conn = pool.get().await?;
-    let asset_path = interact!(conn, move |conn| {
-        repository::asset::get_asset_path_on_disk(conn, asset_id)
+    let (asset_path, duration_ms) = interact!(conn, move |conn| {
+        let asset_path = repository::asset::get_asset_path_on_disk(conn, asset_id)?;
+        let duration_ms = repository::asset::get_video_duration_ms(conn, asset_id)?;
+        Ok((asset_path, duration_ms))
     })
     .await??;
 
@@ -254,7 +260,12 @@ pub async fn perform_side_effects_package_video(
         );
         Some(
             ffmpeg_into_shaka
-                .run_ffmpeg(ffmpeg_path, &mut process_control_recv)
+                .run_ffmpeg(
+                    ffmpeg_path,
+                    &mut process_control_recv,
+                    &progress_send,
+                    duration_ms,
+                )
                 .await?,
         )
     } else {
@@ -0,0 +1,34 @@
+use eyre::{Context, Result};
+
+use crate::{
+    interact,
+    model::{
+        repository::{self, db::PooledDbConn},
+        AssetId,
+    },
+};
+
+use super::obsolete_files::{asset_derived_files, ObsoleteFiles};
+
+/// Delete an Asset whose file has been missing for longer than the grace period,
+/// along with all files we created for it
+#[derive(Debug, Clone)]
+pub struct PurgeAsset {
+    pub asset_id: AssetId,
+}
+
+/// Unlike other operations, the database is changed before performing side effects
+/// (deleting the returned files with `delete_obsolete_files`):
+/// once the rows are gone nothing refers to the files anymore, so failing to delete them
+/// leaves garbage behind but never rows pointing to files that don't exist.
+#[tracing::instrument(skip(conn), level = "debug")]
+pub async fn apply_purge_asset(conn: &mut PooledDbConn, op: &PurgeAsset) -> Result<ObsoleteFiles> {
+    let asset_id = op.asset_id;
+    let files = asset_derived_files(conn, asset_id).await?;
+    interact!(conn, move |conn| {
+        repository::asset::delete_asset(conn, asset_id)
+    })
+    .await?
+    .wrap_err("error deleting Asset")?;
+    Ok(files)
+}
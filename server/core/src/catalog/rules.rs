@@ -15,7 +15,8 @@ use crate::{
     interact,
     model::{
         repository::{self, asset::AssetHasThumbnails, db::PooledDbConn},
-        AlbumId, AssetId, AssetThumbnail, ThumbnailFormat, ThumbnailType, VideoAsset,
+        AlbumId, AssetId, AssetThumbnail, FailedJobKind, ThumbnailFormat, ThumbnailType,
+        VideoAsset,
     },
     processing,
 };
@@ -26,7 +27,10 @@ use super::{
         convert_image::ConvertImage,
         create_album_thumbnail::CreateAlbumThumbnail,
         create_thumbnail::{CreateAssetThumbnail, ThumbnailToCreate},
+        geocode_asset::GeocodeAsset,
+        invalidate_asset::InvalidateAssetDerivedFiles,
         package_video::PackageVideo,
+        purge_asset::PurgeAsset,
     },
 };
 
@@ -156,13 +160,21 @@ pub async fn required_thumbnails_for_asset(
 pub async fn thumbnails_to_create(conn: &mut PooledDbConn) -> Result<Vec<CreateAssetThumbnail>> {
     // always create all thumbnails if any are missing for now
     let limit: Option<i64> = None;
-    let assets_missing_thumbnails: Vec<AssetHasThumbnails> = interact!(conn, move |conn| {
-        repository::asset::get_assets_with_missing_thumbnail(conn, limit)
-            .wrap_err("could not query for Assets with missing thumbnails")
+    let (assets_missing_thumbnails, failed_not_due) = interact!(conn, move |conn| {
+        let assets_missing_thumbnails: Vec<AssetHasThumbnails> =
+            repository::asset::get_assets_with_missing_thumbnail(conn, limit)
+                .wrap_err("could not query for Assets with missing thumbnails")?;
+        let failed_not_due = repository::failed_job::get_assets_with_failed_job_not_due(
+            conn,
+            FailedJobKind::Thumbnail,
+        )?;
+        Ok::<_, eyre::Report>((assets_missing_thumbnails, failed_not_due))
     })
     .await??;
+    let failed_not_due: HashSet<AssetId> = failed_not_due.into_iter().collect();
     Ok(assets_missing_thumbnails
         .into_iter()
+        .filter(|asset| !failed_not_due.contains(&asset.asset_id))
         .map(
             |AssetHasThumbnails {
                  asset_id,
@@ -175,6 +187,84 @@ pub async fn thumbnails_to_create(conn: &mut PooledDbConn) -> Result<Vec<CreateA
         .collect())
 }
 
+/// Reverse geocoding if the Asset has GPS coordinates that were not looked up yet
+#[instrument(skip(conn))]
+pub async fn required_geocoding_for_asset(
+    conn: &mut PooledDbConn,
+    asset_id: AssetId,
+) -> Result<Option<GeocodeAsset>> {
+    let coordinates = interact!(conn, move |conn| {
+        repository::asset_place::get_asset_to_geocode(conn, asset_id)
+    })
+    .await??;
+    Ok(coordinates.map(|coordinates| GeocodeAsset {
+        asset_id,
+        coordinates,
+    }))
+}
+
+#[instrument(skip(conn), level = "debug")]
+pub async fn geocoding_due(conn: &mut PooledDbConn) -> Result<Vec<GeocodeAsset>> {
+    let (assets_to_geocode, failed_not_due) = interact!(conn, move |conn| {
+        let assets_to_geocode = repository::asset_place::get_assets_to_geocode(conn)?;
+        let failed_not_due = repository::failed_job::get_assets_with_failed_job_not_due(
+            conn,
+            FailedJobKind::ReverseGeocode,
+        )?;
+        Ok::<_, eyre::Report>((assets_to_geocode, failed_not_due))
+    })
+    .await??;
+    let failed_not_due: HashSet<AssetId> = failed_not_due.into_iter().collect();
+    Ok(assets_to_geocode
+        .into_iter()
+        .filter(|(asset_id, _)| !failed_not_due.contains(asset_id))
+        .map(|(asset_id, coordinates)| GeocodeAsset {
+            asset_id,
+            coordinates,
+        })
+        .collect())
+}
+
+/// Whether work of `kind` for an Asset should be done, or if it failed before and
+/// should not be retried yet.
+/// Work is retried with exponential backoff (see [`crate::model::retry_delay`]),
+/// immediately when the Asset's file changes or when the external program that failed is
+/// upgraded, and never if the user chose to ignore the failure.
+#[instrument(skip(conn), level = "debug")]
+pub async fn is_retry_due(
+    conn: &mut PooledDbConn,
+    asset_id: AssetId,
+    kind: FailedJobKind,
+) -> Result<bool> {
+    interact!(conn, move |conn| {
+        repository::failed_job::is_failed_job_due(conn, asset_id, kind)
+    })
+    .await?
+}
+
+/// Everything created from an Asset's file is stale once the file changes.
+/// After applying this, the rules for new assets recreate what is needed.
+pub fn invalidate_for_modified_asset(asset_id: AssetId) -> InvalidateAssetDerivedFiles {
+    InvalidateAssetDerivedFiles { asset_id }
+}
+
+/// Assets whose file has been missing for longer than `grace_period`
+#[instrument(skip(conn), level = "debug")]
+pub async fn assets_to_purge(
+    conn: &mut PooledDbConn,
+    grace_period: std::time::Duration,
+) -> Result<Vec<PurgeAsset>> {
+    let missing_before = chrono::Utc::now() - chrono::Duration::from_std(grace_period)?;
+    let asset_ids = interact!(conn, move |conn| {
+        repository::asset::get_assets_missing_since_before(conn, missing_before)
+    })
+    .await??;
+    Ok(asset_ids
+        .into_iter()
+        .map(|asset_id| PurgeAsset { asset_id })
+        .collect())
+}
+
 fn missing_asset_thumbnails(have_thumbnails: Vec<AssetThumbnail>) -> Vec<ThumbnailToCreate> {
     let have_sm_sq_formats: HashSet<ThumbnailFormat> = have_thumbnails
         .iter()
@@ -379,7 +469,19 @@ pub async fn video_packaging_due(conn: &mut PooledDbConn) -> Result<Vec<PackageV
                 mpd_out_key: storage_key::mpd_manifest(asset.base.id),
             }
         });
-    Ok(package_orig_tasks.chain(reencode_tasks).collect())
+    let failed_not_due: HashSet<AssetId> = interact!(conn, move |conn| {
+        repository::failed_job::get_assets_with_failed_job_not_due(
+            conn,
+            FailedJobKind::VideoPackaging,
+        )
+    })
+    .await??
+    .into_iter()
+    .collect();
+    Ok(package_orig_tasks
+        .chain(reencode_tasks)
+        .filter(|package_video| !failed_not_due.contains(&package_video.asset_id))
+        .collect())
 }
 
 pub async fn image_conversion_due(conn: &mut PooledDbConn) -> Result<Vec<ConvertImage>> {
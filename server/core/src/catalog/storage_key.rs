@@ -4,8 +4,18 @@ use crate::model::{AlbumId, AssetId, ThumbnailFormat, ThumbnailType};
 
 use super::image_conversion_target::{ImageConversionTarget, ImageFormatTarget};
 
+/// prefixes of all keys returned by functions in this module.
+/// The data dir also holds other things (like the database), so files we created
+/// are only ever looked for below these.
+pub const PREFIXES: [&str; 4] = ["dash", "thumb", "image", "album_thumb"];
+
 pub fn dash_file(asset_id: AssetId, filename: fmt::Arguments) -> String {
-    format!("dash/{}/{}", asset_id.0, filename)
+    format!("{}/{}", dash_dir(asset_id), filename)
+}
+
+/// prefix of all keys returned by `dash_file`
+pub fn dash_dir(asset_id: AssetId) -> String {
+    format!("dash/{}", asset_id.0)
 }
 
 /// returned key is always in the set of keys returned by `dash_file`
@@ -1,12 +1,16 @@
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
-use color_eyre::eyre::{Context, Result};
+use chrono::NaiveTime;
+use color_eyre::eyre::{eyre, Context, Result};
+use ipnet::IpNet;
 use serde::Deserialize;
-use std::str::FromStr;
+use std::{net::IpAddr, str::FromStr, time::Duration};
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 struct TomlAssetDir {
     path: String,
     name: Option<String>,
+    watch: Option<bool>,
+    rescan_interval_secs: Option<u64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
@@ -24,22 +28,101 @@ struct TomlBinPaths {
     pub exiftool: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct TomlWatcher {
+    pub debounce_ms: Option<u64>,
+    pub fallback_rescan_interval_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct TomlMaintenance {
+    pub tick_secs: Option<u64>,
+    pub rescan_interval_secs: Option<u64>,
+    pub collect_work_interval_secs: Option<u64>,
+    pub vacuum_interval_secs: Option<u64>,
+    pub prune_orphaned_files_interval_secs: Option<u64>,
+    pub find_near_duplicates_interval_secs: Option<u64>,
+    pub detect_series_interval_secs: Option<u64>,
+    pub prune_jobs_interval_secs: Option<u64>,
+    pub job_retention_days: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct TomlAuth {
+    pub session_duration_days: Option<u64>,
+    pub secure_cookies: Option<bool>,
+    pub trusted_networks: Option<Vec<String>>,
+    pub trusted_interfaces: Option<Vec<String>>,
+    pub trusted_user: Option<String>,
+    pub trusted_proxies: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct TomlProcessingActor {
+    pub max_tasks: Option<usize>,
+    pub windows: Option<Vec<String>>,
+    pub nice: Option<i32>,
+    pub ionice_class: Option<String>,
+    pub ionice_level: Option<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct TomlProcessing {
+    #[serde(rename = "Thumbnail")]
+    pub thumbnail: Option<TomlProcessingActor>,
+    #[serde(rename = "ImageConversion")]
+    pub image_conversion: Option<TomlProcessingActor>,
+    #[serde(rename = "VideoPackaging")]
+    pub video_packaging: Option<TomlProcessingActor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct TomlS3Storage {
+    pub bucket: String,
+    pub endpoint: Option<String>,
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    pub key_prefix: Option<String>,
+    pub redirect_to_presigned_urls: Option<bool>,
+    pub presigned_url_expiry_secs: Option<u64>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 struct TomlConfig {
     #[serde(rename = "AssetDirs")]
     pub asset_dirs: Vec<TomlAssetDir>,
     #[serde(rename = "DataDir")]
     pub data_dir: TomlDataDir,
+    #[serde(rename = "S3Storage")]
+    pub s3_storage: Option<TomlS3Storage>,
     #[serde(rename = "BinPaths")]
     pub bin_paths: Option<TomlBinPaths>,
+    #[serde(rename = "Watcher")]
+    pub watcher: Option<TomlWatcher>,
+    #[serde(rename = "Maintenance")]
+    pub maintenance: Option<TomlMaintenance>,
+    #[serde(rename = "Processing")]
+    pub processing: Option<TomlProcessing>,
+    #[serde(rename = "Auth")]
+    pub auth: Option<TomlAuth>,
+    pub missing_asset_grace_period_days: Option<u64>,
+    pub reverse_geocoding: Option<bool>,
     pub address: Option<String>,
     pub port: Option<u16>,
+    pub cors_allowed_origins: Option<Vec<String>>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct AssetDir {
     pub path: PathBuf,
     pub name: Option<String>,
+    /// Watch the directory for changes using inotify (or the platform equivalent).
+    /// Network mounts often don't deliver change events, set this to false for those
+    /// and rely on periodic rescans instead.
+    pub watch: bool,
+    /// If set, the directory is fully rescanned in this interval regardless of `watch`
+    pub rescan_interval: Option<Duration>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -48,6 +131,25 @@ pub struct DataDir {
     pub name: Option<String>,
 }
 
+/// Store thumbnails, DASH files etc. in an S3 compatible bucket instead of the data dir.
+/// The database stays in the data dir.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct S3StorageConfig {
+    pub bucket: String,
+    /// For S3 compatible services other than AWS (MinIO etc.)
+    pub endpoint: Option<String>,
+    /// Unset values are taken from the usual AWS_* environment variables
+    pub region: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// Prepended to all keys, to share a bucket with other things
+    pub key_prefix: Option<String>,
+    /// Redirect clients to presigned URLs for thumbnails and video files
+    /// instead of proxying them through the server
+    pub redirect_to_presigned_urls: bool,
+    pub presigned_url_expiry: Duration,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct BinPaths {
     pub mpd_generator: Option<PathBuf>,
@@ -57,15 +159,283 @@ pub struct BinPaths {
     pub exiftool: Option<PathBuf>,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WatcherConfig {
+    /// How long a path must be quiet before changes to it are acted upon
+    pub debounce: Duration,
+    /// Rescan interval for directories that are not watched or could not be watched
+    /// and don't specify their own rescan_interval
+    pub fallback_rescan_interval: Duration,
+}
+
+impl Default for WatcherConfig {
+    fn default() -> Self {
+        Self {
+            debounce: Duration::from_secs(2),
+            fallback_rescan_interval: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+/// Intervals of the periodic maintenance tasks, None disables a task
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceConfig {
+    /// How often to check if any of the tasks are due
+    pub tick: Duration,
+    /// Full rescan of all asset roots, in case the watcher missed something
+    pub rescan_interval: Option<Duration>,
+    /// Collect the work required by the rules again, picking up work that was dropped
+    /// and failed jobs that are due for a retry
+    pub collect_work_interval: Option<Duration>,
+    /// VACUUM and optimize the database
+    pub vacuum_interval: Option<Duration>,
+    /// Delete files in the data dir that nothing in the database refers to
+    pub prune_orphaned_files_interval: Option<Duration>,
+    /// Compute missing perceptual hashes and group visually similar Assets
+    pub find_near_duplicates_interval: Option<Duration>,
+    /// Put photos taken in quick succession into series
+    pub detect_series_interval: Option<Duration>,
+    /// Delete finished, failed and cancelled jobs older than `job_retention`
+    pub prune_jobs_interval: Option<Duration>,
+    pub job_retention: Duration,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            tick: Duration::from_secs(60),
+            rescan_interval: Some(Duration::from_secs(24 * 60 * 60)),
+            collect_work_interval: Some(Duration::from_secs(60 * 60)),
+            vacuum_interval: Some(Duration::from_secs(7 * 24 * 60 * 60)),
+            prune_orphaned_files_interval: Some(Duration::from_secs(24 * 60 * 60)),
+            find_near_duplicates_interval: Some(Duration::from_secs(24 * 60 * 60)),
+            detect_series_interval: Some(Duration::from_secs(24 * 60 * 60)),
+            prune_jobs_interval: Some(Duration::from_secs(24 * 60 * 60)),
+            job_retention: Duration::from_secs(30 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthConfig {
+    /// Sessions expire this long after logging in
+    pub session_duration: Duration,
+    /// Only send the session cookie over HTTPS
+    pub secure_cookies: bool,
+    /// Requests from these networks don't have to log in and are treated as
+    /// `trusted_user`, unless they send a session cookie or token.
+    /// Includes the networks of `trusted_interfaces` at startup.
+    pub trusted_networks: Vec<IpNet>,
+    /// Name of the user for requests from `trusted_networks`,
+    /// always set if `trusted_networks` is not empty
+    pub trusted_user: Option<String>,
+    /// Reverse proxies whose X-Forwarded-For header is used to find the client address
+    pub trusted_proxies: Vec<IpNet>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            session_duration: Duration::from_secs(30 * 24 * 60 * 60),
+            secure_cookies: false,
+            trusted_networks: Vec::new(),
+            trusted_user: None,
+            trusted_proxies: Vec::new(),
+        }
+    }
+}
+
+fn auth_config(toml_auth: TomlAuth) -> Result<AuthConfig> {
+    let default = AuthConfig::default();
+    let mut trusted_networks = parse_networks(toml_auth.trusted_networks.unwrap_or_default())
+        .wrap_err("invalid entry in trusted_networks")?;
+    for interface in toml_auth.trusted_interfaces.unwrap_or_default() {
+        let networks = interface_networks(&interface)?;
+        if networks.is_empty() {
+            return Err(eyre!(
+                "network interface {} does not exist or has no addresses",
+                interface
+            ));
+        }
+        trusted_networks.extend(networks);
+    }
+    if !trusted_networks.is_empty() && toml_auth.trusted_user.is_none() {
+        return Err(eyre!(
+            "trusted_user must be set when there are trusted networks or interfaces"
+        ));
+    }
+    let trusted_proxies = parse_networks(toml_auth.trusted_proxies.unwrap_or_default())
+        .wrap_err("invalid entry in trusted_proxies")?;
+    Ok(AuthConfig {
+        session_duration: toml_auth
+            .session_duration_days
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60))
+            .unwrap_or(default.session_duration),
+        secure_cookies: toml_auth.secure_cookies.unwrap_or(default.secure_cookies),
+        trusted_networks,
+        trusted_user: toml_auth.trusted_user,
+        trusted_proxies,
+    })
+}
+
+/// CIDR notation, or a single address
+fn parse_networks(networks: Vec<String>) -> Result<Vec<IpNet>> {
+    networks
+        .into_iter()
+        .map(|network| {
+            IpNet::from_str(&network)
+                .or_else(|_| IpAddr::from_str(&network).map(IpNet::from))
+                .map_err(|_| eyre!("'{}' is not a network or address", network))
+        })
+        .collect()
+}
+
+/// The networks a local interface is connected to, e.g. the WireGuard subnet
+/// for a WireGuard interface
+fn interface_networks(interface: &str) -> Result<Vec<IpNet>> {
+    let addrs = nix::ifaddrs::getifaddrs().wrap_err("error listing network interfaces")?;
+    let mut networks = Vec::new();
+    for addr in addrs.filter(|addr| addr.interface_name == interface) {
+        let (Some(address), Some(netmask)) = (addr.address, addr.netmask) else {
+            continue;
+        };
+        let (address, netmask): (IpAddr, IpAddr) =
+            match (address.as_sockaddr_in(), netmask.as_sockaddr_in()) {
+                (Some(address), Some(netmask)) => (address.ip().into(), netmask.ip().into()),
+                _ => match (address.as_sockaddr_in6(), netmask.as_sockaddr_in6()) {
+                    (Some(address), Some(netmask)) => (address.ip().into(), netmask.ip().into()),
+                    _ => continue,
+                },
+            };
+        let network = IpNet::with_netmask(address, netmask)
+            .wrap_err_with(|| format!("invalid netmask for interface {}", interface))?;
+        networks.push(network.trunc());
+    }
+    Ok(networks)
+}
+
+/// A time of day range, spanning midnight if `end` is before `start`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            // start == end is the whole day
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = color_eyre::Report;
+
+    /// Parses "HH:MM-HH:MM"
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| eyre!("time window '{}' is not of the form HH:MM-HH:MM", s))?;
+        let parse = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .wrap_err_with(|| format!("invalid time in time window '{}'", s))
+        };
+        Ok(TimeWindow {
+            start: parse(start)?,
+            end: parse(end)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPriority {
+    /// level 0 (highest) to 7
+    Realtime(u8),
+    /// level 0 (highest) to 7
+    BestEffort(u8),
+    /// only gets disk time when nobody else needs it
+    Idle,
+}
+
+/// Applied to external programs (ffmpeg, shaka-packager) spawned by an actor
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProcessPriority {
+    /// -20 (highest) to 19 (lowest)
+    pub nice: Option<i32>,
+    pub io: Option<IoPriority>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessingActorConfig {
+    /// How many tasks may run at the same time
+    pub max_tasks: usize,
+    /// Tasks only run within these windows (local time),
+    /// outside of them running tasks are suspended. Empty means always.
+    pub windows: Vec<TimeWindow>,
+    pub priority: ProcessPriority,
+}
+
+impl ProcessingActorConfig {
+    fn with_max_tasks(max_tasks: usize) -> Self {
+        Self {
+            max_tasks,
+            windows: Vec::default(),
+            priority: ProcessPriority::default(),
+        }
+    }
+
+    pub fn is_in_window(&self, time: NaiveTime) -> bool {
+        self.windows.is_empty() || self.windows.iter().any(|window| window.contains(time))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessingConfig {
+    pub thumbnail: ProcessingActorConfig,
+    pub image_conversion: ProcessingActorConfig,
+    pub video_packaging: ProcessingActorConfig,
+}
+
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self {
+            thumbnail: ProcessingActorConfig::with_max_tasks(8),
+            image_conversion: ProcessingActorConfig::with_max_tasks(8),
+            video_packaging: ProcessingActorConfig::with_max_tasks(1),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Config {
     pub asset_dirs: Vec<AssetDir>,
     pub data_dir: DataDir,
+    pub s3_storage: Option<S3StorageConfig>,
     pub bin_paths: Option<BinPaths>,
+    pub watcher: WatcherConfig,
+    pub maintenance: MaintenanceConfig,
+    pub processing: ProcessingConfig,
+    pub auth: AuthConfig,
+    /// How long assets whose file has disappeared are kept before they
+    /// (and their album memberships etc.) are deleted for good
+    pub missing_asset_grace_period: Duration,
+    /// Look up the country, region and city of assets with GPS coordinates.
+    /// Place data is downloaded from geonames.org once per country as needed.
+    pub reverse_geocoding: bool,
     pub address: Option<String>,
     pub port: Option<u16>,
+    /// Origins other than our own that may make API requests, empty to disallow
+    /// cross-origin requests altogether
+    pub cors_allowed_origins: Vec<String>,
 }
 
+const DEFAULT_MISSING_ASSET_GRACE_PERIOD_DAYS: u64 = 30;
+const DEFAULT_PRESIGNED_URL_EXPIRY_SECS: u64 = 60 * 60;
+
 pub async fn read_config(path: &Path) -> Result<Config> {
     let toml_str = tokio::fs::read_to_string(path)
         .await
@@ -79,6 +449,8 @@ pub async fn read_config(path: &Path) -> Result<Config> {
             Ok(AssetDir {
                 path,
                 name: toml_value.name,
+                watch: toml_value.watch.unwrap_or(true),
+                rescan_interval: toml_value.rescan_interval_secs.map(Duration::from_secs),
             })
         })
         .collect::<Result<_>>()?;
@@ -89,6 +461,19 @@ pub async fn read_config(path: &Path) -> Result<Config> {
             name: toml_config.data_dir.name,
         }
     };
+    let s3_storage = toml_config.s3_storage.map(|s3| S3StorageConfig {
+        bucket: s3.bucket,
+        endpoint: s3.endpoint,
+        region: s3.region,
+        access_key_id: s3.access_key_id,
+        secret_access_key: s3.secret_access_key,
+        key_prefix: s3.key_prefix,
+        redirect_to_presigned_urls: s3.redirect_to_presigned_urls.unwrap_or(false),
+        presigned_url_expiry: Duration::from_secs(
+            s3.presigned_url_expiry_secs
+                .unwrap_or(DEFAULT_PRESIGNED_URL_EXPIRY_SECS),
+        ),
+    });
     let bin_paths = toml_config.bin_paths.map(|bin_paths| BinPaths {
         mpd_generator: bin_paths.mpd_generator.map(PathBuf::from),
         shaka_packager: bin_paths.shaka_packager.map(PathBuf::from),
@@ -96,13 +481,253 @@ pub async fn read_config(path: &Path) -> Result<Config> {
         ffprobe: bin_paths.ffprobe.map(PathBuf::from),
         exiftool: bin_paths.exiftool.map(PathBuf::from),
     });
+    let watcher = match toml_config.watcher {
+        Some(toml_watcher) => {
+            let default = WatcherConfig::default();
+            WatcherConfig {
+                debounce: toml_watcher
+                    .debounce_ms
+                    .map(Duration::from_millis)
+                    .unwrap_or(default.debounce),
+                fallback_rescan_interval: toml_watcher
+                    .fallback_rescan_interval_secs
+                    .map(Duration::from_secs)
+                    .unwrap_or(default.fallback_rescan_interval),
+            }
+        }
+        None => WatcherConfig::default(),
+    };
+    let maintenance = match toml_config.maintenance {
+        Some(toml_maintenance) => {
+            let default = MaintenanceConfig::default();
+            MaintenanceConfig {
+                tick: toml_maintenance
+                    .tick_secs
+                    // tokio's interval panics on 0
+                    .map(|secs| Duration::from_secs(secs.max(1)))
+                    .unwrap_or(default.tick),
+                rescan_interval: maintenance_interval(
+                    toml_maintenance.rescan_interval_secs,
+                    default.rescan_interval,
+                ),
+                collect_work_interval: maintenance_interval(
+                    toml_maintenance.collect_work_interval_secs,
+                    default.collect_work_interval,
+                ),
+                vacuum_interval: maintenance_interval(
+                    toml_maintenance.vacuum_interval_secs,
+                    default.vacuum_interval,
+                ),
+                prune_orphaned_files_interval: maintenance_interval(
+                    toml_maintenance.prune_orphaned_files_interval_secs,
+                    default.prune_orphaned_files_interval,
+                ),
+                find_near_duplicates_interval: maintenance_interval(
+                    toml_maintenance.find_near_duplicates_interval_secs,
+                    default.find_near_duplicates_interval,
+                ),
+                detect_series_interval: maintenance_interval(
+                    toml_maintenance.detect_series_interval_secs,
+                    default.detect_series_interval,
+                ),
+                prune_jobs_interval: maintenance_interval(
+                    toml_maintenance.prune_jobs_interval_secs,
+                    default.prune_jobs_interval,
+                ),
+                job_retention: toml_maintenance
+                    .job_retention_days
+                    .map(|days| Duration::from_secs(days * 24 * 60 * 60))
+                    .unwrap_or(default.job_retention),
+            }
+        }
+        None => MaintenanceConfig::default(),
+    };
+    let processing = {
+        let default = ProcessingConfig::default();
+        match toml_config.processing {
+            Some(toml_processing) => ProcessingConfig {
+                thumbnail: processing_actor_config(toml_processing.thumbnail, default.thumbnail)
+                    .wrap_err("invalid config for Processing.Thumbnail")?,
+                image_conversion: processing_actor_config(
+                    toml_processing.image_conversion,
+                    default.image_conversion,
+                )
+                .wrap_err("invalid config for Processing.ImageConversion")?,
+                video_packaging: processing_actor_config(
+                    toml_processing.video_packaging,
+                    default.video_packaging,
+                )
+                .wrap_err("invalid config for Processing.VideoPackaging")?,
+            },
+            None => default,
+        }
+    };
+    let auth = match toml_config.auth {
+        Some(toml_auth) => auth_config(toml_auth).wrap_err("invalid config for Auth")?,
+        None => AuthConfig::default(),
+    };
+    let missing_asset_grace_period = Duration::from_secs(
+        toml_config
+            .missing_asset_grace_period_days
+            .unwrap_or(DEFAULT_MISSING_ASSET_GRACE_PERIOD_DAYS)
+            * 24
+            * 60
+            * 60,
+    );
+    let reverse_geocoding = toml_config.reverse_geocoding.unwrap_or(true);
     let address = toml_config.address;
     let port: Option<u16> = toml_config.port;
+    let cors_allowed_origins = toml_config.cors_allowed_origins.unwrap_or_default();
     Ok(Config {
         asset_dirs,
         data_dir,
+        s3_storage,
         bin_paths,
+        watcher,
+        maintenance,
+        processing,
+        auth,
+        missing_asset_grace_period,
+        reverse_geocoding,
         address,
         port,
+        cors_allowed_origins,
+    })
+}
+
+/// An interval of 0 disables the task
+fn maintenance_interval(secs: Option<u64>, default: Option<Duration>) -> Option<Duration> {
+    match secs {
+        Some(0) => None,
+        Some(secs) => Some(Duration::from_secs(secs)),
+        None => default,
+    }
+}
+
+fn processing_actor_config(
+    toml: Option<TomlProcessingActor>,
+    default: ProcessingActorConfig,
+) -> Result<ProcessingActorConfig> {
+    let Some(toml) = toml else {
+        return Ok(default);
+    };
+    let windows = toml
+        .windows
+        .unwrap_or_default()
+        .iter()
+        .map(|window| window.parse())
+        .collect::<Result<Vec<TimeWindow>>>()?;
+    if let Some(nice) = toml.nice {
+        if !(-20..=19).contains(&nice) {
+            return Err(eyre!("nice must be between -20 and 19"));
+        }
+    }
+    let level = toml.ionice_level.unwrap_or(4);
+    if level > 7 {
+        return Err(eyre!("ionice_level must be between 0 and 7"));
+    }
+    let io = match toml.ionice_class.as_deref() {
+        None => None,
+        Some("realtime") => Some(IoPriority::Realtime(level)),
+        Some("best-effort") => Some(IoPriority::BestEffort(level)),
+        Some("idle") => Some(IoPriority::Idle),
+        Some(other) => {
+            return Err(eyre!(
+                "unknown ionice_class '{}', must be one of realtime, best-effort, idle",
+                other
+            ))
+        }
+    };
+    Ok(ProcessingActorConfig {
+        max_tasks: toml.max_tasks.unwrap_or(default.max_tasks).max(1),
+        windows,
+        priority: ProcessPriority {
+            nice: toml.nice,
+            io,
+        },
     })
 }
+
+#[test]
+fn time_window_contains() {
+    let time = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+    let window = |start: &str, end: &str| TimeWindow {
+        start: time(start),
+        end: time(end),
+    };
+
+    let day = window("09:00", "17:00");
+    assert!(!day.contains(time("08:59")));
+    assert!(day.contains(time("09:00")));
+    assert!(day.contains(time("16:59")));
+    assert!(!day.contains(time("17:00")));
+
+    let night = window("22:00", "06:00");
+    assert!(!night.contains(time("21:59")));
+    assert!(night.contains(time("22:00")));
+    assert!(night.contains(time("00:00")));
+    assert!(night.contains(time("05:59")));
+    assert!(!night.contains(time("06:00")));
+    assert!(!night.contains(time("12:00")));
+
+    let whole_day = window("03:00", "03:00");
+    for t in ["00:00", "02:59", "03:00", "12:00", "23:59"] {
+        assert!(whole_day.contains(time(t)), "{} not in {:?}", t, whole_day);
+    }
+}
+
+#[test]
+fn time_window_from_str() {
+    let time = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+    assert_eq!(
+        TimeWindow::from_str("22:00-06:30").unwrap(),
+        TimeWindow {
+            start: time("22:00"),
+            end: time("06:30")
+        }
+    );
+    assert_eq!(
+        TimeWindow::from_str(" 01:00 - 05:00 ").unwrap(),
+        TimeWindow {
+            start: time("01:00"),
+            end: time("05:00")
+        }
+    );
+    for invalid in [
+        "",
+        "22:00",
+        "22:00-",
+        "-06:00",
+        "22:00 06:00",
+        "24:00-06:00",
+        "22:60-06:00",
+        "10pm-6am",
+        "22:00-06:00-08:00",
+    ] {
+        assert!(
+            TimeWindow::from_str(invalid).is_err(),
+            "'{}' should not parse",
+            invalid
+        );
+    }
+}
+
+#[test]
+fn no_windows_means_always_in_window() {
+    let time = |s: &str| NaiveTime::parse_from_str(s, "%H:%M").unwrap();
+    let mut config = ProcessingActorConfig::with_max_tasks(1);
+    assert!(config.is_in_window(time("12:00")));
+    config.windows = vec![
+        TimeWindow {
+            start: time("01:00"),
+            end: time("02:00"),
+        },
+        TimeWindow {
+            start: time("23:00"),
+            end: time("00:30"),
+        },
+    ];
+    assert!(config.is_in_window(time("01:30")));
+    assert!(config.is_in_window(time("00:15")));
+    assert!(!config.is_in_window(time("12:00")));
+}
@@ -1,2 +1,6 @@
+mod s3_storage;
 pub mod scheduler;
 pub mod storage;
+
+#[cfg(test)]
+mod test;
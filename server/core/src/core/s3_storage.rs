@@ -0,0 +1,311 @@
+use std::{ops::Range, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use eyre::{Context, Result};
+use futures::{StreamExt, TryStreamExt};
+use object_store::{
+    aws::AmazonS3Builder, buffered::BufWriter, path::Path as ObjectPath, signer::Signer,
+    GetOptions, GetRange, GetResult, ObjectMeta, ObjectStore,
+};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tracing::instrument;
+
+use crate::config::S3StorageConfig;
+
+use super::storage::{
+    CommandOutputFile, StorageCommandOutput, StorageObject, StorageProvider, StorageReadError,
+};
+
+/// Stores objects in an S3 compatible bucket (AWS, MinIO, Garage...).
+/// Command outputs are written to a local scratch directory and uploaded on
+/// `flush_to_storage`.
+#[derive(Debug, Clone)]
+pub struct S3Storage {
+    store: Arc<dyn ObjectStore>,
+    signer: Option<Arc<dyn Signer>>,
+    /// prepended to every key, so that the bucket can be shared
+    key_prefix: Option<String>,
+    /// None to proxy objects through the server instead of handing out presigned URLs
+    presigned_url_expiry: Option<Duration>,
+    /// Command output files are laid out like their keys below this directory,
+    /// so that commands writing more than one file (shaka-packager's media_info)
+    /// find them next to each other
+    scratch_dir: PathBuf,
+}
+
+#[derive(Debug)]
+pub struct S3OutputFile {
+    path: PathBuf,
+    store: Arc<dyn ObjectStore>,
+    location: ObjectPath,
+}
+
+impl S3Storage {
+    /// Anything left in `scratch_dir` is deleted
+    pub fn new(config: &S3StorageConfig, scratch_dir: PathBuf) -> Result<S3Storage> {
+        let mut builder = AmazonS3Builder::from_env().with_bucket_name(&config.bucket);
+        if let Some(endpoint) = &config.endpoint {
+            builder = builder
+                .with_endpoint(endpoint)
+                .with_allow_http(endpoint.starts_with("http://"));
+        }
+        if let Some(region) = &config.region {
+            builder = builder.with_region(region);
+        }
+        if let Some(access_key_id) = &config.access_key_id {
+            builder = builder.with_access_key_id(access_key_id);
+        }
+        if let Some(secret_access_key) = &config.secret_access_key {
+            builder = builder.with_secret_access_key(secret_access_key);
+        }
+        let s3 = Arc::new(builder.build().wrap_err("error configuring S3 storage")?);
+        let presigned_url_expiry = config
+            .redirect_to_presigned_urls
+            .then_some(config.presigned_url_expiry);
+        Self::with_store(
+            s3.clone(),
+            Some(s3),
+            config.key_prefix.clone(),
+            presigned_url_expiry,
+            scratch_dir,
+        )
+    }
+
+    /// For tests against stores other than S3, like `object_store::memory::InMemory`
+    pub fn with_store(
+        store: Arc<dyn ObjectStore>,
+        signer: Option<Arc<dyn Signer>>,
+        key_prefix: Option<String>,
+        presigned_url_expiry: Option<Duration>,
+        scratch_dir: PathBuf,
+    ) -> Result<S3Storage> {
+        match std::fs::remove_dir_all(&scratch_dir) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err).wrap_err("error clearing S3 scratch directory"),
+        }
+        std::fs::create_dir_all(&scratch_dir).wrap_err("error creating S3 scratch directory")?;
+        Ok(S3Storage {
+            store,
+            signer,
+            key_prefix: key_prefix.map(|prefix| prefix.trim_matches('/').to_owned()),
+            presigned_url_expiry,
+            scratch_dir,
+        })
+    }
+
+    fn location(&self, key: &str) -> ObjectPath {
+        match &self.key_prefix {
+            Some(prefix) => ObjectPath::from(format!("{}/{}", prefix, key)),
+            None => ObjectPath::from(key),
+        }
+    }
+
+    async fn get(
+        &self,
+        key: &str,
+        options: GetOptions,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>, StorageReadError> {
+        let get_result: GetResult = match self.store.get_opts(&self.location(key), options).await {
+            Ok(get_result) => get_result,
+            Err(object_store::Error::NotFound { .. }) => {
+                return Err(StorageReadError::FileNotFound(key.to_owned()))
+            }
+            Err(err) => {
+                return Err(eyre::Report::from(err)
+                    .wrap_err("error reading object")
+                    .into())
+            }
+        };
+        let stream = get_result
+            .into_stream()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err));
+        Ok(Box::new(tokio_util::io::StreamReader::new(stream)))
+    }
+
+    fn key_of_location<'a>(&self, location: &'a ObjectPath) -> Option<&'a str> {
+        let location: &str = location.as_ref();
+        match &self.key_prefix {
+            Some(prefix) => location
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_prefix('/')),
+            None => Some(location),
+        }
+    }
+}
+
+#[async_trait]
+impl StorageProvider for S3Storage {
+    #[instrument(err, skip(self), level = "trace")]
+    async fn open_read_stream(
+        &self,
+        key: &str,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>, StorageReadError> {
+        self.get(key, GetOptions::default()).await
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn open_read_range(
+        &self,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>, StorageReadError> {
+        let range = GetRange::Bounded(range.start as usize..range.end as usize);
+        let options = GetOptions {
+            range: Some(range),
+            ..Default::default()
+        };
+        self.get(key, options).await
+    }
+
+    /// The object is only created once the writer is shut down
+    #[instrument(err, skip(self), level = "trace")]
+    async fn open_write_stream(&self, key: &str) -> Result<Box<dyn AsyncWrite + Send + Unpin>> {
+        Ok(Box::new(BufWriter::new(
+            self.store.clone(),
+            self.location(key),
+        )))
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn exists(&self, key: &str) -> Result<bool> {
+        match self.store.head(&self.location(key)).await {
+            Ok(_) => Ok(true),
+            Err(object_store::Error::NotFound { .. }) => Ok(false),
+            Err(err) => Err(err).wrap_err("error checking if object exists"),
+        }
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn metadata(&self, key: &str) -> Result<StorageObject, StorageReadError> {
+        match self.store.head(&self.location(key)).await {
+            Ok(meta) => Ok(StorageObject {
+                key: key.to_owned(),
+                size: meta.size as u64,
+                last_modified: meta.last_modified,
+            }),
+            Err(object_store::Error::NotFound { .. }) => {
+                Err(StorageReadError::FileNotFound(key.to_owned()))
+            }
+            Err(err) => Err(eyre::Report::from(err)
+                .wrap_err("error getting object metadata")
+                .into()),
+        }
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn delete(&self, key: &str) -> Result<()> {
+        match self.store.delete(&self.location(key)).await {
+            Ok(()) => Ok(()),
+            Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err).wrap_err("error deleting object"),
+        }
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn delete_prefix(&self, prefix: &str) -> Result<()> {
+        let prefix = self.location(prefix);
+        let locations = self
+            .store
+            .list(Some(&prefix))
+            .map_ok(|meta| meta.location)
+            .boxed();
+        self.store
+            .delete_stream(locations)
+            .try_for_each(|_| futures::future::ok(()))
+            .await
+            .wrap_err("error deleting objects")
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn list_prefix(&self, prefix: &str) -> Result<Vec<StorageObject>> {
+        let prefix = self.location(prefix);
+        let objects: Vec<ObjectMeta> = self
+            .store
+            .list(Some(&prefix))
+            .try_collect()
+            .await
+            .wrap_err("error listing objects")?;
+        Ok(objects
+            .iter()
+            .filter_map(|meta| {
+                self.key_of_location(&meta.location)
+                    .map(|key| StorageObject {
+                        key: key.to_owned(),
+                        size: meta.size as u64,
+                        last_modified: meta.last_modified,
+                    })
+            })
+            .collect())
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn new_command_out_file(&self, key: &str) -> Result<CommandOutputFile> {
+        let path = self.scratch_dir.join(key);
+        if let Some(parent) = &path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .wrap_err("could not create directory")?;
+        }
+        Ok(S3OutputFile {
+            path,
+            store: self.store.clone(),
+            location: self.location(key),
+        }
+        .into())
+    }
+
+    async fn local_path(&self, _key: &str) -> Result<Option<PathBuf>> {
+        Ok(None)
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn presigned_url(&self, key: &str) -> Result<Option<String>> {
+        let (Some(signer), Some(expires_in)) = (&self.signer, self.presigned_url_expiry) else {
+            return Ok(None);
+        };
+        let url = signer
+            .signed_url(http::Method::GET, &self.location(key), expires_in)
+            .await
+            .wrap_err("error creating presigned URL")?;
+        Ok(Some(url.to_string()))
+    }
+}
+
+#[async_trait]
+impl StorageCommandOutput for S3OutputFile {
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn size(&self) -> Result<u64> {
+        let file_meta = tokio::fs::metadata(&self.path)
+            .await
+            .wrap_err("error getting file metadata")?;
+        Ok(file_meta.len())
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn flush_to_storage(self) -> Result<()> {
+        let mut file = tokio::fs::File::open(&self.path)
+            .await
+            .wrap_err("error opening command output file")?;
+        let mut writer = BufWriter::new(self.store.clone(), self.location.clone());
+        tokio::io::copy(&mut file, &mut writer)
+            .await
+            .wrap_err("error uploading command output file")?;
+        writer
+            .shutdown()
+            .await
+            .wrap_err("error uploading command output file")?;
+        Ok(())
+    }
+}
+
+impl Drop for S3OutputFile {
+    fn drop(&mut self) {
+        // uploaded or not needed anymore
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
@@ -1,29 +1,60 @@
-use eyre::Result;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use camino::Utf8PathBuf as PathBuf;
+use chrono::{DateTime, Utc};
+use eyre::{eyre, Result};
 use futures::{stream::FuturesUnordered, TryStreamExt};
 use strum::EnumCount;
-use tokio::sync::{mpsc, oneshot};
+use tokio::{
+    sync::{mpsc, oneshot},
+    task::JoinHandle,
+    time::MissedTickBehavior,
+};
 use tracing::instrument;
 
 use crate::{
     actor::{
+        geocoding::{start_geocoding_actor, GeocodingActorHandle, MsgFromGeocoding},
         image_conversion::{
             start_image_conversion_actor, ImageConversionActorHandle, MsgFromImageConversion,
         },
         indexing::{IndexingActorHandle, MsgFromIndexing},
         thumbnail::{
-            start_thumbnail_actor, MsgFromThumbnail, ThumbnailActorHandle, ThumbnailTaskResult,
+            start_thumbnail_actor, MsgFromThumbnail, ThumbnailActorHandle, ThumbnailTaskMsg,
+            ThumbnailTaskResult,
         },
         video_packaging::{
             start_video_packaging_actor, MsgFromVideoPackaging, VideoPackagingActorHandle,
+            VideoPackagingTaskMsg,
         },
+        watcher::{MsgFromWatcher, WatcherActorHandle},
         TaskError,
     },
-    catalog::rules,
-    config::Config,
+    catalog::{
+        operation::{
+            invalidate_asset::apply_invalidate_asset_derived_files,
+            obsolete_files::{delete_obsolete_files, orphaned_files},
+            purge_asset::apply_purge_asset,
+        },
+        rules,
+    },
+    config::{BinPaths, Config, ProcessingActorConfig},
     interact,
     model::{
-        repository::{self, db::DbPool},
-        AssetId, AssetRootDirId,
+        repository::{
+            self,
+            asset_series::SeriesDetectionCandidate,
+            db::{DbPool, PooledDbConn},
+        },
+        AssetId, AssetRootDirId, FailedJobId, FailedJobKind, Job, JobId, JobKind, JobState,
+        JobSubject,
+    },
+    processing::{
+        near_duplicates, reconcile::ReconcileResult, series_detection,
+        series_detection::SeriesCandidate, startup_self_check,
     },
 };
 
@@ -44,6 +75,16 @@ pub enum SchedulerMessage {
 #[derive(Debug)]
 pub enum UserRequest {
     ReindexAssetRoot(AssetRootDirId),
+    /// Does nothing if the job is not queued or running
+    CancelJob(JobId),
+    /// Run a job that is done again, redoing whatever of its work is still required
+    RetryJob(JobId),
+    /// Retry the work that failed now, regardless of when it would be retried automatically
+    RetryFailedJob(FailedJobId),
+    /// Search for near duplicates now instead of waiting for the maintenance task
+    FindNearDuplicates,
+    /// Look for new series now instead of waiting for the maintenance task
+    DetectSeries,
 }
 
 #[derive(Debug, Clone)]
@@ -58,11 +99,60 @@ enum Actors {
     Thumbnail,
     ImageConversion,
     VideoPackaging,
+    Geocoding,
+}
+
+impl Actors {
+    /// Actors that can be paused and have a `ProcessingActorConfig`
+    const PROCESSING: [Actors; 3] = [
+        Actors::Thumbnail,
+        Actors::ImageConversion,
+        Actors::VideoPackaging,
+    ];
 }
 
 #[derive(Debug, Default)]
 struct ActorState {
     has_dropped_msgs: bool,
+    /// has running or queued tasks
+    is_busy: bool,
+    paused_by_user: bool,
+    outside_processing_window: bool,
+    /// what we last told the actor
+    is_paused: bool,
+}
+
+/// Periodic tasks run on `SchedulerMessage::Timer`, each with its own interval
+/// from `MaintenanceConfig`
+#[derive(Debug, Copy, Clone, strum::EnumCount)]
+#[repr(usize)]
+enum MaintenanceTask {
+    RescanAssetRoots,
+    CollectWork,
+    Vacuum,
+    PruneOrphanedFiles,
+    FindNearDuplicates,
+    DetectSeries,
+    PruneJobs,
+}
+
+impl MaintenanceTask {
+    const ALL: [MaintenanceTask; MaintenanceTask::COUNT] = [
+        MaintenanceTask::RescanAssetRoots,
+        MaintenanceTask::CollectWork,
+        MaintenanceTask::Vacuum,
+        MaintenanceTask::PruneOrphanedFiles,
+        MaintenanceTask::FindNearDuplicates,
+        MaintenanceTask::DetectSeries,
+        MaintenanceTask::PruneJobs,
+    ];
+}
+
+#[derive(Debug)]
+struct MaintenanceState {
+    last_run: Instant,
+    /// Tasks that run in the background, so that they don't run twice at the same time
+    running: Option<JoinHandle<()>>,
 }
 
 struct Scheduler {
@@ -75,18 +165,24 @@ struct Scheduler {
     actor_did_shutdown_recvs: Option<Vec<oneshot::Receiver<()>>>,
 
     actor_states: [ActorState; Actors::COUNT],
+    maintenance_states: [MaintenanceState; MaintenanceTask::COUNT],
 
     indexing_actor: IndexingActorHandle,
+    watcher_actor: WatcherActorHandle,
     thumbnail_actor: ThumbnailActorHandle,
     video_packaging_actor: VideoPackagingActorHandle,
     image_conversion_actor: ImageConversionActorHandle,
+    /// None if reverse geocoding is disabled in the config
+    geocoding_actor: Option<GeocodingActorHandle>,
 }
 
 impl SchedulerHandle {
+    /// `geodata_path`: database for the place data downloaded for reverse geocoding
     pub fn new(
         db_pool: DbPool,
         storage: Storage,
         config: Config,
+        geodata_path: PathBuf,
         did_shutdown_send: oneshot::Sender<()>,
     ) -> Self {
         // TODO: indexign shutdown
@@ -95,11 +191,16 @@ impl SchedulerHandle {
         let indexing_actor =
             IndexingActorHandle::new(db_pool.clone(), config.clone(), from_indexing_send);
 
+        let (from_watcher_send, from_watcher_recv) = mpsc::unbounded_channel();
+        let watcher_actor =
+            WatcherActorHandle::new(db_pool.clone(), config.clone(), from_watcher_send);
+
         let (thumbnail_did_shutdown_send, thumbnail_did_shutdown_recv) = oneshot::channel();
         let (from_thumbnail_send, from_thumbnail_recv) = mpsc::unbounded_channel();
         let thumbnail_actor = start_thumbnail_actor(
             db_pool.clone(),
             storage.clone(),
+            config.clone(),
             thumbnail_did_shutdown_send,
             from_thumbnail_send,
         );
@@ -120,47 +221,77 @@ impl SchedulerHandle {
         let image_conversion_actor = start_image_conversion_actor(
             db_pool.clone(),
             storage.clone(),
+            config.clone(),
             image_conversion_did_shutdown_send,
             from_image_conversion_send,
         );
 
+        let mut actor_did_shutdown_recvs = vec![
+            thumbnail_did_shutdown_recv,
+            video_did_shutdown_recv,
+            image_conversion_did_shutdown_recv,
+        ];
+
+        let (from_geocoding_send, from_geocoding_recv) = mpsc::unbounded_channel();
+        let geocoding_actor = if config.reverse_geocoding {
+            let (geocoding_did_shutdown_send, geocoding_did_shutdown_recv) = oneshot::channel();
+            actor_did_shutdown_recvs.push(geocoding_did_shutdown_recv);
+            Some(start_geocoding_actor(
+                db_pool.clone(),
+                geodata_path,
+                geocoding_did_shutdown_send,
+                from_geocoding_send,
+            ))
+        } else {
+            None
+        };
+
         let (send, recv) = mpsc::channel(1000);
+        tokio::spawn(send_timer_ticks(send.clone(), config.maintenance.tick));
         let sched = Scheduler {
             db_pool,
             storage,
             config,
             waiting_for_shutdown: false,
             did_shutdown_send: Some(did_shutdown_send),
-            actor_did_shutdown_recvs: Some(vec![
-                thumbnail_did_shutdown_recv,
-                video_did_shutdown_recv,
-                image_conversion_did_shutdown_recv,
-            ]),
+            actor_did_shutdown_recvs: Some(actor_did_shutdown_recvs),
             actor_states: Default::default(),
+            // startup does everything the maintenance tasks do, so nothing is due right away
+            maintenance_states: std::array::from_fn(|_| MaintenanceState {
+                last_run: Instant::now(),
+                running: None,
+            }),
             indexing_actor: indexing_actor.clone(),
+            watcher_actor,
             thumbnail_actor: thumbnail_actor.clone(),
             video_packaging_actor: video_packaging_actor.clone(),
             image_conversion_actor: image_conversion_actor.clone(),
+            geocoding_actor,
         };
         tokio::spawn(run_scheduler(
             sched,
             recv,
             from_indexing_recv,
+            from_watcher_recv,
             from_thumbnail_recv,
             from_video_packaging_recv,
             from_image_conversion_recv,
+            from_geocoding_recv,
         ));
         Self { send }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn run_scheduler(
     mut sched: Scheduler,
     mut recv: mpsc::Receiver<SchedulerMessage>,
     mut indexing_recv: mpsc::UnboundedReceiver<MsgFromIndexing>,
+    mut watcher_recv: mpsc::UnboundedReceiver<MsgFromWatcher>,
     mut thumbnail_recv: mpsc::UnboundedReceiver<MsgFromThumbnail>,
     mut video_packaging_recv: mpsc::UnboundedReceiver<MsgFromVideoPackaging>,
     mut image_conversion_recv: mpsc::UnboundedReceiver<MsgFromImageConversion>,
+    mut geocoding_recv: mpsc::UnboundedReceiver<MsgFromGeocoding>,
 ) {
     loop {
         tokio::select! {
@@ -172,6 +303,11 @@ async fn run_scheduler(
                     tracing::error!(?err, "error in scheduler");
                 }
             }
+            Some(watcher_msg) = watcher_recv.recv() => {
+                if let Err(err) = sched.on_watcher_msg(watcher_msg).await {
+                    tracing::error!(?err, "error in scheduler");
+                }
+            }
             Some(thumbnail_msg) = thumbnail_recv.recv() => {
                 if let Err(err) = sched.on_thumbnail_msg(thumbnail_msg).await {
                     tracing::error!(?err, "error in scheduler");
@@ -187,6 +323,11 @@ async fn run_scheduler(
                     tracing::error!(?err, "error in scheduler");
                 }
             }
+            Some(geocoding_msg) = geocoding_recv.recv() => {
+                if let Err(err) = sched.on_geocoding_msg(geocoding_msg).await {
+                    tracing::error!(?err, "error in scheduler");
+                }
+            }
             else => {
                 break;
             }
@@ -204,10 +345,21 @@ impl Scheduler {
                 queued_tasks,
             } => {
                 let is_idle = running_tasks == 0 && queued_tasks == 0;
+                actor_state.is_busy = !is_idle;
                 if is_idle && actor_state.has_dropped_msgs {
                     actor_state.has_dropped_msgs = false;
-                    // TODO: what do we do here. reindex all? for other actors we look up in db
-                    // what work they can do but this one is different
+                    // unlike other actors there is nothing in the db telling us what work was
+                    // dropped (files reported by the watcher for example), so rescan everything
+                    let conn = self.db_pool.get().await?;
+                    let asset_roots = interact!(conn, move |conn| {
+                        repository::asset_root_dir::get_asset_roots(conn)
+                    })
+                    .await??;
+                    for asset_root in asset_roots {
+                        self.indexing_actor
+                            .msg_index_asset_root(asset_root.id)
+                            .expect("receiver must be alive");
+                    }
                 }
             }
             MsgFromIndexing::DroppedMessage => {
@@ -218,6 +370,24 @@ impl Scheduler {
                     tracing::error!(?err, "error in on_new_asset_indexed");
                 }
             }
+            MsgFromIndexing::AssetModified(asset_id) => {
+                if let Err(err) = self.on_asset_modified(asset_id).await {
+                    tracing::error!(?err, "error in on_asset_modified");
+                }
+            }
+            MsgFromIndexing::Reconciled {
+                root_dir_id,
+                result,
+            } => {
+                if result != ReconcileResult::default() {
+                    tracing::info!(%root_dir_id, ?result, "Checked for removed files");
+                }
+                tokio::spawn(purge_missing_assets(
+                    self.db_pool.clone(),
+                    self.storage.clone(),
+                    self.config.missing_asset_grace_period,
+                ));
+            }
             MsgFromIndexing::IndexingError {
                 root_dir_id,
                 path,
@@ -240,21 +410,54 @@ impl Scheduler {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn on_watcher_msg(&mut self, msg: MsgFromWatcher) -> Result<()> {
+        match msg {
+            MsgFromWatcher::PathsChanged { root_dir_id, paths } => {
+                self.indexing_actor
+                    .msg_index_paths(root_dir_id, paths)
+                    .expect("receiver must be alive");
+            }
+            MsgFromWatcher::PathsRemoved { root_dir_id, paths } => {
+                self.indexing_actor
+                    .msg_reconcile_paths(root_dir_id, paths)
+                    .expect("receiver must be alive");
+            }
+            MsgFromWatcher::RescanRequired { root_dir_id } => {
+                self.indexing_actor
+                    .msg_index_asset_root(root_dir_id)
+                    .expect("receiver must be alive");
+            }
+            MsgFromWatcher::WatchError {
+                root_dir_id,
+                report,
+            } => {
+                tracing::warn!(?root_dir_id, %report, "error watching asset roots");
+            }
+        }
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     async fn on_new_asset_indexed(&self, asset_id: AssetId) -> Result<()> {
         let mut conn = self.db_pool.get().await.unwrap();
-        let thumbnails_required = rules::required_thumbnails_for_asset(&mut conn, asset_id).await?;
-        if !thumbnails_required.thumbnails.is_empty() {
-            self.thumbnail_actor
-                .msg_create_asset_thumbnail(thumbnails_required)
-                .expect("receiver must be alive");
+        if rules::is_retry_due(&mut conn, asset_id, FailedJobKind::Thumbnail).await? {
+            let thumbnails_required =
+                rules::required_thumbnails_for_asset(&mut conn, asset_id).await?;
+            if !thumbnails_required.thumbnails.is_empty() {
+                self.thumbnail_actor
+                    .msg_create_asset_thumbnail(thumbnails_required)
+                    .expect("receiver must be alive");
+            }
         }
-        let video_packaging_required =
-            rules::required_video_packaging_for_asset(&mut conn, asset_id).await?;
-        for vid_pack in video_packaging_required {
-            self.video_packaging_actor
-                .msg_package_video(vid_pack)
-                .expect("receiver must be alive");
+        if rules::is_retry_due(&mut conn, asset_id, FailedJobKind::VideoPackaging).await? {
+            let video_packaging_required =
+                rules::required_video_packaging_for_asset(&mut conn, asset_id).await?;
+            for vid_pack in video_packaging_required {
+                self.video_packaging_actor
+                    .msg_package_video(vid_pack)
+                    .expect("receiver must be alive");
+            }
         }
 
         let image_conversion_required =
@@ -264,9 +467,32 @@ impl Scheduler {
                 .msg_convert_image(img_convert)
                 .expect("receiver must be alive");
         }
+
+        if let Some(geocoding_actor) = &self.geocoding_actor {
+            if rules::is_retry_due(&mut conn, asset_id, FailedJobKind::ReverseGeocode).await? {
+                if let Some(geocode_asset) =
+                    rules::required_geocoding_for_asset(&mut conn, asset_id).await?
+                {
+                    geocoding_actor
+                        .msg_geocode_asset(geocode_asset)
+                        .expect("receiver must be alive");
+                }
+            }
+        }
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn on_asset_modified(&self, asset_id: AssetId) -> Result<()> {
+        let mut conn = self.db_pool.get().await?;
+        let op = rules::invalidate_for_modified_asset(asset_id);
+        let obsolete_files = apply_invalidate_asset_derived_files(&mut conn, &op).await?;
+        if let Err(err) = delete_obsolete_files(&self.storage, &obsolete_files).await {
+            tracing::warn!(%asset_id, ?err, "error deleting stale files of modified Asset");
+        }
+        self.on_new_asset_indexed(asset_id).await
+    }
+
     #[tracing::instrument(skip(self))]
     async fn on_thumbnail_msg(&mut self, msg: MsgFromThumbnail) -> Result<()> {
         let actor_state = &mut self.actor_states[Actors::Thumbnail as usize];
@@ -277,6 +503,7 @@ impl Scheduler {
                 queued_tasks,
             } => {
                 let is_idle = is_running && active_tasks == 0 && queued_tasks == 0;
+                actor_state.is_busy = active_tasks > 0 || queued_tasks > 0;
                 let found_new_work = if is_idle && actor_state.has_dropped_msgs {
                     actor_state.has_dropped_msgs = false;
                     let mut conn = self.db_pool.get().await?;
@@ -343,6 +570,7 @@ impl Scheduler {
                 queued_tasks,
             } => {
                 let is_idle = is_running && active_tasks == 0 && queued_tasks == 0;
+                actor_state.is_busy = active_tasks > 0 || queued_tasks > 0;
                 let found_new_work = if is_idle && actor_state.has_dropped_msgs {
                     actor_state.has_dropped_msgs = false;
                     let mut conn = self.db_pool.get().await?;
@@ -386,6 +614,7 @@ impl Scheduler {
                 queued_tasks,
             } => {
                 let is_idle = is_running && active_tasks == 0 && queued_tasks == 0;
+                actor_state.is_busy = active_tasks > 0 || queued_tasks > 0;
                 let found_new_work = if is_idle && actor_state.has_dropped_msgs {
                     actor_state.has_dropped_msgs = false;
                     let mut conn = self.db_pool.get().await?;
@@ -414,46 +643,99 @@ impl Scheduler {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self))]
+    async fn on_geocoding_msg(&mut self, msg: MsgFromGeocoding) -> Result<()> {
+        let actor_state = &mut self.actor_states[Actors::Geocoding as usize];
+        match msg {
+            MsgFromGeocoding::ActivityChange {
+                is_running,
+                active_tasks,
+                queued_tasks,
+            } => {
+                let is_idle = is_running && active_tasks == 0 && queued_tasks == 0;
+                actor_state.is_busy = active_tasks > 0 || queued_tasks > 0;
+                let found_new_work = if is_idle && actor_state.has_dropped_msgs {
+                    actor_state.has_dropped_msgs = false;
+                    let mut conn = self.db_pool.get().await?;
+                    let geocoding_required = rules::geocoding_due(&mut conn).await?;
+                    let any_work = !geocoding_required.is_empty();
+                    let geocoding_actor = self.geocoding_actor()?;
+                    for g in geocoding_required {
+                        geocoding_actor
+                            .msg_geocode_asset(g)
+                            .expect("receiver must be alive");
+                    }
+                    any_work
+                } else {
+                    false
+                };
+                if is_idle && !found_new_work {
+                    tracing::info!("Geocoding actor idle");
+                }
+            }
+            MsgFromGeocoding::DroppedMessage => {
+                actor_state.has_dropped_msgs = true;
+            }
+            MsgFromGeocoding::TaskResult(result) => {
+                tracing::debug!(?result);
+            }
+        }
+        Ok(())
+    }
+
     #[tracing::instrument(skip(self))]
     async fn handle_message(&mut self, msg: SchedulerMessage) {
         match msg {
-            SchedulerMessage::Timer => {}
+            SchedulerMessage::Timer => {
+                self.on_timer().await;
+            }
             SchedulerMessage::UserRequest(user_request) => match user_request {
                 UserRequest::ReindexAssetRoot(root_dir_id) => {
                     let _ = self.indexing_actor.msg_index_asset_root(root_dir_id);
                 }
+                UserRequest::CancelJob(job_id) => {
+                    if let Err(err) = self.cancel_job(job_id).await {
+                        tracing::error!(%job_id, ?err, "error cancelling job");
+                    }
+                }
+                UserRequest::RetryJob(job_id) => {
+                    if let Err(err) = self.retry_job(job_id).await {
+                        tracing::error!(%job_id, ?err, "error retrying job");
+                    }
+                }
+                UserRequest::RetryFailedJob(failed_job_id) => {
+                    if let Err(err) = self.retry_failed_job(failed_job_id).await {
+                        tracing::error!(%failed_job_id, ?err, "error retrying failed job");
+                    }
+                }
+                UserRequest::FindNearDuplicates => {
+                    self.run_maintenance_task_now(MaintenanceTask::FindNearDuplicates)
+                        .await;
+                }
+                UserRequest::DetectSeries => {
+                    self.run_maintenance_task_now(MaintenanceTask::DetectSeries)
+                        .await;
+                }
             },
             SchedulerMessage::PauseAllProcessing => {
-                self.thumbnail_actor
-                    .msg_pause_all()
-                    .expect("receiver must be alive");
-                self.video_packaging_actor
-                    .msg_pause_all()
-                    .expect("receiver must be alive");
-                self.image_conversion_actor
-                    .msg_pause_all()
-                    .expect("receiver must be alive");
+                for actor in Actors::PROCESSING {
+                    self.actor_states[actor as usize].paused_by_user = true;
+                    self.update_actor_paused(actor);
+                }
             }
             SchedulerMessage::ResumeAllProcessing => {
-                self.thumbnail_actor
-                    .msg_resume_all()
-                    .expect("receiver must be alive");
-                self.video_packaging_actor
-                    .msg_resume_all()
-                    .expect("receiver must be alive");
-                self.image_conversion_actor
-                    .msg_resume_all()
-                    .expect("receiver must be alive");
+                for actor in Actors::PROCESSING {
+                    self.actor_states[actor as usize].paused_by_user = false;
+                    self.update_actor_paused(actor);
+                }
             }
             SchedulerMessage::PauseVideoPackaging => {
-                self.video_packaging_actor
-                    .msg_pause_all()
-                    .expect("receiver must be alive");
+                self.actor_states[Actors::VideoPackaging as usize].paused_by_user = true;
+                self.update_actor_paused(Actors::VideoPackaging);
             }
             SchedulerMessage::ResumeVideoPackaging => {
-                self.video_packaging_actor
-                    .msg_resume_all()
-                    .expect("receiver must be alive");
+                self.actor_states[Actors::VideoPackaging as usize].paused_by_user = false;
+                self.update_actor_paused(Actors::VideoPackaging);
             }
             SchedulerMessage::Shutdown => {
                 if !self.waiting_for_shutdown {
@@ -467,6 +749,11 @@ impl Scheduler {
                     self.image_conversion_actor
                         .msg_shutdown()
                         .expect("receiver must be alive");
+                    if let Some(geocoding_actor) = &self.geocoding_actor {
+                        geocoding_actor
+                            .msg_shutdown()
+                            .expect("receiver must be alive");
+                    }
                     let did_shutdown_recvs = self
                         .actor_did_shutdown_recvs
                         .take()
@@ -492,14 +779,465 @@ impl Scheduler {
                 }
             }
             SchedulerMessage::Startup => {
+                // the queues these jobs were in are gone, and on_startup collects all work
+                // that is still required again anyway
+                let cancel_result: Result<usize> = async {
+                    let conn = self.db_pool.get().await?;
+                    interact!(conn, move |conn| {
+                        repository::job::cancel_unfinished_jobs(conn, "server was restarted")
+                    })
+                    .await?
+                }
+                .await;
+                match cancel_result {
+                    Ok(0) => {}
+                    Ok(count) => tracing::info!(count, "Cancelled jobs interrupted by restart"),
+                    Err(err) => tracing::error!(?err, "error cancelling interrupted jobs"),
+                }
+                // before any work is queued so nothing starts outside of its processing window
+                self.update_processing_windows();
+                // start watching before the initial scan so we don't miss changes made during it
+                self.watcher_actor
+                    .msg_watch_asset_roots()
+                    .expect("receiver must be alive");
                 tokio::spawn(on_startup(
                     self.db_pool.clone(),
+                    self.config.bin_paths.clone(),
                     self.indexing_actor.clone(),
                     self.thumbnail_actor.clone(),
                     self.video_packaging_actor.clone(),
                     self.image_conversion_actor.clone(),
+                    self.geocoding_actor.clone(),
+                ));
+            }
+        }
+    }
+}
+
+impl Scheduler {
+    async fn get_job(&self, job_id: JobId) -> Result<Job> {
+        let conn = self.db_pool.get().await?;
+        interact!(conn, move |conn| repository::job::find_job(conn, job_id))
+            .await??
+            .ok_or_else(|| eyre!("{} does not exist", job_id))
+    }
+
+    fn geocoding_actor(&self) -> Result<&GeocodingActorHandle> {
+        self.geocoding_actor
+            .as_ref()
+            .ok_or_else(|| eyre!("reverse geocoding is disabled in the config"))
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn cancel_job(&self, job_id: JobId) -> Result<()> {
+        let job = self.get_job(job_id).await?;
+        if job.state.is_done() {
+            return Ok(());
+        }
+        match job.kind {
+            JobKind::IndexAssetRoot | JobKind::IndexPaths | JobKind::ReconcilePaths => {
+                self.indexing_actor.msg_cancel_job(job_id)
+            }
+            JobKind::AssetThumbnail | JobKind::AlbumThumbnail => {
+                self.thumbnail_actor.msg_cancel_job(job_id)
+            }
+            JobKind::PackageVideo => self.video_packaging_actor.msg_cancel_job(job_id),
+            JobKind::ConvertImage => self.image_conversion_actor.msg_cancel_job(job_id),
+            JobKind::ReverseGeocode => self.geocoding_actor()?.msg_cancel_job(job_id),
+        }
+    }
+
+    #[tracing::instrument(skip(self))]
+    async fn retry_failed_job(&self, failed_job_id: FailedJobId) -> Result<()> {
+        let mut conn = self.db_pool.get().await?;
+        let failed_job = interact!(conn, move |conn| {
+            repository::failed_job::find_failed_job(conn, failed_job_id)
+        })
+        .await??
+        .ok_or_else(|| eyre!("{} does not exist", failed_job_id))?;
+        let asset_id = failed_job.asset_id;
+        let is_required = match failed_job.kind {
+            FailedJobKind::Thumbnail => {
+                let thumbnails_required =
+                    rules::required_thumbnails_for_asset(&mut conn, asset_id).await?;
+                let is_required = !thumbnails_required.thumbnails.is_empty();
+                if is_required {
+                    self.thumbnail_actor
+                        .msg_create_asset_thumbnail(thumbnails_required)?;
+                }
+                is_required
+            }
+            FailedJobKind::VideoPackaging => {
+                let video_packaging_required =
+                    rules::required_video_packaging_for_asset(&mut conn, asset_id).await?;
+                let is_required = !video_packaging_required.is_empty();
+                for vid_pack in video_packaging_required {
+                    self.video_packaging_actor.msg_package_video(vid_pack)?;
+                }
+                is_required
+            }
+            FailedJobKind::ReverseGeocode => {
+                let geocoding_required =
+                    rules::required_geocoding_for_asset(&mut conn, asset_id).await?;
+                let is_required = geocoding_required.is_some();
+                if let Some(geocode_asset) = geocoding_required {
+                    self.geocoding_actor()?.msg_geocode_asset(geocode_asset)?;
+                }
+                is_required
+            }
+        };
+        if !is_required {
+            // whatever failed was done in the meantime
+            let kind = failed_job.kind;
+            interact!(conn, move |conn| {
+                repository::failed_job::delete_failed_job(conn, asset_id, kind)
+            })
+            .await??;
+        }
+        Ok(())
+    }
+
+    /// The work of the job is computed again from the rules instead of being stored with it,
+    /// so only what is still missing is done. If nothing is, the job is marked as finished.
+    #[tracing::instrument(skip(self))]
+    async fn retry_job(&self, job_id: JobId) -> Result<()> {
+        let job = self.get_job(job_id).await?;
+        if !job.state.is_done() {
+            return Err(eyre!("{} is still queued or running", job_id));
+        }
+        let mut conn = self.db_pool.get().await?;
+        let is_required = match (job.kind, job.subject) {
+            (JobKind::IndexAssetRoot, JobSubject::AssetRootDir(root_dir_id)) => {
+                self.indexing_actor
+                    .msg_retry_index_asset_root(job_id, root_dir_id)?;
+                true
+            }
+            (JobKind::AssetThumbnail, JobSubject::Asset(asset_id)) => {
+                let thumbnails_required =
+                    rules::required_thumbnails_for_asset(&mut conn, asset_id).await?;
+                let is_required = !thumbnails_required.thumbnails.is_empty();
+                if is_required {
+                    self.thumbnail_actor.msg_retry_job(
+                        job_id,
+                        ThumbnailTaskMsg::CreateAssetThumbnail(thumbnails_required),
+                    )?;
+                }
+                is_required
+            }
+            (JobKind::AlbumThumbnail, JobSubject::Album { album_id, .. }) => {
+                let album_thumbnail = rules::album_thumbnails_to_create(&mut conn)
+                    .await?
+                    .into_iter()
+                    .find(|album_thumbnail| album_thumbnail.album_id == album_id);
+                let is_required = album_thumbnail.is_some();
+                if let Some(album_thumbnail) = album_thumbnail {
+                    self.thumbnail_actor.msg_retry_job(
+                        job_id,
+                        ThumbnailTaskMsg::CreateAlbumThumbnail(album_thumbnail),
+                    )?;
+                }
+                is_required
+            }
+            (JobKind::PackageVideo, JobSubject::Asset(asset_id)) => {
+                let mut video_packaging_required =
+                    rules::required_video_packaging_for_asset(&mut conn, asset_id)
+                        .await?
+                        .into_iter();
+                // the first one takes over the job, any others get their own
+                let is_required = match video_packaging_required.next() {
+                    Some(vid_pack) => {
+                        self.video_packaging_actor
+                            .msg_retry_job(job_id, VideoPackagingTaskMsg::PackageVideo(vid_pack))?;
+                        true
+                    }
+                    None => false,
+                };
+                for vid_pack in video_packaging_required {
+                    self.video_packaging_actor.msg_package_video(vid_pack)?;
+                }
+                is_required
+            }
+            (JobKind::ConvertImage, JobSubject::Asset(asset_id)) => {
+                let mut image_conversion_required = rules::image_conversion_due(&mut conn)
+                    .await?
+                    .into_iter()
+                    .filter(|img_convert| img_convert.asset_id == asset_id);
+                let is_required = match image_conversion_required.next() {
+                    Some(img_convert) => {
+                        self.image_conversion_actor
+                            .msg_retry_job(job_id, img_convert)?;
+                        true
+                    }
+                    None => false,
+                };
+                for img_convert in image_conversion_required {
+                    self.image_conversion_actor.msg_convert_image(img_convert)?;
+                }
+                is_required
+            }
+            (JobKind::ReverseGeocode, JobSubject::Asset(asset_id)) => {
+                let geocoding_required =
+                    rules::required_geocoding_for_asset(&mut conn, asset_id).await?;
+                let is_required = geocoding_required.is_some();
+                if let Some(geocode_asset) = geocoding_required {
+                    self.geocoding_actor()?
+                        .msg_retry_job(job_id, geocode_asset)?;
+                }
+                is_required
+            }
+            (JobKind::IndexPaths | JobKind::ReconcilePaths, _) => {
+                return Err(eyre!(
+                    "{} can not be retried, reindex the asset root instead",
+                    job_id
                 ));
             }
+            (kind, subject) => {
+                return Err(eyre!(
+                    "{} has kind {:?} which does not match subject {:?}",
+                    job_id,
+                    kind,
+                    subject
+                ));
+            }
+        };
+        if !is_required {
+            tracing::info!(%job_id, "Nothing left to do for retried job");
+            interact!(conn, move |conn| {
+                repository::job::set_job_done(conn, job_id, JobState::Finished, None)
+            })
+            .await??;
+        }
+        Ok(())
+    }
+}
+
+impl Scheduler {
+    #[tracing::instrument(skip(self))]
+    async fn on_timer(&mut self) {
+        if self.waiting_for_shutdown {
+            return;
+        }
+        self.update_processing_windows();
+        for task in MaintenanceTask::ALL {
+            let Some(interval) = self.maintenance_interval(task) else {
+                continue;
+            };
+            let state = &self.maintenance_states[task as usize];
+            let is_due = state.last_run.elapsed() >= interval;
+            let is_running = state
+                .running
+                .as_ref()
+                .is_some_and(|running| !running.is_finished());
+            if !is_due || is_running {
+                continue;
+            }
+            // indexing the same root twice at once is wasted effort, wait until it's done
+            if matches!(task, MaintenanceTask::RescanAssetRoots)
+                && self.actor_states[Actors::Indexing as usize].is_busy
+            {
+                continue;
+            }
+            self.run_maintenance_task(task).await;
+        }
+    }
+
+    /// Start a maintenance task outside of its interval, unless it is already running
+    async fn run_maintenance_task_now(&mut self, task: MaintenanceTask) {
+        let is_running = self.maintenance_states[task as usize]
+            .running
+            .as_ref()
+            .is_some_and(|running| !running.is_finished());
+        if is_running {
+            tracing::info!(?task, "Maintenance task is already running");
+            return;
+        }
+        self.run_maintenance_task(task).await;
+    }
+
+    async fn run_maintenance_task(&mut self, task: MaintenanceTask) {
+        let result = self.start_maintenance_task(task).await;
+        let state = &mut self.maintenance_states[task as usize];
+        // also on error, so that a failing task is not retried every tick
+        state.last_run = Instant::now();
+        match result {
+            Ok(running) => state.running = running,
+            Err(err) => tracing::error!(?task, ?err, "error in maintenance task"),
+        }
+    }
+
+    /// Suspend actors whose processing window just ended and resume those whose window started
+    fn update_processing_windows(&mut self) {
+        let now = chrono::Local::now().time();
+        for actor in Actors::PROCESSING {
+            let is_in_window = self.processing_config(actor).is_in_window(now);
+            let actor_state = &mut self.actor_states[actor as usize];
+            if actor_state.outside_processing_window != is_in_window {
+                continue;
+            }
+            actor_state.outside_processing_window = !is_in_window;
+            if is_in_window {
+                tracing::info!(?actor, "Processing window started");
+            } else {
+                tracing::info!(?actor, "Outside of processing window, suspending");
+            }
+            self.update_actor_paused(actor);
+        }
+    }
+
+    fn processing_config(&self, actor: Actors) -> &ProcessingActorConfig {
+        let config = &self.config.processing;
+        match actor {
+            Actors::Thumbnail => &config.thumbnail,
+            Actors::ImageConversion => &config.image_conversion,
+            Actors::VideoPackaging => &config.video_packaging,
+            Actors::Indexing | Actors::Geocoding => {
+                unreachable!("{:?} has no processing config", actor)
+            }
+        }
+    }
+
+    /// Running tasks are suspended (not killed) and resumed where they left off
+    fn update_actor_paused(&mut self, actor: Actors) {
+        let actor_state = &mut self.actor_states[actor as usize];
+        let should_pause = actor_state.paused_by_user || actor_state.outside_processing_window;
+        if should_pause == actor_state.is_paused {
+            return;
+        }
+        actor_state.is_paused = should_pause;
+        let result = match (actor, should_pause) {
+            (Actors::Thumbnail, true) => self.thumbnail_actor.msg_pause_all(),
+            (Actors::Thumbnail, false) => self.thumbnail_actor.msg_resume_all(),
+            (Actors::ImageConversion, true) => self.image_conversion_actor.msg_pause_all(),
+            (Actors::ImageConversion, false) => self.image_conversion_actor.msg_resume_all(),
+            (Actors::VideoPackaging, true) => self.video_packaging_actor.msg_pause_all(),
+            (Actors::VideoPackaging, false) => self.video_packaging_actor.msg_resume_all(),
+            (Actors::Indexing | Actors::Geocoding, _) => {
+                unreachable!("{:?} is not paused by the scheduler", actor)
+            }
+        };
+        result.expect("receiver must be alive");
+    }
+
+    fn maintenance_interval(&self, task: MaintenanceTask) -> Option<Duration> {
+        let config = &self.config.maintenance;
+        match task {
+            MaintenanceTask::RescanAssetRoots => config.rescan_interval,
+            MaintenanceTask::CollectWork => config.collect_work_interval,
+            MaintenanceTask::Vacuum => config.vacuum_interval,
+            MaintenanceTask::PruneOrphanedFiles => config.prune_orphaned_files_interval,
+            MaintenanceTask::FindNearDuplicates => config.find_near_duplicates_interval,
+            MaintenanceTask::DetectSeries => config.detect_series_interval,
+            MaintenanceTask::PruneJobs => config.prune_jobs_interval,
+        }
+    }
+
+    /// Returns the handle of tasks that keep running in the background
+    async fn start_maintenance_task(
+        &self,
+        task: MaintenanceTask,
+    ) -> Result<Option<JoinHandle<()>>> {
+        match task {
+            MaintenanceTask::RescanAssetRoots => {
+                let conn = self.db_pool.get().await?;
+                let asset_roots = interact!(conn, move |conn| {
+                    repository::asset_root_dir::get_asset_roots(conn)
+                })
+                .await??;
+                tracing::info!("Rescanning asset roots");
+                for asset_root in asset_roots {
+                    self.indexing_actor.msg_index_asset_root(asset_root.id)?;
+                }
+                Ok(None)
+            }
+            MaintenanceTask::CollectWork => {
+                self.collect_work().await?;
+                Ok(None)
+            }
+            MaintenanceTask::Vacuum => Ok(Some(tokio::spawn(vacuum_db(self.db_pool.clone())))),
+            MaintenanceTask::PruneOrphanedFiles => Ok(Some(tokio::spawn(prune_orphaned_files(
+                self.db_pool.clone(),
+                self.storage.clone(),
+            )))),
+            MaintenanceTask::FindNearDuplicates => Ok(Some(tokio::spawn(find_near_duplicates(
+                self.db_pool.clone(),
+            )))),
+            MaintenanceTask::DetectSeries => {
+                Ok(Some(tokio::spawn(detect_series(self.db_pool.clone()))))
+            }
+            MaintenanceTask::PruneJobs => Ok(Some(tokio::spawn(prune_jobs(
+                self.db_pool.clone(),
+                self.config.maintenance.job_retention,
+            )))),
+        }
+    }
+
+    /// Queue the work the rules say is required, like on startup.
+    /// Actors that are busy are skipped, the work they have queued would be queued twice.
+    #[tracing::instrument(skip(self))]
+    async fn collect_work(&self) -> Result<()> {
+        let is_busy = |actor: Actors| self.actor_states[actor as usize].is_busy;
+        let mut conn = self.db_pool.get().await?;
+        let mut thumbnail_count = 0;
+        let mut video_packaging_count = 0;
+        let mut image_conversion_count = 0;
+        let mut geocoding_count = 0;
+        if !is_busy(Actors::Thumbnail) {
+            let thumbnails_required = rules::thumbnails_to_create(&mut conn).await?;
+            let album_thumbnails_required = rules::album_thumbnails_to_create(&mut conn).await?;
+            thumbnail_count = thumbnails_required.len() + album_thumbnails_required.len();
+            for t in thumbnails_required {
+                self.thumbnail_actor.msg_create_asset_thumbnail(t)?;
+            }
+            for album_thumb in album_thumbnails_required {
+                self.thumbnail_actor
+                    .msg_create_album_thumbnail(album_thumb)?;
+            }
+        }
+        if !is_busy(Actors::VideoPackaging) {
+            let video_packaging_required = rules::video_packaging_due(&mut conn).await?;
+            video_packaging_count = video_packaging_required.len();
+            for vid_pack in video_packaging_required {
+                self.video_packaging_actor.msg_package_video(vid_pack)?;
+            }
+        }
+        if !is_busy(Actors::ImageConversion) {
+            let image_conversion_required = rules::image_conversion_due(&mut conn).await?;
+            image_conversion_count = image_conversion_required.len();
+            for img_convert in image_conversion_required {
+                self.image_conversion_actor.msg_convert_image(img_convert)?;
+            }
+        }
+        if let Some(geocoding_actor) = &self.geocoding_actor {
+            if !is_busy(Actors::Geocoding) {
+                let geocoding_required = rules::geocoding_due(&mut conn).await?;
+                geocoding_count = geocoding_required.len();
+                for geocode_asset in geocoding_required {
+                    geocoding_actor.msg_geocode_asset(geocode_asset)?;
+                }
+            }
+        }
+        if thumbnail_count + video_packaging_count + image_conversion_count + geocoding_count > 0 {
+            tracing::info!(
+                thumbnail = thumbnail_count,
+                video_packaging = video_packaging_count,
+                image_conversion = image_conversion_count,
+                geocoding = geocoding_count,
+                "Collected required jobs"
+            );
+        }
+        Ok(())
+    }
+}
+
+async fn send_timer_ticks(send: mpsc::Sender<SchedulerMessage>, tick: Duration) {
+    let mut interval = tokio::time::interval(tick);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // the first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        if send.send(SchedulerMessage::Timer).await.is_err() {
+            break;
         }
     }
 }
@@ -507,16 +1245,23 @@ impl Scheduler {
 #[instrument(skip_all)]
 async fn on_startup(
     db_pool: DbPool,
+    bin_paths: Option<BinPaths>,
     indexing_actor: IndexingActorHandle,
     thumbnail_actor: ThumbnailActorHandle,
     video_packaging_actor: VideoPackagingActorHandle,
     image_conversion_actor: ImageConversionActorHandle,
+    geocoding_actor: Option<GeocodingActorHandle>,
 ) {
     let mut conn = db_pool
         .get()
         .await
         .expect("TODO how do we handle errors in scheduler");
 
+    // before collecting work so that it includes the failures that are due again
+    if let Err(err) = retry_failures_of_upgraded_tools(&mut conn, bin_paths.as_ref()).await {
+        tracing::error!(?err, "error checking for upgraded external programs");
+    }
+
     let video_packaging_required = rules::video_packaging_due(&mut conn).await.expect("TODO");
     let video_packaging_count = video_packaging_required.len();
     let image_conversion_required = rules::image_conversion_due(&mut conn).await.expect("TODO");
@@ -526,11 +1271,16 @@ async fn on_startup(
     let album_thumbnails_required = rules::album_thumbnails_to_create(&mut conn)
         .await
         .expect("TODO");
+    let geocoding_required = match geocoding_actor {
+        Some(_) => rules::geocoding_due(&mut conn).await.expect("TODO"),
+        None => Vec::new(),
+    };
     tracing::info!(
         image_conversion = image_conversion_count,
         video_packaging = video_packaging_count,
         thumbnail = thumbnail_count,
         album_thumbnail = album_thumbnails_required.len(),
+        geocoding = geocoding_required.len(),
         "Collected required jobs"
     );
     for vid_pack in video_packaging_required {
@@ -545,6 +1295,11 @@ async fn on_startup(
     for album_thumb in album_thumbnails_required {
         let _ = thumbnail_actor.msg_create_album_thumbnail(album_thumb);
     }
+    if let Some(geocoding_actor) = geocoding_actor {
+        for geocode_asset in geocoding_required {
+            let _ = geocoding_actor.msg_geocode_asset(geocode_asset);
+        }
+    }
 
     let asset_roots = interact!(conn, move |conn| {
         repository::asset_root_dir::get_asset_roots(conn)
@@ -556,3 +1311,259 @@ async fn on_startup(
         let _ = indexing_actor.msg_index_asset_root(asset_root.id);
     }
 }
+
+/// Make failures caused by an external program due for a retry if the program's version
+/// changed since the last startup, it might have been fixed
+#[instrument(skip_all)]
+async fn retry_failures_of_upgraded_tools(
+    conn: &mut PooledDbConn,
+    bin_paths: Option<&BinPaths>,
+) -> Result<()> {
+    for (tool, version) in startup_self_check::tool_versions(bin_paths).await {
+        let (previous_version, retry_count) = interact!(conn, move |conn| {
+            let previous_version = repository::failed_job::get_tool_version(conn, tool)?;
+            let retry_count = if previous_version.as_ref() != Some(&version) {
+                repository::failed_job::set_tool_version(conn, tool, &version)?;
+                match previous_version {
+                    // first startup with this table, nothing failed with an older version
+                    None => 0,
+                    Some(_) => repository::failed_job::set_failed_jobs_due_for_tool(conn, tool)?,
+                }
+            } else {
+                0
+            };
+            Ok::<_, eyre::Report>((previous_version, retry_count))
+        })
+        .await??;
+        if retry_count > 0 {
+            tracing::info!(
+                ?tool,
+                ?previous_version,
+                retry_count,
+                "External program changed, retrying its failures"
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Delete Assets whose file has been missing for longer than `grace_period`
+#[instrument(skip(db_pool, storage))]
+async fn purge_missing_assets(db_pool: DbPool, storage: Storage, grace_period: Duration) {
+    let result: Result<usize> = async {
+        let mut conn = db_pool.get().await?;
+        let to_purge = rules::assets_to_purge(&mut conn, grace_period).await?;
+        let mut count = 0;
+        for op in to_purge {
+            // one Asset that can't be purged shouldn't keep the others around
+            let files = match apply_purge_asset(&mut conn, &op).await {
+                Ok(files) => files,
+                Err(err) => {
+                    tracing::error!(asset_id=%op.asset_id, ?err, "error purging missing Asset");
+                    continue;
+                }
+            };
+            count += 1;
+            if let Err(err) = delete_obsolete_files(&storage, &files).await {
+                tracing::warn!(asset_id=%op.asset_id, ?err, "error deleting files of purged Asset");
+            }
+        }
+        Ok(count)
+    }
+    .await;
+    match result {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(count, "Purged missing assets"),
+        Err(err) => tracing::error!(?err, "error purging missing assets"),
+    }
+}
+
+#[instrument(skip(db_pool))]
+async fn vacuum_db(db_pool: DbPool) {
+    let result: Result<()> = async {
+        let conn = db_pool.get().await?;
+        interact!(conn, move |conn| {
+            repository::db::vacuum_and_optimize(conn)
+        })
+        .await?
+    }
+    .await;
+    match result {
+        Ok(()) => tracing::info!("Vacuumed database"),
+        Err(err) => tracing::error!(?err, "error vacuuming database"),
+    }
+}
+
+/// Delete jobs that are done and older than `retention`
+#[instrument(skip(db_pool))]
+async fn prune_jobs(db_pool: DbPool, retention: Duration) {
+    let result: Result<usize> = async {
+        let conn = db_pool.get().await?;
+        let before = Utc::now() - chrono::Duration::from_std(retention)?;
+        interact!(conn, move |conn| {
+            repository::job::delete_done_jobs_before(conn, before)
+        })
+        .await?
+    }
+    .await;
+    match result {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(count, "Deleted old jobs"),
+        Err(err) => tracing::error!(?err, "error deleting old jobs"),
+    }
+}
+
+/// Delete files in storage that nothing in the database refers to
+#[instrument(skip(db_pool, storage))]
+async fn prune_orphaned_files(db_pool: DbPool, storage: Storage) {
+    let result: Result<usize> = async {
+        let mut conn = db_pool.get().await?;
+        let files = orphaned_files(&mut conn, &storage).await?;
+        let count = files.keys.len() + files.key_prefixes.len();
+        delete_obsolete_files(&storage, &files).await?;
+        Ok(count)
+    }
+    .await;
+    match result {
+        Ok(0) => {}
+        Ok(count) => tracing::info!(count, "Deleted orphaned files"),
+        Err(err) => tracing::error!(?err, "error deleting orphaned files"),
+    }
+}
+
+/// Compute perceptual hashes missing for Assets indexed before they were computed along
+/// with thumbnails, then group all Assets by them
+#[instrument(skip(db_pool))]
+async fn find_near_duplicates(db_pool: DbPool) {
+    let result: Result<usize> = async {
+        let conn = db_pool.get().await?;
+        let without_hash = interact!(conn, move |conn| {
+            repository::asset::get_image_assets_without_perceptual_hash(conn)
+        })
+        .await??;
+        for asset_id in without_hash {
+            let path = interact!(conn, move |conn| {
+                repository::asset::get_asset_path_on_disk(conn, asset_id)
+            })
+            .await??
+            .path_on_disk();
+            match near_duplicates::perceptual_hash_file(path).await {
+                Ok(hash) => {
+                    interact!(conn, move |conn| {
+                        repository::asset::set_perceptual_hash(conn, asset_id, hash)
+                    })
+                    .await??;
+                }
+                Err(err) => {
+                    tracing::warn!(%asset_id, ?err, "error computing perceptual hash");
+                }
+            }
+        }
+        let hashes = interact!(conn, move |conn| {
+            repository::asset::get_perceptual_hashes(conn)
+        })
+        .await??;
+        let groups = tokio::task::spawn_blocking(move || {
+            near_duplicates::cluster_near_duplicates(&hashes, near_duplicates::MAX_HAMMING_DISTANCE)
+        })
+        .await?;
+        let count = groups.len();
+        interact!(conn, move |conn| {
+            repository::near_duplicate::replace_near_duplicate_groups(conn, &groups)
+        })
+        .await??;
+        Ok(count)
+    }
+    .await;
+    match result {
+        Ok(count) => tracing::info!(count, "Found near duplicate groups"),
+        Err(err) => tracing::error!(?err, "error finding near duplicates"),
+    }
+}
+
+/// Put photos taken in quick succession with the same camera into automatic series,
+/// except for ones the user dissolved before. The sharpest photo becomes the selection.
+/// Photos without a perceptual hash yet are only grouped by burst id, the hash is
+/// backfilled by find_near_duplicates.
+#[instrument(skip(db_pool))]
+async fn detect_series(db_pool: DbPool) {
+    let result: Result<usize> = async {
+        let conn = db_pool.get().await?;
+        let candidates = interact!(conn, move |conn| {
+            repository::asset_series::get_series_detection_candidates(conn)
+        })
+        .await??;
+        let taken_dates: Vec<(AssetId, DateTime<Utc>)> = candidates
+            .iter()
+            .map(|candidate| (candidate.asset_id, candidate.taken_date))
+            .collect();
+        let candidates: HashMap<AssetId, SeriesDetectionCandidate> = candidates
+            .into_iter()
+            .map(|candidate| (candidate.asset_id, candidate))
+            .collect();
+        let mut created = 0;
+        for run in series_detection::split_by_time_gaps(&taken_dates) {
+            let mut run_candidates: Vec<SeriesCandidate> = Vec::with_capacity(run.len());
+            for asset_id in run {
+                let exiftool_output = interact!(conn, move |conn| {
+                    repository::asset::get_asset_exiftool_output(conn, asset_id)
+                })
+                .await??;
+                let (camera, burst_id) =
+                    match series_detection::read_camera_and_burst_id(&exiftool_output) {
+                        Ok(res) => res,
+                        Err(err) => {
+                            tracing::warn!(%asset_id, ?err, "error reading exiftool output");
+                            (None, None)
+                        }
+                    };
+                run_candidates.push(SeriesCandidate {
+                    asset_id,
+                    taken_date: candidates[&asset_id].taken_date,
+                    perceptual_hash: candidates[&asset_id].perceptual_hash,
+                    camera,
+                    burst_id,
+                });
+            }
+            for series in series_detection::detect_series(&run_candidates) {
+                let series_clone = series.clone();
+                let deleted = interact!(conn, move |conn| {
+                    repository::asset_series::get_deleted_auto_series(conn, &series_clone)
+                })
+                .await??;
+                if series_detection::was_dissolved(&series, &deleted) {
+                    continue;
+                }
+                let mut sharpness: Vec<(AssetId, Option<f64>)> = Vec::with_capacity(series.len());
+                for asset_id in series.iter().copied() {
+                    let path = interact!(conn, move |conn| {
+                        repository::asset::get_asset_path_on_disk(conn, asset_id)
+                    })
+                    .await??
+                    .path_on_disk();
+                    let value = match series_detection::sharpness_file(path).await {
+                        Ok(value) => Some(value),
+                        Err(err) => {
+                            tracing::warn!(%asset_id, ?err, "error computing sharpness");
+                            None
+                        }
+                    };
+                    sharpness.push((asset_id, value));
+                }
+                let selection =
+                    series_detection::choose_selection(&sharpness).expect("series is not empty");
+                interact!(conn, move |conn| {
+                    repository::asset_series::create_auto_series(conn, &series, selection)
+                })
+                .await??;
+                created += 1;
+            }
+        }
+        Ok(created)
+    }
+    .await;
+    match result {
+        Ok(count) => tracing::info!(count, "Created automatic series"),
+        Err(err) => tracing::error!(?err, "error detecting series"),
+    }
+}
@@ -1,10 +1,15 @@
+use std::ops::Range;
+
 use async_trait::async_trait;
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use chrono::{DateTime, Utc};
 use enum_dispatch::enum_dispatch;
 use eyre::{Context, Result};
-use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, AsyncWrite};
 use tracing::instrument;
 
+pub use super::s3_storage::{S3OutputFile, S3Storage};
+
 /// Abstraction for storing data files in any backing store.
 /// This interface is basically a blob store, where every object has
 /// a `key` used to store and retrieve it.
@@ -17,14 +22,37 @@ pub trait StorageProvider: Clone {
         &self,
         key: &str,
     ) -> Result<Box<dyn AsyncRead + Send + Unpin>, StorageReadError>;
+    /// Read only the bytes in `range`, which must be within the object
+    async fn open_read_range(
+        &self,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>, StorageReadError>;
     async fn open_write_stream(&self, key: &str) -> Result<Box<dyn AsyncWrite + Send + Unpin>>;
     async fn exists(&self, key: &str) -> Result<bool>;
+    async fn metadata(&self, key: &str) -> Result<StorageObject, StorageReadError>;
+    /// Deleting a key that does not exist is not an error
+    async fn delete(&self, key: &str) -> Result<()>;
+    /// Delete every object whose key starts with `prefix/`
+    async fn delete_prefix(&self, prefix: &str) -> Result<()>;
+    /// Every object whose key starts with `prefix/`, in no particular order
+    async fn list_prefix(&self, prefix: &str) -> Result<Vec<StorageObject>>;
     async fn new_command_out_file(&self, key: &str) -> Result<CommandOutputFile>;
     /// If this `StorageProvider` is backed by a local filesystem,
     /// this returns the path `key` maps to assuming `key` exists.
     /// If `key` doesn't exist or the `StorageProvider` is not local,
     /// returns None.
     async fn local_path(&self, key: &str) -> Result<Option<PathBuf>>;
+    /// URL clients can download `key` from directly instead of going through us,
+    /// if this `StorageProvider` supports and is configured to hand those out.
+    async fn presigned_url(&self, key: &str) -> Result<Option<String>>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageObject {
+    pub key: String,
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -62,12 +90,14 @@ pub trait StorageCommandOutput {
 #[enum_dispatch]
 pub enum Storage {
     LocalFileStorage,
+    S3Storage,
 }
 
 impl Clone for Storage {
     fn clone(&self) -> Self {
         match self {
             Self::LocalFileStorage(a) => Self::LocalFileStorage(a.clone()),
+            Self::S3Storage(a) => Self::S3Storage(a.clone()),
         }
     }
 }
@@ -76,6 +106,7 @@ impl Clone for Storage {
 #[derive(Debug)]
 pub enum CommandOutputFile {
     LocalOutputFile,
+    S3OutputFile,
 }
 
 #[derive(Debug, Clone)]
@@ -145,6 +176,23 @@ impl StorageProvider for LocalFileStorage {
         }
     }
 
+    #[instrument(err, skip(self), level = "trace")]
+    async fn open_read_range(
+        &self,
+        key: &str,
+        range: Range<u64>,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin>, StorageReadError> {
+        let mut file = match tokio::fs::File::open(self.root.join(key)).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(StorageReadError::FileNotFound(key.to_owned()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        file.seek(std::io::SeekFrom::Start(range.start)).await?;
+        Ok(Box::new(file.take(range.end - range.start)))
+    }
+
     #[instrument(err, skip(self), level = "trace")]
     async fn open_write_stream(&self, key: &str) -> Result<Box<dyn AsyncWrite + Send + Unpin>> {
         Ok(Box::new(
@@ -165,6 +213,80 @@ impl StorageProvider for LocalFileStorage {
             .wrap_err("error checking if path exists")
     }
 
+    #[instrument(err, skip(self), level = "trace")]
+    async fn metadata(&self, key: &str) -> Result<StorageObject, StorageReadError> {
+        let file_meta = match tokio::fs::metadata(self.root.join(key)).await {
+            Ok(file_meta) if file_meta.is_file() => file_meta,
+            Ok(_) => return Err(StorageReadError::FileNotFound(key.to_owned())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(StorageReadError::FileNotFound(key.to_owned()))
+            }
+            Err(err) => return Err(err.into()),
+        };
+        Ok(StorageObject {
+            key: key.to_owned(),
+            size: file_meta.len(),
+            last_modified: file_meta.modified()?.into(),
+        })
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn delete(&self, key: &str) -> Result<()> {
+        match tokio::fs::remove_file(self.root.join(key)).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err).wrap_err("error deleting file"),
+        }
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn delete_prefix(&self, prefix: &str) -> Result<()> {
+        match tokio::fs::remove_dir_all(self.root.join(prefix)).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err).wrap_err("error deleting directory"),
+        }
+    }
+
+    #[instrument(err, skip(self), level = "trace")]
+    async fn list_prefix(&self, prefix: &str) -> Result<Vec<StorageObject>> {
+        let root = self.root.clone();
+        let dir = self.root.join(prefix);
+        tokio::task::spawn_blocking(move || {
+            let mut objects = Vec::default();
+            if !dir.exists() {
+                return Ok(objects);
+            }
+            for entry in walkdir::WalkDir::new(dir.as_std_path()) {
+                let entry = entry.wrap_err("error listing directory")?;
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                // we never create files with non UTF-8 names, so they're not ours
+                let Some(path) = Path::from_path(entry.path()) else {
+                    continue;
+                };
+                let key = path
+                    .strip_prefix(&root)
+                    .wrap_err("listed file is not in storage root")?
+                    .as_str()
+                    .to_owned();
+                let file_meta = entry.metadata().wrap_err("error getting file metadata")?;
+                let modified = file_meta
+                    .modified()
+                    .wrap_err("error getting file modification time")?;
+                objects.push(StorageObject {
+                    key,
+                    size: file_meta.len(),
+                    last_modified: modified.into(),
+                });
+            }
+            Ok(objects)
+        })
+        .await
+        .wrap_err("error listing files")?
+    }
+
     #[instrument(err, skip(self), level = "trace")]
     async fn new_command_out_file(&self, key: &str) -> Result<CommandOutputFile> {
         let path = self.root.join(key);
@@ -183,4 +305,8 @@ impl StorageProvider for LocalFileStorage {
     async fn local_path(&self, key: &str) -> Result<Option<PathBuf>> {
         Ok(Some(self.root.join(key)))
     }
+
+    async fn presigned_url(&self, _key: &str) -> Result<Option<String>> {
+        Ok(None)
+    }
 }
@@ -0,0 +1 @@
+pub mod s3_storage;
@@ -0,0 +1,188 @@
+use std::{sync::Arc, time::Duration};
+
+use camino::Utf8PathBuf as PathBuf;
+use claims::{assert_matches, assert_none, assert_ok, assert_some};
+use object_store::{memory::InMemory, ObjectStore};
+use pretty_assertions::assert_eq;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::{
+    config::S3StorageConfig,
+    core::storage::{S3Storage, StorageCommandOutput, StorageProvider, StorageReadError},
+};
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let tmp = PathBuf::try_from(std::env::temp_dir()).unwrap();
+    tmp.join(format!("myrti_test_s3_{}_{}", name, std::process::id()))
+}
+
+fn in_memory_storage(name: &str, key_prefix: Option<&str>) -> S3Storage {
+    S3Storage::with_store(
+        Arc::new(InMemory::new()),
+        None,
+        key_prefix.map(|p| p.to_owned()),
+        None,
+        scratch_dir(name),
+    )
+    .unwrap()
+}
+
+async fn write(storage: &S3Storage, key: &str, content: &[u8]) {
+    let mut write = storage.open_write_stream(key).await.unwrap();
+    write.write_all(content).await.unwrap();
+    write.shutdown().await.unwrap();
+}
+
+async fn read(storage: &S3Storage, key: &str) -> Vec<u8> {
+    let mut read = storage.open_read_stream(key).await.unwrap();
+    let mut content = Vec::new();
+    read.read_to_end(&mut content).await.unwrap();
+    content
+}
+
+async fn write_read_delete(storage: &S3Storage) {
+    write(storage, "thumb/1_small.webp", b"thumbnail").await;
+    assert!(assert_ok!(storage.exists("thumb/1_small.webp").await));
+    assert_eq!(read(storage, "thumb/1_small.webp").await, b"thumbnail");
+    let object = assert_ok!(storage.metadata("thumb/1_small.webp").await);
+    assert_eq!(object.key, "thumb/1_small.webp");
+    assert_eq!(object.size, 9);
+    let mut range = assert_ok!(storage.open_read_range("thumb/1_small.webp", 2..5).await);
+    let mut content = Vec::new();
+    range.read_to_end(&mut content).await.unwrap();
+    assert_eq!(content, b"umb");
+
+    assert_ok!(storage.delete("thumb/1_small.webp").await);
+    assert!(!assert_ok!(storage.exists("thumb/1_small.webp").await));
+    assert_matches!(
+        storage.open_read_stream("thumb/1_small.webp").await.err(),
+        Some(StorageReadError::FileNotFound(_))
+    );
+    assert_matches!(
+        storage.metadata("thumb/1_small.webp").await.err(),
+        Some(StorageReadError::FileNotFound(_))
+    );
+    // deleting something that doesn't exist is not an error
+    assert_ok!(storage.delete("thumb/1_small.webp").await);
+}
+
+async fn delete_and_list_prefix(storage: &S3Storage) {
+    write(storage, "dash/1/stream.mpd", b"mpd").await;
+    write(storage, "dash/1/1080.mp4", b"video").await;
+    write(storage, "dash/2/stream.mpd", b"mpd").await;
+    write(storage, "thumb/1_large.avif", b"thumbnail").await;
+
+    let mut keys: Vec<String> = assert_ok!(storage.list_prefix("dash").await)
+        .into_iter()
+        .map(|object| object.key)
+        .collect();
+    keys.sort();
+    assert_eq!(
+        keys,
+        vec!["dash/1/1080.mp4", "dash/1/stream.mpd", "dash/2/stream.mpd"]
+    );
+
+    assert_ok!(storage.delete_prefix("dash/1").await);
+    assert!(!assert_ok!(storage.exists("dash/1/stream.mpd").await));
+    assert!(!assert_ok!(storage.exists("dash/1/1080.mp4").await));
+    assert!(assert_ok!(storage.exists("dash/2/stream.mpd").await));
+    assert!(assert_ok!(storage.exists("thumb/1_large.avif").await));
+}
+
+async fn command_out_file_flushed(storage: &S3Storage) {
+    let out_file = assert_ok!(storage.new_command_out_file("image/1_1.avif").await);
+    let path = out_file.path().to_owned();
+    tokio::fs::write(&path, b"image").await.unwrap();
+    // not uploaded until flushed
+    assert!(!assert_ok!(storage.exists("image/1_1.avif").await));
+    assert_eq!(assert_ok!(out_file.size().await), 5);
+    assert_ok!(out_file.flush_to_storage().await);
+    assert_eq!(read(storage, "image/1_1.avif").await, b"image");
+    // the scratch file is cleaned up
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn in_memory_write_read_delete() {
+    write_read_delete(&in_memory_storage("write_read_delete", None)).await;
+}
+
+#[tokio::test]
+async fn in_memory_delete_and_list_prefix() {
+    delete_and_list_prefix(&in_memory_storage("list_prefix", None)).await;
+}
+
+#[tokio::test]
+async fn in_memory_command_out_file_flushed() {
+    command_out_file_flushed(&in_memory_storage("command_out", None)).await;
+}
+
+#[tokio::test]
+async fn key_prefix_is_hidden_from_keys() {
+    let store = Arc::new(InMemory::new());
+    let storage = S3Storage::with_store(
+        store.clone(),
+        None,
+        Some("/myrti/".to_owned()),
+        None,
+        scratch_dir("key_prefix"),
+    )
+    .unwrap();
+    write(&storage, "thumb/1_small.webp", b"thumbnail").await;
+    // written below the prefix in the bucket
+    assert_ok!(
+        store
+            .head(&object_store::path::Path::from("myrti/thumb/1_small.webp"))
+            .await
+    );
+    let objects = assert_ok!(storage.list_prefix("thumb").await);
+    assert_eq!(objects.len(), 1);
+    assert_eq!(objects[0].key, "thumb/1_small.webp");
+}
+
+#[tokio::test]
+async fn no_presigned_url_without_signer() {
+    let storage = in_memory_storage("no_presign", None);
+    assert_none!(assert_ok!(
+        storage.presigned_url("thumb/1_small.webp").await
+    ));
+}
+
+/// Runs against a real S3 compatible server, e.g. a local MinIO:
+/// `docker run -p 9000:9000 minio/minio server /data`, create a bucket and set
+/// MYRTI_TEST_S3_ENDPOINT, MYRTI_TEST_S3_BUCKET, MYRTI_TEST_S3_ACCESS_KEY_ID
+/// and MYRTI_TEST_S3_SECRET_ACCESS_KEY. Skipped if they are not set.
+#[tokio::test]
+async fn s3_server_roundtrip() {
+    let (Ok(endpoint), Ok(bucket), Ok(access_key_id), Ok(secret_access_key)) = (
+        std::env::var("MYRTI_TEST_S3_ENDPOINT"),
+        std::env::var("MYRTI_TEST_S3_BUCKET"),
+        std::env::var("MYRTI_TEST_S3_ACCESS_KEY_ID"),
+        std::env::var("MYRTI_TEST_S3_SECRET_ACCESS_KEY"),
+    ) else {
+        eprintln!("MYRTI_TEST_S3_* not set, skipping");
+        return;
+    };
+    let config = S3StorageConfig {
+        bucket,
+        endpoint: Some(endpoint),
+        region: None,
+        access_key_id: Some(access_key_id),
+        secret_access_key: Some(secret_access_key),
+        key_prefix: Some(format!("myrti_test_{}", std::process::id())),
+        redirect_to_presigned_urls: true,
+        presigned_url_expiry: Duration::from_secs(60),
+    };
+    let storage = S3Storage::new(&config, scratch_dir("server")).unwrap();
+    write_read_delete(&storage).await;
+    delete_and_list_prefix(&storage).await;
+    command_out_file_flushed(&storage).await;
+
+    write(&storage, "thumb/2_small.webp", b"thumbnail").await;
+    let url = assert_some!(assert_ok!(
+        storage.presigned_url("thumb/2_small.webp").await
+    ));
+    assert!(url.contains("thumb/2_small.webp"));
+
+    assert_ok!(storage.delete_prefix("").await);
+}
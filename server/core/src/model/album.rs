@@ -1,6 +1,7 @@
 use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
 
-use super::{AlbumId, AlbumItemId, Asset};
+use super::{AlbumId, AlbumItemId, Asset, AssetRootDirId, AssetType, UserId};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Album {
@@ -9,6 +10,37 @@ pub struct Album {
     pub description: Option<String>,
     pub created_at: DateTime<Utc>,
     pub changed_at: DateTime<Utc>,
+    /// None for albums created before there were users
+    pub owner_id: Option<UserId>,
+    /// Only visible to the owner
+    pub is_private: bool,
+    /// Set for smart albums, whose items are the Assets matching the filter
+    pub smart_filter: Option<SmartAlbumFilter>,
+}
+
+/// Conditions an Asset must all fulfill to be in a smart album, None matches everything.
+/// Stored as JSON in the database.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SmartAlbumFilter {
+    pub taken_after: Option<DateTime<Utc>>,
+    pub taken_before: Option<DateTime<Utc>>,
+    pub root_dir_id: Option<AssetRootDirId>,
+    /// Prefix of the path in the root dir
+    pub path_prefix: Option<String>,
+    /// EXIF Make, compared case insensitively
+    pub camera_make: Option<String>,
+    pub asset_type: Option<AssetType>,
+    pub location: Option<LocationRadius>,
+    pub has_gps: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct LocationRadius {
+    /// multiplied by 10e8 like GpsCoordinates
+    pub lat: i64,
+    /// multiplied by 10e8 like GpsCoordinates
+    pub lon: i64,
+    pub radius_meters: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -68,6 +68,10 @@ pub struct CreateAssetBase {
     /// JSON output of exiftool
     pub exiftool_output: Vec<u8>,
     pub gps_coordinates: Option<GpsCoordinates>,
+    /// Modification time of the file when it was indexed
+    pub file_mtime: Option<DateTime<Utc>>,
+    /// File size in bytes
+    pub file_size: Option<i64>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
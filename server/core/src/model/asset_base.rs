@@ -14,7 +14,6 @@ pub struct AssetBase {
     pub root_dir_id: AssetRootDirId,
     pub file_type: String,
     pub file_path: PathBuf,
-    pub is_hidden: bool,
     pub added_at: DateTime<Utc>,
     pub taken_date: DateTime<Utc>,
     pub timestamp_info: TimestampInfo,
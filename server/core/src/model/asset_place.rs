@@ -0,0 +1,11 @@
+/// Where an Asset was taken, found by reverse geocoding its GPS coordinates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetPlace {
+    /// ISO 3166-1 alpha-2 code
+    pub country_code: String,
+    pub country: String,
+    /// First level administrative division (state, province etc.), if the country has them
+    pub admin_region: Option<String>,
+    /// Nearest city, town or village
+    pub city: String,
+}
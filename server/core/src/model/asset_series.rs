@@ -0,0 +1,13 @@
+use super::{Asset, AssetSeriesId};
+
+/// Photos of a burst or of the same subject, shown as one item in the timeline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetSeries {
+    pub id: AssetSeriesId,
+    /// Created by series detection, dissolving it is remembered
+    pub is_auto: bool,
+    /// Most recent first, like in the timeline
+    pub assets: Vec<Asset>,
+    /// Indices into `assets` of the photos shown in place of the series
+    pub selection_indices: Vec<usize>,
+}
@@ -1,8 +1,8 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::repository::db_entity::DbAssetType;
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Copy, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Copy, Hash)]
 pub enum AssetType {
     Image,
     Video,
@@ -0,0 +1,38 @@
+use camino::Utf8PathBuf as PathBuf;
+
+use super::{AssetId, AssetRootDirId, DuplicateAssetId};
+
+/// A file with the same content as an existing Asset
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DuplicateAsset {
+    pub id: DuplicateAssetId,
+    pub asset_id: AssetId,
+    pub root_dir_id: AssetRootDirId,
+    pub file_path: PathBuf,
+}
+
+/// Space taken up by files with the same content as an Asset's file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicatesReport {
+    /// Most wasted bytes first
+    pub root_dirs: Vec<RootDirDuplicates>,
+    /// Most wasted bytes first
+    pub assets: Vec<AssetDuplicates>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RootDirDuplicates {
+    pub root_dir_id: AssetRootDirId,
+    pub duplicate_count: i64,
+    pub wasted_bytes: i64,
+    /// Duplicates of Assets indexed before file sizes were stored, not in `wasted_bytes`
+    pub unknown_size_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetDuplicates {
+    pub asset_id: AssetId,
+    pub duplicate_count: i64,
+    /// None if the size of the Asset's file is not known
+    pub wasted_bytes: Option<i64>,
+}
@@ -1,15 +1,62 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 
-use super::AssetId;
+use super::{AssetId, FailedJobId};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct FailedThumbnailJob {
+/// Work for an Asset that failed, kept so that it is not retried over and over
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedJob {
+    pub id: FailedJobId,
     pub asset_id: AssetId,
-    pub file_hash: u64,
-    pub date: DateTime<Utc>,
+    pub kind: FailedJobKind,
+    /// Hash of the Asset's file when it failed, the job is retried once the file changes
+    pub file_hash: Option<u64>,
+    pub failure: JobFailure,
+    pub attempts: i32,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+    /// The job is not retried automatically before this
+    pub retry_after: DateTime<Utc>,
+    /// Set by the user to not retry automatically anymore, unless the file changes
+    pub is_ignored: bool,
 }
 
-pub enum FailedVideoPackagingJob {
-    Transcoding,
-    PackageOriginal,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FailedJobKind {
+    Thumbnail,
+    VideoPackaging,
+    ReverseGeocode,
+}
+
+/// Why a job failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JobFailure {
+    pub error: String,
+    /// External program that failed, if the failure came from one
+    pub tool: Option<Tool>,
+    pub command: Option<String>,
+    /// End of what the program wrote to stderr
+    pub stderr: Option<String>,
+}
+
+/// External programs whose failures are recorded and that are retried when their version changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Tool {
+    FFmpeg,
+    ShakaPackager,
+    MpdGenerator,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 3] = [Tool::FFmpeg, Tool::ShakaPackager, Tool::MpdGenerator];
+}
+
+const FIRST_RETRY_DELAY_HOURS: i64 = 1;
+const MAX_RETRY_DELAY_DAYS: i64 = 30;
+
+/// How long to wait before retrying automatically after `attempts` failed attempts.
+/// Doubles with every attempt, starting at an hour and capped at 30 days.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    let delay = Duration::hours(FIRST_RETRY_DELAY_HOURS * 2i64.pow(doublings));
+    delay.min(Duration::days(MAX_RETRY_DELAY_DAYS))
 }
@@ -1,9 +1,11 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 
 macro_rules! impl_id {
     ($ident:ident) => {
-        #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy, Hash, Serialize)]
+        #[derive(
+            Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Copy, Hash, Serialize, Deserialize,
+        )]
         pub struct $ident(pub i64);
 
         impl From<i64> for $ident {
@@ -34,3 +36,8 @@ impl_id!(ImageRepresentationId);
 impl_id!(TimelineGroupItemId);
 impl_id!(TimelineGroupId);
 impl_id!(AssetSeriesId);
+impl_id!(JobId);
+impl_id!(FailedJobId);
+impl_id!(UserId);
+impl_id!(UserTokenId);
+impl_id!(ShareLinkId);
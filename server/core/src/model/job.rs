@@ -0,0 +1,76 @@
+use chrono::{DateTime, Utc};
+
+use super::{AlbumId, AssetId, AssetRootDirId, JobId};
+
+/// Work done by one of the actors, persisted so that it is visible across restarts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub state: JobState,
+    pub subject: JobSubject,
+    pub progress: Option<JobProgress>,
+    /// Set if state is Failed, or Cancelled for a reason other than the user asking for it
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JobKind {
+    IndexAssetRoot,
+    /// Index files reported by the filesystem watcher
+    IndexPaths,
+    /// Check files reported as removed by the filesystem watcher
+    ReconcilePaths,
+    AssetThumbnail,
+    AlbumThumbnail,
+    PackageVideo,
+    ConvertImage,
+    ReverseGeocode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn is_done(&self) -> bool {
+        matches!(
+            self,
+            JobState::Finished | JobState::Failed | JobState::Cancelled
+        )
+    }
+}
+
+/// What a Job works on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JobSubject {
+    Asset(AssetId),
+    Album {
+        album_id: AlbumId,
+        asset_id: AssetId,
+    },
+    AssetRootDir(AssetRootDirId),
+}
+
+/// Progress in a unit that depends on the JobKind:
+/// files scanned for indexing, milliseconds of output for ffmpeg
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobProgress {
+    pub done: i64,
+    /// None if not known (yet), like the number of files in an asset root while walking it
+    pub total: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateJob {
+    pub kind: JobKind,
+    pub subject: JobSubject,
+}
@@ -3,26 +3,40 @@ pub mod repository;
 mod album;
 mod asset;
 mod asset_base;
+mod asset_place;
 mod asset_projections;
 mod asset_root_dir;
+mod asset_series;
 mod asset_thumbnail;
 mod asset_type;
 mod data_dir;
+mod duplicate_asset;
 mod failed_job;
 mod id_types;
+mod job;
+mod near_duplicate;
 mod representation;
+mod share_link;
 mod timeline_group;
+mod user;
 pub use album::*;
 pub use asset::*;
 pub use asset_base::*;
+pub use asset_place::*;
 pub use asset_projections::*;
 pub use asset_root_dir::*;
+pub use asset_series::*;
 pub use asset_thumbnail::*;
 pub use asset_type::*;
 pub use data_dir::*;
+pub use duplicate_asset::*;
 pub use failed_job::*;
 pub use id_types::*;
+pub use job::*;
+pub use near_duplicate::*;
 pub use representation::*;
+pub use share_link::*;
 pub use timeline_group::*;
+pub use user::*;
 
 mod util;
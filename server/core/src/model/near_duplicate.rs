@@ -0,0 +1,11 @@
+use super::{Asset, AssetId};
+
+/// Visually similar Assets, like resized copies, re-exports or shots of a burst
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NearDuplicateGroup {
+    /// Smallest AssetId in the group
+    pub id: AssetId,
+    pub assets: Vec<Asset>,
+    /// The copy to keep: the highest resolution, then the largest file, then the oldest
+    pub suggested_best: AssetId,
+}
@@ -11,22 +11,37 @@ use tracing::instrument;
 
 use crate::model::{
     self,
-    repository::db_entity::{DbAlbum, DbAlbumWithItemCount, DbAsset, DbInsertAlbum},
-    util::datetime_to_db_repr,
-    Album, AlbumId, AlbumItem, AlbumItemId, AlbumItemType, Asset, AssetId,
+    repository::db_entity::{DbAlbum, DbAlbumWithItemCount, DbAsset, DbAssetType, DbInsertAlbum},
+    util::{bool_to_int, datetime_to_db_repr},
+    Album, AlbumId, AlbumItem, AlbumItemId, AlbumItemType, Asset, AssetId, LocationRadius,
+    SmartAlbumFilter, UserId,
 };
 
-use super::{db::DbConn, db_entity::DbAlbumItem, schema};
+use super::{
+    db::DbConn,
+    db_entity::DbAlbumItem,
+    schema,
+    search::{escape_like, exiftool_field},
+    search_index,
+};
 
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct CreateAlbum {
     pub name: Option<String>,
     pub description: Option<String>,
+    pub owner_id: Option<UserId>,
+    pub is_private: bool,
+    /// Creates a smart album, its items are filled in right away
+    pub smart_filter: Option<SmartAlbumFilter>,
 }
 
-/// Get all albums ordered by changed_at (descending)
+/// Get all albums visible to the user ordered by changed_at (descending).
+/// Assets the user has hidden are not counted.
 #[instrument(skip(conn), level = "trace")]
-pub fn get_all_albums_with_asset_count(conn: &mut DbConn) -> Result<Vec<(Album, i64)>> {
+pub fn get_all_albums_with_asset_count(
+    conn: &mut DbConn,
+    user_id: UserId,
+) -> Result<Vec<(Album, i64)>> {
     let mut qb = SqliteQueryBuilder::new();
     qb.push_sql("SELECT ");
     DbAlbum::as_select().to_sql(&mut qb, &diesel::sqlite::Sqlite)?;
@@ -38,13 +53,21 @@ pub fn get_all_albums_with_asset_count(conn: &mut DbConn) -> Result<Vec<(Album,
         (
         SELECT album_id, COUNT(AlbumItem.album_item_id) as item_count
         FROM AlbumItem
+        WHERE NOT EXISTS (
+            SELECT 1 FROM UserAsset
+            WHERE UserAsset.asset_id = AlbumItem.asset_id
+            AND UserAsset.user_id = ?1 AND UserAsset.is_hidden = 1
+        )
         GROUP BY album_id
         ) g
     ON Album.album_id = g.album_id
+    WHERE Album.is_private = 0 OR Album.owner_id = ?1
     ORDER BY Album.changed_at DESC;
     "#,
     );
-    let db_albums: Vec<DbAlbumWithItemCount> = diesel::sql_query(qb.finish()).load(conn)?;
+    let db_albums: Vec<DbAlbumWithItemCount> = diesel::sql_query(qb.finish())
+        .bind::<diesel::sql_types::BigInt, _>(user_id.0)
+        .load(conn)?;
     db_albums
         .into_iter()
         .map(|a| a.album.try_into().map(|album| (album, a.item_count)))
@@ -58,6 +81,19 @@ pub fn get_album(conn: &mut DbConn, album_id: AlbumId) -> Result<Album> {
     db_album.try_into()
 }
 
+/// Private albums are only visible to their owner, the others to everyone
+#[instrument(skip(conn), level = "trace")]
+pub fn is_album_visible(conn: &mut DbConn, album_id: AlbumId, user_id: UserId) -> Result<bool> {
+    use schema::Album;
+    let count: i64 = Album::table
+        .filter(Album::album_id.eq(album_id.0))
+        .filter(Album::is_private.eq(0).or(Album::owner_id.eq(user_id.0)))
+        .count()
+        .get_result(conn)
+        .wrap_err("error querying table Album")?;
+    Ok(count > 0)
+}
+
 #[instrument(err(Debug), skip(conn), level = "trace")]
 pub fn create_album(
     conn: &mut DbConn,
@@ -66,6 +102,12 @@ pub fn create_album(
 ) -> Result<AlbumId> {
     use schema::{Album, AlbumItem};
     let now = datetime_to_db_repr(&Utc::now());
+    let smart_filter = create_album
+        .smart_filter
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+    let is_smart = smart_filter.is_some();
     let album_id: AlbumId = conn.transaction(|conn| {
         let album_id = diesel::insert_into(Album::table)
             .values(DbInsertAlbum {
@@ -74,6 +116,9 @@ pub fn create_album(
                 description: create_album.description.map(Cow::Owned),
                 created_at: now,
                 changed_at: now,
+                owner_id: create_album.owner_id.map(|id| id.0),
+                is_private: bool_to_int(create_album.is_private),
+                smart_filter: smart_filter.map(Cow::Owned),
             })
             .returning(Album::album_id)
             .get_result(conn)
@@ -97,6 +142,10 @@ pub fn create_album(
             })
             .collect::<Result<Vec<_>>>()
             .wrap_err("error inserting one or more AlbumItem")?;
+        if is_smart {
+            refresh_smart_album(conn, album_id)?;
+        }
+        search_index::index_album(conn, album_id)?;
         Ok::<AlbumId, eyre::Report>(album_id)
     })?;
     Ok(album_id)
@@ -111,12 +160,24 @@ struct AlbumItemRow {
     pub asset: Option<DbAsset>,
 }
 
+/// Items of the album, without the Assets the user has hidden
 #[instrument(skip(conn))]
-pub fn get_items_in_album(conn: &mut DbConn, album_id: AlbumId) -> Result<Vec<AlbumItem>> {
-    use schema::{AlbumItem, Asset};
+pub fn get_items_in_album(
+    conn: &mut DbConn,
+    album_id: AlbumId,
+    user_id: UserId,
+) -> Result<Vec<AlbumItem>> {
+    use schema::{AlbumItem, Asset, UserAsset};
+    let hidden_asset_ids = UserAsset::table
+        .filter(UserAsset::user_id.eq(user_id.0))
+        .filter(UserAsset::is_hidden.eq(1))
+        .select(UserAsset::asset_id.nullable());
     let rows: Vec<AlbumItemRow> = AlbumItem::table
         .left_join(Asset::table)
         .filter(AlbumItem::album_id.eq(album_id.0))
+        .filter(AlbumItem::asset_id.is_null().or(diesel::dsl::not(
+            AlbumItem::asset_id.eq_any(hidden_asset_ids),
+        )))
         .order_by(AlbumItem::idx)
         .select(AlbumItemRow::as_select())
         .load(conn)
@@ -187,7 +248,7 @@ pub fn append_assets_to_album(
     asset_ids: &[AssetId],
 ) -> Result<()> {
     use diesel::dsl::max;
-    use schema::{Album, AlbumItem};
+    use schema::AlbumItem;
     conn.transaction(|conn| {
         let last_index: Option<i32> = AlbumItem::table
             .filter(AlbumItem::album_id.eq(album_id.0))
@@ -211,10 +272,7 @@ pub fn append_assets_to_album(
                 Ok(AlbumItemId(album_item_id))
             })
             .collect::<Result<Vec<_>>>()?;
-        let now = datetime_to_db_repr(&Utc::now());
-        diesel::update(Album::table)
-            .set(Album::changed_at.eq(now))
-            .execute(conn)?;
+        album_changed(conn, album_id)?;
         Ok(())
     })
 }
@@ -232,7 +290,7 @@ pub fn append_items_to_album(
     items: &[AddItemToAlbum],
 ) -> Result<()> {
     use diesel::dsl::max;
-    use schema::{Album, AlbumItem};
+    use schema::AlbumItem;
     conn.immediate_transaction(|conn| {
         let last_index: Option<i32> = AlbumItem::table
             .filter(AlbumItem::album_id.eq(album_id.0))
@@ -266,10 +324,7 @@ pub fn append_items_to_album(
                 Ok(AlbumItemId(album_item_id))
             })
             .collect::<Result<Vec<_>>>()?;
-        let now = datetime_to_db_repr(&Utc::now());
-        diesel::update(Album::table)
-            .set(Album::changed_at.eq(now))
-            .execute(conn)?;
+        album_changed(conn, album_id)?;
         Ok(())
     })
 }
@@ -329,7 +384,416 @@ pub fn remove_items_from_album(
         "#)
             .bind::<diesel::sql_types::BigInt, _>(album_id.0)
             .execute(conn).wrap_err("error assigning new AlbumItem indices")?;
+        album_changed(conn, album_id)?;
         Ok::<_, eyre::Report>(())
         })?;
     Ok(())
 }
+
+/// Fields that are None are left unchanged
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpdateAlbum {
+    pub name: Option<String>,
+    /// Some(None) removes the description
+    pub description: Option<Option<String>>,
+    /// Only for smart albums, their items are updated right away
+    pub smart_filter: Option<SmartAlbumFilter>,
+}
+
+#[instrument(skip(conn))]
+pub fn update_album(conn: &mut DbConn, album_id: AlbumId, update: UpdateAlbum) -> Result<()> {
+    use schema::Album;
+    conn.immediate_transaction(|conn| {
+        if let Some(name) = update.name {
+            diesel::update(Album::table.find(album_id.0))
+                .set(Album::name.eq(name))
+                .execute(conn)
+                .wrap_err("error updating Album name")?;
+        }
+        if let Some(description) = update.description {
+            diesel::update(Album::table.find(album_id.0))
+                .set(Album::description.eq(description))
+                .execute(conn)
+                .wrap_err("error updating Album description")?;
+        }
+        if let Some(smart_filter) = update.smart_filter {
+            diesel::update(Album::table.find(album_id.0))
+                .set(Album::smart_filter.eq(serde_json::to_string(&smart_filter)?))
+                .execute(conn)
+                .wrap_err("error updating Album smart_filter")?;
+            refresh_smart_album(conn, album_id)?;
+        }
+        album_changed(conn, album_id)
+    })
+}
+
+/// Move the items to `position` in the album, in the order they are given.
+/// `position` is counted without the moved items and clamped to the end of the album.
+/// Items that are not part of the album are ignored.
+#[instrument(skip(conn))]
+pub fn move_album_items(
+    conn: &mut DbConn,
+    album_id: AlbumId,
+    item_ids: &[AlbumItemId],
+    position: usize,
+) -> Result<()> {
+    conn.immediate_transaction(|conn| {
+        let mut order = get_item_order(conn, album_id)?;
+        let moved: Vec<i64> = item_ids
+            .iter()
+            .map(|id| id.0)
+            .filter(|id| order.contains(id))
+            .collect();
+        order.retain(|id| !moved.contains(id));
+        let position = position.min(order.len());
+        order.splice(position..position, moved);
+        set_item_order(conn, album_id, &order)?;
+        album_changed(conn, album_id)
+    })
+}
+
+/// Insert items at `position` in the album, or append them if it is None or past the end
+#[instrument(skip(conn))]
+pub fn insert_items_into_album(
+    conn: &mut DbConn,
+    album_id: AlbumId,
+    items: &[AddItemToAlbum],
+    position: Option<usize>,
+) -> Result<Vec<AlbumItemId>> {
+    use diesel::dsl::max;
+    use schema::AlbumItem;
+    conn.immediate_transaction(|conn| {
+        let mut order = get_item_order(conn, album_id)?;
+        let last_index: Option<i32> = AlbumItem::table
+            .filter(AlbumItem::album_id.eq(album_id.0))
+            .select(max(AlbumItem::idx))
+            .get_result(conn)?;
+        // insert after the last item, the indices are rewritten below anyway
+        let first_insert_index = last_index.map(|last| last + 1).unwrap_or(0);
+        let inserted_ids = items
+            .iter()
+            .zip(first_insert_index..)
+            .map(|(item, idx)| {
+                let (ty, asset_id, text) = match item {
+                    AddItemToAlbum::Asset(asset_id) => (1, Some(asset_id.0), None),
+                    AddItemToAlbum::Text(text) => (2, None, Some(text.as_str())),
+                };
+                let album_item_id: i64 = diesel::insert_into(AlbumItem::table)
+                    .values((
+                        AlbumItem::album_id.eq(album_id.0),
+                        AlbumItem::ty.eq(ty),
+                        AlbumItem::asset_id.eq(asset_id),
+                        AlbumItem::text.eq(text),
+                        AlbumItem::idx.eq(idx),
+                    ))
+                    .returning(AlbumItem::album_item_id)
+                    .get_result(conn)?;
+                Ok(album_item_id)
+            })
+            .collect::<Result<Vec<i64>>>()
+            .wrap_err("error inserting one or more AlbumItem")?;
+        let position = position.unwrap_or(order.len()).min(order.len());
+        order.splice(position..position, inserted_ids.iter().copied());
+        set_item_order(conn, album_id, &order)?;
+        album_changed(conn, album_id)?;
+        Ok(inserted_ids.into_iter().map(AlbumItemId).collect())
+    })
+}
+
+/// Replace the items of a smart album with the Assets currently matching its filter,
+/// ordered by taken_date. Returns false if nothing changed.
+#[instrument(skip(conn))]
+pub fn refresh_smart_album(conn: &mut DbConn, album_id: AlbumId) -> Result<bool> {
+    use schema::AlbumItem;
+    conn.transaction(|conn| {
+        let album = get_album(conn, album_id)?;
+        let filter = album
+            .smart_filter
+            .ok_or(eyre!("Album {} is not a smart album", album_id))?;
+        let matching = get_smart_album_asset_ids(conn, &filter, None)?;
+        let current: Vec<Option<i64>> = AlbumItem::table
+            .filter(AlbumItem::album_id.eq(album_id.0))
+            .order_by(AlbumItem::idx)
+            .select(AlbumItem::asset_id)
+            .load(conn)
+            .wrap_err("error querying table AlbumItem")?;
+        if current
+            .iter()
+            .copied()
+            .eq(matching.iter().map(|asset_id| Some(asset_id.0)))
+        {
+            return Ok(false);
+        }
+        diesel::delete(AlbumItem::table.filter(AlbumItem::album_id.eq(album_id.0)))
+            .execute(conn)
+            .wrap_err("error deleting from table AlbumItem")?;
+        append_assets_to_album(conn, album_id, &matching)?;
+        Ok(true)
+    })
+}
+
+/// Add the Asset to or remove it from the smart albums it now matches or no longer matches
+/// after creating or changing it, keeping the items ordered by taken_date
+#[instrument(skip(conn))]
+pub fn update_smart_albums_for_asset(conn: &mut DbConn, asset_id: AssetId) -> Result<()> {
+    use schema::{Album, AlbumItem, Asset};
+    conn.transaction(|conn| {
+        let smart_albums: Vec<(i64, String)> = Album::table
+            .filter(Album::smart_filter.is_not_null())
+            .select((Album::album_id, Album::smart_filter.assume_not_null()))
+            .load(conn)
+            .wrap_err("error querying table Album")?;
+        if smart_albums.is_empty() {
+            return Ok(());
+        }
+        let taken_date: i64 = Asset::table
+            .find(asset_id.0)
+            .select(Asset::taken_date)
+            .get_result(conn)
+            .wrap_err("error querying table Asset")?;
+        for (album_id, filter) in smart_albums {
+            let album_id = AlbumId(album_id);
+            let filter: SmartAlbumFilter =
+                serde_json::from_str(&filter).wrap_err("invalid Album.smart_filter")?;
+            let matches = !get_smart_album_asset_ids(conn, &filter, Some(asset_id))?.is_empty();
+            let item_indices: Vec<i32> = AlbumItem::table
+                .filter(AlbumItem::album_id.eq(album_id.0))
+                .filter(AlbumItem::asset_id.eq(asset_id.0))
+                .order_by(AlbumItem::idx.desc())
+                .select(AlbumItem::idx)
+                .load(conn)
+                .wrap_err("error querying table AlbumItem")?;
+            match (matches, item_indices.is_empty()) {
+                (true, true) => {
+                    // before the first item that comes after the Asset
+                    let next_idx: Option<i32> = AlbumItem::table
+                        .inner_join(Asset::table)
+                        .filter(AlbumItem::album_id.eq(album_id.0))
+                        .filter(
+                            Asset::taken_date.gt(taken_date).or(Asset::taken_date
+                                .eq(taken_date)
+                                .and(Asset::asset_id.gt(asset_id.0))),
+                        )
+                        .select(diesel::dsl::min(AlbumItem::idx))
+                        .get_result(conn)
+                        .wrap_err("error querying table AlbumItem")?;
+                    let idx = match next_idx {
+                        Some(idx) => idx,
+                        None => AlbumItem::table
+                            .filter(AlbumItem::album_id.eq(album_id.0))
+                            .select(diesel::dsl::max(AlbumItem::idx))
+                            .get_result::<Option<i32>>(conn)
+                            .wrap_err("error querying table AlbumItem")?
+                            .map(|last| last + 1)
+                            .unwrap_or(0),
+                    };
+                    shift_item_indices(conn, album_id, idx, 1)?;
+                    diesel::insert_into(AlbumItem::table)
+                        .values((
+                            AlbumItem::album_id.eq(album_id.0),
+                            AlbumItem::ty.eq(1),
+                            AlbumItem::asset_id.eq(Some(asset_id.0)),
+                            AlbumItem::text.eq(Option::<String>::None),
+                            AlbumItem::idx.eq(idx),
+                        ))
+                        .execute(conn)
+                        .wrap_err("error inserting into table AlbumItem")?;
+                    album_changed(conn, album_id)?;
+                }
+                (false, false) => {
+                    // from the back so that the indices of the remaining ones don't change
+                    for idx in item_indices {
+                        diesel::delete(
+                            AlbumItem::table
+                                .filter(AlbumItem::album_id.eq(album_id.0))
+                                .filter(AlbumItem::idx.eq(idx)),
+                        )
+                        .execute(conn)
+                        .wrap_err("error deleting from table AlbumItem")?;
+                        shift_item_indices(conn, album_id, idx + 1, -1)?;
+                    }
+                    album_changed(conn, album_id)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Assets matching the filter ordered by taken_date, only checks `only_asset` if given
+fn get_smart_album_asset_ids(
+    conn: &mut DbConn,
+    filter: &SmartAlbumFilter,
+    only_asset: Option<AssetId>,
+) -> Result<Vec<AssetId>> {
+    use diesel::{dsl::sql, sql_types};
+    use schema::Asset;
+    let mut query = Asset::table
+        .select((Asset::asset_id, Asset::gps_latitude, Asset::gps_longitude))
+        .filter(Asset::missing_since.is_null())
+        .order_by((Asset::taken_date, Asset::asset_id))
+        .into_boxed();
+    if let Some(asset_id) = only_asset {
+        query = query.filter(Asset::asset_id.eq(asset_id.0));
+    }
+    if let Some(taken_after) = &filter.taken_after {
+        query = query.filter(Asset::taken_date.ge(datetime_to_db_repr(taken_after)));
+    }
+    if let Some(taken_before) = &filter.taken_before {
+        query = query.filter(Asset::taken_date.lt(datetime_to_db_repr(taken_before)));
+    }
+    if let Some(root_dir_id) = filter.root_dir_id {
+        query = query.filter(Asset::root_dir_id.eq(root_dir_id.0));
+    }
+    if let Some(path_prefix) = &filter.path_prefix {
+        query = query.filter(
+            Asset::file_path
+                .like(format!("{}%", escape_like(path_prefix)))
+                .escape('\\'),
+        );
+    }
+    if let Some(camera_make) = &filter.camera_make {
+        query = query.filter(
+            sql::<sql_types::Bool>(&format!(
+                "LOWER(TRIM({})) = LOWER(",
+                exiftool_field("$[0].EXIF.Make")
+            ))
+            .bind::<sql_types::Text, _>(camera_make.trim().to_owned())
+            .sql(")"),
+        );
+    }
+    if let Some(asset_type) = filter.asset_type {
+        query = query.filter(Asset::ty.eq(DbAssetType::from(asset_type) as i32));
+    }
+    if filter.has_gps == Some(true) || filter.location.is_some() {
+        query = query.filter(Asset::gps_latitude.is_not_null());
+    } else if filter.has_gps == Some(false) {
+        query = query.filter(Asset::gps_latitude.is_null());
+    }
+    let rows: Vec<(i64, Option<i64>, Option<i64>)> = query
+        .load(conn)
+        .wrap_err("error querying Assets for smart album")?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, lat, lon)| match (&filter.location, lat, lon) {
+            (None, _, _) => true,
+            (Some(location), Some(lat), Some(lon)) => is_in_radius(location, *lat, *lon),
+            (Some(_), _, _) => false,
+        })
+        .map(|(asset_id, _, _)| AssetId(asset_id))
+        .collect())
+}
+
+fn is_in_radius(location: &LocationRadius, lat: i64, lon: i64) -> bool {
+    const EARTH_RADIUS_METERS: f64 = 6_371_000.0;
+    let to_radians = |coord: i64| (coord as f64 / 10e8).to_radians();
+    let (lat1, lon1) = (to_radians(location.lat), to_radians(location.lon));
+    let (lat2, lon2) = (to_radians(lat), to_radians(lon));
+    // haversine
+    let a = ((lat2 - lat1) / 2.0).sin().powi(2)
+        + lat1.cos() * lat2.cos() * ((lon2 - lon1) / 2.0).sin().powi(2);
+    let distance = 2.0 * EARTH_RADIUS_METERS * a.sqrt().asin();
+    distance <= location.radius_meters as f64
+}
+
+/// Drop the thumbnails of albums containing the Asset,
+/// they may have been created from a previous version of its file
+#[instrument(skip(conn))]
+pub fn invalidate_album_thumbnails_with_asset(conn: &mut DbConn, asset_id: AssetId) -> Result<()> {
+    use schema::AlbumItem;
+    let album_ids: Vec<i64> = AlbumItem::table
+        .filter(AlbumItem::asset_id.eq(asset_id.0))
+        .select(AlbumItem::album_id)
+        .distinct()
+        .load(conn)
+        .wrap_err("error querying table AlbumItem")?;
+    for album_id in album_ids {
+        album_changed(conn, AlbumId(album_id))?;
+    }
+    Ok(())
+}
+
+/// Also deletes the album's items and thumbnails, share links are deleted by ON DELETE CASCADE
+#[instrument(skip(conn))]
+pub fn delete_album(conn: &mut DbConn, album_id: AlbumId) -> Result<()> {
+    use schema::{Album, AlbumItem, AlbumThumbnail};
+    conn.immediate_transaction(|conn| {
+        diesel::delete(AlbumItem::table.filter(AlbumItem::album_id.eq(album_id.0)))
+            .execute(conn)
+            .wrap_err("error deleting from table AlbumItem")?;
+        diesel::delete(AlbumThumbnail::table.filter(AlbumThumbnail::album_id.eq(album_id.0)))
+            .execute(conn)
+            .wrap_err("error deleting from table AlbumThumbnail")?;
+        diesel::delete(Album::table.find(album_id.0))
+            .execute(conn)
+            .wrap_err("error deleting from table Album")?;
+        search_index::remove_album(conn, album_id)?;
+        Ok(())
+    })
+}
+
+/// AlbumItem ids ordered by idx
+fn get_item_order(conn: &mut DbConn, album_id: AlbumId) -> Result<Vec<i64>> {
+    use schema::AlbumItem;
+    AlbumItem::table
+        .filter(AlbumItem::album_id.eq(album_id.0))
+        .order_by(AlbumItem::idx)
+        .select(AlbumItem::album_item_id)
+        .load(conn)
+        .wrap_err("error querying table AlbumItem")
+}
+
+/// Set AlbumItem.idx to 0..n in the order of `ordered_item_ids`.
+/// Must be called in a transaction.
+fn set_item_order(conn: &mut DbConn, album_id: AlbumId, ordered_item_ids: &[i64]) -> Result<()> {
+    use schema::AlbumItem;
+    // UNIQUE(album_id, idx) is checked for every row, so go through negative indices
+    // that can't collide with the current ones
+    for (new_idx, item_id) in ordered_item_ids.iter().enumerate() {
+        let new_idx = i32::try_from(new_idx)?;
+        diesel::update(AlbumItem::table.find(item_id))
+            .set(AlbumItem::idx.eq(-new_idx - 1))
+            .execute(conn)
+            .wrap_err("error updating AlbumItem idx")?;
+    }
+    diesel::sql_query("UPDATE AlbumItem SET idx = -idx - 1 WHERE album_id = ?;")
+        .bind::<diesel::sql_types::BigInt, _>(album_id.0)
+        .execute(conn)
+        .wrap_err("error updating AlbumItem idx")?;
+    Ok(())
+}
+
+/// Add `by` to the idx of the album's items at `from_idx` and after.
+/// Must be called in a transaction.
+fn shift_item_indices(conn: &mut DbConn, album_id: AlbumId, from_idx: i32, by: i32) -> Result<()> {
+    // through negative indices like in set_item_order
+    diesel::sql_query("UPDATE AlbumItem SET idx = -(idx + ?) - 1 WHERE album_id = ? AND idx >= ?;")
+        .bind::<diesel::sql_types::Integer, _>(by)
+        .bind::<diesel::sql_types::BigInt, _>(album_id.0)
+        .bind::<diesel::sql_types::Integer, _>(from_idx)
+        .execute(conn)
+        .wrap_err("error updating AlbumItem idx")?;
+    diesel::sql_query("UPDATE AlbumItem SET idx = -idx - 1 WHERE album_id = ? AND idx < 0;")
+        .bind::<diesel::sql_types::BigInt, _>(album_id.0)
+        .execute(conn)
+        .wrap_err("error updating AlbumItem idx")?;
+    Ok(())
+}
+
+/// Bump changed_at so that clients notice and drop the thumbnail,
+/// it is created again from the (maybe different) first asset.
+/// Also updates the album's row in the search index.
+fn album_changed(conn: &mut DbConn, album_id: AlbumId) -> Result<()> {
+    use schema::{Album, AlbumThumbnail};
+    let now = datetime_to_db_repr(&Utc::now());
+    diesel::update(Album::table.find(album_id.0))
+        .set(Album::changed_at.eq(now))
+        .execute(conn)
+        .wrap_err("error updating Album changed_at")?;
+    diesel::delete(AlbumThumbnail::table.filter(AlbumThumbnail::album_id.eq(album_id.0)))
+        .execute(conn)
+        .wrap_err("error deleting from table AlbumThumbnail")?;
+    search_index::index_album(conn, album_id)?;
+    Ok(())
+}
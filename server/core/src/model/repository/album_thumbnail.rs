@@ -53,3 +53,12 @@ pub fn get_albums_with_missing_thumbnails(conn: &mut DbConn) -> Result<Vec<Album
         .load(conn).wrap_err("error querying for Albums with missing thumbnails")?;
     Ok(rows.into_iter().map(|r| AlbumId(r.album_id)).collect())
 }
+
+#[tracing::instrument(skip(conn))]
+pub fn get_all_album_thumbnail_keys(conn: &mut DbConn) -> Result<Vec<String>> {
+    use schema::AlbumThumbnail;
+    AlbumThumbnail::table
+        .select(AlbumThumbnail::file_key)
+        .load(conn)
+        .wrap_err("error querying table AlbumThumbnail")
+}
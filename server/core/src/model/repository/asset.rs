@@ -1,7 +1,7 @@
 use std::borrow::Cow;
 
-use camino::Utf8Path as Path;
-use chrono::Utc;
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use chrono::{DateTime, Utc};
 use color_eyre::eyre;
 use diesel::dsl::sql;
 use diesel::sql_types::Bool;
@@ -11,16 +11,20 @@ use tracing::instrument;
 
 use crate::model::{
     self, Asset, AssetId, AssetPathOnDisk, AssetRootDirId, AssetThumbnail, AssetThumbnailId,
-    AssetType, CreateAsset, CreateAssetSpe, TimestampInfo, VideoAsset,
+    AssetType, CreateAsset, CreateAssetSpe, TimestampInfo, UserId, VideoAsset,
 };
 use crate::model::{
     repository::db_entity::{to_db_asset_ty, DbAssetPathOnDisk, DbAssetThumbnail},
-    util::{bool_to_int, datetime_to_db_repr, hash_u64_to_vec8, to_db_thumbnail_type},
+    util::{
+        bool_to_int, datetime_from_db_repr, datetime_to_db_repr, hash_u64_to_vec8,
+        hash_vec8_to_u64, to_db_thumbnail_type,
+    },
 };
 
 use super::db::DbConn;
 use super::db_entity::{to_db_timezone_info, DbAsset, DbInsertAsset};
 use super::schema;
+use super::search_index;
 
 #[instrument(skip(conn))]
 pub fn get_asset(conn: &mut DbConn, id: AssetId) -> Result<Asset> {
@@ -41,20 +45,29 @@ pub fn get_asset_with_hash(conn: &mut DbConn, with_hash: u64) -> Result<Option<A
     Ok(maybe_id.map(AssetId))
 }
 
+/// Path of the file served as the Asset's original,
+/// other paths of identical files are in DuplicateAsset
 #[instrument(skip(conn))]
 pub fn get_asset_path_on_disk(conn: &mut DbConn, id: AssetId) -> Result<AssetPathOnDisk> {
+    find_asset_path_on_disk(conn, id)?.ok_or(eyre::eyre!("no Asset with id {}", id))
+}
+
+/// Like `get_asset_path_on_disk`, but returns None if the Asset does not exist
+#[instrument(skip(conn))]
+pub fn find_asset_path_on_disk(conn: &mut DbConn, id: AssetId) -> Result<Option<AssetPathOnDisk>> {
     use schema::Asset;
     use schema::AssetRootDir;
-    let asset: DbAssetPathOnDisk = Asset::table
+    let asset: Option<DbAssetPathOnDisk> = Asset::table
         .inner_join(AssetRootDir::table)
         .filter(Asset::asset_id.eq(id.0))
         .select((Asset::asset_id, Asset::file_path, AssetRootDir::path))
-        .first(conn)?;
-    Ok(AssetPathOnDisk {
+        .first(conn)
+        .optional()?;
+    Ok(asset.map(|asset| AssetPathOnDisk {
         id: AssetId(asset.asset_id),
         path_in_asset_root: asset.path_in_asset_root.into(),
         asset_root_path: asset.asset_root_path.into(),
-    })
+    }))
 }
 
 #[instrument(skip(conn))]
@@ -124,6 +137,7 @@ pub fn get_assets_with_missing_thumbnail(
             .filter(not(exists(
                 AssetThumbnail::table.filter(AssetThumbnail::asset_id.eq(Asset::asset_id)),
             )))
+            .filter(Asset::missing_since.is_null())
             .select(AssetIdRow::as_select())
             .load(conn)
             .wrap_err("error querying for Assets with zero thumbnails")?
@@ -139,6 +153,10 @@ pub fn get_assets_with_missing_thumbnail(
         WHERE at.asset_id = AssetThumbnail.asset_id
         GROUP BY at.asset_id
     ) < $1
+    AND EXISTS (
+        SELECT * FROM Asset
+        WHERE Asset.asset_id = AssetThumbnail.asset_id AND Asset.missing_since IS NULL
+    )
     ORDER BY AssetThumbnail.asset_id;
     "#,
     )
@@ -199,8 +217,76 @@ pub fn get_thumbnails_for_asset(
         .collect::<Result<Vec<_>>>()
 }
 
+#[instrument(skip(conn))]
+pub fn get_all_thumbnails(conn: &mut DbConn) -> Result<Vec<AssetThumbnail>> {
+    use schema::AssetThumbnail;
+    let rows: Vec<DbAssetThumbnail> = AssetThumbnail::table
+        .select(DbAssetThumbnail::as_select())
+        .get_results(conn)
+        .wrap_err("error querying table AssetThumbnail")?;
+    rows.into_iter()
+        .map(|r| r.try_into())
+        .collect::<Result<Vec<_>>>()
+}
+
 #[instrument(skip(conn))]
 pub fn create_asset(conn: &mut DbConn, create_asset: CreateAsset) -> Result<AssetId> {
+    let insertable = to_db_insert_asset(&create_asset);
+    let id: i64 = insert_into(schema::Asset::table)
+        .values(&insertable)
+        .returning(schema::Asset::asset_id)
+        .get_result(conn)
+        .wrap_err("error inserting Asset")?;
+    search_index::index_asset(conn, AssetId(id))?;
+    Ok(AssetId(id))
+}
+
+/// Overwrite everything read from the Asset's file after the file was changed.
+/// The id and everything referencing it (albums, groups etc.) stay the same,
+/// as do the path and per user state like hidden flags.
+/// Thumbnails of albums containing the Asset are dropped to be created again.
+#[instrument(skip(conn, create_asset))]
+pub fn update_asset_from_file(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+    create_asset: &CreateAsset,
+) -> Result<()> {
+    use schema::Asset;
+    let row = to_db_insert_asset(create_asset);
+    conn.transaction(|conn| {
+        diesel::update(Asset::table.find(asset_id.0))
+            .set((
+                Asset::ty.eq(row.ty),
+                Asset::file_type.eq(&row.file_type),
+                Asset::hash.eq(&row.hash),
+                Asset::taken_date.eq(row.taken_date),
+                Asset::timezone_offset.eq(&row.timezone_offset),
+                Asset::timezone_info.eq(row.timezone_info),
+                Asset::width.eq(row.width),
+                Asset::height.eq(row.height),
+                // reset, any correction was for the old file
+                Asset::rotation_correction.eq(row.rotation_correction),
+                Asset::exiftool_output.eq(&row.exiftool_output),
+                Asset::gps_latitude.eq(row.gps_latitude),
+                Asset::gps_longitude.eq(row.gps_longitude),
+                Asset::image_format_name.eq(&row.image_format_name),
+                Asset::ffprobe_output.eq(&row.ffprobe_output),
+                Asset::video_codec_name.eq(&row.video_codec_name),
+                Asset::video_bitrate.eq(row.video_bitrate),
+                Asset::video_duration_ms.eq(row.video_duration_ms),
+                Asset::audio_codec_name.eq(&row.audio_codec_name),
+                Asset::has_dash.eq(row.has_dash),
+                Asset::file_mtime.eq(row.file_mtime),
+                Asset::file_size.eq(row.file_size),
+            ))
+            .execute(conn)
+            .wrap_err("error updating Asset")?;
+        search_index::index_asset(conn, asset_id)?;
+        super::album::invalidate_album_thumbnails_with_asset(conn, asset_id)
+    })
+}
+
+fn to_db_insert_asset(create_asset: &CreateAsset) -> DbInsertAsset<'_> {
     let timezone_offset: Option<_> = match create_asset.base.timestamp_info {
         TimestampInfo::TzCertain(tz)
         | TimestampInfo::TzSetByUser(tz)
@@ -208,14 +294,14 @@ pub fn create_asset(conn: &mut DbConn, create_asset: CreateAsset) -> Result<Asse
         | TimestampInfo::TzGuessedLocal(tz) => Some(Cow::Owned(tz.to_string())),
         TimestampInfo::UtcCertain | TimestampInfo::NoTimestamp => None,
     };
-    let insertable: DbInsertAsset = DbInsertAsset {
+    DbInsertAsset {
         asset_id: None,
         ty: to_db_asset_ty(match &create_asset.spe {
             CreateAssetSpe::Image(_) => AssetType::Image,
             CreateAssetSpe::Video(_) => AssetType::Video,
         }),
         root_dir_id: create_asset.base.root_dir_id.0,
-        file_type: create_asset.base.file_type.into(),
+        file_type: create_asset.base.file_type.as_str().into(),
         file_path: create_asset.base.file_path.as_str().into(),
         is_hidden: bool_to_int(false),
         hash: create_asset
@@ -272,13 +358,14 @@ pub fn create_asset(conn: &mut DbConn, create_asset: CreateAsset) -> Result<Asse
             CreateAssetSpe::Image(_) => None,
             CreateAssetSpe::Video(video) => Some(bool_to_int(video.has_dash)),
         },
-    };
-    let id: i64 = insert_into(schema::Asset::table)
-        .values(&insertable)
-        .returning(schema::Asset::asset_id)
-        .get_result(conn)
-        .wrap_err("error inserting Asset")?;
-    Ok(AssetId(id))
+
+        file_mtime: create_asset
+            .base
+            .file_mtime
+            .as_ref()
+            .map(datetime_to_db_repr),
+        file_size: create_asset.base.file_size,
+    }
 }
 
 #[instrument(skip(conn))]
@@ -310,13 +397,55 @@ pub fn set_asset_has_dash(conn: &mut DbConn, asset_id: AssetId, has_dash: bool)
     Ok(())
 }
 
+#[instrument(skip(conn))]
+pub fn set_perceptual_hash(conn: &mut DbConn, asset_id: AssetId, hash: u64) -> Result<()> {
+    use schema::Asset;
+    diesel::update(Asset::table.find(asset_id.0))
+        .set(Asset::perceptual_hash.eq(hash_u64_to_vec8(hash)))
+        .execute(conn)
+        .wrap_err("error setting Asset perceptual_hash")?;
+    Ok(())
+}
+
+/// Perceptual hashes of all Assets that have one and whose file is not missing
+#[instrument(skip(conn))]
+pub fn get_perceptual_hashes(conn: &mut DbConn) -> Result<Vec<(AssetId, u64)>> {
+    use schema::Asset;
+    let rows: Vec<(i64, Option<Vec<u8>>)> = Asset::table
+        .filter(Asset::perceptual_hash.is_not_null())
+        .filter(Asset::missing_since.is_null())
+        .select((Asset::asset_id, Asset::perceptual_hash))
+        .load(conn)
+        .wrap_err("error querying Asset perceptual hashes")?;
+    rows.into_iter()
+        .filter_map(|(id, hash)| hash.map(|hash| (id, hash)))
+        .map(|(id, hash)| Ok((AssetId(id), hash_vec8_to_u64(hash)?)))
+        .collect()
+}
+
+/// Images indexed before perceptual hashes were computed along with thumbnails.
+/// Videos are left out, their hash is computed from the snapshot for the thumbnail.
+#[instrument(skip(conn))]
+pub fn get_image_assets_without_perceptual_hash(conn: &mut DbConn) -> Result<Vec<AssetId>> {
+    use schema::Asset;
+    let ids: Vec<i64> = Asset::table
+        .filter(Asset::ty.eq(to_db_asset_ty(AssetType::Image)))
+        .filter(Asset::perceptual_hash.is_null())
+        .filter(Asset::missing_since.is_null())
+        .select(Asset::asset_id)
+        .load(conn)
+        .wrap_err("error querying Assets without perceptual hash")?;
+    Ok(ids.into_iter().map(AssetId).collect())
+}
+
 #[instrument(skip(conn))]
 pub fn get_video_assets_without_dash(conn: &mut DbConn) -> Result<Vec<VideoAsset>> {
     use schema::Asset::dsl::*;
     let db_assets: Vec<DbAsset> = Asset
         .filter(
             ty.eq(to_db_asset_ty(AssetType::Video))
-                .and(has_dash.eq(bool_to_int(false))),
+                .and(has_dash.eq(bool_to_int(false)))
+                .and(missing_since.is_null()),
         )
         .select(DbAsset::as_select())
         .load(conn)?;
@@ -343,6 +472,7 @@ pub fn get_video_assets_with_no_acceptable_repr(conn: &mut DbConn) -> Result<Vec
     let query = Asset::table
         .select(DbAsset::as_select())
         .filter(Asset::ty.eq(to_db_asset_ty(AssetType::Video)))
+        .filter(Asset::missing_since.is_null())
         .filter(sql::<Bool>(
             r#"
             (
@@ -394,7 +524,8 @@ pub fn get_videos_in_acceptable_codec_without_dash(conn: &mut DbConn) -> Result<
             Asset::ty
                 .eq(to_db_asset_ty(AssetType::Video))
                 .and(Asset::has_dash.assume_not_null().eq(bool_to_int(false)))
-                .and(Asset::file_type.eq("mp4")),
+                .and(Asset::file_type.eq("mp4"))
+                .and(Asset::missing_since.is_null()),
         )
         .filter(
             sql::<Bool>(r#"
@@ -423,6 +554,7 @@ pub fn get_image_assets_with_no_acceptable_repr(
     use schema::{Asset, ImageRepresentation};
     let asset_ids: Vec<i64> = Asset::table
         .filter(Asset::ty.eq(to_db_asset_ty(AssetType::Image)))
+        .filter(Asset::missing_since.is_null())
         .filter(not(Asset::image_format_name
             .assume_not_null()
             .eq_any(acceptable_codecs)))
@@ -449,15 +581,143 @@ pub fn get_ffprobe_output(conn: &mut DbConn, asset_id: AssetId) -> Result<Vec<u8
 }
 
 #[instrument(skip(conn))]
-pub fn set_assets_hidden(conn: &mut DbConn, set_hidden: bool, asset_ids: &[AssetId]) -> Result<()> {
+pub fn get_video_duration_ms(conn: &mut DbConn, asset_id: AssetId) -> Result<Option<i64>> {
     use schema::Asset;
-    diesel::update(Asset::table.filter(Asset::asset_id.eq_any(asset_ids.iter().map(|id| id.0))))
-        .set(Asset::is_hidden.eq(bool_to_int(set_hidden)))
-        .execute(conn)
-        .wrap_err("error updating column Asset.is_hidden")?;
+    let duration_ms: Option<i64> = Asset::table
+        .find(asset_id.0)
+        .select(Asset::video_duration_ms)
+        .first(conn)
+        .wrap_err("error querying column Asset.video_duration_ms")?;
+    Ok(duration_ms)
+}
+
+/// Hidden Assets are not shown to the user in the timeline or in albums
+#[instrument(skip(conn))]
+pub fn set_assets_hidden(
+    conn: &mut DbConn,
+    user_id: UserId,
+    set_hidden: bool,
+    asset_ids: &[AssetId],
+) -> Result<()> {
+    use schema::UserAsset;
+    conn.immediate_transaction(|conn| {
+        for asset_id in asset_ids {
+            diesel::insert_into(UserAsset::table)
+                .values((
+                    UserAsset::user_id.eq(user_id.0),
+                    UserAsset::asset_id.eq(asset_id.0),
+                    UserAsset::is_hidden.eq(bool_to_int(set_hidden)),
+                    UserAsset::is_archived.eq(0),
+                ))
+                .on_conflict((UserAsset::user_id, UserAsset::asset_id))
+                .do_update()
+                .set(UserAsset::is_hidden.eq(bool_to_int(set_hidden)))
+                .execute(conn)
+                .wrap_err("error updating column UserAsset.is_hidden")?;
+        }
+        delete_unused_user_assets(conn, user_id)
+    })
+}
+
+/// Archived Assets are not shown to the user in the timeline, but still in albums
+#[instrument(skip(conn))]
+pub fn set_assets_archived(
+    conn: &mut DbConn,
+    user_id: UserId,
+    set_archived: bool,
+    asset_ids: &[AssetId],
+) -> Result<()> {
+    use schema::UserAsset;
+    conn.immediate_transaction(|conn| {
+        for asset_id in asset_ids {
+            diesel::insert_into(UserAsset::table)
+                .values((
+                    UserAsset::user_id.eq(user_id.0),
+                    UserAsset::asset_id.eq(asset_id.0),
+                    UserAsset::is_hidden.eq(0),
+                    UserAsset::is_archived.eq(bool_to_int(set_archived)),
+                ))
+                .on_conflict((UserAsset::user_id, UserAsset::asset_id))
+                .do_update()
+                .set(UserAsset::is_archived.eq(bool_to_int(set_archived)))
+                .execute(conn)
+                .wrap_err("error updating column UserAsset.is_archived")?;
+        }
+        delete_unused_user_assets(conn, user_id)
+    })
+}
+
+/// Rows without any flag set are not needed
+fn delete_unused_user_assets(conn: &mut DbConn, user_id: UserId) -> Result<()> {
+    use schema::UserAsset;
+    diesel::delete(
+        UserAsset::table
+            .filter(UserAsset::user_id.eq(user_id.0))
+            .filter(UserAsset::is_hidden.eq(0))
+            .filter(UserAsset::is_archived.eq(0)),
+    )
+    .execute(conn)
+    .wrap_err("error deleting from table UserAsset")?;
     Ok(())
 }
 
+/// Whether the Asset exists and the user may see it:
+/// not hidden by them and not in another user's private Album
+#[instrument(skip(conn))]
+pub fn is_asset_visible(conn: &mut DbConn, asset_id: AssetId, user_id: UserId) -> Result<bool> {
+    use diesel::dsl::{exists, not};
+    use schema::{Album, AlbumItem, Asset, UserAsset};
+    let hidden = UserAsset::table
+        .filter(UserAsset::asset_id.eq(asset_id.0))
+        .filter(UserAsset::user_id.eq(user_id.0))
+        .filter(UserAsset::is_hidden.eq(1));
+    let in_private_album = AlbumItem::table
+        .inner_join(Album::table)
+        .filter(AlbumItem::asset_id.eq(asset_id.0))
+        .filter(Album::is_private.eq(1))
+        .filter(Album::owner_id.ne(user_id.0));
+    let visible: bool = diesel::select(
+        exists(Asset::table.find(asset_id.0))
+            .and(not(exists(hidden)))
+            .and(not(exists(in_private_album))),
+    )
+    .get_result(conn)
+    .wrap_err("error querying Asset visibility")?;
+    Ok(visible)
+}
+
+/// Assets the user has hidden, most recent first
+#[instrument(skip(conn))]
+pub fn get_hidden_assets(conn: &mut DbConn, user_id: UserId) -> Result<Vec<Asset>> {
+    use schema::{Asset, UserAsset};
+    let db_assets: Vec<DbAsset> = UserAsset::table
+        .inner_join(Asset::table)
+        .filter(UserAsset::user_id.eq(user_id.0))
+        .filter(UserAsset::is_hidden.eq(1))
+        .filter(Asset::missing_since.is_null())
+        .order_by(Asset::taken_date.desc())
+        .select(DbAsset::as_select())
+        .load(conn)
+        .wrap_err("error querying table UserAsset")?;
+    db_assets.into_iter().map(|a| a.try_into()).collect()
+}
+
+/// Assets the user has archived, most recent first
+#[instrument(skip(conn))]
+pub fn get_archived_assets(conn: &mut DbConn, user_id: UserId) -> Result<Vec<Asset>> {
+    use schema::{Asset, UserAsset};
+    let db_assets: Vec<DbAsset> = UserAsset::table
+        .inner_join(Asset::table)
+        .filter(UserAsset::user_id.eq(user_id.0))
+        .filter(UserAsset::is_archived.eq(1))
+        .filter(Asset::missing_since.is_null())
+        .order_by(Asset::taken_date.desc())
+        .select(DbAsset::as_select())
+        .load(conn)
+        .wrap_err("error querying table UserAsset")?;
+    db_assets.into_iter().map(|a| a.try_into()).collect()
+}
+
 #[instrument(skip(conn))]
 pub fn set_asset_rotation_correction(
     conn: &mut DbConn,
@@ -471,3 +731,264 @@ pub fn set_asset_rotation_correction(
         .wrap_err("error updating column Asset.rotation_correction")?;
     Ok(())
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetFileInRoot {
+    pub asset_id: AssetId,
+    pub path_in_root: PathBuf,
+    pub is_missing: bool,
+}
+
+#[instrument(skip(conn))]
+pub fn get_asset_files_in_root(
+    conn: &mut DbConn,
+    root_dir_id: AssetRootDirId,
+) -> Result<Vec<AssetFileInRoot>> {
+    use schema::Asset;
+    let rows: Vec<(i64, String, Option<i64>)> = Asset::table
+        .filter(Asset::root_dir_id.eq(root_dir_id.0))
+        .select((Asset::asset_id, Asset::file_path, Asset::missing_since))
+        .load(conn)
+        .wrap_err("error querying table Asset")?;
+    Ok(rows
+        .into_iter()
+        .map(|(asset_id, file_path, missing_since)| AssetFileInRoot {
+            asset_id: AssetId(asset_id),
+            path_in_root: file_path.into(),
+            is_missing: missing_since.is_some(),
+        })
+        .collect())
+}
+
+/// Mark assets whose file no longer exists as missing since `missing_since`, or clear the flag
+/// if it is None. Assets that are already missing keep their original timestamp.
+/// Smart albums are updated for all of them.
+#[instrument(skip(conn))]
+pub fn set_assets_missing(
+    conn: &mut DbConn,
+    asset_ids: &[AssetId],
+    missing_since: Option<DateTime<Utc>>,
+) -> Result<()> {
+    use schema::Asset;
+    let ids = asset_ids.iter().map(|id| id.0);
+    match missing_since {
+        Some(missing_since) => diesel::update(
+            Asset::table
+                .filter(Asset::asset_id.eq_any(ids))
+                .filter(Asset::missing_since.is_null()),
+        )
+        .set(Asset::missing_since.eq(datetime_to_db_repr(&missing_since)))
+        .execute(conn),
+        None => diesel::update(Asset::table.filter(Asset::asset_id.eq_any(ids)))
+            .set(Asset::missing_since.eq(None::<i64>))
+            .execute(conn),
+    }
+    .wrap_err("error updating column Asset.missing_since")?;
+    for asset_id in asset_ids {
+        super::album::update_smart_albums_for_asset(conn, *asset_id)?;
+    }
+    Ok(())
+}
+
+/// Point an asset to a new file, used when it was moved or renamed.
+/// Also clears the missing flag and updates smart albums for the new path.
+#[instrument(skip(conn))]
+pub fn set_asset_path(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+    root_dir_id: AssetRootDirId,
+    path_in_root: &Path,
+) -> Result<()> {
+    use schema::Asset;
+    diesel::update(Asset::table.find(asset_id.0))
+        .set((
+            Asset::root_dir_id.eq(root_dir_id.0),
+            Asset::file_path.eq(path_in_root.as_str()),
+            Asset::missing_since.eq(None::<i64>),
+        ))
+        .execute(conn)
+        .wrap_err("error updating Asset path")?;
+    search_index::index_asset(conn, asset_id)?;
+    super::album::update_smart_albums_for_asset(conn, asset_id)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetFileState {
+    pub asset_id: AssetId,
+    pub hash: Option<u64>,
+    pub file_mtime: Option<DateTime<Utc>>,
+    pub file_size: Option<i64>,
+    pub is_missing: bool,
+}
+
+type AssetFileStateRow = (i64, Option<Vec<u8>>, Option<i64>, Option<i64>, Option<i64>);
+
+fn asset_file_state_from_row(
+    (asset_id, hash, file_mtime, file_size, missing_since): AssetFileStateRow,
+) -> Result<AssetFileState> {
+    Ok(AssetFileState {
+        asset_id: AssetId(asset_id),
+        hash: hash.map(hash_vec8_to_u64).transpose()?,
+        file_mtime: file_mtime.map(datetime_from_db_repr).transpose()?,
+        file_size,
+        is_missing: missing_since.is_some(),
+    })
+}
+
+#[instrument(skip(conn))]
+pub fn get_asset_file_state_with_path(
+    conn: &mut DbConn,
+    root_dir_id: AssetRootDirId,
+    path_in_root: &Path,
+) -> Result<Option<AssetFileState>> {
+    use schema::Asset;
+    let row: Option<AssetFileStateRow> = Asset::table
+        .filter(Asset::root_dir_id.eq(root_dir_id.0))
+        .filter(Asset::file_path.eq(path_in_root.as_str()))
+        .select((
+            Asset::asset_id,
+            Asset::hash,
+            Asset::file_mtime,
+            Asset::file_size,
+            Asset::missing_since,
+        ))
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table Asset")?;
+    row.map(asset_file_state_from_row).transpose()
+}
+
+#[instrument(skip(conn))]
+pub fn get_asset_file_state(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+) -> Result<Option<AssetFileState>> {
+    use schema::Asset;
+    let row: Option<AssetFileStateRow> = Asset::table
+        .find(asset_id.0)
+        .select((
+            Asset::asset_id,
+            Asset::hash,
+            Asset::file_mtime,
+            Asset::file_size,
+            Asset::missing_since,
+        ))
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table Asset")?;
+    row.map(asset_file_state_from_row).transpose()
+}
+
+/// Store modification time and size of an Asset's file, used when the file was touched
+/// but its contents are unchanged
+#[instrument(skip(conn))]
+pub fn set_asset_file_stat(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+    file_mtime: DateTime<Utc>,
+    file_size: i64,
+) -> Result<()> {
+    use schema::Asset;
+    diesel::update(Asset::table.find(asset_id.0))
+        .set((
+            Asset::file_mtime.eq(datetime_to_db_repr(&file_mtime)),
+            Asset::file_size.eq(file_size),
+        ))
+        .execute(conn)
+        .wrap_err("error updating Asset file_mtime and file_size")?;
+    Ok(())
+}
+
+#[instrument(skip(conn))]
+pub fn get_assets_missing_since_before(
+    conn: &mut DbConn,
+    before: DateTime<Utc>,
+) -> Result<Vec<AssetId>> {
+    use schema::Asset;
+    let ids: Vec<i64> = Asset::table
+        .filter(Asset::missing_since.le(datetime_to_db_repr(&before)))
+        .select(Asset::asset_id)
+        .load(conn)
+        .wrap_err("error querying table Asset")?;
+    Ok(ids.into_iter().map(AssetId).collect())
+}
+
+/// Delete thumbnails, representations and failed job records of an Asset,
+/// so that they are created again from the Asset's current file.
+/// Files in storage must be deleted by the caller.
+#[instrument(skip(conn))]
+pub fn delete_asset_derived_data(conn: &mut DbConn, asset_id: AssetId) -> Result<()> {
+    use schema::Asset;
+    conn.immediate_transaction(|conn| {
+        delete_derived_rows(conn, asset_id.0)?;
+        // only videos have a non null has_dash
+        diesel::update(
+            Asset::table
+                .find(asset_id.0)
+                .filter(Asset::has_dash.is_not_null()),
+        )
+        .set(Asset::has_dash.eq(bool_to_int(false)))
+        .execute(conn)?;
+        // recomputed with the thumbnails
+        diesel::update(Asset::table.find(asset_id.0))
+            .set(Asset::perceptual_hash.eq(None::<Vec<u8>>))
+            .execute(conn)?;
+        Ok::<_, diesel::result::Error>(())
+    })
+    .wrap_err("error deleting Asset thumbnails and representations")?;
+    Ok(())
+}
+
+fn delete_derived_rows(conn: &mut DbConn, id: i64) -> Result<(), diesel::result::Error> {
+    use schema::{
+        AssetThumbnail, AudioRepresentation, FailedJob, ImageRepresentation, VideoRepresentation,
+    };
+    diesel::delete(AssetThumbnail::table.filter(AssetThumbnail::asset_id.eq(id))).execute(conn)?;
+    diesel::delete(VideoRepresentation::table.filter(VideoRepresentation::asset_id.eq(id)))
+        .execute(conn)?;
+    diesel::delete(AudioRepresentation::table.filter(AudioRepresentation::asset_id.eq(id)))
+        .execute(conn)?;
+    diesel::delete(ImageRepresentation::table.filter(ImageRepresentation::asset_id.eq(id)))
+        .execute(conn)?;
+    diesel::delete(FailedJob::table.filter(FailedJob::asset_id.eq(id))).execute(conn)?;
+    Ok(())
+}
+
+/// Delete an Asset and all rows referencing it.
+/// Files in storage (thumbnails, representations) must be deleted by the caller.
+#[instrument(skip(conn))]
+pub fn delete_asset(conn: &mut DbConn, asset_id: AssetId) -> Result<()> {
+    use schema::{
+        AlbumItem, AssetPlace, DeletedAutoAssetSeries, DuplicateAsset, MotionPhotoVideoFile,
+        NearDuplicate, TimelineGroupItem,
+    };
+    conn.immediate_transaction(|conn| {
+        let id = asset_id.0;
+        delete_derived_rows(conn, id)?;
+        diesel::delete(MotionPhotoVideoFile::table.filter(MotionPhotoVideoFile::asset_id.eq(id)))
+            .execute(conn)?;
+        diesel::delete(DuplicateAsset::table.filter(DuplicateAsset::asset_id.eq(id)))
+            .execute(conn)?;
+        diesel::delete(NearDuplicate::table.filter(NearDuplicate::asset_id.eq(id)))
+            .execute(conn)?;
+        diesel::delete(AssetPlace::table.find(id)).execute(conn)?;
+        diesel::delete(AlbumItem::table.filter(AlbumItem::asset_id.eq(id))).execute(conn)?;
+        diesel::delete(TimelineGroupItem::table.filter(TimelineGroupItem::asset_id.eq(id)))
+            .execute(conn)?;
+        diesel::delete(
+            DeletedAutoAssetSeries::table.filter(DeletedAutoAssetSeries::asset_id.eq(id)),
+        )
+        .execute(conn)?;
+        diesel::update(
+            schema::Asset::table.filter(schema::Asset::motion_photo_assoc_asset_id.eq(id)),
+        )
+        .set(schema::Asset::motion_photo_assoc_asset_id.eq(None::<i64>))
+        .execute(conn)?;
+        diesel::delete(schema::Asset::table.find(id)).execute(conn)?;
+        search_index::remove_asset(conn, asset_id)?;
+        Ok::<_, eyre::Report>(())
+    })
+    .wrap_err("error deleting Asset")?;
+    Ok(())
+}
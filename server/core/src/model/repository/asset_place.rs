@@ -0,0 +1,122 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use eyre::{Context, Result};
+use tracing::instrument;
+
+use crate::model::{util::datetime_to_db_repr, AssetId, AssetPlace, GpsCoordinates};
+
+use super::db::DbConn;
+use super::schema;
+
+/// Restricts Assets to those geocoded to a place. Fields that are None match any place.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PlaceFilter {
+    pub country_code: Option<String>,
+    pub admin_region: Option<String>,
+    pub city: Option<String>,
+}
+
+/// None if the Asset has not been geocoded yet or is not inside any country
+#[instrument(skip(conn))]
+pub fn get_asset_place(conn: &mut DbConn, asset_id: AssetId) -> Result<Option<AssetPlace>> {
+    use schema::AssetPlace;
+    let row: Option<(String, String, Option<String>, String)> = AssetPlace::table
+        .find(asset_id.0)
+        // all NULL if the coordinates are not inside any country
+        .filter(AssetPlace::country_code.is_not_null())
+        .select((
+            AssetPlace::country_code.assume_not_null(),
+            AssetPlace::country.assume_not_null(),
+            AssetPlace::admin_region,
+            AssetPlace::city.assume_not_null(),
+        ))
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table AssetPlace")?;
+    Ok(row.map(
+        |(country_code, country, admin_region, city)| crate::model::AssetPlace {
+            country_code,
+            country,
+            admin_region,
+            city,
+        },
+    ))
+}
+
+/// Store the place found for `coordinates`, None if they are not inside any country.
+/// The Asset is geocoded again once its coordinates differ from these.
+#[instrument(skip(conn))]
+pub fn set_asset_place(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+    coordinates: GpsCoordinates,
+    place: Option<&AssetPlace>,
+) -> Result<()> {
+    use schema::AssetPlace;
+    diesel::replace_into(AssetPlace::table)
+        .values((
+            AssetPlace::asset_id.eq(asset_id.0),
+            AssetPlace::gps_latitude.eq(coordinates.lat),
+            AssetPlace::gps_longitude.eq(coordinates.lon),
+            AssetPlace::country_code.eq(place.map(|p| &p.country_code)),
+            AssetPlace::country.eq(place.map(|p| &p.country)),
+            AssetPlace::admin_region.eq(place.and_then(|p| p.admin_region.as_ref())),
+            AssetPlace::city.eq(place.map(|p| &p.city)),
+            AssetPlace::geocoded_at.eq(datetime_to_db_repr(&Utc::now())),
+        ))
+        .execute(conn)
+        .wrap_err("error inserting into table AssetPlace")?;
+    Ok(())
+}
+
+/// Assets with GPS coordinates that were never geocoded or whose coordinates changed since
+#[instrument(skip(conn), level = "debug")]
+pub fn get_assets_to_geocode(conn: &mut DbConn) -> Result<Vec<(AssetId, GpsCoordinates)>> {
+    assets_to_geocode(conn, None)
+}
+
+/// The coordinates to geocode if the Asset has any and they were not geocoded yet
+#[instrument(skip(conn), level = "debug")]
+pub fn get_asset_to_geocode(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+) -> Result<Option<GpsCoordinates>> {
+    Ok(assets_to_geocode(conn, Some(asset_id))?
+        .pop()
+        .map(|(_, coordinates)| coordinates))
+}
+
+fn assets_to_geocode(
+    conn: &mut DbConn,
+    only_asset_id: Option<AssetId>,
+) -> Result<Vec<(AssetId, GpsCoordinates)>> {
+    use schema::{Asset, AssetPlace};
+    let mut query = Asset::table
+        .left_join(AssetPlace::table)
+        .filter(Asset::missing_since.is_null())
+        .filter(Asset::gps_latitude.is_not_null())
+        .filter(Asset::gps_longitude.is_not_null())
+        .filter(
+            AssetPlace::asset_id
+                .is_null()
+                .or(AssetPlace::gps_latitude.nullable().ne(Asset::gps_latitude))
+                .or(AssetPlace::gps_longitude
+                    .nullable()
+                    .ne(Asset::gps_longitude)),
+        )
+        .select((Asset::asset_id, Asset::gps_latitude, Asset::gps_longitude))
+        .into_boxed();
+    if let Some(asset_id) = only_asset_id {
+        query = query.filter(Asset::asset_id.eq(asset_id.0));
+    }
+    let rows: Vec<(i64, Option<i64>, Option<i64>)> = query
+        .load(conn)
+        .wrap_err("error querying for Assets to geocode")?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(asset_id, lat, lon)| match (lat, lon) {
+            (Some(lat), Some(lon)) => Some((AssetId(asset_id), GpsCoordinates { lat, lon })),
+            _ => None,
+        })
+        .collect())
+}
@@ -1,52 +1,389 @@
+use chrono::{DateTime, Utc};
 use eyre::{eyre, Context, Result};
 
-use crate::model::{AssetId, AssetSeriesId};
+use crate::model::{
+    self,
+    util::{bool_to_int, datetime_from_db_repr, hash_vec8_to_u64},
+    AssetId, AssetSeriesId, AssetType, UserId,
+};
 
-use super::{db::DbConn, schema};
+use super::{
+    db::DbConn,
+    db_entity::{to_db_asset_ty, DbAsset},
+    schema,
+};
 
+/// `selection` are the photos shown in place of the series, they must be part of it
 #[tracing::instrument(skip(conn))]
-pub fn create_series(conn: &mut DbConn, asset_ids: &[AssetId]) -> Result<AssetSeriesId> {
-    use diesel::prelude::*;
-    use schema::{Asset, AssetSeries};
-
+pub fn create_series(
+    conn: &mut DbConn,
+    asset_ids: &[AssetId],
+    selection: &[AssetId],
+) -> Result<AssetSeriesId> {
     if asset_ids.is_empty() {
         return Err(eyre!("asset_ids can not be empty"));
     }
+    insert_series(conn, asset_ids, false, selection)
+}
 
+/// Series found by series detection, marked is_auto so that dissolving it is remembered
+/// in DeletedAutoAssetSeries
+#[tracing::instrument(skip(conn))]
+pub fn create_auto_series(
+    conn: &mut DbConn,
+    asset_ids: &[AssetId],
+    selection: AssetId,
+) -> Result<AssetSeriesId> {
+    insert_series(conn, asset_ids, true, &[selection])
+}
+
+fn insert_series(
+    conn: &mut DbConn,
+    asset_ids: &[AssetId],
+    is_auto: bool,
+    selection: &[AssetId],
+) -> Result<AssetSeriesId> {
+    use diesel::prelude::*;
+    use schema::AssetSeries;
+
+    if !selection.iter().all(|id| asset_ids.contains(id)) {
+        return Err(eyre!("selection must be part of the series"));
+    }
     conn.immediate_transaction(|conn| {
         let series_id = diesel::insert_into(AssetSeries::table)
-            .values(AssetSeries::is_auto.eq(0))
+            .values(AssetSeries::is_auto.eq(bool_to_int(is_auto)))
             .returning(AssetSeries::series_id)
             .get_result(conn)
             .wrap_err("error inserting into table AssetSeries")?;
+        let series_id = AssetSeriesId(series_id);
+        add_to_series(conn, series_id, asset_ids)?;
+        set_selection(conn, series_id, selection)?;
+        Ok(series_id)
+    })
+}
 
-        let affected_rows = diesel::update(
-            Asset::table.filter(
-                Asset::asset_id
-                    .eq_any(asset_ids.iter().map(|id| id.0))
-                    // can not already be part of a series
-                    .and(Asset::series_id.is_null()),
-            ),
-        )
-        .set((
-            Asset::series_id.eq(series_id),
-            Asset::is_series_selection.eq(0),
-        ))
+/// Must be called in a transaction
+fn add_to_series(conn: &mut DbConn, series_id: AssetSeriesId, asset_ids: &[AssetId]) -> Result<()> {
+    use diesel::prelude::*;
+    use schema::Asset;
+
+    let affected_rows = diesel::update(
+        Asset::table.filter(
+            Asset::asset_id
+                .eq_any(asset_ids.iter().map(|id| id.0))
+                // can not already be part of a series
+                .and(Asset::series_id.is_null()),
+        ),
+    )
+    .set((
+        Asset::series_id.eq(series_id.0),
+        Asset::is_series_selection.eq(0),
+    ))
+    .execute(conn)
+    .wrap_err("error updating table Asset")?;
+
+    // WHERE Asset.series_id IS NULL prevented some rows from being changed
+    if affected_rows != asset_ids.len() {
+        return Err(eyre!("one or more assets were already part of a series"));
+    }
+    Ok(())
+}
+
+/// Must be called in a transaction
+fn set_selection(conn: &mut DbConn, series_id: AssetSeriesId, selection: &[AssetId]) -> Result<()> {
+    use diesel::prelude::*;
+    use schema::Asset;
+
+    diesel::update(Asset::table.filter(Asset::series_id.eq(series_id.0)))
+        .set(Asset::is_series_selection.eq(0))
         .execute(conn)
         .wrap_err("error updating table Asset")?;
+    let affected_rows = diesel::update(
+        Asset::table.filter(
+            Asset::series_id
+                .eq(series_id.0)
+                .and(Asset::asset_id.eq_any(selection.iter().map(|id| id.0))),
+        ),
+    )
+    .set(Asset::is_series_selection.eq(1))
+    .execute(conn)
+    .wrap_err("error setting series selection")?;
+    if affected_rows != selection.len() {
+        return Err(eyre!("selection must be part of the series"));
+    }
+    Ok(())
+}
+
+/// The series with the Assets the user has not hidden. Missing Assets are left out too.
+#[tracing::instrument(skip(conn))]
+pub fn get_series(
+    conn: &mut DbConn,
+    series_id: AssetSeriesId,
+    user_id: UserId,
+) -> Result<Option<model::AssetSeries>> {
+    use diesel::prelude::*;
+    use schema::{Asset, AssetSeries, UserAsset};
 
-        // WHERE Asset.series_id IS NULL prevented some rows from being changed
-        if affected_rows != asset_ids.len() {
-            return Err(eyre!("one or more assets were already part of a series"));
+    let is_auto: Option<i32> = AssetSeries::table
+        .filter(AssetSeries::series_id.eq(series_id.0))
+        .select(AssetSeries::is_auto)
+        .get_result(conn)
+        .optional()
+        .wrap_err("error querying table AssetSeries")?;
+    let Some(is_auto) = is_auto else {
+        return Ok(None);
+    };
+    let hidden_asset_ids = UserAsset::table
+        .filter(UserAsset::user_id.eq(user_id.0))
+        .filter(UserAsset::is_hidden.eq(1))
+        .select(UserAsset::asset_id);
+    let rows: Vec<(DbAsset, Option<i32>)> = Asset::table
+        .filter(Asset::series_id.eq(series_id.0))
+        .filter(Asset::missing_since.is_null())
+        .filter(diesel::dsl::not(Asset::asset_id.eq_any(hidden_asset_ids)))
+        .order_by((Asset::taken_date.desc(), Asset::asset_id.desc()))
+        .select((DbAsset::as_select(), Asset::is_series_selection))
+        .load(conn)
+        .wrap_err("error querying series assets")?;
+    let mut assets = Vec::with_capacity(rows.len());
+    let mut selection_indices = Vec::new();
+    for (idx, (db_asset, is_series_selection)) in rows.into_iter().enumerate() {
+        if is_series_selection == Some(1) {
+            selection_indices.push(idx);
         }
+        assets.push(db_asset.try_into()?);
+    }
+    Ok(Some(model::AssetSeries {
+        id: series_id,
+        is_auto: is_auto != 0,
+        assets,
+        selection_indices,
+    }))
+}
 
-        // TODO: remove this. series with no selection is a valid state, we just don't
-        // handle it yet on the client
-        let affected_rows = diesel::update(Asset::table.filter(Asset::asset_id.eq(asset_ids[0].0)))
-            .set(Asset::is_series_selection.eq(1))
+/// All Assets of the series, including hidden and missing ones.
+/// Empty if the series does not exist.
+#[tracing::instrument(skip(conn))]
+pub fn get_series_asset_ids(conn: &mut DbConn, series_id: AssetSeriesId) -> Result<Vec<AssetId>> {
+    use diesel::prelude::*;
+    use schema::Asset;
+
+    let ids: Vec<i64> = Asset::table
+        .filter(Asset::series_id.eq(series_id.0))
+        .select(Asset::asset_id)
+        .load(conn)
+        .wrap_err("error querying series assets")?;
+    Ok(ids.into_iter().map(AssetId).collect())
+}
+
+/// The series each of the Assets is part of, Assets that don't exist are left out
+#[tracing::instrument(skip(conn))]
+pub fn get_series_of_assets(
+    conn: &mut DbConn,
+    asset_ids: &[AssetId],
+) -> Result<Vec<(AssetId, Option<AssetSeriesId>)>> {
+    use diesel::prelude::*;
+    use schema::Asset;
+
+    let rows: Vec<(i64, Option<i64>)> = Asset::table
+        .filter(Asset::asset_id.eq_any(asset_ids.iter().map(|id| id.0)))
+        .select((Asset::asset_id, Asset::series_id))
+        .load(conn)
+        .wrap_err("error querying table Asset")?;
+    Ok(rows
+        .into_iter()
+        .map(|(asset_id, series_id)| (AssetId(asset_id), series_id.map(AssetSeriesId)))
+        .collect())
+}
+
+#[tracing::instrument(skip(conn))]
+pub fn add_assets_to_series(
+    conn: &mut DbConn,
+    series_id: AssetSeriesId,
+    asset_ids: &[AssetId],
+) -> Result<()> {
+    conn.immediate_transaction(|conn| add_to_series(conn, series_id, asset_ids))
+}
+
+/// Remove Assets from the series. If this removes the whole selection, the most recent
+/// remaining Asset is selected. If fewer than two Assets remain the series is dissolved.
+/// For automatically created series, the removed Assets are recorded in
+/// DeletedAutoAssetSeries so that series detection does not put them back together.
+#[tracing::instrument(skip(conn))]
+pub fn remove_assets_from_series(
+    conn: &mut DbConn,
+    series_id: AssetSeriesId,
+    asset_ids: &[AssetId],
+) -> Result<()> {
+    use diesel::prelude::*;
+    use schema::Asset;
+
+    conn.immediate_transaction(|conn| {
+        let removed: Vec<i64> = Asset::table
+            .filter(Asset::series_id.eq(series_id.0))
+            .filter(Asset::asset_id.eq_any(asset_ids.iter().map(|id| id.0)))
+            .order_by(Asset::asset_id)
+            .select(Asset::asset_id)
+            .load(conn)
+            .wrap_err("error querying series assets")?;
+        let removed: Vec<AssetId> = removed.into_iter().map(AssetId).collect();
+        record_deleted_auto_series(conn, series_id, &removed)?;
+        diesel::update(Asset::table.filter(Asset::asset_id.eq_any(removed.iter().map(|id| id.0))))
+            .set((
+                Asset::series_id.eq(None::<i64>),
+                Asset::is_series_selection.eq(None::<i32>),
+            ))
             .execute(conn)
-            .wrap_err("error setting first asset to selection true")?;
-        assert!(affected_rows == 1);
-        Ok(AssetSeriesId(series_id))
+            .wrap_err("error updating table Asset")?;
+
+        let remaining: Vec<(i64, Option<i32>)> = Asset::table
+            .filter(Asset::series_id.eq(series_id.0))
+            .order_by((Asset::taken_date.desc(), Asset::asset_id.desc()))
+            .select((Asset::asset_id, Asset::is_series_selection))
+            .load(conn)
+            .wrap_err("error querying series assets")?;
+        if remaining.len() < 2 {
+            return dissolve(conn, series_id);
+        }
+        if !remaining
+            .iter()
+            .any(|(_, is_selection)| *is_selection == Some(1))
+        {
+            set_selection(conn, series_id, &[AssetId(remaining[0].0)])?;
+        }
+        Ok(())
     })
 }
+
+/// `selection` must not be empty and only contain Assets of the series
+#[tracing::instrument(skip(conn))]
+pub fn set_series_selection(
+    conn: &mut DbConn,
+    series_id: AssetSeriesId,
+    selection: &[AssetId],
+) -> Result<()> {
+    if selection.is_empty() {
+        return Err(eyre!("selection can not be empty"));
+    }
+    conn.immediate_transaction(|conn| set_selection(conn, series_id, selection))
+}
+
+/// Delete the series, its Assets are shown on their own again.
+/// Automatically created series are recorded in DeletedAutoAssetSeries
+/// so that series detection does not create them again.
+#[tracing::instrument(skip(conn))]
+pub fn dissolve_series(conn: &mut DbConn, series_id: AssetSeriesId) -> Result<()> {
+    conn.immediate_transaction(|conn| dissolve(conn, series_id))
+}
+
+/// Must be called in a transaction
+fn dissolve(conn: &mut DbConn, series_id: AssetSeriesId) -> Result<()> {
+    use diesel::prelude::*;
+    use schema::{Asset, AssetSeries};
+
+    let asset_ids = get_series_asset_ids(conn, series_id)?;
+    record_deleted_auto_series(conn, series_id, &asset_ids)?;
+    diesel::update(Asset::table.filter(Asset::series_id.eq(series_id.0)))
+        .set((
+            Asset::series_id.eq(None::<i64>),
+            Asset::is_series_selection.eq(None::<i32>),
+        ))
+        .execute(conn)
+        .wrap_err("error updating table Asset")?;
+    diesel::delete(AssetSeries::table.filter(AssetSeries::series_id.eq(series_id.0)))
+        .execute(conn)
+        .wrap_err("error deleting from table AssetSeries")?;
+    Ok(())
+}
+
+/// Does nothing if the series was not created automatically
+fn record_deleted_auto_series(
+    conn: &mut DbConn,
+    series_id: AssetSeriesId,
+    asset_ids: &[AssetId],
+) -> Result<()> {
+    use diesel::prelude::*;
+    use schema::{AssetSeries, DeletedAutoAssetSeries};
+
+    let is_auto: Option<i32> = AssetSeries::table
+        .filter(AssetSeries::series_id.eq(series_id.0))
+        .select(AssetSeries::is_auto)
+        .get_result(conn)
+        .optional()
+        .wrap_err("error querying table AssetSeries")?;
+    if is_auto != Some(1) || asset_ids.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<_> = asset_ids
+        .iter()
+        .map(|asset_id| {
+            (
+                DeletedAutoAssetSeries::asset_id.eq(asset_id.0),
+                DeletedAutoAssetSeries::series_id.eq(series_id.0),
+            )
+        })
+        .collect();
+    diesel::insert_into(DeletedAutoAssetSeries::table)
+        .values(rows)
+        .execute(conn)
+        .wrap_err("error inserting into table DeletedAutoAssetSeries")?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeriesDetectionCandidate {
+    pub asset_id: AssetId,
+    pub taken_date: DateTime<Utc>,
+    pub perceptual_hash: Option<u64>,
+}
+
+/// Images that may be put into a series by series detection: not missing and not already
+/// part of a series. Sorted by taken_date.
+#[tracing::instrument(skip(conn))]
+pub fn get_series_detection_candidates(conn: &mut DbConn) -> Result<Vec<SeriesDetectionCandidate>> {
+    use diesel::prelude::*;
+    use schema::Asset;
+
+    let rows: Vec<(i64, i64, Option<Vec<u8>>)> = Asset::table
+        .filter(Asset::ty.eq(to_db_asset_ty(AssetType::Image)))
+        .filter(Asset::series_id.is_null())
+        .filter(Asset::missing_since.is_null())
+        .order_by((Asset::taken_date.asc(), Asset::asset_id.asc()))
+        .select((Asset::asset_id, Asset::taken_date, Asset::perceptual_hash))
+        .load(conn)
+        .wrap_err("error querying series detection candidates")?;
+    rows.into_iter()
+        .map(|(id, taken_date, hash)| {
+            Ok(SeriesDetectionCandidate {
+                asset_id: AssetId(id),
+                taken_date: datetime_from_db_repr(taken_date)?,
+                perceptual_hash: hash.map(hash_vec8_to_u64).transpose()?,
+            })
+        })
+        .collect()
+}
+
+/// Rows of DeletedAutoAssetSeries for any of the Assets: which automatically created
+/// series they were in before the user dissolved it
+#[tracing::instrument(skip(conn))]
+pub fn get_deleted_auto_series(
+    conn: &mut DbConn,
+    asset_ids: &[AssetId],
+) -> Result<Vec<(AssetSeriesId, AssetId)>> {
+    use diesel::prelude::*;
+    use schema::DeletedAutoAssetSeries;
+
+    let rows: Vec<(i64, i64)> = DeletedAutoAssetSeries::table
+        .filter(DeletedAutoAssetSeries::asset_id.eq_any(asset_ids.iter().map(|id| id.0)))
+        .order_by(DeletedAutoAssetSeries::id)
+        .select((
+            DeletedAutoAssetSeries::series_id,
+            DeletedAutoAssetSeries::asset_id,
+        ))
+        .load(conn)
+        .wrap_err("error querying table DeletedAutoAssetSeries")?;
+    Ok(rows
+        .into_iter()
+        .map(|(series_id, asset_id)| (AssetSeriesId(series_id), AssetId(asset_id)))
+        .collect())
+}
@@ -37,6 +37,41 @@ pub fn open_in_memory_and_migrate() -> diesel::sqlite::SqliteConnection {
     conn
 }
 
+/// Only runs the migrations before `migration` (its directory name),
+/// to test what it does to existing rows
+#[cfg(test)]
+pub fn open_in_memory_and_migrate_until(migration: &str) -> diesel::sqlite::SqliteConnection {
+    use diesel::{migration::Migration, Connection};
+    let mut conn = diesel::sqlite::SqliteConnection::establish(":memory:")
+        .expect("error opening in memory db");
+    connection_setup(&mut conn).expect("error configuring in memory db connection");
+    let pending = conn
+        .pending_migrations(MIGRATIONS)
+        .expect("error listing migrations");
+    let until = pending
+        .iter()
+        .position(|m| m.name().to_string() == migration)
+        .expect("no migration with this name");
+    for m in &pending[..until] {
+        conn.run_migration(m.as_ref())
+            .expect("error running migrations on in memory connection");
+    }
+    conn
+}
+
+/// File backed pool for tests of code that takes a DbPool,
+/// an in memory database would be separate for every connection
+#[cfg(test)]
+pub async fn open_temp_db_pool_and_migrate(dir: &camino::Utf8Path) -> DbPool {
+    let pool = open_db_pool(dir.join("myrti.db").as_str()).expect("error opening test db pool");
+    let conn = pool.get().await.expect("error getting test db connection");
+    conn.interact(migrate)
+        .await
+        .expect("migrating test db panicked")
+        .expect("error running migrations on test db");
+    pool
+}
+
 pub fn migrate(conn: &mut diesel::SqliteConnection) -> Result<()> {
     match conn.run_pending_migrations(MIGRATIONS) {
         Ok(_) => {}
@@ -56,6 +91,21 @@ PRAGMA busy_timeout = 30000;
     Ok(())
 }
 
+/// Rebuild the database file to reclaim free space and let sqlite update its statistics
+/// for the query planner. This takes a while for large databases and blocks writers meanwhile.
+pub fn vacuum_and_optimize(conn: &mut DbConn) -> Result<()> {
+    conn.batch_execute(
+        r#"
+VACUUM;
+PRAGMA optimize;
+-- VACUUM goes through the WAL, which is now as large as the database
+PRAGMA wal_checkpoint(TRUNCATE);
+    "#,
+    )
+    .wrap_err("error running VACUUM")?;
+    Ok(())
+}
+
 type SqlitePool = Pool<Manager>;
 
 pub type PooledDbConn = deadpool_diesel::Connection<diesel::SqliteConnection>;
@@ -1,8 +1,9 @@
 use std::borrow::Cow;
 
 use diesel::{prelude::Insertable, Queryable, QueryableByName, Selectable};
+use eyre::Context;
 
-use crate::model::{util::datetime_from_db_repr, Album, AlbumId};
+use crate::model::{util::datetime_from_db_repr, Album, AlbumId, UserId};
 
 #[derive(Debug, Clone, PartialEq, Eq, Queryable, QueryableByName, Selectable)]
 #[diesel(table_name = super::super::schema::Album)]
@@ -13,6 +14,9 @@ pub struct DbAlbum {
     pub description: Option<String>,
     pub created_at: i64,
     pub changed_at: i64,
+    pub owner_id: Option<i64>,
+    pub is_private: i32,
+    pub smart_filter: Option<String>,
 }
 
 impl TryFrom<DbAlbum> for Album {
@@ -21,12 +25,20 @@ impl TryFrom<DbAlbum> for Album {
     fn try_from(value: DbAlbum) -> Result<Self, Self::Error> {
         let created_at = datetime_from_db_repr(value.created_at)?;
         let changed_at = datetime_from_db_repr(value.changed_at)?;
+        let smart_filter = value
+            .smart_filter
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .wrap_err("invalid Album.smart_filter")?;
         Ok(Album {
             id: AlbumId(value.album_id),
             name: value.name,
             description: value.description,
             created_at,
             changed_at,
+            owner_id: value.owner_id.map(UserId),
+            is_private: value.is_private != 0,
+            smart_filter,
         })
     }
 }
@@ -49,4 +61,7 @@ pub struct DbInsertAlbum<'a> {
     pub description: Option<Cow<'a, str>>,
     pub created_at: i64,
     pub changed_at: i64,
+    pub owner_id: Option<i64>,
+    pub is_private: i32,
+    pub smart_filter: Option<Cow<'a, str>>,
 }
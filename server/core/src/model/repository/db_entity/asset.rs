@@ -21,7 +21,6 @@ pub struct DbAsset {
     pub file_type: String,
     pub file_path: String,
     pub hash: Option<Vec<u8>>,
-    pub is_hidden: i32,
     pub added_at: i64,
     pub taken_date: i64,
     pub timezone_offset: Option<String>,
@@ -63,7 +62,6 @@ impl TryFrom<DbAsset> for Asset {
             root_dir_id: AssetRootDirId(value.root_dir_id),
             file_type: value.file_type,
             file_path: value.file_path.into(),
-            is_hidden: value.is_hidden != 0,
             added_at: datetime_from_db_repr(value.added_at)?,
             hash,
             taken_date: datetime_from_db_repr(value.taken_date)?,
@@ -108,6 +106,7 @@ pub struct DbInsertAsset<'a> {
     pub root_dir_id: i64,
     pub file_type: Cow<'a, str>,
     pub file_path: Cow<'a, str>,
+    /// Not used anymore, hiding is per user in UserAsset
     pub is_hidden: i32,
     pub hash: Option<Cow<'a, [u8]>>,
     pub added_at: i64,
@@ -133,6 +132,9 @@ pub struct DbInsertAsset<'a> {
     pub video_duration_ms: Option<i64>,
     pub audio_codec_name: Option<Cow<'a, str>>,
     pub has_dash: Option<i32>,
+
+    pub file_mtime: Option<i64>,
+    pub file_size: Option<i64>,
 }
 
 pub fn to_db_asset_ty(ty: AssetType) -> i32 {
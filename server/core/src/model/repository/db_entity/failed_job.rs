@@ -1,27 +1,84 @@
 use diesel::{Queryable, Selectable};
+use eyre::{eyre, Result};
 
 use crate::model::{
     util::{datetime_from_db_repr, hash_vec8_to_u64},
-    AssetId, FailedThumbnailJob,
+    AssetId, FailedJob, FailedJobId, FailedJobKind, JobFailure, Tool,
 };
 
 #[derive(Debug, Clone, Queryable, Selectable)]
-#[diesel(table_name = super::super::schema::FailedThumbnailJob)]
+#[diesel(table_name = super::super::schema::FailedJob)]
 #[diesel(check_for_backend(diesel::sqlite::Sqlite))]
-pub struct DbFailedThumbnailJob {
+pub struct DbFailedJob {
+    pub failed_job_id: i64,
     pub asset_id: i64,
-    pub file_hash: Vec<u8>,
-    pub date: i64,
+    pub kind: i32,
+    pub file_hash: Option<Vec<u8>>,
+    pub tool: Option<i32>,
+    pub command: Option<String>,
+    pub stderr: Option<String>,
+    pub error: String,
+    pub attempts: i32,
+    pub first_failed_at: i64,
+    pub last_failed_at: i64,
+    pub retry_after: i64,
+    pub is_ignored: i32,
 }
 
-impl TryFrom<DbFailedThumbnailJob> for FailedThumbnailJob {
+impl TryFrom<DbFailedJob> for FailedJob {
     type Error = eyre::Report;
 
-    fn try_from(value: DbFailedThumbnailJob) -> Result<Self, Self::Error> {
-        Ok(FailedThumbnailJob {
+    fn try_from(value: DbFailedJob) -> Result<Self, Self::Error> {
+        Ok(FailedJob {
+            id: FailedJobId(value.failed_job_id),
             asset_id: AssetId(value.asset_id),
-            file_hash: hash_vec8_to_u64(value.file_hash)?,
-            date: datetime_from_db_repr(value.date)?,
+            kind: from_db_failed_job_kind(value.kind)?,
+            file_hash: value.file_hash.map(hash_vec8_to_u64).transpose()?,
+            failure: JobFailure {
+                error: value.error,
+                tool: value.tool.map(from_db_tool).transpose()?,
+                command: value.command,
+                stderr: value.stderr,
+            },
+            attempts: value.attempts,
+            first_failed_at: datetime_from_db_repr(value.first_failed_at)?,
+            last_failed_at: datetime_from_db_repr(value.last_failed_at)?,
+            retry_after: datetime_from_db_repr(value.retry_after)?,
+            is_ignored: value.is_ignored != 0,
         })
     }
 }
+
+pub fn to_db_failed_job_kind(kind: FailedJobKind) -> i32 {
+    match kind {
+        FailedJobKind::Thumbnail => 1,
+        FailedJobKind::VideoPackaging => 2,
+        FailedJobKind::ReverseGeocode => 3,
+    }
+}
+
+pub fn from_db_failed_job_kind(i: i32) -> Result<FailedJobKind> {
+    match i {
+        1 => Ok(FailedJobKind::Thumbnail),
+        2 => Ok(FailedJobKind::VideoPackaging),
+        3 => Ok(FailedJobKind::ReverseGeocode),
+        _ => Err(eyre!("Invalid column kind in FailedJob row")),
+    }
+}
+
+pub fn to_db_tool(tool: Tool) -> i32 {
+    match tool {
+        Tool::FFmpeg => 1,
+        Tool::ShakaPackager => 2,
+        Tool::MpdGenerator => 3,
+    }
+}
+
+pub fn from_db_tool(i: i32) -> Result<Tool> {
+    match i {
+        1 => Ok(Tool::FFmpeg),
+        2 => Ok(Tool::ShakaPackager),
+        3 => Ok(Tool::MpdGenerator),
+        _ => Err(eyre!("Invalid column tool in FailedJob row")),
+    }
+}
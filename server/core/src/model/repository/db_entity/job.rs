@@ -0,0 +1,106 @@
+use diesel::{Queryable, Selectable};
+use eyre::{eyre, Result};
+
+use crate::model::{
+    util::datetime_from_db_repr, AlbumId, AssetId, AssetRootDirId, Job, JobId, JobKind,
+    JobProgress, JobState, JobSubject,
+};
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = super::super::schema::Job)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DbJob {
+    pub job_id: i64,
+    pub kind: i32,
+    pub state: i32,
+    pub asset_id: Option<i64>,
+    pub album_id: Option<i64>,
+    pub asset_root_dir_id: Option<i64>,
+    pub progress_done: Option<i64>,
+    pub progress_total: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub started_at: Option<i64>,
+    pub finished_at: Option<i64>,
+}
+
+impl TryFrom<DbJob> for Job {
+    type Error = eyre::Report;
+
+    fn try_from(value: DbJob) -> Result<Self, Self::Error> {
+        let subject = match (value.asset_id, value.album_id, value.asset_root_dir_id) {
+            (Some(asset_id), None, None) => JobSubject::Asset(AssetId(asset_id)),
+            (Some(asset_id), Some(album_id), None) => JobSubject::Album {
+                album_id: AlbumId(album_id),
+                asset_id: AssetId(asset_id),
+            },
+            (None, None, Some(root_dir_id)) => {
+                JobSubject::AssetRootDir(AssetRootDirId(root_dir_id))
+            }
+            _ => return Err(eyre!("Invalid combination of subject columns in Job row")),
+        };
+        let progress = value.progress_done.map(|done| JobProgress {
+            done,
+            total: value.progress_total,
+        });
+        Ok(Job {
+            id: JobId(value.job_id),
+            kind: from_db_job_kind(value.kind)?,
+            state: from_db_job_state(value.state)?,
+            subject,
+            progress,
+            error: value.error,
+            created_at: datetime_from_db_repr(value.created_at)?,
+            started_at: value.started_at.map(datetime_from_db_repr).transpose()?,
+            finished_at: value.finished_at.map(datetime_from_db_repr).transpose()?,
+        })
+    }
+}
+
+pub fn to_db_job_kind(kind: JobKind) -> i32 {
+    match kind {
+        JobKind::IndexAssetRoot => 1,
+        JobKind::IndexPaths => 2,
+        JobKind::ReconcilePaths => 3,
+        JobKind::AssetThumbnail => 4,
+        JobKind::AlbumThumbnail => 5,
+        JobKind::PackageVideo => 6,
+        JobKind::ConvertImage => 7,
+        JobKind::ReverseGeocode => 8,
+    }
+}
+
+pub fn from_db_job_kind(i: i32) -> Result<JobKind> {
+    match i {
+        1 => Ok(JobKind::IndexAssetRoot),
+        2 => Ok(JobKind::IndexPaths),
+        3 => Ok(JobKind::ReconcilePaths),
+        4 => Ok(JobKind::AssetThumbnail),
+        5 => Ok(JobKind::AlbumThumbnail),
+        6 => Ok(JobKind::PackageVideo),
+        7 => Ok(JobKind::ConvertImage),
+        8 => Ok(JobKind::ReverseGeocode),
+        _ => Err(eyre!("Invalid column kind in Job row")),
+    }
+}
+
+pub fn to_db_job_state(state: JobState) -> i32 {
+    match state {
+        JobState::Queued => 1,
+        JobState::Running => 2,
+        JobState::Finished => 3,
+        JobState::Failed => 4,
+        JobState::Cancelled => 5,
+    }
+}
+
+pub fn from_db_job_state(i: i32) -> Result<JobState> {
+    match i {
+        1 => Ok(JobState::Queued),
+        2 => Ok(JobState::Running),
+        3 => Ok(JobState::Finished),
+        4 => Ok(JobState::Failed),
+        5 => Ok(JobState::Cancelled),
+        _ => Err(eyre!("Invalid column state in Job row")),
+    }
+}
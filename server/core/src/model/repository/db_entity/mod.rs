@@ -6,8 +6,11 @@ mod asset_thumbnail;
 mod asset_type;
 mod data_dir;
 mod failed_job;
+mod job;
 mod representation;
+mod share_link;
 mod timeline_group;
+mod user;
 
 pub use album::*;
 pub use album_item::*;
@@ -17,5 +20,8 @@ pub use asset_thumbnail::*;
 pub use asset_type::*;
 pub use data_dir::*;
 pub use failed_job::*;
+pub use job::*;
 pub use representation::*;
+pub use share_link::*;
 pub use timeline_group::*;
+pub use user::*;
@@ -0,0 +1,46 @@
+use diesel::{Queryable, Selectable};
+use eyre::{eyre, Result};
+
+use crate::model::{
+    util::datetime_from_db_repr, AlbumId, AssetId, ShareLink, ShareLinkId, ShareLinkTarget,
+    TimelineGroupId, UserId,
+};
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = super::super::schema::ShareLink)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DbShareLink {
+    pub share_link_id: i64,
+    pub album_id: Option<i64>,
+    pub timeline_group_id: Option<i64>,
+    pub asset_id: Option<i64>,
+    pub created_by_id: i64,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub password_hash: Option<String>,
+    pub allow_download: i32,
+}
+
+impl TryFrom<DbShareLink> for ShareLink {
+    type Error = eyre::Report;
+
+    fn try_from(value: DbShareLink) -> Result<Self, Self::Error> {
+        let target = match (value.album_id, value.timeline_group_id, value.asset_id) {
+            (Some(album_id), None, None) => ShareLinkTarget::Album(AlbumId(album_id)),
+            (None, Some(group_id), None) => {
+                ShareLinkTarget::TimelineGroup(TimelineGroupId(group_id))
+            }
+            (None, None, Some(asset_id)) => ShareLinkTarget::Asset(AssetId(asset_id)),
+            _ => return Err(eyre!("ShareLink row must have exactly one target")),
+        };
+        Ok(ShareLink {
+            id: ShareLinkId(value.share_link_id),
+            target,
+            created_by: UserId(value.created_by_id),
+            created_at: datetime_from_db_repr(value.created_at)?,
+            expires_at: value.expires_at.map(datetime_from_db_repr).transpose()?,
+            password_hash: value.password_hash,
+            allow_download: value.allow_download != 0,
+        })
+    }
+}
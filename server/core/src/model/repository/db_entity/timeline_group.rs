@@ -1,6 +1,6 @@
 use diesel::{Queryable, Selectable};
 
-use crate::model::{util::datetime_from_db_repr, TimelineGroup, TimelineGroupId};
+use crate::model::{util::datetime_from_db_repr, TimelineGroup, TimelineGroupId, UserId};
 
 #[derive(Debug, Clone, PartialEq, Eq, Queryable, Selectable)]
 #[diesel(table_name = super::super::schema::TimelineGroup)]
@@ -11,6 +11,8 @@ pub struct DbTimelineGroup {
     pub display_date: i64,
     pub created_at: i64,
     pub changed_at: i64,
+    pub owner_id: Option<i64>,
+    pub is_private: i32,
 }
 
 impl TryFrom<DbTimelineGroup> for TimelineGroup {
@@ -26,6 +28,8 @@ impl TryFrom<DbTimelineGroup> for TimelineGroup {
             display_date,
             created_at,
             changed_at,
+            owner_id: value.owner_id.map(UserId),
+            is_private: value.is_private != 0,
         })
     }
 }
@@ -0,0 +1,88 @@
+use diesel::{Queryable, Selectable};
+use eyre::{eyre, Result};
+
+use crate::model::{
+    util::datetime_from_db_repr, User, UserId, UserRole, UserToken, UserTokenId, UserTokenKind,
+};
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = super::super::schema::User)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DbUser {
+    pub user_id: i64,
+    pub name: String,
+    pub role: i32,
+    pub created_at: i64,
+}
+
+impl TryFrom<DbUser> for User {
+    type Error = eyre::Report;
+
+    fn try_from(value: DbUser) -> Result<Self, Self::Error> {
+        Ok(User {
+            id: UserId(value.user_id),
+            name: value.name,
+            role: from_db_user_role(value.role)?,
+            created_at: datetime_from_db_repr(value.created_at)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = super::super::schema::UserToken)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct DbUserToken {
+    pub user_token_id: i64,
+    pub user_id: i64,
+    pub kind: i32,
+    pub name: Option<String>,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+    pub last_used_at: Option<i64>,
+}
+
+impl TryFrom<DbUserToken> for UserToken {
+    type Error = eyre::Report;
+
+    fn try_from(value: DbUserToken) -> Result<Self, Self::Error> {
+        Ok(UserToken {
+            id: UserTokenId(value.user_token_id),
+            user_id: UserId(value.user_id),
+            kind: from_db_user_token_kind(value.kind)?,
+            name: value.name,
+            created_at: datetime_from_db_repr(value.created_at)?,
+            expires_at: value.expires_at.map(datetime_from_db_repr).transpose()?,
+            last_used_at: value.last_used_at.map(datetime_from_db_repr).transpose()?,
+        })
+    }
+}
+
+pub fn to_db_user_role(role: UserRole) -> i32 {
+    match role {
+        UserRole::Admin => 1,
+        UserRole::User => 2,
+    }
+}
+
+pub fn from_db_user_role(i: i32) -> Result<UserRole> {
+    match i {
+        1 => Ok(UserRole::Admin),
+        2 => Ok(UserRole::User),
+        _ => Err(eyre!("Invalid column role in User row")),
+    }
+}
+
+pub fn to_db_user_token_kind(kind: UserTokenKind) -> i32 {
+    match kind {
+        UserTokenKind::Session => 1,
+        UserTokenKind::Api => 2,
+    }
+}
+
+pub fn from_db_user_token_kind(i: i32) -> Result<UserTokenKind> {
+    match i {
+        1 => Ok(UserTokenKind::Session),
+        2 => Ok(UserTokenKind::Api),
+        _ => Err(eyre!("Invalid column kind in UserToken row")),
+    }
+}
@@ -1,9 +1,15 @@
+use std::collections::HashMap;
+
 use camino::Utf8Path as Path;
+use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use eyre::Result;
+use eyre::{eyre, Context, Result};
 use tracing::instrument;
 
-use crate::model::{AssetId, AssetRootDirId, DuplicateAssetId};
+use crate::model::{
+    AssetDuplicates, AssetId, AssetRootDirId, DuplicateAsset, DuplicateAssetId, DuplicatesReport,
+    RootDirDuplicates,
+};
 
 use super::db::DbConn;
 use super::schema::{self};
@@ -31,3 +37,253 @@ pub fn insert_duplicate_asset<'a>(
         .get_result(conn)?;
     Ok(DuplicateAssetId(id))
 }
+
+fn duplicate_from_row(
+    (id, asset_id, root_dir_id, file_path): (i64, i64, i64, String),
+) -> DuplicateAsset {
+    DuplicateAsset {
+        id: DuplicateAssetId(id),
+        asset_id: AssetId(asset_id),
+        root_dir_id: AssetRootDirId(root_dir_id),
+        file_path: file_path.into(),
+    }
+}
+
+#[instrument(skip(conn))]
+pub fn get_duplicates_in_root(
+    conn: &mut DbConn,
+    root_dir_id: AssetRootDirId,
+) -> Result<Vec<DuplicateAsset>> {
+    use schema::DuplicateAsset;
+    let rows: Vec<(i64, i64, i64, String)> = DuplicateAsset::table
+        .filter(DuplicateAsset::root_dir_id.eq(root_dir_id.0))
+        .select((
+            DuplicateAsset::dup_asset_id,
+            DuplicateAsset::asset_id,
+            DuplicateAsset::root_dir_id,
+            DuplicateAsset::file_path,
+        ))
+        .load(conn)
+        .wrap_err("error querying table DuplicateAsset")?;
+    Ok(rows.into_iter().map(duplicate_from_row).collect())
+}
+
+#[instrument(skip(conn))]
+pub fn get_duplicates_of_asset(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+) -> Result<Vec<DuplicateAsset>> {
+    use schema::DuplicateAsset;
+    let rows: Vec<(i64, i64, i64, String)> = DuplicateAsset::table
+        .filter(DuplicateAsset::asset_id.eq(asset_id.0))
+        .select((
+            DuplicateAsset::dup_asset_id,
+            DuplicateAsset::asset_id,
+            DuplicateAsset::root_dir_id,
+            DuplicateAsset::file_path,
+        ))
+        .order_by(DuplicateAsset::dup_asset_id)
+        .load(conn)
+        .wrap_err("error querying table DuplicateAsset")?;
+    Ok(rows.into_iter().map(duplicate_from_row).collect())
+}
+
+#[instrument(skip(conn))]
+pub fn find_duplicate_asset(
+    conn: &mut DbConn,
+    id: DuplicateAssetId,
+) -> Result<Option<DuplicateAsset>> {
+    use schema::DuplicateAsset;
+    let row: Option<(i64, i64, i64, String)> = DuplicateAsset::table
+        .find(id.0)
+        .select((
+            DuplicateAsset::dup_asset_id,
+            DuplicateAsset::asset_id,
+            DuplicateAsset::root_dir_id,
+            DuplicateAsset::file_path,
+        ))
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table DuplicateAsset")?;
+    Ok(row.map(duplicate_from_row))
+}
+
+#[instrument(skip(conn))]
+pub fn find_duplicate_with_path(
+    conn: &mut DbConn,
+    root_dir_id: AssetRootDirId,
+    path: &Path,
+) -> Result<Option<DuplicateAsset>> {
+    use schema::DuplicateAsset;
+    let row: Option<(i64, i64, i64, String)> = DuplicateAsset::table
+        .filter(DuplicateAsset::root_dir_id.eq(root_dir_id.0))
+        .filter(DuplicateAsset::file_path.eq(path.as_str()))
+        .select((
+            DuplicateAsset::dup_asset_id,
+            DuplicateAsset::asset_id,
+            DuplicateAsset::root_dir_id,
+            DuplicateAsset::file_path,
+        ))
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table DuplicateAsset")?;
+    Ok(row.map(duplicate_from_row))
+}
+
+/// The file of `asset_id` was modified and is now identical to the file of `other_asset_id`.
+/// The file is recorded as a duplicate of the other Asset and `asset_id` is marked as missing,
+/// as if its file had been deleted, so that it is purged after the grace period unless
+/// its content turns up again somewhere else.
+/// The file's modification time and size are stored so that it is not hashed again.
+#[instrument(skip(conn))]
+pub fn replace_asset_file_with_duplicate(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+    other_asset_id: AssetId,
+    file_mtime: DateTime<Utc>,
+    file_size: i64,
+    missing_since: DateTime<Utc>,
+) -> Result<DuplicateAssetId> {
+    use schema::Asset;
+    conn.immediate_transaction(|conn| {
+        let (root_dir_id, file_path): (i64, String) = Asset::table
+            .find(asset_id.0)
+            .select((Asset::root_dir_id, Asset::file_path))
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| eyre!("no Asset with id {}", asset_id))?;
+        let dup_id = match find_duplicate_with_path(
+            conn,
+            AssetRootDirId(root_dir_id),
+            Path::new(&file_path),
+        )? {
+            Some(dup) if dup.asset_id == other_asset_id => dup.id,
+            Some(dup) => {
+                diesel::update(schema::DuplicateAsset::table.find(dup.id.0))
+                    .set(schema::DuplicateAsset::asset_id.eq(other_asset_id.0))
+                    .execute(conn)?;
+                dup.id
+            }
+            None => insert_duplicate_asset(
+                conn,
+                NewDuplicateAsset {
+                    existing_asset_id: other_asset_id,
+                    asset_root_dir_id: AssetRootDirId(root_dir_id),
+                    path_in_asset_root: Path::new(&file_path),
+                },
+            )?,
+        };
+        super::asset::set_asset_file_stat(conn, asset_id, file_mtime, file_size)?;
+        super::asset::set_assets_missing(conn, &[asset_id], Some(missing_since))?;
+        Ok(dup_id)
+    })
+}
+
+/// Duplicates per AssetRootDir and the `assets_limit` Assets with the most wasted bytes.
+/// A duplicate wastes as many bytes as the Asset's file has.
+#[instrument(skip(conn))]
+pub fn get_duplicates_report(conn: &mut DbConn, assets_limit: usize) -> Result<DuplicatesReport> {
+    use schema::{Asset, DuplicateAsset};
+    let rows: Vec<(i64, i64, Option<i64>)> = DuplicateAsset::table
+        .inner_join(Asset::table)
+        .select((
+            DuplicateAsset::root_dir_id,
+            DuplicateAsset::asset_id,
+            Asset::file_size,
+        ))
+        .load(conn)
+        .wrap_err("error querying table DuplicateAsset")?;
+    let mut root_dirs: HashMap<i64, RootDirDuplicates> = HashMap::new();
+    let mut assets: HashMap<i64, AssetDuplicates> = HashMap::new();
+    for (root_dir_id, asset_id, file_size) in rows {
+        let root_dir = root_dirs
+            .entry(root_dir_id)
+            .or_insert_with(|| RootDirDuplicates {
+                root_dir_id: AssetRootDirId(root_dir_id),
+                duplicate_count: 0,
+                wasted_bytes: 0,
+                unknown_size_count: 0,
+            });
+        root_dir.duplicate_count += 1;
+        match file_size {
+            Some(file_size) => root_dir.wasted_bytes += file_size,
+            None => root_dir.unknown_size_count += 1,
+        }
+        let asset = assets.entry(asset_id).or_insert_with(|| AssetDuplicates {
+            asset_id: AssetId(asset_id),
+            duplicate_count: 0,
+            wasted_bytes: file_size.map(|_| 0),
+        });
+        asset.duplicate_count += 1;
+        asset.wasted_bytes = asset.wasted_bytes.zip(file_size).map(|(a, b)| a + b);
+    }
+    let mut root_dirs: Vec<RootDirDuplicates> = root_dirs.into_values().collect();
+    root_dirs.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then(a.root_dir_id.cmp(&b.root_dir_id))
+    });
+    let mut assets: Vec<AssetDuplicates> = assets.into_values().collect();
+    assets.sort_by(|a, b| {
+        b.wasted_bytes
+            .cmp(&a.wasted_bytes)
+            .then(b.duplicate_count.cmp(&a.duplicate_count))
+            .then(a.asset_id.cmp(&b.asset_id))
+    });
+    assets.truncate(assets_limit);
+    Ok(DuplicatesReport { root_dirs, assets })
+}
+
+#[instrument(skip(conn))]
+pub fn delete_duplicate_assets(conn: &mut DbConn, ids: &[DuplicateAssetId]) -> Result<()> {
+    use schema::DuplicateAsset;
+    diesel::delete(
+        DuplicateAsset::table
+            .filter(DuplicateAsset::dup_asset_id.eq_any(ids.iter().map(|id| id.0))),
+    )
+    .execute(conn)
+    .wrap_err("error deleting from table DuplicateAsset")?;
+    Ok(())
+}
+
+/// Make the duplicate's file the Asset's file and forget about the duplicate,
+/// used when the original file of the Asset is gone
+#[instrument(skip(conn))]
+pub fn promote_duplicate_asset(conn: &mut DbConn, dup: &DuplicateAsset) -> Result<()> {
+    conn.immediate_transaction(|conn| {
+        super::asset::set_asset_path(conn, dup.asset_id, dup.root_dir_id, &dup.file_path)?;
+        delete_duplicate_assets(conn, &[dup.id])?;
+        Ok(())
+    })
+}
+
+/// Serve the duplicate's file as the Asset's original from now on, the Asset's current file
+/// becomes a duplicate
+#[instrument(skip(conn))]
+pub fn make_duplicate_primary(conn: &mut DbConn, dup: &DuplicateAsset) -> Result<()> {
+    use schema::Asset;
+    conn.immediate_transaction(|conn| {
+        let (root_dir_id, file_path): (i64, String) = Asset::table
+            .find(dup.asset_id.0)
+            .select((Asset::root_dir_id, Asset::file_path))
+            .first(conn)
+            .optional()?
+            .ok_or_else(|| eyre!("no Asset with id {}", dup.asset_id))?;
+        delete_duplicate_assets(conn, &[dup.id])?;
+        super::asset::set_asset_path(conn, dup.asset_id, dup.root_dir_id, &dup.file_path)?;
+        // same content so same size, but the mtime of the other file is not known here.
+        // The next scan stores it.
+        diesel::update(Asset::table.find(dup.asset_id.0))
+            .set(Asset::file_mtime.eq(None::<i64>))
+            .execute(conn)?;
+        insert_duplicate_asset(
+            conn,
+            NewDuplicateAsset {
+                existing_asset_id: dup.asset_id,
+                asset_root_dir_id: AssetRootDirId(root_dir_id),
+                path_in_asset_root: Path::new(&file_path),
+            },
+        )?;
+        Ok(())
+    })
+}
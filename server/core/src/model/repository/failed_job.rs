@@ -1,34 +1,243 @@
-use crate::model::util::{datetime_to_db_repr, hash_u64_to_vec8};
-use crate::model::{repository::db_entity::DbFailedThumbnailJob, AssetId, FailedThumbnailJob};
+use chrono::Utc;
 use diesel::prelude::*;
-use eyre::Result;
+use eyre::{Context, Result};
 use tracing::instrument;
 
+use crate::model::{
+    repository::db_entity::{to_db_failed_job_kind, to_db_tool, DbFailedJob},
+    retry_delay,
+    util::{bool_to_int, datetime_to_db_repr},
+    AssetId, FailedJob, FailedJobId, FailedJobKind, JobFailure, Tool,
+};
+
 use super::db::DbConn;
 use super::schema;
 
+/// Record that a job failed, or failed again.
+/// The next automatic retry is pushed back further with every attempt (see [`retry_delay`]),
+/// unless the Asset's file changed since the last failure in which case we start over.
+#[instrument(skip(conn, failure), level = "debug")]
+pub fn record_failed_job(
+    conn: &mut DbConn,
+    asset_id: AssetId,
+    kind: FailedJobKind,
+    failure: &JobFailure,
+) -> Result<()> {
+    use schema::{Asset, FailedJob};
+    let db_kind = to_db_failed_job_kind(kind);
+    conn.immediate_transaction(|conn| {
+        let file_hash: Option<Vec<u8>> = Asset::table
+            .find(asset_id.0)
+            .select(Asset::hash)
+            .first(conn)?;
+        let previous: Option<(i64, Option<Vec<u8>>, i32)> = FailedJob::table
+            .filter(FailedJob::asset_id.eq(asset_id.0))
+            .filter(FailedJob::kind.eq(db_kind))
+            .select((
+                FailedJob::failed_job_id,
+                FailedJob::file_hash,
+                FailedJob::attempts,
+            ))
+            .first(conn)
+            .optional()?;
+        let now = Utc::now();
+        let tool = failure.tool.map(to_db_tool);
+        match previous {
+            Some((id, previous_hash, attempts)) if previous_hash == file_hash => {
+                let attempts = attempts + 1;
+                diesel::update(FailedJob::table.find(id))
+                    .set((
+                        FailedJob::tool.eq(tool),
+                        FailedJob::command.eq(&failure.command),
+                        FailedJob::stderr.eq(&failure.stderr),
+                        FailedJob::error.eq(&failure.error),
+                        FailedJob::attempts.eq(attempts),
+                        FailedJob::last_failed_at.eq(datetime_to_db_repr(&now)),
+                        FailedJob::retry_after
+                            .eq(datetime_to_db_repr(&(now + retry_delay(attempts)))),
+                    ))
+                    .execute(conn)?;
+            }
+            _ => {
+                if let Some((id, _, _)) = previous {
+                    diesel::delete(FailedJob::table.find(id)).execute(conn)?;
+                }
+                diesel::insert_into(FailedJob::table)
+                    .values((
+                        FailedJob::asset_id.eq(asset_id.0),
+                        FailedJob::kind.eq(db_kind),
+                        FailedJob::file_hash.eq(&file_hash),
+                        FailedJob::tool.eq(tool),
+                        FailedJob::command.eq(&failure.command),
+                        FailedJob::stderr.eq(&failure.stderr),
+                        FailedJob::error.eq(&failure.error),
+                        FailedJob::attempts.eq(1),
+                        FailedJob::first_failed_at.eq(datetime_to_db_repr(&now)),
+                        FailedJob::last_failed_at.eq(datetime_to_db_repr(&now)),
+                        FailedJob::retry_after.eq(datetime_to_db_repr(&(now + retry_delay(1)))),
+                        FailedJob::is_ignored.eq(bool_to_int(false)),
+                    ))
+                    .execute(conn)?;
+            }
+        }
+        Ok::<_, diesel::result::Error>(())
+    })
+    .wrap_err("error recording failure in table FailedJob")?;
+    Ok(())
+}
+
+/// Forget about a failure after the job succeeded
+#[instrument(skip(conn), level = "debug")]
+pub fn delete_failed_job(conn: &mut DbConn, asset_id: AssetId, kind: FailedJobKind) -> Result<()> {
+    use schema::FailedJob;
+    diesel::delete(
+        FailedJob::table
+            .filter(FailedJob::asset_id.eq(asset_id.0))
+            .filter(FailedJob::kind.eq(to_db_failed_job_kind(kind))),
+    )
+    .execute(conn)
+    .wrap_err("error deleting from table FailedJob")?;
+    Ok(())
+}
+
 #[instrument(skip(conn), level = "trace")]
-pub fn insert_failed_thumbnail_job(conn: &mut DbConn, j: &FailedThumbnailJob) -> Result<()> {
-    use schema::FailedThumbnailJob;
-    diesel::insert_into(FailedThumbnailJob::table)
-        .values((
-            FailedThumbnailJob::asset_id.eq(j.asset_id.0),
-            FailedThumbnailJob::file_hash.eq(hash_u64_to_vec8(j.file_hash)),
-            FailedThumbnailJob::date.eq(datetime_to_db_repr(&j.date)),
+pub fn find_failed_job(conn: &mut DbConn, id: FailedJobId) -> Result<Option<FailedJob>> {
+    use schema::FailedJob;
+    let db_failed_job: Option<DbFailedJob> = FailedJob::table
+        .find(id.0)
+        .select(DbFailedJob::as_select())
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table FailedJob")?;
+    db_failed_job.map(|f| f.try_into()).transpose()
+}
+
+/// Most recent failures first
+#[instrument(skip(conn), level = "trace")]
+pub fn get_failed_jobs(
+    conn: &mut DbConn,
+    kind: Option<FailedJobKind>,
+    include_ignored: bool,
+) -> Result<Vec<FailedJob>> {
+    use schema::FailedJob;
+    let mut query = FailedJob::table
+        .select(DbFailedJob::as_select())
+        .order_by((
+            FailedJob::last_failed_at.desc(),
+            FailedJob::failed_job_id.desc(),
         ))
-        .execute(conn)?;
-    Ok(())
+        .into_boxed();
+    if let Some(kind) = kind {
+        query = query.filter(FailedJob::kind.eq(to_db_failed_job_kind(kind)));
+    }
+    if !include_ignored {
+        query = query.filter(FailedJob::is_ignored.eq(bool_to_int(false)));
+    }
+    let db_failed_jobs: Vec<DbFailedJob> = query
+        .load(conn)
+        .wrap_err("error querying table FailedJob")?;
+    db_failed_jobs.into_iter().map(|f| f.try_into()).collect()
 }
 
+/// Assets with a failed job of `kind` that must not be retried automatically (yet):
+/// the file is unchanged since the failure and the failure is ignored or not due for a retry.
 #[instrument(skip(conn), level = "trace")]
-pub fn get_failed_thumbnail_job_for_asset(
+pub fn get_assets_with_failed_job_not_due(
+    conn: &mut DbConn,
+    kind: FailedJobKind,
+) -> Result<Vec<AssetId>> {
+    use schema::{Asset, FailedJob};
+    let now = datetime_to_db_repr(&Utc::now());
+    let ids: Vec<i64> = FailedJob::table
+        .inner_join(Asset::table)
+        .filter(FailedJob::kind.eq(to_db_failed_job_kind(kind)))
+        .filter(FailedJob::file_hash.is(Asset::hash))
+        .filter(
+            FailedJob::is_ignored
+                .eq(bool_to_int(true))
+                .or(FailedJob::retry_after.gt(now)),
+        )
+        .select(FailedJob::asset_id)
+        .load(conn)
+        .wrap_err("error querying table FailedJob")?;
+    Ok(ids.into_iter().map(AssetId).collect())
+}
+
+/// Like [`get_assets_with_failed_job_not_due`] for a single Asset
+#[instrument(skip(conn), level = "trace")]
+pub fn is_failed_job_due(
     conn: &mut DbConn,
     asset_id: AssetId,
-) -> Result<Option<FailedThumbnailJob>> {
-    use schema::FailedThumbnailJob;
-    let db_ftj: Option<DbFailedThumbnailJob> = FailedThumbnailJob::table
-        .filter(FailedThumbnailJob::asset_id.eq(asset_id.0))
+    kind: FailedJobKind,
+) -> Result<bool> {
+    use schema::{Asset, FailedJob};
+    let now = datetime_to_db_repr(&Utc::now());
+    let not_due_count: i64 = FailedJob::table
+        .inner_join(Asset::table)
+        .filter(FailedJob::asset_id.eq(asset_id.0))
+        .filter(FailedJob::kind.eq(to_db_failed_job_kind(kind)))
+        .filter(FailedJob::file_hash.is(Asset::hash))
+        .filter(
+            FailedJob::is_ignored
+                .eq(bool_to_int(true))
+                .or(FailedJob::retry_after.gt(now)),
+        )
+        .count()
+        .get_result(conn)
+        .wrap_err("error querying table FailedJob")?;
+    Ok(not_due_count == 0)
+}
+
+#[instrument(skip(conn), level = "debug")]
+pub fn set_failed_job_ignored(conn: &mut DbConn, id: FailedJobId, is_ignored: bool) -> Result<()> {
+    use schema::FailedJob;
+    diesel::update(FailedJob::table.find(id.0))
+        .set(FailedJob::is_ignored.eq(bool_to_int(is_ignored)))
+        .execute(conn)
+        .wrap_err("error updating table FailedJob")?;
+    Ok(())
+}
+
+/// Make failures caused by `tool` due for a retry now, returns how many there were.
+/// Ignored failures stay ignored.
+#[instrument(skip(conn), level = "debug")]
+pub fn set_failed_jobs_due_for_tool(conn: &mut DbConn, tool: Tool) -> Result<usize> {
+    use schema::FailedJob;
+    let count = diesel::update(
+        FailedJob::table
+            .filter(FailedJob::tool.eq(to_db_tool(tool)))
+            .filter(FailedJob::is_ignored.eq(bool_to_int(false))),
+    )
+    .set(FailedJob::retry_after.eq(datetime_to_db_repr(&Utc::now())))
+    .execute(conn)
+    .wrap_err("error updating table FailedJob")?;
+    Ok(count)
+}
+
+/// Version of `tool` seen at the last startup
+#[instrument(skip(conn), level = "trace")]
+pub fn get_tool_version(conn: &mut DbConn, tool: Tool) -> Result<Option<String>> {
+    use schema::ToolVersion;
+    ToolVersion::table
+        .find(to_db_tool(tool))
+        .select(ToolVersion::version)
         .first(conn)
-        .optional()?;
-    db_ftj.map(|db_ftj| db_ftj.try_into()).transpose()
+        .optional()
+        .wrap_err("error querying table ToolVersion")
+}
+
+#[instrument(skip(conn), level = "debug")]
+pub fn set_tool_version(conn: &mut DbConn, tool: Tool, version: &str) -> Result<()> {
+    use schema::ToolVersion;
+    diesel::insert_into(ToolVersion::table)
+        .values((
+            ToolVersion::tool.eq(to_db_tool(tool)),
+            ToolVersion::version.eq(version),
+        ))
+        .on_conflict(ToolVersion::tool)
+        .do_update()
+        .set(ToolVersion::version.eq(version))
+        .execute(conn)
+        .wrap_err("error upserting into table ToolVersion")?;
+    Ok(())
 }
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use eyre::{Context, Result};
+use tracing::instrument;
+
+use crate::model::{
+    repository::db_entity::{to_db_job_kind, to_db_job_state, DbJob},
+    util::datetime_to_db_repr,
+    CreateJob, Job, JobId, JobProgress, JobState, JobSubject,
+};
+
+use super::db::DbConn;
+use super::schema;
+
+#[instrument(skip(conn), level = "trace")]
+pub fn create_job(conn: &mut DbConn, create_job: &CreateJob) -> Result<JobId> {
+    use schema::Job;
+    let (asset_id, album_id, asset_root_dir_id) = match create_job.subject {
+        JobSubject::Asset(asset_id) => (Some(asset_id.0), None, None),
+        JobSubject::Album { album_id, asset_id } => (Some(asset_id.0), Some(album_id.0), None),
+        JobSubject::AssetRootDir(root_dir_id) => (None, None, Some(root_dir_id.0)),
+    };
+    let id = diesel::insert_into(Job::table)
+        .values((
+            Job::kind.eq(to_db_job_kind(create_job.kind)),
+            Job::state.eq(to_db_job_state(JobState::Queued)),
+            Job::asset_id.eq(asset_id),
+            Job::album_id.eq(album_id),
+            Job::asset_root_dir_id.eq(asset_root_dir_id),
+            Job::created_at.eq(datetime_to_db_repr(&Utc::now())),
+        ))
+        .returning(Job::job_id)
+        .get_result(conn)
+        .wrap_err("error inserting into table Job")?;
+    Ok(JobId(id))
+}
+
+#[instrument(skip(conn), level = "trace")]
+pub fn find_job(conn: &mut DbConn, job_id: JobId) -> Result<Option<Job>> {
+    use schema::Job;
+    let db_job: Option<DbJob> = Job::table
+        .find(job_id.0)
+        .select(DbJob::as_select())
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table Job")?;
+    db_job.map(|db_job| db_job.try_into()).transpose()
+}
+
+/// Most recent jobs first.
+/// Pass the id of the last job of the previous page as `before` to get the next page.
+#[instrument(skip(conn), level = "trace")]
+pub fn get_jobs(
+    conn: &mut DbConn,
+    states: Option<&[JobState]>,
+    before: Option<JobId>,
+    limit: i64,
+) -> Result<Vec<Job>> {
+    use schema::Job;
+    let mut query = Job::table
+        .select(DbJob::as_select())
+        .order_by(Job::job_id.desc())
+        .limit(limit)
+        .into_boxed();
+    if let Some(states) = states {
+        query = query.filter(Job::state.eq_any(states.iter().copied().map(to_db_job_state)));
+    }
+    if let Some(before) = before {
+        query = query.filter(Job::job_id.lt(before.0));
+    }
+    let db_jobs: Vec<DbJob> = query.load(conn).wrap_err("error querying table Job")?;
+    db_jobs
+        .into_iter()
+        .map(|db_job| db_job.try_into())
+        .collect()
+}
+
+/// Put a job back into the queue to be run again, forgetting any previous result
+#[instrument(skip(conn), level = "trace")]
+pub fn set_job_queued(conn: &mut DbConn, job_id: JobId) -> Result<()> {
+    use schema::Job;
+    diesel::update(Job::table.find(job_id.0))
+        .set((
+            Job::state.eq(to_db_job_state(JobState::Queued)),
+            Job::progress_done.eq(None::<i64>),
+            Job::progress_total.eq(None::<i64>),
+            Job::error.eq(None::<String>),
+            Job::started_at.eq(None::<i64>),
+            Job::finished_at.eq(None::<i64>),
+        ))
+        .execute(conn)
+        .wrap_err("error updating table Job")?;
+    Ok(())
+}
+
+#[instrument(skip(conn), level = "trace")]
+pub fn set_job_running(conn: &mut DbConn, job_id: JobId) -> Result<()> {
+    use schema::Job;
+    diesel::update(Job::table.find(job_id.0))
+        .set((
+            Job::state.eq(to_db_job_state(JobState::Running)),
+            Job::started_at.eq(datetime_to_db_repr(&Utc::now())),
+        ))
+        .execute(conn)
+        .wrap_err("error updating table Job")?;
+    Ok(())
+}
+
+#[instrument(skip(conn), level = "trace")]
+pub fn set_job_progress(conn: &mut DbConn, job_id: JobId, progress: JobProgress) -> Result<()> {
+    use schema::Job;
+    diesel::update(Job::table.find(job_id.0))
+        .set((
+            Job::progress_done.eq(progress.done),
+            Job::progress_total.eq(progress.total),
+        ))
+        .execute(conn)
+        .wrap_err("error updating table Job")?;
+    Ok(())
+}
+
+/// Mark a job as Finished, Failed or Cancelled
+#[instrument(skip(conn), level = "trace")]
+pub fn set_job_done(
+    conn: &mut DbConn,
+    job_id: JobId,
+    state: JobState,
+    error: Option<String>,
+) -> Result<()> {
+    use schema::Job;
+    debug_assert!(state.is_done());
+    diesel::update(Job::table.find(job_id.0))
+        .set((
+            Job::state.eq(to_db_job_state(state)),
+            Job::error.eq(error),
+            Job::finished_at.eq(datetime_to_db_repr(&Utc::now())),
+        ))
+        .execute(conn)
+        .wrap_err("error updating table Job")?;
+    Ok(())
+}
+
+/// Cancel all jobs that are still queued or running, for when the server starts
+/// and the actor queues they were in are gone.
+/// Returns the number of cancelled jobs.
+#[instrument(skip(conn))]
+pub fn cancel_unfinished_jobs(conn: &mut DbConn, error: &str) -> Result<usize> {
+    use schema::Job;
+    let unfinished = [JobState::Queued, JobState::Running].map(to_db_job_state);
+    let count = diesel::update(Job::table.filter(Job::state.eq_any(unfinished)))
+        .set((
+            Job::state.eq(to_db_job_state(JobState::Cancelled)),
+            Job::error.eq(error),
+            Job::finished_at.eq(datetime_to_db_repr(&Utc::now())),
+        ))
+        .execute(conn)
+        .wrap_err("error updating table Job")?;
+    Ok(count)
+}
+
+/// Delete Finished, Failed and Cancelled jobs that finished before `before`.
+/// Returns the number of deleted jobs.
+#[instrument(skip(conn))]
+pub fn delete_done_jobs_before(conn: &mut DbConn, before: DateTime<Utc>) -> Result<usize> {
+    use schema::Job;
+    let done = [JobState::Finished, JobState::Failed, JobState::Cancelled].map(to_db_job_state);
+    let count = diesel::delete(
+        Job::table
+            .filter(Job::state.eq_any(done))
+            .filter(Job::finished_at.lt(datetime_to_db_repr(&before))),
+    )
+    .execute(conn)
+    .wrap_err("error deleting from table Job")?;
+    Ok(count)
+}
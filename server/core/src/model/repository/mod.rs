@@ -1,6 +1,7 @@
 pub mod album;
 pub mod album_thumbnail;
 pub mod asset;
+pub mod asset_place;
 pub mod asset_root_dir;
 pub mod asset_series;
 pub mod config;
@@ -8,13 +9,19 @@ pub mod db;
 pub mod db_entity;
 pub mod duplicate_asset;
 pub mod failed_job;
+pub mod job;
+pub mod near_duplicate;
 pub mod representation;
 #[allow(non_snake_case)]
 mod schema;
+pub mod search;
+pub mod search_index;
+pub mod share_link;
 #[cfg(test)]
-mod test;
+pub(crate) mod test;
 pub mod timeline;
 pub mod timeline_group;
+pub mod user;
 
 #[macro_export()]
 macro_rules! interact {
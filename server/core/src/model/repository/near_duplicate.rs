@@ -0,0 +1,110 @@
+use diesel::prelude::*;
+use eyre::{Context, Result};
+use tracing::instrument;
+
+use crate::model::{Asset, AssetId, NearDuplicateGroup, UserId};
+
+use super::db::DbConn;
+use super::db_entity::DbAsset;
+use super::schema;
+
+/// Replace all groups with the result of a new search
+#[instrument(skip(conn, groups))]
+pub fn replace_near_duplicate_groups(conn: &mut DbConn, groups: &[Vec<AssetId>]) -> Result<()> {
+    use schema::NearDuplicate;
+    let rows: Vec<_> = groups
+        .iter()
+        .filter(|group| group.len() > 1)
+        .flat_map(|group| {
+            let group_id = group.iter().min().expect("groups are not empty").0;
+            group.iter().map(move |asset_id| {
+                (
+                    NearDuplicate::asset_id.eq(asset_id.0),
+                    NearDuplicate::group_id.eq(group_id),
+                )
+            })
+        })
+        .collect();
+    conn.immediate_transaction(|conn| {
+        diesel::delete(NearDuplicate::table).execute(conn)?;
+        // stay below sqlite's limit of variables per statement
+        for chunk in rows.chunks(1000) {
+            diesel::insert_into(NearDuplicate::table)
+                .values(chunk)
+                .execute(conn)?;
+        }
+        Ok::<_, diesel::result::Error>(())
+    })
+    .wrap_err("error replacing rows of table NearDuplicate")?;
+    Ok(())
+}
+
+/// Groups with at least two Assets the user has not hidden, largest groups first
+#[instrument(skip(conn))]
+pub fn get_near_duplicate_groups(
+    conn: &mut DbConn,
+    user_id: UserId,
+) -> Result<Vec<NearDuplicateGroup>> {
+    use schema::{NearDuplicate, UserAsset};
+    let hidden_asset_ids = UserAsset::table
+        .filter(UserAsset::user_id.eq(user_id.0))
+        .filter(UserAsset::is_hidden.eq(1))
+        .select(UserAsset::asset_id);
+    let rows: Vec<(i64, DbAsset, Option<i64>)> = NearDuplicate::table
+        .inner_join(schema::Asset::table)
+        .filter(schema::Asset::missing_since.is_null())
+        .filter(diesel::dsl::not(
+            NearDuplicate::asset_id.eq_any(hidden_asset_ids),
+        ))
+        .order_by((NearDuplicate::group_id, NearDuplicate::asset_id))
+        .select((
+            NearDuplicate::group_id,
+            DbAsset::as_select(),
+            schema::Asset::file_size,
+        ))
+        .load(conn)
+        .wrap_err("error querying table NearDuplicate")?;
+    let mut groups: Vec<NearDuplicateGroup> = Vec::new();
+    let mut current_group_id: Option<i64> = None;
+    let mut current: Vec<(Asset, Option<i64>)> = Vec::new();
+    for (group_id, db_asset, file_size) in rows {
+        if current_group_id != Some(group_id) {
+            if let Some(group) = near_duplicate_group(std::mem::take(&mut current)) {
+                groups.push(group);
+            }
+            current_group_id = Some(group_id);
+        }
+        current.push((db_asset.try_into()?, file_size));
+    }
+    if let Some(group) = near_duplicate_group(current) {
+        groups.push(group);
+    }
+    groups.sort_by(|a, b| b.assets.len().cmp(&a.assets.len()).then(a.id.cmp(&b.id)));
+    Ok(groups)
+}
+
+/// None if there aren't at least two Assets left
+fn near_duplicate_group(assets: Vec<(Asset, Option<i64>)>) -> Option<NearDuplicateGroup> {
+    if assets.len() < 2 {
+        return None;
+    }
+    let id = assets.iter().map(|(asset, _)| asset.base.id).min()?;
+    let suggested_best = assets
+        .iter()
+        .max_by(|(a, a_file_size), (b, b_file_size)| {
+            let pixels =
+                |asset: &Asset| asset.base.size.width as i64 * asset.base.size.height as i64;
+            pixels(a)
+                .cmp(&pixels(b))
+                .then(a_file_size.cmp(b_file_size))
+                // older copies are more likely the original
+                .then(b.base.taken_date.cmp(&a.base.taken_date))
+                .then(b.base.id.cmp(&a.base.id))
+        })
+        .map(|(asset, _)| asset.base.id)?;
+    Some(NearDuplicateGroup {
+        id,
+        assets: assets.into_iter().map(|(asset, _)| asset).collect(),
+        suggested_best,
+    })
+}
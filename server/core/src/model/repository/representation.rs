@@ -138,3 +138,36 @@ pub fn get_audio_representations(
         .map(|db_repr| db_repr.try_into())
         .collect::<Result<Vec<_>>>()
 }
+
+/// Storage keys of the files of all image, video and audio representations
+#[tracing::instrument(skip(conn), level = "trace")]
+pub fn get_all_representation_file_keys(conn: &mut DbConn) -> Result<Vec<String>> {
+    use schema::{AudioRepresentation, ImageRepresentation, VideoRepresentation};
+    let image_keys: Vec<String> = ImageRepresentation::table
+        .select(ImageRepresentation::file_key)
+        .load(conn)
+        .wrap_err("error querying table ImageRepresentation")?;
+    let video_keys: Vec<(String, String)> = VideoRepresentation::table
+        .select((
+            VideoRepresentation::file_key,
+            VideoRepresentation::media_info_key,
+        ))
+        .load(conn)
+        .wrap_err("error querying table VideoRepresentation")?;
+    let audio_keys: Vec<(String, String)> = AudioRepresentation::table
+        .select((
+            AudioRepresentation::file_key,
+            AudioRepresentation::media_info_key,
+        ))
+        .load(conn)
+        .wrap_err("error querying table AudioRepresentation")?;
+    Ok(image_keys
+        .into_iter()
+        .chain(
+            video_keys
+                .into_iter()
+                .chain(audio_keys)
+                .flat_map(|(file_key, media_info_key)| [file_key, media_info_key]),
+        )
+        .collect())
+}
@@ -5,6 +5,9 @@ diesel::table! {
         description -> Nullable<Text>,
         created_at -> BigInt,
         changed_at -> BigInt,
+        owner_id -> Nullable<BigInt>,
+        is_private -> Integer,
+        smart_filter -> Nullable<Text>,
     }
 }
 
@@ -54,6 +57,25 @@ diesel::table! {
         video_duration_ms -> Nullable<BigInt>,
         audio_codec_name -> Nullable<Text>,
         has_dash -> Nullable<Integer>,
+
+        missing_since -> Nullable<BigInt>,
+        file_mtime -> Nullable<BigInt>,
+        file_size -> Nullable<BigInt>,
+
+        perceptual_hash -> Nullable<Binary>,
+    }
+}
+
+diesel::table! {
+    AssetPlace (asset_id) {
+        asset_id -> BigInt,
+        gps_latitude -> BigInt,
+        gps_longitude -> BigInt,
+        country_code -> Nullable<Text>,
+        country -> Nullable<Text>,
+        admin_region -> Nullable<Text>,
+        city -> Nullable<Text>,
+        geocoded_at -> BigInt,
     }
 }
 
@@ -102,26 +124,37 @@ diesel::table! {
 }
 
 diesel::table! {
-    FailedFFmpeg (asset_id) {
-        asset_id -> BigInt,
-        file_hash -> Binary,
-        date -> BigInt,
-    }
-}
-
-diesel::table! {
-    FailedShakaPackager (asset_id) {
+    FailedJob (failed_job_id) {
+        failed_job_id -> BigInt,
         asset_id -> BigInt,
-        file_hash -> Binary,
-        date -> BigInt,
+        kind -> Integer,
+        file_hash -> Nullable<Binary>,
+        tool -> Nullable<Integer>,
+        command -> Nullable<Text>,
+        stderr -> Nullable<Text>,
+        error -> Text,
+        attempts -> Integer,
+        first_failed_at -> BigInt,
+        last_failed_at -> BigInt,
+        retry_after -> BigInt,
+        is_ignored -> Integer,
     }
 }
 
 diesel::table! {
-    FailedThumbnailJob (asset_id) {
-        asset_id -> BigInt,
-        file_hash -> Binary,
-        date -> BigInt,
+    Job (job_id) {
+        job_id -> BigInt,
+        kind -> Integer,
+        state -> Integer,
+        asset_id -> Nullable<BigInt>,
+        album_id -> Nullable<BigInt>,
+        asset_root_dir_id -> Nullable<BigInt>,
+        progress_done -> Nullable<BigInt>,
+        progress_total -> Nullable<BigInt>,
+        error -> Nullable<Text>,
+        created_at -> BigInt,
+        started_at -> Nullable<BigInt>,
+        finished_at -> Nullable<BigInt>,
     }
 }
 
@@ -171,6 +204,28 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    NearDuplicate (asset_id) {
+        asset_id -> BigInt,
+        group_id -> BigInt,
+    }
+}
+
+diesel::table! {
+    ShareLink (share_link_id) {
+        share_link_id -> BigInt,
+        token_hash -> Binary,
+        album_id -> Nullable<BigInt>,
+        timeline_group_id -> Nullable<BigInt>,
+        asset_id -> Nullable<BigInt>,
+        created_by_id -> BigInt,
+        created_at -> BigInt,
+        expires_at -> Nullable<BigInt>,
+        password_hash -> Nullable<Text>,
+        allow_download -> Integer,
+    }
+}
+
 diesel::table! {
     TimelineGroup (timeline_group_id) {
         timeline_group_id -> BigInt,
@@ -178,6 +233,8 @@ diesel::table! {
         display_date -> BigInt,
         created_at -> BigInt,
         changed_at -> BigInt,
+        owner_id -> Nullable<BigInt>,
+        is_private -> Integer,
     }
 }
 
@@ -189,6 +246,45 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    ToolVersion (tool) {
+        tool -> Integer,
+        version -> Text,
+    }
+}
+
+diesel::table! {
+    User (user_id) {
+        user_id -> BigInt,
+        name -> Text,
+        password_hash -> Text,
+        role -> Integer,
+        created_at -> BigInt,
+    }
+}
+
+diesel::table! {
+    UserAsset (user_id, asset_id) {
+        user_id -> BigInt,
+        asset_id -> BigInt,
+        is_hidden -> Integer,
+        is_archived -> Integer,
+    }
+}
+
+diesel::table! {
+    UserToken (user_token_id) {
+        user_token_id -> BigInt,
+        user_id -> BigInt,
+        kind -> Integer,
+        name -> Nullable<Text>,
+        token_hash -> Binary,
+        created_at -> BigInt,
+        expires_at -> Nullable<BigInt>,
+        last_used_at -> Nullable<BigInt>,
+    }
+}
+
 diesel::table! {
     VideoRepresentation (video_repr_id) {
         video_repr_id -> BigInt,
@@ -218,14 +314,24 @@ diesel::joinable!(AlbumItem -> Album (album_id));
 diesel::joinable!(AlbumItem -> Asset (asset_id));
 diesel::joinable!(AlbumThumbnail -> Album (album_id));
 diesel::joinable!(Asset -> AssetRootDir (root_dir_id));
+diesel::joinable!(AssetPlace -> Asset (asset_id));
 diesel::joinable!(Asset -> AssetSeries (series_id));
 diesel::joinable!(AssetThumbnail -> Asset (asset_id));
 diesel::joinable!(AudioRepresentation -> Asset (asset_id));
 diesel::joinable!(DuplicateAsset -> Asset (asset_id));
 diesel::joinable!(DuplicateAsset -> AssetRootDir (root_dir_id));
+diesel::joinable!(FailedJob -> Asset (asset_id));
 diesel::joinable!(ImageRepresentation -> Asset (asset_id));
+diesel::joinable!(NearDuplicate -> Asset (asset_id));
+diesel::joinable!(ShareLink -> Album (album_id));
+diesel::joinable!(ShareLink -> Asset (asset_id));
+diesel::joinable!(ShareLink -> TimelineGroup (timeline_group_id));
+diesel::joinable!(ShareLink -> User (created_by_id));
 diesel::joinable!(TimelineGroupItem -> Asset (asset_id));
 diesel::joinable!(TimelineGroupItem -> TimelineGroup (group_id));
+diesel::joinable!(UserAsset -> Asset (asset_id));
+diesel::joinable!(UserAsset -> User (user_id));
+diesel::joinable!(UserToken -> User (user_id));
 diesel::joinable!(VideoRepresentation -> Asset (asset_id));
 diesel::joinable!(DeletedAutoAssetSeries -> Asset (asset_id));
 diesel::joinable!(MotionPhotoVideoFile -> Asset (asset_id));
@@ -235,17 +341,23 @@ diesel::allow_tables_to_appear_in_same_query!(
     AlbumItem,
     AlbumThumbnail,
     Asset,
+    AssetPlace,
     AssetRootDir,
     AssetThumbnail,
     AudioRepresentation,
     DataDir,
     DuplicateAsset,
-    FailedFFmpeg,
-    FailedShakaPackager,
-    FailedThumbnailJob,
+    FailedJob,
     ImageRepresentation,
+    Job,
+    NearDuplicate,
+    ShareLink,
     TimelineGroup,
     TimelineGroupItem,
+    ToolVersion,
+    User,
+    UserAsset,
+    UserToken,
     AssetSeries,
     VideoRepresentation,
     DeletedAutoAssetSeries,
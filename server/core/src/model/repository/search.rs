@@ -0,0 +1,218 @@
+use chrono::{DateTime, Utc};
+use diesel::{dsl::sql, prelude::*, sql_types};
+use eyre::{Context, Result};
+use tracing::instrument;
+
+use crate::model::{
+    repository::db_entity::{DbAsset, DbAssetType},
+    util::datetime_to_db_repr,
+    AlbumId, Asset, AssetId, AssetRootDirId, AssetType, TimelineGroupId, UserId,
+};
+
+use super::{db::DbConn, schema};
+
+/// Conditions an Asset must all fulfill to be found, None matches everything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetSearch {
+    pub taken_after: Option<DateTime<Utc>>,
+    pub taken_before: Option<DateTime<Utc>>,
+    pub asset_type: Option<AssetType>,
+    pub file_type: Option<String>,
+    /// Video or audio codec name
+    pub codec: Option<String>,
+    pub root_dir_id: Option<AssetRootDirId>,
+    /// GLOB pattern for the path in the root dir
+    pub path_glob: Option<String>,
+    /// Part of EXIF Make, case insensitive
+    pub camera_make: Option<String>,
+    /// Part of EXIF Model, case insensitive
+    pub camera_model: Option<String>,
+    /// Part of EXIF LensModel, case insensitive
+    pub lens: Option<String>,
+    pub min_iso: Option<i64>,
+    pub max_iso: Option<i64>,
+    /// Millimeters
+    pub min_focal_length: Option<f64>,
+    /// Millimeters
+    pub max_focal_length: Option<f64>,
+    pub gps_bounds: Option<GpsBounds>,
+    /// Only the Assets the user has hidden instead of only the others
+    pub hidden: bool,
+    pub album_id: Option<AlbumId>,
+    pub timeline_group_id: Option<TimelineGroupId>,
+}
+
+/// Coordinates multiplied by 10e8 like GpsCoordinates.
+/// min_lon > max_lon means the box crosses the antimeridian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GpsBounds {
+    pub min_lat: i64,
+    pub max_lat: i64,
+    pub min_lon: i64,
+    pub max_lon: i64,
+}
+
+/// Position of the last Asset of the previous page in the search order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SearchCursor {
+    pub taken_date: DateTime<Utc>,
+    pub asset_id: AssetId,
+}
+
+impl From<&Asset> for SearchCursor {
+    fn from(asset: &Asset) -> Self {
+        SearchCursor {
+            taken_date: asset.base.taken_date,
+            asset_id: asset.base.id,
+        }
+    }
+}
+
+/// Assets (not missing) matching the search ordered by taken_date, newest first.
+/// Starts after `after` if given, the Asset there does not need to exist anymore.
+#[instrument(skip(conn))]
+pub fn search_assets(
+    conn: &mut DbConn,
+    user_id: UserId,
+    search: &AssetSearch,
+    after: Option<SearchCursor>,
+    limit: i64,
+) -> Result<Vec<Asset>> {
+    use schema::{AlbumItem, Asset, TimelineGroupItem, UserAsset};
+    let mut query = Asset::table
+        .filter(Asset::missing_since.is_null())
+        .select(DbAsset::as_select())
+        .order_by((Asset::taken_date.desc(), Asset::asset_id.desc()))
+        .limit(limit)
+        .into_boxed();
+    if let Some(after) = after {
+        let after_taken_date = datetime_to_db_repr(&after.taken_date);
+        query = query.filter(
+            Asset::taken_date.lt(after_taken_date).or(Asset::taken_date
+                .eq(after_taken_date)
+                .and(Asset::asset_id.lt(after.asset_id.0))),
+        );
+    }
+    if let Some(taken_after) = &search.taken_after {
+        query = query.filter(Asset::taken_date.ge(datetime_to_db_repr(taken_after)));
+    }
+    if let Some(taken_before) = &search.taken_before {
+        query = query.filter(Asset::taken_date.lt(datetime_to_db_repr(taken_before)));
+    }
+    if let Some(asset_type) = search.asset_type {
+        query = query.filter(Asset::ty.eq(DbAssetType::from(asset_type) as i32));
+    }
+    if let Some(file_type) = &search.file_type {
+        query = query.filter(Asset::file_type.eq(file_type.to_lowercase()));
+    }
+    if let Some(codec) = &search.codec {
+        query = query.filter(
+            Asset::video_codec_name
+                .eq(codec)
+                .or(Asset::audio_codec_name.eq(codec)),
+        );
+    }
+    if let Some(root_dir_id) = search.root_dir_id {
+        query = query.filter(Asset::root_dir_id.eq(root_dir_id.0));
+    }
+    if let Some(path_glob) = &search.path_glob {
+        query = query.filter(
+            sql::<sql_types::Bool>("Asset.file_path GLOB ")
+                .bind::<sql_types::Text, _>(path_glob.clone()),
+        );
+    }
+    for (path, value) in [
+        ("$[0].EXIF.Make", &search.camera_make),
+        ("$[0].EXIF.Model", &search.camera_model),
+        ("$[0].EXIF.LensModel", &search.lens),
+    ] {
+        if let Some(value) = value {
+            query = query.filter(
+                sql::<sql_types::Bool>(&format!("{} LIKE ", exiftool_field(path)))
+                    .bind::<sql_types::Text, _>(format!("%{}%", escape_like(value.trim())))
+                    .sql(" ESCAPE '\\'"),
+            );
+        }
+    }
+    for (path, op, value) in [
+        ("$[0].EXIF.ISO", ">=", search.min_iso.map(|iso| iso as f64)),
+        ("$[0].EXIF.ISO", "<=", search.max_iso.map(|iso| iso as f64)),
+        ("$[0].EXIF.FocalLength", ">=", search.min_focal_length),
+        ("$[0].EXIF.FocalLength", "<=", search.max_focal_length),
+    ] {
+        if let Some(value) = value {
+            query = query.filter(
+                sql::<sql_types::Bool>(&format!("{} {} ", exiftool_field(path), op))
+                    .bind::<sql_types::Double, _>(value),
+            );
+        }
+    }
+    if let Some(bounds) = search.gps_bounds {
+        query = query.filter(
+            Asset::gps_latitude
+                .ge(bounds.min_lat)
+                .and(Asset::gps_latitude.le(bounds.max_lat)),
+        );
+        query = if bounds.min_lon <= bounds.max_lon {
+            query.filter(
+                Asset::gps_longitude
+                    .ge(bounds.min_lon)
+                    .and(Asset::gps_longitude.le(bounds.max_lon)),
+            )
+        } else {
+            query.filter(
+                Asset::gps_longitude
+                    .ge(bounds.min_lon)
+                    .or(Asset::gps_longitude.le(bounds.max_lon)),
+            )
+        };
+    }
+    let hidden_asset_ids = UserAsset::table
+        .filter(UserAsset::user_id.eq(user_id.0))
+        .filter(UserAsset::is_hidden.eq(1))
+        .select(UserAsset::asset_id);
+    query = if search.hidden {
+        query.filter(Asset::asset_id.eq_any(hidden_asset_ids))
+    } else {
+        query.filter(diesel::dsl::not(Asset::asset_id.eq_any(hidden_asset_ids)))
+    };
+    if let Some(album_id) = search.album_id {
+        query = query.filter(
+            Asset::asset_id.eq_any(
+                AlbumItem::table
+                    .filter(AlbumItem::album_id.eq(album_id.0))
+                    .select(AlbumItem::asset_id.assume_not_null()),
+            ),
+        );
+    }
+    if let Some(group_id) = search.timeline_group_id {
+        query = query.filter(
+            Asset::asset_id.eq_any(
+                TimelineGroupItem::table
+                    .filter(TimelineGroupItem::group_id.eq(group_id.0))
+                    .select(TimelineGroupItem::asset_id),
+            ),
+        );
+    }
+    let db_assets: Vec<DbAsset> = query.load(conn).wrap_err("error searching Assets")?;
+    db_assets
+        .into_iter()
+        .map(|db_asset| db_asset.try_into())
+        .collect::<Result<Vec<_>>>()
+}
+
+/// SQL expression for a value in the stored exiftool output (`exiftool -j -g -n`),
+/// which is an array with a single object
+pub(crate) fn exiftool_field(path: &str) -> String {
+    format!(
+        "json_extract(CAST(Asset.exiftool_output AS TEXT), '{}')",
+        path
+    )
+}
+
+/// Escape `%`, `_` and `\` for LIKE ... ESCAPE '\'
+pub(crate) fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
@@ -0,0 +1,215 @@
+use diesel::{prelude::*, sql_types};
+use eyre::{eyre, Context, Result};
+use tracing::instrument;
+
+use crate::model::{AlbumId, AssetId, TimelineGroupId, UserId};
+
+use super::db::DbConn;
+
+// SearchIndex.rowid is (id << 2) | kind
+const KIND_ASSET: i64 = 1;
+const KIND_ALBUM: i64 = 2;
+const KIND_TIMELINE_GROUP: i64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextSearchHit {
+    Asset(AssetId),
+    Album(AlbumId),
+    TimelineGroup(TimelineGroupId),
+}
+
+#[derive(Debug, Clone, QueryableByName)]
+struct SearchIndexRow {
+    #[diesel(sql_type = sql_types::BigInt)]
+    kind: i64,
+    #[diesel(sql_type = sql_types::BigInt)]
+    ref_id: i64,
+}
+
+/// Assets, Albums and TimelineGroups visible to the user containing all words of the query
+/// (or words starting with them), best match first.
+/// Names weigh more than descriptions and keywords.
+#[instrument(skip(conn))]
+pub fn search_text(
+    conn: &mut DbConn,
+    user_id: UserId,
+    query: &str,
+    limit: i64,
+) -> Result<Vec<TextSearchHit>> {
+    let Some(match_expr) = fts_match_expression(query) else {
+        return Ok(Vec::new());
+    };
+    let rows: Vec<SearchIndexRow> = diesel::sql_query(
+        r#"
+    SELECT kind, ref_id FROM (
+        SELECT rowid & 3 AS kind, rowid >> 2 AS ref_id, bm25(SearchIndex, 10.0, 2.0, 5.0) AS rank
+        FROM SearchIndex
+        WHERE SearchIndex MATCH ?1
+    )
+    WHERE CASE kind
+        WHEN 1 THEN
+            EXISTS (
+                SELECT 1 FROM Asset
+                WHERE Asset.asset_id = ref_id AND Asset.missing_since IS NULL
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM UserAsset
+                WHERE UserAsset.asset_id = ref_id
+                AND UserAsset.user_id = ?2 AND UserAsset.is_hidden = 1
+            )
+        WHEN 2 THEN
+            EXISTS (
+                SELECT 1 FROM Album
+                WHERE Album.album_id = ref_id
+                AND (Album.is_private = 0 OR Album.owner_id = ?2)
+            )
+        WHEN 3 THEN
+            EXISTS (
+                SELECT 1 FROM TimelineGroup
+                WHERE TimelineGroup.timeline_group_id = ref_id
+                AND (TimelineGroup.is_private = 0 OR TimelineGroup.owner_id = ?2)
+            )
+        ELSE 0
+    END
+    ORDER BY rank
+    LIMIT ?3;
+    "#,
+    )
+    .bind::<sql_types::Text, _>(match_expr)
+    .bind::<sql_types::BigInt, _>(user_id.0)
+    .bind::<sql_types::BigInt, _>(limit)
+    .load(conn)
+    .wrap_err("error querying SearchIndex")?;
+    rows.into_iter()
+        .map(|row| match row.kind {
+            KIND_ASSET => Ok(TextSearchHit::Asset(AssetId(row.ref_id))),
+            KIND_ALBUM => Ok(TextSearchHit::Album(AlbumId(row.ref_id))),
+            KIND_TIMELINE_GROUP => Ok(TextSearchHit::TimelineGroup(TimelineGroupId(row.ref_id))),
+            other => Err(eyre!("invalid SearchIndex kind {}", other)),
+        })
+        .collect()
+}
+
+/// Quote every word so that user input can't be FTS5 query syntax,
+/// and match prefixes so that results show up while typing
+fn fts_match_expression(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// Index the path, ImageDescription and keywords of the Asset again
+pub(crate) fn index_asset(conn: &mut DbConn, asset_id: AssetId) -> Result<()> {
+    remove(conn, KIND_ASSET, asset_id.0)?;
+    // the exiftool output of test Assets etc. may not be JSON
+    diesel::sql_query(
+        r#"
+    INSERT INTO SearchIndex (rowid, name, description, keywords)
+    SELECT (asset_id << 2) | ?1, file_path,
+        json_extract(exif, '$[0].EXIF.ImageDescription'),
+        IFNULL(json_extract(exif, '$[0].EXIF.XPKeywords'), '') || ' '
+            || IFNULL(json_extract(exif, '$[0].IPTC.Keywords'), '')
+    FROM (
+        SELECT asset_id, file_path,
+            CASE WHEN json_valid(CAST(exiftool_output AS TEXT))
+                THEN CAST(exiftool_output AS TEXT) ELSE '[]' END AS exif
+        FROM Asset
+        WHERE asset_id = ?2
+    );
+    "#,
+    )
+    .bind::<sql_types::BigInt, _>(KIND_ASSET)
+    .bind::<sql_types::BigInt, _>(asset_id.0)
+    .execute(conn)
+    .wrap_err("error indexing Asset for search")?;
+    Ok(())
+}
+
+/// Index the name, description and text items of the Album again
+pub(crate) fn index_album(conn: &mut DbConn, album_id: AlbumId) -> Result<()> {
+    remove(conn, KIND_ALBUM, album_id.0)?;
+    diesel::sql_query(
+        r#"
+    INSERT INTO SearchIndex (rowid, name, description, keywords)
+    SELECT (album_id << 2) | ?1, name,
+        IFNULL(description, '') || ' ' || IFNULL((
+            SELECT group_concat(text, ' ') FROM AlbumItem
+            WHERE AlbumItem.album_id = Album.album_id AND AlbumItem.ty = 2
+        ), ''),
+        NULL
+    FROM Album
+    WHERE album_id = ?2;
+    "#,
+    )
+    .bind::<sql_types::BigInt, _>(KIND_ALBUM)
+    .bind::<sql_types::BigInt, _>(album_id.0)
+    .execute(conn)
+    .wrap_err("error indexing Album for search")?;
+    Ok(())
+}
+
+/// Index the name of the TimelineGroup again
+pub(crate) fn index_timeline_group(conn: &mut DbConn, group_id: TimelineGroupId) -> Result<()> {
+    remove(conn, KIND_TIMELINE_GROUP, group_id.0)?;
+    diesel::sql_query(
+        r#"
+    INSERT INTO SearchIndex (rowid, name, description, keywords)
+    SELECT (timeline_group_id << 2) | ?1, name, NULL, NULL
+    FROM TimelineGroup
+    WHERE timeline_group_id = ?2;
+    "#,
+    )
+    .bind::<sql_types::BigInt, _>(KIND_TIMELINE_GROUP)
+    .bind::<sql_types::BigInt, _>(group_id.0)
+    .execute(conn)
+    .wrap_err("error indexing TimelineGroup for search")?;
+    Ok(())
+}
+
+pub(crate) fn remove_asset(conn: &mut DbConn, asset_id: AssetId) -> Result<()> {
+    remove(conn, KIND_ASSET, asset_id.0)
+}
+
+pub(crate) fn remove_album(conn: &mut DbConn, album_id: AlbumId) -> Result<()> {
+    remove(conn, KIND_ALBUM, album_id.0)
+}
+
+pub(crate) fn remove_timeline_group(conn: &mut DbConn, group_id: TimelineGroupId) -> Result<()> {
+    remove(conn, KIND_TIMELINE_GROUP, group_id.0)
+}
+
+/// Remove the private Albums and TimelineGroups of the user, before deleting them
+pub(crate) fn remove_private_of_user(conn: &mut DbConn, user_id: UserId) -> Result<()> {
+    diesel::sql_query(
+        r#"
+    DELETE FROM SearchIndex
+    WHERE rowid IN (
+        SELECT (album_id << 2) | ?1 FROM Album WHERE owner_id = ?3 AND is_private = 1
+        UNION ALL
+        SELECT (timeline_group_id << 2) | ?2 FROM TimelineGroup
+        WHERE owner_id = ?3 AND is_private = 1
+    );
+    "#,
+    )
+    .bind::<sql_types::BigInt, _>(KIND_ALBUM)
+    .bind::<sql_types::BigInt, _>(KIND_TIMELINE_GROUP)
+    .bind::<sql_types::BigInt, _>(user_id.0)
+    .execute(conn)
+    .wrap_err("error deleting from SearchIndex")?;
+    Ok(())
+}
+
+fn remove(conn: &mut DbConn, kind: i64, ref_id: i64) -> Result<()> {
+    diesel::sql_query("DELETE FROM SearchIndex WHERE rowid = ?;")
+        .bind::<sql_types::BigInt, _>((ref_id << 2) | kind)
+        .execute(conn)
+        .wrap_err("error deleting from SearchIndex")?;
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use eyre::{Context, Result};
+use tracing::instrument;
+
+use crate::model::{
+    repository::db_entity::{DbAsset, DbShareLink},
+    util::{bool_to_int, datetime_to_db_repr},
+    Asset, AssetId, CreateShareLink, ShareLink, ShareLinkId, ShareLinkTarget, UserId,
+};
+
+use super::db::DbConn;
+use super::schema;
+
+#[instrument(skip(conn, create_link))]
+pub fn create_share_link(conn: &mut DbConn, create_link: &CreateShareLink) -> Result<ShareLinkId> {
+    use schema::ShareLink;
+    let (album_id, timeline_group_id, asset_id) = match create_link.target {
+        ShareLinkTarget::Album(id) => (Some(id.0), None, None),
+        ShareLinkTarget::TimelineGroup(id) => (None, Some(id.0), None),
+        ShareLinkTarget::Asset(id) => (None, None, Some(id.0)),
+    };
+    let id = diesel::insert_into(ShareLink::table)
+        .values((
+            ShareLink::token_hash.eq(&create_link.token_hash),
+            ShareLink::album_id.eq(album_id),
+            ShareLink::timeline_group_id.eq(timeline_group_id),
+            ShareLink::asset_id.eq(asset_id),
+            ShareLink::created_by_id.eq(create_link.created_by.0),
+            ShareLink::created_at.eq(datetime_to_db_repr(&Utc::now())),
+            ShareLink::expires_at.eq(create_link.expires_at.as_ref().map(datetime_to_db_repr)),
+            ShareLink::password_hash.eq(&create_link.password_hash),
+            ShareLink::allow_download.eq(bool_to_int(create_link.allow_download)),
+        ))
+        .returning(ShareLink::share_link_id)
+        .get_result(conn)
+        .wrap_err("error inserting into table ShareLink")?;
+    Ok(ShareLinkId(id))
+}
+
+/// Users can only share what they can see themselves
+#[instrument(skip(conn), level = "trace")]
+pub fn is_share_target_visible(
+    conn: &mut DbConn,
+    target: ShareLinkTarget,
+    user_id: UserId,
+) -> Result<bool> {
+    use schema::Asset;
+    match target {
+        ShareLinkTarget::Album(album_id) => super::album::is_album_visible(conn, album_id, user_id),
+        ShareLinkTarget::TimelineGroup(group_id) => {
+            super::timeline_group::is_timeline_group_visible(conn, group_id, user_id)
+        }
+        ShareLinkTarget::Asset(asset_id) => {
+            let count: i64 = Asset::table
+                .filter(Asset::asset_id.eq(asset_id.0))
+                .count()
+                .get_result(conn)
+                .wrap_err("error querying table Asset")?;
+            Ok(count > 0)
+        }
+    }
+}
+
+/// The ShareLink for a token, if it exists and has not expired
+#[instrument(skip(conn, token_hash))]
+pub fn find_share_link_for_token(
+    conn: &mut DbConn,
+    token_hash: &[u8],
+    now: DateTime<Utc>,
+) -> Result<Option<ShareLink>> {
+    use schema::ShareLink;
+    let db_link: Option<DbShareLink> = ShareLink::table
+        .filter(ShareLink::token_hash.eq(token_hash))
+        .filter(
+            ShareLink::expires_at
+                .is_null()
+                .or(ShareLink::expires_at.gt(datetime_to_db_repr(&now))),
+        )
+        .select(DbShareLink::as_select())
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table ShareLink")?;
+    db_link.map(|l| l.try_into()).transpose()
+}
+
+#[instrument(skip(conn))]
+pub fn get_share_links(conn: &mut DbConn, user_id: UserId) -> Result<Vec<ShareLink>> {
+    use schema::ShareLink;
+    let db_links: Vec<DbShareLink> = ShareLink::table
+        .filter(ShareLink::created_by_id.eq(user_id.0))
+        .select(DbShareLink::as_select())
+        .order_by(ShareLink::share_link_id)
+        .load(conn)
+        .wrap_err("error querying table ShareLink")?;
+    db_links.into_iter().map(|l| l.try_into()).collect()
+}
+
+/// Returns false if the User has no ShareLink with this id
+#[instrument(skip(conn))]
+pub fn delete_share_link(conn: &mut DbConn, user_id: UserId, id: ShareLinkId) -> Result<bool> {
+    use schema::ShareLink;
+    let deleted = diesel::delete(
+        ShareLink::table
+            .filter(ShareLink::share_link_id.eq(id.0))
+            .filter(ShareLink::created_by_id.eq(user_id.0)),
+    )
+    .execute(conn)
+    .wrap_err("error deleting from table ShareLink")?;
+    Ok(deleted > 0)
+}
+
+#[instrument(skip(conn))]
+pub fn delete_expired_share_links(conn: &mut DbConn, now: DateTime<Utc>) -> Result<usize> {
+    use schema::ShareLink;
+    diesel::delete(ShareLink::table.filter(ShareLink::expires_at.le(datetime_to_db_repr(&now))))
+        .execute(conn)
+        .wrap_err("error deleting from table ShareLink")
+}
+
+/// The Assets visible through a ShareLink, in album order for albums and
+/// newest first for TimelineGroups
+#[instrument(skip(conn))]
+pub fn get_shared_assets(conn: &mut DbConn, target: ShareLinkTarget) -> Result<Vec<Asset>> {
+    use schema::{AlbumItem, Asset, TimelineGroupItem};
+    let db_assets: Vec<DbAsset> = match target {
+        ShareLinkTarget::Album(album_id) => AlbumItem::table
+            .inner_join(Asset::table)
+            .filter(AlbumItem::album_id.eq(album_id.0))
+            .filter(Asset::missing_since.is_null())
+            .order_by(AlbumItem::idx)
+            .select(DbAsset::as_select())
+            .load(conn)
+            .wrap_err("error querying table AlbumItem")?,
+        ShareLinkTarget::TimelineGroup(group_id) => TimelineGroupItem::table
+            .inner_join(Asset::table)
+            .filter(TimelineGroupItem::group_id.eq(group_id.0))
+            .filter(Asset::missing_since.is_null())
+            .order_by((Asset::taken_date.desc(), Asset::asset_id.desc()))
+            .select(DbAsset::as_select())
+            .load(conn)
+            .wrap_err("error querying table TimelineGroupItem")?,
+        ShareLinkTarget::Asset(asset_id) => Asset::table
+            .filter(Asset::asset_id.eq(asset_id.0))
+            .filter(Asset::missing_since.is_null())
+            .select(DbAsset::as_select())
+            .load(conn)
+            .wrap_err("error querying table Asset")?,
+    };
+    db_assets.into_iter().map(|a| a.try_into()).collect()
+}
+
+/// If an Asset may be served for a ShareLink
+#[instrument(skip(conn), level = "trace")]
+pub fn is_asset_shared(
+    conn: &mut DbConn,
+    target: ShareLinkTarget,
+    asset_id: AssetId,
+) -> Result<bool> {
+    use schema::{AlbumItem, TimelineGroupItem};
+    let count: i64 = match target {
+        ShareLinkTarget::Album(album_id) => AlbumItem::table
+            .filter(AlbumItem::album_id.eq(album_id.0))
+            .filter(AlbumItem::asset_id.eq(asset_id.0))
+            .count()
+            .get_result(conn)
+            .wrap_err("error querying table AlbumItem")?,
+        ShareLinkTarget::TimelineGroup(group_id) => TimelineGroupItem::table
+            .filter(TimelineGroupItem::group_id.eq(group_id.0))
+            .filter(TimelineGroupItem::asset_id.eq(asset_id.0))
+            .count()
+            .get_result(conn)
+            .wrap_err("error querying table TimelineGroupItem")?,
+        ShareLinkTarget::Asset(shared_asset_id) => i64::from(shared_asset_id == asset_id),
+    };
+    Ok(count > 0)
+}
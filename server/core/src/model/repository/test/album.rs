@@ -6,6 +6,7 @@ use itertools::Itertools;
 use proptest::prelude::*;
 
 use super::proptest_arb::{arb_new_album, arb_new_asset};
+use super::utc_now_millis_zero;
 use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
 
 use crate::model::repository::album::AddItemToAlbum;
@@ -13,7 +14,7 @@ use crate::model::repository::test::proptest_arb::arb_new_album_item;
 use crate::model::repository::test::util::prop_insert_create_test_asset;
 use crate::model::{
     repository::{self, album::CreateAlbum},
-    Album, Asset, AssetId, AssetRootDir, AssetRootDirId,
+    Album, Asset, AssetId, AssetRootDir, AssetRootDirId, UserId,
 };
 use crate::model::{AlbumItem, AlbumItemType, AssetBase};
 
@@ -65,6 +66,9 @@ fn prop_create_retrieve_albums() {
             let create_album = CreateAlbum {
                 name: album.name.clone(),
                 description: album.description.clone(),
+                owner_id: album.owner_id,
+                is_private: album.is_private,
+                smart_filter: None,
             };
             // TODO initial creation with assets to insert right away not tested here
             let album_insert_result = repository::album::create_album(&mut conn, create_album, &[]);
@@ -91,7 +95,8 @@ fn prop_create_retrieve_albums() {
             albums_items_with_ids.push((album.clone(), assets_actually_appended));
         }
         for (album, expected_items) in albums_items_with_ids {
-            let retrieve_result = repository::album::get_items_in_album(&mut conn, album.id);
+            // nothing is hidden for any user
+            let retrieve_result = repository::album::get_items_in_album(&mut conn, album.id, UserId(1));
             prop_assert!(retrieve_result.is_ok());
             let actual_items_in_album: Vec<AlbumItem> = retrieve_result.unwrap();
             let expected_indices: Vec<usize> = (0..expected_items.len()).collect();
@@ -117,3 +122,186 @@ fn prop_create_retrieve_albums() {
         }
     })
 }
+
+#[test]
+fn prop_edit_and_delete_album() {
+    use crate::model::AlbumItemId;
+
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 1..8),
+        text_position in 0usize..10,
+        move_position in 0usize..10,
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let asset_ids: Vec<AssetId> = assets.iter().map(|asset| asset.base.id).collect();
+        let album_id = assert_ok!(repository::album::create_album(
+            &mut conn,
+            CreateAlbum {
+                name: Some("album".into()),
+                description: Some("description".into()),
+                owner_id: None,
+                is_private: false,
+                smart_filter: None,
+            },
+            &asset_ids
+        ));
+        let created = assert_ok!(repository::album::get_album(&mut conn, album_id));
+
+        assert_ok!(repository::album::update_album(
+            &mut conn,
+            album_id,
+            repository::album::UpdateAlbum {
+                name: Some("renamed".into()),
+                description: Some(None),
+                smart_filter: None,
+            }
+        ));
+        let updated = assert_ok!(repository::album::get_album(&mut conn, album_id));
+        prop_assert_eq!(updated.name.as_deref(), Some("renamed"));
+        prop_assert_eq!(updated.description, None);
+        prop_assert!(updated.changed_at >= created.changed_at);
+
+        let item_ids = |conn: &mut _| -> Result<Vec<AlbumItemId>, TestCaseError> {
+            let items = assert_ok!(repository::album::get_items_in_album(conn, album_id, UserId(1)));
+            Ok(items.into_iter().map(|item| item.id).collect())
+        };
+        let mut expected = item_ids(&mut conn)?;
+        let inserted = assert_ok!(repository::album::insert_items_into_album(
+            &mut conn,
+            album_id,
+            &[AddItemToAlbum::Text("caption".into())],
+            Some(text_position)
+        ));
+        prop_assert_eq!(inserted.len(), 1);
+        let text_position = text_position.min(expected.len());
+        expected.insert(text_position, inserted[0]);
+        prop_assert_eq!(&item_ids(&mut conn)?, &expected);
+
+        // move the caption and the first asset
+        let moved = vec![inserted[0], expected[if text_position == 0 { 1 } else { 0 }]];
+        assert_ok!(repository::album::move_album_items(&mut conn, album_id, &moved, move_position));
+        expected.retain(|id| !moved.contains(id));
+        let move_position = move_position.min(expected.len());
+        expected.splice(move_position..move_position, moved);
+        prop_assert_eq!(&item_ids(&mut conn)?, &expected);
+        let actual_indices: Vec<i32> = {
+            use diesel::prelude::*;
+            use super::super::schema::AlbumItem;
+            AlbumItem::table
+                .filter(AlbumItem::album_id.eq(album_id.0))
+                .select(AlbumItem::idx)
+                .order_by(AlbumItem::idx)
+                .load(&mut conn).unwrap()
+        };
+        prop_assert_eq!(actual_indices, (0..expected.len() as i32).collect::<Vec<_>>());
+
+        assert_ok!(repository::album::delete_album(&mut conn, album_id));
+        prop_assert!(repository::album::get_album(&mut conn, album_id).is_err());
+        let remaining = assert_ok!(repository::album::get_items_in_album(&mut conn, album_id, UserId(1)));
+        prop_assert!(remaining.is_empty());
+    })
+}
+
+#[test]
+fn prop_smart_album() {
+    use crate::model::{AssetType, SmartAlbumFilter};
+
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 0..10),
+        new_asset in arb_new_asset(),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let mut assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let filter = SmartAlbumFilter {
+            asset_type: Some(AssetType::Image),
+            has_gps: Some(true),
+            ..Default::default()
+        };
+        let album_id = assert_ok!(repository::album::create_album(
+            &mut conn,
+            CreateAlbum {
+                name: Some("smart".into()),
+                description: None,
+                owner_id: None,
+                is_private: false,
+                smart_filter: Some(filter.clone()),
+            },
+            &[]
+        ));
+        let album = assert_ok!(repository::album::get_album(&mut conn, album_id));
+        prop_assert_eq!(album.smart_filter.as_ref(), Some(&filter));
+
+        let expected_items = |assets: &[Asset]| -> Vec<AssetId> {
+            assets
+                .iter()
+                .filter(|asset| asset.base.ty == AssetType::Image && asset.base.gps_coordinates.is_some())
+                .sorted_by_key(|asset| (asset.base.taken_date, asset.base.id))
+                .map(|asset| asset.base.id)
+                .collect()
+        };
+        let actual_items = |conn: &mut _| -> Vec<AssetId> {
+            assert_ok!(repository::album::get_assets_in_album(conn, album_id, None))
+                .into_iter()
+                .map(|asset| asset.base.id)
+                .collect()
+        };
+        let actual_indices = |conn: &mut super::db::DbConn| -> Vec<i32> {
+            use diesel::prelude::*;
+            use super::super::schema::AlbumItem;
+            AlbumItem::table
+                .filter(AlbumItem::album_id.eq(album_id.0))
+                .select(AlbumItem::idx)
+                .order_by(AlbumItem::idx)
+                .load(conn)
+                .unwrap()
+        };
+        prop_assert_eq!(actual_items(&mut conn), expected_items(&assets));
+
+        // new assets are added at their place when they match
+        let new_asset = set_assets_root_dir(vec![new_asset], root_dir_id);
+        let new_asset = prop_insert_create_test_assets(&mut conn, &new_asset)?;
+        let new_asset_id = new_asset[0].base.id;
+        assert_ok!(repository::album::update_smart_albums_for_asset(&mut conn, new_asset_id));
+        assets.extend(new_asset);
+        let expected = expected_items(&assets);
+        prop_assert_eq!(actual_items(&mut conn), expected.clone());
+        prop_assert_eq!(actual_indices(&mut conn), (0..expected.len() as i32).collect::<Vec<_>>());
+
+        // and removed when their file goes missing
+        assert_ok!(repository::asset::set_assets_missing(&mut conn, &[new_asset_id], Some(utc_now_millis_zero())));
+        let expected: Vec<AssetId> = expected.into_iter().filter(|id| *id != new_asset_id).collect();
+        prop_assert_eq!(actual_items(&mut conn), expected.clone());
+        prop_assert_eq!(actual_indices(&mut conn), (0..expected.len() as i32).collect::<Vec<_>>());
+        assert_ok!(repository::asset::set_assets_missing(&mut conn, &[new_asset_id], None));
+        prop_assert_eq!(actual_items(&mut conn), expected_items(&assets));
+
+        // changing the filter updates the items right away
+        assert_ok!(repository::album::update_album(
+            &mut conn,
+            album_id,
+            repository::album::UpdateAlbum {
+                smart_filter: Some(SmartAlbumFilter::default()),
+                ..Default::default()
+            }
+        ));
+        let all: Vec<AssetId> = assets
+            .iter()
+            .sorted_by_key(|asset| (asset.base.taken_date, asset.base.id))
+            .map(|asset| asset.base.id)
+            .collect();
+        prop_assert_eq!(actual_items(&mut conn), all);
+    })
+}
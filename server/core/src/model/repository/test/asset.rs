@@ -11,13 +11,14 @@ use proptest::prelude::*;
 use proptest_arb::{arb_new_asset, arb_new_video_asset};
 
 use crate::model::{
-    repository, Asset, AssetBase, AssetId, AssetRootDir, AssetRootDirId, AssetSpe, AssetType,
+    repository::{self, db::DbConn},
+    Asset, AssetBase, AssetId, AssetRootDir, AssetRootDirId, AssetSpe, AssetType,
     AudioRepresentation, AudioRepresentationId, CreateAsset, CreateAssetBase, CreateAssetImage,
     CreateAssetSpe, Image, Size, ThumbnailFormat, ThumbnailType, TimestampInfo, Video, VideoAsset,
     VideoRepresentation, VideoRepresentationId,
 };
 
-use super::util::{set_asset_root_dir, set_video_asset_root_dir};
+use super::util::{create_image_asset, set_asset_root_dir, set_video_asset_root_dir};
 use super::*;
 
 #[test]
@@ -80,7 +81,6 @@ fn inserting_mismatching_asset_ty_and_spe_fails() {
             ty: AssetType::Video,
             file_type: "jpeg".to_owned(),
             file_path: PathBuf::from("image.jpg"),
-            is_hidden: false,
             added_at: utc_now_millis_zero(),
             taken_date: utc_now_millis_zero()
                 .checked_sub_months(Months::new(2))
@@ -225,7 +225,6 @@ fn get_videos_without_dash() {
             root_dir_id: root_dir2_id,
             file_type: "mp4".to_owned(),
             file_path: PathBuf::from("video.mp4"),
-            is_hidden: false,
             added_at: utc_now_millis_zero(),
             taken_date: utc_now_millis_zero()
                 .checked_sub_months(Months::new(3))
@@ -358,7 +357,6 @@ fn get_videos_in_acceptable_codec_without_dash() {
             root_dir_id,
             file_type: "mp4".to_owned(),
             file_path: PathBuf::from("video.mp4"),
-            is_hidden: false,
             added_at: utc_now_millis_zero(),
             taken_date: utc_now_millis_zero()
                 .checked_sub_months(Months::new(3))
@@ -650,3 +648,377 @@ fn prop_get_videos_with_no_acceptable_codec_repr() {
         prop_assert_eq!(actual_ids, expected_no_acceptable_reprs);
     });
 }
+
+#[test]
+fn prop_delete_asset_referenced_as_motion_photo() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 2..5),
+    )| {
+        use repository::schema::Asset;
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = super::util::set_assets_root_dir(assets, root_dir_id);
+        let assets = super::util::prop_insert_create_test_assets(&mut conn, &assets)?;
+        let deleted_id = assets[0].base.id;
+        let referencing_ids: Vec<i64> = assets[1..].iter().map(|a| a.base.id.0).collect();
+        assert_ok!(diesel::update(Asset::table.filter(Asset::asset_id.eq_any(&referencing_ids)))
+            .set(Asset::motion_photo_assoc_asset_id.eq(deleted_id.0))
+            .execute(&mut conn));
+
+        assert_ok!(repository::asset::delete_asset(&mut conn, deleted_id));
+        assert_err!(repository::asset::get_asset(&mut conn, deleted_id));
+        let assoc_ids: Vec<Option<i64>> = assert_ok!(Asset::table
+            .filter(Asset::asset_id.eq_any(&referencing_ids))
+            .select(Asset::motion_photo_assoc_asset_id)
+            .load(&mut conn));
+        prop_assert_eq!(assoc_ids, vec![None; referencing_ids.len()]);
+    });
+}
+
+#[test]
+fn missing_assets_are_purged_only_after_grace_period() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let asset_root_dir = AssetRootDir {
+        id: AssetRootDirId(0),
+        path: PathBuf::from("/path/to/assets"),
+    };
+    let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(
+        &mut conn,
+        &asset_root_dir
+    ));
+    let gone_long_ago = assert_ok!(repository::asset::create_asset(
+        &mut conn,
+        create_image_asset(root_dir_id, "a.jpg", 1)
+    ));
+    let gone_recently = assert_ok!(repository::asset::create_asset(
+        &mut conn,
+        create_image_asset(root_dir_id, "b.jpg", 2)
+    ));
+    let present = assert_ok!(repository::asset::create_asset(
+        &mut conn,
+        create_image_asset(root_dir_id, "c.jpg", 3)
+    ));
+    let now = utc_now_millis_zero();
+    assert_ok!(repository::asset::set_assets_missing(
+        &mut conn,
+        &[gone_long_ago],
+        Some(now - chrono::Duration::days(60))
+    ));
+    assert_ok!(repository::asset::set_assets_missing(
+        &mut conn,
+        &[gone_recently],
+        Some(now - chrono::Duration::hours(1))
+    ));
+    // still missing on the next scan, the original timestamp is kept
+    assert_ok!(repository::asset::set_assets_missing(
+        &mut conn,
+        &[gone_long_ago, gone_recently],
+        Some(now)
+    ));
+
+    let grace_period_start = now - chrono::Duration::days(30);
+    assert_eq!(
+        assert_ok!(repository::asset::get_assets_missing_since_before(
+            &mut conn,
+            grace_period_start
+        )),
+        vec![gone_long_ago]
+    );
+    let mut files = assert_ok!(repository::asset::get_asset_files_in_root(
+        &mut conn,
+        root_dir_id
+    ));
+    files.sort_by_key(|file| file.asset_id);
+    assert_eq!(
+        files
+            .iter()
+            .map(|file| (file.asset_id, file.is_missing))
+            .collect_vec(),
+        vec![
+            (gone_long_ago, true),
+            (gone_recently, true),
+            (present, false)
+        ]
+    );
+
+    // file reappeared within the grace period
+    assert_ok!(repository::asset::set_assets_missing(
+        &mut conn,
+        &[gone_recently],
+        None
+    ));
+    let state = assert_ok!(repository::asset::get_asset_file_state(
+        &mut conn,
+        gone_recently
+    ))
+    .unwrap();
+    assert!(!state.is_missing);
+    assert_eq!(
+        assert_ok!(repository::asset::get_assets_missing_since_before(
+            &mut conn, now
+        )),
+        vec![gone_long_ago]
+    );
+}
+
+#[test]
+fn moved_asset_points_to_new_path() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(
+        &mut conn,
+        &AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        }
+    ));
+    let other_root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(
+        &mut conn,
+        &AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/more/assets"),
+        }
+    ));
+    let asset_id = assert_ok!(repository::asset::create_asset(
+        &mut conn,
+        create_image_asset(root_dir_id, "old/a.jpg", 1)
+    ));
+    assert_ok!(repository::asset::set_assets_missing(
+        &mut conn,
+        &[asset_id],
+        Some(utc_now_millis_zero())
+    ));
+
+    assert_ok!(repository::asset::set_asset_path(
+        &mut conn,
+        asset_id,
+        other_root_dir_id,
+        camino::Utf8Path::new("new/a.jpg")
+    ));
+    let path = assert_ok!(repository::asset::get_asset_path_on_disk(
+        &mut conn, asset_id
+    ));
+    assert_eq!(
+        path.path_on_disk(),
+        PathBuf::from("/path/to/more/assets/new/a.jpg")
+    );
+    let state = assert_ok!(repository::asset::get_asset_file_state(&mut conn, asset_id)).unwrap();
+    assert!(!state.is_missing);
+    assert_eq!(
+        assert_ok!(repository::asset::get_asset_file_state_with_path(
+            &mut conn,
+            root_dir_id,
+            camino::Utf8Path::new("old/a.jpg")
+        )),
+        None
+    );
+}
+
+fn insert_root_and_asset(
+    conn: &mut DbConn,
+    create_asset: impl FnOnce(AssetRootDirId) -> CreateAsset,
+) -> (AssetRootDirId, AssetId) {
+    let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(
+        conn,
+        &AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        }
+    ));
+    let asset_id = assert_ok!(repository::asset::create_asset(
+        conn,
+        create_asset(root_dir_id)
+    ));
+    (root_dir_id, asset_id)
+}
+
+#[test]
+fn unchanged_file_stat_is_read_back_exactly() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let mtime = utc_now_millis_zero();
+    let (root_dir_id, asset_id) = insert_root_and_asset(&mut conn, |root_dir_id| {
+        let mut create_asset = create_image_asset(root_dir_id, "a.jpg", 1);
+        create_asset.base.file_mtime = Some(mtime);
+        create_asset.base.file_size = Some(1234);
+        create_asset
+    });
+    let state = assert_ok!(repository::asset::get_asset_file_state_with_path(
+        &mut conn,
+        root_dir_id,
+        camino::Utf8Path::new("a.jpg")
+    ))
+    .unwrap();
+    // compared to the file's current stat to decide whether to hash it again
+    assert_eq!(
+        state,
+        repository::asset::AssetFileState {
+            asset_id,
+            hash: Some(1),
+            file_mtime: Some(mtime),
+            file_size: Some(1234),
+            is_missing: false,
+        }
+    );
+}
+
+#[test]
+fn touched_file_only_updates_file_stat() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let (_root_dir_id, asset_id) = insert_root_and_asset(&mut conn, |root_dir_id| {
+        let mut create_asset = create_image_asset(root_dir_id, "a.jpg", 1);
+        create_asset.base.file_mtime = Some(utc_now_millis_zero() - chrono::Duration::days(1));
+        create_asset.base.file_size = Some(1234);
+        create_asset
+    });
+    let asset_before = assert_ok!(repository::asset::get_asset(&mut conn, asset_id));
+    let new_mtime = utc_now_millis_zero();
+    assert_ok!(repository::asset::set_asset_file_stat(
+        &mut conn, asset_id, new_mtime, 1234
+    ));
+    let state = assert_ok!(repository::asset::get_asset_file_state(&mut conn, asset_id)).unwrap();
+    assert_eq!(state.file_mtime, Some(new_mtime));
+    assert_eq!(state.hash, Some(1));
+    assert_eq!(
+        assert_ok!(repository::asset::get_asset(&mut conn, asset_id)),
+        asset_before
+    );
+}
+
+#[test]
+fn modified_file_updates_asset_in_place() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let (root_dir_id, asset_id) = insert_root_and_asset(&mut conn, |root_dir_id| {
+        create_image_asset(root_dir_id, "a.jpg", 1)
+    });
+    let album_id = assert_ok!(repository::album::create_album(
+        &mut conn,
+        repository::album::CreateAlbum {
+            name: Some("album".to_owned()),
+            description: None,
+            owner_id: None,
+            is_private: false,
+            smart_filter: None,
+        },
+        &[asset_id]
+    ));
+    for format_name in ["webp", "avif"] {
+        assert_ok!(repository::album_thumbnail::insert_album_thumbnail(
+            &mut conn,
+            repository::album_thumbnail::InsertAlbumThumbnail {
+                album_id,
+                format_name: format_name.to_owned(),
+                size: 256,
+                file_key: format!("album_thumb.{}", format_name),
+            }
+        ));
+    }
+    assert_eq!(
+        assert_ok!(repository::album_thumbnail::get_albums_with_missing_thumbnails(&mut conn)),
+        vec![]
+    );
+
+    let mut modified = create_image_asset(root_dir_id, "a.jpg", 2);
+    modified.base.taken_date = utc_now_millis_zero() - chrono::Duration::days(365);
+    modified.base.size = Size {
+        width: 768,
+        height: 1024,
+    };
+    modified.base.file_mtime = Some(utc_now_millis_zero());
+    modified.base.file_size = Some(4321);
+    assert_ok!(repository::asset::update_asset_from_file(
+        &mut conn, asset_id, &modified
+    ));
+
+    let asset = assert_ok!(repository::asset::get_asset(&mut conn, asset_id));
+    assert_eq!(asset.base.hash, Some(2));
+    assert_eq!(asset.base.taken_date, modified.base.taken_date);
+    assert_eq!(asset.base.size, modified.base.size);
+    assert_eq!(asset.base.file_path, PathBuf::from("a.jpg"));
+    assert_eq!(
+        assert_ok!(repository::asset::get_asset_with_hash(&mut conn, 2)),
+        Some(asset_id)
+    );
+    assert_eq!(
+        assert_ok!(repository::asset::get_asset_with_hash(&mut conn, 1)),
+        None
+    );
+    let state = assert_ok!(repository::asset::get_asset_file_state(&mut conn, asset_id)).unwrap();
+    assert_eq!(state.file_mtime, modified.base.file_mtime);
+    assert_eq!(state.file_size, Some(4321));
+    // still in the album, but the album thumbnail may show the old version
+    assert_eq!(
+        assert_ok!(repository::album::get_assets_in_album(
+            &mut conn, album_id, None
+        ))
+        .into_iter()
+        .map(|asset| asset.base.id)
+        .collect_vec(),
+        vec![asset_id]
+    );
+    assert_eq!(
+        assert_ok!(repository::album_thumbnail::get_albums_with_missing_thumbnails(&mut conn)),
+        vec![album_id]
+    );
+}
+
+#[test]
+fn modified_file_identical_to_other_asset_becomes_duplicate() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let (root_dir_id, asset_id) = insert_root_and_asset(&mut conn, |root_dir_id| {
+        create_image_asset(root_dir_id, "a.jpg", 1)
+    });
+    let other_asset_id = assert_ok!(repository::asset::create_asset(
+        &mut conn,
+        create_image_asset(root_dir_id, "b.jpg", 2)
+    ));
+    let mtime = utc_now_millis_zero();
+
+    let dup_id = assert_ok!(
+        repository::duplicate_asset::replace_asset_file_with_duplicate(
+            &mut conn,
+            asset_id,
+            other_asset_id,
+            mtime,
+            4321,
+            utc_now_millis_zero()
+        )
+    );
+    let dup = assert_ok!(repository::duplicate_asset::find_duplicate_with_path(
+        &mut conn,
+        root_dir_id,
+        camino::Utf8Path::new("a.jpg")
+    ))
+    .unwrap();
+    assert_eq!(dup.id, dup_id);
+    assert_eq!(dup.asset_id, other_asset_id);
+    let state = assert_ok!(repository::asset::get_asset_file_state(&mut conn, asset_id)).unwrap();
+    assert!(state.is_missing);
+    // not hashed again as long as the file doesn't change
+    assert_eq!(state.file_mtime, Some(mtime));
+    assert_eq!(state.file_size, Some(4321));
+    // the old content can still be found if it turns up elsewhere
+    assert_eq!(state.hash, Some(1));
+
+    // happening again does not record the path twice
+    assert_ok!(
+        repository::duplicate_asset::replace_asset_file_with_duplicate(
+            &mut conn,
+            asset_id,
+            other_asset_id,
+            mtime,
+            4321,
+            utc_now_millis_zero()
+        )
+    );
+    assert_eq!(
+        assert_ok!(repository::duplicate_asset::get_duplicates_of_asset(
+            &mut conn,
+            other_asset_id
+        ))
+        .len(),
+        1
+    );
+}
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+
+use camino::Utf8PathBuf as PathBuf;
+use claims::assert_ok;
+use proptest::prelude::*;
+
+use crate::model::{
+    repository::{self, asset_place::PlaceFilter},
+    AssetId, AssetPlace, AssetRootDir, AssetRootDirId, GpsCoordinates, UserId,
+};
+
+use super::proptest_arb::arb_new_asset;
+use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
+
+#[test]
+fn prop_geocode_assets_and_filter_timeline() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 1..30),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let with_gps: Vec<(AssetId, GpsCoordinates)> = assets
+            .iter()
+            .filter_map(|a| a.base.gps_coordinates.map(|c| (a.base.id, c)))
+            .collect();
+
+        let to_geocode: HashSet<(AssetId, GpsCoordinates)> =
+            assert_ok!(repository::asset_place::get_assets_to_geocode(&mut conn)).into_iter().collect();
+        prop_assert_eq!(&to_geocode, &with_gps.iter().copied().collect());
+        let Some(&(asset_id, coordinates)) = with_gps.first() else {
+            return Ok(());
+        };
+
+        let place = AssetPlace {
+            country_code: "CH".to_owned(),
+            country: "Switzerland".to_owned(),
+            admin_region: Some("Zurich".to_owned()),
+            city: "Zürich".to_owned(),
+        };
+        assert_ok!(repository::asset_place::set_asset_place(&mut conn, asset_id, coordinates, Some(&place)));
+        prop_assert_eq!(assert_ok!(repository::asset_place::get_asset_place(&mut conn, asset_id)), Some(place));
+        prop_assert_eq!(assert_ok!(repository::asset_place::get_asset_to_geocode(&mut conn, asset_id)), None);
+        prop_assert_eq!(assert_ok!(repository::asset_place::get_assets_to_geocode(&mut conn)).len(), with_gps.len() - 1);
+
+        let timeline_ids = |conn: &mut _, place: &PlaceFilter| -> HashSet<AssetId> {
+            assert_ok!(repository::timeline::get_timeline_chunk(conn, UserId(1), None, 1000, place))
+                .iter()
+                .flat_map(|el| el.get_assets().iter().map(|a| a.base.id))
+                .collect()
+        };
+        let in_zurich = PlaceFilter {
+            country_code: Some("CH".to_owned()),
+            admin_region: None,
+            city: Some("Zürich".to_owned()),
+        };
+        prop_assert_eq!(timeline_ids(&mut conn, &in_zurich), HashSet::from([asset_id]));
+        prop_assert_eq!(timeline_ids(&mut conn, &PlaceFilter::default()).len(), assets.len());
+        let in_geneva = PlaceFilter {
+            city: Some("Geneva".to_owned()),
+            ..in_zurich.clone()
+        };
+        prop_assert!(timeline_ids(&mut conn, &in_geneva).is_empty());
+        let sections = assert_ok!(repository::timeline::get_sections(&mut conn, UserId(1), &in_zurich));
+        prop_assert_eq!(sections.iter().map(|s| s.num_assets).sum::<i64>(), 1);
+
+        // not inside any country
+        assert_ok!(repository::asset_place::set_asset_place(&mut conn, asset_id, coordinates, None));
+        prop_assert_eq!(assert_ok!(repository::asset_place::get_asset_place(&mut conn, asset_id)), None);
+        prop_assert_eq!(assert_ok!(repository::asset_place::get_asset_to_geocode(&mut conn, asset_id)), None);
+        prop_assert!(timeline_ids(&mut conn, &in_zurich).is_empty());
+
+        // geocoded again once the coordinates no longer match
+        let moved = GpsCoordinates { lat: coordinates.lat + 1, ..coordinates };
+        assert_ok!(repository::asset_place::set_asset_place(&mut conn, asset_id, moved, None));
+        prop_assert_eq!(assert_ok!(repository::asset_place::get_asset_to_geocode(&mut conn, asset_id)), Some(coordinates));
+
+        assert_ok!(repository::asset::delete_asset(&mut conn, asset_id));
+        prop_assert_eq!(assert_ok!(repository::asset_place::get_asset_place(&mut conn, asset_id)), None);
+    })
+}
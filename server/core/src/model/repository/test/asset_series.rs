@@ -0,0 +1,103 @@
+use camino::Utf8PathBuf as PathBuf;
+use claims::{assert_err, assert_ok};
+use diesel::prelude::*;
+use proptest::prelude::*;
+
+use crate::model::{
+    repository::{self, schema},
+    AssetId, AssetRootDir, AssetRootDirId, UserRole,
+};
+
+use super::proptest_arb::arb_new_image_asset;
+use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
+
+#[test]
+fn prop_create_auto_series() {
+    proptest!(|(
+        images in prop::collection::vec(arb_new_image_asset(), 3..10),
+        selection_idx in any::<prop::sample::Index>(),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(images.into_iter().map(|image| image.into()).collect(), root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let asset_ids: Vec<AssetId> = assets.iter().map(|asset| asset.base.id).collect();
+        let candidates = assert_ok!(repository::asset_series::get_series_detection_candidates(&mut conn));
+        prop_assert_eq!(candidates.len(), assets.len());
+        prop_assert!(candidates.windows(2).all(|w| w[0].taken_date <= w[1].taken_date));
+
+        let (series_ids, rest) = asset_ids.split_at(asset_ids.len() - 1);
+        let selection = *selection_idx.get(series_ids);
+        assert_err!(repository::asset_series::create_auto_series(&mut conn, series_ids, rest[0]));
+        let series_id = assert_ok!(repository::asset_series::create_auto_series(&mut conn, series_ids, selection));
+        prop_assert_eq!(
+            assert_ok!(repository::asset_series::get_series_asset_ids(&mut conn, series_id)).len(),
+            series_ids.len()
+        );
+
+        let candidates = assert_ok!(repository::asset_series::get_series_detection_candidates(&mut conn));
+        let candidate_ids: Vec<AssetId> = candidates.iter().map(|c| c.asset_id).collect();
+        prop_assert_eq!(candidate_ids, rest.to_vec());
+
+        let selections: Vec<i64> = assert_ok!(schema::Asset::table
+            .filter(schema::Asset::is_series_selection.eq(1))
+            .select(schema::Asset::asset_id)
+            .load(&mut conn));
+        prop_assert_eq!(selections, vec![selection.0]);
+        let is_auto: Vec<i32> = assert_ok!(schema::AssetSeries::table
+            .select(schema::AssetSeries::is_auto)
+            .load(&mut conn));
+        prop_assert_eq!(is_auto, vec![1]);
+    });
+}
+
+#[test]
+fn prop_edit_and_dissolve_series() {
+    proptest!(|(
+        images in prop::collection::vec(arb_new_image_asset(), 4..10),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let user_id = assert_ok!(repository::user::create_user(&mut conn, "alice", "hash", UserRole::Admin));
+        let assets = set_assets_root_dir(images.into_iter().map(|image| image.into()).collect(), root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let asset_ids: Vec<AssetId> = assets.iter().map(|asset| asset.base.id).collect();
+        let (series_ids, rest) = asset_ids.split_at(3);
+
+        let series_id = assert_ok!(repository::asset_series::create_auto_series(&mut conn, series_ids, series_ids[0]));
+        // already part of a series
+        assert_err!(repository::asset_series::add_assets_to_series(&mut conn, series_id, &series_ids[..1]));
+        assert_ok!(repository::asset_series::add_assets_to_series(&mut conn, series_id, rest));
+        assert_err!(repository::asset_series::set_series_selection(&mut conn, series_id, &[]));
+        assert_ok!(repository::asset_series::set_series_selection(&mut conn, series_id, &series_ids[1..3]));
+        let series = assert_ok!(repository::asset_series::get_series(&mut conn, series_id, user_id)).unwrap();
+        prop_assert!(series.is_auto);
+        prop_assert_eq!(series.assets.len(), asset_ids.len());
+        let mut selected: Vec<AssetId> = series.selection_indices.iter().map(|&idx| series.assets[idx].base.id).collect();
+        selected.sort();
+        prop_assert_eq!(selected, series_ids[1..3].to_vec());
+
+        // removing the whole selection selects another asset
+        assert_ok!(repository::asset_series::remove_assets_from_series(&mut conn, series_id, &series_ids[1..3]));
+        let series = assert_ok!(repository::asset_series::get_series(&mut conn, series_id, user_id)).unwrap();
+        prop_assert_eq!(series.assets.len(), asset_ids.len() - 2);
+        prop_assert_eq!(series.selection_indices.len(), 1);
+        let deleted = assert_ok!(repository::asset_series::get_deleted_auto_series(&mut conn, &asset_ids));
+        prop_assert_eq!(deleted, vec![(series_id, series_ids[1]), (series_id, series_ids[2])]);
+
+        assert_ok!(repository::asset_series::dissolve_series(&mut conn, series_id));
+        prop_assert!(assert_ok!(repository::asset_series::get_series(&mut conn, series_id, user_id)).is_none());
+        let deleted = assert_ok!(repository::asset_series::get_deleted_auto_series(&mut conn, &asset_ids));
+        prop_assert_eq!(deleted.len(), asset_ids.len());
+        let candidates = assert_ok!(repository::asset_series::get_series_detection_candidates(&mut conn));
+        prop_assert_eq!(candidates.len(), asset_ids.len());
+    });
+}
@@ -0,0 +1,60 @@
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use claims::{assert_ok, assert_some};
+use proptest::prelude::*;
+
+use crate::model::{
+    repository::{self, duplicate_asset::NewDuplicateAsset},
+    AssetRootDir, AssetRootDirId,
+};
+
+use super::proptest_arb::arb_new_asset;
+use super::util::{prop_insert_create_test_asset, set_assets_root_dir};
+
+#[test]
+fn prop_make_duplicate_primary_swaps_paths() {
+    proptest!(|(
+        asset in arb_new_asset(),
+        file_size in 1i64..1_000_000,
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let root_a = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/a"),
+        }));
+        let root_b = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/b"),
+        }));
+        let asset = set_assets_root_dir(vec![asset], root_a).remove(0);
+        let asset = prop_insert_create_test_asset(&mut conn, &asset)?;
+        let asset_id = asset.base.id;
+        assert_ok!(repository::asset::set_asset_file_stat(&mut conn, asset_id, super::utc_now_millis_zero(), file_size));
+        for (root_dir_id, path) in [(root_b, "copy.jpg"), (root_b, "other/copy.jpg"), (root_a, "copy.jpg")] {
+            assert_ok!(repository::duplicate_asset::insert_duplicate_asset(&mut conn, NewDuplicateAsset {
+                existing_asset_id: asset_id,
+                asset_root_dir_id: root_dir_id,
+                path_in_asset_root: Path::new(path),
+            }));
+        }
+
+        let report = assert_ok!(repository::duplicate_asset::get_duplicates_report(&mut conn, 10));
+        prop_assert_eq!(report.root_dirs.len(), 2);
+        prop_assert_eq!(report.root_dirs[0].root_dir_id, root_b);
+        prop_assert_eq!(report.root_dirs[0].duplicate_count, 2);
+        prop_assert_eq!(report.root_dirs[0].wasted_bytes, 2 * file_size);
+        prop_assert_eq!(report.root_dirs[1].wasted_bytes, file_size);
+        prop_assert_eq!(report.assets.len(), 1);
+        prop_assert_eq!(report.assets[0].wasted_bytes, Some(3 * file_size));
+
+        let dups = assert_ok!(repository::duplicate_asset::get_duplicates_of_asset(&mut conn, asset_id));
+        let new_primary = dups[1].clone();
+        assert_ok!(repository::duplicate_asset::make_duplicate_primary(&mut conn, &new_primary));
+        let path = assert_ok!(repository::asset::get_asset_path_on_disk(&mut conn, asset_id));
+        prop_assert_eq!(path.path_on_disk(), PathBuf::from("/path/to/b/other/copy.jpg"));
+        let dups = assert_ok!(repository::duplicate_asset::get_duplicates_of_asset(&mut conn, asset_id));
+        prop_assert_eq!(dups.len(), 3);
+        let old_primary = assert_some!(dups.iter().find(|dup| dup.file_path == asset.base.file_path));
+        prop_assert_eq!(old_primary.root_dir_id, root_a);
+        prop_assert!(dups.iter().all(|dup| dup.id != new_primary.id));
+    });
+}
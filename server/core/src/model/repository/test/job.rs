@@ -0,0 +1,53 @@
+use chrono::Duration;
+use claims::assert_ok;
+
+use crate::model::{repository, AssetRootDirId, CreateJob, JobId, JobKind, JobState, JobSubject};
+
+use super::*;
+
+#[test]
+fn delete_done_jobs_before_keeps_unfinished_and_recent_jobs() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let create_job = CreateJob {
+        kind: JobKind::IndexAssetRoot,
+        subject: JobSubject::AssetRootDir(AssetRootDirId(1)),
+    };
+    let mut job_with_state = |state: JobState| -> JobId {
+        let job_id = assert_ok!(repository::job::create_job(&mut conn, &create_job));
+        if state != JobState::Queued {
+            assert_ok!(repository::job::set_job_running(&mut conn, job_id));
+        }
+        if state.is_done() {
+            assert_ok!(repository::job::set_job_done(
+                &mut conn, job_id, state, None
+            ));
+        }
+        job_id
+    };
+    let queued = job_with_state(JobState::Queued);
+    let running = job_with_state(JobState::Running);
+    job_with_state(JobState::Finished);
+    job_with_state(JobState::Failed);
+    job_with_state(JobState::Cancelled);
+
+    // all done jobs finished just now
+    let an_hour_ago = utc_now_millis_zero() - Duration::hours(1);
+    let deleted = assert_ok!(repository::job::delete_done_jobs_before(
+        &mut conn,
+        an_hour_ago
+    ));
+    assert_eq!(deleted, 0);
+    let all_jobs = assert_ok!(repository::job::get_jobs(&mut conn, None, None, 100));
+    assert_eq!(all_jobs.len(), 5);
+
+    let in_an_hour = utc_now_millis_zero() + Duration::hours(1);
+    let deleted = assert_ok!(repository::job::delete_done_jobs_before(
+        &mut conn, in_an_hour
+    ));
+    assert_eq!(deleted, 3);
+    let remaining: Vec<JobId> = assert_ok!(repository::job::get_jobs(&mut conn, None, None, 100))
+        .into_iter()
+        .map(|job| job.id)
+        .collect();
+    assert_eq!(remaining, vec![running, queued]);
+}
@@ -4,13 +4,23 @@ use super::db;
 
 pub mod album;
 pub mod asset;
+pub mod asset_place;
 pub mod asset_root_dir;
+pub mod asset_series;
+pub mod duplicate_asset;
 pub mod image_representation;
+pub mod job;
+pub mod near_duplicate;
 pub mod proptest_arb;
 pub mod representation;
+pub mod search;
+pub mod search_index;
+pub mod share_link;
 pub mod timeline;
 pub mod timeline_group;
+pub mod user;
 pub mod util;
+pub mod visibility;
 
 pub fn utc_now_millis_zero() -> chrono::DateTime<chrono::Utc> {
     chrono::Utc::now().trunc_subsecs(3)
@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use camino::Utf8PathBuf as PathBuf;
+use claims::assert_ok;
+use proptest::prelude::*;
+
+use crate::{
+    model::{repository, AssetId, AssetRootDir, AssetRootDirId, UserRole},
+    processing::near_duplicates::{cluster_near_duplicates, MAX_HAMMING_DISTANCE},
+};
+
+use super::proptest_arb::arb_new_asset;
+use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
+
+#[test]
+fn prop_near_duplicate_groups() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 2..20),
+        hidden_idx in any::<prop::sample::Index>(),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let alice = assert_ok!(repository::user::create_user(&mut conn, "alice", "hash", UserRole::Admin));
+        let bob = assert_ok!(repository::user::create_user(&mut conn, "bob", "hash", UserRole::User));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        // even and odd assets are two groups of similar images
+        for (i, asset) in assets.iter().enumerate() {
+            let hash = if i % 2 == 0 { i as u64 } else { !(i as u64) };
+            assert_ok!(repository::asset::set_perceptual_hash(&mut conn, asset.base.id, hash));
+        }
+        let hashes = assert_ok!(repository::asset::get_perceptual_hashes(&mut conn));
+        prop_assert_eq!(hashes.len(), assets.len());
+        let groups = cluster_near_duplicates(&hashes, MAX_HAMMING_DISTANCE);
+        assert_ok!(repository::near_duplicate::replace_near_duplicate_groups(&mut conn, &groups));
+
+        let group_sets = |groups: &[crate::model::NearDuplicateGroup]| -> HashSet<Vec<AssetId>> {
+            groups
+                .iter()
+                .map(|group| group.assets.iter().map(|a| a.base.id).collect())
+                .collect()
+        };
+        let expected = |skip: Option<AssetId>| -> HashSet<Vec<AssetId>> {
+            (0..2)
+                .map(|parity| {
+                    assets
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, a)| i % 2 == parity && Some(a.base.id) != skip)
+                        .map(|(_, a)| a.base.id)
+                        .collect::<Vec<_>>()
+                })
+                .filter(|group| group.len() > 1)
+                .collect()
+        };
+        let bob_groups = assert_ok!(repository::near_duplicate::get_near_duplicate_groups(&mut conn, bob));
+        prop_assert_eq!(group_sets(&bob_groups), expected(None));
+        for group in &bob_groups {
+            let pixels = |id: AssetId| {
+                let asset = group.assets.iter().find(|a| a.base.id == id).unwrap();
+                asset.base.size.width as i64 * asset.base.size.height as i64
+            };
+            let max_pixels = group.assets.iter().map(|a| pixels(a.base.id)).max().unwrap();
+            prop_assert_eq!(pixels(group.suggested_best), max_pixels);
+            prop_assert_eq!(group.id, *group.assets.iter().map(|a| &a.base.id).min().unwrap());
+        }
+
+        let hidden_id = hidden_idx.get(&assets).base.id;
+        assert_ok!(repository::asset::set_assets_hidden(&mut conn, alice, true, &[hidden_id]));
+        let alice_groups = assert_ok!(repository::near_duplicate::get_near_duplicate_groups(&mut conn, alice));
+        prop_assert_eq!(group_sets(&alice_groups), expected(Some(hidden_id)));
+
+        assert_ok!(repository::near_duplicate::replace_near_duplicate_groups(&mut conn, &[]));
+        prop_assert!(assert_ok!(repository::near_duplicate::get_near_duplicate_groups(&mut conn, bob)).is_empty());
+    });
+}
@@ -59,7 +59,6 @@ prop_compose! {
     )
     (
         file_path in path_strategy().no_shrink(),
-        is_hidden in any::<bool>(),
         added_at in arb_datetime_utc(),
         taken_date in arb_datetime_utc(),
         timestamp_info in timestamp_info_strategy(),
@@ -82,7 +81,6 @@ prop_compose! {
             root_dir_id: AssetRootDirId(0),
             file_type: file_type.clone(),
             file_path,
-            is_hidden,
             added_at,
             taken_date,
             timestamp_info,
@@ -169,6 +167,8 @@ prop_compose! {
             created_at,
             changed_at,
             display_date,
+            owner_id: None,
+            is_private: false,
         }
     }
 }
@@ -188,6 +188,9 @@ prop_compose! {
             description,
             created_at,
             changed_at,
+            owner_id: None,
+            is_private: false,
+            smart_filter: None,
         }
     }
 }
@@ -0,0 +1,133 @@
+use camino::Utf8PathBuf as PathBuf;
+use claims::assert_ok;
+use itertools::Itertools;
+use proptest::prelude::*;
+
+use crate::model::{
+    repository::{
+        self,
+        db::DbConn,
+        search::{AssetSearch, SearchCursor},
+    },
+    Asset, AssetId, AssetRootDir, AssetRootDirId, AssetType, UserId, UserRole,
+};
+
+use super::proptest_arb::arb_new_asset;
+use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
+
+/// All pages of the search
+fn search_all(
+    conn: &mut DbConn,
+    user_id: UserId,
+    search: &AssetSearch,
+    page_size: i64,
+) -> Vec<AssetId> {
+    let mut found: Vec<AssetId> = Vec::new();
+    let mut after: Option<SearchCursor> = None;
+    loop {
+        let page = assert_ok!(repository::search::search_assets(
+            conn, user_id, search, after, page_size
+        ));
+        let is_last_page = (page.len() as i64) < page_size;
+        found.extend(page.iter().map(|asset| asset.base.id));
+        if is_last_page {
+            return found;
+        }
+        after = page.last().map(SearchCursor::from);
+    }
+}
+
+fn newest_first<'a>(assets: impl Iterator<Item = &'a Asset>) -> Vec<AssetId> {
+    assets
+        .sorted_by_key(|asset| std::cmp::Reverse((asset.base.taken_date, asset.base.id)))
+        .map(|asset| asset.base.id)
+        .collect()
+}
+
+#[test]
+fn prop_search_assets() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 0..15),
+        page_size in 1i64..5,
+        hidden_idxs in prop::collection::vec(any::<prop::sample::Index>(), 0..3),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let user_id = assert_ok!(repository::user::create_user(&mut conn, "user", "hash", UserRole::User));
+        let hidden: Vec<AssetId> = if assets.is_empty() {
+            Vec::new()
+        } else {
+            hidden_idxs.iter().map(|idx| idx.get(&assets).base.id).unique().collect()
+        };
+        assert_ok!(repository::asset::set_assets_hidden(&mut conn, user_id, true, &hidden));
+
+        let visible = assets.iter().filter(|asset| !hidden.contains(&asset.base.id));
+        prop_assert_eq!(
+            search_all(&mut conn, user_id, &AssetSearch::default(), page_size),
+            newest_first(visible.clone())
+        );
+        prop_assert_eq!(
+            search_all(&mut conn, user_id, &AssetSearch { hidden: true, ..Default::default() }, page_size),
+            newest_first(assets.iter().filter(|asset| hidden.contains(&asset.base.id)))
+        );
+        let videos = AssetSearch {
+            asset_type: Some(AssetType::Video),
+            ..Default::default()
+        };
+        prop_assert_eq!(
+            search_all(&mut conn, user_id, &videos, page_size),
+            newest_first(visible.clone().filter(|asset| asset.base.ty == AssetType::Video))
+        );
+        // the whole world, so all Assets with coordinates
+        let with_gps = AssetSearch {
+            gps_bounds: Some(repository::search::GpsBounds {
+                min_lat: (-90.0 * 10e8) as i64,
+                max_lat: (90.0 * 10e8) as i64,
+                min_lon: (-180.0 * 10e8) as i64,
+                max_lon: (180.0 * 10e8) as i64,
+            }),
+            ..Default::default()
+        };
+        prop_assert_eq!(
+            search_all(&mut conn, user_id, &with_gps, page_size),
+            newest_first(visible.filter(|asset| asset.base.gps_coordinates.is_some()))
+        );
+    })
+}
+
+#[test]
+fn prop_cursor_outlives_its_asset() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 1..15),
+        cursor_idx in any::<prop::sample::Index>(),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let user_id = assert_ok!(repository::user::create_user(&mut conn, "user", "hash", UserRole::User));
+        let ordered = newest_first(assets.iter());
+        let cursor_pos = cursor_idx.index(ordered.len());
+        let cursor_asset = assets.iter().find(|asset| asset.base.id == ordered[cursor_pos]).unwrap();
+        let after = SearchCursor::from(cursor_asset);
+
+        assert_ok!(repository::asset::delete_asset(&mut conn, cursor_asset.base.id));
+        let page: Vec<AssetId> = assert_ok!(repository::search::search_assets(
+            &mut conn, user_id, &AssetSearch::default(), Some(after), 100
+        ))
+        .iter()
+        .map(|asset| asset.base.id)
+        .collect();
+        prop_assert_eq!(page, ordered[cursor_pos + 1..].to_vec());
+    });
+}
@@ -0,0 +1,184 @@
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use claims::assert_ok;
+use proptest::prelude::*;
+
+use crate::model::{
+    repository::{
+        self,
+        album::{CreateAlbum, UpdateAlbum},
+        search_index::TextSearchHit,
+        timeline_group::{CreateTimelineGroup, UpdateTimelineGroup},
+    },
+    AssetRootDir, AssetRootDirId, UserRole,
+};
+
+use super::proptest_arb::arb_new_asset;
+use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
+
+#[test]
+fn prop_search_asset_paths() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 1..10),
+        idx in any::<prop::sample::Index>(),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let user_id = assert_ok!(repository::user::create_user(&mut conn, "user", "hash", UserRole::User));
+        let asset_id = idx.get(&assets).base.id;
+
+        assert_ok!(repository::asset::set_asset_path(
+            &mut conn,
+            asset_id,
+            root_dir_id,
+            Path::new("2019-07 Rome trip Oma/IMG_0001.jpg")
+        ));
+        let hits = assert_ok!(repository::search_index::search_text(&mut conn, user_id, "rome oma", 10));
+        prop_assert_eq!(hits, vec![TextSearchHit::Asset(asset_id)]);
+        // prefixes of the last word while typing
+        let hits = assert_ok!(repository::search_index::search_text(&mut conn, user_id, "trip Om", 10));
+        prop_assert_eq!(hits, vec![TextSearchHit::Asset(asset_id)]);
+
+        assert_ok!(repository::asset::set_assets_hidden(&mut conn, user_id, true, &[asset_id]));
+        prop_assert!(assert_ok!(repository::search_index::search_text(&mut conn, user_id, "rome", 10)).is_empty());
+        assert_ok!(repository::asset::set_assets_hidden(&mut conn, user_id, false, &[asset_id]));
+
+        assert_ok!(repository::asset::delete_asset(&mut conn, asset_id));
+        prop_assert!(assert_ok!(repository::search_index::search_text(&mut conn, user_id, "rome", 10)).is_empty());
+    })
+}
+
+#[test]
+fn search_albums_and_timeline_groups() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let alice = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "alice",
+        "hash",
+        UserRole::Admin
+    ));
+    let bob = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "bob",
+        "hash",
+        UserRole::User
+    ));
+    let search = |conn: &mut _, user_id, query: &str| {
+        assert_ok!(repository::search_index::search_text(
+            conn, user_id, query, 10
+        ))
+    };
+    let shared_album = assert_ok!(repository::album::create_album(
+        &mut conn,
+        CreateAlbum {
+            name: Some("Summer in Zürich".to_owned()),
+            description: Some("with the whole family".to_owned()),
+            owner_id: Some(alice),
+            is_private: false,
+            smart_filter: None,
+        },
+        &[]
+    ));
+    let private_album = assert_ok!(repository::album::create_album(
+        &mut conn,
+        CreateAlbum {
+            name: Some("Zurich, private".to_owned()),
+            description: None,
+            owner_id: Some(alice),
+            is_private: true,
+            smart_filter: None,
+        },
+        &[]
+    ));
+    let group_id = assert_ok!(repository::timeline_group::create_timeline_group(
+        &mut conn,
+        CreateTimelineGroup {
+            name: Some("Hiking".to_owned()),
+            display_date: super::utc_now_millis_zero(),
+            asset_ids: Vec::new(),
+            owner_id: Some(bob),
+            is_private: false,
+        },
+    ));
+
+    // diacritics are ignored and names rank above descriptions
+    assert_eq!(
+        search(&mut conn, alice, "zurich"),
+        vec![
+            TextSearchHit::Album(private_album),
+            TextSearchHit::Album(shared_album)
+        ]
+    );
+    assert_eq!(
+        search(&mut conn, bob, "zurich"),
+        vec![TextSearchHit::Album(shared_album)]
+    );
+    assert_eq!(
+        search(&mut conn, bob, "family"),
+        vec![TextSearchHit::Album(shared_album)]
+    );
+    // FTS5 syntax in the query is searched for literally
+    assert!(search(&mut conn, bob, "\"family OR NOT* (").is_empty());
+    assert!(search(&mut conn, bob, "  ").is_empty());
+
+    assert_ok!(repository::album::update_album(
+        &mut conn,
+        shared_album,
+        UpdateAlbum {
+            name: Some("Winter in Zürich".to_owned()),
+            description: Some(None),
+            smart_filter: None,
+        }
+    ));
+    assert!(search(&mut conn, bob, "family").is_empty());
+    assert_eq!(
+        search(&mut conn, bob, "winter"),
+        vec![TextSearchHit::Album(shared_album)]
+    );
+    assert_ok!(repository::album::append_items_to_album(
+        &mut conn,
+        shared_album,
+        &[repository::album::AddItemToAlbum::Text(
+            "Snowball fight".to_owned()
+        )]
+    ));
+    assert_eq!(
+        search(&mut conn, bob, "snowball"),
+        vec![TextSearchHit::Album(shared_album)]
+    );
+
+    assert_eq!(
+        search(&mut conn, alice, "hiking"),
+        vec![TextSearchHit::TimelineGroup(group_id)]
+    );
+    assert_ok!(repository::timeline_group::update_timeline_group(
+        &mut conn,
+        group_id,
+        UpdateTimelineGroup {
+            name: Some("Climbing".to_owned()),
+            display_date: None,
+        }
+    ));
+    assert!(search(&mut conn, alice, "hiking").is_empty());
+    assert_eq!(
+        search(&mut conn, alice, "climb"),
+        vec![TextSearchHit::TimelineGroup(group_id)]
+    );
+    assert_ok!(repository::timeline_group::delete_timeline_group(
+        &mut conn, group_id
+    ));
+    assert!(search(&mut conn, alice, "climbing").is_empty());
+
+    assert_ok!(repository::album::delete_album(&mut conn, shared_album));
+    assert_eq!(
+        search(&mut conn, alice, "zurich"),
+        vec![TextSearchHit::Album(private_album)]
+    );
+    assert_ok!(repository::user::delete_user(&mut conn, alice));
+    assert!(search(&mut conn, bob, "zurich").is_empty());
+}
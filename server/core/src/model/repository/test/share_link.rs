@@ -0,0 +1,113 @@
+use camino::Utf8PathBuf as PathBuf;
+use chrono::Duration;
+use claims::{assert_none, assert_ok, assert_some};
+use proptest::prelude::*;
+
+use crate::model::{
+    repository::{self, album::CreateAlbum},
+    AssetId, AssetRootDir, AssetRootDirId, CreateShareLink, ShareLinkTarget, UserRole,
+};
+
+use super::proptest_arb::arb_new_asset;
+use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
+use super::*;
+
+#[test]
+fn prop_share_link_only_shares_album_assets() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 1..20),
+        num_in_album in 0..20usize,
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let alice = assert_ok!(repository::user::create_user(&mut conn, "alice", "hash", UserRole::Admin));
+        let bob = assert_ok!(repository::user::create_user(&mut conn, "bob", "hash", UserRole::User));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let num_in_album = num_in_album.min(assets.len());
+        let album_asset_ids: Vec<AssetId> = assets[..num_in_album].iter().map(|a| a.base.id).collect();
+        let album_id = assert_ok!(repository::album::create_album(
+            &mut conn,
+            CreateAlbum {
+                name: Some("album".to_owned()),
+                description: None,
+                owner_id: Some(alice),
+                is_private: true,
+                smart_filter: None,
+            },
+            &album_asset_ids
+        ));
+        let target = ShareLinkTarget::Album(album_id);
+        let now = utc_now_millis_zero();
+        let link_id = assert_ok!(repository::share_link::create_share_link(&mut conn, &CreateShareLink {
+            target,
+            created_by: alice,
+            token_hash: vec![1, 2, 3],
+            expires_at: Some(now + Duration::days(1)),
+            password_hash: None,
+            allow_download: false,
+        }));
+
+        let link = assert_some!(assert_ok!(repository::share_link::find_share_link_for_token(&mut conn, &[1, 2, 3], now)));
+        prop_assert_eq!(link.id, link_id);
+        prop_assert_eq!(link.target, target);
+        let shared_ids: Vec<AssetId> = assert_ok!(repository::share_link::get_shared_assets(&mut conn, target))
+            .into_iter().map(|a| a.base.id).collect();
+        prop_assert_eq!(&shared_ids, &album_asset_ids);
+        for (idx, asset) in assets.iter().enumerate() {
+            let is_shared = assert_ok!(repository::share_link::is_asset_shared(&mut conn, target, asset.base.id));
+            prop_assert_eq!(is_shared, idx < num_in_album);
+        }
+
+        assert_none!(assert_ok!(repository::share_link::find_share_link_for_token(&mut conn, &[1, 2, 3], now + Duration::days(2))));
+        // only the creator can delete a link
+        prop_assert!(!assert_ok!(repository::share_link::delete_share_link(&mut conn, bob, link_id)));
+        prop_assert!(assert_ok!(repository::share_link::delete_share_link(&mut conn, alice, link_id)));
+        assert_none!(assert_ok!(repository::share_link::find_share_link_for_token(&mut conn, &[1, 2, 3], now)));
+    });
+}
+
+#[test]
+fn deleting_user_deletes_share_links() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let alice = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "alice",
+        "hash",
+        UserRole::Admin
+    ));
+    let album_id = assert_ok!(repository::album::create_album(
+        &mut conn,
+        CreateAlbum {
+            name: Some("private".to_owned()),
+            description: None,
+            owner_id: Some(alice),
+            is_private: true,
+            smart_filter: None,
+        },
+        &[]
+    ));
+    assert_ok!(repository::share_link::create_share_link(
+        &mut conn,
+        &CreateShareLink {
+            target: ShareLinkTarget::Album(album_id),
+            created_by: alice,
+            token_hash: vec![4, 5, 6],
+            expires_at: None,
+            password_hash: Some("hash".to_owned()),
+            allow_download: true,
+        }
+    ));
+    assert_ok!(repository::user::delete_user(&mut conn, alice));
+    assert_none!(assert_ok!(
+        repository::share_link::find_share_link_for_token(
+            &mut conn,
+            &[4, 5, 6],
+            utc_now_millis_zero()
+        )
+    ));
+}
@@ -7,11 +7,11 @@ use is_sorted::IsSorted;
 use proptest::prelude::*;
 
 use crate::model::{
-    repository, repository::timeline::TimelineElement,  Asset, AssetId,
-    AssetRootDir, AssetRootDirId, TimelineGroup,
-    repository::db::DbConn,
+    repository, repository::asset_place::PlaceFilter, repository::db::DbConn,
+    repository::timeline::TimelineElement, Asset, AssetId, AssetRootDir, AssetRootDirId,
+    TimelineGroup, UserId,
 };
-use proptest_arb::{arb_new_timeline_group, arb_new_asset};
+use proptest_arb::{arb_new_asset, arb_new_timeline_group};
 
 use super::{util::*, *};
 
@@ -21,7 +21,6 @@ struct GroupWithAssets {
     pub assets: Vec<Asset>,
 }
 
-
 #[test]
 fn prop_test_timeline() {
     // generate assets
@@ -92,7 +91,7 @@ fn prop_test_timeline() {
             // prop_assert!(chunk_idx < expected_num_chunks);
             dbgstr.push_str("CHUNK\n");
             let chunk = {
-                let c = repository::timeline::get_timeline_chunk(&mut conn, last_id, timeline_chunk_size as i64);
+                let c = repository::timeline::get_timeline_chunk(&mut conn, UserId(1), last_id, timeline_chunk_size as i64, &PlaceFilter::default());
                 prop_assert!(c.is_ok(), "get_timeline_chunk returned error: \n{:?}", c.unwrap_err());
                 c.unwrap()
             };
@@ -122,9 +121,9 @@ fn prop_test_timeline() {
             chunks.push(chunk);
         }
         let next_chunk = {
-            let c = repository::timeline::get_timeline_chunk(&mut conn, last_id, timeline_chunk_size as i64);
+            let c = repository::timeline::get_timeline_chunk(&mut conn, UserId(1), last_id, timeline_chunk_size as i64, &PlaceFilter::default());
             prop_assert!(c.is_ok(), "get_timeline_chunk error:\n{}", c.unwrap_err());
-            c.unwrap() 
+            c.unwrap()
         };
         prop_assert!(next_chunk.is_empty());
 
@@ -166,9 +165,9 @@ fn prop_test_timeline() {
                     }
                 }
                 let last_el = match last_timeline_element {
-                    None => { 
-                        last_timeline_element = Some(tlel); 
-                        continue; 
+                    None => {
+                        last_timeline_element = Some(tlel);
+                        continue;
                     }
                     Some(last_el) => last_el
                 };
@@ -224,8 +223,7 @@ fn prop_insert_timeline_groups_insert_add_assets(
             conn,
             &group.group.clone(),
             assets_with_id.iter().map(|asset| asset.base.id),
-        )
-        ?;
+        )?;
         groups_with_ids.push(GroupWithAssets {
             assets: assets_with_id,
             group: group_with_id,
@@ -3,8 +3,9 @@ use chrono::Months;
 use claims::{assert_err, assert_ok};
 
 use crate::model::{
-    repository::{self, timeline_group::CreateTimelineGroup}, AssetId, AssetRootDir, AssetRootDirId, CreateAsset, CreateAssetBase,
-    CreateAssetImage, CreateAssetSpe, Size, TimestampInfo,
+    repository::{self, timeline_group::CreateTimelineGroup},
+    AssetId, AssetRootDir, AssetRootDirId, CreateAsset, CreateAssetBase, CreateAssetImage,
+    CreateAssetSpe, Size, TimestampInfo,
 };
 
 use super::*;
@@ -68,6 +69,8 @@ fn adding_asset_to_multiple_group_albums_fails() {
         name: Some("group1".into()),
         display_date: utc_now_millis_zero(),
         asset_ids: Vec::new(),
+        owner_id: None,
+        is_private: false,
     };
     let group2 = CreateTimelineGroup {
         name: Some("group2".into()),
@@ -75,6 +78,8 @@ fn adding_asset_to_multiple_group_albums_fails() {
             .checked_sub_months(Months::new(2))
             .unwrap(),
         asset_ids: Vec::new(),
+        owner_id: None,
+        is_private: false,
     };
     let group_id = assert_ok!(repository::timeline_group::create_timeline_group(
         &mut conn, group
@@ -112,3 +117,89 @@ fn adding_asset_to_multiple_group_albums_fails() {
         &[asset2_id]
     ));
 }
+
+#[test]
+fn prop_edit_and_delete_timeline_group() {
+    use proptest::prelude::*;
+
+    use super::proptest_arb::arb_new_asset;
+    use super::util::{prop_insert_create_test_assets, set_assets_root_dir};
+
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 2..8),
+        display_date in super::proptest_arb::arb_datetime_utc(),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let mut assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        // newest first
+        assets.sort_by_key(|asset| std::cmp::Reverse(asset.base.taken_date));
+        let asset_ids: Vec<AssetId> = assets.iter().map(|asset| asset.base.id).collect();
+        let group_id = assert_ok!(repository::timeline_group::create_timeline_group(
+            &mut conn,
+            CreateTimelineGroup {
+                name: Some("group".into()),
+                display_date: assets[0].base.taken_date,
+                asset_ids: asset_ids.clone(),
+                owner_id: None,
+                is_private: false,
+            }
+        ));
+        let created = assert_ok!(repository::timeline_group::get_timeline_group(&mut conn, group_id));
+
+        assert_ok!(repository::timeline_group::update_timeline_group(
+            &mut conn,
+            group_id,
+            repository::timeline_group::UpdateTimelineGroup {
+                name: Some("renamed".into()),
+                display_date: Some(display_date),
+            }
+        ));
+        let updated = assert_ok!(repository::timeline_group::get_timeline_group(&mut conn, group_id));
+        prop_assert_eq!(updated.name.as_deref(), Some("renamed"));
+        prop_assert_eq!(updated.display_date, display_date);
+        prop_assert!(updated.changed_at >= created.changed_at);
+
+        // display_date follows the newest remaining asset
+        let deleted = assert_ok!(repository::timeline_group::remove_assets_from_group(&mut conn, group_id, &asset_ids[..1]));
+        prop_assert!(!deleted);
+        let updated = assert_ok!(repository::timeline_group::get_timeline_group(&mut conn, group_id));
+        prop_assert_eq!(updated.display_date, assets[1].base.taken_date);
+        let in_group = assert_ok!(repository::timeline_group::get_assets_in_group(&mut conn, group_id));
+        prop_assert_eq!(in_group.len(), asset_ids.len() - 1);
+
+        // removing the last assets deletes the group
+        let deleted = assert_ok!(repository::timeline_group::remove_assets_from_group(&mut conn, group_id, &asset_ids));
+        prop_assert!(deleted);
+        let _ = assert_err!(repository::timeline_group::get_timeline_group(&mut conn, group_id));
+
+        let group_id = assert_ok!(repository::timeline_group::create_timeline_group(
+            &mut conn,
+            CreateTimelineGroup {
+                name: Some("group".into()),
+                display_date: assets[0].base.taken_date,
+                asset_ids: asset_ids.clone(),
+                owner_id: None,
+                is_private: false,
+            }
+        ));
+        assert_ok!(repository::timeline_group::delete_timeline_group(&mut conn, group_id));
+        let _ = assert_err!(repository::timeline_group::get_timeline_group(&mut conn, group_id));
+        // the assets can be grouped again
+        assert_ok!(repository::timeline_group::create_timeline_group(
+            &mut conn,
+            CreateTimelineGroup {
+                name: None,
+                display_date: assets[0].base.taken_date,
+                asset_ids: asset_ids.clone(),
+                owner_id: None,
+                is_private: false,
+            }
+        ));
+    });
+}
@@ -0,0 +1,125 @@
+use chrono::Duration;
+use claims::{assert_none, assert_ok, assert_some};
+use diesel::connection::SimpleConnection;
+use pretty_assertions::assert_eq;
+
+use crate::model::{repository, AssetId, CreateUserToken, UserRole, UserTokenKind};
+
+use super::*;
+
+#[test]
+fn token_lookup_respects_expiry() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let user_id = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "alice",
+        "hash",
+        UserRole::Admin
+    ));
+    let now = utc_now_millis_zero();
+    let session = CreateUserToken {
+        user_id,
+        kind: UserTokenKind::Session,
+        name: None,
+        token_hash: vec![1, 2, 3],
+        expires_at: Some(now + Duration::days(1)),
+    };
+    let api = CreateUserToken {
+        user_id,
+        kind: UserTokenKind::Api,
+        name: Some("script".to_owned()),
+        token_hash: vec![4, 5, 6],
+        expires_at: None,
+    };
+    assert_ok!(repository::user::create_user_token(&mut conn, &session));
+    let api_token_id = assert_ok!(repository::user::create_user_token(&mut conn, &api));
+
+    let (user, token) = assert_some!(assert_ok!(repository::user::find_user_for_token(
+        &mut conn,
+        &[1, 2, 3],
+        now
+    )));
+    assert_eq!(user.id, user_id);
+    assert_eq!(user.name, "alice");
+    assert_eq!(token.kind, UserTokenKind::Session);
+
+    // the session has expired, the API token never does
+    let later = now + Duration::days(2);
+    assert_none!(assert_ok!(repository::user::find_user_for_token(
+        &mut conn,
+        &[1, 2, 3],
+        later
+    )));
+    let (_, token) = assert_some!(assert_ok!(repository::user::find_user_for_token(
+        &mut conn,
+        &[4, 5, 6],
+        later
+    )));
+    assert_eq!(token.id, api_token_id);
+
+    assert_eq!(
+        assert_ok!(repository::user::delete_expired_user_tokens(
+            &mut conn, later
+        )),
+        1
+    );
+    let api_tokens = assert_ok!(repository::user::get_user_tokens(
+        &mut conn,
+        user_id,
+        UserTokenKind::Api
+    ));
+    assert_eq!(api_tokens.len(), 1);
+}
+
+#[test]
+fn deleting_user_deletes_tokens() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let user_id = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "bob",
+        "hash",
+        UserRole::User
+    ));
+    let token = CreateUserToken {
+        user_id,
+        kind: UserTokenKind::Api,
+        name: Some("script".to_owned()),
+        token_hash: vec![7, 8, 9],
+        expires_at: None,
+    };
+    assert_ok!(repository::user::create_user_token(&mut conn, &token));
+    assert_ok!(repository::user::delete_user(&mut conn, user_id));
+    assert_none!(assert_ok!(repository::user::find_user(&mut conn, user_id)));
+    assert_none!(assert_ok!(repository::user::find_user_for_token(
+        &mut conn,
+        &[7, 8, 9],
+        utc_now_millis_zero()
+    )));
+}
+
+#[test]
+fn assets_hidden_before_there_were_users_stay_hidden() {
+    let mut conn = super::db::open_in_memory_and_migrate_until("2026-10-17-140000_user_visibility");
+    assert_ok!(conn.batch_execute(
+        r#"
+INSERT INTO AssetRootDir (asset_root_dir_id, path) VALUES (1, '/photos');
+INSERT INTO Asset (asset_id, ty, root_dir_id, file_path, file_type, is_hidden, added_at, taken_date,
+  timezone_info, width, height, exiftool_output, motion_photo, image_format_name)
+VALUES
+  (1, 1, 1, 'hidden.jpg', 'jpeg', 1, 0, 0, 2, 1024, 768, X'7B7D', 0, 'jpeg'),
+  (2, 1, 1, 'visible.jpg', 'jpeg', 0, 0, 0, 2, 1024, 768, X'7B7D', 0, 'jpeg');
+    "#
+    ));
+    assert_ok!(super::db::migrate(&mut conn));
+
+    // hidden for the first user and any created after
+    for (name, role) in [("alice", UserRole::Admin), ("bob", UserRole::User)] {
+        let user_id = assert_ok!(repository::user::create_user(&mut conn, name, "hash", role));
+        let hidden: Vec<AssetId> =
+            assert_ok!(repository::asset::get_hidden_assets(&mut conn, user_id))
+                .into_iter()
+                .map(|asset| asset.base.id)
+                .collect();
+        assert_eq!(hidden, vec![AssetId(1)], "hidden assets of {}", name);
+    }
+}
@@ -2,10 +2,38 @@ use proptest::prelude::*;
 
 use crate::model::{
     repository::{self, db::DbConn, timeline_group::CreateTimelineGroup},
-    Asset, AssetBase, AssetId, AssetRootDirId, AssetSpe, TimelineGroup, TimelineGroupId,
+    Asset, AssetBase, AssetId, AssetRootDirId, AssetSpe, CreateAsset, CreateAssetBase,
+    CreateAssetImage, CreateAssetSpe, Size, TimelineGroup, TimelineGroupId, TimestampInfo,
     VideoAsset,
 };
 
+/// Image asset with the given path and hash and made up metadata,
+/// for tests that are only about files and not their contents
+pub fn create_image_asset(root_dir_id: AssetRootDirId, file_path: &str, hash: u64) -> CreateAsset {
+    CreateAsset {
+        base: CreateAssetBase {
+            root_dir_id,
+            file_type: "jpeg".to_owned(),
+            file_path: file_path.into(),
+            taken_date: super::utc_now_millis_zero(),
+            timestamp_info: TimestampInfo::UtcCertain,
+            size: Size {
+                width: 1024,
+                height: 768,
+            },
+            rotation_correction: None,
+            hash: Some(hash),
+            exiftool_output: b"{}".to_vec(),
+            gps_coordinates: None,
+            file_mtime: None,
+            file_size: None,
+        },
+        spe: CreateAssetSpe::Image(CreateAssetImage {
+            image_format_name: "jpeg".to_owned(),
+        }),
+    }
+}
+
 /// Inserts asset and returns them in the same order, with asset_id set
 /// For VideoAssets, it uses an empty string as ffprobe_output
 pub fn prop_insert_create_test_assets(
@@ -76,6 +104,8 @@ pub fn prop_insert_timeline_group_add_assets(
         name: group.name.clone(),
         display_date: group.display_date,
         asset_ids: Vec::new(),
+        owner_id: group.owner_id,
+        is_private: group.is_private,
     };
     // initial creation with assets to insert right away not tested here
     let group_insert_result = repository::timeline_group::create_timeline_group(conn, create_group);
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use camino::Utf8PathBuf as PathBuf;
+use claims::assert_ok;
+use proptest::prelude::*;
+
+use crate::model::{
+    repository::{self, album::CreateAlbum, asset_place::PlaceFilter, db::DbConn},
+    AssetId, AssetRootDir, AssetRootDirId, UserId, UserRole,
+};
+
+use super::proptest_arb::arb_new_asset;
+use super::util::{create_image_asset, prop_insert_create_test_assets, set_assets_root_dir};
+
+#[test]
+fn prop_hidden_assets_are_per_user() {
+    proptest!(|(
+        assets in prop::collection::vec(arb_new_asset(), 1..30),
+        hidden_idxs in prop::collection::vec(any::<prop::sample::Index>(), 0..10),
+    )| {
+        let mut conn = super::db::open_in_memory_and_migrate();
+        let asset_root_dir = AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        };
+        let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(&mut conn, &asset_root_dir));
+        let alice = assert_ok!(repository::user::create_user(&mut conn, "alice", "hash", UserRole::Admin));
+        let bob = assert_ok!(repository::user::create_user(&mut conn, "bob", "hash", UserRole::User));
+        let assets = set_assets_root_dir(assets, root_dir_id);
+        let assets = prop_insert_create_test_assets(&mut conn, &assets)?;
+        let all_ids: HashSet<AssetId> = assets.iter().map(|a| a.base.id).collect();
+        let hidden_ids: Vec<AssetId> = hidden_idxs.iter().map(|idx| idx.get(&assets).base.id).collect();
+        let hidden_set: HashSet<AssetId> = hidden_ids.iter().copied().collect();
+        assert_ok!(repository::asset::set_assets_hidden(&mut conn, alice, true, &hidden_ids));
+
+        let alice_hidden: HashSet<AssetId> = assert_ok!(repository::asset::get_hidden_assets(&mut conn, alice))
+            .into_iter().map(|a| a.base.id).collect();
+        prop_assert_eq!(&alice_hidden, &hidden_set);
+        prop_assert!(assert_ok!(repository::asset::get_hidden_assets(&mut conn, bob)).is_empty());
+
+        let timeline_ids = |conn: &mut DbConn, user_id: UserId| -> HashSet<AssetId> {
+            assert_ok!(repository::timeline::get_timeline_chunk(conn, user_id, None, 1000, &PlaceFilter::default()))
+                .iter()
+                .flat_map(|el| el.get_assets().iter().map(|a| a.base.id))
+                .collect()
+        };
+        let expected_alice: HashSet<AssetId> = all_ids.difference(&hidden_set).copied().collect();
+        prop_assert_eq!(timeline_ids(&mut conn, alice), expected_alice);
+        prop_assert_eq!(timeline_ids(&mut conn, bob), all_ids.clone());
+
+        assert_ok!(repository::asset::set_assets_hidden(&mut conn, alice, false, &hidden_ids));
+        prop_assert!(assert_ok!(repository::asset::get_hidden_assets(&mut conn, alice)).is_empty());
+        prop_assert_eq!(timeline_ids(&mut conn, alice), all_ids);
+    });
+}
+
+#[test]
+fn private_albums_only_visible_to_owner() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let alice = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "alice",
+        "hash",
+        UserRole::Admin
+    ));
+    let bob = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "bob",
+        "hash",
+        UserRole::User
+    ));
+    let private_album = assert_ok!(repository::album::create_album(
+        &mut conn,
+        CreateAlbum {
+            name: Some("private".to_owned()),
+            description: None,
+            owner_id: Some(alice),
+            is_private: true,
+            smart_filter: None,
+        },
+        &[]
+    ));
+    let shared_album = assert_ok!(repository::album::create_album(
+        &mut conn,
+        CreateAlbum {
+            name: Some("shared".to_owned()),
+            description: None,
+            owner_id: Some(bob),
+            is_private: false,
+            smart_filter: None,
+        },
+        &[]
+    ));
+
+    let album_ids = |conn: &mut DbConn, user_id: UserId| -> HashSet<_> {
+        assert_ok!(repository::album::get_all_albums_with_asset_count(
+            conn, user_id
+        ))
+        .into_iter()
+        .map(|(album, _)| album.id)
+        .collect()
+    };
+    assert_eq!(
+        album_ids(&mut conn, alice),
+        HashSet::from([private_album, shared_album])
+    );
+    assert_eq!(album_ids(&mut conn, bob), HashSet::from([shared_album]));
+    assert!(assert_ok!(repository::album::is_album_visible(
+        &mut conn,
+        private_album,
+        alice
+    )));
+    assert!(!assert_ok!(repository::album::is_album_visible(
+        &mut conn,
+        private_album,
+        bob
+    )));
+
+    // private albums go away with their owner, shared ones are kept without owner
+    assert_ok!(repository::user::delete_user(&mut conn, alice));
+    assert!(!assert_ok!(repository::album::is_album_visible(
+        &mut conn,
+        private_album,
+        bob
+    )));
+    assert_ok!(repository::user::delete_user(&mut conn, bob));
+    let album = assert_ok!(repository::album::get_album(&mut conn, shared_album));
+    assert_eq!(album.owner_id, None);
+}
+
+#[test]
+fn hidden_assets_and_assets_in_private_albums_are_not_visible() {
+    let mut conn = super::db::open_in_memory_and_migrate();
+    let root_dir_id = assert_ok!(repository::asset_root_dir::insert_asset_root(
+        &mut conn,
+        &AssetRootDir {
+            id: AssetRootDirId(0),
+            path: PathBuf::from("/path/to/assets"),
+        }
+    ));
+    let alice = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "alice",
+        "hash",
+        UserRole::Admin
+    ));
+    let bob = assert_ok!(repository::user::create_user(
+        &mut conn,
+        "bob",
+        "hash",
+        UserRole::User
+    ));
+    let [plain, hidden, in_private_album] = ["a.jpg", "b.jpg", "c.jpg"]
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| {
+            assert_ok!(repository::asset::create_asset(
+                &mut conn,
+                create_image_asset(root_dir_id, path, i as u64)
+            ))
+        })
+        .collect::<Vec<_>>()
+        .try_into()
+        .unwrap();
+    assert_ok!(repository::asset::set_assets_hidden(
+        &mut conn,
+        bob,
+        true,
+        &[hidden]
+    ));
+    assert_ok!(repository::album::create_album(
+        &mut conn,
+        CreateAlbum {
+            name: Some("private".to_owned()),
+            description: None,
+            owner_id: Some(alice),
+            is_private: true,
+            smart_filter: None,
+        },
+        &[in_private_album]
+    ));
+
+    let visible = |conn: &mut DbConn, asset_id: AssetId, user_id: UserId| -> bool {
+        assert_ok!(repository::asset::is_asset_visible(conn, asset_id, user_id))
+    };
+    assert!(visible(&mut conn, plain, alice));
+    assert!(visible(&mut conn, plain, bob));
+    assert!(visible(&mut conn, hidden, alice));
+    assert!(!visible(&mut conn, hidden, bob));
+    assert!(visible(&mut conn, in_private_album, alice));
+    assert!(!visible(&mut conn, in_private_album, bob));
+    assert!(!visible(&mut conn, AssetId(1234), alice));
+}
@@ -14,9 +14,12 @@ use tracing::instrument;
 
 use crate::model::{
     util::datetime_from_db_repr, Asset, AssetId, AssetSeriesId, TimelineGroup, TimelineGroupId,
+    UserId,
 };
 
-use super::{db::DbConn, db_entity::DbAsset, timeline_group::get_timeline_group};
+use super::{
+    asset_place::PlaceFilter, db::DbConn, db_entity::DbAsset, timeline_group::get_timeline_group,
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum TimelineElement {
@@ -55,8 +58,10 @@ struct RowAssetGroupId {
 #[tracing::instrument(skip(conn))]
 pub fn get_timeline_chunk(
     conn: &mut DbConn,
+    user_id: UserId,
     last_id: Option<AssetId>,
     max_count: i64,
+    place: &PlaceFilter,
 ) -> Result<Vec<TimelineElement>> {
     // timezone to calculate local dates in
     let timezone = &chrono::Local;
@@ -70,7 +75,8 @@ pub fn get_timeline_chunk(
         FROM Asset
         LEFT JOIN TimelineGroupItem ON TimelineGroupItem.asset_id = Asset.asset_id
         LEFT JOIN TimelineGroup ON TimelineGroupItem.group_id = TimelineGroup.timeline_group_id
-        WHERE Asset.asset_id = $1
+        AND (TimelineGroup.is_private = 0 OR TimelineGroup.owner_id = ?3)
+        WHERE Asset.asset_id = ?1
     )
     SELECT
     "#);
@@ -81,10 +87,29 @@ pub fn get_timeline_chunk(
     CASE WHEN TimelineGroup.timeline_group_id IS NOT NULL THEN TimelineGroup.display_date ELSE Asset.taken_date END AS sort_group_date
     FROM Asset
     LEFT JOIN TimelineGroupItem ON TimelineGroupItem.asset_id = Asset.asset_id
+    -- assets in other users' private groups are shown ungrouped
     LEFT JOIN TimelineGroup ON TimelineGroupItem.group_id = TimelineGroup.timeline_group_id
+    AND (TimelineGroup.is_private = 0 OR TimelineGroup.owner_id = ?3)
     WHERE
+    Asset.missing_since IS NULL
+    AND NOT EXISTS (
+        SELECT 1 FROM UserAsset
+        WHERE UserAsset.asset_id = Asset.asset_id AND UserAsset.user_id = ?3
+        AND (UserAsset.is_hidden = 1 OR UserAsset.is_archived = 1)
+    )
+    AND (
+        (?4 IS NULL AND ?5 IS NULL AND ?6 IS NULL)
+        OR EXISTS (
+            SELECT 1 FROM AssetPlace
+            WHERE AssetPlace.asset_id = Asset.asset_id
+            AND (?4 IS NULL OR AssetPlace.country_code = ?4)
+            AND (?5 IS NULL OR AssetPlace.admin_region = ?5)
+            AND (?6 IS NULL OR AssetPlace.city = ?6)
+        )
+    )
+    AND
     (
-        ($1 IS NULL)
+        (?1 IS NULL)
         OR
         (sort_group_date, group_id, Asset.taken_date, Asset.asset_id) < (SELECT sort_group_date, album_id, taken_date, asset_id FROM last_asset)
         OR
@@ -95,12 +120,16 @@ pub fn get_timeline_chunk(
         )
     )
     ORDER BY sort_group_date DESC, group_id DESC, Asset.taken_date DESC, Asset.asset_id DESC
-    LIMIT $2;
+    LIMIT ?2;
     "#);
-    use diesel::sql_types::{BigInt, Nullable};
+    use diesel::sql_types::{BigInt, Nullable, Text};
     let assets_groupid: Vec<RowAssetGroupId> = sql_query(qb.finish())
         .bind::<Nullable<BigInt>, _>(last_id.map(|id| id.0))
         .bind::<BigInt, _>(max_count)
+        .bind::<BigInt, _>(user_id.0)
+        .bind::<Nullable<Text>, _>(place.country_code.as_deref())
+        .bind::<Nullable<Text>, _>(place.admin_region.as_deref())
+        .bind::<Nullable<Text>, _>(place.city.as_deref())
         .load(conn)?;
     let mut timeline_els: Vec<TimelineElement> = Vec::default();
     for row in assets_groupid {
@@ -193,7 +222,11 @@ struct RowTimelineSection {
 }
 
 #[tracing::instrument(skip(conn))]
-pub fn get_sections(conn: &mut DbConn) -> Result<Vec<TimelineSection>> {
+pub fn get_sections(
+    conn: &mut DbConn,
+    user_id: UserId,
+    place: &PlaceFilter,
+) -> Result<Vec<TimelineSection>> {
     const SQL_SEGMENT_IDX: &str = include_str!("timeline_segment_idx.sql");
     const QUERY: &str = formatcp!(
         r#"
@@ -234,7 +267,13 @@ pub fn get_sections(conn: &mut DbConn) -> Result<Vec<TimelineSection>> {
     FROM section_segments;
     "#
     );
-    let rows: Vec<RowTimelineSection> = sql_query(QUERY).load(conn)?;
+    use diesel::sql_types::{BigInt, Nullable, Text};
+    let rows: Vec<RowTimelineSection> = sql_query(QUERY)
+        .bind::<BigInt, _>(user_id.0)
+        .bind::<Nullable<Text>, _>(place.country_code.as_deref())
+        .bind::<Nullable<Text>, _>(place.admin_region.as_deref())
+        .bind::<Nullable<Text>, _>(place.city.as_deref())
+        .load(conn)?;
     let sections = rows
         .into_iter()
         .map(|row| {
@@ -317,8 +356,10 @@ struct RowTimelineSegmentInSection {
 #[instrument(err, skip(conn))]
 pub fn get_segments_in_section(
     conn: &mut DbConn,
+    user_id: UserId,
     segment_min: i64,
     segment_max: i64,
+    place: &PlaceFilter,
 ) -> Result<Vec<TimelineSegment>> {
     const SQL_SEGMENT_IDX: &str = include_str!("timeline_segment_idx.sql");
     let mut qb = SqliteQueryBuilder::new();
@@ -343,14 +384,19 @@ pub fn get_segments_in_section(
     FROM
     tl_segment_idx INNER JOIN Asset ON Asset.asset_id = tl_segment_idx.asset_id
     WHERE
-    ? <= segment_idx AND segment_idx <= ?
+    ?5 <= segment_idx AND segment_idx <= ?6
     ORDER BY tl_segment_idx.sort_date DESC, tl_segment_idx.series_date DESC, tl_segment_idx.taken_date DESC,
     tl_segment_idx.series_id, tl_segment_idx.group_id DESC, tl_segment_idx.asset_id;
     "#,
     );
+    use diesel::sql_types::{BigInt, Nullable, Text};
     let query = sql_query(qb.finish())
-        .bind::<diesel::sql_types::BigInt, _>(segment_min)
-        .bind::<diesel::sql_types::BigInt, _>(segment_max);
+        .bind::<BigInt, _>(user_id.0)
+        .bind::<Nullable<Text>, _>(place.country_code.as_deref())
+        .bind::<Nullable<Text>, _>(place.admin_region.as_deref())
+        .bind::<Nullable<Text>, _>(place.city.as_deref())
+        .bind::<BigInt, _>(segment_min)
+        .bind::<BigInt, _>(segment_max);
     let rows: Vec<RowTimelineSegmentInSection> = query
         .load(conn)
         .wrap_err("error querying timeline segments in section")?;
@@ -1,16 +1,16 @@
 use chrono::{DateTime, Utc};
 use diesel::prelude::*;
-use eyre::Result;
+use eyre::{Context, Result};
 use tracing::instrument;
 
 use crate::model::{
     repository::db_entity::{DbAsset, DbTimelineGroup},
     timeline_group::TimelineGroup,
-    util::{datetime_from_db_repr, datetime_to_db_repr},
-    Asset, AssetId, TimelineGroupId,
+    util::{bool_to_int, datetime_from_db_repr, datetime_to_db_repr},
+    Asset, AssetId, TimelineGroupId, UserId,
 };
 
-use super::{db::DbConn, schema};
+use super::{db::DbConn, schema, search_index};
 
 #[instrument(skip(conn))]
 pub fn get_timeline_group(conn: &mut DbConn, id: TimelineGroupId) -> Result<TimelineGroup> {
@@ -20,6 +20,27 @@ pub fn get_timeline_group(conn: &mut DbConn, id: TimelineGroupId) -> Result<Time
     db_timeline_group.try_into()
 }
 
+/// Private groups are only visible to their owner, the others to everyone
+#[instrument(skip(conn))]
+pub fn is_timeline_group_visible(
+    conn: &mut DbConn,
+    id: TimelineGroupId,
+    user_id: UserId,
+) -> Result<bool> {
+    use schema::TimelineGroup;
+    let count: i64 = TimelineGroup::table
+        .filter(TimelineGroup::timeline_group_id.eq(id.0))
+        .filter(
+            TimelineGroup::is_private
+                .eq(0)
+                .or(TimelineGroup::owner_id.eq(user_id.0)),
+        )
+        .count()
+        .get_result(conn)
+        .wrap_err("error querying table TimelineGroup")?;
+    Ok(count > 0)
+}
+
 #[instrument(skip(conn))]
 pub fn get_timeline_group_album_for_asset(
     conn: &mut DbConn,
@@ -44,6 +65,8 @@ pub struct CreateTimelineGroup {
     pub name: Option<String>,
     pub display_date: DateTime<Utc>,
     pub asset_ids: Vec<AssetId>,
+    pub owner_id: Option<UserId>,
+    pub is_private: bool,
 }
 
 #[instrument(skip(conn))]
@@ -60,6 +83,8 @@ pub fn create_timeline_group(
                 TimelineGroup::display_date.eq(datetime_to_db_repr(&ctg.display_date)),
                 TimelineGroup::created_at.eq(datetime_to_db_repr(&now)),
                 TimelineGroup::changed_at.eq(datetime_to_db_repr(&now)),
+                TimelineGroup::owner_id.eq(ctg.owner_id.map(|id| id.0)),
+                TimelineGroup::is_private.eq(bool_to_int(ctg.is_private)),
             ))
             .returning(TimelineGroup::timeline_group_id)
             .get_result(conn)?;
@@ -72,6 +97,7 @@ pub fn create_timeline_group(
                 ))
                 .execute(conn)?;
         }
+        search_index::index_timeline_group(conn, TimelineGroupId(group_id))?;
         Ok(TimelineGroupId(group_id))
     })
 }
@@ -109,26 +135,127 @@ pub fn add_assets_to_group(
                 ))
                 .execute(conn)?;
         }
-        // Update TimelineGroup.display_date to most recent asset in case it changed
-        // no query builder sorry idk how subqueries work
-        diesel::sql_query(
-            r#"
-        UPDATE TimelineGroup
-        SET display_date = (
-            SELECT MAX(Asset.taken_date)
-            FROM Asset INNER JOIN TimelineGroupItem ON TimelineGroupItem.asset_id = Asset.asset_id
-            WHERE TimelineGroupItem.group_id = ?
-        )
-        WHERE TimelineGroup.timeline_group_id = ?;
-        "#,
-        )
-        .bind::<diesel::sql_types::BigInt, _>(group_id.0)
-        .bind::<diesel::sql_types::BigInt, _>(group_id.0)
-        .execute(conn)?;
+        update_display_date_to_newest_asset(conn, group_id)?;
+        set_changed_at_now(conn, group_id)?;
         Ok(())
     })
 }
 
+/// Assets not in the group are ignored. If no assets remain the group is deleted,
+/// otherwise display_date is set to the newest remaining asset.
+/// Returns true if the group was deleted.
+#[instrument(skip(conn))]
+pub fn remove_assets_from_group(
+    conn: &mut DbConn,
+    group_id: TimelineGroupId,
+    asset_ids: &[AssetId],
+) -> Result<bool> {
+    use schema::TimelineGroupItem;
+    conn.immediate_transaction(|conn| {
+        diesel::delete(
+            TimelineGroupItem::table
+                .filter(TimelineGroupItem::group_id.eq(group_id.0))
+                .filter(TimelineGroupItem::asset_id.eq_any(asset_ids.iter().map(|id| id.0))),
+        )
+        .execute(conn)
+        .wrap_err("error deleting from table TimelineGroupItem")?;
+        let remaining: i64 = TimelineGroupItem::table
+            .filter(TimelineGroupItem::group_id.eq(group_id.0))
+            .count()
+            .get_result(conn)
+            .wrap_err("error querying table TimelineGroupItem")?;
+        if remaining == 0 {
+            delete_group(conn, group_id)?;
+            return Ok(true);
+        }
+        update_display_date_to_newest_asset(conn, group_id)?;
+        set_changed_at_now(conn, group_id)?;
+        Ok(false)
+    })
+}
+
+/// Fields that are None are left unchanged
+#[derive(Debug, Clone, Default)]
+pub struct UpdateTimelineGroup {
+    pub name: Option<String>,
+    pub display_date: Option<DateTime<Utc>>,
+}
+
+#[instrument(skip(conn))]
+pub fn update_timeline_group(
+    conn: &mut DbConn,
+    group_id: TimelineGroupId,
+    update: UpdateTimelineGroup,
+) -> Result<()> {
+    use schema::TimelineGroup;
+    conn.immediate_transaction(|conn| {
+        if let Some(name) = update.name {
+            diesel::update(TimelineGroup::table.find(group_id.0))
+                .set(TimelineGroup::name.eq(name))
+                .execute(conn)
+                .wrap_err("error updating TimelineGroup name")?;
+            search_index::index_timeline_group(conn, group_id)?;
+        }
+        if let Some(display_date) = update.display_date {
+            diesel::update(TimelineGroup::table.find(group_id.0))
+                .set(TimelineGroup::display_date.eq(datetime_to_db_repr(&display_date)))
+                .execute(conn)
+                .wrap_err("error updating TimelineGroup display_date")?;
+        }
+        set_changed_at_now(conn, group_id)
+    })
+}
+
+/// Delete the group, its assets are grouped by day in the timeline again
+#[instrument(skip(conn))]
+pub fn delete_timeline_group(conn: &mut DbConn, group_id: TimelineGroupId) -> Result<()> {
+    conn.immediate_transaction(|conn| delete_group(conn, group_id))
+}
+
+/// Must be called in a transaction
+fn delete_group(conn: &mut DbConn, group_id: TimelineGroupId) -> Result<()> {
+    use schema::{TimelineGroup, TimelineGroupItem};
+    diesel::delete(TimelineGroupItem::table.filter(TimelineGroupItem::group_id.eq(group_id.0)))
+        .execute(conn)
+        .wrap_err("error deleting from table TimelineGroupItem")?;
+    // ShareLinks of the group are deleted by ON DELETE CASCADE
+    diesel::delete(TimelineGroup::table.find(group_id.0))
+        .execute(conn)
+        .wrap_err("error deleting from table TimelineGroup")?;
+    search_index::remove_timeline_group(conn, group_id)?;
+    Ok(())
+}
+
+fn update_display_date_to_newest_asset(conn: &mut DbConn, group_id: TimelineGroupId) -> Result<()> {
+    // no query builder sorry idk how subqueries work
+    diesel::sql_query(
+        r#"
+    UPDATE TimelineGroup
+    SET display_date = (
+        SELECT MAX(Asset.taken_date)
+        FROM Asset INNER JOIN TimelineGroupItem ON TimelineGroupItem.asset_id = Asset.asset_id
+        WHERE TimelineGroupItem.group_id = ?
+    )
+    WHERE TimelineGroup.timeline_group_id = ?;
+    "#,
+    )
+    .bind::<diesel::sql_types::BigInt, _>(group_id.0)
+    .bind::<diesel::sql_types::BigInt, _>(group_id.0)
+    .execute(conn)
+    .wrap_err("error updating TimelineGroup display_date")?;
+    Ok(())
+}
+
+/// So that clients notice the timeline changed
+fn set_changed_at_now(conn: &mut DbConn, group_id: TimelineGroupId) -> Result<()> {
+    use schema::TimelineGroup;
+    diesel::update(TimelineGroup::table.find(group_id.0))
+        .set(TimelineGroup::changed_at.eq(datetime_to_db_repr(&Utc::now())))
+        .execute(conn)
+        .wrap_err("error updating TimelineGroup changed_at")?;
+    Ok(())
+}
+
 #[instrument(skip(conn))]
 pub fn get_assets_in_group(conn: &mut DbConn, group_id: TimelineGroupId) -> Result<Vec<Asset>> {
     use schema::{Asset, TimelineGroupItem};
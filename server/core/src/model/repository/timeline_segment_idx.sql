@@ -1,3 +1,5 @@
+-- ?1: UserId whose timeline this is
+-- ?2, ?3, ?4: country code, admin region and city of AssetPlace to filter by, NULL matches any
 WITH
 -- AssetSeries with date of its oldest Asset  
 series AS (
@@ -7,6 +9,7 @@ series AS (
 		COUNT(Asset.asset_id) AS series_len
 	FROM Asset INNER JOIN AssetSeries
 	ON Asset.series_id = AssetSeries.series_id 
+	WHERE Asset.missing_since IS NULL
 	GROUP BY AssetSeries.series_id
 ),
 timeline_sort AS (
@@ -24,11 +27,28 @@ timeline_sort AS (
 	Asset LEFT JOIN (
 		SELECT TimelineGroup.timeline_group_id AS group_id, TimelineGroup.display_date AS group_date, TimelineGroupItem.group_id, TimelineGroupItem.asset_id
 		FROM TimelineGroup INNER JOIN TimelineGroupItem ON TimelineGroup.timeline_group_id = TimelineGroupItem.group_id
+		-- assets in other users' private groups are shown ungrouped
+		WHERE TimelineGroup.is_private = 0 OR TimelineGroup.owner_id = ?1
 	) tgi
 	ON Asset.asset_id = tgi.asset_id
 	LEFT JOIN series
 	ON Asset.series_id = series.series_id
-	WHERE Asset.is_hidden = 0
+	WHERE Asset.missing_since IS NULL
+	AND NOT EXISTS (
+		SELECT 1 FROM UserAsset
+		WHERE UserAsset.asset_id = Asset.asset_id AND UserAsset.user_id = ?1
+		AND (UserAsset.is_hidden = 1 OR UserAsset.is_archived = 1)
+	)
+	AND (
+		(?2 IS NULL AND ?3 IS NULL AND ?4 IS NULL)
+		OR EXISTS (
+			SELECT 1 FROM AssetPlace
+			WHERE AssetPlace.asset_id = Asset.asset_id
+			AND (?2 IS NULL OR AssetPlace.country_code = ?2)
+			AND (?3 IS NULL OR AssetPlace.admin_region = ?3)
+			AND (?4 IS NULL OR AssetPlace.city = ?4)
+		)
+	)
 	ORDER BY sort_date DESC, series_date DESC, taken_date DESC, 
 	-- fallback sort by id to get stable results
 	series_id, group_id, asset_id
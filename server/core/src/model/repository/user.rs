@@ -0,0 +1,326 @@
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use eyre::{Context, Result};
+use tracing::instrument;
+
+use crate::model::{
+    repository::db_entity::{to_db_user_role, to_db_user_token_kind, DbUser, DbUserToken},
+    util::datetime_to_db_repr,
+    CreateUserToken, User, UserId, UserRole, UserToken, UserTokenId, UserTokenKind,
+};
+
+use super::db::DbConn;
+use super::schema;
+use super::search_index;
+
+/// last_used_at of a token is only updated if it is older than this,
+/// to not write to the database on every request
+const TOKEN_LAST_USED_RESOLUTION_MINUTES: i64 = 5;
+
+#[instrument(skip(conn, password_hash))]
+pub fn create_user(
+    conn: &mut DbConn,
+    name: &str,
+    password_hash: &str,
+    role: UserRole,
+) -> Result<UserId> {
+    use schema::User;
+    conn.immediate_transaction(|conn| {
+        let id = diesel::insert_into(User::table)
+            .values((
+                User::name.eq(name),
+                User::password_hash.eq(password_hash),
+                User::role.eq(to_db_user_role(role)),
+                User::created_at.eq(datetime_to_db_repr(&Utc::now())),
+            ))
+            .returning(User::user_id)
+            .get_result(conn)
+            .wrap_err("error inserting into table User")?;
+        // Asset.is_hidden is only set for Assets hidden before there were users,
+        // those stay hidden for everyone
+        diesel::sql_query(
+            r#"
+        INSERT INTO UserAsset (user_id, asset_id, is_hidden, is_archived)
+        SELECT ?, asset_id, 1, 0 FROM Asset WHERE is_hidden = 1;
+        "#,
+        )
+        .bind::<diesel::sql_types::BigInt, _>(id)
+        .execute(conn)
+        .wrap_err("error inserting into table UserAsset")?;
+        Ok(UserId(id))
+    })
+}
+
+#[instrument(skip(conn))]
+pub fn find_user(conn: &mut DbConn, user_id: UserId) -> Result<Option<User>> {
+    use schema::User;
+    let db_user: Option<DbUser> = User::table
+        .find(user_id.0)
+        .select(DbUser::as_select())
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table User")?;
+    db_user.map(|u| u.try_into()).transpose()
+}
+
+#[instrument(skip(conn))]
+pub fn get_users(conn: &mut DbConn) -> Result<Vec<User>> {
+    use schema::User;
+    let db_users: Vec<DbUser> = User::table
+        .select(DbUser::as_select())
+        .order_by(User::user_id)
+        .load(conn)
+        .wrap_err("error querying table User")?;
+    db_users.into_iter().map(|u| u.try_into()).collect()
+}
+
+#[instrument(skip(conn))]
+pub fn find_user_by_name(conn: &mut DbConn, name: &str) -> Result<Option<User>> {
+    use schema::User;
+    let db_user: Option<DbUser> = User::table
+        .filter(User::name.eq(name))
+        .select(DbUser::as_select())
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table User")?;
+    db_user.map(|u| u.try_into()).transpose()
+}
+
+/// The User and their password hash
+#[instrument(skip(conn))]
+pub fn find_user_for_login(conn: &mut DbConn, name: &str) -> Result<Option<(User, String)>> {
+    use schema::User;
+    let row: Option<(DbUser, String)> = User::table
+        .filter(User::name.eq(name))
+        .select((DbUser::as_select(), User::password_hash))
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table User")?;
+    row.map(|(db_user, password_hash)| Ok((db_user.try_into()?, password_hash)))
+        .transpose()
+}
+
+#[instrument(skip(conn))]
+pub fn user_with_name_exists(conn: &mut DbConn, name: &str) -> Result<bool> {
+    use schema::User;
+    let count: i64 = User::table
+        .filter(User::name.eq(name))
+        .count()
+        .get_result(conn)
+        .wrap_err("error querying table User")?;
+    Ok(count > 0)
+}
+
+#[instrument(skip(conn))]
+pub fn count_users_with_role(conn: &mut DbConn, role: UserRole) -> Result<i64> {
+    use schema::User;
+    User::table
+        .filter(User::role.eq(to_db_user_role(role)))
+        .count()
+        .get_result(conn)
+        .wrap_err("error querying table User")
+}
+
+#[instrument(skip(conn, password_hash))]
+pub fn set_user_password(conn: &mut DbConn, user_id: UserId, password_hash: &str) -> Result<()> {
+    use schema::User;
+    diesel::update(User::table.find(user_id.0))
+        .set(User::password_hash.eq(password_hash))
+        .execute(conn)
+        .wrap_err("error updating table User")?;
+    Ok(())
+}
+
+/// Also deletes the User's sessions, API tokens, private Albums and private TimelineGroups.
+/// Their other Albums and TimelineGroups are kept without owner.
+#[instrument(skip(conn))]
+pub fn delete_user(conn: &mut DbConn, user_id: UserId) -> Result<()> {
+    use schema::{Album, AlbumItem, AlbumThumbnail, TimelineGroup, TimelineGroupItem, User};
+    conn.immediate_transaction(|conn| {
+        search_index::remove_private_of_user(conn, user_id)?;
+        let private_albums = Album::table
+            .filter(Album::owner_id.eq(user_id.0))
+            .filter(Album::is_private.eq(1))
+            .select(Album::album_id);
+        diesel::delete(AlbumItem::table.filter(AlbumItem::album_id.eq_any(private_albums)))
+            .execute(conn)
+            .wrap_err("error deleting from table AlbumItem")?;
+        diesel::delete(
+            AlbumThumbnail::table.filter(AlbumThumbnail::album_id.eq_any(private_albums)),
+        )
+        .execute(conn)
+        .wrap_err("error deleting from table AlbumThumbnail")?;
+        diesel::delete(
+            Album::table
+                .filter(Album::owner_id.eq(user_id.0))
+                .filter(Album::is_private.eq(1)),
+        )
+        .execute(conn)
+        .wrap_err("error deleting from table Album")?;
+        diesel::update(Album::table.filter(Album::owner_id.eq(user_id.0)))
+            .set(Album::owner_id.eq(Option::<i64>::None))
+            .execute(conn)
+            .wrap_err("error updating table Album")?;
+
+        let private_groups = TimelineGroup::table
+            .filter(TimelineGroup::owner_id.eq(user_id.0))
+            .filter(TimelineGroup::is_private.eq(1))
+            .select(TimelineGroup::timeline_group_id);
+        diesel::delete(
+            TimelineGroupItem::table.filter(TimelineGroupItem::group_id.eq_any(private_groups)),
+        )
+        .execute(conn)
+        .wrap_err("error deleting from table TimelineGroupItem")?;
+        diesel::delete(
+            TimelineGroup::table
+                .filter(TimelineGroup::owner_id.eq(user_id.0))
+                .filter(TimelineGroup::is_private.eq(1)),
+        )
+        .execute(conn)
+        .wrap_err("error deleting from table TimelineGroup")?;
+        diesel::update(TimelineGroup::table.filter(TimelineGroup::owner_id.eq(user_id.0)))
+            .set(TimelineGroup::owner_id.eq(Option::<i64>::None))
+            .execute(conn)
+            .wrap_err("error updating table TimelineGroup")?;
+
+        diesel::delete(User::table.find(user_id.0))
+            .execute(conn)
+            .wrap_err("error deleting from table User")?;
+        Ok(())
+    })
+}
+
+#[instrument(skip(conn, create_token))]
+pub fn create_user_token(conn: &mut DbConn, create_token: &CreateUserToken) -> Result<UserTokenId> {
+    use schema::UserToken;
+    let id = diesel::insert_into(UserToken::table)
+        .values((
+            UserToken::user_id.eq(create_token.user_id.0),
+            UserToken::kind.eq(to_db_user_token_kind(create_token.kind)),
+            UserToken::name.eq(&create_token.name),
+            UserToken::token_hash.eq(&create_token.token_hash),
+            UserToken::created_at.eq(datetime_to_db_repr(&Utc::now())),
+            UserToken::expires_at.eq(create_token.expires_at.as_ref().map(datetime_to_db_repr)),
+        ))
+        .returning(UserToken::user_token_id)
+        .get_result(conn)
+        .wrap_err("error inserting into table UserToken")?;
+    Ok(UserTokenId(id))
+}
+
+/// The User a token belongs to, if the token exists and has not expired.
+/// Records that the token was used.
+#[instrument(skip(conn, token_hash))]
+pub fn find_user_for_token(
+    conn: &mut DbConn,
+    token_hash: &[u8],
+    now: DateTime<Utc>,
+) -> Result<Option<(User, UserToken)>> {
+    use schema::{User, UserToken};
+    let now_db = datetime_to_db_repr(&now);
+    let row: Option<(DbUser, DbUserToken)> = UserToken::table
+        .inner_join(User::table)
+        .filter(UserToken::token_hash.eq(token_hash))
+        .filter(
+            UserToken::expires_at
+                .is_null()
+                .or(UserToken::expires_at.gt(now_db)),
+        )
+        .select((DbUser::as_select(), DbUserToken::as_select()))
+        .first(conn)
+        .optional()
+        .wrap_err("error querying table UserToken")?;
+    let Some((db_user, db_token)) = row else {
+        return Ok(None);
+    };
+    let user = crate::model::User::try_from(db_user)?;
+    let token = crate::model::UserToken::try_from(db_token)?;
+    let needs_update = match token.last_used_at {
+        None => true,
+        Some(last_used_at) => {
+            now - last_used_at > Duration::minutes(TOKEN_LAST_USED_RESOLUTION_MINUTES)
+        }
+    };
+    if needs_update {
+        diesel::update(UserToken::table.find(token.id.0))
+            .set(UserToken::last_used_at.eq(now_db))
+            .execute(conn)
+            .wrap_err("error updating table UserToken")?;
+    }
+    Ok(Some((user, token)))
+}
+
+#[instrument(skip(conn))]
+pub fn get_user_tokens(
+    conn: &mut DbConn,
+    user_id: UserId,
+    kind: UserTokenKind,
+) -> Result<Vec<UserToken>> {
+    use schema::UserToken;
+    let db_tokens: Vec<DbUserToken> = UserToken::table
+        .filter(UserToken::user_id.eq(user_id.0))
+        .filter(UserToken::kind.eq(to_db_user_token_kind(kind)))
+        .select(DbUserToken::as_select())
+        .order_by(UserToken::user_token_id)
+        .load(conn)
+        .wrap_err("error querying table UserToken")?;
+    db_tokens.into_iter().map(|t| t.try_into()).collect()
+}
+
+#[instrument(skip(conn))]
+pub fn get_user_token(conn: &mut DbConn, token_id: UserTokenId) -> Result<UserToken> {
+    use schema::UserToken;
+    let db_token: DbUserToken = UserToken::table
+        .find(token_id.0)
+        .select(DbUserToken::as_select())
+        .first(conn)
+        .wrap_err("error querying table UserToken")?;
+    db_token.try_into()
+}
+
+/// Returns false if the User has no token of this kind with this id
+#[instrument(skip(conn))]
+pub fn delete_user_token(
+    conn: &mut DbConn,
+    user_id: UserId,
+    kind: UserTokenKind,
+    token_id: UserTokenId,
+) -> Result<bool> {
+    use schema::UserToken;
+    let deleted = diesel::delete(
+        UserToken::table
+            .filter(UserToken::user_token_id.eq(token_id.0))
+            .filter(UserToken::user_id.eq(user_id.0))
+            .filter(UserToken::kind.eq(to_db_user_token_kind(kind))),
+    )
+    .execute(conn)
+    .wrap_err("error deleting from table UserToken")?;
+    Ok(deleted > 0)
+}
+
+/// Log out every session of a User except `keep`, e.g. after changing their password
+#[instrument(skip(conn))]
+pub fn delete_user_sessions(
+    conn: &mut DbConn,
+    user_id: UserId,
+    keep: Option<UserTokenId>,
+) -> Result<()> {
+    use schema::UserToken;
+    diesel::delete(
+        UserToken::table
+            .filter(UserToken::user_id.eq(user_id.0))
+            .filter(UserToken::kind.eq(to_db_user_token_kind(UserTokenKind::Session)))
+            .filter(UserToken::user_token_id.ne(keep.map(|id| id.0).unwrap_or(-1))),
+    )
+    .execute(conn)
+    .wrap_err("error deleting from table UserToken")?;
+    Ok(())
+}
+
+#[instrument(skip(conn))]
+pub fn delete_expired_user_tokens(conn: &mut DbConn, now: DateTime<Utc>) -> Result<usize> {
+    use schema::UserToken;
+    diesel::delete(UserToken::table.filter(UserToken::expires_at.le(datetime_to_db_repr(&now))))
+        .execute(conn)
+        .wrap_err("error deleting from table UserToken")
+}
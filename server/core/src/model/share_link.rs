@@ -0,0 +1,36 @@
+use chrono::{DateTime, Utc};
+
+use super::{AlbumId, AssetId, ShareLinkId, TimelineGroupId, UserId};
+
+/// Shows an Album, TimelineGroup or single Asset to people without an account.
+/// The token itself is only known to whoever got the link.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShareLink {
+    pub id: ShareLinkId,
+    pub target: ShareLinkTarget,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    /// None for links that are valid until deleted
+    pub expires_at: Option<DateTime<Utc>>,
+    /// argon2 hash in PHC string format, None if no password is needed
+    pub password_hash: Option<String>,
+    /// If original files may be downloaded, otherwise only thumbnails and representations
+    pub allow_download: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ShareLinkTarget {
+    Album(AlbumId),
+    TimelineGroup(TimelineGroupId),
+    Asset(AssetId),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateShareLink {
+    pub target: ShareLinkTarget,
+    pub created_by: UserId,
+    pub token_hash: Vec<u8>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub password_hash: Option<String>,
+    pub allow_download: bool,
+}
@@ -1,6 +1,6 @@
 use chrono::{DateTime, Utc};
 
-use super::TimelineGroupId;
+use super::{TimelineGroupId, UserId};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct TimelineGroup {
@@ -9,4 +9,8 @@ pub struct TimelineGroup {
     pub display_date: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
     pub changed_at: DateTime<Utc>,
+    /// None for groups created before there were users
+    pub owner_id: Option<UserId>,
+    /// Only shown to the owner, other users see the assets ungrouped
+    pub is_private: bool,
 }
@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+
+use super::{UserId, UserTokenId};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User {
+    pub id: UserId,
+    pub name: String,
+    pub role: UserRole,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserRole {
+    /// Can additionally manage users, asset roots and background jobs
+    Admin,
+    User,
+}
+
+/// A login session or API token. The token itself is only known to the client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UserToken {
+    pub id: UserTokenId,
+    pub user_id: UserId,
+    pub kind: UserTokenKind,
+    /// Given by the user for API tokens
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// None for API tokens, which are valid until deleted
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UserTokenKind {
+    Session,
+    Api,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreateUserToken {
+    pub user_id: UserId,
+    pub kind: UserTokenKind,
+    pub name: Option<String>,
+    pub token_hash: Vec<u8>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
@@ -1,13 +1,16 @@
 use std::process::Stdio;
 
 use camino::Utf8Path as Path;
-use eyre::{eyre, Context, Result};
+use eyre::{Context, Result};
 use tokio::process::Command;
 use tracing::instrument;
 
-use crate::processing::{
-    process_control::{run_process, ProcessControlReceiver, ProcessResult},
-    video::ffmpeg::FFmpegError,
+use crate::{
+    model::Tool,
+    processing::{
+        process_control::{run_process, CommandError, ProcessControlReceiver, ProcessResult},
+        video::ffmpeg::FFmpegError,
+    },
 };
 
 #[instrument(skip(control_recv))]
@@ -17,20 +20,22 @@ pub async fn ffmpeg_snapshot(
     ffmpeg_bin_path: Option<&str>,
     control_recv: &mut ProcessControlReceiver,
 ) -> Result<()> {
-    let child = Command::new(ffmpeg_bin_path.unwrap_or("ffmpeg"))
+    let mut command = Command::new(ffmpeg_bin_path.unwrap_or("ffmpeg"));
+    command
         .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .args(["-nostdin", "-y", "-hide_banner"])
+        .stderr(Stdio::piped())
+        .args(["-nostdin", "-y", "-hide_banner", "-nostats"])
         .arg("-i")
         .arg(video_path)
         .args(["-ss", "00:00:00.00", "-frames:v", "1"])
-        .arg(output)
-        .spawn()
-        .wrap_err(FFmpegError::ErrorStarting)?;
+        .arg(output);
+    let child = command.spawn().wrap_err(FFmpegError::ErrorStarting)?;
 
     match run_process(child, control_recv).await {
         ProcessResult::RanToEnd(output) if output.status.success() => Ok(()),
-        ProcessResult::RanToEnd(_output) => Err(eyre!("ffmpeg exited with an error")),
+        ProcessResult::RanToEnd(output) => {
+            Err(CommandError::new(Tool::FFmpeg, &command, &output).into())
+        }
         ProcessResult::TerminatedBySignal(_) => Err(FFmpegError::TerminatedBySignal.into()),
         ProcessResult::OtherError(err) => Err(err.wrap_err("error running ffmpeg")),
     }
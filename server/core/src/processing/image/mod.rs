@@ -4,7 +4,8 @@ mod vips_wrapper;
 pub use vips_wrapper::init as vips_init;
 pub use vips_wrapper::OutDimension;
 pub use vips_wrapper::{
-    convert_image, get_image_size, save_test_heif_image, save_test_jpeg_image, save_test_webp_image,
+    convert_image, get_image_size, perceptual_hash, save_test_heif_image, save_test_jpeg_image,
+    save_test_webp_image, sharpness,
 };
 
 pub mod image_conversion;
@@ -25,6 +25,8 @@ pub struct ThumbnailParams<'a> {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ThumbnailResult {
     pub actual_size: Size,
+    /// dHash of the image, computed along with thumbnails that keep the aspect ratio
+    pub perceptual_hash: Option<u64>,
 }
 
 #[async_trait]
@@ -68,6 +70,7 @@ impl GenerateThumbnailTrait for GenerateThumbnail {
                 width: vips_result.actual_size.width,
                 height: vips_result.actual_size.height,
             },
+            perceptual_hash: vips_result.perceptual_hash,
         })
     }
 
@@ -112,6 +115,7 @@ impl GenerateThumbnailTrait for GenerateThumbnailMock {
                 width: 400,
                 height: 400,
             },
+            perceptual_hash: None,
         })
     }
 
@@ -125,6 +129,7 @@ impl GenerateThumbnailTrait for GenerateThumbnailMock {
                 width: 400,
                 height: 400,
             },
+            perceptual_hash: None,
         })
     }
 }
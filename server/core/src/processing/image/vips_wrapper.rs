@@ -58,6 +58,8 @@ pub struct VipsThumbnailParams {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct VipsThumbailResult {
     pub actual_size: Size,
+    /// Only for `OutDimension::KeepAspect`
+    pub perceptual_hash: Option<u64>,
 }
 
 pub fn generate_thumbnail(params: VipsThumbnailParams) -> Result<VipsThumbailResult> {
@@ -79,6 +81,8 @@ pub fn generate_thumbnail(params: VipsThumbnailParams) -> Result<VipsThumbailRes
     let mut c_result = wrapper::ThumbnailResult {
         actual_width: 0,
         actual_height: 0,
+        has_perceptual_hash: false,
+        perceptual_hash: 0,
     };
     let params = wrapper::ThumbnailParams {
         in_path: c_path.as_ptr(),
@@ -110,7 +114,37 @@ pub fn generate_thumbnail(params: VipsThumbnailParams) -> Result<VipsThumbailRes
         width: c_result.actual_width,
         height: c_result.actual_height,
     };
-    Ok(VipsThumbailResult { actual_size })
+    let perceptual_hash = c_result
+        .has_perceptual_hash
+        .then_some(c_result.perceptual_hash);
+    Ok(VipsThumbailResult {
+        actual_size,
+        perceptual_hash,
+    })
+}
+
+/// dHash of the image, the same as `VipsThumbailResult::perceptual_hash`
+pub fn perceptual_hash(path: &Path) -> Result<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .wrap_err(format!("Could not convert path {} to bytes", &path))?;
+    let mut out: u64 = 0;
+    let ret = unsafe { wrapper::perceptual_hash(c_path.as_ptr(), &mut out as *mut _) };
+    match ret {
+        0 => Ok(out),
+        _ => Err(eyre!("Error computing perceptual hash with libvips")),
+    }
+}
+
+/// Variance of the Laplacian of the image, higher values mean more edges and a sharper image
+pub fn sharpness(path: &Path) -> Result<f64> {
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .wrap_err(format!("Could not convert path {} to bytes", &path))?;
+    let mut out: f64 = 0.0;
+    let ret = unsafe { wrapper::sharpness(c_path.as_ptr(), &mut out as *mut _) };
+    match ret {
+        0 => Ok(out),
+        _ => Err(eyre!("Error computing sharpness with libvips")),
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -1,11 +1,14 @@
 use camino::Utf8Path as Path;
-use chrono::{DateTime, Local, Utc};
+use chrono::{DateTime, Local, SubsecRound, Utc};
 use color_eyre::eyre::Result;
 use eyre::{eyre, Context};
 
 use crate::{
     config, interact,
-    model::{repository::db::DbPool, repository::duplicate_asset::NewDuplicateAsset, *},
+    model::{
+        repository::asset::AssetFileState, repository::db::DbPool,
+        repository::duplicate_asset::NewDuplicateAsset, *,
+    },
     processing::{self, hash::hash_file},
 };
 
@@ -14,31 +17,264 @@ use super::{
     video::{streams::FFProbeStreamsTrait, FFProbe},
 };
 
-/// Returns Some(AssetId) if a new, non duplicate asset was indexed and added to the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexFileResult {
+    /// A new, non duplicate asset was indexed and added to the database
+    NewAsset(AssetId),
+    /// The file of an existing Asset changed and the Asset was updated from it
+    AssetModified(AssetId),
+    /// The file is already indexed and unchanged, a duplicate, a known Asset that was moved,
+    /// or not something we can index
+    NothingNew,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct FileStat {
+    /// truncated to milliseconds like all timestamps in the database
+    pub mtime: DateTime<Utc>,
+    pub size: i64,
+}
+
 #[tracing::instrument(skip(pool, asset_root, bin_paths))]
 pub async fn index_file(
     path: &Path,
     asset_root: &AssetRootDir,
     pool: &DbPool,
     bin_paths: Option<&config::BinPaths>,
-) -> Result<Option<AssetId>> {
+) -> Result<IndexFileResult> {
     let path_in_asset_root = path
         .strip_prefix(&asset_root.path)
         .wrap_err("file to index is not in provided asset root")?;
     let path_in_asset_root2 = path_in_asset_root.to_owned();
     let asset_root_id = asset_root.id;
+    let file_stat = read_file_stat(path).await?;
     let conn = pool.get().await?;
-    let existing = interact!(conn, move |conn| {
-        repository::asset::asset_or_duplicate_with_path_exists(
+    let (existing_asset, is_duplicate) = interact!(conn, move |conn| {
+        let existing_asset = repository::asset::get_asset_file_state_with_path(
             conn,
             asset_root_id,
             &path_in_asset_root2,
-        )
+        )?;
+        let is_duplicate = match &existing_asset {
+            Some(existing_asset) if existing_asset.is_missing => {
+                let dup = repository::duplicate_asset::find_duplicate_with_path(
+                    conn,
+                    asset_root_id,
+                    &path_in_asset_root2,
+                )?;
+                let is_unchanged = existing_asset.file_mtime == Some(file_stat.mtime)
+                    && existing_asset.file_size == Some(file_stat.size);
+                match dup {
+                    // the file was replaced by a copy of another Asset, see `reindex_if_modified`
+                    Some(_) if is_unchanged => true,
+                    dup => {
+                        // the file was missing and reappeared, or was changed again after
+                        // becoming a copy of another Asset
+                        if let Some(dup) = dup {
+                            repository::duplicate_asset::delete_duplicate_assets(conn, &[dup.id])?;
+                        }
+                        repository::asset::set_assets_missing(
+                            conn,
+                            &[existing_asset.asset_id],
+                            None,
+                        )?;
+                        false
+                    }
+                }
+            }
+            Some(_) => false,
+            None => repository::asset::asset_or_duplicate_with_path_exists(
+                conn,
+                asset_root_id,
+                &path_in_asset_root2,
+            )?,
+        };
+        Ok((existing_asset, is_duplicate))
+    })
+    .await??;
+    if is_duplicate {
+        return Ok(IndexFileResult::NothingNew);
+    }
+    if let Some(existing_asset) = existing_asset {
+        return reindex_if_modified(path, asset_root, existing_asset, file_stat, pool, bin_paths)
+            .await;
+    }
+
+    let Some(mut create_asset) =
+        read_asset_from_file(path, asset_root, bin_paths, file_stat).await?
+    else {
+        return Ok(IndexFileResult::NothingNew);
+    };
+    let hash = hash_file_at(path).await?;
+    create_asset.base.hash = Some(hash);
+    let existing_with_same_hash = interact!(conn, move |conn| {
+        match repository::asset::get_asset_with_hash(conn, hash)? {
+            Some(existing_id) => Ok(Some(repository::asset::get_asset_path_on_disk(
+                conn,
+                existing_id,
+            )?)),
+            None => Ok(None),
+        }
+    })
+    .await??;
+    if let Some(existing) = existing_with_same_hash {
+        index_file_with_known_hash(existing, asset_root_id, path_in_asset_root, file_stat, pool)
+            .await?;
+        return Ok(IndexFileResult::NothingNew);
+    }
+    let id = interact!(conn, move |conn| {
+        let id = repository::asset::create_asset(conn, create_asset)?;
+        repository::album::update_smart_albums_for_asset(conn, id)?;
+        Ok(id)
     })
     .await??;
-    if existing {
-        return Ok(None);
+    Ok(IndexFileResult::NewAsset(id))
+}
+
+/// Handle a file whose hash matches the existing Asset `existing`.
+/// If the existing Asset's file is gone, this file was moved or renamed: keep the Asset
+/// (and with it album and group memberships) and point it to the new location.
+/// Otherwise this is just another copy of the same file.
+pub(super) async fn index_file_with_known_hash(
+    existing: AssetPathOnDisk,
+    asset_root_id: AssetRootDirId,
+    path_in_asset_root: &Path,
+    file_stat: FileStat,
+    pool: &DbPool,
+) -> Result<()> {
+    let existing_file_exists = tokio::fs::try_exists(existing.path_on_disk())
+        .await
+        .unwrap_or(true);
+    let existing_asset_id = existing.id;
+    let path_in_asset_root = path_in_asset_root.to_owned();
+    let conn = pool.get().await?;
+    interact!(conn, move |conn| {
+        // the path of a missing Asset can be taken by a different file,
+        // see `reindex_if_modified`
+        let existing_is_missing = repository::asset::get_asset_file_state(conn, existing_asset_id)?
+            .is_some_and(|state| state.is_missing);
+        if existing_file_exists && !existing_is_missing {
+            repository::duplicate_asset::insert_duplicate_asset(
+                conn,
+                NewDuplicateAsset {
+                    existing_asset_id,
+                    asset_root_dir_id: asset_root_id,
+                    path_in_asset_root: &path_in_asset_root,
+                },
+            )?;
+        } else {
+            tracing::info!(asset_id=%existing_asset_id, path=%path_in_asset_root, "Asset file was moved");
+            repository::asset::set_asset_path(
+                conn,
+                existing_asset_id,
+                asset_root_id,
+                &path_in_asset_root,
+            )?;
+            repository::asset::set_asset_file_stat(
+                conn,
+                existing_asset_id,
+                file_stat.mtime,
+                file_stat.size,
+            )?;
+        }
+        Ok(())
+    })
+    .await?
+}
+
+/// Check whether the file of an already indexed Asset changed and if so, update the Asset
+/// from the new file.
+/// If the new file is identical to another Asset's file, it becomes a duplicate of that Asset
+/// and this Asset is marked as missing.
+pub(super) async fn reindex_if_modified(
+    path: &Path,
+    asset_root: &AssetRootDir,
+    existing: AssetFileState,
+    file_stat: FileStat,
+    pool: &DbPool,
+    bin_paths: Option<&config::BinPaths>,
+) -> Result<IndexFileResult> {
+    let asset_id = existing.asset_id;
+    if existing.file_mtime == Some(file_stat.mtime) && existing.file_size == Some(file_stat.size) {
+        return Ok(IndexFileResult::NothingNew);
+    }
+    let hash = hash_file_at(path).await?;
+    let conn = pool.get().await?;
+    if existing.hash == Some(hash) {
+        // only touched, or indexed before we stored mtime and size
+        interact!(conn, move |conn| {
+            repository::asset::set_asset_file_stat(conn, asset_id, file_stat.mtime, file_stat.size)
+        })
+        .await??;
+        return Ok(IndexFileResult::NothingNew);
     }
+    let other_asset_id = interact!(conn, move |conn| {
+        repository::asset::get_asset_with_hash(conn, hash)
+    })
+    .await??;
+    if let Some(other_asset_id) = other_asset_id {
+        tracing::warn!(%asset_id, %other_asset_id, %path, "Asset file was modified and is now identical to another Asset");
+        interact!(conn, move |conn| {
+            repository::duplicate_asset::replace_asset_file_with_duplicate(
+                conn,
+                asset_id,
+                other_asset_id,
+                file_stat.mtime,
+                file_stat.size,
+                Utc::now(),
+            )
+        })
+        .await??;
+        return Ok(IndexFileResult::NothingNew);
+    }
+    tracing::info!(%asset_id, %path, "Asset file was modified, reindexing");
+    let mut create_asset = read_asset_from_file(path, asset_root, bin_paths, file_stat)
+        .await?
+        .ok_or(eyre!(
+            "modified file is no longer a supported image or video"
+        ))?;
+    create_asset.base.hash = Some(hash);
+    interact!(conn, move |conn| {
+        repository::asset::update_asset_from_file(conn, asset_id, &create_asset)?;
+        // taken_date, location etc. may have changed
+        repository::album::update_smart_albums_for_asset(conn, asset_id)
+    })
+    .await??;
+    Ok(IndexFileResult::AssetModified(asset_id))
+}
+
+pub(super) async fn read_file_stat(path: &Path) -> Result<FileStat> {
+    let metadata = tokio::fs::metadata(path)
+        .await
+        .wrap_err("could not read file metadata")?;
+    let mtime: DateTime<Utc> = metadata
+        .modified()
+        .wrap_err("could not read file modification time")?
+        .into();
+    Ok(FileStat {
+        mtime: mtime.trunc_subsecs(3),
+        size: metadata.len() as i64,
+    })
+}
+
+async fn hash_file_at(path: &Path) -> Result<u64> {
+    let file = tokio::fs::File::open(&path)
+        .await
+        .wrap_err("could not open asset file")?
+        .try_into_std()
+        .unwrap();
+    hash_file(file).await
+}
+
+/// Read metadata of a file with exiftool, ffprobe and vips.
+/// Returns None if it's not an image or video we can handle.
+/// The returned CreateAsset has no hash set.
+async fn read_asset_from_file(
+    path: &Path,
+    asset_root: &AssetRootDir,
+    bin_paths: Option<&config::BinPaths>,
+    file_stat: FileStat,
+) -> Result<Option<CreateAsset>> {
     let exiftool_path = bin_paths
         .and_then(|bp| bp.exiftool.as_ref())
         .map(|p| p.as_path());
@@ -129,34 +365,6 @@ pub async fn index_file(
             return Ok(None);
         }
     };
-    let file = tokio::fs::File::open(&path)
-        .await
-        .wrap_err("could not open asset file")?
-        .try_into_std()
-        .unwrap();
-    let hash = hash_file(file).await?;
-    let conn = pool.get().await?;
-    let path_in_asset_root2 = path_in_asset_root.to_owned();
-    let is_duplicate = interact!(conn, move |conn| {
-        let existing_with_same_hash = repository::asset::get_asset_with_hash(conn, hash)?;
-        if let Some(existing_asset_id) = existing_with_same_hash {
-            repository::duplicate_asset::insert_duplicate_asset(
-                conn,
-                NewDuplicateAsset {
-                    existing_asset_id,
-                    asset_root_dir_id: asset_root_id,
-                    path_in_asset_root: &path_in_asset_root2,
-                },
-            )?;
-            Ok(true)
-        } else {
-            Ok(false)
-        }
-    })
-    .await??;
-    if is_duplicate {
-        return Ok(None);
-    }
     let timestamp_guess = figure_out_utc_timestamp(&metadata);
     let (timestamp, timestamp_info): (DateTime<Utc>, TimestampInfo) = match timestamp_guess {
         TimestampGuess::None => (Utc::now(), TimestampInfo::NoTimestamp),
@@ -189,16 +397,15 @@ pub async fn index_file(
         size,
         rotation_correction: None,
         exiftool_output: exiftool_json,
-        hash: Some(hash),
+        // computed separately, only once we know this is a file we want
+        hash: None,
         gps_coordinates: coordinates,
+        file_mtime: Some(file_stat.mtime),
+        file_size: Some(file_stat.size),
     };
     let create_asset = CreateAsset {
         base: create_asset_base,
         spe: create_asset_spe,
     };
-    let id = interact!(conn, move |conn| {
-        repository::asset::create_asset(conn, create_asset)
-    })
-    .await??;
-    Ok(Some(id))
+    Ok(Some(create_asset))
 }
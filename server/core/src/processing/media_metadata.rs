@@ -55,6 +55,8 @@ pub mod exiftool {
         pub orientation: Option<i32>,
         #[serde(rename = "Make")]
         pub make: Option<String>,
+        #[serde(rename = "Model")]
+        pub model: Option<String>,
     }
 
     #[derive(Debug, Clone, Deserialize)]
@@ -87,6 +89,8 @@ pub mod exiftool {
         /// https://exiftool.org/makernote_types.html
         #[serde(rename = "MakerNotes")]
         pub maker_notes: Option<serde_json::Value>,
+        #[serde(rename = "XMP")]
+        pub xmp: Option<serde_json::Value>,
     }
 }
 
@@ -2,7 +2,10 @@ pub mod hash;
 pub mod image;
 pub mod indexing;
 pub mod media_metadata;
+pub mod near_duplicates;
 pub mod process_control;
+pub mod reconcile;
+pub mod series_detection;
 pub mod startup_self_check;
 pub mod video;
 
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use camino::Utf8PathBuf as PathBuf;
+use eyre::{Context, Result};
+
+use crate::model::AssetId;
+
+/// Largest number of differing bits of two perceptual hashes considered near duplicates
+pub const MAX_HAMMING_DISTANCE: u32 = 6;
+
+/// dHash of an image, on the rayon pool since libvips blocks
+#[tracing::instrument]
+pub async fn perceptual_hash_file(path: PathBuf) -> Result<u64> {
+    let (tx, rx) = tokio::sync::oneshot::channel::<Result<u64>>();
+    rayon::spawn(move || {
+        let res = super::image::perceptual_hash(&path);
+        tx.send(res).unwrap();
+    });
+    rx.await
+        .wrap_err("could not compute perceptual hash")?
+        .wrap_err("could not compute perceptual hash")
+}
+
+/// Groups of Assets whose hashes are at most `max_distance` bits apart, transitively:
+/// if a is close to b and b to c, a and c are in the same group even if they are not close.
+/// Assets without any near duplicate are left out.
+pub fn cluster_near_duplicates(hashes: &[(AssetId, u64)], max_distance: u32) -> Vec<Vec<AssetId>> {
+    // Pigeonhole: split the hashes into max_distance + 1 chunks, two hashes that differ in
+    // at most max_distance bits are equal in at least one chunk.
+    // Only hashes that share a chunk have to be compared, instead of all pairs.
+    let num_chunks = (max_distance + 1).min(64);
+    let chunk_bounds: Vec<(u32, u32)> = (0..num_chunks)
+        .map(|i| (i * 64 / num_chunks, (i + 1) * 64 / num_chunks))
+        .collect();
+    let mut union_find = UnionFind::new(hashes.len());
+    for (start, end) in chunk_bounds {
+        let mask = if end - start == 64 {
+            u64::MAX
+        } else {
+            ((1u64 << (end - start)) - 1) << start
+        };
+        let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+        for (idx, (_, hash)) in hashes.iter().enumerate() {
+            buckets.entry(hash & mask).or_default().push(idx);
+        }
+        for bucket in buckets.values() {
+            for (i, &a) in bucket.iter().enumerate() {
+                for &b in &bucket[i + 1..] {
+                    if (hashes[a].1 ^ hashes[b].1).count_ones() <= max_distance {
+                        union_find.union(a, b);
+                    }
+                }
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<AssetId>> = HashMap::new();
+    for (idx, (asset_id, _)) in hashes.iter().enumerate() {
+        groups
+            .entry(union_find.find(idx))
+            .or_default()
+            .push(*asset_id);
+    }
+    let mut groups: Vec<Vec<AssetId>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort();
+            group
+        })
+        .collect();
+    groups.sort();
+    groups
+}
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(len: usize) -> Self {
+        UnionFind {
+            parent: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            // path halving
+            self.parent[idx] = self.parent[self.parent[idx]];
+            idx = self.parent[idx];
+        }
+        idx
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let root_a = self.find(a);
+        let root_b = self.find(b);
+        if root_a != root_b {
+            self.parent[root_b] = root_a;
+        }
+    }
+}
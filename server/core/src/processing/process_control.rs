@@ -4,6 +4,11 @@ use nix::{
 };
 use tokio::sync::{mpsc, oneshot};
 
+use crate::{
+    config::{IoPriority, ProcessPriority},
+    model::{JobFailure, JobProgress, Tool},
+};
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ProcessControl {
     Suspend,
@@ -19,7 +24,70 @@ pub enum ProcessResult {
     OtherError(eyre::Report),
 }
 
-pub type ProcessControlReceiver = mpsc::Receiver<ProcessControl>;
+/// Control messages for the processes run by a task, one after the other,
+/// and the priority they are run with
+#[derive(Debug)]
+pub struct ProcessControlReceiver {
+    pub recv: mpsc::Receiver<ProcessControl>,
+    pub priority: ProcessPriority,
+}
+
+pub fn process_control_channel(
+    priority: ProcessPriority,
+) -> (mpsc::Sender<ProcessControl>, ProcessControlReceiver) {
+    let (send, recv) = mpsc::channel(1);
+    (send, ProcessControlReceiver { recv, priority })
+}
+
+/// For long running processes to report how far along they are
+pub type ProgressSender = mpsc::UnboundedSender<JobProgress>;
+
+/// Only keep the end of stderr, which is where the error usually is
+const MAX_STDERR_LEN: usize = 16 * 1024;
+
+/// An external program that exited with an error.
+/// Returned inside the error chain so that the failure can be recorded with what was run.
+#[derive(thiserror::Error, Debug)]
+#[error("{tool:?} exited with {status}")]
+pub struct CommandError {
+    pub tool: Tool,
+    pub command: String,
+    pub stderr: String,
+    pub status: std::process::ExitStatus,
+}
+
+impl CommandError {
+    pub fn new(
+        tool: Tool,
+        command: &tokio::process::Command,
+        output: &std::process::Output,
+    ) -> Self {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut start = stderr.len().saturating_sub(MAX_STDERR_LEN);
+        while !stderr.is_char_boundary(start) {
+            start += 1;
+        }
+        Self {
+            tool,
+            command: format!("{:?}", command.as_std()),
+            stderr: stderr[start..].to_owned(),
+            status: output.status,
+        }
+    }
+}
+
+/// Describe a failed job, with the command and its output if an external program failed
+pub fn job_failure(report: &eyre::Report) -> JobFailure {
+    let command_error = report
+        .chain()
+        .find_map(|err| err.downcast_ref::<CommandError>());
+    JobFailure {
+        error: format!("{:#}", report),
+        tool: command_error.map(|err| err.tool),
+        command: command_error.map(|err| err.command.clone()),
+        stderr: command_error.map(|err| err.stderr.clone()),
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ProcessState {
@@ -33,7 +101,7 @@ enum ProcessState {
 #[cfg(target_family = "unix")]
 pub async fn run_process(
     child: tokio::process::Child,
-    control_recv: &mut mpsc::Receiver<ProcessControl>,
+    control_recv: &mut ProcessControlReceiver,
     // stdout_lines_send: mpsc::Receiver<Option<String>>,
 ) -> ProcessResult {
     let pid = child.id().expect("child process must not have completed");
@@ -42,6 +110,10 @@ pub async fn run_process(
     let mut killed_by_signal = false;
     let mut state = ProcessState::Running;
     let pid = Pid::from_raw(pid.try_into().expect("pid_t is a signed 32-bit int"));
+    // set after spawning, the process runs with normal priority for a moment but that's fine
+    if let Err(err) = set_process_priority(pid, &control_recv.priority) {
+        tracing::warn!(?err, %pid, "error setting process priority");
+    }
     loop {
         tokio::select! {
             // Err variant is produced when sender is dropped, which we can ignore
@@ -60,7 +132,7 @@ pub async fn run_process(
                     }
                 }
             }
-            msg = control_recv.recv() => {
+            msg = control_recv.recv.recv() => {
                 match msg {
                     Some(msg) => {
                         if killed_by_signal {
@@ -88,7 +160,7 @@ pub async fn run_process(
                             }
                         }
                         }
-                        if msg == ProcessControl::Suspend { 
+                        if msg == ProcessControl::Suspend {
                             state = ProcessState::Stopped;
                         } else if msg == ProcessControl::Resume {
                             state = ProcessState::Running;
@@ -103,3 +175,54 @@ pub async fn run_process(
         }
     }
 }
+
+#[cfg(target_family = "unix")]
+fn set_process_priority(pid: Pid, priority: &ProcessPriority) -> eyre::Result<()> {
+    use eyre::Context;
+    if let Some(nice) = priority.nice {
+        // SAFETY: setpriority has no memory safety requirements
+        let ret =
+            unsafe { libc::setpriority(libc::PRIO_PROCESS, pid.as_raw() as libc::id_t, nice) };
+        if ret == -1 {
+            return Err(std::io::Error::last_os_error()).wrap_err("error setting nice value");
+        }
+    }
+    if let Some(io) = priority.io {
+        set_io_priority(pid, io)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_io_priority(pid: Pid, io: IoPriority) -> eyre::Result<()> {
+    use eyre::Context;
+    // from linux/ioprio.h, libc doesn't have these
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    let (class, level) = match io {
+        IoPriority::Realtime(level) => (1, level),
+        IoPriority::BestEffort(level) => (2, level),
+        IoPriority::Idle => (3, 0),
+    };
+    let ioprio = (class << IOPRIO_CLASS_SHIFT) | libc::c_int::from(level);
+    // SAFETY: ioprio_set only takes integer arguments
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            pid.as_raw(),
+            ioprio,
+        )
+    };
+    if ret == -1 {
+        return Err(std::io::Error::last_os_error()).wrap_err("error setting IO priority");
+    }
+    Ok(())
+}
+
+#[cfg(all(target_family = "unix", not(target_os = "linux")))]
+fn set_io_priority(_pid: Pid, _io: IoPriority) -> eyre::Result<()> {
+    Err(eyre::eyre!(
+        "setting IO priority is only supported on Linux"
+    ))
+}
@@ -0,0 +1,178 @@
+//! Bringing the catalog in line with files that were deleted or moved out of asset roots.
+//!
+//! Files that were moved within or between asset roots are matched back to their Asset by hash
+//! when they are indexed at their new location (see [`super::indexing::index_file`]), so here we
+//! only need to deal with paths that no longer exist.
+
+use std::collections::{HashMap, HashSet};
+
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use chrono::Utc;
+use eyre::{eyre, Context, Result};
+
+use crate::{
+    interact,
+    model::{repository, repository::db::DbPool, AssetId, AssetRootDir, AssetRootDirId},
+};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReconcileResult {
+    /// Assets whose file is gone, now marked as missing
+    pub newly_missing: usize,
+    /// Assets marked as missing whose file exists again
+    pub reappeared: usize,
+    /// Assets whose file is gone but that had a duplicate which took its place
+    pub replaced_by_duplicate: usize,
+    /// DuplicateAssets whose file is gone
+    pub removed_duplicates: usize,
+}
+
+/// Check every Asset and DuplicateAsset in `asset_root` for whether its file still exists
+#[tracing::instrument(skip(pool))]
+pub async fn reconcile_asset_root(
+    asset_root: &AssetRootDir,
+    pool: &DbPool,
+) -> Result<ReconcileResult> {
+    reconcile(asset_root, None, pool).await
+}
+
+/// Like [`reconcile_asset_root`], but only check Assets at or below `paths`.
+/// `paths` are absolute, as reported by the filesystem watcher.
+#[tracing::instrument(skip(pool))]
+pub async fn reconcile_paths(
+    asset_root: &AssetRootDir,
+    paths: &[PathBuf],
+    pool: &DbPool,
+) -> Result<ReconcileResult> {
+    let paths_in_root: Vec<PathBuf> = paths
+        .iter()
+        .filter_map(|path| path.strip_prefix(&asset_root.path).ok())
+        .map(|path| path.to_owned())
+        .collect();
+    if paths_in_root.is_empty() {
+        return Ok(ReconcileResult::default());
+    }
+    reconcile(asset_root, Some(&paths_in_root), pool).await
+}
+
+async fn reconcile(
+    asset_root: &AssetRootDir,
+    only_below: Option<&[PathBuf]>,
+    pool: &DbPool,
+) -> Result<ReconcileResult> {
+    // If the whole root is gone it is much more likely that a drive or network share is not
+    // mounted than that the user deleted everything, so don't touch anything.
+    let root_exists = tokio::fs::try_exists(&asset_root.path)
+        .await
+        .unwrap_or(false);
+    if !root_exists {
+        return Err(eyre!(
+            "asset root {} does not exist, not reconciling",
+            asset_root.path
+        ));
+    }
+    let is_selected = |path: &Path| match only_below {
+        None => true,
+        Some(prefixes) => prefixes.iter().any(|prefix| path.starts_with(prefix)),
+    };
+
+    let root_id = asset_root.id;
+    let conn = pool.get().await?;
+    let (asset_files, duplicates, asset_roots) = interact!(conn, move |conn| {
+        let asset_files = repository::asset::get_asset_files_in_root(conn, root_id)?;
+        let duplicates = repository::duplicate_asset::get_duplicates_in_root(conn, root_id)?;
+        let asset_roots = repository::asset_root_dir::get_asset_roots(conn)?;
+        Ok((asset_files, duplicates, asset_roots))
+    })
+    .await??;
+    let root_paths: HashMap<AssetRootDirId, PathBuf> = asset_roots
+        .into_iter()
+        .map(|root| (root.id, root.path))
+        .collect();
+
+    let mut result = ReconcileResult::default();
+
+    let mut gone_duplicates = Vec::default();
+    for dup in duplicates.iter().filter(|dup| is_selected(&dup.file_path)) {
+        if !file_exists(&asset_root.path.join(&dup.file_path)).await? {
+            gone_duplicates.push(dup.id);
+        }
+    }
+    result.removed_duplicates = gone_duplicates.len();
+    if !gone_duplicates.is_empty() {
+        interact!(conn, move |conn| {
+            repository::duplicate_asset::delete_duplicate_assets(conn, &gone_duplicates)
+        })
+        .await??;
+    }
+
+    // a missing Asset's path can be taken by a copy of another Asset,
+    // see `super::indexing::reindex_if_modified`
+    let duplicate_paths: HashSet<&Path> = duplicates
+        .iter()
+        .map(|dup| dup.file_path.as_path())
+        .collect();
+    let mut gone_assets: Vec<AssetId> = Vec::default();
+    let mut reappeared: Vec<AssetId> = Vec::default();
+    for asset_file in asset_files
+        .iter()
+        .filter(|asset_file| is_selected(&asset_file.path_in_root))
+    {
+        let exists = file_exists(&asset_root.path.join(&asset_file.path_in_root)).await?;
+        match (exists, asset_file.is_missing) {
+            (true, true) if duplicate_paths.contains(asset_file.path_in_root.as_path()) => {}
+            (true, true) => reappeared.push(asset_file.asset_id),
+            (false, false) => gone_assets.push(asset_file.asset_id),
+            _ => {}
+        }
+    }
+
+    let mut now_missing: Vec<AssetId> = Vec::default();
+    for asset_id in gone_assets {
+        // if there is another copy of the file, keep the Asset and use that one instead
+        let dups = interact!(conn, move |conn| {
+            repository::duplicate_asset::get_duplicates_of_asset(conn, asset_id)
+        })
+        .await??;
+        let mut replacement = None;
+        for dup in dups {
+            let Some(dup_root_path) = root_paths.get(&dup.root_dir_id) else {
+                continue;
+            };
+            if file_exists(&dup_root_path.join(&dup.file_path)).await? {
+                replacement = Some(dup);
+                break;
+            }
+        }
+        match replacement {
+            Some(dup) => {
+                tracing::info!(%asset_id, path=%dup.file_path, "Asset file is gone, using duplicate instead");
+                interact!(conn, move |conn| {
+                    repository::duplicate_asset::promote_duplicate_asset(conn, &dup)
+                })
+                .await??;
+                result.replaced_by_duplicate += 1;
+            }
+            None => now_missing.push(asset_id),
+        }
+    }
+
+    result.newly_missing = now_missing.len();
+    result.reappeared = reappeared.len();
+    if !now_missing.is_empty() || !reappeared.is_empty() {
+        let now = Utc::now();
+        interact!(conn, move |conn| {
+            repository::asset::set_assets_missing(conn, &now_missing, Some(now))?;
+            repository::asset::set_assets_missing(conn, &reappeared, None)?;
+            Ok(())
+        })
+        .await??;
+    }
+    Ok(result)
+}
+
+async fn file_exists(path: &Path) -> Result<bool> {
+    tokio::fs::try_exists(path)
+        .await
+        .wrap_err_with(|| format!("error checking if file {} exists", path))
+}
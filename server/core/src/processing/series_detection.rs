@@ -0,0 +1,159 @@
+use std::collections::{HashMap, HashSet};
+
+use camino::Utf8PathBuf as PathBuf;
+use chrono::{DateTime, Duration, Utc};
+use eyre::{eyre, Context, Result};
+
+use crate::model::{AssetId, AssetSeriesId};
+
+use super::media_metadata::exiftool;
+
+/// Largest time between two consecutive photos of a series
+pub const MAX_SERIES_GAP_SECS: i64 = 5;
+
+/// Largest number of differing bits of the perceptual hashes of two consecutive photos
+/// of a series. Looser than for near duplicates, things move between frames.
+pub const MAX_SERIES_HAMMING_DISTANCE: u32 = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Camera {
+    pub make: String,
+    pub model: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct SeriesCandidate {
+    pub asset_id: AssetId,
+    pub taken_date: DateTime<Utc>,
+    pub perceptual_hash: Option<u64>,
+    pub camera: Option<Camera>,
+    /// Id the camera gives all photos of a burst
+    pub burst_id: Option<String>,
+}
+
+/// Camera and burst id from the stored exiftool output of an Asset
+pub fn read_camera_and_burst_id(
+    exiftool_output: &[u8],
+) -> Result<(Option<Camera>, Option<String>)> {
+    let output: exiftool::Output = serde_json::from_slice::<Vec<exiftool::Output>>(exiftool_output)
+        .wrap_err("failed to parse exiftool output")?
+        .pop() // json is an array with a single element
+        .ok_or(eyre!("failed to parse exiftool output"))?;
+    let camera = output.exif.as_ref().and_then(|exif| {
+        Some(Camera {
+            make: exif.make.clone()?.trim().to_owned(),
+            model: exif.model.clone()?.trim().to_owned(),
+        })
+    });
+    // Apple: MakerNotes:BurstUUID, Google: XMP-GCamera:BurstID
+    let burst_id = [
+        output.maker_notes.as_ref().and_then(|v| v.get("BurstUUID")),
+        output.xmp.as_ref().and_then(|v| v.get("BurstID")),
+    ]
+    .into_iter()
+    .flatten()
+    .find_map(|value| match value {
+        serde_json::Value::String(s) if !s.is_empty() => Some(s.clone()),
+        serde_json::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    });
+    Ok((camera, burst_id))
+}
+
+/// Runs of Assets (sorted by taken_date) that were taken at most `MAX_SERIES_GAP_SECS` apart
+/// from the previous one. Only these can contain series, so the exiftool output has to
+/// be loaded only for them. Runs of a single Asset are left out.
+pub fn split_by_time_gaps(sorted_assets: &[(AssetId, DateTime<Utc>)]) -> Vec<Vec<AssetId>> {
+    let mut runs: Vec<Vec<AssetId>> = Vec::new();
+    let mut current: Vec<AssetId> = Vec::new();
+    let mut last_date: Option<DateTime<Utc>> = None;
+    for (asset_id, taken_date) in sorted_assets {
+        if last_date.is_some_and(|last_date| {
+            *taken_date - last_date > Duration::seconds(MAX_SERIES_GAP_SECS)
+        }) {
+            runs.push(std::mem::take(&mut current));
+        }
+        current.push(*asset_id);
+        last_date = Some(*taken_date);
+    }
+    runs.push(current);
+    runs.retain(|run| run.len() > 1);
+    runs
+}
+
+/// Groups of photos taken in quick succession with the same camera that belong together:
+/// they have the same burst id or, if the camera does not write one, look alike.
+/// Photos without a known camera (screenshots, downloads...) are never part of a series.
+pub fn detect_series(candidates: &[SeriesCandidate]) -> Vec<Vec<AssetId>> {
+    let mut by_camera: HashMap<&Camera, Vec<&SeriesCandidate>> = HashMap::new();
+    for candidate in candidates {
+        if let Some(camera) = &candidate.camera {
+            by_camera.entry(camera).or_default().push(candidate);
+        }
+    }
+    let mut series: Vec<Vec<AssetId>> = Vec::new();
+    for mut assets in by_camera.into_values() {
+        assets.sort_by_key(|asset| (asset.taken_date, asset.asset_id));
+        let mut current: Vec<AssetId> = Vec::new();
+        let mut prev: Option<&SeriesCandidate> = None;
+        for asset in assets {
+            if !prev.is_some_and(|prev| belong_together(prev, asset)) {
+                series.push(std::mem::take(&mut current));
+            }
+            current.push(asset.asset_id);
+            prev = Some(asset);
+        }
+        series.push(current);
+    }
+    series.retain(|series| series.len() > 1);
+    series.sort();
+    series
+}
+
+fn belong_together(prev: &SeriesCandidate, next: &SeriesCandidate) -> bool {
+    if next.taken_date - prev.taken_date > Duration::seconds(MAX_SERIES_GAP_SECS) {
+        return false;
+    }
+    match (&prev.burst_id, &next.burst_id) {
+        (Some(a), Some(b)) => a == b,
+        _ => match (prev.perceptual_hash, next.perceptual_hash) {
+            (Some(a), Some(b)) => (a ^ b).count_ones() <= MAX_SERIES_HAMMING_DISTANCE,
+            _ => false,
+        },
+    }
+}
+
+/// Whether the user already dissolved an automatically created series with (part of)
+/// these Assets, i.e. two or more of them were in the same deleted series.
+/// `deleted` are the rows of DeletedAutoAssetSeries for the Assets.
+pub fn was_dissolved(series: &[AssetId], deleted: &[(AssetSeriesId, AssetId)]) -> bool {
+    let series: HashSet<AssetId> = series.iter().copied().collect();
+    let mut seen: HashSet<AssetSeriesId> = HashSet::new();
+    deleted
+        .iter()
+        .filter(|(_, asset_id)| series.contains(asset_id))
+        .any(|(series_id, _)| !seen.insert(*series_id))
+}
+
+/// The sharpest photo, or the first one if the sharpness of none is known
+pub fn choose_selection(sharpness: &[(AssetId, Option<f64>)]) -> Option<AssetId> {
+    sharpness
+        .iter()
+        .filter_map(|(asset_id, sharpness)| sharpness.map(|sharpness| (*asset_id, sharpness)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(asset_id, _)| asset_id)
+        .or_else(|| sharpness.first().map(|(asset_id, _)| *asset_id))
+}
+
+/// Variance of the Laplacian of an image, on the rayon pool since libvips blocks
+#[tracing::instrument]
+pub async fn sharpness_file(path: PathBuf) -> Result<f64> {
+    let (tx, rx) = tokio::sync::oneshot::channel::<Result<f64>>();
+    rayon::spawn(move || {
+        let res = super::image::sharpness(&path);
+        tx.send(res).unwrap();
+    });
+    rx.await
+        .wrap_err("could not compute sharpness")?
+        .wrap_err("could not compute sharpness")
+}
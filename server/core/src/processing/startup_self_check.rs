@@ -14,6 +14,7 @@ use crate::{
         operation::package_video::AudioEncodingTarget,
     },
     config::BinPaths,
+    model::Tool,
     util::OptionPathExt,
 };
 
@@ -31,9 +32,65 @@ pub async fn run_self_check(bin_paths: Option<&BinPaths>) -> Result<(), ()> {
     let exiftool_bin_path: Option<&Path> = bin_paths.and_then(|bp| bp.exiftool.as_opt_path());
     check_can_run_exiftool(exiftool_bin_path).await?;
     check_can_encode_vips_images().await?;
+    for (tool, version) in tool_versions(bin_paths).await {
+        tracing::info!(?tool, version, "Found external program");
+    }
     Ok(())
 }
 
+/// Version of each external program whose failures are recorded, see [`Tool`].
+/// When a version changes, the failures caused by that program are retried.
+/// Programs that could not be run are left out.
+pub async fn tool_versions(bin_paths: Option<&BinPaths>) -> Vec<(Tool, String)> {
+    let mut versions = Vec::default();
+    for tool in Tool::ALL {
+        let (bin_path, default_bin, version_arg) = match tool {
+            Tool::FFmpeg => (
+                bin_paths.and_then(|bp| bp.ffmpeg.as_opt_path()),
+                "ffmpeg",
+                "-version",
+            ),
+            Tool::ShakaPackager => (
+                bin_paths.and_then(|bp| bp.shaka_packager.as_opt_path()),
+                "packager",
+                "--version",
+            ),
+            Tool::MpdGenerator => (
+                bin_paths.and_then(|bp| bp.mpd_generator.as_opt_path()),
+                "mpd_generator",
+                "--version",
+            ),
+        };
+        let bin = bin_path.map(|p| p.as_str()).unwrap_or(default_bin);
+        match tool_version(bin, version_arg).await {
+            Some(version) => versions.push((tool, version)),
+            None => tracing::warn!(?tool, "Could not get version of external program"),
+        }
+    }
+    versions
+}
+
+/// First line of the program's version output
+async fn tool_version(bin: &str, version_arg: &str) -> Option<String> {
+    let output = Command::new(bin)
+        .arg(version_arg)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(ToOwned::to_owned)
+}
+
 async fn check_can_run_ffmpeg(ffmpeg_bin_path: Option<&Path>) -> Result<(), ()> {
     let spawn_result = Command::new(ffmpeg_bin_path.map(|p| p.as_str()).unwrap_or("ffmpeg"))
         .arg("-version")
@@ -0,0 +1,129 @@
+use crate::{
+    interact,
+    model::{repository, AssetId},
+    processing::{
+        hash::hash_file,
+        indexing::{index_file, read_file_stat, reindex_if_modified, IndexFileResult},
+        reconcile::ReconcileResult,
+    },
+};
+
+use super::util::{setup, TestRoot};
+
+async fn hash_of(test_root: &TestRoot, path_in_root: &str) -> u64 {
+    let file = std::fs::File::open(test_root.root.path.join(path_in_root)).unwrap();
+    hash_file(file).await.unwrap()
+}
+
+async fn reindex(test_root: &TestRoot, path_in_root: &str, asset_id: AssetId) -> IndexFileResult {
+    let path = test_root.root.path.join(path_in_root);
+    let file_stat = read_file_stat(&path).await.unwrap();
+    let conn = test_root.pool.get().await.unwrap();
+    let existing = interact!(conn, move |conn| {
+        repository::asset::get_asset_file_state(conn, asset_id)
+    })
+    .await
+    .unwrap()
+    .unwrap()
+    .unwrap();
+    // without exiftool configured, actually reading the file's metadata fails
+    reindex_if_modified(
+        &path,
+        &test_root.root,
+        existing,
+        file_stat,
+        &test_root.pool,
+        None,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn unchanged_file_is_not_hashed_again() {
+    let test_root = setup().await;
+    // not the real hash, so hashing the file would make it look modified
+    let asset_id = test_root.create_asset("a.jpg", 1).await;
+    let file_stat = read_file_stat(&test_root.root.path.join("a.jpg"))
+        .await
+        .unwrap();
+    let conn = test_root.pool.get().await.unwrap();
+    interact!(conn, move |conn| {
+        repository::asset::set_asset_file_stat(conn, asset_id, file_stat.mtime, file_stat.size)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+
+    assert_eq!(
+        reindex(&test_root, "a.jpg", asset_id).await,
+        IndexFileResult::NothingNew
+    );
+}
+
+#[tokio::test]
+async fn touched_file_only_updates_file_stat() {
+    let test_root = setup().await;
+    test_root.write_file("a.jpg");
+    let hash = hash_of(&test_root, "a.jpg").await;
+    // indexed before file stats were stored
+    let asset_id = test_root.create_asset("a.jpg", hash).await;
+
+    assert_eq!(
+        reindex(&test_root, "a.jpg", asset_id).await,
+        IndexFileResult::NothingNew
+    );
+    let file_stat = read_file_stat(&test_root.root.path.join("a.jpg"))
+        .await
+        .unwrap();
+    let conn = test_root.pool.get().await.unwrap();
+    let state = interact!(conn, move |conn| {
+        repository::asset::get_asset_file_state(conn, asset_id)
+    })
+    .await
+    .unwrap()
+    .unwrap()
+    .unwrap();
+    assert_eq!(state.hash, Some(hash));
+    assert_eq!(state.file_mtime, Some(file_stat.mtime));
+    assert_eq!(state.file_size, Some(file_stat.size));
+}
+
+#[tokio::test]
+async fn modified_file_identical_to_other_asset_becomes_duplicate() {
+    let test_root = setup().await;
+    let asset_id = test_root.create_asset("a.jpg", 1).await;
+    // the file at a.jpg now has the same content as the other Asset's file
+    let other_hash = hash_of(&test_root, "a.jpg").await;
+    let other_asset_id = test_root.create_asset("b.jpg", other_hash).await;
+
+    assert_eq!(
+        reindex(&test_root, "a.jpg", asset_id).await,
+        IndexFileResult::NothingNew
+    );
+    assert!(test_root.is_missing(asset_id).await);
+    let conn = test_root.pool.get().await.unwrap();
+    let duplicates = interact!(conn, move |conn| {
+        repository::duplicate_asset::get_duplicates_of_asset(conn, other_asset_id)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].file_path.as_str(), "a.jpg");
+
+    // later scans leave it alone instead of hashing it again or bringing the Asset back
+    assert_eq!(
+        index_file(
+            &test_root.root.path.join("a.jpg"),
+            &test_root.root,
+            &test_root.pool,
+            None
+        )
+        .await
+        .unwrap(),
+        IndexFileResult::NothingNew
+    );
+    assert_eq!(test_root.reconcile().await, ReconcileResult::default());
+    assert!(test_root.is_missing(asset_id).await);
+}
@@ -1 +1,6 @@
+pub mod indexing;
 pub mod metadata;
+pub mod near_duplicates;
+pub mod reconcile;
+pub mod series_detection;
+pub mod util;
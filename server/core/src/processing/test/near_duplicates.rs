@@ -0,0 +1,35 @@
+use crate::{
+    model::AssetId,
+    processing::near_duplicates::{cluster_near_duplicates, MAX_HAMMING_DISTANCE},
+};
+
+#[test]
+fn near_duplicates_are_clustered_transitively() {
+    let base: u64 = 0x0123_4567_89ab_cdef;
+    let hashes = vec![
+        (AssetId(1), base),
+        // MAX_HAMMING_DISTANCE bits away from 1, spread over all chunks
+        (AssetId(2), base ^ 0x0101_0101_0101_0000),
+        // close to 2, but not to 1
+        (
+            AssetId(3),
+            base ^ 0x0101_0101_0101_0000 ^ 0x0000_0000_0000_0007,
+        ),
+        // unrelated
+        (AssetId(4), !base),
+        (AssetId(5), base ^ 0x00ff_00ff_00ff_00ff),
+    ];
+    assert_eq!(
+        (hashes[0].1 ^ hashes[1].1).count_ones(),
+        MAX_HAMMING_DISTANCE
+    );
+    let groups = cluster_near_duplicates(&hashes, MAX_HAMMING_DISTANCE);
+    assert_eq!(groups, vec![vec![AssetId(1), AssetId(2), AssetId(3)]]);
+}
+
+#[test]
+fn identical_hashes_are_near_duplicates() {
+    let hashes = vec![(AssetId(2), 42), (AssetId(1), 42), (AssetId(3), 43)];
+    let groups = cluster_near_duplicates(&hashes, 0);
+    assert_eq!(groups, vec![vec![AssetId(1), AssetId(2)]]);
+}
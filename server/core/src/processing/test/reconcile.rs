@@ -0,0 +1,242 @@
+use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
+use chrono::{SubsecRound, Utc};
+
+use crate::{
+    interact,
+    model::{
+        repository::{self, album::CreateAlbum},
+        SmartAlbumFilter,
+    },
+    processing::{
+        indexing::{index_file_with_known_hash, FileStat},
+        reconcile::ReconcileResult,
+    },
+};
+
+use super::util::setup;
+
+#[tokio::test]
+async fn vanished_file_is_marked_missing() {
+    let test_root = setup().await;
+    let asset_id = test_root.create_asset("a.jpg", 1).await;
+    let _other_id = test_root.create_asset("b.jpg", 2).await;
+    assert_eq!(test_root.reconcile().await, ReconcileResult::default());
+
+    std::fs::remove_file(test_root.root.path.join("a.jpg")).unwrap();
+    assert_eq!(
+        test_root.reconcile().await,
+        ReconcileResult {
+            newly_missing: 1,
+            ..Default::default()
+        }
+    );
+    assert!(test_root.is_missing(asset_id).await);
+    // already known to be missing
+    assert_eq!(test_root.reconcile().await, ReconcileResult::default());
+}
+
+#[tokio::test]
+async fn file_reappearing_within_grace_period_is_not_purged() {
+    let test_root = setup().await;
+    let asset_id = test_root.create_asset("dir/a.jpg", 1).await;
+    std::fs::remove_file(test_root.root.path.join("dir/a.jpg")).unwrap();
+    test_root.reconcile().await;
+    assert!(test_root.is_missing(asset_id).await);
+
+    // e.g. a network share that was unmounted for a while
+    test_root.write_file("dir/a.jpg");
+    assert_eq!(
+        test_root.reconcile().await,
+        ReconcileResult {
+            reappeared: 1,
+            ..Default::default()
+        }
+    );
+    assert!(!test_root.is_missing(asset_id).await);
+    let conn = test_root.pool.get().await.unwrap();
+    let to_purge = interact!(conn, move |conn| {
+        repository::asset::get_assets_missing_since_before(conn, Utc::now())
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert!(to_purge.is_empty());
+}
+
+#[tokio::test]
+async fn moved_file_is_matched_to_asset_by_hash() {
+    let test_root = setup().await;
+    let asset_id = test_root.create_asset("a.jpg", 1).await;
+    std::fs::create_dir(test_root.root.path.join("sorted")).unwrap();
+    std::fs::rename(
+        test_root.root.path.join("a.jpg"),
+        test_root.root.path.join("sorted/a.jpg"),
+    )
+    .unwrap();
+    // the watcher may report the removal before the new file is indexed
+    test_root.reconcile().await;
+    assert!(test_root.is_missing(asset_id).await);
+
+    let conn = test_root.pool.get().await.unwrap();
+    let existing = interact!(conn, move |conn| {
+        repository::asset::get_asset_path_on_disk(conn, asset_id)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    let file_stat = FileStat {
+        mtime: Utc::now().trunc_subsecs(3),
+        size: 12,
+    };
+    index_file_with_known_hash(
+        existing,
+        test_root.root.id,
+        Path::new("sorted/a.jpg"),
+        file_stat,
+        &test_root.pool,
+    )
+    .await
+    .unwrap();
+
+    assert!(!test_root.is_missing(asset_id).await);
+    let (path, state, duplicates) = interact!(conn, move |conn| {
+        let path = repository::asset::get_asset_path_on_disk(conn, asset_id)?;
+        let state = repository::asset::get_asset_file_state(conn, asset_id)?;
+        let duplicates = repository::duplicate_asset::get_duplicates_of_asset(conn, asset_id)?;
+        Ok((path, state, duplicates))
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(path.path_in_asset_root, PathBuf::from("sorted/a.jpg"));
+    let state = state.unwrap();
+    assert_eq!(state.file_mtime, Some(file_stat.mtime));
+    assert_eq!(state.file_size, Some(file_stat.size));
+    assert!(duplicates.is_empty());
+}
+
+#[tokio::test]
+async fn moving_file_updates_smart_albums() {
+    let test_root = setup().await;
+    let asset_id = test_root.create_asset("a.jpg", 1).await;
+    let conn = test_root.pool.get().await.unwrap();
+    let album_id = interact!(conn, move |conn| {
+        repository::album::create_album(
+            conn,
+            CreateAlbum {
+                name: Some("sorted".to_owned()),
+                description: None,
+                owner_id: None,
+                is_private: false,
+                smart_filter: Some(SmartAlbumFilter {
+                    path_prefix: Some("sorted/".to_owned()),
+                    ..Default::default()
+                }),
+            },
+            &[],
+        )
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    let album_asset_ids = || async {
+        let conn = test_root.pool.get().await.unwrap();
+        interact!(conn, move |conn| {
+            repository::album::get_assets_in_album(conn, album_id, None)
+        })
+        .await
+        .unwrap()
+        .unwrap()
+        .into_iter()
+        .map(|asset| asset.base.id)
+        .collect::<Vec<_>>()
+    };
+    assert!(album_asset_ids().await.is_empty());
+
+    test_root.write_file("sorted/a.jpg");
+    std::fs::remove_file(test_root.root.path.join("a.jpg")).unwrap();
+    let existing = interact!(conn, move |conn| {
+        repository::asset::get_asset_path_on_disk(conn, asset_id)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    index_file_with_known_hash(
+        existing,
+        test_root.root.id,
+        Path::new("sorted/a.jpg"),
+        FileStat {
+            mtime: Utc::now().trunc_subsecs(3),
+            size: 12,
+        },
+        &test_root.pool,
+    )
+    .await
+    .unwrap();
+    assert_eq!(album_asset_ids().await, vec![asset_id]);
+
+    // and out of the album when the file goes missing
+    std::fs::remove_file(test_root.root.path.join("sorted/a.jpg")).unwrap();
+    test_root.reconcile().await;
+    assert!(test_root.is_missing(asset_id).await);
+    assert!(album_asset_ids().await.is_empty());
+
+    test_root.write_file("sorted/a.jpg");
+    test_root.reconcile().await;
+    assert_eq!(album_asset_ids().await, vec![asset_id]);
+}
+
+#[tokio::test]
+async fn copied_file_is_recorded_as_duplicate() {
+    let test_root = setup().await;
+    let asset_id = test_root.create_asset("a.jpg", 1).await;
+    test_root.write_file("copy/a.jpg");
+
+    let conn = test_root.pool.get().await.unwrap();
+    let existing = interact!(conn, move |conn| {
+        repository::asset::get_asset_path_on_disk(conn, asset_id)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    index_file_with_known_hash(
+        existing,
+        test_root.root.id,
+        Path::new("copy/a.jpg"),
+        FileStat {
+            mtime: Utc::now().trunc_subsecs(3),
+            size: 5,
+        },
+        &test_root.pool,
+    )
+    .await
+    .unwrap();
+
+    let (path, duplicates) = interact!(conn, move |conn| {
+        let path = repository::asset::get_asset_path_on_disk(conn, asset_id)?;
+        let duplicates = repository::duplicate_asset::get_duplicates_of_asset(conn, asset_id)?;
+        Ok((path, duplicates))
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(path.path_in_asset_root, PathBuf::from("a.jpg"));
+    assert_eq!(
+        duplicates
+            .iter()
+            .map(|dup| dup.file_path.clone())
+            .collect::<Vec<_>>(),
+        vec![PathBuf::from("copy/a.jpg")]
+    );
+
+    // the original going away makes the copy take its place instead of marking it missing
+    std::fs::remove_file(test_root.root.path.join("a.jpg")).unwrap();
+    assert_eq!(
+        test_root.reconcile().await,
+        ReconcileResult {
+            replaced_by_duplicate: 1,
+            ..Default::default()
+        }
+    );
+    assert!(!test_root.is_missing(asset_id).await);
+}
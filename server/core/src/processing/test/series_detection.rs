@@ -0,0 +1,150 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+
+use crate::{
+    model::{AssetId, AssetSeriesId},
+    processing::series_detection::{
+        choose_selection, detect_series, read_camera_and_burst_id, split_by_time_gaps,
+        was_dissolved, Camera, SeriesCandidate,
+    },
+};
+
+fn start() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()
+}
+
+fn camera(model: &str) -> Option<Camera> {
+    Some(Camera {
+        make: "Apple".into(),
+        model: model.into(),
+    })
+}
+
+fn candidate(id: i64, secs: i64, hash: u64, camera: Option<Camera>) -> SeriesCandidate {
+    SeriesCandidate {
+        asset_id: AssetId(id),
+        taken_date: start() + Duration::seconds(secs),
+        perceptual_hash: Some(hash),
+        camera,
+        burst_id: None,
+    }
+}
+
+#[test]
+fn read_camera_and_burst_id_from_exiftool_output() {
+    let json = br#"[{
+        "File": {"MIMEType": "image/jpeg"},
+        "EXIF": {"Make": "Apple", "Model": "iPhone 12 "},
+        "MakerNotes": {"BurstUUID": "6A1F0C2E-1B7D-4F4A-9C55-9F3E2D1A0B7C"}
+    }]"#;
+    let (camera, burst_id) = read_camera_and_burst_id(json).unwrap();
+    assert_eq!(
+        camera,
+        Some(Camera {
+            make: "Apple".into(),
+            model: "iPhone 12".into()
+        })
+    );
+    assert_eq!(
+        burst_id.as_deref(),
+        Some("6A1F0C2E-1B7D-4F4A-9C55-9F3E2D1A0B7C")
+    );
+
+    let json = br#"[{"File": {}, "EXIF": {"Make": "Google"}}]"#;
+    assert_eq!(read_camera_and_burst_id(json).unwrap(), (None, None));
+}
+
+#[test]
+fn split_by_time_gaps_drops_single_assets() {
+    let dates = vec![
+        (AssetId(1), start()),
+        (AssetId(2), start() + Duration::seconds(2)),
+        (AssetId(3), start() + Duration::seconds(6)),
+        (AssetId(4), start() + Duration::seconds(60)),
+        (AssetId(5), start() + Duration::seconds(120)),
+        (AssetId(6), start() + Duration::seconds(121)),
+    ];
+    assert_eq!(
+        split_by_time_gaps(&dates),
+        vec![
+            vec![AssetId(1), AssetId(2), AssetId(3)],
+            vec![AssetId(5), AssetId(6)]
+        ]
+    );
+}
+
+#[test]
+fn series_need_same_camera_and_similar_looks() {
+    let hash: u64 = 0x0123_4567_89ab_cdef;
+    let candidates = vec![
+        candidate(1, 0, hash, camera("iPhone 12")),
+        candidate(2, 1, hash ^ 0xff, camera("iPhone 12")),
+        // taken at the same time by someone else
+        candidate(3, 1, hash, camera("iPhone 15")),
+        // different subject
+        candidate(4, 2, !hash, camera("iPhone 12")),
+        candidate(5, 3, !hash, camera("iPhone 12")),
+        // no camera
+        candidate(6, 3, hash, None),
+        candidate(7, 4, hash, None),
+    ];
+    assert_eq!(
+        detect_series(&candidates),
+        vec![vec![AssetId(1), AssetId(2)], vec![AssetId(4), AssetId(5)]]
+    );
+}
+
+#[test]
+fn burst_id_overrides_perceptual_hash() {
+    let hash: u64 = 0x0123_4567_89ab_cdef;
+    let mut candidates = vec![
+        candidate(1, 0, hash, camera("iPhone 12")),
+        candidate(2, 1, !hash, camera("iPhone 12")),
+        candidate(3, 2, !hash, camera("iPhone 12")),
+    ];
+    candidates[0].burst_id = Some("a".into());
+    candidates[1].burst_id = Some("a".into());
+    candidates[2].burst_id = Some("b".into());
+    assert_eq!(
+        detect_series(&candidates),
+        vec![vec![AssetId(1), AssetId(2)]]
+    );
+}
+
+#[test]
+fn dissolved_series_are_not_recreated() {
+    let series = vec![AssetId(1), AssetId(2), AssetId(3)];
+    assert!(!was_dissolved(&series, &[]));
+    // only one of the assets was in the dissolved series
+    assert!(!was_dissolved(
+        &series,
+        &[
+            (AssetSeriesId(1), AssetId(1)),
+            (AssetSeriesId(1), AssetId(7))
+        ]
+    ));
+    assert!(was_dissolved(
+        &series,
+        &[
+            (AssetSeriesId(1), AssetId(1)),
+            (AssetSeriesId(2), AssetId(2)),
+            (AssetSeriesId(1), AssetId(3))
+        ]
+    ));
+}
+
+#[test]
+fn sharpest_asset_is_selected() {
+    assert_eq!(
+        choose_selection(&[
+            (AssetId(1), Some(10.0)),
+            (AssetId(2), Some(30.0)),
+            (AssetId(3), None)
+        ]),
+        Some(AssetId(2))
+    );
+    assert_eq!(
+        choose_selection(&[(AssetId(1), None), (AssetId(2), None)]),
+        Some(AssetId(1))
+    );
+    assert_eq!(choose_selection(&[]), None);
+}
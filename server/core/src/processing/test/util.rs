@@ -0,0 +1,87 @@
+use camino::Utf8Path as Path;
+use tempfile::TempDir;
+
+use crate::{
+    interact,
+    model::{
+        repository::{
+            self,
+            db::{open_temp_db_pool_and_migrate, DbPool},
+            test::util::create_image_asset,
+        },
+        AssetId, AssetRootDir, AssetRootDirId,
+    },
+    processing::reconcile::{reconcile_asset_root, ReconcileResult},
+};
+
+/// Asset root in a temporary directory and a database in the same directory
+pub struct TestRoot {
+    _dir: TempDir,
+    pub root: AssetRootDir,
+    pub pool: DbPool,
+}
+
+pub async fn setup() -> TestRoot {
+    let dir = tempfile::tempdir().unwrap();
+    let dir_path = Path::from_path(dir.path()).unwrap().to_owned();
+    let root_path = dir_path.join("assets");
+    std::fs::create_dir(&root_path).unwrap();
+    let pool = open_temp_db_pool_and_migrate(&dir_path).await;
+    let conn = pool.get().await.unwrap();
+    let root = AssetRootDir {
+        id: AssetRootDirId(0),
+        path: root_path,
+    };
+    let root2 = root.clone();
+    let root_id = interact!(conn, move |conn| {
+        repository::asset_root_dir::insert_asset_root(conn, &root2)
+    })
+    .await
+    .unwrap()
+    .unwrap();
+    TestRoot {
+        _dir: dir,
+        root: AssetRootDir {
+            id: root_id,
+            ..root
+        },
+        pool,
+    }
+}
+
+impl TestRoot {
+    /// Write a file with its path as content
+    pub fn write_file(&self, path_in_root: &str) {
+        let path = self.root.path.join(path_in_root);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, path_in_root).unwrap();
+    }
+
+    pub async fn create_asset(&self, path_in_root: &str, hash: u64) -> AssetId {
+        self.write_file(path_in_root);
+        let create_asset = create_image_asset(self.root.id, path_in_root, hash);
+        let conn = self.pool.get().await.unwrap();
+        interact!(conn, move |conn| {
+            repository::asset::create_asset(conn, create_asset)
+        })
+        .await
+        .unwrap()
+        .unwrap()
+    }
+
+    pub async fn is_missing(&self, asset_id: AssetId) -> bool {
+        let conn = self.pool.get().await.unwrap();
+        interact!(conn, move |conn| {
+            repository::asset::get_asset_file_state(conn, asset_id)
+        })
+        .await
+        .unwrap()
+        .unwrap()
+        .unwrap()
+        .is_missing
+    }
+
+    pub async fn reconcile(&self) -> ReconcileResult {
+        reconcile_asset_root(&self.root, &self.pool).await.unwrap()
+    }
+}
@@ -2,13 +2,19 @@ use std::{ffi::OsString, process::Stdio};
 
 use async_trait::async_trait;
 use camino::Utf8Path as Path;
-use eyre::{eyre, Context, Result};
-use tokio::process::Command;
-use tracing::{debug, instrument};
+use eyre::{Context, Result};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::{ChildStdout, Command},
+};
+use tracing::{debug, instrument, Instrument};
 
 use crate::{
     core::storage::{Storage, StorageCommandOutput, StorageProvider},
-    processing::process_control::{run_process, ProcessControlReceiver, ProcessResult},
+    model::{JobProgress, Tool},
+    processing::process_control::{
+        run_process, CommandError, ProcessControlReceiver, ProcessResult, ProgressSender,
+    },
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -50,37 +56,112 @@ pub struct FFmpeg {
     flags: Vec<OsString>,
 }
 
-#[async_trait]
-impl FFmpegLocalOutputTrait for FFmpeg {
-    #[instrument(name = "ffmpeg", skip(self, control_recv))]
-    async fn run_with_local_output(
+impl FFmpeg {
+    /// Like `run_with_local_output`, reporting progress as milliseconds of output written
+    /// out of `duration_ms`, the duration of the input if known.
+    #[instrument(name = "ffmpeg", skip(self, control_recv, progress_send))]
+    pub async fn run_with_local_output_and_progress(
+        &self,
+        input: &str,
+        output: &Path,
+        ffmpeg_bin_path: Option<&Path>,
+        control_recv: &mut ProcessControlReceiver,
+        progress_send: &ProgressSender,
+        duration_ms: Option<i64>,
+    ) -> Result<()> {
+        self.run_local(
+            input,
+            output,
+            ffmpeg_bin_path,
+            control_recv,
+            Some((progress_send, duration_ms)),
+        )
+        .await
+    }
+
+    async fn run_local(
         &self,
         input: &str,
         output: &Path,
         ffmpeg_bin_path: Option<&Path>,
         control_recv: &mut ProcessControlReceiver,
+        progress: Option<(&ProgressSender, Option<i64>)>,
     ) -> Result<()> {
         let mut command = Command::new(ffmpeg_bin_path.unwrap_or("ffmpeg".into()));
+        // stderr is kept for when ffmpeg fails, so only log what is needed to find out why
         command
             .arg("-nostdin")
             .arg("-y")
+            .arg("-hide_banner")
+            .arg("-nostats")
             .stdout(Stdio::null())
-            .stderr(Stdio::null());
+            .stderr(Stdio::piped());
+        if progress.is_some() {
+            command
+                .arg("-progress")
+                .arg("pipe:1")
+                .stdout(Stdio::piped());
+        }
         command.args(self.pre_input_flags.iter());
         command.arg("-i").arg(input);
         command.args(self.flags.iter());
         command.arg(output);
         debug!(command = ?command.as_std(), "Invoking ffmpeg");
-        let child = command.spawn().wrap_err(FFmpegError::ErrorStarting)?;
+        let mut child = command.spawn().wrap_err(FFmpegError::ErrorStarting)?;
+        if let Some((progress_send, duration_ms)) = progress {
+            let stdout = child.stdout.take().expect("stdout was piped");
+            tokio::spawn(
+                report_progress(stdout, progress_send.clone(), duration_ms).in_current_span(),
+            );
+        }
         match run_process(child, control_recv).await {
             ProcessResult::RanToEnd(output) if output.status.success() => Ok(()),
-            ProcessResult::RanToEnd(_output) => Err(eyre!("ffmpeg exited with an error")),
+            ProcessResult::RanToEnd(output) => {
+                Err(CommandError::new(Tool::FFmpeg, &command, &output).into())
+            }
             ProcessResult::TerminatedBySignal(_) => Err(FFmpegError::TerminatedBySignal.into()),
             ProcessResult::OtherError(err) => Err(err.wrap_err("error running ffmpeg")),
         }
     }
 }
 
+/// Parse the output of `-progress` which is blocks of key=value lines
+async fn report_progress(
+    stdout: ChildStdout,
+    progress_send: ProgressSender,
+    duration_ms: Option<i64>,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        // value is N/A until the first frame is written
+        let out_time_us = line
+            .strip_prefix("out_time_us=")
+            .and_then(|value| value.parse::<i64>().ok());
+        if let Some(out_time_us) = out_time_us {
+            let done = (out_time_us / 1000).max(0);
+            let _ = progress_send.send(JobProgress {
+                done: duration_ms.map(|total| done.min(total)).unwrap_or(done),
+                total: duration_ms,
+            });
+        }
+    }
+}
+
+#[async_trait]
+impl FFmpegLocalOutputTrait for FFmpeg {
+    #[instrument(name = "ffmpeg", skip(self, control_recv))]
+    async fn run_with_local_output(
+        &self,
+        input: &str,
+        output: &Path,
+        ffmpeg_bin_path: Option<&Path>,
+        control_recv: &mut ProcessControlReceiver,
+    ) -> Result<()> {
+        self.run_local(input, output, ffmpeg_bin_path, control_recv, None)
+            .await
+    }
+}
+
 #[async_trait]
 impl FFmpegTrait for FFmpeg {
     fn new(pre_input_flags: Vec<OsString>, flags: Vec<OsString>) -> Self {
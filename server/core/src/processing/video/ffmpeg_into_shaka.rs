@@ -2,10 +2,13 @@ use async_trait::async_trait;
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use eyre::{Context, Result};
 
-use crate::{core::storage::Storage, processing::process_control::ProcessControlReceiver};
+use crate::{
+    core::storage::Storage,
+    processing::process_control::{ProcessControlReceiver, ProgressSender},
+};
 
 use super::{
-    ffmpeg::{FFmpeg, FFmpegLocalOutputTrait, FFmpegTrait},
+    ffmpeg::{FFmpeg, FFmpegTrait},
     shaka::{RepresentationType, ShakaPackager, ShakaPackagerTrait, ShakaResult},
     streams::FFProbeStreamsTrait,
     transcode::{ffmpeg_audio_flags, ffmpeg_video_flags, ProduceAudio, ProduceVideo},
@@ -18,10 +21,13 @@ pub trait FFmpegIntoShakaFFmpegTrait {
 
     fn new(input: PathBuf, video: Option<&ProduceVideo>, audio: Option<&ProduceAudio>) -> Self;
 
+    /// `duration_ms` of the input is used to report progress
     async fn run_ffmpeg(
         self,
         ffmpeg_bin_path: Option<&Path>,
         control_recv: &mut ProcessControlReceiver,
+        progress_send: &ProgressSender,
+        duration_ms: Option<i64>,
     ) -> Result<Self::Next>;
 }
 
@@ -68,6 +74,8 @@ impl FFmpegIntoShakaFFmpegTrait for FFmpegIntoShaka {
         self,
         ffmpeg_bin_path: Option<&Path>,
         control_recv: &mut ProcessControlReceiver,
+        progress_send: &ProgressSender,
+        duration_ms: Option<i64>,
     ) -> Result<Self::Next> {
         let ffmpeg_out_path = tempfile::Builder::new()
             .suffix(".mp4")
@@ -79,11 +87,13 @@ impl FFmpegIntoShakaFFmpegTrait for FFmpegIntoShaka {
             .try_into()
             .expect("temp files should have utf8 paths");
         self.ffmpeg
-            .run_with_local_output(
+            .run_with_local_output_and_progress(
                 self.input.as_str(),
                 &utf8_path,
                 ffmpeg_bin_path,
                 control_recv,
+                progress_send,
+                duration_ms,
             )
             .await?;
         Ok(FFmpegIntoShakaAfterFFmpeg { ffmpeg_out_path })
@@ -150,6 +160,8 @@ impl FFmpegIntoShakaFFmpegTrait for FFmpegIntoShakaMock {
         self,
         ffmpeg_bin_path: Option<&Path>,
         control_recv: &mut ProcessControlReceiver,
+        progress_send: &ProgressSender,
+        duration_ms: Option<i64>,
     ) -> Result<Self::Next> {
         Ok(self)
     }
@@ -1,10 +1,16 @@
+use std::process::Stdio;
+
 use async_trait::async_trait;
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
-use eyre::{eyre, Context, Result};
+use eyre::{Context, Result};
 use tokio::process::Command;
 use tracing::{debug, instrument};
 
-use crate::core::storage::{Storage, StorageCommandOutput, StorageProvider};
+use crate::{
+    core::storage::{Storage, StorageCommandOutput, StorageProvider},
+    model::Tool,
+    processing::process_control::CommandError,
+};
 
 #[async_trait]
 pub trait MpdGeneratorTrait {
@@ -46,7 +52,7 @@ impl MpdGeneratorTrait for MpdGenerator {
                     .wrap_err("error creating temp file")?;
                 let temp_path = tempfile.into_temp_path();
                 let mut read = storage.open_read_stream(key).await?;
-                let mut write = tokio::fs::File::open(&temp_path).await?;
+                let mut write = tokio::fs::File::create(&temp_path).await?;
                 tokio::io::copy(&mut read, &mut write).await?;
                 MediaInfoPath::Tempfile(temp_path)
             };
@@ -69,17 +75,18 @@ impl MpdGeneratorTrait for MpdGenerator {
         command
             .arg(format!("--input={}", input_str))
             .arg(format!("--output={}", mpd_out_str));
+        command.stdout(Stdio::null()).stderr(Stdio::piped());
         debug!(?command, "Invoking mpd_generator");
-        let result = command
+        let output = command
             .spawn()
             .wrap_err(format!("error calling mpd_generator ({})", mpd_generator))?
-            .wait()
+            .wait_with_output()
             .await?;
         command_out_file.flush_to_storage().await?;
         // FIXME mpd_generator just skips input media_info files that fail to open/don't exist
         // but carries on and exits with 0 so this check doesn't do anything
-        if !result.success() {
-            return Err(eyre!("mpd_generator exited with error"));
+        if !output.status.success() {
+            return Err(CommandError::new(Tool::MpdGenerator, &command, &output).into());
         }
         Ok(())
     }
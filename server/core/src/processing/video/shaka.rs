@@ -8,7 +8,10 @@ use tracing::{debug, instrument};
 
 use crate::{
     core::storage::{Storage, StorageCommandOutput, StorageProvider},
-    processing::process_control::{run_process, ProcessControlReceiver, ProcessResult},
+    model::Tool,
+    processing::process_control::{
+        run_process, CommandError, ProcessControlReceiver, ProcessResult,
+    },
 };
 
 #[derive(thiserror::Error, Debug)]
@@ -107,10 +110,9 @@ impl ShakaPackagerWithLocalOutputTrait for ShakaPackager {
 
         match run_process(child, control_recv).await {
             ProcessResult::RanToEnd(output) if output.status.success() => Ok(()),
-            ProcessResult::RanToEnd(output) => Err(eyre!(
-                "shaka packager exited with an error:\n{}",
-                String::from_utf8_lossy(&output.stderr)
-            )),
+            ProcessResult::RanToEnd(output) => {
+                Err(CommandError::new(Tool::ShakaPackager, &command, &output).into())
+            }
             ProcessResult::TerminatedBySignal(_) => Err(ShakaError::TerminatedBySignal.into()),
             ProcessResult::OtherError(err) => Err(err.wrap_err("error running shaka packager")),
         }
@@ -131,7 +133,7 @@ impl ShakaPackagerTrait for ShakaPackager {
         let mp4_out_file = storage.new_command_out_file(output_key).await?;
         let media_info_key = format!("{}.media_info", output_key);
         let media_info_out_file = storage.new_command_out_file(&media_info_key).await?;
-        // FIXME: This will break with StorageProviders that use tempfiles as command outputs
+        // StorageProviders that use tempfiles as command outputs must lay them out like their keys
         debug_assert!(media_info_out_file
             .path()
             .parent()
@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
 use eyre::{Context, Result};
-use tokio::fs::File;
+use tokio::{fs::File, io::AsyncWriteExt};
 use tracing::instrument;
 
 use crate::{
@@ -77,6 +77,10 @@ impl ShakaIntoFFmpegTrait for ShakaIntoFFmpeg {
             .await
             .wrap_err("error opening media_info file")?;
         tokio::io::copy(&mut read_media_info, &mut write_media_info).await?;
+        write_media_info
+            .shutdown()
+            .await
+            .wrap_err("error writing media_info file")?;
 
         ffmpeg
             .run(
@@ -0,0 +1,61 @@
+#include <vips/vips.h>
+#include "vips_wrapper.h"
+
+// Width the image is scaled to first, so that the result does not depend on the resolution
+#define SHARPNESS_INPUT_WIDTH 1024
+
+int sharpness(const char *path, double *out) {
+  if (path == NULL || out == NULL) {
+    return -1;
+  }
+  VipsImage *image = NULL;
+  VipsImage *grey = NULL;
+  VipsImage *band = NULL;
+  VipsImage *mask = NULL;
+  VipsImage *edges = NULL;
+  double deviation = 0;
+  int ret = vips_thumbnail(path, &image, SHARPNESS_INPUT_WIDTH, NULL);
+  if (!ret) {
+    ret = vips_colourspace(image, &grey, VIPS_INTERPRETATION_B_W, NULL);
+  }
+  if (!ret) {
+    // drop alpha
+    ret = vips_extract_band(grey, &band, 0, NULL);
+  }
+  if (!ret) {
+    mask = vips_image_new_matrixv(3, 3, 0.0, 1.0, 0.0, 1.0, -4.0, 1.0, 0.0,
+                                  1.0, 0.0);
+    if (mask == NULL) {
+      ret = -1;
+    }
+  }
+  if (!ret) {
+    ret = vips_conv(band, &edges, mask, "precision", VIPS_PRECISION_FLOAT,
+                    NULL);
+  }
+  if (!ret) {
+    ret = vips_deviate(edges, &deviation, NULL);
+  }
+  if (!ret) {
+    // variance of the Laplacian, blurry images have few edges
+    *out = deviation * deviation;
+  } else {
+    printf("libvips error: %s", vips_error_buffer());
+  }
+  if (edges != NULL) {
+    g_object_unref(edges);
+  }
+  if (mask != NULL) {
+    g_object_unref(mask);
+  }
+  if (band != NULL) {
+    g_object_unref(band);
+  }
+  if (grey != NULL) {
+    g_object_unref(grey);
+  }
+  if (image != NULL) {
+    g_object_unref(image);
+  }
+  return ret;
+}
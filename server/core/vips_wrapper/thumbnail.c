@@ -18,10 +18,87 @@ int init() {
 
 void teardown() { vips_shutdown(); }
 
+// Width the perceptual hash is computed at, the same as the large thumbnail
+// so that hashes from thumbnail() and perceptual_hash() match
+#define PERCEPTUAL_HASH_INPUT_WIDTH 400
+
+// dHash: shrink to 9x8 greyscale and compare horizontally adjacent pixels.
+// Survives resizing, recompression and small colour changes.
+static int dhash(VipsImage *in, unsigned long long *out) {
+  VipsImage *small = NULL;
+  VipsImage *grey = NULL;
+  VipsImage *band = NULL;
+  VipsImage *dbl = NULL;
+  double *pixels = NULL;
+  size_t size = 0;
+  int ret = vips_thumbnail_image(in, &small, 9, "height", 8, "size",
+                                 VIPS_SIZE_FORCE, NULL);
+  if (!ret) {
+    ret = vips_colourspace(small, &grey, VIPS_INTERPRETATION_B_W, NULL);
+  }
+  if (!ret) {
+    // drop alpha
+    ret = vips_extract_band(grey, &band, 0, NULL);
+  }
+  if (!ret) {
+    ret = vips_cast(band, &dbl, VIPS_FORMAT_DOUBLE, NULL);
+  }
+  if (!ret) {
+    pixels = vips_image_write_to_memory(dbl, &size);
+    if (pixels == NULL || size != 9 * 8 * sizeof(double)) {
+      ret = -1;
+    }
+  }
+  if (!ret) {
+    unsigned long long hash = 0;
+    for (int y = 0; y < 8; ++y) {
+      for (int x = 0; x < 8; ++x) {
+        hash = (hash << 1) | (pixels[y * 9 + x] < pixels[y * 9 + x + 1]);
+      }
+    }
+    *out = hash;
+  } else {
+    printf("libvips error: %s", vips_error_buffer());
+  }
+  g_free(pixels);
+  if (dbl != NULL) {
+    g_object_unref(dbl);
+  }
+  if (band != NULL) {
+    g_object_unref(band);
+  }
+  if (grey != NULL) {
+    g_object_unref(grey);
+  }
+  if (small != NULL) {
+    g_object_unref(small);
+  }
+  return ret;
+}
+
+int perceptual_hash(const char *path, unsigned long long *out) {
+  if (path == NULL || out == NULL) {
+    return -1;
+  }
+  VipsImage *image = NULL;
+  int ret = vips_thumbnail(path, &image, PERCEPTUAL_HASH_INPUT_WIDTH, NULL);
+  if (ret) {
+    printf("libvips error: %s", vips_error_buffer());
+    if (image != NULL) {
+      g_object_unref(image);
+    }
+    return ret;
+  }
+  ret = dhash(image, out);
+  g_object_unref(image);
+  return ret;
+}
+
 int thumbnail(ThumbnailParams params, ThumbnailResult* result) {
   if (result == NULL) {
     return -1;
   }
+  result->has_perceptual_hash = false;
 
   for (unsigned long long i = 0; i < params.num_out_paths; ++i) {
     VipsImage* out = NULL;
@@ -42,6 +119,22 @@ int thumbnail(ThumbnailParams params, ThumbnailResult* result) {
     ret = vips_image_write_to_file(out, params.out_paths[i], NULL);
     result->actual_width = out->Xsize;
     result->actual_height = out->Ysize;
+    if (!ret && params.keep_aspect && !result->has_perceptual_hash) {
+      VipsImage *hash_input = out;
+      VipsImage *resized = NULL;
+      // so the hash matches perceptual_hash() regardless of the thumbnail size
+      if (params.width != PERCEPTUAL_HASH_INPUT_WIDTH &&
+          !vips_thumbnail(params.in_path, &resized,
+                          PERCEPTUAL_HASH_INPUT_WIDTH, NULL)) {
+        hash_input = resized;
+      }
+      // a missing hash is not worth failing the thumbnail over
+      result->has_perceptual_hash =
+          dhash(hash_input, &result->perceptual_hash) == 0;
+      if (resized != NULL) {
+        g_object_unref(resized);
+      }
+    }
     if (out != NULL) {
       g_object_unref(out);
     }
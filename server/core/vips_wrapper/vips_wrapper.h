@@ -18,10 +18,16 @@ typedef struct ThumbnailOptions {
 typedef struct ThumbnailResult {
   int actual_width;
   int actual_height;
+  // only computed for keep_aspect thumbnails, cropping would change the hash
+  bool has_perceptual_hash;
+  unsigned long long perceptual_hash;
 } ThumbnailResult;
 
 int thumbnail(ThumbnailParams, ThumbnailResult *);
 
+// same hash as ThumbnailResult.perceptual_hash, for images that already have thumbnails
+int perceptual_hash(const char *path, unsigned long long *out);
+
 typedef struct ImageInfo {
   int width;
   int height;
@@ -29,6 +35,9 @@ typedef struct ImageInfo {
 
 int read_image_info(const char *path, ImageInfo *out);
 
+// variance of the Laplacian of the image, higher is sharper
+int sharpness(const char *path, double *out);
+
 typedef struct HeifSaveParams {
   int quality;
   int lossless;
@@ -30,7 +30,7 @@ pub async fn ingest_country_info(read: impl AsyncRead + Unpin + Send, pool: &DbP
         if no_longer_exist.contains(&country_info.geoname_id) {
             continue;
         }
-        sqlx::query!(
+        sqlx::query(
             r#"
 INSERT INTO Country(
 geoname_id,
@@ -40,22 +40,22 @@ continent,
 neighbors
 ) VALUES (?, ?, ?, ?, ?);
         "#,
-            country_info.geoname_id,
-            country_info.iso,
-            country_info.country,
-            country_info.continent,
-            country_info.neighbors
         )
+        .bind(country_info.geoname_id)
+        .bind(country_info.iso)
+        .bind(country_info.country)
+        .bind(country_info.continent)
+        .bind(country_info.neighbors)
         .execute(pool)
         .await?;
     }
     let now = chrono::Utc::now().timestamp();
-    sqlx::query!(
+    sqlx::query(
         r#"
 INSERT INTO CountryListPresent VALUES (0, ?);
     "#,
-        now
     )
+    .bind(now)
     .execute(pool)
     .await?;
     Ok(())
@@ -73,12 +73,12 @@ pub async fn ingest_country_geojson(
         .into_records();
     while let Some(record) = records.next().await {
         let record: GeoJsonCsv = record?.deserialize(None)?;
-        let exists = sqlx::query!(
+        let exists = sqlx::query(
             r#"
 SELECT * FROM Country WHERE geoname_id=?;
         "#,
-            record.geoname_id
         )
+        .bind(record.geoname_id)
         .fetch_optional(pool)
         .await?
         .is_some();
@@ -96,7 +96,7 @@ SELECT * FROM Country WHERE geoname_id=?;
         };
         let (bbox_x0, bbox_y0) = bbox.min().x_y();
         let (bbox_x1, bbox_y1) = bbox.max().x_y();
-        sqlx::query!(
+        sqlx::query(
             r#"
 INSERT INTO CountryGeometry(
 geoname_id, 
@@ -107,23 +107,23 @@ bbox_ymax,
 geojson
 ) VALUES (?, ?, ?, ?, ?, ?);
         "#,
-            record.geoname_id,
-            bbox_x0,
-            bbox_y0,
-            bbox_x1,
-            bbox_y1,
-            record.geojson,
         )
+        .bind(record.geoname_id)
+        .bind(bbox_x0)
+        .bind(bbox_y0)
+        .bind(bbox_x1)
+        .bind(bbox_y1)
+        .bind(record.geojson)
         .execute(pool)
         .await?;
     }
     let now = chrono::Utc::now().timestamp();
-    sqlx::query!(
+    sqlx::query(
         r#"
 INSERT INTO CountryGeometryPresent VALUES (0, ?);
     "#,
-        now
     )
+    .bind(now)
     .execute(pool)
     .await?;
     Ok(())
@@ -1,4 +1,4 @@
-use std::{cell::RefCell, collections::HashSet, str::FromStr};
+use std::{collections::HashSet, str::FromStr};
 
 use camino::Utf8Path as Path;
 use chrono::{DateTime, Utc};
@@ -9,7 +9,6 @@ use color_eyre::{
 use futures::StreamExt;
 use geo::{Contains, Coord, Geometry};
 use geojson::GeoJson;
-use serde::Deserialize;
 use sqlx::{migrate::MigrateDatabase, Sqlite, SqlitePool};
 use thiserror::Error;
 use tracing::info;
@@ -28,7 +27,7 @@ pub struct GeonameId(pub i64);
 pub struct ReverseGeocoder {
     pool: DbPool,
     index: UsearchIndex,
-    countries_in_index: RefCell<HashSet<GeonameId>>,
+    countries_in_index: HashSet<GeonameId>,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -56,6 +55,20 @@ pub struct LookupResult {
     pub feature_type: String,
     pub country_code: String,
     pub country_id: GeonameId,
+    pub country_name: String,
+    /// First level administrative division (state, province etc.), if the country has them
+    pub admin1_name: Option<String>,
+}
+
+#[derive(sqlx::FromRow)]
+struct GeonameRow {
+    name: String,
+    latitude: f64,
+    longitude: f64,
+    feature_class: String,
+    feature_code: String,
+    country_code: String,
+    admin1_code: String,
 }
 
 impl ReverseGeocoder {
@@ -121,11 +134,11 @@ impl ReverseGeocoder {
         if let None = country_data_exists {
             return Err(ReverseGeocodeError::CountryDataNotPresent { country_id });
         }
-        if !self.countries_in_index.borrow().contains(&country_id) {
+        if !self.countries_in_index.contains(&country_id) {
             add_country_to_index(country_id, &self.pool, &mut self.index)
                 .await
                 .wrap_err("error adding country data to index")?;
-            self.countries_in_index.borrow_mut().insert(country_id);
+            self.countries_in_index.insert(country_id);
         }
         let result_geoname_id = self
             .index
@@ -134,16 +147,38 @@ impl ReverseGeocoder {
             .wrap_err("error searching usearch index")?[0]
             .geoname_id
             .0;
-        let geoname_row = sqlx::query!(
+        let geoname_row: GeonameRow = sqlx::query_as(
             r#"
-SELECT * FROM Geoname 
+SELECT name, latitude, longitude, feature_class, feature_code, country_code, admin1_code
+FROM Geoname 
 WHERE geoname_id = ?;
         "#,
-            result_geoname_id
         )
+        .bind(result_geoname_id)
         .fetch_one(&self.pool)
         .await
         .wrap_err("error getting result row from table Geoname")?;
+        let country_name: String = sqlx::query_scalar(
+            r#"
+SELECT name FROM Country
+WHERE geoname_id = ?;
+        "#,
+        )
+        .bind(country_id.0)
+        .fetch_one(&self.pool)
+        .await
+        .wrap_err("error getting row from table Country")?;
+        let admin1_name: Option<String> = sqlx::query_scalar(
+            r#"
+SELECT name FROM Geoname
+WHERE country_code = ? AND admin1_code = ? AND feature_code = 'ADM1';
+        "#,
+        )
+        .bind(&geoname_row.country_code)
+        .bind(&geoname_row.admin1_code)
+        .fetch_optional(&self.pool)
+        .await
+        .wrap_err("error getting admin1 row from table Geoname")?;
         Ok(Some(LookupResult {
             geoname_id: GeonameId(result_geoname_id),
             name: geoname_row.name,
@@ -155,6 +190,8 @@ WHERE geoname_id = ?;
             feature_type: geoname_row.feature_code,
             country_code: geoname_row.country_code,
             country_id,
+            country_name,
+            admin1_name,
         }))
     }
 
@@ -208,17 +245,16 @@ WHERE geoname_id = ?;
     }
 
     pub async fn download_country_data(&self, country_id: GeonameId) -> Result<()> {
-        let country_code = sqlx::query!(
+        let country_code: String = sqlx::query_scalar(
             r#"
 SELECT iso FROM Country
 WHERE geoname_id = ?;
         "#,
-            country_id.0
         )
+        .bind(country_id.0)
         .fetch_one(&self.pool)
         .await
-        .wrap_err("could not get row from table Country")?
-        .iso;
+        .wrap_err("could not get row from table Country")?;
         let country_data_path = tempfile::Builder::new()
             .tempfile()
             .wrap_err("error creating temp file")?
@@ -243,13 +279,13 @@ WHERE geoname_id = ?;
         .await
         .wrap_err("error ingesting country data")?;
         let now = chrono::Utc::now().timestamp();
-        sqlx::query!(
+        sqlx::query(
             r#"
 INSERT INTO CountryDataPresent VALUES (?, ?);
     "#,
-            country_id.0,
-            now
         )
+        .bind(country_id.0)
+        .bind(now)
         .execute(&self.pool)
         .await?;
         Ok(())
@@ -261,43 +297,37 @@ async fn add_country_to_index(
     pool: &DbPool,
     index: &mut UsearchIndex,
 ) -> Result<()> {
-    let country_code = sqlx::query!(
+    let country_code: String = sqlx::query_scalar(
         r#"
 SELECT iso FROM COUNTRY
 WHERE geoname_id = ?;
     "#,
-        country_id.0
     )
+    .bind(country_id.0)
     .fetch_one(pool)
     .await
-    .wrap_err("could not query table Country")?
-    .iso;
-    let mut rows = sqlx::query!(
+    .wrap_err("could not query table Country")?;
+    let mut rows = sqlx::query_as::<_, (i64, f64, f64)>(
         r#"
 SELECT geoname_id, latitude, longitude FROM Geoname 
 WHERE country_code = ? AND feature_class = 'P'
 AND feature_code IN ('PPL', 'PPLL', 'PPLS', 'PPLA', 'PPLA2', 'PPLA3', 'PPLA4', 'PPLA5', 'PPLC', 'PPLG');
     "#,
-        country_code
     )
-    .fetch_many(pool);
+    .bind(country_code)
+    .fetch(pool);
     while let Some(row) = rows.next().await {
-        let row = row.wrap_err("error querying table Geoname")?;
-        match row {
-            sqlx::Either::Right(row) => {
-                index
-                    .add_to_index(
-                        row.geoname_id as u64,
-                        Coordinates {
-                            lat: row.latitude as f32,
-                            lon: row.longitude as f32,
-                        },
-                    )
-                    .await
-                    .wrap_err("error adding entry to index")?;
-            }
-            sqlx::Either::Left(_) => {}
-        }
+        let (geoname_id, latitude, longitude) = row.wrap_err("error querying table Geoname")?;
+        index
+            .add_to_index(
+                geoname_id as u64,
+                Coordinates {
+                    lat: latitude as f32,
+                    lon: longitude as f32,
+                },
+            )
+            .await
+            .wrap_err("error adding entry to index")?;
     }
     Ok(())
 }
@@ -307,15 +337,14 @@ async fn country_contains(
     coord: Coordinates,
     pool: &DbPool,
 ) -> Result<bool> {
-    let geojson_str = sqlx::query!(
+    let geojson_str: String = sqlx::query_scalar(
         r#"
 SELECT geojson FROM CountryGeometry WHERE geoname_id = ?;
     "#,
-        geoname_id.0
     )
+    .bind(geoname_id.0)
     .fetch_one(pool)
-    .await?
-    .geojson;
+    .await?;
     let geojson = GeoJson::from_str(&geojson_str).unwrap();
     let geom: geo::Geometry<f32> = geojson.try_into()?;
     match &geom {
@@ -332,32 +361,27 @@ SELECT geojson FROM CountryGeometry WHERE geoname_id = ?;
 }
 
 async fn country_candidates_by_bbox(coord: Coordinates, pool: &DbPool) -> Result<Vec<GeonameId>> {
-    #[derive(Deserialize)]
-    struct QueryResult {
-        pub geoname_id: i64,
-    }
     // geojson is lon, lat!
-    let results: Vec<_> = sqlx::query_as!(
-        QueryResult,
+    let results: Vec<_> = sqlx::query_scalar::<_, i64>(
         r#"
 SELECT Country.geoname_id as geoname_id FROM Country, CountryGeometry
 WHERE bbox_xmin < $1 AND bbox_xmax > $1
 AND bbox_ymin < $2 AND bbox_ymax > $2
 AND Country.geoname_id = CountryGeometry.geoname_id;
     "#,
-        coord.lon,
-        coord.lat,
     )
+    .bind(coord.lon)
+    .bind(coord.lat)
     .fetch_all(pool)
     .await?
     .into_iter()
-    .map(|r| GeonameId(r.geoname_id))
+    .map(GeonameId)
     .collect();
     Ok(results)
 }
 
 async fn country_list_exists(pool: &DbPool) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
-    let result = sqlx::query!(
+    let result: Option<i64> = sqlx::query_scalar(
         r#"
 SELECT added_at
 FROM CountryListPresent;
@@ -367,8 +391,8 @@ FROM CountryListPresent;
     .await
     .wrap_err("could not query table CountryListPresent")?;
     let added_at = result
-        .map(|r| {
-            chrono::DateTime::from_timestamp(r.added_at, 0)
+        .map(|added_at| {
+            chrono::DateTime::from_timestamp(added_at, 0)
                 .ok_or(eyre!("invalid timestamp in column added_at"))
         })
         .transpose()?;
@@ -376,7 +400,7 @@ FROM CountryListPresent;
 }
 
 async fn country_geometry_exists(pool: &DbPool) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
-    let result = sqlx::query!(
+    let result: Option<i64> = sqlx::query_scalar(
         r#"
 SELECT added_at
 FROM CountryGeometryPresent;
@@ -386,8 +410,8 @@ FROM CountryGeometryPresent;
     .await
     .wrap_err("could not query table CargoGeometryPresent")?;
     let added_at = result
-        .map(|r| {
-            chrono::DateTime::from_timestamp(r.added_at, 0)
+        .map(|added_at| {
+            chrono::DateTime::from_timestamp(added_at, 0)
                 .ok_or(eyre!("invalid timestamp in column added_at"))
         })
         .transpose()?;
@@ -398,20 +422,20 @@ pub async fn country_data_exists(
     country_geoname_id: GeonameId,
     pool: &DbPool,
 ) -> Result<Option<chrono::DateTime<chrono::Utc>>> {
-    let result = sqlx::query!(
+    let result: Option<i64> = sqlx::query_scalar(
         r#"
-SELECT geoname_id, added_at
+SELECT added_at
 FROM CountryDataPresent
 WHERE geoname_id = ?;
     "#,
-        country_geoname_id.0
     )
+    .bind(country_geoname_id.0)
     .fetch_optional(pool)
     .await
     .wrap_err("could not query table CountryDataPresent")?;
     let added_at = result
-        .map(|r| {
-            chrono::DateTime::from_timestamp(r.added_at, 0)
+        .map(|added_at| {
+            chrono::DateTime::from_timestamp(added_at, 0)
                 .ok_or(eyre!("invalid timestamp in column added_at"))
         })
         .transpose()?;
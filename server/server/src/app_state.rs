@@ -1,6 +1,7 @@
 use std::sync::Arc;
 
 use core::{
+    config::AuthConfig,
     core::{scheduler::SchedulerHandle, storage::Storage},
     model::repository::db::DbPool,
 };
@@ -9,6 +10,7 @@ pub struct AppState {
     pub pool: DbPool,
     pub storage: Storage,
     pub scheduler: SchedulerHandle,
+    pub auth: AuthConfig,
 }
 
 pub type SharedState = Arc<AppState>;
@@ -0,0 +1,368 @@
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use eyre::{eyre, Context, Result};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// Archives are written front to back without seeking, so they can be streamed to the
+/// client while they are created. Entries are stored uncompressed since photos and videos
+/// are compressed already.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+}
+
+const TAR_BLOCK_SIZE: usize = 512;
+const ZIP_VERSION: u16 = 45; // 4.5: zip64
+const ZIP_FLAGS: u16 = 0x0808; // data descriptor follows the file data, file names are UTF-8
+const COPY_BUF_SIZE: usize = 64 * 1024;
+
+pub struct ArchiveWriter<W> {
+    out: W,
+    format: ArchiveFormat,
+    /// bytes written so far
+    offset: u64,
+    zip_entries: Vec<ZipEntry>,
+}
+
+/// What the zip central directory needs to know about an entry
+struct ZipEntry {
+    name: Vec<u8>,
+    crc: u32,
+    size: u64,
+    local_header_offset: u64,
+    dos_time: u16,
+    dos_date: u16,
+}
+
+impl<W: AsyncWrite + Unpin> ArchiveWriter<W> {
+    pub fn new(out: W, format: ArchiveFormat) -> ArchiveWriter<W> {
+        ArchiveWriter {
+            out,
+            format,
+            offset: 0,
+            zip_entries: Vec::default(),
+        }
+    }
+
+    /// `size` must be the exact number of bytes `read` returns, the archive is broken
+    /// (and an error returned) if the file changes while it is being read.
+    pub async fn add_file(
+        &mut self,
+        name: &str,
+        modified: DateTime<Utc>,
+        size: u64,
+        read: impl AsyncRead + Unpin,
+    ) -> Result<()> {
+        match self.format {
+            ArchiveFormat::Zip => self.add_zip_file(name, modified, size, read).await,
+            ArchiveFormat::Tar => self.add_tar_file(name, modified, size, read).await,
+        }
+    }
+
+    pub async fn add_bytes(
+        &mut self,
+        name: &str,
+        modified: DateTime<Utc>,
+        data: &[u8],
+    ) -> Result<()> {
+        self.add_file(name, modified, data.len() as u64, data).await
+    }
+
+    /// Writes the trailer of the archive and flushes it
+    pub async fn finish(mut self) -> Result<W> {
+        match self.format {
+            ArchiveFormat::Zip => self.write_zip_central_directory().await?,
+            ArchiveFormat::Tar => self.write(&[0; 2 * TAR_BLOCK_SIZE]).await?,
+        }
+        self.out.flush().await.wrap_err("error flushing archive")?;
+        Ok(self.out)
+    }
+
+    async fn write(&mut self, buf: &[u8]) -> Result<()> {
+        self.out
+            .write_all(buf)
+            .await
+            .wrap_err("error writing archive")?;
+        self.offset += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Copies exactly `size` bytes, returning their CRC-32
+    async fn copy_file_data(&mut self, size: u64, read: impl AsyncRead + Unpin) -> Result<u32> {
+        let mut read = read.take(size);
+        let mut buf = vec![0u8; COPY_BUF_SIZE];
+        let mut hasher = crc32fast::Hasher::new();
+        let mut copied: u64 = 0;
+        loop {
+            let n = read
+                .read(&mut buf)
+                .await
+                .wrap_err("error reading file for archive")?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+            self.write(&buf[..n]).await?;
+            copied += n as u64;
+        }
+        if copied != size {
+            return Err(eyre!(
+                "file is shorter than expected ({} instead of {} bytes)",
+                copied,
+                size
+            ));
+        }
+        Ok(hasher.finalize())
+    }
+
+    async fn add_tar_file(
+        &mut self,
+        name: &str,
+        modified: DateTime<Utc>,
+        size: u64,
+        read: impl AsyncRead + Unpin,
+    ) -> Result<()> {
+        let mtime = modified.timestamp().max(0) as u64;
+        if name.len() > 100 {
+            // the header only has room for 100 bytes, longer names go into a pax extended header
+            let record = pax_record("path", name);
+            let header = tar_header(b"././@PaxHeader", b'x', record.len() as u64, mtime);
+            self.write(&header).await?;
+            self.write(&record).await?;
+            self.write(&tar_padding(record.len() as u64)).await?;
+        }
+        let truncated = truncate_utf8(name, 100);
+        let header = tar_header(truncated.as_bytes(), b'0', size, mtime);
+        self.write(&header).await?;
+        self.copy_file_data(size, read).await?;
+        self.write(&tar_padding(size)).await?;
+        Ok(())
+    }
+
+    async fn add_zip_file(
+        &mut self,
+        name: &str,
+        modified: DateTime<Utc>,
+        size: u64,
+        read: impl AsyncRead + Unpin,
+    ) -> Result<()> {
+        let local_header_offset = self.offset;
+        let zip64 = size >= u32::MAX as u64;
+        let (dos_time, dos_date) = dos_date_time(modified);
+        let mut header = Vec::with_capacity(30 + name.len() + 20);
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        header.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // stored
+        header.extend_from_slice(&dos_time.to_le_bytes());
+        header.extend_from_slice(&dos_date.to_le_bytes());
+        // crc and sizes are in the data descriptor
+        header.extend_from_slice(&0u32.to_le_bytes());
+        let size_placeholder: u32 = if zip64 { u32::MAX } else { 0 };
+        header.extend_from_slice(&size_placeholder.to_le_bytes());
+        header.extend_from_slice(&size_placeholder.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        let extra_len: u16 = if zip64 { 20 } else { 0 };
+        header.extend_from_slice(&extra_len.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        if zip64 {
+            header.extend_from_slice(&1u16.to_le_bytes());
+            header.extend_from_slice(&16u16.to_le_bytes());
+            header.extend_from_slice(&0u64.to_le_bytes());
+            header.extend_from_slice(&0u64.to_le_bytes());
+        }
+        self.write(&header).await?;
+        let crc = self.copy_file_data(size, read).await?;
+
+        let mut descriptor = Vec::with_capacity(24);
+        descriptor.extend_from_slice(&0x08074b50u32.to_le_bytes());
+        descriptor.extend_from_slice(&crc.to_le_bytes());
+        if zip64 {
+            descriptor.extend_from_slice(&size.to_le_bytes());
+            descriptor.extend_from_slice(&size.to_le_bytes());
+        } else {
+            descriptor.extend_from_slice(&(size as u32).to_le_bytes());
+            descriptor.extend_from_slice(&(size as u32).to_le_bytes());
+        }
+        self.write(&descriptor).await?;
+        self.zip_entries.push(ZipEntry {
+            name: name.as_bytes().to_vec(),
+            crc,
+            size,
+            local_header_offset,
+            dos_time,
+            dos_date,
+        });
+        Ok(())
+    }
+
+    async fn write_zip_central_directory(&mut self) -> Result<()> {
+        let central_directory_offset = self.offset;
+        let entries = std::mem::take(&mut self.zip_entries);
+        for entry in &entries {
+            let size_too_large = entry.size >= u32::MAX as u64;
+            let offset_too_large = entry.local_header_offset >= u32::MAX as u64;
+            // zip64 extra field, only with the values that don't fit
+            let mut extra = Vec::new();
+            if size_too_large {
+                extra.extend_from_slice(&entry.size.to_le_bytes());
+                extra.extend_from_slice(&entry.size.to_le_bytes());
+            }
+            if offset_too_large {
+                extra.extend_from_slice(&entry.local_header_offset.to_le_bytes());
+            }
+            let mut header = Vec::with_capacity(46 + entry.name.len() + 4 + extra.len());
+            header.extend_from_slice(&0x02014b50u32.to_le_bytes());
+            // made by unix, so that the file mode in the external attributes is used
+            header.extend_from_slice(&(ZIP_VERSION | (3 << 8)).to_le_bytes());
+            header.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+            header.extend_from_slice(&ZIP_FLAGS.to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes());
+            header.extend_from_slice(&entry.dos_time.to_le_bytes());
+            header.extend_from_slice(&entry.dos_date.to_le_bytes());
+            header.extend_from_slice(&entry.crc.to_le_bytes());
+            let size = if size_too_large {
+                u32::MAX
+            } else {
+                entry.size as u32
+            };
+            header.extend_from_slice(&size.to_le_bytes());
+            header.extend_from_slice(&size.to_le_bytes());
+            header.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            let extra_len = if extra.is_empty() {
+                0
+            } else {
+                4 + extra.len() as u16
+            };
+            header.extend_from_slice(&extra_len.to_le_bytes());
+            header.extend_from_slice(&0u16.to_le_bytes()); // comment
+            header.extend_from_slice(&0u16.to_le_bytes()); // disk
+            header.extend_from_slice(&0u16.to_le_bytes()); // internal attributes
+            header.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+            let offset = if offset_too_large {
+                u32::MAX
+            } else {
+                entry.local_header_offset as u32
+            };
+            header.extend_from_slice(&offset.to_le_bytes());
+            header.extend_from_slice(&entry.name);
+            if !extra.is_empty() {
+                header.extend_from_slice(&1u16.to_le_bytes());
+                header.extend_from_slice(&(extra.len() as u16).to_le_bytes());
+                header.extend_from_slice(&extra);
+            }
+            self.write(&header).await?;
+        }
+        let central_directory_size = self.offset - central_directory_offset;
+        let num_entries = entries.len() as u64;
+        let needs_zip64 = num_entries >= u16::MAX as u64
+            || central_directory_offset >= u32::MAX as u64
+            || central_directory_size >= u32::MAX as u64;
+        let mut end = Vec::new();
+        if needs_zip64 {
+            let zip64_end_offset = self.offset;
+            end.extend_from_slice(&0x06064b50u32.to_le_bytes());
+            end.extend_from_slice(&44u64.to_le_bytes()); // size of the rest of this record
+            end.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+            end.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+            end.extend_from_slice(&0u32.to_le_bytes());
+            end.extend_from_slice(&0u32.to_le_bytes());
+            end.extend_from_slice(&num_entries.to_le_bytes());
+            end.extend_from_slice(&num_entries.to_le_bytes());
+            end.extend_from_slice(&central_directory_size.to_le_bytes());
+            end.extend_from_slice(&central_directory_offset.to_le_bytes());
+            // zip64 end of central directory locator
+            end.extend_from_slice(&0x07064b50u32.to_le_bytes());
+            end.extend_from_slice(&0u32.to_le_bytes());
+            end.extend_from_slice(&zip64_end_offset.to_le_bytes());
+            end.extend_from_slice(&1u32.to_le_bytes());
+        }
+        end.extend_from_slice(&0x06054b50u32.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        end.extend_from_slice(&0u16.to_le_bytes());
+        let num_entries16 = num_entries.min(u16::MAX as u64) as u16;
+        end.extend_from_slice(&num_entries16.to_le_bytes());
+        end.extend_from_slice(&num_entries16.to_le_bytes());
+        end.extend_from_slice(&(central_directory_size.min(u32::MAX as u64) as u32).to_le_bytes());
+        end.extend_from_slice(
+            &(central_directory_offset.min(u32::MAX as u64) as u32).to_le_bytes(),
+        );
+        end.extend_from_slice(&0u16.to_le_bytes()); // comment
+        self.write(&end).await
+    }
+}
+
+/// ustar header for a regular file or pax extended header
+fn tar_header(name: &[u8], typeflag: u8, size: u64, mtime: u64) -> [u8; TAR_BLOCK_SIZE] {
+    let mut header = [0u8; TAR_BLOCK_SIZE];
+    header[..name.len()].copy_from_slice(name);
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    if size < 0o77777777777 {
+        write_octal(&mut header[124..136], size);
+    } else {
+        // base-256 for files of 8GiB and larger
+        header[124] = 0x80;
+        header[128..136].copy_from_slice(&size.to_be_bytes());
+    }
+    write_octal(&mut header[136..148], mtime);
+    header[156] = typeflag;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    // the checksum is calculated with the checksum field set to spaces
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|b| *b as u32).sum();
+    write_octal(&mut header[148..155], checksum as u64);
+    header[155] = b' ';
+    header
+}
+
+/// Zero padded octal number followed by a NUL byte, filling `field`
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let formatted = format!("{:0width$o}", value, width = digits);
+    field[..digits].copy_from_slice(&formatted.as_bytes()[formatted.len() - digits..]);
+    field[digits] = 0;
+}
+
+fn tar_padding(size: u64) -> Vec<u8> {
+    let rem = (size % TAR_BLOCK_SIZE as u64) as usize;
+    if rem == 0 {
+        Vec::new()
+    } else {
+        vec![0; TAR_BLOCK_SIZE - rem]
+    }
+}
+
+/// "<length> <key>=<value>\n", where length includes itself
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let rest = format!(" {}={}\n", key, value);
+    let mut len = rest.len() + 1;
+    while (len.to_string().len() + rest.len()) != len {
+        len = len.to_string().len() + rest.len();
+    }
+    format!("{}{}", len, rest).into_bytes()
+}
+
+fn truncate_utf8(s: &str, max_len: usize) -> &str {
+    if s.len() <= max_len {
+        return s;
+    }
+    let mut end = max_len;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    &s[..end]
+}
+
+/// MS-DOS time and date, which can't represent anything before 1980
+fn dos_date_time(datetime: DateTime<Utc>) -> (u16, u16) {
+    if datetime.year() < 1980 {
+        return (0, (1 << 5) | 1);
+    }
+    let time = (datetime.hour() << 11) | (datetime.minute() << 5) | (datetime.second() / 2);
+    let date =
+        ((datetime.year() as u32 - 1980).min(127) << 9) | (datetime.month() << 5) | datetime.day();
+    (time as u16, date as u16)
+}
@@ -0,0 +1,285 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{
+        header::{AUTHORIZATION, COOKIE},
+        HeaderMap, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use eyre::{eyre, Context, Result};
+use ipnet::IpNet;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+
+use core::{
+    config::AuthConfig,
+    deadpool_diesel, interact,
+    model::{self, repository},
+};
+
+use crate::{app_state::SharedState, http_error::HttpError};
+
+pub const SESSION_COOKIE: &str = "myrti_session";
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// The logged in user, available to handlers behind the `require_user` middleware
+/// as `Extension<CurrentUser>`
+#[derive(Debug, Clone)]
+pub struct CurrentUser {
+    pub user: model::User,
+    /// Session or API token the request was authenticated with,
+    /// None for requests from a trusted network
+    pub token: Option<model::UserToken>,
+}
+
+impl CurrentUser {
+    pub fn is_admin(&self) -> bool {
+        self.user.role == model::UserRole::Admin
+    }
+
+    /// The session token, if the request was made with one
+    pub fn session(&self) -> Option<&model::UserToken> {
+        self.token
+            .as_ref()
+            .filter(|token| token.kind == model::UserTokenKind::Session)
+    }
+}
+
+/// Rejects requests without a valid session cookie or API token,
+/// unless they come from a trusted network
+pub async fn require_user(
+    State(app_state): State<SharedState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let found = match request_token(request.headers()) {
+        Some(token) => user_for_token(&app_state, &token).await,
+        None => match trusted_user_name(&request, &app_state.auth) {
+            Some(name) => user_for_trusted_network(&app_state, name).await,
+            None => return unauthorized(),
+        },
+    };
+    match found {
+        Ok(Some(current_user)) => {
+            request.extensions_mut().insert(current_user);
+            next.run(request).await
+        }
+        Ok(None) => unauthorized(),
+        Err(err) => HttpError::from(err).into_response(),
+    }
+}
+
+async fn user_for_token(app_state: &SharedState, token: &str) -> Result<Option<CurrentUser>> {
+    let token_hash = hash_token(token);
+    let conn = app_state.pool.get().await?;
+    let found = interact!(conn, move |conn| {
+        repository::user::find_user_for_token(conn, &token_hash, chrono::Utc::now())
+    })
+    .await??;
+    Ok(found.map(|(user, token)| CurrentUser {
+        user,
+        token: Some(token),
+    }))
+}
+
+async fn user_for_trusted_network(
+    app_state: &SharedState,
+    name: String,
+) -> Result<Option<CurrentUser>> {
+    let conn = app_state.pool.get().await?;
+    let user = interact!(conn, move |conn| {
+        let user = repository::user::find_user_by_name(conn, &name)?;
+        if user.is_none() {
+            tracing::warn!(name, "trusted_user does not exist, requiring login");
+        }
+        Ok(user)
+    })
+    .await??;
+    Ok(user.map(|user| CurrentUser { user, token: None }))
+}
+
+/// The configured trusted_user if the request comes from a trusted network
+pub(crate) fn trusted_user_name(request: &Request, config: &AuthConfig) -> Option<String> {
+    let trusted_user = config.trusted_user.as_ref()?;
+    let client_ip = client_ip(request, &config.trusted_proxies)?;
+    config
+        .trusted_networks
+        .iter()
+        .any(|network| network.contains(&client_ip))
+        .then(|| trusted_user.clone())
+}
+
+/// Address of the client that made the request. For requests from trusted proxies
+/// this is the first address in X-Forwarded-For from the right that is not itself
+/// a trusted proxy, since every proxy appends the address it got the request from.
+pub(crate) fn client_ip(request: &Request, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let ConnectInfo(peer) = request.extensions().get::<ConnectInfo<SocketAddr>>()?;
+    let is_trusted_proxy = |ip: &IpAddr| trusted_proxies.iter().any(|network| network.contains(ip));
+    let mut client_ip = peer.ip().to_canonical();
+    let forwarded_for: Vec<&str> = request
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+    for forwarded in forwarded_for.into_iter().rev() {
+        if !is_trusted_proxy(&client_ip) {
+            break;
+        }
+        // don't guess if a proxy sent garbage
+        client_ip = forwarded.trim().parse::<IpAddr>().ok()?.to_canonical();
+    }
+    Some(client_ip)
+}
+
+/// Must be layered inside of `require_user`
+pub async fn require_admin(
+    Extension(current_user): Extension<CurrentUser>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !current_user.is_admin() {
+        return (
+            StatusCode::FORBIDDEN,
+            HttpError::from(eyre!("only admins can do this")),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        HttpError::from(eyre!("not logged in")),
+    )
+        .into_response()
+}
+
+/// API clients send `Authorization: Bearer <token>`, browsers the session cookie
+fn request_token(headers: &HeaderMap) -> Option<String> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return Some(token.trim().to_owned());
+    }
+    cookie_value(headers, SESSION_COOKIE)
+}
+
+pub fn cookie_value(headers: &HeaderMap, cookie_name: &str) -> Option<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|cookie| cookie.trim().split_once('='))
+        .find(|(name, _)| *name == cookie_name)
+        .map(|(_, value)| value.to_owned())
+}
+
+/// Set-Cookie header value for a new session
+pub fn session_cookie(token: &str, config: &AuthConfig) -> String {
+    let secure = if config.secure_cookies {
+        "; Secure"
+    } else {
+        ""
+    };
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}{}",
+        SESSION_COOKIE,
+        token,
+        config.session_duration.as_secs(),
+        secure
+    )
+}
+
+/// Set-Cookie header value that removes the session cookie
+pub fn clear_session_cookie() -> String {
+    format!(
+        "{}=; Path=/; HttpOnly; SameSite=Lax; Max-Age=0",
+        SESSION_COOKIE
+    )
+}
+
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| eyre!("error hashing password: {}", err))?;
+    Ok(hash.to_string())
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> Result<bool> {
+    let parsed = PasswordHash::new(password_hash)
+        .map_err(|err| eyre!("invalid password hash in database: {}", err))?;
+    match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+        Ok(()) => Ok(true),
+        Err(argon2::password_hash::Error::Password) => Ok(false),
+        Err(err) => Err(eyre!("error verifying password: {}", err)),
+    }
+}
+
+/// A new random session or API token, hex encoded
+pub fn new_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Only hashes of tokens are stored, a plain hash is enough since tokens are random
+pub fn hash_token(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+/// Same rules for the CLI and the API
+pub fn validate_user_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.trim() != name {
+        return Err(eyre!(
+            "user name can not be empty or start or end with spaces"
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_password(password: &str) -> Result<()> {
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(eyre!(
+            "password must be at least {} characters long",
+            MIN_PASSWORD_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+/// Creates a user after checking that the name is valid and not taken
+pub async fn create_user(
+    pool: &model::repository::db::DbPool,
+    name: String,
+    password: &str,
+    role: model::UserRole,
+) -> Result<model::UserId> {
+    validate_user_name(&name)?;
+    validate_password(password)?;
+    let password = password.to_owned();
+    let password_hash = tokio::task::spawn_blocking(move || hash_password(&password)).await??;
+    let conn = pool.get().await?;
+    interact!(conn, move |conn| {
+        if repository::user::user_with_name_exists(conn, &name)? {
+            return Err(eyre!("a user named '{}' already exists", name));
+        }
+        repository::user::create_user(conn, &name, &password_hash, role)
+    })
+    .await?
+    .wrap_err("error creating user")
+}
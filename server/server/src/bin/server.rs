@@ -3,25 +3,31 @@ use std::{
     sync::Arc,
 };
 
-use axum::{http::Method, Router};
+use axum::{
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderValue, Method,
+    },
+    middleware, Router,
+};
 use camino::{Utf8Path as Path, Utf8PathBuf as PathBuf};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use eyre::{self, Context, Result};
 use myrti::{
     app_state::{AppState, SharedState},
-    routes,
+    auth, routes,
     spa_serve_dir::SpaServeDirService,
 };
 use tokio::{signal, sync::oneshot};
 use tower::ServiceBuilder;
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::CorsLayer,
     request_id::MakeRequestUuid,
     services::ServeDir,
     trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
     ServiceBuilderExt,
 };
-use tracing::info;
+use tracing::{info, warn};
 use tracing_error::ErrorLayer;
 use tracing_subscriber::{prelude::*, EnvFilter};
 
@@ -29,7 +35,7 @@ use core::{
     config::Config,
     core::{
         scheduler::{SchedulerHandle, SchedulerMessage},
-        storage::{LocalFileStorage, Storage},
+        storage::{LocalFileStorage, S3Storage, Storage},
     },
     deadpool_diesel, interact,
     model::{
@@ -37,7 +43,7 @@ use core::{
             self,
             db::{self, DbPool},
         },
-        AssetRootDir, AssetRootDirId,
+        AssetRootDir, AssetRootDirId, UserRole,
     },
 };
 
@@ -59,6 +65,20 @@ struct Cli {
     #[cfg(feature = "opentelemetry")]
     #[arg(long)]
     otel_endpoint: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an admin user and exit, the password is asked for interactively
+    CreateAdmin {
+        name: String,
+        /// Read the password from the first line of stdin instead
+        #[arg(long, default_value_t = false)]
+        password_stdin: bool,
+    },
 }
 
 async fn db_setup(dir: &Path) -> Result<DbPool> {
@@ -69,6 +89,26 @@ async fn db_setup(dir: &Path) -> Result<DbPool> {
     Ok(pool)
 }
 
+async fn create_admin(pool: &DbPool, name: String, password_stdin: bool) -> Result<()> {
+    let password = if password_stdin {
+        let mut line = String::new();
+        std::io::stdin()
+            .read_line(&mut line)
+            .wrap_err("error reading password from stdin")?;
+        line.trim_end_matches(['\r', '\n']).to_owned()
+    } else {
+        let password = rpassword::prompt_password("Password: ")?;
+        let repeated = rpassword::prompt_password("Repeat password: ")?;
+        if password != repeated {
+            return Err(eyre::eyre!("passwords do not match"));
+        }
+        password
+    };
+    auth::create_user(pool, name.clone(), &password, UserRole::Admin).await?;
+    info!("Created admin user '{}'", name);
+    Ok(())
+}
+
 async fn store_asset_roots_from_config(
     config_dir: &Path,
     config: &Config,
@@ -162,6 +202,21 @@ async fn main() -> Result<()> {
         .parent()
         .expect("has read config file, so parent must be a directory");
 
+    if let Some(Command::CreateAdmin {
+        name,
+        password_stdin,
+    }) = args.command
+    {
+        let data_dir_path = if config.data_dir.path.is_absolute() {
+            config.data_dir.path.clone()
+        } else {
+            config_dir.join(&config.data_dir.path)
+        };
+        std::fs::create_dir_all(&data_dir_path).wrap_err("error creating data dir")?;
+        let pool = db_setup(&data_dir_path).await?;
+        return create_admin(&pool, name, password_stdin).await;
+    }
+
     if !args.skip_startup_check {
         tracing::info!("Running self check");
         core::startup_self_check::run_self_check(config.bin_paths.as_ref())
@@ -190,12 +245,37 @@ async fn main() -> Result<()> {
     let pool = db_setup(&data_dir_path).await.unwrap();
     store_asset_roots_from_config(config_dir, &config, &pool).await?;
     std::fs::create_dir_all(&storage_path).unwrap();
-    let storage: Storage = LocalFileStorage::new(storage_path).into();
+    let storage: Storage = match &config.s3_storage {
+        Some(s3_config) => {
+            info!(bucket = s3_config.bucket, "Using S3 storage");
+            S3Storage::new(s3_config, data_dir_path.join("s3_scratch"))?.into()
+        }
+        None => LocalFileStorage::new(storage_path).into(),
+    };
+    let auth_config = config.auth.clone();
+    if let Some(trusted_user) = auth_config.trusted_user.clone() {
+        info!(
+            networks = ?auth_config.trusted_networks,
+            "Requests from trusted networks are logged in as {}", trusted_user
+        );
+        let conn = pool.get().await?;
+        let exists = interact!(conn, move |conn| {
+            repository::user::user_with_name_exists(conn, &trusted_user)
+        })
+        .await??;
+        if !exists {
+            warn!(
+                "trusted_user does not exist, requests from trusted networks will have to log in"
+            );
+        }
+    }
+    let cors_allowed_origins = config.cors_allowed_origins.clone();
     let (scheduler_did_shutdown_send, scheduler_did_shutdown_recv) = oneshot::channel();
     let scheduler = SchedulerHandle::new(
         pool.clone(),
         storage.clone(),
         config,
+        data_dir_path.join("geodata.db"),
         scheduler_did_shutdown_send,
     );
 
@@ -222,22 +302,36 @@ async fn main() -> Result<()> {
         pool: pool.clone(),
         storage,
         scheduler: scheduler.clone(),
+        auth: auth_config,
     });
-    let cors = CorsLayer::new()
-        // allow `GET` and `POST` when accessing the resource
-        .allow_methods([Method::GET, Method::POST])
-        // allow requests from any origin
-        .allow_origin(Any);
-    let app = Router::new()
-        .nest("/api/timeline", routes::timeline::router())
-        .nest("/api/albums", routes::album::router())
-        .nest("/api/assets", routes::asset::router())
-        .nest("/api/photoSeries", routes::photo_series::router())
-        .nest("/api/assetRoots", routes::asset_roots::router())
-        .nest("/api/dash", routes::dash::router())
-        .nest("/api/timelinegroups", routes::timeline_group::router())
-        .nest("/api/jobs", routes::jobs::router())
-        .nest("/api", routes::api_router())
+    let admin_api = Router::new()
+        .nest("/jobs", routes::jobs::router())
+        .nest("/failures", routes::failures::router())
+        .nest("/users", routes::user::router())
+        .nest("/assets", routes::asset::admin_router())
+        .merge(routes::api_router())
+        .route_layer(middleware::from_fn(auth::require_admin));
+    let api = Router::new()
+        .nest("/timeline", routes::timeline::router())
+        .nest("/albums", routes::album::router())
+        .nest("/assets", routes::asset::router())
+        .nest("/photoSeries", routes::photo_series::router())
+        .nest("/assetRoots", routes::asset_roots::router())
+        .nest("/dash", routes::dash::router())
+        .nest("/download", routes::download::router())
+        .nest("/timelinegroups", routes::timeline_group::router())
+        .nest("/auth", routes::auth::router())
+        .nest("/shares", routes::share::router())
+        .nest("/search", routes::search::router())
+        .merge(admin_api)
+        .route_layer(middleware::from_fn_with_state(
+            shared_state.clone(),
+            auth::require_user,
+        ));
+    let mut app = Router::new()
+        .nest("/api/auth", routes::auth::public_router())
+        .nest("/api/share", routes::share::public_router())
+        .nest("/api", api)
         .fallback_service(SpaServeDirService::new(ServeDir::new("./static")))
         .layer(
             ServiceBuilder::new()
@@ -247,18 +341,42 @@ async fn main() -> Result<()> {
                         .make_span_with(DefaultMakeSpan::new().include_headers(true))
                         .on_response(DefaultOnResponse::new().include_headers(true)),
                 ),
-        )
-        .layer(cors)
-        .with_state(shared_state);
+        );
+    // the web app is served by us, so cross-origin requests are only needed for
+    // other frontends
+    if !cors_allowed_origins.is_empty() {
+        let origins = cors_allowed_origins
+            .iter()
+            .map(|origin| HeaderValue::from_str(origin))
+            .collect::<Result<Vec<_>, _>>()
+            .wrap_err("invalid origin in cors_allowed_origins")?;
+        let cors = CorsLayer::new()
+            .allow_methods([
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ])
+            .allow_headers([AUTHORIZATION, CONTENT_TYPE])
+            .allow_credentials(true)
+            .allow_origin(origins);
+        app = app.layer(cors);
+    }
+    let app = app.with_state(shared_state);
     // .route("/api/assets", get(get_assets))
     // .route("/api/assetRoots", get(get_asset_roots))
     let listener = tokio::net::TcpListener::bind(SocketAddr::new(addr, port))
         .await
         .wrap_err("Error binding socket")?;
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    // the client address is needed for trusted networks
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
     info!("Shutting down...");
     scheduler
         .send
@@ -0,0 +1,359 @@
+use std::{
+    borrow::Cow,
+    hash::{Hash, Hasher},
+    ops::Range,
+};
+
+use axum::{
+    body::Body,
+    http::{
+        header::{
+            ACCEPT_RANGES, CACHE_CONTROL, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE,
+            CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+        },
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+};
+use camino::Utf8Path as Path;
+use chrono::{DateTime, Utc};
+use eyre::{eyre, Context};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+
+use core::core::storage::{Storage, StorageProvider, StorageReadError};
+
+use crate::http_error::{ApiResult, HttpError};
+
+/// How long clients may cache a response without asking again
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheControl {
+    /// The URL always refers to the same content,
+    /// e.g. because it contains the id of a file that is never modified
+    Immutable,
+    /// The content behind the URL can change, clients have to revalidate
+    /// with the ETag or modification time before using their cached copy
+    Revalidate,
+}
+
+/// Responds with a file from storage or the local filesystem, supporting
+/// single byte ranges and conditional requests.
+#[derive(Debug, Clone)]
+pub struct FileResponse {
+    content_type: Option<Cow<'static, str>>,
+    content_disposition: Option<HeaderValue>,
+    /// Overrides the ETag derived from the file's size and modification time
+    etag: Option<String>,
+    cache_control: CacheControl,
+}
+
+#[derive(Debug, Clone)]
+pub struct FileMeta {
+    pub size: u64,
+    pub last_modified: DateTime<Utc>,
+    pub etag: String,
+}
+
+impl FileResponse {
+    pub fn new(content_type: Option<Cow<'static, str>>) -> FileResponse {
+        FileResponse {
+            content_type,
+            content_disposition: None,
+            etag: None,
+            cache_control: CacheControl::Revalidate,
+        }
+    }
+
+    pub fn cache_control(mut self, cache_control: CacheControl) -> FileResponse {
+        self.cache_control = cache_control;
+        self
+    }
+
+    /// Use the file's content hash as ETag. The hash must belong to the file as it is now.
+    pub fn etag_from_hash(mut self, hash: u64) -> FileResponse {
+        self.etag = Some(format!("\"{:016x}\"", hash));
+        self
+    }
+
+    /// `Content-Disposition: attachment` if `download` is true, `inline` otherwise
+    pub fn content_disposition(
+        mut self,
+        file_name: &[u8],
+        download: bool,
+    ) -> eyre::Result<FileResponse> {
+        let mut value = match download {
+            true => b"attachment; filename=\"".to_vec(),
+            false => b"inline; filename=\"".to_vec(),
+        };
+        value.extend_from_slice(file_name);
+        value.push(b'"');
+        self.content_disposition = Some(
+            HeaderValue::from_bytes(&value).wrap_err("error setting content-disposition header")?,
+        );
+        Ok(self)
+    }
+
+    /// 404 if `key` does not exist
+    #[tracing::instrument(skip(self, storage, request_headers))]
+    pub async fn from_storage(
+        self,
+        storage: &Storage,
+        key: &str,
+        request_headers: &HeaderMap,
+    ) -> ApiResult<Response> {
+        let object = match storage.metadata(key).await {
+            Ok(object) => object,
+            Err(StorageReadError::FileNotFound(_)) => return Ok(not_found()),
+            Err(err) => {
+                return Err(eyre::Report::from(err)
+                    .wrap_err("error getting object metadata")
+                    .into())
+            }
+        };
+        let meta = FileMeta {
+            size: object.size,
+            last_modified: object.last_modified,
+            etag: self
+                .etag
+                .clone()
+                .unwrap_or_else(|| derived_etag(key, object.size, object.last_modified)),
+        };
+        let range = match self.check_request(&meta, request_headers) {
+            Ok(range) => range,
+            Err(response) => return Ok(*response),
+        };
+        let read = match &range {
+            Some(range) => storage.open_read_range(key, range.clone()).await,
+            None => storage.open_read_stream(key).await,
+        };
+        let read = match read {
+            Ok(read) => read,
+            // deleted in the meantime
+            Err(StorageReadError::FileNotFound(_)) => return Ok(not_found()),
+            Err(err) => {
+                return Err(eyre::Report::from(err)
+                    .wrap_err("could not open object for reading")
+                    .into())
+            }
+        };
+        self.respond(&meta, range, read)
+    }
+
+    /// 404 if `path` does not exist
+    #[tracing::instrument(skip(self, request_headers))]
+    pub async fn from_local_file(
+        self,
+        path: &Path,
+        request_headers: &HeaderMap,
+    ) -> ApiResult<Response> {
+        let mut file = match tokio::fs::File::open(path).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(not_found()),
+            Err(err) => {
+                return Err(eyre::Report::from(err)
+                    .wrap_err("error opening file")
+                    .into())
+            }
+        };
+        let file_meta = file
+            .metadata()
+            .await
+            .wrap_err("error getting file metadata")?;
+        let last_modified: DateTime<Utc> = file_meta
+            .modified()
+            .wrap_err("error getting file modification time")?
+            .into();
+        let meta = FileMeta {
+            size: file_meta.len(),
+            last_modified,
+            etag: self
+                .etag
+                .clone()
+                .unwrap_or_else(|| derived_etag(path.as_str(), file_meta.len(), last_modified)),
+        };
+        let range = match self.check_request(&meta, request_headers) {
+            Ok(range) => range,
+            Err(response) => return Ok(*response),
+        };
+        let read: Box<dyn AsyncRead + Send + Unpin> = match &range {
+            Some(range) => {
+                file.seek(std::io::SeekFrom::Start(range.start)).await?;
+                Box::new(file.take(range.end - range.start))
+            }
+            None => Box::new(file),
+        };
+        self.respond(&meta, range, read)
+    }
+
+    /// Returns the requested range, or the response if there is no need to send
+    /// the file (not modified or invalid range)
+    pub fn check_request(
+        &self,
+        meta: &FileMeta,
+        request_headers: &HeaderMap,
+    ) -> Result<Option<Range<u64>>, Box<Response>> {
+        let not_modified = match request_headers.get(IF_NONE_MATCH) {
+            Some(if_none_match) => etag_matches(if_none_match, &meta.etag),
+            // If-Modified-Since is only considered without If-None-Match
+            None => request_headers
+                .get(IF_MODIFIED_SINCE)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+                .map(|since| meta.last_modified.timestamp() <= since.timestamp())
+                .unwrap_or(false),
+        };
+        if not_modified {
+            let mut response = StatusCode::NOT_MODIFIED.into_response();
+            self.insert_cache_headers(response.headers_mut(), meta);
+            return Err(Box::new(response));
+        }
+
+        // a Range request for an older version of the file gets the whole current file
+        let range_header = match request_headers.get(IF_RANGE) {
+            Some(if_range) if if_range.as_bytes() != meta.etag.as_bytes() => None,
+            _ => request_headers.get(RANGE),
+        };
+        match range_header.and_then(|range| parse_range(range, meta.size)) {
+            Some(Ok(range)) => Ok(Some(range)),
+            Some(Err(())) => Err(Box::new(
+                (
+                    StatusCode::RANGE_NOT_SATISFIABLE,
+                    [(CONTENT_RANGE, format!("bytes */{}", meta.size))],
+                )
+                    .into_response(),
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn respond(
+        &self,
+        meta: &FileMeta,
+        range: Option<Range<u64>>,
+        read: Box<dyn AsyncRead + Send + Unpin>,
+    ) -> ApiResult<Response> {
+        let body = Body::from_stream(ReaderStream::new(read));
+        let mut response = body.into_response();
+        let headers = response.headers_mut();
+        self.insert_cache_headers(headers, meta);
+        headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        if let Some(content_type) = &self.content_type {
+            headers.insert(
+                CONTENT_TYPE,
+                HeaderValue::from_str(content_type)
+                    .wrap_err("error setting content-type header")?,
+            );
+        }
+        if let Some(content_disposition) = &self.content_disposition {
+            headers.insert(CONTENT_DISPOSITION, content_disposition.clone());
+        }
+        match range {
+            Some(range) => {
+                *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+                let headers = response.headers_mut();
+                headers.insert(CONTENT_LENGTH, (range.end - range.start).into());
+                headers.insert(
+                    CONTENT_RANGE,
+                    HeaderValue::from_str(&format!(
+                        "bytes {}-{}/{}",
+                        range.start,
+                        range.end - 1,
+                        meta.size
+                    ))
+                    .wrap_err("error setting content-range header")?,
+                );
+            }
+            None => {
+                headers.insert(CONTENT_LENGTH, meta.size.into());
+            }
+        }
+        Ok(response)
+    }
+
+    fn insert_cache_headers(&self, headers: &mut HeaderMap, meta: &FileMeta) {
+        if let Ok(etag) = HeaderValue::from_str(&meta.etag) {
+            headers.insert(ETAG, etag);
+        }
+        let last_modified = meta
+            .last_modified
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string();
+        if let Ok(last_modified) = HeaderValue::from_str(&last_modified) {
+            headers.insert(LAST_MODIFIED, last_modified);
+        }
+        let cache_control = match self.cache_control {
+            CacheControl::Immutable => "public, max-age=31536000, immutable",
+            CacheControl::Revalidate => "no-cache",
+        };
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static(cache_control));
+    }
+}
+
+fn not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        HttpError::from(eyre!("no such file")),
+    )
+        .into_response()
+}
+
+/// Files in storage are only ever replaced as a whole, so together with the key
+/// size and modification time identify a version
+fn derived_etag(key: &str, size: u64, last_modified: DateTime<Utc>) -> String {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    key.hash(&mut hasher);
+    format!(
+        "\"{:x}-{:x}-{:x}\"",
+        hasher.finish(),
+        last_modified.timestamp_millis(),
+        size
+    )
+}
+
+fn etag_matches(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(if_none_match) = if_none_match.to_str() else {
+        return false;
+    };
+    if_none_match.trim() == "*"
+        || if_none_match
+            .split(',')
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == etag)
+}
+
+/// Only single byte ranges are supported, which is all video players request.
+/// Returns None if the header should be ignored and the whole file sent,
+/// and Err if the range can not be satisfied.
+pub fn parse_range(header: &HeaderValue, size: u64) -> Option<Result<Range<u64>, ()>> {
+    let spec = header.to_str().ok()?.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    let range = if start.is_empty() {
+        // last n bytes
+        let suffix_len: u64 = end.parse().ok()?;
+        if suffix_len == 0 || size == 0 {
+            return Some(Err(()));
+        }
+        size.saturating_sub(suffix_len)..size
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            size
+        } else {
+            let end: u64 = end.parse().ok()?;
+            if end < start {
+                return None;
+            }
+            // end is inclusive and may be past the end of the file
+            end.saturating_add(1).min(size)
+        };
+        if start >= size {
+            return Some(Err(()));
+        }
+        start..end
+    };
+    Some(Ok(range))
+}
@@ -1,7 +1,13 @@
 pub mod app_state;
+mod archive;
+pub mod auth;
+mod file_response;
 pub mod http_error;
 mod mime_type;
 pub mod openapi;
 pub mod routes;
 mod schema;
 pub mod spa_serve_dir;
+
+#[cfg(test)]
+mod test;
@@ -3,6 +3,7 @@ use std::borrow::Cow;
 pub fn guess_mime_type(file_ext: &str) -> Option<Cow<'static, str>> {
     match file_ext {
         "mp4" => Some(Cow::Borrowed("video/mp4")),
+        "mpd" => Some(Cow::Borrowed("application/dash+xml")),
         "avif" => Some(Cow::Borrowed("image/avif")),
         "webp" => Some(Cow::Borrowed("image/webp")),
         "jpg" | "jpeg" => Some(Cow::Borrowed("image/jpeg")),
@@ -1,28 +1,29 @@
 use axum::{
     extract::{Path, State},
-    http::{header::CONTENT_TYPE, StatusCode},
-    response::{IntoResponse, Response},
-    routing::{get, post, put},
-    Json, Router,
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+    routing::{delete, get, patch, post, put},
+    Extension, Json, Router,
 };
-use axum_extra::body::AsyncReadBody;
 use eyre::{eyre, Context, Result};
 use serde::{Deserialize, Serialize};
 use utoipa::{IntoParams, ToSchema};
 
 use core::{
     catalog::storage_key,
-    core::storage::{StorageProvider, StorageReadError},
+    core::storage::StorageProvider,
     deadpool_diesel, interact,
     model::{self, repository},
 };
 
 use crate::{
     app_state::SharedState,
+    auth::CurrentUser,
+    file_response::FileResponse,
     http_error::{ApiResult, HttpError},
     schema::{
         asset::{AssetSpe, AssetWithSpe, Image, Video},
-        Album, AlbumId, AlbumItemId, AssetId,
+        Album, AlbumId, AlbumItemId, AssetId, SmartAlbumFilter,
     },
 };
 
@@ -34,16 +35,26 @@ pub fn router() -> Router<SharedState> {
         .route("/", post(create_album))
         .route("/:id/assets", put(append_assets_to_album))
         .route("/:id", get(get_album_details))
+        .route("/:id", patch(update_album))
+        .route("/:id", delete(delete_album))
         .route("/:id/thumbnail/:size/:format", get(get_album_thumbnail))
         .route("/:id/deleteItems", post(delete_album_items))
+        .route("/:id/moveItems", post(move_album_items))
+        .route("/:id/insertItems", post(insert_album_items))
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, ToSchema, IntoParams)]
+#[derive(Debug, Clone, PartialEq, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateAlbumRequest {
     pub name: String,
     pub description: Option<String>,
     pub assets: Vec<AssetId>,
+    /// Private albums are only visible to the user creating them
+    #[serde(default)]
+    pub is_private: bool,
+    /// Creates a smart album containing the assets matching the filter,
+    /// `assets` must be empty then
+    pub smart_filter: Option<SmartAlbumFilter>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, ToSchema)]
@@ -52,16 +63,85 @@ pub struct CreateAlbumResponse {
     pub album_id: i64,
 }
 
+/// Albums that don't exist and private albums of other users are both 404
+async fn is_album_visible(
+    app_state: &SharedState,
+    album_id: model::AlbumId,
+    current_user: &CurrentUser,
+) -> ApiResult<bool> {
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let visible = interact!(conn, move |conn| {
+        repository::album::is_album_visible(conn, album_id, user_id)
+    })
+    .await??;
+    Ok(visible)
+}
+
+fn album_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        HttpError::from(eyre!("no such album")),
+    )
+        .into_response()
+}
+
+/// Only the owner of an album or an admin can change it
+async fn may_change_album(
+    app_state: &SharedState,
+    album_id: model::AlbumId,
+    current_user: &CurrentUser,
+) -> ApiResult<bool> {
+    let conn = app_state.pool.get().await?;
+    let album = interact!(conn, move |conn| {
+        repository::album::get_album(conn, album_id)
+    })
+    .await??;
+    // albums created before there were users belong to everyone
+    let is_owner = album.owner_id.is_none() || album.owner_id == Some(current_user.user.id);
+    Ok(is_owner || current_user.is_admin())
+}
+
+fn not_album_owner() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        HttpError::from(eyre!("only the owner can change an album")),
+    )
+        .into_response()
+}
+
+async fn is_smart_album(app_state: &SharedState, album_id: model::AlbumId) -> ApiResult<bool> {
+    let conn = app_state.pool.get().await?;
+    let album = interact!(conn, move |conn| {
+        repository::album::get_album(conn, album_id)
+    })
+    .await??;
+    Ok(album.smart_filter.is_some())
+}
+
+/// Items of smart albums follow their filter
+fn smart_album_items_conflict() -> Response {
+    (
+        StatusCode::CONFLICT,
+        HttpError::from(eyre!("items of smart albums can not be edited")),
+    )
+        .into_response()
+}
+
 #[utoipa::path(
     get,
     path = "/api/albums",
     responses((status = 200, body=Vec<Album>)),
 )]
 #[tracing::instrument(fields(request = true), skip(app_state))]
-pub async fn get_all_albums(State(app_state): State<SharedState>) -> ApiResult<Json<Vec<Album>>> {
+pub async fn get_all_albums(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Json<Vec<Album>>> {
+    let user_id = current_user.user.id;
     let conn = app_state.pool.get().await?;
     let albums: Vec<Album> = interact!(conn, move |conn| {
-        repository::album::get_all_albums_with_asset_count(conn)
+        repository::album::get_all_albums_with_asset_count(conn, user_id)
     })
     .await??
     .into_iter()
@@ -79,14 +159,21 @@ pub async fn get_all_albums(State(app_state): State<SharedState>) -> ApiResult<J
 #[tracing::instrument(fields(request = true), skip(app_state))]
 pub async fn create_album(
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
     Json(request): Json<CreateAlbumRequest>,
 ) -> ApiResult<Json<CreateAlbumResponse>> {
     if request.name.is_empty() {
         return Err(eyre!("name can not be empty").into());
     }
+    if request.smart_filter.is_some() && !request.assets.is_empty() {
+        return Err(eyre!("smart albums can not be created with assets").into());
+    }
     let create_album = repository::album::CreateAlbum {
         name: Some(request.name),
         description: request.description,
+        owner_id: Some(current_user.user.id),
+        is_private: request.is_private,
+        smart_filter: request.smart_filter.map(|f| f.try_into()).transpose()?,
     };
     let asset_ids: Vec<model::AssetId> = request
         .assets
@@ -125,12 +212,17 @@ pub struct AlbumDetailsResponse {
     pub items: Vec<AlbumItem>,
     pub name: Option<String>,
     pub description: Option<String>,
+    /// Set for smart albums
+    pub smart_filter: Option<SmartAlbumFilter>,
 }
 
 #[utoipa::path(
     get,
     path = "/api/albums/{id}",
-    responses((status = 200, body=AlbumDetailsResponse)),
+    responses(
+        (status = 200, body=AlbumDetailsResponse),
+        (status = NOT_FOUND, description = "No such album or not visible to the user")
+    ),
     params(
         ("id"=String, description="Album id")
     )
@@ -139,11 +231,16 @@ pub struct AlbumDetailsResponse {
 pub async fn get_album_details(
     Path(album_id): Path<AlbumId>,
     State(app_state): State<SharedState>,
-) -> ApiResult<Json<AlbumDetailsResponse>> {
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
     let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
+    let user_id = current_user.user.id;
     let conn = app_state.pool.get().await?;
     let items = interact!(conn, move |conn| {
-        repository::album::get_items_in_album(conn, album_id)
+        repository::album::get_items_in_album(conn, album_id, user_id)
     })
     .await??;
     let items: Vec<_> = items
@@ -175,8 +272,10 @@ pub async fn get_album_details(
     Ok(Json(AlbumDetailsResponse {
         name: album.name,
         description: album.description,
+        smart_filter: album.smart_filter.as_ref().map(SmartAlbumFilter::from),
         items,
-    }))
+    })
+    .into_response())
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -195,7 +294,12 @@ pub struct AppendAssetsRequest {
     put,
     path = "/api/albums/{id}/assets",
     request_body = AppendAssetsRequest,
-    responses((status = 200, body=AppendAssetsResponse)),
+    responses(
+        (status = 200, body=AppendAssetsResponse),
+        (status = FORBIDDEN, description = "Album belongs to another user"),
+        (status = NOT_FOUND, description = "No such album or not visible to the user"),
+        (status = CONFLICT, description = "Album is a smart album")
+    ),
     params(
         ("id"=String, description="Album id")
     )
@@ -204,9 +308,19 @@ pub struct AppendAssetsRequest {
 pub async fn append_assets_to_album(
     Path(album_id): Path<AlbumId>,
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
     Json(req): Json<AppendAssetsRequest>,
-) -> ApiResult<Json<AppendAssetsResponse>> {
+) -> ApiResult<Response> {
     let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
+    if !may_change_album(&app_state, album_id, &current_user).await? {
+        return Ok(not_album_owner());
+    }
+    if is_smart_album(&app_state, album_id).await? {
+        return Ok(smart_album_items_conflict());
+    }
     let asset_ids: Vec<_> = req
         .asset_ids
         .into_iter()
@@ -217,7 +331,7 @@ pub async fn append_assets_to_album(
         })
         .collect::<Result<_>>()?;
     if asset_ids.is_empty() {
-        return Ok(Json(AppendAssetsResponse { success: true }));
+        return Ok(Json(AppendAssetsResponse { success: true }).into_response());
     }
     let conn = app_state.pool.get().await?;
     let append_result = interact!(conn, move |conn| {
@@ -229,13 +343,14 @@ pub async fn append_assets_to_album(
         tracing::warn!(?err);
         Err(err.into())
     } else {
-        Ok(Json(AppendAssetsResponse { success: true }))
+        Ok(Json(AppendAssetsResponse { success: true }).into_response())
     }
 }
 
 #[utoipa::path(get, path = "/api/albums/{id}/thumbnail/{size}/{format}",
 responses(
-    (status = 200, body=String, content_type = "application/octet")
+    (status = 200, body=String, content_type = "application/octet"),
+    (status = NOT_FOUND, description = "No such album or not visible to the user")
         ),
     params(
         ("id" = String, Path, description = "AlbumId to get thumbnail for"),
@@ -246,34 +361,25 @@ responses(
 pub async fn get_album_thumbnail(
     Path((album_id, _size, format)): Path<(AlbumId, String, ThumbnailFormat)>,
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    headers: HeaderMap,
 ) -> ApiResult<Response> {
     let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
     // TODO dedupe this, same thing is required for asset thumbnails and image reprs
     let (format, content_type) = match format {
         ThumbnailFormat::Avif => (model::ThumbnailFormat::Avif, "image/avif"),
         ThumbnailFormat::Webp => (model::ThumbnailFormat::Webp, "image/webp"),
     };
     let file_key = storage_key::album_thumbnail(album_id, format);
-    let read = app_state.storage.open_read_stream(&file_key).await;
-    let read = match read {
-        Err(err) => match err {
-            StorageReadError::FileNotFound(_) => {
-                return Ok((
-                    StatusCode::NOT_FOUND,
-                    HttpError::from(eyre!("no such object")),
-                )
-                    .into_response());
-            }
-            _ => {
-                return Err(eyre!("could not open object for reading").into());
-            }
-        },
-        Ok(r) => r,
-    };
-    let headers = [(CONTENT_TYPE, content_type)];
-    let body = AsyncReadBody::new(read);
-    // TODO add size hint for files https://github.com/tokio-rs/axum/discussions/2074
-    Ok((headers, body).into_response())
+    if let Some(url) = app_state.storage.presigned_url(&file_key).await? {
+        return Ok(Redirect::temporary(&url).into_response());
+    }
+    FileResponse::new(Some(content_type.into()))
+        .from_storage(&app_state.storage, &file_key, &headers)
+        .await
 }
 
 #[derive(Debug, Clone, Deserialize, ToSchema, IntoParams)]
@@ -286,7 +392,12 @@ pub struct DeleteAlbumItemRequest {
     post,
     path = "/api/albums/{id}/deleteItems",
     request_body = DeleteAlbumItemRequest,
-    responses((status = 200, body=())),
+    responses(
+        (status = 200, body=()),
+        (status = FORBIDDEN, description = "Album belongs to another user"),
+        (status = NOT_FOUND, description = "No such album or not visible to the user"),
+        (status = CONFLICT, description = "Album is a smart album")
+    ),
     params(
         ("id"=String, description="Album id")
     )
@@ -295,20 +406,258 @@ pub struct DeleteAlbumItemRequest {
 pub async fn delete_album_items(
     Path(album_id): Path<AlbumId>,
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
     Json(req): Json<DeleteAlbumItemRequest>,
-) -> ApiResult<()> {
-    let conn = app_state.pool.get().await?;
-    let item_ids: Vec<model::AlbumItemId> = req
-        .item_ids
-        .into_iter()
-        .map(|id| Ok(model::AlbumItemId(id.0.parse()?)))
-        .collect::<Result<Vec<_>>>()
-        .wrap_err("bad item ids")?;
+) -> ApiResult<Response> {
+    let item_ids = parse_item_ids(req.item_ids)?;
     let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
+    if !may_change_album(&app_state, album_id, &current_user).await? {
+        return Ok(not_album_owner());
+    }
+    if is_smart_album(&app_state, album_id).await? {
+        return Ok(smart_album_items_conflict());
+    }
+    let conn = app_state.pool.get().await?;
     interact!(conn, move |conn| {
         repository::album::remove_items_from_album(conn, album_id, &item_ids)
             .wrap_err("error removing items from album")
     })
     .await??;
-    Ok(())
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateAlbumRequest {
+    pub name: Option<String>,
+    /// An empty description removes it
+    pub description: Option<String>,
+    /// Only for smart albums
+    pub smart_filter: Option<SmartAlbumFilter>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/albums/{id}",
+    request_body = UpdateAlbumRequest,
+    responses(
+        (status = 200, description = "Album was updated"),
+        (status = BAD_REQUEST, description = "smartFilter was given for a normal album"),
+        (status = FORBIDDEN, description = "Album belongs to another user"),
+        (status = NOT_FOUND, description = "No such album or not visible to the user")
+    ),
+    params(
+        ("id"=String, description="Album id")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn update_album(
+    Path(album_id): Path<AlbumId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(req): Json<UpdateAlbumRequest>,
+) -> ApiResult<Response> {
+    if req.name.as_ref().is_some_and(|name| name.is_empty()) {
+        return Err(eyre!("name can not be empty").into());
+    }
+    let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
+    if !may_change_album(&app_state, album_id, &current_user).await? {
+        return Ok(not_album_owner());
+    }
+    if req.smart_filter.is_some() && !is_smart_album(&app_state, album_id).await? {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            HttpError::from(eyre!("not a smart album")),
+        )
+            .into_response());
+    }
+    let update = repository::album::UpdateAlbum {
+        name: req.name,
+        description: req
+            .description
+            .map(|description| Some(description).filter(|d| !d.is_empty())),
+        smart_filter: req.smart_filter.map(|f| f.try_into()).transpose()?,
+    };
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::album::update_album(conn, album_id, update)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Only the owner of the album or an admin can delete it
+#[utoipa::path(
+    delete,
+    path = "/api/albums/{id}",
+    responses(
+        (status = 200, description = "Album was deleted"),
+        (status = FORBIDDEN, description = "Album belongs to another user"),
+        (status = NOT_FOUND, description = "No such album or not visible to the user")
+    ),
+    params(
+        ("id"=String, description="Album id")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn delete_album(
+    Path(album_id): Path<AlbumId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
+    if !may_change_album(&app_state, album_id, &current_user).await? {
+        return Ok(not_album_owner());
+    }
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::album::delete_album(conn, album_id)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct MoveAlbumItemsRequest {
+    /// Moved to `position` in this order
+    pub item_ids: Vec<AlbumItemId>,
+    /// Index in the album without the moved items, larger values move them to the end
+    pub position: usize,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/albums/{id}/moveItems",
+    request_body = MoveAlbumItemsRequest,
+    responses(
+        (status = 200, description = "Items were moved"),
+        (status = FORBIDDEN, description = "Album belongs to another user"),
+        (status = NOT_FOUND, description = "No such album or not visible to the user"),
+        (status = CONFLICT, description = "Album is a smart album")
+    ),
+    params(
+        ("id"=String, description="Album id")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn move_album_items(
+    Path(album_id): Path<AlbumId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(req): Json<MoveAlbumItemsRequest>,
+) -> ApiResult<Response> {
+    let item_ids = parse_item_ids(req.item_ids)?;
+    let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
+    if !may_change_album(&app_state, album_id, &current_user).await? {
+        return Ok(not_album_owner());
+    }
+    if is_smart_album(&app_state, album_id).await? {
+        return Ok(smart_album_items_conflict());
+    }
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::album::move_album_items(conn, album_id, &item_ids, req.position)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "itemType", rename_all = "camelCase")]
+pub enum InsertAlbumItem {
+    #[serde(rename_all = "camelCase")]
+    Asset {
+        asset_id: AssetId,
+    },
+    Text {
+        text: String,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertAlbumItemsRequest {
+    pub items: Vec<InsertAlbumItem>,
+    /// Index of the first inserted item, appended to the end if not given
+    pub position: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct InsertAlbumItemsResponse {
+    pub item_ids: Vec<AlbumItemId>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/albums/{id}/insertItems",
+    request_body = InsertAlbumItemsRequest,
+    responses(
+        (status = 200, body = InsertAlbumItemsResponse),
+        (status = FORBIDDEN, description = "Album belongs to another user"),
+        (status = NOT_FOUND, description = "No such album or not visible to the user"),
+        (status = CONFLICT, description = "Album is a smart album")
+    ),
+    params(
+        ("id"=String, description="Album id")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn insert_album_items(
+    Path(album_id): Path<AlbumId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(req): Json<InsertAlbumItemsRequest>,
+) -> ApiResult<Response> {
+    let items: Vec<repository::album::AddItemToAlbum> = req
+        .items
+        .into_iter()
+        .map(|item| match item {
+            InsertAlbumItem::Asset { asset_id } => Ok(repository::album::AddItemToAlbum::Asset(
+                asset_id.try_into()?,
+            )),
+            InsertAlbumItem::Text { text } => Ok(repository::album::AddItemToAlbum::Text(text)),
+        })
+        .collect::<Result<Vec<_>>>()
+        .wrap_err("bad items")?;
+    let album_id: model::AlbumId = album_id.try_into()?;
+    if !is_album_visible(&app_state, album_id, &current_user).await? {
+        return Ok(album_not_found());
+    }
+    if !may_change_album(&app_state, album_id, &current_user).await? {
+        return Ok(not_album_owner());
+    }
+    if is_smart_album(&app_state, album_id).await? {
+        return Ok(smart_album_items_conflict());
+    }
+    let conn = app_state.pool.get().await?;
+    let item_ids = interact!(conn, move |conn| {
+        repository::album::insert_items_into_album(conn, album_id, &items, req.position)
+    })
+    .await??;
+    Ok(Json(InsertAlbumItemsResponse {
+        item_ids: item_ids.into_iter().map(|id| id.into()).collect(),
+    })
+    .into_response())
+}
+
+fn parse_item_ids(item_ids: Vec<AlbumItemId>) -> Result<Vec<model::AlbumItemId>> {
+    item_ids
+        .into_iter()
+        .map(|id| Ok(model::AlbumItemId(id.0.parse()?)))
+        .collect::<Result<Vec<_>>>()
+        .wrap_err("bad item ids")
 }
@@ -1,34 +1,35 @@
-use std::{collections::HashMap, ffi::OsString, ops::Deref, os::unix::prelude::OsStrExt};
+use std::collections::HashMap;
 
 use axum::{
-    body::Body,
     extract::{Path, Query, State},
-    http::{
-        header::{self, CONTENT_TYPE},
-        HeaderMap, HeaderValue, StatusCode,
-    },
-    response::{IntoResponse, Response},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
-use axum_extra::body::AsyncReadBody;
+use chrono::{DateTime, Utc};
 use eyre::{eyre, Context, Result};
 use serde::{Deserialize, Serialize};
-use tokio_util::io::ReaderStream;
-use utoipa::ToSchema;
+use utoipa::{IntoParams, ToSchema};
 
 use core::{
     catalog::storage_key,
-    core::storage::{StorageProvider, StorageReadError},
+    core::storage::StorageProvider,
     deadpool_diesel, interact,
     model::{self, repository},
 };
 
 use crate::{
     app_state::SharedState,
+    auth::CurrentUser,
+    file_response::{CacheControl, FileResponse},
     http_error::{ApiResult, HttpError},
     mime_type::{guess_mime_type, guess_mime_type_path},
-    schema::{asset::Asset, AssetId, ImageRepresentationId},
+    schema::{
+        asset::{Asset, AssetPlace, NearDuplicateGroup},
+        duplicate_asset::{AssetDuplicates, AssetFilePath, DuplicateAsset, DuplicatesReport},
+        AssetId, DuplicateAssetId, ImageRepresentationId,
+    },
 };
 
 pub fn router() -> Router<SharedState> {
@@ -39,7 +40,12 @@ pub fn router() -> Router<SharedState> {
         .route("/thumbnail/:id/:size/:format", get(get_thumbnail))
         .route("/original/:id", get(get_asset_file))
         .route("/timeline", get(super::timeline::get_timeline))
+        .route("/hidden", get(get_hidden_assets))
         .route("/hidden", post(set_assets_hidden))
+        .route("/archived", get(get_archived_assets))
+        .route("/archived", post(set_assets_archived))
+        .route("/nearDuplicates", get(get_near_duplicates))
+        .route("/:id/duplicates", get(get_asset_duplicates))
         .route(
             "/repr/:asset_id/:repr_id",
             get(get_image_asset_representation),
@@ -47,6 +53,16 @@ pub fn router() -> Router<SharedState> {
         .route("/:id/rotation", post(set_asset_rotation_correction))
 }
 
+/// Routes that expose or change files across all asset roots, admins only
+pub fn admin_router() -> Router<SharedState> {
+    Router::new()
+        .route("/duplicates", get(get_duplicates_report))
+        .route(
+            "/:id/duplicates/:duplicate_id/makePrimary",
+            post(make_duplicate_primary),
+        )
+}
+
 #[utoipa::path(get, path = "/api/assets",
     responses(
         (status = 200, body=[Asset])
@@ -83,6 +99,9 @@ async fn get_asset(
 #[serde(rename_all = "camelCase")]
 pub struct AssetDetailsResponse {
     pub exiftool_output: serde_json::Value,
+    /// Not set until the asset's GPS coordinates have been geocoded,
+    /// or if it has none or they are not inside any country
+    pub place: Option<AssetPlace>,
 }
 
 #[utoipa::path(get, path = "/api/assets/{id}/details",
@@ -100,8 +119,10 @@ async fn get_asset_details(
 ) -> ApiResult<Json<AssetDetailsResponse>> {
     let asset_id: model::AssetId = asset_id.try_into()?;
     let conn = app_state.pool.get().await?;
-    let exiftool_output = interact!(conn, move |conn| {
-        repository::asset::get_asset_exiftool_output(conn, asset_id)
+    let (exiftool_output, place) = interact!(conn, move |conn| {
+        let exiftool_output = repository::asset::get_asset_exiftool_output(conn, asset_id)?;
+        let place = repository::asset_place::get_asset_place(conn, asset_id)?;
+        Ok::<_, eyre::Report>((exiftool_output, place))
     })
     .await??;
     let json = match serde_json::from_slice(&exiftool_output)
@@ -117,6 +138,7 @@ async fn get_asset_details(
     }?;
     Ok(Json(AssetDetailsResponse {
         exiftool_output: json,
+        place: place.map(AssetPlace::from),
     }))
 }
 
@@ -145,9 +167,10 @@ pub enum ThumbnailFormat {
     )
 )]
 #[tracing::instrument(fields(request = true), skip(app_state))]
-async fn get_thumbnail(
+pub async fn get_thumbnail(
     Path((asset_id, size, format)): Path<(AssetId, ThumbnailSize, ThumbnailFormat)>,
     State(app_state): State<SharedState>,
+    headers: HeaderMap,
 ) -> ApiResult<Response> {
     let asset_id: model::AssetId = asset_id.try_into()?;
     let (thumb_key, content_type) = match (size, format) {
@@ -184,26 +207,12 @@ async fn get_thumbnail(
             "image/webp",
         ),
     };
-    let read = app_state.storage.open_read_stream(&thumb_key).await;
-    let read = match read {
-        Err(err) => match err {
-            StorageReadError::FileNotFound(_) => {
-                return Ok((
-                    StatusCode::NOT_FOUND,
-                    HttpError::from(eyre!("no such object")),
-                )
-                    .into_response());
-            }
-            _ => {
-                return Err(eyre!("could not open object for reading").into());
-            }
-        },
-        Ok(r) => r,
-    };
-    let headers = [(CONTENT_TYPE, content_type)];
-    let body = AsyncReadBody::new(read);
-    // TODO add size hint for files https://github.com/tokio-rs/axum/discussions/2074
-    return Ok((headers, body).into_response());
+    if let Some(url) = app_state.storage.presigned_url(&thumb_key).await? {
+        return Ok(Redirect::temporary(&url).into_response());
+    }
+    FileResponse::new(Some(content_type.into()))
+        .from_storage(&app_state.storage, &thumb_key, &headers)
+        .await
 }
 
 #[utoipa::path(get, path = "/api/assets/original/{id}",
@@ -216,50 +225,47 @@ async fn get_thumbnail(
     )
 )]
 #[tracing::instrument(fields(request = true), skip(app_state))]
-async fn get_asset_file(
+pub async fn get_asset_file(
     Path(asset_id): Path<AssetId>,
     Query(query): Query<HashMap<String, String>>,
     State(app_state): State<SharedState>,
+    headers: HeaderMap,
 ) -> ApiResult<Response> {
     let id: model::AssetId = asset_id.try_into()?;
     let conn = app_state.pool.get().await?;
-    let path = interact!(conn, move |conn| {
-        repository::asset::get_asset_path_on_disk(conn, id)
+    let (asset_path, file_state) = interact!(conn, move |conn| {
+        let asset_path = repository::asset::find_asset_path_on_disk(conn, id)?;
+        let file_state = repository::asset::get_asset_file_state(conn, id)?;
+        Ok((asset_path, file_state))
     })
-    .await??
-    .path_on_disk();
-    let file = tokio::fs::File::open(&path).await?;
-    let stream = ReaderStream::new(file);
-    let body = Body::from_stream(stream);
+    .await??;
+    let Some(asset_path) = asset_path else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such asset")),
+        )
+            .into_response());
+    };
+    let path = asset_path.path_on_disk();
     let download = query
         .get("download")
         .map(|s| s.to_lowercase() == "true")
         .unwrap_or(false);
-    let mut headers = HeaderMap::new();
+    let mut response = FileResponse::new(guess_mime_type_path(&path));
     if let Some(file_name) = path.file_name() {
-        let mut s = match download {
-            true => OsString::from("attachment; filename=\""),
-            false => OsString::from("inline; filename=\""),
-        };
-        s.push(file_name);
-        s.push("\"");
-        headers.insert(
-            header::CONTENT_DISPOSITION,
-            HeaderValue::from_bytes(s.as_bytes())
-                .wrap_err("error setting content-disposition header")?,
-        );
+        response = response.content_disposition(file_name.as_bytes(), download)?;
     }
-    let content_type = guess_mime_type_path(&path);
-    if let Some(content_type) = content_type {
-        headers.insert(
-            header::CONTENT_TYPE,
-            content_type
-                .deref()
-                .try_into()
-                .wrap_err("error setting content-type header")?,
-        );
+    // the hash is only valid if the file has not been modified since it was indexed
+    if let (Some(file_state), Ok(file_meta)) = (file_state, tokio::fs::metadata(&path).await) {
+        let file_mtime: Option<DateTime<Utc>> = file_meta.modified().ok().map(Into::into);
+        let unchanged = file_state.file_size == Some(file_meta.len() as i64)
+            && file_state.file_mtime.map(|t| t.timestamp_millis())
+                == file_mtime.map(|t| t.timestamp_millis());
+        if let (true, Some(hash)) = (unchanged, file_state.hash) {
+            response = response.etag_from_hash(hash);
+        }
     }
-    Ok((headers, body).into_response())
+    response.from_local_file(&path, &headers).await
 }
 
 #[utoipa::path(get, path = "/api/assets/repr/{assetId}/{reprId}",
@@ -273,11 +279,13 @@ async fn get_asset_file(
     )
 )]
 #[tracing::instrument(fields(request = true), skip(app_state))]
-async fn get_image_asset_representation(
+pub async fn get_image_asset_representation(
     Path((asset_id, repr_id)): Path<(AssetId, ImageRepresentationId)>,
     Query(query): Query<HashMap<String, String>>,
     State(app_state): State<SharedState>,
+    headers: HeaderMap,
 ) -> ApiResult<Response> {
+    let asset_id: model::AssetId = asset_id.try_into()?;
     let repr_id: model::ImageRepresentationId = repr_id.try_into()?;
     // removing format name/file extension from storage key would make this query unnecessary but
     // it's nice to have for now
@@ -288,44 +296,24 @@ async fn get_image_asset_representation(
     })
     .await?
     .wrap_err("no such repr_id")?;
-    let storage_key = repr.file_key;
-    let read_stream = app_state
-        .storage
-        .open_read_stream(&storage_key)
-        .await
-        .wrap_err("error opening read stream")?;
-    let stream = ReaderStream::new(read_stream);
-    let body = Body::from_stream(stream);
+    if repr.asset_id != asset_id {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such repr_id for this asset")),
+        )
+            .into_response());
+    }
     let download = query
         .get("download")
         .map(|s| s.to_lowercase() == "true")
         .unwrap_or(false);
-    let mut headers = HeaderMap::new();
-
     let file_name = format!("{}.{}", repr.asset_id.0, &repr.format_name);
-    let mut s = match download {
-        true => OsString::from("attachment; filename=\""),
-        false => OsString::from("inline; filename=\""),
-    };
-    s.push(file_name);
-    s.push("\"");
-    headers.insert(
-        header::CONTENT_DISPOSITION,
-        HeaderValue::from_bytes(s.as_bytes())
-            .wrap_err("error setting content-disposition header")?,
-    );
-
-    let content_type = guess_mime_type(&repr.format_name);
-    if let Some(content_type) = content_type {
-        headers.insert(
-            header::CONTENT_TYPE,
-            content_type
-                .deref()
-                .try_into()
-                .wrap_err("error setting content-type header")?,
-        );
-    }
-    Ok((headers, body).into_response())
+    // representations are never modified, a new one gets a new id
+    FileResponse::new(guess_mime_type(&repr.format_name))
+        .cache_control(CacheControl::Immutable)
+        .content_disposition(file_name.as_bytes(), download)?
+        .from_storage(&app_state.storage, &repr.file_key, &headers)
+        .await
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
@@ -351,6 +339,7 @@ pub struct HideAssetsRequest {
 #[tracing::instrument(fields(request = true), skip(app_state))]
 async fn set_assets_hidden(
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
     Json(req): Json<HideAssetsRequest>,
 ) -> ApiResult<()> {
     let asset_ids: Vec<model::AssetId> = req
@@ -358,15 +347,261 @@ async fn set_assets_hidden(
         .into_iter()
         .map(model::AssetId::try_from)
         .collect::<Result<Vec<_>>>()?;
+    let user_id = current_user.user.id;
+    let set_hidden = req.what == HideAssetAction::Hide;
     let conn = app_state.pool.get().await?;
     interact!(conn, move |conn| {
-        repository::asset::set_assets_hidden(conn, true, &asset_ids)
+        repository::asset::set_assets_hidden(conn, user_id, set_hidden, &asset_ids)
     })
     .await?
     .wrap_err("error setting Assets hidden")?;
     Ok(())
 }
 
+#[utoipa::path(get, path = "/api/assets/hidden",
+    responses(
+        (status = 200, body=[Asset])
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+async fn get_hidden_assets(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Json<Vec<Asset>>> {
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let assets: Vec<Asset> = interact!(conn, move |conn| {
+        repository::asset::get_hidden_assets(conn, user_id)
+    })
+    .await??
+    .into_iter()
+    .map(|a| a.into())
+    .collect();
+    Ok(Json(assets))
+}
+
+/// Groups of visually similar Assets found by the last search for near duplicates
+#[utoipa::path(get, path = "/api/assets/nearDuplicates",
+    responses(
+        (status = 200, body=[NearDuplicateGroup])
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+async fn get_near_duplicates(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Json<Vec<NearDuplicateGroup>>> {
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let groups: Vec<NearDuplicateGroup> = interact!(conn, move |conn| {
+        repository::near_duplicate::get_near_duplicate_groups(conn, user_id)
+    })
+    .await??
+    .into_iter()
+    .map(|group| group.into())
+    .collect();
+    Ok(Json(groups))
+}
+
+const DEFAULT_DUPLICATES_REPORT_LIMIT: usize = 100;
+const MAX_DUPLICATES_REPORT_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatesReportRequest {
+    /// How many of the Assets with the most wasted bytes to return
+    pub limit: Option<usize>,
+}
+
+/// Files with the same content as an Asset's file, per asset root and per Asset
+#[utoipa::path(get, path = "/api/assets/duplicates",
+    params(DuplicatesReportRequest),
+    responses(
+        (status = 200, body = DuplicatesReport)
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+async fn get_duplicates_report(
+    State(app_state): State<SharedState>,
+    Query(request): Query<DuplicatesReportRequest>,
+) -> ApiResult<Json<DuplicatesReport>> {
+    let limit = request
+        .limit
+        .unwrap_or(DEFAULT_DUPLICATES_REPORT_LIMIT)
+        .clamp(1, MAX_DUPLICATES_REPORT_LIMIT);
+    let conn = app_state.pool.get().await?;
+    let report = interact!(conn, move |conn| {
+        repository::duplicate_asset::get_duplicates_report(conn, limit)
+    })
+    .await??;
+    Ok(Json(report.into()))
+}
+
+/// All known paths of the Asset's file
+#[utoipa::path(get, path = "/api/assets/{id}/duplicates",
+    responses(
+        (status = 200, body = AssetDuplicates),
+        (status = NOT_FOUND, description = "Asset not found")
+    ),
+    params(
+        ("id" = String, Path, description = "AssetId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+async fn get_asset_duplicates(
+    Path(asset_id): Path<AssetId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let asset_id: model::AssetId = asset_id.try_into()?;
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let result = interact!(conn, move |conn| {
+        if !repository::asset::is_asset_visible(conn, asset_id, user_id)? {
+            return Ok(None);
+        }
+        let asset = repository::asset::get_asset(conn, asset_id)?;
+        let duplicates = repository::duplicate_asset::get_duplicates_of_asset(conn, asset_id)?;
+        Ok(Some((asset, duplicates)))
+    })
+    .await??;
+    let Some((asset, duplicates)) = result else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such Asset")),
+        )
+            .into_response());
+    };
+    Ok(Json(AssetDuplicates {
+        primary: AssetFilePath {
+            asset_root_id: asset.base.root_dir_id.into(),
+            path_in_root: asset.base.file_path.to_string(),
+        },
+        duplicates: duplicates.into_iter().map(DuplicateAsset::from).collect(),
+    })
+    .into_response())
+}
+
+/// Serve the duplicate's file as the Asset's original, the current file becomes a duplicate
+#[utoipa::path(post, path = "/api/assets/{id}/duplicates/{duplicate_id}/makePrimary",
+    responses(
+        (status = 200),
+        (status = NOT_FOUND, description = "Asset has no such duplicate"),
+        (status = CONFLICT, description = "The duplicate's file does not exist anymore")
+    ),
+    params(
+        ("id" = String, Path, description = "AssetId"),
+        ("duplicate_id" = String, Path, description = "DuplicateAssetId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+async fn make_duplicate_primary(
+    Path((asset_id, duplicate_id)): Path<(AssetId, DuplicateAssetId)>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    let asset_id: model::AssetId = asset_id.try_into()?;
+    let duplicate_id: model::DuplicateAssetId = duplicate_id.try_into()?;
+    let conn = app_state.pool.get().await?;
+    let found = interact!(conn, move |conn| {
+        let Some(dup) = repository::duplicate_asset::find_duplicate_asset(conn, duplicate_id)?
+        else {
+            return Ok(None);
+        };
+        let root_dir = repository::asset_root_dir::get_asset_root(conn, dup.root_dir_id)?;
+        Ok(Some((dup, root_dir)))
+    })
+    .await??;
+    let (dup, root_dir) = match found {
+        Some((dup, root_dir)) if dup.asset_id == asset_id => (dup, root_dir),
+        _ => {
+            return Ok((
+                StatusCode::NOT_FOUND,
+                HttpError::from(eyre!("no such duplicate of the Asset")),
+            )
+                .into_response())
+        }
+    };
+    let exists = tokio::fs::try_exists(root_dir.path.join(&dup.file_path))
+        .await
+        .unwrap_or(false);
+    if !exists {
+        return Ok((
+            StatusCode::CONFLICT,
+            HttpError::from(eyre!("the duplicate's file does not exist")),
+        )
+            .into_response());
+    }
+    interact!(conn, move |conn| {
+        repository::duplicate_asset::make_duplicate_primary(conn, &dup)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveAssetAction {
+    Archive,
+    Unarchive,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchiveAssetsRequest {
+    pub what: ArchiveAssetAction,
+    pub asset_ids: Vec<AssetId>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/assets/archived",
+    request_body=ArchiveAssetsRequest,
+    responses((status=200)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+async fn set_assets_archived(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(req): Json<ArchiveAssetsRequest>,
+) -> ApiResult<()> {
+    let asset_ids: Vec<model::AssetId> = req
+        .asset_ids
+        .into_iter()
+        .map(model::AssetId::try_from)
+        .collect::<Result<Vec<_>>>()?;
+    let user_id = current_user.user.id;
+    let set_archived = req.what == ArchiveAssetAction::Archive;
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::asset::set_assets_archived(conn, user_id, set_archived, &asset_ids)
+    })
+    .await?
+    .wrap_err("error setting Assets archived")?;
+    Ok(())
+}
+
+#[utoipa::path(get, path = "/api/assets/archived",
+    responses(
+        (status = 200, body=[Asset])
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+async fn get_archived_assets(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Json<Vec<Asset>>> {
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let assets: Vec<Asset> = interact!(conn, move |conn| {
+        repository::asset::get_archived_assets(conn, user_id)
+    })
+    .await??
+    .into_iter()
+    .map(|a| a.into())
+    .collect();
+    Ok(Json(assets))
+}
+
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct SetAssetRotationRequest {
@@ -0,0 +1,326 @@
+use axum::{
+    extract::{Path, State},
+    http::{header::SET_COOKIE, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::Utc;
+use eyre::{eyre, Context};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use core::{
+    deadpool_diesel, interact,
+    model::{self, repository},
+};
+
+use crate::{
+    app_state::SharedState,
+    auth::{self, CurrentUser},
+    http_error::{ApiResult, HttpError},
+    schema::{
+        user::{ApiToken, User},
+        UserTokenId,
+    },
+};
+
+/// Routes that work without being logged in
+pub fn public_router() -> Router<SharedState> {
+    Router::new().route("/login", post(login))
+}
+
+pub fn router() -> Router<SharedState> {
+    Router::new()
+        .route("/logout", post(logout))
+        .route("/me", get(get_current_user))
+        .route("/password", post(change_password))
+        .route("/tokens", get(get_api_tokens))
+        .route("/tokens", post(create_api_token))
+        .route("/tokens/:id", delete(delete_api_token))
+}
+
+lazy_static! {
+    /// Checked against when the user does not exist, so that response times
+    /// don't tell which user names exist
+    static ref DUMMY_PASSWORD_HASH: String =
+        auth::hash_password("dummy password").expect("hashing password must work");
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginRequest {
+    pub name: String,
+    pub password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/login",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, body = User, description = "Logged in, the session cookie is set"),
+        (status = UNAUTHORIZED, description = "Wrong user name or password")
+    ),
+)]
+#[tracing::instrument(fields(request = true, name = request.name), skip(app_state, request))]
+pub async fn login(
+    State(app_state): State<SharedState>,
+    Json(request): Json<LoginRequest>,
+) -> ApiResult<Response> {
+    let conn = app_state.pool.get().await?;
+    let name = request.name.clone();
+    let found = interact!(conn, move |conn| {
+        repository::user::find_user_for_login(conn, &name)
+    })
+    .await??;
+    let (user, password_hash) = match found {
+        Some((user, password_hash)) => (Some(user), Some(password_hash)),
+        None => (None, None),
+    };
+    let password = request.password;
+    // the dummy hash is computed on first use, which is just as slow as verifying
+    let password_ok = tokio::task::spawn_blocking(move || {
+        let password_hash = password_hash
+            .as_deref()
+            .unwrap_or(DUMMY_PASSWORD_HASH.as_str());
+        auth::verify_password(&password, password_hash)
+    })
+    .await
+    .wrap_err("error verifying password")??;
+    let user = match (user, password_ok) {
+        (Some(user), true) => user,
+        _ => {
+            return Ok((
+                StatusCode::UNAUTHORIZED,
+                HttpError::from(eyre!("wrong user name or password")),
+            )
+                .into_response());
+        }
+    };
+
+    let token = auth::new_token();
+    let now = Utc::now();
+    let create_token = model::CreateUserToken {
+        user_id: user.id,
+        kind: model::UserTokenKind::Session,
+        name: None,
+        token_hash: auth::hash_token(&token),
+        expires_at: Some(
+            now + chrono::Duration::from_std(app_state.auth.session_duration)
+                .wrap_err("session duration out of range")?,
+        ),
+    };
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::user::delete_expired_user_tokens(conn, now)?;
+        repository::user::create_user_token(conn, &create_token)
+    })
+    .await??;
+    let cookie = auth::session_cookie(&token, &app_state.auth);
+    Ok(([(SET_COOKIE, cookie)], Json(User::from(&user))).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/logout",
+    responses((status = 200, description = "The session was ended")),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn logout(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    // logging out with an API token would delete it, which is probably not what the caller wants
+    if let Some(session) = current_user.session() {
+        let conn = app_state.pool.get().await?;
+        let user_id = current_user.user.id;
+        let token_id = session.id;
+        interact!(conn, move |conn| {
+            repository::user::delete_user_token(
+                conn,
+                user_id,
+                model::UserTokenKind::Session,
+                token_id,
+            )
+        })
+        .await??;
+    }
+    Ok([(SET_COOKIE, auth::clear_session_cookie())].into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/me",
+    responses((status = 200, body = User)),
+)]
+#[tracing::instrument(fields(request = true))]
+pub async fn get_current_user(Extension(current_user): Extension<CurrentUser>) -> Json<User> {
+    Json(User::from(&current_user.user))
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/password",
+    request_body = ChangePasswordRequest,
+    responses(
+        (status = 200, description = "Password changed, other sessions were logged out"),
+        (status = BAD_REQUEST, description = "The new password is not allowed"),
+        (status = FORBIDDEN, description = "The current password is wrong")
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, request))]
+pub async fn change_password(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<ChangePasswordRequest>,
+) -> ApiResult<Response> {
+    if let Err(err) = auth::validate_password(&request.new_password) {
+        return Ok((StatusCode::BAD_REQUEST, HttpError::from(err)).into_response());
+    }
+    let conn = app_state.pool.get().await?;
+    let name = current_user.user.name.clone();
+    let found = interact!(conn, move |conn| {
+        repository::user::find_user_for_login(conn, &name)
+    })
+    .await??;
+    let Some((_, password_hash)) = found else {
+        return Err(eyre!("logged in user does not exist").into());
+    };
+    let ChangePasswordRequest {
+        current_password,
+        new_password,
+    } = request;
+    let new_password_hash = tokio::task::spawn_blocking(move || {
+        if !auth::verify_password(&current_password, &password_hash)? {
+            return Ok(None);
+        }
+        auth::hash_password(&new_password).map(Some)
+    })
+    .await
+    .wrap_err("error hashing password")??;
+    let Some(new_password_hash) = new_password_hash else {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            HttpError::from(eyre!("wrong password")),
+        )
+            .into_response());
+    };
+    let conn = app_state.pool.get().await?;
+    let user_id = current_user.user.id;
+    // keep the session that changed the password
+    let keep = current_user.session().map(|session| session.id);
+    interact!(conn, move |conn| {
+        repository::user::set_user_password(conn, user_id, &new_password_hash)?;
+        repository::user::delete_user_sessions(conn, user_id, keep)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/auth/tokens",
+    responses((status = 200, body = Vec<ApiToken>)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn get_api_tokens(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Json<Vec<ApiToken>>> {
+    let conn = app_state.pool.get().await?;
+    let user_id = current_user.user.id;
+    let tokens = interact!(conn, move |conn| {
+        repository::user::get_user_tokens(conn, user_id, model::UserTokenKind::Api)
+    })
+    .await??;
+    Ok(Json(tokens.iter().map(ApiToken::from).collect()))
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateApiTokenResponse {
+    pub api_token: ApiToken,
+    /// Send as `Authorization: Bearer <token>`. Only returned here, it can not be retrieved later.
+    pub token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/auth/tokens",
+    request_body = CreateApiTokenRequest,
+    responses((status = 200, body = CreateApiTokenResponse)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn create_api_token(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<CreateApiTokenRequest>,
+) -> ApiResult<Json<CreateApiTokenResponse>> {
+    let token = auth::new_token();
+    let create_token = model::CreateUserToken {
+        user_id: current_user.user.id,
+        kind: model::UserTokenKind::Api,
+        name: Some(request.name),
+        token_hash: auth::hash_token(&token),
+        expires_at: None,
+    };
+    let conn = app_state.pool.get().await?;
+    let api_token = interact!(conn, move |conn| {
+        let token_id = repository::user::create_user_token(conn, &create_token)?;
+        repository::user::get_user_token(conn, token_id)
+    })
+    .await??;
+    Ok(Json(CreateApiTokenResponse {
+        api_token: ApiToken::from(&api_token),
+        token,
+    }))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/auth/tokens/{id}",
+    responses(
+        (status = 200, description = "Token was deleted"),
+        (status = NOT_FOUND, description = "No such token")
+    ),
+    params(
+        ("id" = String, Path, description = "UserTokenId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn delete_api_token(
+    Path(id): Path<UserTokenId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let token_id: model::UserTokenId = id.try_into()?;
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let deleted = interact!(conn, move |conn| {
+        repository::user::delete_user_token(conn, user_id, model::UserTokenKind::Api, token_id)
+    })
+    .await??;
+    if !deleted {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such token")),
+        )
+            .into_response());
+    }
+    Ok(StatusCode::OK.into_response())
+}
@@ -1,57 +1,69 @@
 use axum::{
-    body::Body,
-    extract::{Path, Request, State},
-    response::{IntoResponse, Response},
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Redirect, Response},
     routing::get,
     Router,
 };
-use eyre::Context;
+use eyre::eyre;
 use serde::Deserialize;
-use tower::ServiceExt;
-use tracing::Instrument;
 
 use core::{catalog::storage_key, core::storage::StorageProvider, model};
 
-use crate::{app_state::SharedState, http_error::ApiResult, schema::AssetId};
+use crate::{
+    app_state::SharedState,
+    file_response::FileResponse,
+    http_error::{ApiResult, HttpError},
+    mime_type::guess_mime_type_path,
+    schema::AssetId,
+};
 
 pub fn router() -> Router<SharedState> {
     Router::new().route("/:id/*path", get(get_dash_file).options(get_dash_file))
 }
 
 #[derive(Debug, Clone, Deserialize)]
-struct DashFilePath {
+pub struct DashFilePath {
     pub id: AssetId,
     pub path: String,
 }
 
 #[tracing::instrument(fields(request = true), skip(app_state))]
-async fn get_dash_file(
+pub async fn get_dash_file(
     Path(path): Path<DashFilePath>,
     State(app_state): State<SharedState>,
-    request: Request<Body>,
+    headers: HeaderMap,
 ) -> ApiResult<Response> {
     let asset_id: model::AssetId = path.id.try_into()?;
+    if !is_plain_relative_path(&path.path) {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such DASH file")),
+        )
+            .into_response());
+    }
 
     let storage_key = storage_key::dash_file(asset_id, format_args!("{}", &path.path));
-    // TODO (#8)
-    // TODO handle non-local StorageProvider
-    // TODO return correct error code for not found
-    // let content_type = match &path.path {
-    //     path if path.ends_with("mp4") => "video/mp4",
-    //     path if path.ends_with("stream.mpd") => "application/octet-stream",
-    //     _ => return Ok(StatusCode::NOT_FOUND.into_response()),
-    // };
-    // let read = app_state.storage.open_read_stream(&storage_key).await?;
-    // let headers = [(CONTENT_TYPE, content_type)];
-    let path = app_state
-        .storage
-        .local_path(&storage_key)
-        .await?
-        .expect("not implemented for non-local StorageProvider");
-    let serve_dir = tower_http::services::ServeFile::new(&path)
-        .oneshot(request)
-        .in_current_span()
+    // the manifest references segments by relative URLs, which would resolve against the
+    // presigned URL if we redirected it
+    let is_manifest = path.path.ends_with(".mpd");
+    if !is_manifest {
+        if let Some(url) = app_state.storage.presigned_url(&storage_key).await? {
+            return Ok(Redirect::temporary(&url).into_response());
+        }
+    }
+    let content_type = guess_mime_type_path(camino::Utf8Path::new(&path.path));
+    FileResponse::new(content_type)
+        .from_storage(&app_state.storage, &storage_key, &headers)
         .await
-        .wrap_err("error serving file")?;
-    Ok(serve_dir.into_response())
+}
+
+/// `path` is (already percent-decoded) user input that becomes part of a storage key,
+/// so it must not be able to leave the DASH directory of the Asset
+fn is_plain_relative_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.contains('\\')
+        && path
+            .split('/')
+            .all(|component| !component.is_empty() && component != "." && component != "..")
 }
@@ -0,0 +1,440 @@
+use std::collections::HashSet;
+
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{
+        header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use camino::Utf8PathBuf as PathBuf;
+use chrono::{DateTime, Utc};
+use eyre::{eyre, Context, Result};
+use serde::Deserialize;
+use tokio::io::AsyncWrite;
+use tokio_util::io::ReaderStream;
+use utoipa::{IntoParams, ToSchema};
+
+use core::{
+    catalog::storage_key,
+    core::storage::{Storage, StorageProvider, StorageReadError},
+    deadpool_diesel, interact,
+    model::{self, repository},
+};
+
+use crate::{
+    app_state::SharedState,
+    archive::{self, ArchiveWriter},
+    auth::CurrentUser,
+    http_error::{ApiResult, HttpError},
+    schema::{AlbumId, TimelineGroupId},
+};
+
+/// Buffer between the task writing the archive and the response body
+const ARCHIVE_PIPE_SIZE: usize = 256 * 1024;
+/// Representations that can replace HEIF originals, in order of preference
+const HEIF_SUBSTITUTE_FORMATS: &[&str] = &["jpeg", "avif"];
+
+pub fn router() -> Router<SharedState> {
+    Router::new().route("/", get(download_archive))
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum ArchiveFormat {
+    #[default]
+    Zip,
+    Tar,
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct DownloadRequest {
+    /// Exactly one of albumId, timelineGroupId and assetIds must be given
+    pub album_id: Option<AlbumId>,
+    pub timeline_group_id: Option<TimelineGroupId>,
+    /// Comma separated AssetIds
+    pub asset_ids: Option<String>,
+    #[serde(default)]
+    pub format: ArchiveFormat,
+    /// Put JPEG or AVIF representations into the archive instead of HEIF originals, where they exist
+    #[serde(default)]
+    pub substitute_heif: bool,
+}
+
+/// Where the data of a file in the archive comes from
+#[derive(Debug, Clone)]
+enum EntrySource {
+    LocalFile(PathBuf),
+    Storage(String),
+}
+
+#[derive(Debug, Clone)]
+struct ArchiveEntry {
+    name: String,
+    source: EntrySource,
+    thumbnail_key: String,
+    is_video: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/download",
+    params(DownloadRequest),
+    responses(
+        (status = 200, body = String, content_type = "application/octet", description = "ZIP or TAR archive with the original files, their thumbnails and an index.html to browse them"),
+        (status = BAD_REQUEST, description = "Not exactly one of albumId, timelineGroupId and assetIds, or malformed assetIds"),
+        (status = NOT_FOUND, description = "No such album or TimelineGroup or not visible to the user")
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn download_archive(
+    Query(request): Query<DownloadRequest>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let user_id = current_user.user.id;
+    let album_id: Option<model::AlbumId> = request.album_id.map(|id| id.try_into()).transpose()?;
+    let group_id: Option<model::TimelineGroupId> = request
+        .timeline_group_id
+        .map(|id| id.try_into())
+        .transpose()?;
+    let asset_ids = match request
+        .asset_ids
+        .as_deref()
+        .map(parse_asset_ids)
+        .transpose()
+    {
+        Ok(asset_ids) => asset_ids,
+        Err(err) => return Ok((StatusCode::BAD_REQUEST, HttpError::from(err)).into_response()),
+    };
+    let conn = app_state.pool.get().await?;
+    let source = interact!(conn, move |conn| {
+        match (album_id, group_id, asset_ids) {
+            (Some(album_id), None, None) => {
+                if !repository::album::is_album_visible(conn, album_id, user_id)? {
+                    return Ok(None);
+                }
+                let album = repository::album::get_album(conn, album_id)?;
+                let assets = repository::album::get_items_in_album(conn, album_id, user_id)?
+                    .into_iter()
+                    .filter_map(|item| match item.item {
+                        model::AlbumItemType::Asset(asset) => Some(asset),
+                        model::AlbumItemType::Text(_) => None,
+                    })
+                    .collect::<Vec<_>>();
+                Ok(Some((album.name, assets)))
+            }
+            (None, Some(group_id), None) => {
+                if !repository::timeline_group::is_timeline_group_visible(conn, group_id, user_id)?
+                {
+                    return Ok(None);
+                }
+                let group = repository::timeline_group::get_timeline_group(conn, group_id)?;
+                let assets = repository::timeline_group::get_assets_in_group(conn, group_id)?;
+                Ok(Some((group.name, assets)))
+            }
+            (None, None, Some(asset_ids)) => {
+                let assets = asset_ids
+                    .into_iter()
+                    .map(|id| repository::asset::get_asset(conn, id))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(Some((None, assets)))
+            }
+            _ => Err(eyre!(
+                "exactly one of albumId, timelineGroupId and assetIds is required"
+            )),
+        }
+    })
+    .await?;
+    let (name, assets) = match source {
+        Ok(Some(source)) => source,
+        Ok(None) => {
+            return Ok((
+                StatusCode::NOT_FOUND,
+                HttpError::from(eyre!("no such album or TimelineGroup")),
+            )
+                .into_response())
+        }
+        Err(err) => return Ok((StatusCode::BAD_REQUEST, HttpError::from(err)).into_response()),
+    };
+
+    let substitute_heif = request.substitute_heif;
+    let conn = app_state.pool.get().await?;
+    let entries = interact!(conn, move |conn| {
+        archive_entries(conn, &assets, substitute_heif)
+    })
+    .await??;
+
+    let (format, extension, content_type) = match request.format {
+        ArchiveFormat::Zip => (archive::ArchiveFormat::Zip, "zip", "application/zip"),
+        ArchiveFormat::Tar => (archive::ArchiveFormat::Tar, "tar", "application/x-tar"),
+    };
+    let archive_name = format!(
+        "{}.{}",
+        sanitize_file_name(name.as_deref().unwrap_or("myrti")),
+        extension
+    );
+    let content_disposition =
+        HeaderValue::from_str(&format!("attachment; filename=\"{}\"", archive_name))
+            .wrap_err("error setting content-disposition header")?;
+
+    let (writer, reader) = tokio::io::duplex(ARCHIVE_PIPE_SIZE);
+    let storage = app_state.storage.clone();
+    let title = name.unwrap_or_default();
+    tokio::spawn(async move {
+        if let Err(err) = write_archive(writer, format, &storage, &entries, &title).await {
+            // the client gets a truncated archive, there is no way to report errors anymore
+            tracing::warn!(?err, "error writing archive");
+        }
+    });
+    Ok((
+        [
+            (CONTENT_TYPE, HeaderValue::from_static(content_type)),
+            (CONTENT_DISPOSITION, content_disposition),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response())
+}
+
+/// Decides what goes into the archive under which name, names are unique
+fn archive_entries(
+    conn: &mut repository::db::DbConn,
+    assets: &[model::Asset],
+    substitute_heif: bool,
+) -> Result<Vec<ArchiveEntry>> {
+    let mut used_names: HashSet<String> = HashSet::default();
+    // names of the thumbnails, so index.html can't be overwritten
+    used_names.insert("index.html".to_owned());
+    let mut entries = Vec::with_capacity(assets.len());
+    for asset in assets {
+        let Some(path) = repository::asset::find_asset_path_on_disk(conn, asset.base.id)? else {
+            continue;
+        };
+        let path = path.path_on_disk();
+        let file_name = path.file_name().unwrap_or("unnamed").to_owned();
+        let mut name = file_name.clone();
+        let mut source = EntrySource::LocalFile(path.clone());
+        if let model::AssetSpe::Image(image) = &asset.sp {
+            if substitute_heif && image.image_format_name == "heif" {
+                let reprs =
+                    repository::representation::get_image_representations(conn, asset.base.id)?;
+                let substitute = HEIF_SUBSTITUTE_FORMATS
+                    .iter()
+                    .find_map(|format| reprs.iter().find(|repr| repr.format_name == *format));
+                if let Some(repr) = substitute {
+                    let extension = match repr.format_name.as_str() {
+                        "jpeg" => "jpg",
+                        other => other,
+                    };
+                    name = format!("{}.{}", path.file_stem().unwrap_or("unnamed"), extension);
+                    source = EntrySource::Storage(repr.file_key.clone());
+                }
+            }
+        }
+        entries.push(ArchiveEntry {
+            name: unique_name(&mut used_names, &name),
+            source,
+            thumbnail_key: storage_key::thumbnail(
+                asset.base.id,
+                model::ThumbnailType::SmallSquare,
+                model::ThumbnailFormat::Webp,
+            ),
+            is_video: matches!(asset.sp, model::AssetSpe::Video(_)),
+        });
+    }
+    Ok(entries)
+}
+
+async fn write_archive(
+    out: impl AsyncWrite + Unpin,
+    format: archive::ArchiveFormat,
+    storage: &Storage,
+    entries: &[ArchiveEntry],
+    title: &str,
+) -> Result<()> {
+    let mut archive = ArchiveWriter::new(out, format);
+    // entries whose thumbnail made it into the archive, for index.html
+    let mut written: Vec<(&ArchiveEntry, bool)> = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let added = match &entry.source {
+            EntrySource::LocalFile(path) => add_local_file(&mut archive, &entry.name, path).await?,
+            EntrySource::Storage(key) => {
+                add_storage_file(&mut archive, &entry.name, storage, key).await?
+            }
+        };
+        if !added {
+            tracing::warn!(
+                name = entry.name,
+                "file for archive does not exist, skipping"
+            );
+            continue;
+        }
+        let thumbnail_name = format!("thumbnails/{}.webp", entry.name);
+        let has_thumbnail =
+            add_storage_file(&mut archive, &thumbnail_name, storage, &entry.thumbnail_key).await?;
+        written.push((entry, has_thumbnail));
+    }
+    let index = gallery_html(title, &written);
+    archive
+        .add_bytes("index.html", Utc::now(), index.as_bytes())
+        .await?;
+    archive.finish().await?;
+    Ok(())
+}
+
+/// Returns false if the file does not exist
+async fn add_local_file(
+    archive: &mut ArchiveWriter<impl AsyncWrite + Unpin>,
+    name: &str,
+    path: &PathBuf,
+) -> Result<bool> {
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(eyre::Report::from(err).wrap_err("error opening file")),
+    };
+    let meta = file
+        .metadata()
+        .await
+        .wrap_err("error getting file metadata")?;
+    let modified: DateTime<Utc> = meta
+        .modified()
+        .map(Into::into)
+        .unwrap_or_else(|_| Utc::now());
+    archive.add_file(name, modified, meta.len(), file).await?;
+    Ok(true)
+}
+
+/// Returns false if the object does not exist
+async fn add_storage_file(
+    archive: &mut ArchiveWriter<impl AsyncWrite + Unpin>,
+    name: &str,
+    storage: &Storage,
+    key: &str,
+) -> Result<bool> {
+    let object = match storage.metadata(key).await {
+        Ok(object) => object,
+        Err(StorageReadError::FileNotFound(_)) => return Ok(false),
+        Err(err) => return Err(eyre::Report::from(err).wrap_err("error getting object metadata")),
+    };
+    let read = match storage.open_read_stream(key).await {
+        Ok(read) => read,
+        Err(StorageReadError::FileNotFound(_)) => return Ok(false),
+        Err(err) => {
+            return Err(eyre::Report::from(err).wrap_err("could not open object for reading"))
+        }
+    };
+    archive
+        .add_file(name, object.last_modified, object.size, read)
+        .await?;
+    Ok(true)
+}
+
+/// Appends " (2)", " (3)"... before the extension if the name is taken
+fn unique_name(used_names: &mut HashSet<String>, name: &str) -> String {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+        _ => (name, None),
+    };
+    let mut candidate = name.to_owned();
+    let mut n = 2;
+    while used_names.contains(&candidate.to_lowercase()) {
+        candidate = match extension {
+            Some(extension) => format!("{} ({}).{}", stem, n, extension),
+            None => format!("{} ({})", stem, n),
+        };
+        n += 1;
+    }
+    // case insensitive, archives are often extracted on filesystems that are
+    used_names.insert(candidate.to_lowercase());
+    candidate
+}
+
+/// Comma separated AssetIds
+fn parse_asset_ids(ids: &str) -> Result<Vec<model::AssetId>> {
+    ids.split(',')
+        .map(|id| id.trim().parse::<i64>().map(model::AssetId))
+        .collect::<Result<Vec<_>, _>>()
+        .wrap_err_with(|| format!("invalid assetIds '{}'", ids))
+}
+
+/// Only characters that are safe in a Content-Disposition header and on every filesystem
+fn sanitize_file_name(name: &str) -> String {
+    let sanitized: String = name
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() && c.is_ascii() => c,
+            ' ' | '-' | '_' | '.' | '(' | ')' => c,
+            _ => '_',
+        })
+        .collect();
+    match sanitized.trim_matches(['.', ' ']) {
+        "" => "myrti".to_owned(),
+        trimmed => trimmed.to_owned(),
+    }
+}
+
+/// Percent encodes everything but unreserved characters, for relative links in index.html
+fn url_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Static page showing the thumbnails, linking to the files next to it
+fn gallery_html(title: &str, entries: &[(&ArchiveEntry, bool)]) -> String {
+    let title = html_escape::encode_text(if title.is_empty() { "Photos" } else { title });
+    let mut html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+<style>
+body {{ font-family: sans-serif; margin: 1rem; background: #111; color: #eee; }}
+.grid {{ display: grid; grid-template-columns: repeat(auto-fill, minmax(160px, 1fr)); gap: 4px; }}
+.grid a {{ position: relative; display: block; aspect-ratio: 1; background: #333; color: #eee; overflow: hidden; }}
+.grid img {{ width: 100%; height: 100%; object-fit: cover; }}
+.grid span {{ position: absolute; left: 4px; bottom: 4px; font-size: 0.8rem; text-shadow: 0 0 2px #000; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<div class="grid">
+"#
+    );
+    for (entry, has_thumbnail) in entries {
+        let href = url_encode_path(&entry.name);
+        let name = html_escape::encode_text(&entry.name);
+        html.push_str(&format!(r#"<a href="{}" title="{}">"#, href, name));
+        if *has_thumbnail {
+            html.push_str(&format!(
+                r#"<img src="{}" alt="{}" loading="lazy">"#,
+                url_encode_path(&format!("thumbnails/{}.webp", entry.name)),
+                name
+            ));
+        } else {
+            html.push_str(&format!("<span>{}</span>", name));
+        }
+        if entry.is_video {
+            html.push_str("<span>&#9654;</span>");
+        }
+        html.push_str("</a>\n");
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
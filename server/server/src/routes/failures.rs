@@ -0,0 +1,165 @@
+use core::{
+    core::scheduler::{SchedulerMessage, UserRequest},
+    deadpool_diesel, interact,
+    model::{self, repository},
+};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use eyre::{eyre, Context};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::{
+    app_state::SharedState,
+    http_error::{ApiResult, HttpError},
+    schema::{
+        failed_job::{FailedJob, FailedJobKind},
+        FailedJobId,
+    },
+};
+
+pub fn router() -> Router<SharedState> {
+    Router::new()
+        .route("/", get(get_failures))
+        .route("/:id/retry", post(retry_failure))
+        .route("/:id/ignore", post(ignore_failure))
+        .route("/:id/unignore", post(unignore_failure))
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct FailuresRequest {
+    /// Only return failures of this kind
+    pub kind: Option<FailedJobKind>,
+    /// Also return failures that were ignored, default false
+    pub include_ignored: Option<bool>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/failures",
+    params(FailuresRequest),
+    responses((status = 200, body = Vec<FailedJob>)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn get_failures(
+    State(app_state): State<SharedState>,
+    Query(request): Query<FailuresRequest>,
+) -> ApiResult<Json<Vec<FailedJob>>> {
+    let kind: Option<model::FailedJobKind> = request.kind.map(|kind| kind.into());
+    let include_ignored = request.include_ignored.unwrap_or(false);
+    let conn = app_state.pool.get().await?;
+    let failed_jobs = interact!(conn, move |conn| {
+        repository::failed_job::get_failed_jobs(conn, kind, include_ignored)
+    })
+    .await??;
+    Ok(Json(failed_jobs.iter().map(|f| f.into()).collect()))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/failures/{id}/retry",
+    responses(
+        (status = 200, description = "The failed work was queued again"),
+        (status = NOT_FOUND, description = "Failure not found")
+    ),
+    params(
+        ("id" = String, Path, description = "FailedJobId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn retry_failure(
+    Path(id): Path<String>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    let failed_job = match find_failed_job(&app_state, id).await? {
+        Ok(failed_job) => failed_job,
+        Err(response) => return Ok(response),
+    };
+    app_state
+        .scheduler
+        .send
+        .send(SchedulerMessage::UserRequest(UserRequest::RetryFailedJob(
+            failed_job.id,
+        )))
+        .await
+        .wrap_err("error sending message to scheduler")?;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/failures/{id}/ignore",
+    responses(
+        (status = 200, description = "The failed work will not be retried automatically anymore, unless the file changes"),
+        (status = NOT_FOUND, description = "Failure not found")
+    ),
+    params(
+        ("id" = String, Path, description = "FailedJobId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn ignore_failure(
+    Path(id): Path<String>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    set_ignored(&app_state, id, true).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/failures/{id}/unignore",
+    responses(
+        (status = 200, description = "The failed work is retried automatically again"),
+        (status = NOT_FOUND, description = "Failure not found")
+    ),
+    params(
+        ("id" = String, Path, description = "FailedJobId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn unignore_failure(
+    Path(id): Path<String>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    set_ignored(&app_state, id, false).await
+}
+
+async fn set_ignored(app_state: &SharedState, id: String, is_ignored: bool) -> ApiResult<Response> {
+    let failed_job = match find_failed_job(app_state, id).await? {
+        Ok(failed_job) => failed_job,
+        Err(response) => return Ok(response),
+    };
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::failed_job::set_failed_job_ignored(conn, failed_job.id, is_ignored)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Err contains the 404 response if the failure doesn't exist
+async fn find_failed_job(
+    app_state: &SharedState,
+    id: String,
+) -> ApiResult<Result<model::FailedJob, Response>> {
+    let failed_job_id: model::FailedJobId = FailedJobId(id).try_into()?;
+    let conn = app_state.pool.get().await?;
+    let failed_job = interact!(conn, move |conn| {
+        repository::failed_job::find_failed_job(conn, failed_job_id)
+    })
+    .await??;
+    Ok(failed_job.ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such failure")),
+        )
+            .into_response()
+    }))
+}
@@ -1,16 +1,202 @@
-use core::core::scheduler::SchedulerMessage;
+use core::{
+    core::scheduler::{SchedulerMessage, UserRequest},
+    deadpool_diesel, interact,
+    model::{self, repository},
+};
 
-use axum::{extract::State, routing::post, Router};
-use eyre::Context;
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use eyre::{eyre, Context};
+use serde::Deserialize;
+use utoipa::IntoParams;
 
-use crate::{app_state::SharedState, http_error::ApiResult};
+use crate::{
+    app_state::SharedState,
+    http_error::{ApiResult, HttpError},
+    schema::{
+        job::{Job, JobState},
+        JobId,
+    },
+};
 
 pub fn router() -> Router<SharedState> {
     Router::new()
+        .route("/", get(get_jobs))
+        .route("/:id", get(get_job))
+        .route("/:id/cancel", post(cancel_job))
+        .route("/:id/retry", post(retry_job))
         .route("/pauseAllProcessing", post(pause_all_processing))
         .route("/resumeAllProcessing", post(resume_all_processing))
         .route("/pauseVideoProcessing", post(pause_video_processing))
         .route("/resumeVideoProcessing", post(resume_video_processing))
+        .route("/findNearDuplicates", post(find_near_duplicates))
+        .route("/detectSeries", post(detect_series))
+}
+
+const DEFAULT_JOBS_LIMIT: i64 = 100;
+const MAX_JOBS_LIMIT: i64 = 1000;
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct JobsRequest {
+    /// Only return jobs in this state
+    pub state: Option<JobState>,
+    /// Id of the last job of the previous page
+    pub before: Option<JobId>,
+    pub limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/jobs",
+    params(JobsRequest),
+    responses((status = 200, body = Vec<Job>)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn get_jobs(
+    State(app_state): State<SharedState>,
+    Query(request): Query<JobsRequest>,
+) -> ApiResult<Json<Vec<Job>>> {
+    let states: Option<Vec<model::JobState>> = request.state.map(|state| vec![state.into()]);
+    let before: Option<model::JobId> = request.before.map(|id| id.try_into()).transpose()?;
+    let limit = request
+        .limit
+        .unwrap_or(DEFAULT_JOBS_LIMIT)
+        .clamp(1, MAX_JOBS_LIMIT);
+    let conn = app_state.pool.get().await?;
+    let jobs = interact!(conn, move |conn| {
+        repository::job::get_jobs(conn, states.as_deref(), before, limit)
+    })
+    .await??;
+    Ok(Json(jobs.iter().map(|job| job.into()).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/jobs/{id}",
+    responses(
+        (status = 200, body = Job),
+        (status = NOT_FOUND, description = "Job not found")
+    ),
+    params(
+        ("id" = String, Path, description = "JobId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn get_job(
+    Path(id): Path<String>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    let job = match find_job(&app_state, id).await? {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+    Ok(Json(Job::from(&job)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/jobs/{id}/cancel",
+    responses(
+        (status = 200, description = "Cancellation was requested"),
+        (status = NOT_FOUND, description = "Job not found"),
+        (status = CONFLICT, description = "Job is already done")
+    ),
+    params(
+        ("id" = String, Path, description = "JobId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn cancel_job(
+    Path(id): Path<String>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    let job = match find_job(&app_state, id).await? {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+    if job.state.is_done() {
+        return Ok((
+            StatusCode::CONFLICT,
+            HttpError::from(eyre!("job is already done")),
+        )
+            .into_response());
+    }
+    app_state
+        .scheduler
+        .send
+        .send(SchedulerMessage::UserRequest(UserRequest::CancelJob(
+            job.id,
+        )))
+        .await
+        .wrap_err("error sending message to scheduler")?;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/jobs/{id}/retry",
+    responses(
+        (status = 200, description = "Job was queued again"),
+        (status = BAD_REQUEST, description = "Jobs of this kind can not be retried"),
+        (status = NOT_FOUND, description = "Job not found"),
+        (status = CONFLICT, description = "Job is still queued or running")
+    ),
+    params(
+        ("id" = String, Path, description = "JobId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn retry_job(
+    Path(id): Path<String>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    let job = match find_job(&app_state, id).await? {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+    if !job.state.is_done() {
+        return Ok((
+            StatusCode::CONFLICT,
+            HttpError::from(eyre!("job is still queued or running")),
+        )
+            .into_response());
+    }
+    // we don't know which paths these were for anymore
+    if matches!(
+        job.kind,
+        model::JobKind::IndexPaths | model::JobKind::ReconcilePaths
+    ) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            HttpError::from(eyre!(
+                "job can not be retried, reindex the asset root instead"
+            )),
+        )
+            .into_response());
+    }
+    app_state
+        .scheduler
+        .send
+        .send(SchedulerMessage::UserRequest(UserRequest::RetryJob(job.id)))
+        .await
+        .wrap_err("error sending message to scheduler")?;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Err contains the 404 response if the job doesn't exist
+async fn find_job(app_state: &SharedState, id: String) -> ApiResult<Result<model::Job, Response>> {
+    let job_id: model::JobId = JobId(id).try_into()?;
+    let conn = app_state.pool.get().await?;
+    let job = interact!(conn, move |conn| repository::job::find_job(conn, job_id)).await??;
+    Ok(job.ok_or_else(|| {
+        (StatusCode::NOT_FOUND, HttpError::from(eyre!("no such job"))).into_response()
+    }))
 }
 
 async fn pause_all_processing(State(app_state): State<SharedState>) -> ApiResult<()> {
@@ -52,3 +238,28 @@ async fn resume_video_processing(State(app_state): State<SharedState>) -> ApiRes
         .wrap_err("error sending message to scheduler")?;
     Ok(())
 }
+
+/// Compute missing perceptual hashes and group visually similar Assets now,
+/// results are available from /api/assets/nearDuplicates when done
+async fn find_near_duplicates(State(app_state): State<SharedState>) -> ApiResult<()> {
+    app_state
+        .scheduler
+        .send
+        .send(SchedulerMessage::UserRequest(
+            UserRequest::FindNearDuplicates,
+        ))
+        .await
+        .wrap_err("error sending message to scheduler")?;
+    Ok(())
+}
+
+/// Put photos taken in quick succession into automatic series now
+async fn detect_series(State(app_state): State<SharedState>) -> ApiResult<()> {
+    app_state
+        .scheduler
+        .send
+        .send(SchedulerMessage::UserRequest(UserRequest::DetectSeries))
+        .await
+        .wrap_err("error sending message to scheduler")?;
+    Ok(())
+}
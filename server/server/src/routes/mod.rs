@@ -17,11 +17,17 @@ use crate::{app_state::SharedState, http_error::HttpError};
 pub mod album;
 pub mod asset;
 pub mod asset_roots;
+pub mod auth;
 pub mod dash;
+pub mod download;
+pub mod failures;
 pub mod jobs;
 pub mod photo_series;
+pub mod search;
+pub mod share;
 pub mod timeline;
 pub mod timeline_group;
+pub mod user;
 
 #[derive(Deserialize)]
 struct QueryIndexAssetRoot {
@@ -47,6 +53,7 @@ async fn post_index_asset_root(
     Ok(())
 }
 
+/// Only for admins
 pub fn api_router() -> Router<SharedState> {
     Router::new().route("/indexAssetRoot", post(post_index_asset_root))
 }
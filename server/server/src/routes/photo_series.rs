@@ -3,7 +3,13 @@ use core::{
     model::{self, repository},
 };
 
-use axum::{extract::State, routing::post, Json, Router};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Extension, Json, Router,
+};
 use core::deadpool_diesel;
 use eyre::{eyre, Context, Result};
 use serde::{Deserialize, Serialize};
@@ -11,18 +17,27 @@ use utoipa::ToSchema;
 
 use crate::{
     app_state::SharedState,
-    http_error::ApiResult,
-    schema::{AssetId, AssetSeriesId},
+    auth::CurrentUser,
+    http_error::{ApiResult, HttpError},
+    schema::{asset_series::AssetSeries, AssetId, AssetSeriesId},
 };
 
 pub fn router() -> Router<SharedState> {
-    Router::new().route("/", post(create_series))
+    Router::new()
+        .route("/", post(create_series))
+        .route("/:id", get(get_series))
+        .route("/:id", delete(dissolve_series))
+        .route("/:id/assets", put(add_assets_to_series))
+        .route("/:id/removeAssets", post(remove_assets_from_series))
+        .route("/:id/selection", put(set_series_selection))
 }
 
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateSeriesRequest {
     pub asset_ids: Vec<AssetId>,
+    /// The photos shown in place of the series, the first asset if not given
+    pub selection: Option<Vec<AssetId>>,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema)]
@@ -44,15 +59,14 @@ pub async fn create_series(
     if request.asset_ids.is_empty() {
         return Err(eyre!("assetIds can not be empty").into());
     }
-    let asset_ids: Vec<model::AssetId> = request
-        .asset_ids
-        .iter()
-        .map(model::AssetId::try_from)
-        .collect::<Result<Vec<_>>>()
-        .wrap_err("invalid assetIds")?;
+    let asset_ids = parse_asset_ids(&request.asset_ids)?;
+    let selection = match &request.selection {
+        Some(selection) => parse_asset_ids(selection)?,
+        None => vec![asset_ids[0]],
+    };
     let conn = app_state.pool.get().await?;
     let series_id = interact!(conn, move |conn| {
-        repository::asset_series::create_series(conn, &asset_ids)
+        repository::asset_series::create_series(conn, &asset_ids, &selection)
     })
     .await
     .wrap_err("error creating AssetSeries")??;
@@ -60,3 +74,236 @@ pub async fn create_series(
         series_id: AssetSeriesId(series_id.0.to_string()),
     }))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/photoSeries/{id}",
+    responses(
+        (status = 200, body = AssetSeries),
+        (status = NOT_FOUND, description = "No such series")
+    ),
+    params(
+        ("id" = String, Path, description = "AssetSeriesId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn get_series(
+    Path(series_id): Path<AssetSeriesId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let series_id: model::AssetSeriesId = series_id.try_into()?;
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let series = interact!(conn, move |conn| {
+        repository::asset_series::get_series(conn, series_id, user_id)
+    })
+    .await??;
+    match series {
+        Some(series) => Ok(Json(AssetSeries::from(series)).into_response()),
+        None => Ok(series_not_found()),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SeriesAssetsRequest {
+    pub asset_ids: Vec<AssetId>,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/photoSeries/{id}/assets",
+    request_body = SeriesAssetsRequest,
+    responses(
+        (status = 200, description = "Assets were added"),
+        (status = BAD_REQUEST, description = "One or more assets do not exist"),
+        (status = NOT_FOUND, description = "No such series"),
+        (status = CONFLICT, description = "One or more assets are already part of a series")
+    ),
+    params(
+        ("id" = String, Path, description = "AssetSeriesId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn add_assets_to_series(
+    Path(series_id): Path<AssetSeriesId>,
+    State(app_state): State<SharedState>,
+    Json(request): Json<SeriesAssetsRequest>,
+) -> ApiResult<Response> {
+    let series_id: model::AssetSeriesId = series_id.try_into()?;
+    let mut asset_ids = parse_asset_ids(&request.asset_ids)?;
+    asset_ids.sort();
+    asset_ids.dedup();
+    let conn = app_state.pool.get().await?;
+    let series_asset_ids = interact!(conn, move |conn| {
+        repository::asset_series::get_series_asset_ids(conn, series_id)
+    })
+    .await??;
+    if series_asset_ids.is_empty() {
+        return Ok(series_not_found());
+    }
+    let asset_ids_copy = asset_ids.clone();
+    let current_series = interact!(conn, move |conn| {
+        repository::asset_series::get_series_of_assets(conn, &asset_ids_copy)
+    })
+    .await??;
+    if current_series.len() != asset_ids.len() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            HttpError::from(eyre!("one or more assets do not exist")),
+        )
+            .into_response());
+    }
+    if current_series
+        .iter()
+        .any(|(_, current_series_id)| current_series_id.is_some())
+    {
+        return Ok((
+            StatusCode::CONFLICT,
+            HttpError::from(eyre!("one or more assets are already part of a series")),
+        )
+            .into_response());
+    }
+    interact!(conn, move |conn| {
+        repository::asset_series::add_assets_to_series(conn, series_id, &asset_ids)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Assets that are not part of the series are ignored. If the whole selection is removed
+/// the most recent remaining asset is selected, if fewer than two assets remain the
+/// series is dissolved.
+#[utoipa::path(
+    post,
+    path = "/api/photoSeries/{id}/removeAssets",
+    request_body = SeriesAssetsRequest,
+    responses(
+        (status = 200, description = "Assets were removed"),
+        (status = NOT_FOUND, description = "No such series")
+    ),
+    params(
+        ("id" = String, Path, description = "AssetSeriesId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn remove_assets_from_series(
+    Path(series_id): Path<AssetSeriesId>,
+    State(app_state): State<SharedState>,
+    Json(request): Json<SeriesAssetsRequest>,
+) -> ApiResult<Response> {
+    let series_id: model::AssetSeriesId = series_id.try_into()?;
+    let asset_ids = parse_asset_ids(&request.asset_ids)?;
+    let conn = app_state.pool.get().await?;
+    let series_asset_ids = interact!(conn, move |conn| {
+        repository::asset_series::get_series_asset_ids(conn, series_id)
+    })
+    .await??;
+    if series_asset_ids.is_empty() {
+        return Ok(series_not_found());
+    }
+    interact!(conn, move |conn| {
+        repository::asset_series::remove_assets_from_series(conn, series_id, &asset_ids)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Replace the photos shown in place of the series
+#[utoipa::path(
+    put,
+    path = "/api/photoSeries/{id}/selection",
+    request_body = SeriesAssetsRequest,
+    responses(
+        (status = 200, description = "Selection was changed"),
+        (status = BAD_REQUEST, description = "Selection is empty or contains assets not in the series"),
+        (status = NOT_FOUND, description = "No such series")
+    ),
+    params(
+        ("id" = String, Path, description = "AssetSeriesId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn set_series_selection(
+    Path(series_id): Path<AssetSeriesId>,
+    State(app_state): State<SharedState>,
+    Json(request): Json<SeriesAssetsRequest>,
+) -> ApiResult<Response> {
+    let series_id: model::AssetSeriesId = series_id.try_into()?;
+    let mut selection = parse_asset_ids(&request.asset_ids)?;
+    selection.sort();
+    selection.dedup();
+    let conn = app_state.pool.get().await?;
+    let series_asset_ids = interact!(conn, move |conn| {
+        repository::asset_series::get_series_asset_ids(conn, series_id)
+    })
+    .await??;
+    if series_asset_ids.is_empty() {
+        return Ok(series_not_found());
+    }
+    if selection.is_empty() || !selection.iter().all(|id| series_asset_ids.contains(id)) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            HttpError::from(eyre!(
+                "selection must not be empty and only contain assets of the series"
+            )),
+        )
+            .into_response());
+    }
+    interact!(conn, move |conn| {
+        repository::asset_series::set_series_selection(conn, series_id, &selection)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+/// Delete the series, its assets are shown on their own again.
+/// Series that were created automatically are not created again.
+#[utoipa::path(
+    delete,
+    path = "/api/photoSeries/{id}",
+    responses(
+        (status = 200, description = "Series was dissolved"),
+        (status = NOT_FOUND, description = "No such series")
+    ),
+    params(
+        ("id" = String, Path, description = "AssetSeriesId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn dissolve_series(
+    Path(series_id): Path<AssetSeriesId>,
+    State(app_state): State<SharedState>,
+) -> ApiResult<Response> {
+    let series_id: model::AssetSeriesId = series_id.try_into()?;
+    let conn = app_state.pool.get().await?;
+    let series_asset_ids = interact!(conn, move |conn| {
+        repository::asset_series::get_series_asset_ids(conn, series_id)
+    })
+    .await??;
+    if series_asset_ids.is_empty() {
+        return Ok(series_not_found());
+    }
+    interact!(conn, move |conn| {
+        repository::asset_series::dissolve_series(conn, series_id)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+fn parse_asset_ids(asset_ids: &[AssetId]) -> Result<Vec<model::AssetId>> {
+    asset_ids
+        .iter()
+        .map(model::AssetId::try_from)
+        .collect::<Result<Vec<_>>>()
+        .wrap_err("invalid assetIds")
+}
+
+fn series_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        HttpError::from(eyre!("no such series")),
+    )
+        .into_response()
+}
@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+
+use core::{
+    deadpool_diesel, interact,
+    model::{
+        self, repository,
+        repository::{search::SearchCursor, search_index::TextSearchHit},
+    },
+};
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    app_state::SharedState,
+    auth::CurrentUser,
+    http_error::{ApiResult, HttpError},
+    schema::{
+        asset::{AssetType, AssetWithSpe},
+        Album, AlbumId, AssetRootDirId, TimelineGroupId,
+    },
+};
+
+pub fn router() -> Router<SharedState> {
+    Router::new()
+        .route("/", get(search))
+        .route("/text", get(search_text))
+}
+
+const DEFAULT_SEARCH_LIMIT: i64 = 100;
+const MAX_SEARCH_LIMIT: i64 = 1000;
+const DEFAULT_TEXT_SEARCH_LIMIT: i64 = 50;
+const MAX_TEXT_SEARCH_LIMIT: i64 = 200;
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchRequest {
+    pub taken_after: Option<DateTime<Utc>>,
+    pub taken_before: Option<DateTime<Utc>>,
+    pub asset_type: Option<AssetType>,
+    /// e.g. jpeg, heic, mp4
+    pub file_type: Option<String>,
+    /// Video or audio codec, e.g. hevc, aac
+    pub codec: Option<String>,
+    pub root_dir_id: Option<AssetRootDirId>,
+    /// GLOB pattern for the path in the root dir, e.g. `2019/*Rome*`
+    pub path_glob: Option<String>,
+    /// Part of the camera make, case insensitive
+    pub camera_make: Option<String>,
+    /// Part of the camera model, case insensitive
+    pub camera_model: Option<String>,
+    /// Part of the lens model, case insensitive
+    pub lens: Option<String>,
+    pub min_iso: Option<i64>,
+    pub max_iso: Option<i64>,
+    /// Millimeters
+    pub min_focal_length: Option<f64>,
+    /// Millimeters
+    pub max_focal_length: Option<f64>,
+    /// GPS bounding box, all four or none must be given.
+    /// minLongitude > maxLongitude for boxes crossing the antimeridian.
+    pub min_latitude: Option<f64>,
+    pub max_latitude: Option<f64>,
+    pub min_longitude: Option<f64>,
+    pub max_longitude: Option<f64>,
+    /// Only return assets the user has hidden instead of only the others
+    #[serde(default)]
+    pub hidden: bool,
+    pub album_id: Option<AlbumId>,
+    pub timeline_group_id: Option<TimelineGroupId>,
+    /// nextCursor of the previous page
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResponse {
+    /// Newest first
+    pub assets: Vec<AssetWithSpe>,
+    /// None on the last page
+    pub next_cursor: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/search",
+    params(SearchRequest),
+    responses(
+        (status = 200, body = SearchResponse),
+        (status = BAD_REQUEST, description = "Invalid cursor"),
+        (status = NOT_FOUND, description = "Album or timeline group not found")
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn search(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(request): Query<SearchRequest>,
+) -> ApiResult<Response> {
+    let user_id = current_user.user.id;
+    let limit = request
+        .limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT);
+    let after = match request.cursor.as_deref().map(parse_cursor) {
+        None => None,
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => {
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                HttpError::from(eyre!("invalid cursor")),
+            )
+                .into_response())
+        }
+    };
+    let gps_bounds = match (
+        request.min_latitude,
+        request.max_latitude,
+        request.min_longitude,
+        request.max_longitude,
+    ) {
+        (None, None, None, None) => None,
+        (Some(min_lat), Some(max_lat), Some(min_lon), Some(max_lon)) => {
+            Some(repository::search::GpsBounds {
+                min_lat: (min_lat * 10e8) as i64,
+                max_lat: (max_lat * 10e8) as i64,
+                min_lon: (min_lon * 10e8) as i64,
+                max_lon: (max_lon * 10e8) as i64,
+            })
+        }
+        _ => {
+            return Err(eyre!(
+                "minLatitude, maxLatitude, minLongitude and maxLongitude must be given together"
+            )
+            .into())
+        }
+    };
+    let search = repository::search::AssetSearch {
+        taken_after: request.taken_after,
+        taken_before: request.taken_before,
+        asset_type: request.asset_type.map(model::AssetType::from),
+        file_type: request.file_type,
+        codec: request.codec,
+        root_dir_id: request.root_dir_id.map(|id| id.try_into()).transpose()?,
+        path_glob: request.path_glob,
+        camera_make: request.camera_make,
+        camera_model: request.camera_model,
+        lens: request.lens,
+        min_iso: request.min_iso,
+        max_iso: request.max_iso,
+        min_focal_length: request.min_focal_length,
+        max_focal_length: request.max_focal_length,
+        gps_bounds,
+        hidden: request.hidden,
+        album_id: request.album_id.map(|id| id.try_into()).transpose()?,
+        timeline_group_id: request
+            .timeline_group_id
+            .map(|id| id.try_into())
+            .transpose()?,
+    };
+    let conn = app_state.pool.get().await?;
+    let album_id = search.album_id;
+    let group_id = search.timeline_group_id;
+    let (album_visible, group_visible) = interact!(conn, move |conn| {
+        let album_visible = album_id
+            .map(|album_id| repository::album::is_album_visible(conn, album_id, user_id))
+            .transpose()?;
+        let group_visible = group_id
+            .map(|group_id| {
+                repository::timeline_group::is_timeline_group_visible(conn, group_id, user_id)
+            })
+            .transpose()?;
+        Ok((album_visible, group_visible))
+    })
+    .await??;
+    if album_visible == Some(false) {
+        return Ok(not_found("no such album"));
+    }
+    if group_visible == Some(false) {
+        return Ok(not_found("no such timeline group"));
+    }
+    // one more than requested to know if there is a next page
+    let mut assets = interact!(conn, move |conn| {
+        repository::search::search_assets(conn, user_id, &search, after, limit + 1)
+    })
+    .await??;
+    let next_cursor = if assets.len() as i64 > limit {
+        assets.truncate(limit as usize);
+        assets
+            .last()
+            .map(|asset| format_cursor(&SearchCursor::from(asset)))
+    } else {
+        None
+    };
+    let mut api_assets: Vec<AssetWithSpe> = Vec::with_capacity(assets.len());
+    for asset in &assets {
+        api_assets.push(super::timeline::asset_with_spe(&app_state.pool, asset).await?);
+    }
+    Ok(Json(SearchResponse {
+        assets: api_assets,
+        next_cursor,
+    })
+    .into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchTextRequest {
+    /// Words to search for in file paths, image descriptions and keywords,
+    /// album names, descriptions and texts and timeline group names.
+    /// The last word may be incomplete.
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchTextResponse {
+    /// Best match first
+    pub hits: Vec<SearchTextHit>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum SearchTextHit {
+    #[serde(rename_all = "camelCase")]
+    Asset { asset: AssetWithSpe },
+    #[serde(rename_all = "camelCase")]
+    Album { album: Album },
+    #[serde(rename_all = "camelCase")]
+    TimelineGroup {
+        timeline_group_id: TimelineGroupId,
+        name: Option<String>,
+        display_date: DateTime<Utc>,
+    },
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/search/text",
+    params(SearchTextRequest),
+    responses((status = 200, body = SearchTextResponse)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn search_text(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(request): Query<SearchTextRequest>,
+) -> ApiResult<Json<SearchTextResponse>> {
+    let user_id = current_user.user.id;
+    let limit = request
+        .limit
+        .unwrap_or(DEFAULT_TEXT_SEARCH_LIMIT)
+        .clamp(1, MAX_TEXT_SEARCH_LIMIT);
+    let conn = app_state.pool.get().await?;
+    let (hits, album_counts) = interact!(conn, move |conn| {
+        let hits = repository::search_index::search_text(conn, user_id, &request.q, limit)?;
+        let album_counts: HashMap<model::AlbumId, (model::Album, i64)> = if hits
+            .iter()
+            .any(|hit| matches!(hit, TextSearchHit::Album(_)))
+        {
+            repository::album::get_all_albums_with_asset_count(conn, user_id)?
+                .into_iter()
+                .map(|(album, num_assets)| (album.id, (album, num_assets)))
+                .collect()
+        } else {
+            HashMap::new()
+        };
+        Ok((hits, album_counts))
+    })
+    .await??;
+    let mut api_hits: Vec<SearchTextHit> = Vec::with_capacity(hits.len());
+    for hit in hits {
+        match hit {
+            TextSearchHit::Asset(asset_id) => {
+                let asset = interact!(conn, move |conn| {
+                    repository::asset::get_asset(conn, asset_id)
+                })
+                .await??;
+                api_hits.push(SearchTextHit::Asset {
+                    asset: super::timeline::asset_with_spe(&app_state.pool, &asset).await?,
+                });
+            }
+            TextSearchHit::Album(album_id) => {
+                if let Some((album, num_assets)) = album_counts.get(&album_id) {
+                    api_hits.push(SearchTextHit::Album {
+                        album: Album::from_model(album, *num_assets),
+                    });
+                }
+            }
+            TextSearchHit::TimelineGroup(group_id) => {
+                let group = interact!(conn, move |conn| {
+                    repository::timeline_group::get_timeline_group(conn, group_id)
+                })
+                .await??;
+                api_hits.push(SearchTextHit::TimelineGroup {
+                    timeline_group_id: group.id.into(),
+                    name: group.name,
+                    display_date: group.display_date,
+                });
+            }
+        }
+    }
+    Ok(Json(SearchTextResponse { hits: api_hits }))
+}
+
+/// Cursors are `<taken_date in unix millis>_<asset id>` of the last Asset on the page
+fn format_cursor(cursor: &SearchCursor) -> String {
+    format!(
+        "{}_{}",
+        cursor.taken_date.timestamp_millis(),
+        cursor.asset_id.0
+    )
+}
+
+fn parse_cursor(cursor: &str) -> Option<SearchCursor> {
+    let (taken_date, asset_id) = cursor.split_once('_')?;
+    Some(SearchCursor {
+        taken_date: Utc
+            .timestamp_millis_opt(taken_date.parse().ok()?)
+            .single()?,
+        asset_id: model::AssetId(asset_id.parse().ok()?),
+    })
+}
+
+fn not_found(message: &'static str) -> Response {
+    (StatusCode::NOT_FOUND, HttpError::from(eyre!(message))).into_response()
+}
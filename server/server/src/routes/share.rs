@@ -0,0 +1,543 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::SET_COOKIE, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use eyre::{eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use core::{
+    deadpool_diesel, interact,
+    model::{self, repository},
+};
+
+use crate::{
+    app_state::SharedState,
+    auth::{self, CurrentUser},
+    http_error::{ApiResult, HttpError},
+    schema::{
+        asset::AssetWithSpe,
+        share_link::{ShareLink, ShareLinkTarget},
+        AssetId, ImageRepresentationId, ShareLinkId,
+    },
+};
+
+use super::{
+    asset::{ThumbnailFormat, ThumbnailSize},
+    dash::DashFilePath,
+};
+
+/// Cookie set after entering the password of a share link, followed by the ShareLinkId
+const SHARE_COOKIE_PREFIX: &str = "myrti_share_";
+
+/// Managing the current user's share links
+pub fn router() -> Router<SharedState> {
+    Router::new()
+        .route("/", get(get_share_links))
+        .route("/", post(create_share_link))
+        .route("/:id", delete(delete_share_link))
+}
+
+/// Routes that work without being logged in, only for the shared assets
+pub fn public_router() -> Router<SharedState> {
+    Router::new()
+        .route("/:token", get(get_shared))
+        .route("/:token/unlock", post(unlock_share_link))
+        .route(
+            "/:token/thumbnail/:id/:size/:format",
+            get(get_shared_thumbnail),
+        )
+        .route(
+            "/:token/repr/:asset_id/:repr_id",
+            get(get_shared_image_representation),
+        )
+        .route("/:token/original/:id", get(get_shared_asset_file))
+        .route(
+            "/:token/dash/:id/*path",
+            get(get_shared_dash_file).options(get_shared_dash_file),
+        )
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/shares",
+    responses((status = 200, body = Vec<ShareLink>)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn get_share_links(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Json<Vec<ShareLink>>> {
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let links = interact!(conn, move |conn| {
+        repository::share_link::get_share_links(conn, user_id)
+    })
+    .await??;
+    Ok(Json(links.iter().map(ShareLink::from).collect()))
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateShareLinkRequest {
+    pub target: ShareLinkTarget,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub password: Option<String>,
+    #[serde(default)]
+    pub allow_download: bool,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateShareLinkResponse {
+    pub share_link: ShareLink,
+    /// Goes into the `/api/share/{token}` URLs. Only returned here, it can not be retrieved later.
+    pub token: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/shares",
+    request_body = CreateShareLinkRequest,
+    responses(
+        (status = 200, body = CreateShareLinkResponse),
+        (status = BAD_REQUEST, description = "Expiry date in the past or empty password"),
+        (status = NOT_FOUND, description = "Nothing to share or not visible to the user")
+    ),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, request))]
+pub async fn create_share_link(
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<CreateShareLinkRequest>,
+) -> ApiResult<Response> {
+    let now = Utc::now();
+    if request
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= now)
+    {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            HttpError::from(eyre!("expiresAt must be in the future")),
+        )
+            .into_response());
+    }
+    if request.password.as_ref().is_some_and(|p| p.is_empty()) {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            HttpError::from(eyre!("password can not be empty")),
+        )
+            .into_response());
+    }
+    let target: model::ShareLinkTarget = request.target.try_into()?;
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let visible = interact!(conn, move |conn| {
+        repository::share_link::is_share_target_visible(conn, target, user_id)
+    })
+    .await??;
+    if !visible {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("nothing to share")),
+        )
+            .into_response());
+    }
+    let password_hash = match request.password {
+        Some(password) => Some(
+            tokio::task::spawn_blocking(move || auth::hash_password(&password))
+                .await
+                .wrap_err("error hashing password")??,
+        ),
+        None => None,
+    };
+    let token = auth::new_token();
+    let create_link = model::CreateShareLink {
+        target,
+        created_by: user_id,
+        token_hash: auth::hash_token(&token),
+        expires_at: request.expires_at,
+        password_hash,
+        allow_download: request.allow_download,
+    };
+    let conn = app_state.pool.get().await?;
+    let share_link = interact!(conn, move |conn| {
+        repository::share_link::delete_expired_share_links(conn, now)?;
+        let id = repository::share_link::create_share_link(conn, &create_link)?;
+        repository::share_link::get_share_links(conn, user_id)?
+            .into_iter()
+            .find(|link| link.id == id)
+            .ok_or(eyre!("created ShareLink does not exist"))
+    })
+    .await??;
+    Ok(Json(CreateShareLinkResponse {
+        share_link: ShareLink::from(&share_link),
+        token,
+    })
+    .into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/shares/{id}",
+    responses(
+        (status = 200, description = "Share link was deleted"),
+        (status = NOT_FOUND, description = "No such share link")
+    ),
+    params(
+        ("id" = String, Path, description = "ShareLinkId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn delete_share_link(
+    Path(id): Path<ShareLinkId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let link_id: model::ShareLinkId = id.try_into()?;
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let deleted = interact!(conn, move |conn| {
+        repository::share_link::delete_share_link(conn, user_id, link_id)
+    })
+    .await??;
+    if !deleted {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such share link")),
+        )
+            .into_response());
+    }
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedResponse {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub allow_download: bool,
+    pub assets: Vec<AssetWithSpe>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/share/{token}",
+    responses(
+        (status = 200, body = SharedResponse),
+        (status = UNAUTHORIZED, description = "The share link needs a password, see /api/share/{token}/unlock"),
+        (status = NOT_FOUND, description = "No such share link or it has expired")
+    ),
+    params(
+        ("token" = String, Path, description = "Share link token")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, token, headers))]
+pub async fn get_shared(
+    Path(token): Path<String>,
+    State(app_state): State<SharedState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    let link = match unlocked_share_link(&app_state, &token, &headers).await? {
+        Ok(link) => link,
+        Err(response) => return Ok(response),
+    };
+    let target = link.target;
+    let conn = app_state.pool.get().await?;
+    let (name, description, assets) = interact!(conn, move |conn| {
+        let (name, description) = match target {
+            model::ShareLinkTarget::Album(album_id) => {
+                let album = repository::album::get_album(conn, album_id)?;
+                (album.name, album.description)
+            }
+            model::ShareLinkTarget::TimelineGroup(group_id) => {
+                let group = repository::timeline_group::get_timeline_group(conn, group_id)?;
+                (group.name, None)
+            }
+            model::ShareLinkTarget::Asset(_) => (None, None),
+        };
+        let assets = repository::share_link::get_shared_assets(conn, target)?;
+        Ok::<_, eyre::Report>((name, description, assets))
+    })
+    .await??;
+    let mut api_assets = Vec::with_capacity(assets.len());
+    for asset in &assets {
+        api_assets.push(super::timeline::asset_with_spe(&app_state.pool, asset).await?);
+    }
+    Ok(Json(SharedResponse {
+        name,
+        description,
+        allow_download: link.allow_download,
+        assets: api_assets,
+    })
+    .into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UnlockShareLinkRequest {
+    pub password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/share/{token}/unlock",
+    request_body = UnlockShareLinkRequest,
+    responses(
+        (status = 200, description = "Password is correct, a cookie for this share link is set"),
+        (status = FORBIDDEN, description = "Wrong password"),
+        (status = NOT_FOUND, description = "No such share link or it has expired")
+    ),
+    params(
+        ("token" = String, Path, description = "Share link token")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, token, request))]
+pub async fn unlock_share_link(
+    Path(token): Path<String>,
+    State(app_state): State<SharedState>,
+    Json(request): Json<UnlockShareLinkRequest>,
+) -> ApiResult<Response> {
+    let Some(link) = find_share_link(&app_state, &token).await? else {
+        return Ok(share_link_not_found());
+    };
+    let Some(password_hash) = link.password_hash.clone() else {
+        return Ok(StatusCode::OK.into_response());
+    };
+    let password_ok = tokio::task::spawn_blocking(move || {
+        auth::verify_password(&request.password, &password_hash)
+    })
+    .await
+    .wrap_err("error verifying password")??;
+    if !password_ok {
+        return Ok((
+            StatusCode::FORBIDDEN,
+            HttpError::from(eyre!("wrong password")),
+        )
+            .into_response());
+    }
+    let cookie = unlock_cookie(&link, &token, &app_state.auth);
+    Ok([(SET_COOKIE, cookie)].into_response())
+}
+
+#[utoipa::path(get, path = "/api/share/{token}/thumbnail/{id}/{size}/{format}",
+    responses(
+        (status = 200, body=String, content_type = "application/octet"),
+        (status = UNAUTHORIZED, description = "The share link needs a password"),
+        (status = NOT_FOUND, description = "No such share link or the asset is not shared")
+    ),
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("id" = String, Path, description = "AssetId"),
+        ("size" = ThumbnailSize, Path, description = "Thumbnail size"),
+        ("format" = ThumbnailFormat, Path, description = "Image format for thumbnail")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, token, headers))]
+pub async fn get_shared_thumbnail(
+    Path((token, asset_id, size, format)): Path<(String, AssetId, ThumbnailSize, ThumbnailFormat)>,
+    State(app_state): State<SharedState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if let Err(response) =
+        check_asset_shared(&app_state, &token, &asset_id, &headers, false).await?
+    {
+        return Ok(response);
+    }
+    super::asset::get_thumbnail(Path((asset_id, size, format)), State(app_state), headers).await
+}
+
+#[utoipa::path(get, path = "/api/share/{token}/repr/{assetId}/{reprId}",
+    responses(
+        (status = 200, body=String, content_type = "application/octet"),
+        (status = UNAUTHORIZED, description = "The share link needs a password"),
+        (status = NOT_FOUND, description = "No such share link or the asset is not shared")
+    ),
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("assetId" = String, Path, description = "AssetId"),
+        ("reprId" = String, Path, description = "ImageRepresentationId"),
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, token, headers))]
+pub async fn get_shared_image_representation(
+    Path((token, asset_id, repr_id)): Path<(String, AssetId, ImageRepresentationId)>,
+    query: Query<HashMap<String, String>>,
+    State(app_state): State<SharedState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if let Err(response) =
+        check_asset_shared(&app_state, &token, &asset_id, &headers, false).await?
+    {
+        return Ok(response);
+    }
+    super::asset::get_image_asset_representation(
+        Path((asset_id, repr_id)),
+        query,
+        State(app_state),
+        headers,
+    )
+    .await
+}
+
+#[utoipa::path(get, path = "/api/share/{token}/original/{id}",
+    responses(
+        (status = 200, body=String, content_type = "application/octet"),
+        (status = UNAUTHORIZED, description = "The share link needs a password"),
+        (status = FORBIDDEN, description = "The share link does not allow downloads"),
+        (status = NOT_FOUND, description = "No such share link or the asset is not shared")
+    ),
+    params(
+        ("token" = String, Path, description = "Share link token"),
+        ("id" = String, Path, description = "AssetId"),
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, token, headers))]
+pub async fn get_shared_asset_file(
+    Path((token, asset_id)): Path<(String, AssetId)>,
+    query: Query<HashMap<String, String>>,
+    State(app_state): State<SharedState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if let Err(response) = check_asset_shared(&app_state, &token, &asset_id, &headers, true).await?
+    {
+        return Ok(response);
+    }
+    super::asset::get_asset_file(Path(asset_id), query, State(app_state), headers).await
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SharedDashFilePath {
+    pub token: String,
+    pub id: AssetId,
+    pub path: String,
+}
+
+#[tracing::instrument(fields(request = true), skip(app_state, path, headers))]
+pub async fn get_shared_dash_file(
+    Path(path): Path<SharedDashFilePath>,
+    State(app_state): State<SharedState>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    if let Err(response) =
+        check_asset_shared(&app_state, &path.token, &path.id, &headers, false).await?
+    {
+        return Ok(response);
+    }
+    let dash_path = DashFilePath {
+        id: path.id,
+        path: path.path,
+    };
+    super::dash::get_dash_file(Path(dash_path), State(app_state), headers).await
+}
+
+async fn find_share_link(app_state: &SharedState, token: &str) -> Result<Option<model::ShareLink>> {
+    let token_hash = auth::hash_token(token);
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::share_link::find_share_link_for_token(conn, &token_hash, Utc::now())
+    })
+    .await?
+}
+
+/// The share link for a token, if it exists, has not expired and its password was entered.
+/// Otherwise the response to send instead.
+async fn unlocked_share_link(
+    app_state: &SharedState,
+    token: &str,
+    headers: &HeaderMap,
+) -> ApiResult<Result<model::ShareLink, Response>> {
+    let Some(link) = find_share_link(app_state, token).await? else {
+        return Ok(Err(share_link_not_found()));
+    };
+    if link.password_hash.is_some() {
+        let cookie_name = format!("{}{}", SHARE_COOKIE_PREFIX, link.id.0);
+        let unlocked = auth::cookie_value(headers, &cookie_name)
+            .is_some_and(|value| value == unlock_cookie_value(&link, token));
+        if !unlocked {
+            return Ok(Err((
+                StatusCode::UNAUTHORIZED,
+                HttpError::from(eyre!("this share link needs a password")),
+            )
+                .into_response()));
+        }
+    }
+    Ok(Ok(link))
+}
+
+/// Like `unlocked_share_link`, but also checks that the asset is part of what is shared
+async fn check_asset_shared(
+    app_state: &SharedState,
+    token: &str,
+    asset_id: &AssetId,
+    headers: &HeaderMap,
+    is_download: bool,
+) -> ApiResult<Result<(), Response>> {
+    let link = match unlocked_share_link(app_state, token, headers).await? {
+        Ok(link) => link,
+        Err(response) => return Ok(Err(response)),
+    };
+    if is_download && !link.allow_download {
+        return Ok(Err((
+            StatusCode::FORBIDDEN,
+            HttpError::from(eyre!("this share link does not allow downloads")),
+        )
+            .into_response()));
+    }
+    let asset_id: model::AssetId = asset_id.try_into()?;
+    let target = link.target;
+    let conn = app_state.pool.get().await?;
+    let is_shared = interact!(conn, move |conn| {
+        repository::share_link::is_asset_shared(conn, target, asset_id)
+    })
+    .await??;
+    if !is_shared {
+        return Ok(Err(share_link_not_found()));
+    }
+    Ok(Ok(()))
+}
+
+fn share_link_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        HttpError::from(eyre!("no such share link")),
+    )
+        .into_response()
+}
+
+/// Derived from the password hash, which only the server knows and which changes
+/// with every new password, so the cookie does not need to be stored
+fn unlock_cookie_value(link: &model::ShareLink, token: &str) -> String {
+    let password_hash = link.password_hash.as_deref().unwrap_or_default();
+    auth::hash_token(&format!("{}:{}", token, password_hash))
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Only sent with requests for this share link, lasts until the browser is closed
+fn unlock_cookie(
+    link: &model::ShareLink,
+    token: &str,
+    config: &core::config::AuthConfig,
+) -> String {
+    let secure = if config.secure_cookies {
+        "; Secure"
+    } else {
+        ""
+    };
+    format!(
+        "{}{}={}; Path=/api/share/{}; HttpOnly; SameSite=Lax{}",
+        SHARE_COOKIE_PREFIX,
+        link.id.0,
+        unlock_cookie_value(link, token),
+        token,
+        secure
+    )
+}
@@ -1,7 +1,7 @@
 use axum::{
     extract::{Path, Query, State},
     routing::get,
-    Json, Router,
+    Extension, Json, Router,
 };
 use chrono::{DateTime, Utc};
 use eyre::{eyre, Context, Result};
@@ -12,6 +12,7 @@ use utoipa::{IntoParams, ToSchema};
 
 use crate::{
     app_state::SharedState,
+    auth::CurrentUser,
     http_error::ApiResult,
     schema::{
         asset::{AssetSpe, AssetWithSpe, Image, ImageRepresentation, Video},
@@ -25,6 +26,7 @@ use core::{
         self,
         repository::{
             self,
+            asset_place::PlaceFilter,
             db::DbPool,
             timeline::{AssetsInTimeline, TimelineElement, TimelineSegmentType},
         },
@@ -45,10 +47,33 @@ pub struct TimelineRequest {
     pub last_fetch: Option<String>,
 }
 
+/// Only include assets that were reverse geocoded to this place,
+/// values as in the place of the asset details.
+/// Section ids depend on the filter, so segments must be requested with the same one.
+#[derive(Debug, Clone, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaceFilterRequest {
+    /// ISO 3166-1 alpha-2 country code, e.g. CH
+    pub country_code: Option<String>,
+    /// State, province etc.
+    pub admin_region: Option<String>,
+    pub city: Option<String>,
+}
+
+impl From<PlaceFilterRequest> for PlaceFilter {
+    fn from(value: PlaceFilterRequest) -> Self {
+        PlaceFilter {
+            country_code: value.country_code,
+            admin_region: value.admin_region,
+            city: value.city,
+        }
+    }
+}
+
 #[utoipa::path(
     get,
     path = "/api/assets/timeline",
-    params(TimelineRequest),
+    params(TimelineRequest, PlaceFilterRequest),
     responses(
     (status = 200, body=TimelineChunk)
     )
@@ -56,18 +81,28 @@ pub struct TimelineRequest {
 #[instrument(skip(app_state))]
 pub async fn get_timeline(
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
     Query(req_body): Query<TimelineRequest>,
+    Query(place): Query<PlaceFilterRequest>,
 ) -> ApiResult<Json<TimelineChunk>> {
     debug!(?req_body);
+    let place: PlaceFilter = place.into();
     let local_tz = &chrono::Local; // TODO inject from config
     let now = Utc::now();
     let last_asset_id: Option<model::AssetId> = req_body
         .last_asset_id
         .map(model::AssetId::try_from)
         .transpose()?;
+    let user_id = current_user.user.id;
     let conn = app_state.pool.get().await?;
     let groups = interact!(conn, move |conn| {
-        repository::timeline::get_timeline_chunk(conn, last_asset_id, req_body.max_count.into())
+        repository::timeline::get_timeline_chunk(
+            conn,
+            user_id,
+            last_asset_id,
+            req_body.max_count.into(),
+            &place,
+        )
     })
     .await??;
     let filtered_nonempty_groups = groups.into_iter().filter(|group| match group {
@@ -139,6 +174,7 @@ pub struct TimelineSection {
 #[utoipa::path(
     get,
     path = "/api/timeline/sections",
+    params(PlaceFilterRequest),
     responses(
     (status = 200, body=TimelineSectionsResponse)
     )
@@ -146,10 +182,14 @@ pub struct TimelineSection {
 #[instrument(skip(app_state))]
 pub async fn get_timeline_sections(
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(place): Query<PlaceFilterRequest>,
 ) -> ApiResult<Json<TimelineSectionsResponse>> {
+    let user_id = current_user.user.id;
+    let place: PlaceFilter = place.into();
     let conn = app_state.pool.get().await?;
     let sections: Vec<TimelineSection> = interact!(conn, move |conn| {
-        repository::timeline::get_sections(conn)
+        repository::timeline::get_sections(conn, user_id, &place)
     })
     .await??
     .into_iter()
@@ -219,23 +259,34 @@ pub struct TimelineSegmentsResponse {
         (status = 200, body=TimelineSegmentsResponse)
     ),
     params(
-        ("id"=String, description="Section id")
+        ("id"=String, description="Section id"),
+        PlaceFilterRequest,
     )
 )]
 #[tracing::instrument(fields(request = true), skip(app_state))]
 pub async fn get_timeline_segments(
     Path(section_id): Path<String>,
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Query(place): Query<PlaceFilterRequest>,
 ) -> ApiResult<Json<TimelineSegmentsResponse>> {
     let (segment_min, segment_max) = section_id
         .split_once('_')
         .ok_or(eyre!("invalid sectionId"))?;
     let segment_min: i64 = segment_min.parse().wrap_err("invalid sectionId")?;
     let segment_max: i64 = segment_max.parse().wrap_err("invalid sectionId")?;
+    let place: PlaceFilter = place.into();
+    let user_id = current_user.user.id;
     let conn = app_state.pool.get().await?;
     let pool = &app_state.pool;
     let segments_result: Result<Vec<TimelineSegment>> = interact!(conn, move |conn| {
-        repository::timeline::get_segments_in_section(conn, segment_min, segment_max)
+        repository::timeline::get_segments_in_section(
+            conn,
+            user_id,
+            segment_min,
+            segment_max,
+            &place,
+        )
     })
     .await??
     .into_iter()
@@ -331,7 +382,7 @@ async fn asset_with_reprs(pool: DbPool, asset: model::Asset) -> eyre::Result<Ass
     })
 }
 
-async fn asset_with_spe(pool: &DbPool, asset: &model::Asset) -> eyre::Result<AssetWithSpe> {
+pub async fn asset_with_spe(pool: &DbPool, asset: &model::Asset) -> eyre::Result<AssetWithSpe> {
     let conn = pool.get().await?;
     match &asset.sp {
         model::AssetSpe::Image(_image) => {
@@ -4,9 +4,11 @@ use core::{
 };
 
 use axum::{
-    extract::State,
-    routing::{post, put},
-    Json, Router,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, patch, post, put},
+    Extension, Json, Router,
 };
 use chrono::{DateTime, Utc};
 use eyre::{eyre, Context, Result};
@@ -15,7 +17,8 @@ use utoipa::ToSchema;
 
 use crate::{
     app_state::SharedState,
-    http_error::ApiResult,
+    auth::CurrentUser,
+    http_error::{ApiResult, HttpError},
     schema::{AssetId, TimelineGroupId},
 };
 
@@ -23,6 +26,9 @@ pub fn router() -> Router<SharedState> {
     Router::new()
         .route("/", post(create_timeline_group))
         .route("/", put(add_to_timeline_group))
+        .route("/:id", patch(update_timeline_group))
+        .route("/:id", delete(delete_timeline_group))
+        .route("/:id/removeAssets", post(remove_from_timeline_group))
 }
 
 #[derive(Debug, Clone, Deserialize, ToSchema, PartialEq, Eq)]
@@ -30,6 +36,9 @@ pub fn router() -> Router<SharedState> {
 pub struct CreateTimelineGroupRequest {
     pub assets: Vec<AssetId>,
     pub name: String,
+    /// Private groups are only shown in the timeline of the user creating them
+    #[serde(default)]
+    pub is_private: bool,
 }
 
 #[derive(Debug, Clone, Serialize, ToSchema, PartialEq, Eq)]
@@ -48,6 +57,7 @@ pub struct CreateTimelineGroupResponse {
 #[tracing::instrument(fields(request = true), skip(app_state))]
 pub async fn create_timeline_group(
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
     Json(request): Json<CreateTimelineGroupRequest>,
 ) -> ApiResult<Json<CreateTimelineGroupResponse>> {
     if request.assets.is_empty() {
@@ -73,6 +83,8 @@ pub async fn create_timeline_group(
         name: Some(request.name),
         display_date,
         asset_ids,
+        owner_id: Some(current_user.user.id),
+        is_private: request.is_private,
     };
     let timeline_group_id = interact!(conn, move |conn| {
         repository::timeline_group::create_timeline_group(conn, create_timeline_group)
@@ -96,12 +108,16 @@ pub struct AddToTimelineGroupRequest {
     put,
     path = "/api/timelinegroups",
     request_body = AddToTimelineGroupRequest,
-    responses((status = 200)),
+    responses(
+        (status = 200),
+        (status = NOT_FOUND, description = "No such TimelineGroup or not visible to the user")
+    ),
 )]
 pub async fn add_to_timeline_group(
     State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
     Json(request): Json<AddToTimelineGroupRequest>,
-) -> ApiResult<()> {
+) -> ApiResult<Response> {
     if request.assets.is_empty() {
         return Err(eyre!("assetIds can not be empty").into());
     }
@@ -111,10 +127,198 @@ pub async fn add_to_timeline_group(
         .map(|id| id.try_into())
         .collect::<Result<Vec<_>>>()?;
     let group_id: model::TimelineGroupId = request.group_id.try_into()?;
+    if !is_group_visible(&app_state, group_id, &current_user).await? {
+        return Ok(group_not_found());
+    }
     let conn = app_state.pool.get().await?;
     interact!(conn, move |conn| {
         repository::timeline_group::add_assets_to_group(conn, group_id, &asset_ids)
     })
     .await??;
-    Ok(())
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateTimelineGroupRequest {
+    pub name: Option<String>,
+    /// Where the group is placed in the timeline.
+    /// Reset to the date of the newest asset when assets are added or removed.
+    pub display_date: Option<DateTime<Utc>>,
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/timelinegroups/{id}",
+    request_body = UpdateTimelineGroupRequest,
+    responses(
+        (status = 200),
+        (status = NOT_FOUND, description = "No such TimelineGroup or not visible to the user"),
+        (status = FORBIDDEN, description = "TimelineGroup belongs to another user")
+    ),
+    params(
+        ("id" = String, Path, description = "TimelineGroupId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn update_timeline_group(
+    Path(group_id): Path<TimelineGroupId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<UpdateTimelineGroupRequest>,
+) -> ApiResult<Response> {
+    if request.name.as_deref() == Some("") {
+        return Err(eyre!("name can not be empty").into());
+    }
+    let group_id: model::TimelineGroupId = group_id.try_into()?;
+    if !is_group_visible(&app_state, group_id, &current_user).await? {
+        return Ok(group_not_found());
+    }
+    if !may_change_group(&app_state, group_id, &current_user).await? {
+        return Ok(not_group_owner());
+    }
+    let update = repository::timeline_group::UpdateTimelineGroup {
+        name: request.name,
+        display_date: request.display_date,
+    };
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::timeline_group::update_timeline_group(conn, group_id, update)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveFromTimelineGroupRequest {
+    pub assets: Vec<AssetId>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RemoveFromTimelineGroupResponse {
+    /// No assets were left so the group was deleted
+    pub group_deleted: bool,
+}
+
+/// Assets not in the group are ignored
+#[utoipa::path(
+    post,
+    path = "/api/timelinegroups/{id}/removeAssets",
+    request_body = RemoveFromTimelineGroupRequest,
+    responses(
+        (status = 200, body = RemoveFromTimelineGroupResponse),
+        (status = NOT_FOUND, description = "No such TimelineGroup or not visible to the user"),
+        (status = FORBIDDEN, description = "TimelineGroup belongs to another user")
+    ),
+    params(
+        ("id" = String, Path, description = "TimelineGroupId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn remove_from_timeline_group(
+    Path(group_id): Path<TimelineGroupId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+    Json(request): Json<RemoveFromTimelineGroupRequest>,
+) -> ApiResult<Response> {
+    let asset_ids: Vec<model::AssetId> = request
+        .assets
+        .into_iter()
+        .map(|id| id.try_into())
+        .collect::<Result<Vec<_>>>()?;
+    let group_id: model::TimelineGroupId = group_id.try_into()?;
+    if !is_group_visible(&app_state, group_id, &current_user).await? {
+        return Ok(group_not_found());
+    }
+    if !may_change_group(&app_state, group_id, &current_user).await? {
+        return Ok(not_group_owner());
+    }
+    let conn = app_state.pool.get().await?;
+    let group_deleted = interact!(conn, move |conn| {
+        repository::timeline_group::remove_assets_from_group(conn, group_id, &asset_ids)
+    })
+    .await??;
+    Ok(Json(RemoveFromTimelineGroupResponse { group_deleted }).into_response())
+}
+
+/// Delete the group, its assets are grouped by day in the timeline again
+#[utoipa::path(
+    delete,
+    path = "/api/timelinegroups/{id}",
+    responses(
+        (status = 200),
+        (status = NOT_FOUND, description = "No such TimelineGroup or not visible to the user"),
+        (status = FORBIDDEN, description = "TimelineGroup belongs to another user")
+    ),
+    params(
+        ("id" = String, Path, description = "TimelineGroupId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn delete_timeline_group(
+    Path(group_id): Path<TimelineGroupId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let group_id: model::TimelineGroupId = group_id.try_into()?;
+    if !is_group_visible(&app_state, group_id, &current_user).await? {
+        return Ok(group_not_found());
+    }
+    if !may_change_group(&app_state, group_id, &current_user).await? {
+        return Ok(not_group_owner());
+    }
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::timeline_group::delete_timeline_group(conn, group_id)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn is_group_visible(
+    app_state: &SharedState,
+    group_id: model::TimelineGroupId,
+    current_user: &CurrentUser,
+) -> ApiResult<bool> {
+    let user_id = current_user.user.id;
+    let conn = app_state.pool.get().await?;
+    let visible = interact!(conn, move |conn| {
+        repository::timeline_group::is_timeline_group_visible(conn, group_id, user_id)
+    })
+    .await??;
+    Ok(visible)
+}
+
+/// Only the owner and admins can change a TimelineGroup,
+/// TimelineGroups created before there were users belong to everyone
+async fn may_change_group(
+    app_state: &SharedState,
+    group_id: model::TimelineGroupId,
+    current_user: &CurrentUser,
+) -> ApiResult<bool> {
+    let conn = app_state.pool.get().await?;
+    let group = interact!(conn, move |conn| {
+        repository::timeline_group::get_timeline_group(conn, group_id)
+    })
+    .await??;
+    let is_owner = group.owner_id.is_none() || group.owner_id == Some(current_user.user.id);
+    Ok(is_owner || current_user.is_admin())
+}
+
+fn not_group_owner() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        HttpError::from(eyre!("only the owner can change a TimelineGroup")),
+    )
+        .into_response()
+}
+
+fn group_not_found() -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        HttpError::from(eyre!("no such TimelineGroup")),
+    )
+        .into_response()
 }
@@ -0,0 +1,204 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use eyre::{eyre, Context};
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use core::{
+    deadpool_diesel, interact,
+    model::{self, repository},
+};
+
+use crate::{
+    app_state::SharedState,
+    auth::{self, CurrentUser},
+    http_error::{ApiResult, HttpError},
+    schema::{
+        user::{User, UserRole},
+        UserId,
+    },
+};
+
+/// Only for admins
+pub fn router() -> Router<SharedState> {
+    Router::new()
+        .route("/", get(get_users))
+        .route("/", post(create_user))
+        .route("/:id", delete(delete_user))
+        .route("/:id/password", post(set_user_password))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/users",
+    responses((status = 200, body = Vec<User>)),
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn get_users(State(app_state): State<SharedState>) -> ApiResult<Json<Vec<User>>> {
+    let conn = app_state.pool.get().await?;
+    let users = interact!(conn, move |conn| { repository::user::get_users(conn) }).await??;
+    Ok(Json(users.iter().map(User::from).collect()))
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateUserRequest {
+    pub name: String,
+    pub password: String,
+    pub role: UserRole,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users",
+    request_body = CreateUserRequest,
+    responses(
+        (status = 200, body = User),
+        (status = BAD_REQUEST, description = "Invalid user name or password"),
+        (status = CONFLICT, description = "A user with this name already exists")
+    ),
+)]
+#[tracing::instrument(fields(request = true, name = request.name), skip(app_state, request))]
+pub async fn create_user(
+    State(app_state): State<SharedState>,
+    Json(request): Json<CreateUserRequest>,
+) -> ApiResult<Response> {
+    let validated =
+        auth::validate_user_name(&request.name).and(auth::validate_password(&request.password));
+    if let Err(err) = validated {
+        return Ok((StatusCode::BAD_REQUEST, HttpError::from(err)).into_response());
+    }
+    let conn = app_state.pool.get().await?;
+    let name = request.name.clone();
+    let exists = interact!(conn, move |conn| {
+        repository::user::user_with_name_exists(conn, &name)
+    })
+    .await??;
+    if exists {
+        return Ok((
+            StatusCode::CONFLICT,
+            HttpError::from(eyre!("a user with this name already exists")),
+        )
+            .into_response());
+    }
+    let user_id = auth::create_user(
+        &app_state.pool,
+        request.name,
+        &request.password,
+        request.role.into(),
+    )
+    .await?;
+    let conn = app_state.pool.get().await?;
+    let user = interact!(conn, move |conn| {
+        repository::user::find_user(conn, user_id)
+    })
+    .await??
+    .ok_or(eyre!("created user does not exist"))?;
+    Ok(Json(User::from(&user)).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/users/{id}",
+    responses(
+        (status = 200, description = "User and their sessions and API tokens were deleted"),
+        (status = NOT_FOUND, description = "No such user"),
+        (status = CONFLICT, description = "Admins can not delete themselves")
+    ),
+    params(
+        ("id" = String, Path, description = "UserId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state))]
+pub async fn delete_user(
+    Path(id): Path<UserId>,
+    State(app_state): State<SharedState>,
+    Extension(current_user): Extension<CurrentUser>,
+) -> ApiResult<Response> {
+    let user_id: model::UserId = id.try_into()?;
+    // this also makes sure there is always an admin left
+    if user_id == current_user.user.id {
+        return Ok((
+            StatusCode::CONFLICT,
+            HttpError::from(eyre!("can not delete yourself")),
+        )
+            .into_response());
+    }
+    let conn = app_state.pool.get().await?;
+    let user = interact!(conn, move |conn| {
+        repository::user::find_user(conn, user_id)
+    })
+    .await??;
+    if user.is_none() {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such user")),
+        )
+            .into_response());
+    }
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::user::delete_user(conn, user_id)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SetUserPasswordRequest {
+    pub password: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/users/{id}/password",
+    request_body = SetUserPasswordRequest,
+    responses(
+        (status = 200, description = "Password changed, the user's sessions were logged out"),
+        (status = BAD_REQUEST, description = "The password is not allowed"),
+        (status = NOT_FOUND, description = "No such user")
+    ),
+    params(
+        ("id" = String, Path, description = "UserId")
+    )
+)]
+#[tracing::instrument(fields(request = true), skip(app_state, request))]
+pub async fn set_user_password(
+    Path(id): Path<UserId>,
+    State(app_state): State<SharedState>,
+    Json(request): Json<SetUserPasswordRequest>,
+) -> ApiResult<Response> {
+    let user_id: model::UserId = id.try_into()?;
+    if let Err(err) = auth::validate_password(&request.password) {
+        return Ok((StatusCode::BAD_REQUEST, HttpError::from(err)).into_response());
+    }
+    let conn = app_state.pool.get().await?;
+    let user = interact!(conn, move |conn| {
+        repository::user::find_user(conn, user_id)
+    })
+    .await??;
+    if user.is_none() {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            HttpError::from(eyre!("no such user")),
+        )
+            .into_response());
+    }
+    let password = request.password;
+    let password_hash = tokio::task::spawn_blocking(move || auth::hash_password(&password))
+        .await
+        .wrap_err("error hashing password")??;
+    let conn = app_state.pool.get().await?;
+    interact!(conn, move |conn| {
+        repository::user::set_user_password(conn, user_id, &password_hash)?;
+        repository::user::delete_user_sessions(conn, user_id, None)
+    })
+    .await??;
+    Ok(StatusCode::OK.into_response())
+}
@@ -1,10 +1,11 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use eyre::eyre;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use core::model;
 
-use super::AlbumId;
+use super::{asset::AssetType, AlbumId, AssetRootDirId, UserId};
 
 #[derive(Debug, Clone, Serialize, PartialEq, Eq, Hash, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -15,6 +16,12 @@ pub struct Album {
     pub num_assets: i64,
     pub created_at: DateTime<Utc>,
     pub changed_at: DateTime<Utc>,
+    /// None for albums created before there were users
+    pub owner_id: Option<UserId>,
+    /// Only visible to the owner
+    pub is_private: bool,
+    /// Items of smart albums are the assets matching a filter and can not be edited
+    pub is_smart: bool,
 }
 
 impl Album {
@@ -26,6 +33,85 @@ impl Album {
             num_assets,
             created_at: value.created_at,
             changed_at: value.changed_at,
+            owner_id: value.owner_id.map(UserId::from),
+            is_private: value.is_private,
+            is_smart: value.smart_filter.is_some(),
         }
     }
 }
+
+/// Assets must match all of the given conditions to be in a smart album
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SmartAlbumFilter {
+    pub taken_after: Option<DateTime<Utc>>,
+    pub taken_before: Option<DateTime<Utc>>,
+    pub root_dir_id: Option<AssetRootDirId>,
+    /// Prefix of the path in the root dir
+    pub path_prefix: Option<String>,
+    /// EXIF Make, compared case insensitively
+    pub camera_make: Option<String>,
+    pub asset_type: Option<AssetType>,
+    pub location: Option<LocationRadius>,
+    pub has_gps: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationRadius {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius_meters: f64,
+}
+
+impl From<&model::SmartAlbumFilter> for SmartAlbumFilter {
+    fn from(value: &model::SmartAlbumFilter) -> Self {
+        SmartAlbumFilter {
+            taken_after: value.taken_after,
+            taken_before: value.taken_before,
+            root_dir_id: value.root_dir_id.map(AssetRootDirId::from),
+            path_prefix: value.path_prefix.clone(),
+            camera_make: value.camera_make.clone(),
+            asset_type: value.asset_type.map(AssetType::from),
+            location: value.location.map(|location| LocationRadius {
+                latitude: location.lat as f64 / 10e8,
+                longitude: location.lon as f64 / 10e8,
+                radius_meters: location.radius_meters as f64,
+            }),
+            has_gps: value.has_gps,
+        }
+    }
+}
+
+impl TryFrom<SmartAlbumFilter> for model::SmartAlbumFilter {
+    type Error = eyre::Report;
+
+    fn try_from(value: SmartAlbumFilter) -> Result<Self, Self::Error> {
+        let location = value
+            .location
+            .map(|location| {
+                if !(-90.0..=90.0).contains(&location.latitude)
+                    || !(-180.0..=180.0).contains(&location.longitude)
+                    || !(0.0..).contains(&location.radius_meters)
+                {
+                    return Err(eyre!("invalid location"));
+                }
+                Ok(model::LocationRadius {
+                    lat: (location.latitude * 10e8) as i64,
+                    lon: (location.longitude * 10e8) as i64,
+                    radius_meters: location.radius_meters.round() as i64,
+                })
+            })
+            .transpose()?;
+        Ok(model::SmartAlbumFilter {
+            taken_after: value.taken_after,
+            taken_before: value.taken_before,
+            root_dir_id: value.root_dir_id.map(|id| id.try_into()).transpose()?,
+            path_prefix: value.path_prefix,
+            camera_make: value.camera_make,
+            asset_type: value.asset_type.map(model::AssetType::from),
+            location,
+            has_gps: value.has_gps,
+        })
+    }
+}
@@ -1,5 +1,5 @@
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
 use core::model;
@@ -9,7 +9,7 @@ use crate::mime_type::guess_mime_type;
 
 use super::{AssetId, AssetRootDirId};
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub enum AssetType {
     Image,
@@ -68,6 +68,29 @@ pub struct Video {
     pub has_dash: bool,
 }
 
+/// Visually similar Assets
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NearDuplicateGroup {
+    pub id: AssetId,
+    pub assets: Vec<Asset>,
+    /// The copy to keep: the highest resolution, then the largest file, then the oldest
+    pub suggested_best: AssetId,
+}
+
+/// Where the asset was taken, from reverse geocoding its GPS coordinates
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetPlace {
+    /// ISO 3166-1 alpha-2 country code
+    pub country_code: String,
+    pub country: String,
+    /// State, province etc., if the country has them
+    pub admin_region: Option<String>,
+    /// Nearest city, town or village
+    pub city: String,
+}
+
 impl From<&model::Asset> for Asset {
     fn from(value: &model::Asset) -> Self {
         let mime_type = guess_mime_type(&value.base.file_type)
@@ -114,3 +137,24 @@ impl From<model::AssetType> for AssetType {
         }
     }
 }
+
+impl From<model::AssetPlace> for AssetPlace {
+    fn from(value: model::AssetPlace) -> Self {
+        AssetPlace {
+            country_code: value.country_code,
+            country: value.country,
+            admin_region: value.admin_region,
+            city: value.city,
+        }
+    }
+}
+
+impl From<model::NearDuplicateGroup> for NearDuplicateGroup {
+    fn from(value: model::NearDuplicateGroup) -> Self {
+        NearDuplicateGroup {
+            id: value.id.into(),
+            assets: value.assets.into_iter().map(Asset::from).collect(),
+            suggested_best: value.suggested_best.into(),
+        }
+    }
+}
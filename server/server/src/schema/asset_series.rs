@@ -0,0 +1,28 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use core::model;
+
+use super::{asset::Asset, AssetSeriesId};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetSeries {
+    pub id: AssetSeriesId,
+    /// Created by series detection
+    pub is_auto: bool,
+    /// assets[0] is most recent, last is oldest asset
+    pub assets: Vec<Asset>,
+    pub selection_indices: Vec<usize>,
+}
+
+impl From<model::AssetSeries> for AssetSeries {
+    fn from(value: model::AssetSeries) -> Self {
+        AssetSeries {
+            id: value.id.into(),
+            is_auto: value.is_auto,
+            assets: value.assets.into_iter().map(Asset::from).collect(),
+            selection_indices: value.selection_indices,
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use core::model;
+
+use super::{AssetId, AssetRootDirId, DuplicateAssetId};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetFilePath {
+    pub asset_root_id: AssetRootDirId,
+    pub path_in_root: String,
+}
+
+/// Another file with the same content as an Asset's file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateAsset {
+    pub id: DuplicateAssetId,
+    pub asset_root_id: AssetRootDirId,
+    pub path_in_root: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetDuplicates {
+    /// The file served as the original
+    pub primary: AssetFilePath,
+    pub duplicates: Vec<DuplicateAsset>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicatesReport {
+    pub duplicate_count: i64,
+    pub wasted_bytes: i64,
+    /// Most wasted bytes first
+    pub root_dirs: Vec<RootDirDuplicates>,
+    /// Most wasted bytes first
+    pub assets: Vec<AssetDuplicatesSize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RootDirDuplicates {
+    pub asset_root_id: AssetRootDirId,
+    pub duplicate_count: i64,
+    pub wasted_bytes: i64,
+    /// Duplicates of Assets whose file size is not known yet, not counted in wastedBytes
+    pub unknown_size_count: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetDuplicatesSize {
+    pub asset_id: AssetId,
+    pub duplicate_count: i64,
+    pub wasted_bytes: Option<i64>,
+}
+
+impl From<model::DuplicateAsset> for DuplicateAsset {
+    fn from(value: model::DuplicateAsset) -> Self {
+        DuplicateAsset {
+            id: value.id.into(),
+            asset_root_id: value.root_dir_id.into(),
+            path_in_root: value.file_path.to_string(),
+        }
+    }
+}
+
+impl From<model::DuplicatesReport> for DuplicatesReport {
+    fn from(value: model::DuplicatesReport) -> Self {
+        DuplicatesReport {
+            duplicate_count: value.root_dirs.iter().map(|r| r.duplicate_count).sum(),
+            wasted_bytes: value.root_dirs.iter().map(|r| r.wasted_bytes).sum(),
+            root_dirs: value
+                .root_dirs
+                .into_iter()
+                .map(|r| RootDirDuplicates {
+                    asset_root_id: r.root_dir_id.into(),
+                    duplicate_count: r.duplicate_count,
+                    wasted_bytes: r.wasted_bytes,
+                    unknown_size_count: r.unknown_size_count,
+                })
+                .collect(),
+            assets: value
+                .assets
+                .into_iter()
+                .map(|a| AssetDuplicatesSize {
+                    asset_id: a.asset_id.into(),
+                    duplicate_count: a.duplicate_count,
+                    wasted_bytes: a.wasted_bytes,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use core::model;
+
+use super::{AssetId, FailedJobId};
+
+/// Work for an asset that failed and is retried automatically after `retryAfter`,
+/// or earlier if the file or the external program that failed changes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedJob {
+    pub id: FailedJobId,
+    pub asset_id: AssetId,
+    pub kind: FailedJobKind,
+    pub error: String,
+    /// External program that failed, if the failure came from one
+    pub tool: Option<Tool>,
+    pub command: Option<String>,
+    /// End of what the program wrote to stderr
+    pub stderr: Option<String>,
+    pub attempts: i32,
+    pub first_failed_at: DateTime<Utc>,
+    pub last_failed_at: DateTime<Utc>,
+    pub retry_after: DateTime<Utc>,
+    pub is_ignored: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum FailedJobKind {
+    Thumbnail,
+    VideoPackaging,
+    ReverseGeocode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum Tool {
+    Ffmpeg,
+    ShakaPackager,
+    MpdGenerator,
+}
+
+impl From<&model::FailedJob> for FailedJob {
+    fn from(value: &model::FailedJob) -> Self {
+        FailedJob {
+            id: value.id.into(),
+            asset_id: value.asset_id.into(),
+            kind: value.kind.into(),
+            error: value.failure.error.clone(),
+            tool: value.failure.tool.map(|tool| tool.into()),
+            command: value.failure.command.clone(),
+            stderr: value.failure.stderr.clone(),
+            attempts: value.attempts,
+            first_failed_at: value.first_failed_at,
+            last_failed_at: value.last_failed_at,
+            retry_after: value.retry_after,
+            is_ignored: value.is_ignored,
+        }
+    }
+}
+
+impl From<model::FailedJobKind> for FailedJobKind {
+    fn from(value: model::FailedJobKind) -> Self {
+        match value {
+            model::FailedJobKind::Thumbnail => FailedJobKind::Thumbnail,
+            model::FailedJobKind::VideoPackaging => FailedJobKind::VideoPackaging,
+            model::FailedJobKind::ReverseGeocode => FailedJobKind::ReverseGeocode,
+        }
+    }
+}
+
+impl From<FailedJobKind> for model::FailedJobKind {
+    fn from(value: FailedJobKind) -> Self {
+        match value {
+            FailedJobKind::Thumbnail => model::FailedJobKind::Thumbnail,
+            FailedJobKind::VideoPackaging => model::FailedJobKind::VideoPackaging,
+            FailedJobKind::ReverseGeocode => model::FailedJobKind::ReverseGeocode,
+        }
+    }
+}
+
+impl From<model::Tool> for Tool {
+    fn from(value: model::Tool) -> Self {
+        match value {
+            model::Tool::FFmpeg => Tool::Ffmpeg,
+            model::Tool::ShakaPackager => Tool::ShakaPackager,
+            model::Tool::MpdGenerator => Tool::MpdGenerator,
+        }
+    }
+}
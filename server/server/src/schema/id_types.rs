@@ -57,6 +57,18 @@ pub struct ImageRepresentationId(pub String);
 pub struct TimelineGroupId(pub String);
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, ToSchema)]
 pub struct AssetSeriesId(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct JobId(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct FailedJobId(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct UserId(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct UserTokenId(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct ShareLinkId(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Hash, ToSchema)]
+pub struct DuplicateAssetId(pub String);
 
 impl_api_id!(AlbumId);
 impl_api_id!(AlbumItemId);
@@ -65,3 +77,9 @@ impl_api_id!(AssetRootDirId);
 impl_api_id!(ImageRepresentationId);
 impl_api_id!(TimelineGroupId);
 impl_api_id!(AssetSeriesId);
+impl_api_id!(JobId);
+impl_api_id!(FailedJobId);
+impl_api_id!(UserId);
+impl_api_id!(UserTokenId);
+impl_api_id!(ShareLinkId);
+impl_api_id!(DuplicateAssetId);
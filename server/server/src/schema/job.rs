@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use core::model;
+
+use super::{AlbumId, AssetId, AssetRootDirId, JobId};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: JobId,
+    pub kind: JobKind,
+    pub state: JobState,
+    #[serde(flatten)]
+    pub subject: JobSubject,
+    pub progress: Option<JobProgress>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum JobKind {
+    IndexAssetRoot,
+    IndexPaths,
+    ReconcilePaths,
+    AssetThumbnail,
+    AlbumThumbnail,
+    PackageVideo,
+    ConvertImage,
+    ReverseGeocode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Finished,
+    Failed,
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "subjectType")]
+pub enum JobSubject {
+    #[serde(rename_all = "camelCase")]
+    Asset { asset_id: AssetId },
+    #[serde(rename_all = "camelCase")]
+    Album {
+        album_id: AlbumId,
+        asset_id: AssetId,
+    },
+    #[serde(rename_all = "camelCase")]
+    AssetRoot { asset_root_id: AssetRootDirId },
+}
+
+/// `done` and `total` are files scanned for indexing and milliseconds of video for
+/// video packaging. `total` is missing if it is not known.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct JobProgress {
+    pub done: i64,
+    pub total: Option<i64>,
+}
+
+impl From<&model::Job> for Job {
+    fn from(value: &model::Job) -> Self {
+        Job {
+            id: value.id.into(),
+            kind: value.kind.into(),
+            state: value.state.into(),
+            subject: value.subject.into(),
+            progress: value.progress.map(|progress| JobProgress {
+                done: progress.done,
+                total: progress.total,
+            }),
+            error: value.error.clone(),
+            created_at: value.created_at,
+            started_at: value.started_at,
+            finished_at: value.finished_at,
+        }
+    }
+}
+
+impl From<model::JobKind> for JobKind {
+    fn from(value: model::JobKind) -> Self {
+        match value {
+            model::JobKind::IndexAssetRoot => JobKind::IndexAssetRoot,
+            model::JobKind::IndexPaths => JobKind::IndexPaths,
+            model::JobKind::ReconcilePaths => JobKind::ReconcilePaths,
+            model::JobKind::AssetThumbnail => JobKind::AssetThumbnail,
+            model::JobKind::AlbumThumbnail => JobKind::AlbumThumbnail,
+            model::JobKind::PackageVideo => JobKind::PackageVideo,
+            model::JobKind::ConvertImage => JobKind::ConvertImage,
+            model::JobKind::ReverseGeocode => JobKind::ReverseGeocode,
+        }
+    }
+}
+
+impl From<model::JobState> for JobState {
+    fn from(value: model::JobState) -> Self {
+        match value {
+            model::JobState::Queued => JobState::Queued,
+            model::JobState::Running => JobState::Running,
+            model::JobState::Finished => JobState::Finished,
+            model::JobState::Failed => JobState::Failed,
+            model::JobState::Cancelled => JobState::Cancelled,
+        }
+    }
+}
+
+impl From<JobState> for model::JobState {
+    fn from(value: JobState) -> Self {
+        match value {
+            JobState::Queued => model::JobState::Queued,
+            JobState::Running => model::JobState::Running,
+            JobState::Finished => model::JobState::Finished,
+            JobState::Failed => model::JobState::Failed,
+            JobState::Cancelled => model::JobState::Cancelled,
+        }
+    }
+}
+
+impl From<model::JobSubject> for JobSubject {
+    fn from(value: model::JobSubject) -> Self {
+        match value {
+            model::JobSubject::Asset(asset_id) => JobSubject::Asset {
+                asset_id: asset_id.into(),
+            },
+            model::JobSubject::Album { album_id, asset_id } => JobSubject::Album {
+                album_id: album_id.into(),
+                asset_id: asset_id.into(),
+            },
+            model::JobSubject::AssetRootDir(root_dir_id) => JobSubject::AssetRoot {
+                asset_root_id: root_dir_id.into(),
+            },
+        }
+    }
+}
@@ -1,8 +1,14 @@
 pub mod album;
 pub mod asset;
 mod asset_root_dir;
+pub mod asset_series;
+pub mod duplicate_asset;
+pub mod failed_job;
 pub mod id_types;
+pub mod job;
+pub mod share_link;
 pub mod timeline;
+pub mod user;
 pub use album::*;
 pub use asset_root_dir::*;
 pub use id_types::*;
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use core::model;
+
+use super::{AlbumId, AssetId, ShareLinkId, TimelineGroupId};
+
+/// A share link, without the token which is only returned once when it is created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLink {
+    pub id: ShareLinkId,
+    pub target: ShareLinkTarget,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub has_password: bool,
+    pub allow_download: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase", tag = "type")]
+pub enum ShareLinkTarget {
+    #[serde(rename_all = "camelCase")]
+    Album { album_id: AlbumId },
+    #[serde(rename_all = "camelCase")]
+    TimelineGroup { timeline_group_id: TimelineGroupId },
+    #[serde(rename_all = "camelCase")]
+    Asset { asset_id: AssetId },
+}
+
+impl From<&model::ShareLink> for ShareLink {
+    fn from(value: &model::ShareLink) -> Self {
+        ShareLink {
+            id: value.id.into(),
+            target: value.target.into(),
+            created_at: value.created_at,
+            expires_at: value.expires_at,
+            has_password: value.password_hash.is_some(),
+            allow_download: value.allow_download,
+        }
+    }
+}
+
+impl From<model::ShareLinkTarget> for ShareLinkTarget {
+    fn from(value: model::ShareLinkTarget) -> Self {
+        match value {
+            model::ShareLinkTarget::Album(id) => ShareLinkTarget::Album {
+                album_id: id.into(),
+            },
+            model::ShareLinkTarget::TimelineGroup(id) => ShareLinkTarget::TimelineGroup {
+                timeline_group_id: id.into(),
+            },
+            model::ShareLinkTarget::Asset(id) => ShareLinkTarget::Asset {
+                asset_id: id.into(),
+            },
+        }
+    }
+}
+
+impl TryFrom<ShareLinkTarget> for model::ShareLinkTarget {
+    type Error = eyre::Report;
+
+    fn try_from(value: ShareLinkTarget) -> Result<Self, Self::Error> {
+        Ok(match value {
+            ShareLinkTarget::Album { album_id } => {
+                model::ShareLinkTarget::Album(album_id.try_into()?)
+            }
+            ShareLinkTarget::TimelineGroup { timeline_group_id } => {
+                model::ShareLinkTarget::TimelineGroup(timeline_group_id.try_into()?)
+            }
+            ShareLinkTarget::Asset { asset_id } => {
+                model::ShareLinkTarget::Asset(asset_id.try_into()?)
+            }
+        })
+    }
+}
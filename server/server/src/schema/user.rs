@@ -0,0 +1,73 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use core::model;
+
+use super::{UserId, UserTokenId};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub id: UserId,
+    pub name: String,
+    pub role: UserRole,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub enum UserRole {
+    Admin,
+    User,
+}
+
+/// An API token, without the token itself which is only returned once when it is created
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiToken {
+    pub id: UserTokenId,
+    pub name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<&model::User> for User {
+    fn from(value: &model::User) -> Self {
+        User {
+            id: value.id.into(),
+            name: value.name.clone(),
+            role: value.role.into(),
+            created_at: value.created_at,
+        }
+    }
+}
+
+impl From<model::UserRole> for UserRole {
+    fn from(value: model::UserRole) -> Self {
+        match value {
+            model::UserRole::Admin => UserRole::Admin,
+            model::UserRole::User => UserRole::User,
+        }
+    }
+}
+
+impl From<UserRole> for model::UserRole {
+    fn from(value: UserRole) -> Self {
+        match value {
+            UserRole::Admin => model::UserRole::Admin,
+            UserRole::User => model::UserRole::User,
+        }
+    }
+}
+
+impl From<&model::UserToken> for ApiToken {
+    fn from(value: &model::UserToken) -> Self {
+        ApiToken {
+            id: value.id.into(),
+            name: value.name.clone(),
+            created_at: value.created_at,
+            last_used_at: value.last_used_at,
+        }
+    }
+}
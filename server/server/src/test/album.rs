@@ -0,0 +1,227 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::Response,
+    Extension, Json,
+};
+use camino::Utf8Path;
+use tempfile::TempDir;
+
+use core::{
+    config::AuthConfig,
+    core::{
+        scheduler::SchedulerHandle,
+        storage::{LocalFileStorage, Storage},
+    },
+    deadpool_diesel, interact,
+    model::{
+        self,
+        repository::{
+            self,
+            album::CreateAlbum,
+            db::{migrate, open_db_pool},
+        },
+        UserRole,
+    },
+};
+
+use crate::{
+    app_state::{AppState, SharedState},
+    auth::CurrentUser,
+    http_error::ApiResult,
+    routes::album::{
+        append_assets_to_album, delete_album, delete_album_items, insert_album_items,
+        move_album_items, update_album, AppendAssetsRequest, DeleteAlbumItemRequest,
+        InsertAlbumItem, InsertAlbumItemsRequest, MoveAlbumItemsRequest, UpdateAlbumRequest,
+    },
+    schema::AlbumId,
+};
+
+struct TestState {
+    _dir: TempDir,
+    app_state: SharedState,
+}
+
+async fn setup() -> TestState {
+    let dir = tempfile::tempdir().unwrap();
+    let dir_path = Utf8Path::from_path(dir.path()).unwrap();
+    let pool = open_db_pool(dir_path.join("myrti.db").as_str()).unwrap();
+    let conn = pool.get().await.unwrap();
+    conn.interact(migrate).await.unwrap().unwrap();
+    // nothing is scheduled by the album routes
+    let (send, _) = tokio::sync::mpsc::channel(1);
+    let app_state = Arc::new(AppState {
+        pool,
+        storage: Storage::LocalFileStorage(LocalFileStorage::new(dir_path.join("storage"))),
+        scheduler: SchedulerHandle { send },
+        auth: AuthConfig::default(),
+    });
+    TestState {
+        _dir: dir,
+        app_state,
+    }
+}
+
+async fn create_user(app_state: &SharedState, name: &'static str, role: UserRole) -> CurrentUser {
+    let conn = app_state.pool.get().await.unwrap();
+    let user = interact!(conn, move |conn| {
+        let user_id = repository::user::create_user(conn, name, "hash", role)?;
+        repository::user::find_user(conn, user_id)
+    })
+    .await
+    .unwrap()
+    .unwrap()
+    .unwrap();
+    CurrentUser { user, token: None }
+}
+
+async fn create_album(app_state: &SharedState, owner: &CurrentUser) -> model::AlbumId {
+    let owner_id = owner.user.id;
+    let conn = app_state.pool.get().await.unwrap();
+    interact!(conn, move |conn| {
+        repository::album::create_album(
+            conn,
+            CreateAlbum {
+                name: Some("album".to_owned()),
+                description: None,
+                owner_id: Some(owner_id),
+                is_private: false,
+                smart_filter: None,
+            },
+            &[],
+        )
+    })
+    .await
+    .unwrap()
+    .unwrap()
+}
+
+fn status(result: ApiResult<Response>) -> StatusCode {
+    result.unwrap().status()
+}
+
+/// Calls every handler that changes an album and returns their status codes
+async fn change_album(
+    app_state: &SharedState,
+    album_id: model::AlbumId,
+    user: &CurrentUser,
+) -> Vec<(&'static str, StatusCode)> {
+    let id = || Path(AlbumId::from(&album_id));
+    let state = || State(app_state.clone());
+    let current_user = || Extension(user.clone());
+    vec![
+        (
+            "update",
+            status(
+                update_album(
+                    id(),
+                    state(),
+                    current_user(),
+                    Json(UpdateAlbumRequest {
+                        name: Some("renamed".to_owned()),
+                        description: None,
+                        smart_filter: None,
+                    }),
+                )
+                .await,
+            ),
+        ),
+        (
+            "append",
+            status(
+                append_assets_to_album(
+                    id(),
+                    state(),
+                    current_user(),
+                    Json(AppendAssetsRequest {
+                        asset_ids: Vec::new(),
+                    }),
+                )
+                .await,
+            ),
+        ),
+        (
+            "insert",
+            status(
+                insert_album_items(
+                    id(),
+                    state(),
+                    current_user(),
+                    Json(InsertAlbumItemsRequest {
+                        items: vec![InsertAlbumItem::Text {
+                            text: "text".to_owned(),
+                        }],
+                        position: None,
+                    }),
+                )
+                .await,
+            ),
+        ),
+        (
+            "move",
+            status(
+                move_album_items(
+                    id(),
+                    state(),
+                    current_user(),
+                    Json(MoveAlbumItemsRequest {
+                        item_ids: Vec::new(),
+                        position: 0,
+                    }),
+                )
+                .await,
+            ),
+        ),
+        (
+            "deleteItems",
+            status(
+                delete_album_items(
+                    id(),
+                    state(),
+                    current_user(),
+                    Json(DeleteAlbumItemRequest {
+                        item_ids: Vec::new(),
+                    }),
+                )
+                .await,
+            ),
+        ),
+        (
+            "delete",
+            status(delete_album(id(), state(), current_user()).await),
+        ),
+    ]
+}
+
+#[tokio::test]
+async fn only_owner_or_admin_can_change_album() {
+    let test_state = setup().await;
+    let app_state = &test_state.app_state;
+    let owner = create_user(app_state, "owner", UserRole::User).await;
+    let other = create_user(app_state, "other", UserRole::User).await;
+    let admin = create_user(app_state, "admin", UserRole::Admin).await;
+
+    let album_id = create_album(app_state, &owner).await;
+    for (handler, status) in change_album(app_state, album_id, &other).await {
+        assert_eq!(status, StatusCode::FORBIDDEN, "{}", handler);
+    }
+    let album = {
+        let conn = app_state.pool.get().await.unwrap();
+        interact!(conn, move |conn| {
+            repository::album::get_album(conn, album_id)
+        })
+        .await
+        .unwrap()
+        .unwrap()
+    };
+    assert_eq!(album.name.as_deref(), Some("album"));
+
+    for user in [&owner, &admin] {
+        let album_id = create_album(app_state, &owner).await;
+        for (handler, status) in change_album(app_state, album_id, user).await {
+            assert_eq!(status, StatusCode::OK, "{} by {}", handler, user.user.name);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+use std::io::{Cursor, Read};
+
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::archive::{ArchiveFormat, ArchiveWriter};
+
+fn modified() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 10).unwrap()
+}
+
+/// Longer than the 100 bytes a tar header has room for
+fn long_name() -> String {
+    let name = format!(
+        "{}/{}/IMG_20240501_123010_Bärensee.jpg",
+        "Sommerurlaub am Gardasee mit der ganzen Familie",
+        "und viel zu vielen Fotos vom Essen im Restaurant am Hafen"
+    );
+    assert!(name.len() > 100);
+    name
+}
+
+fn test_files() -> Vec<(String, Vec<u8>)> {
+    vec![
+        ("a.jpg".to_owned(), b"not really a jpeg".to_vec()),
+        ("empty.txt".to_owned(), Vec::new()),
+        // more than one copy buffer and not a multiple of the tar block size
+        (
+            "thumbnails/b.avif".to_owned(),
+            (0..200_001u32).map(|i| (i % 251) as u8).collect(),
+        ),
+        (long_name(), b"long name".to_vec()),
+        ("index.html".to_owned(), b"<html></html>".to_vec()),
+    ]
+}
+
+async fn write_archive(format: ArchiveFormat, files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut writer = ArchiveWriter::new(Vec::new(), format);
+    for (name, data) in files {
+        writer.add_bytes(name, modified(), data).await.unwrap();
+    }
+    writer.finish().await.unwrap()
+}
+
+fn read_zip(archive: Vec<u8>) -> Vec<(String, Vec<u8>)> {
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+    (0..zip.len())
+        .map(|i| {
+            let mut file = zip.by_index(i).unwrap();
+            let mut data = Vec::new();
+            // also checks the CRC
+            file.read_to_end(&mut data).unwrap();
+            (file.name().to_owned(), data)
+        })
+        .collect()
+}
+
+fn read_tar(archive: Vec<u8>) -> Vec<(String, Vec<u8>)> {
+    let mut tar = tar::Archive::new(Cursor::new(archive));
+    tar.entries()
+        .unwrap()
+        .map(|entry| {
+            let mut entry = entry.unwrap();
+            assert_eq!(
+                entry.header().mtime().unwrap(),
+                modified().timestamp() as u64
+            );
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data).unwrap();
+            (entry.path().unwrap().to_str().unwrap().to_owned(), data)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn zip_can_be_read_back() {
+    let files = test_files();
+    let archive = write_archive(ArchiveFormat::Zip, &files).await;
+
+    let mut zip = zip::ZipArchive::new(Cursor::new(archive.clone())).unwrap();
+    let file = zip.by_index(0).unwrap();
+    let last_modified = file.last_modified();
+    assert_eq!(
+        (
+            last_modified.year(),
+            last_modified.month(),
+            last_modified.day(),
+            last_modified.hour(),
+            last_modified.minute(),
+            last_modified.second()
+        ),
+        (2024, 5, 1, 12, 30, 10)
+    );
+    assert_eq!(file.unix_mode(), Some(0o100644));
+    drop(file);
+
+    assert_eq!(read_zip(archive), files);
+}
+
+#[tokio::test]
+async fn tar_can_be_read_back() {
+    let files = test_files();
+    let archive = write_archive(ArchiveFormat::Tar, &files).await;
+    assert_eq!(archive.len() % 512, 0);
+    assert_eq!(read_tar(archive), files);
+}
+
+#[tokio::test]
+async fn zip64_end_of_central_directory_for_many_entries() {
+    let files: Vec<(String, Vec<u8>)> = (0..u16::MAX as u32 + 1)
+        .map(|i| (format!("{}.txt", i), Vec::new()))
+        .collect();
+    let archive = write_archive(ArchiveFormat::Zip, &files).await;
+    let zip = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+    assert_eq!(zip.len(), files.len());
+    assert!(zip.file_names().any(|name| name == "65535.txt"));
+}
+
+#[tokio::test]
+async fn file_shorter_than_its_size_is_an_error() {
+    for format in [ArchiveFormat::Zip, ArchiveFormat::Tar] {
+        let mut writer = ArchiveWriter::new(Vec::new(), format);
+        let data: &[u8] = b"truncated";
+        let result = writer
+            .add_file("a.jpg", modified(), data.len() as u64 + 1, data)
+            .await;
+        assert!(result.is_err(), "{:?}", format);
+    }
+}
+
+#[tokio::test]
+async fn file_longer_than_its_size_is_cut_off() {
+    for format in [ArchiveFormat::Zip, ArchiveFormat::Tar] {
+        let mut writer = ArchiveWriter::new(Vec::new(), format);
+        let data: &[u8] = b"grew while reading";
+        writer.add_file("a.jpg", modified(), 4, data).await.unwrap();
+        writer.add_bytes("b.jpg", modified(), b"b").await.unwrap();
+        let archive = writer.finish().await.unwrap();
+        let expected = vec![
+            ("a.jpg".to_owned(), b"grew".to_vec()),
+            ("b.jpg".to_owned(), b"b".to_vec()),
+        ];
+        let entries = match format {
+            ArchiveFormat::Zip => read_zip(archive),
+            ArchiveFormat::Tar => read_tar(archive),
+        };
+        assert_eq!(entries, expected, "{:?}", format);
+    }
+}
@@ -0,0 +1,134 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request},
+    http,
+};
+use ipnet::IpNet;
+
+use core::config::AuthConfig;
+
+use crate::auth::{client_ip, trusted_user_name};
+
+fn request(peer: Option<&str>, forwarded_for: &[&str]) -> Request {
+    let mut builder = http::Request::builder().uri("/api/assets");
+    for value in forwarded_for {
+        builder = builder.header("x-forwarded-for", *value);
+    }
+    let mut request = builder.body(Body::empty()).unwrap();
+    if let Some(peer) = peer {
+        let peer = SocketAddr::new(peer.parse().unwrap(), 51234);
+        request.extensions_mut().insert(ConnectInfo(peer));
+    }
+    request
+}
+
+fn networks(networks: &[&str]) -> Vec<IpNet> {
+    networks.iter().map(|net| net.parse().unwrap()).collect()
+}
+
+fn auth_config(trusted_user: Option<&str>) -> AuthConfig {
+    AuthConfig {
+        trusted_networks: networks(&["192.168.1.0/24", "fd00::/64"]),
+        trusted_user: trusted_user.map(str::to_owned),
+        trusted_proxies: networks(&["10.0.0.1/32", "10.0.0.2/32"]),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn client_ip_table() {
+    #[rustfmt::skip]
+    let cases: &[(&str, &[&str], Option<&str>)] = &[
+        // direct connections
+        ("192.168.1.5", &[], Some("192.168.1.5")),
+        ("203.0.113.7", &[], Some("203.0.113.7")),
+        ("2001:db8::1", &[], Some("2001:db8::1")),
+        // X-Forwarded-For from anyone but a trusted proxy is ignored
+        ("203.0.113.7", &["192.168.1.5"], Some("203.0.113.7")),
+        ("192.168.1.9", &["192.168.1.5"], Some("192.168.1.9")),
+        ("203.0.113.7", &["garbage"], Some("203.0.113.7")),
+        // trusted proxy
+        ("10.0.0.1", &["192.168.1.5"], Some("192.168.1.5")),
+        ("10.0.0.1", &[" 192.168.1.5 "], Some("192.168.1.5")),
+        ("10.0.0.1", &["2001:db8::1"], Some("2001:db8::1")),
+        // proxy that did not set the header
+        ("10.0.0.1", &[], Some("10.0.0.1")),
+        // chain of trusted proxies, in one header or several
+        ("10.0.0.1", &["192.168.1.5, 10.0.0.2"], Some("192.168.1.5")),
+        ("10.0.0.1", &["192.168.1.5", "10.0.0.2"], Some("192.168.1.5")),
+        ("10.0.0.1", &["10.0.0.2, 10.0.0.1"], Some("10.0.0.2")),
+        // entries left of the first untrusted address are made up by the client
+        ("10.0.0.1", &["192.168.1.5, 203.0.113.7"], Some("203.0.113.7")),
+        ("10.0.0.1", &["192.168.1.5, 203.0.113.7, 10.0.0.2"], Some("203.0.113.7")),
+        ("10.0.0.1", &["garbage, 203.0.113.7"], Some("203.0.113.7")),
+        // garbage where the client address should be
+        ("10.0.0.1", &["garbage"], None),
+        ("10.0.0.1", &[""], None),
+        ("10.0.0.1", &["192.168.1.5:1234"], None),
+        ("10.0.0.1", &["192.168.1.5, "], None),
+        ("10.0.0.1", &["192.168.1.5, unknown, 10.0.0.2"], None),
+        // IPv4-mapped IPv6 addresses, e.g. from a dual stack socket
+        ("::ffff:192.168.1.5", &[], Some("192.168.1.5")),
+        ("::ffff:10.0.0.1", &["192.168.1.5"], Some("192.168.1.5")),
+        ("10.0.0.1", &["::ffff:192.168.1.5"], Some("192.168.1.5")),
+        ("10.0.0.1", &["192.168.1.5, ::ffff:10.0.0.2"], Some("192.168.1.5")),
+    ];
+    let trusted_proxies = auth_config(None).trusted_proxies;
+    for (peer, forwarded_for, expected) in cases {
+        let expected: Option<IpAddr> = expected.map(|ip| ip.parse().unwrap());
+        assert_eq!(
+            client_ip(&request(Some(*peer), forwarded_for), &trusted_proxies),
+            expected,
+            "peer {} with X-Forwarded-For {:?}",
+            peer,
+            forwarded_for
+        );
+    }
+}
+
+#[test]
+fn trusted_user_name_table() {
+    #[rustfmt::skip]
+    let cases: &[(&str, &[&str], bool)] = &[
+        ("192.168.1.5", &[], true),
+        ("::ffff:192.168.1.5", &[], true),
+        ("fd00::5", &[], true),
+        ("192.168.2.5", &[], false),
+        ("fd00:0:0:1::5", &[], false),
+        // untrusted peer can't pretend to be on a trusted network
+        ("203.0.113.7", &["192.168.1.5"], false),
+        // but a trusted proxy can forward requests from one
+        ("10.0.0.1", &["192.168.1.5"], true),
+        ("10.0.0.1", &["192.168.1.5", "10.0.0.2"], true),
+        ("10.0.0.1", &["192.168.1.5, 203.0.113.7"], false),
+        ("10.0.0.1", &["garbage"], false),
+        // the proxy itself is not in a trusted network
+        ("10.0.0.1", &[], false),
+    ];
+    let config = auth_config(Some("family"));
+    for (peer, forwarded_for, is_trusted) in cases {
+        assert_eq!(
+            trusted_user_name(&request(Some(*peer), forwarded_for), &config),
+            is_trusted.then(|| "family".to_owned()),
+            "peer {} with X-Forwarded-For {:?}",
+            peer,
+            forwarded_for
+        );
+    }
+}
+
+#[test]
+fn no_trusted_user_without_config_or_peer_address() {
+    let no_user = auth_config(None);
+    assert_eq!(
+        trusted_user_name(&request(Some("192.168.1.5"), &[]), &no_user),
+        None
+    );
+    let config = auth_config(Some("family"));
+    assert_eq!(
+        trusted_user_name(&request(None, &["192.168.1.5"]), &config),
+        None
+    );
+}
@@ -0,0 +1,183 @@
+use std::ops::Range;
+
+use axum::http::{
+    header::{CONTENT_RANGE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, IF_RANGE, RANGE},
+    HeaderMap, HeaderValue, StatusCode,
+};
+use chrono::{TimeZone, Utc};
+
+use crate::file_response::{parse_range, FileMeta, FileResponse};
+
+#[test]
+fn parse_range_table() {
+    #[rustfmt::skip]
+    let cases: &[(&str, u64, Option<Result<Range<u64>, ()>>)] = &[
+        ("bytes=0-499", 1000, Some(Ok(0..500))),
+        ("bytes=0-0", 1000, Some(Ok(0..1))),
+        ("bytes= 10 - 19 ", 1000, Some(Ok(10..20))),
+        // open ended
+        ("bytes=500-", 1000, Some(Ok(500..1000))),
+        ("bytes=0-", 1000, Some(Ok(0..1000))),
+        // end past the end of the file
+        ("bytes=900-5000", 1000, Some(Ok(900..1000))),
+        ("bytes=0-18446744073709551615", 1000, Some(Ok(0..1000))),
+        // suffix
+        ("bytes=-200", 1000, Some(Ok(800..1000))),
+        ("bytes=-1000", 1000, Some(Ok(0..1000))),
+        ("bytes=-5000", 1000, Some(Ok(0..1000))),
+        ("bytes=-0", 1000, Some(Err(()))),
+        // start at or past the end of the file
+        ("bytes=1000-", 1000, Some(Err(()))),
+        ("bytes=1000-1001", 1000, Some(Err(()))),
+        ("bytes=5000-6000", 1000, Some(Err(()))),
+        // empty file
+        ("bytes=0-", 0, Some(Err(()))),
+        ("bytes=0-10", 0, Some(Err(()))),
+        ("bytes=-1", 0, Some(Err(()))),
+        // multiple ranges are ignored
+        ("bytes=0-1,5-10", 1000, None),
+        ("bytes=-1,-2", 1000, None),
+        // malformed
+        ("", 1000, None),
+        ("bytes=", 1000, None),
+        ("bytes=-", 1000, None),
+        ("bytes=5", 1000, None),
+        ("bytes=abc-", 1000, None),
+        ("bytes=0-abc", 1000, None),
+        ("bytes=--5", 1000, None),
+        ("bytes=10-5", 1000, None),
+        ("items=0-10", 1000, None),
+        ("0-10", 1000, None),
+    ];
+    for (header, size, expected) in cases {
+        let header_value = HeaderValue::from_str(header).unwrap();
+        assert_eq!(
+            &parse_range(&header_value, *size),
+            expected,
+            "Range: {:?} of {} bytes",
+            header,
+            size
+        );
+    }
+}
+
+fn meta() -> FileMeta {
+    FileMeta {
+        size: 1000,
+        last_modified: Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(),
+        etag: "\"abc\"".to_owned(),
+    }
+}
+
+fn headers(headers: &[(axum::http::HeaderName, &str)]) -> HeaderMap {
+    headers
+        .iter()
+        .map(|(name, value)| (name.clone(), HeaderValue::from_str(value).unwrap()))
+        .collect()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Expected {
+    Full,
+    Partial(Range<u64>),
+    Status(StatusCode),
+}
+
+#[test]
+fn check_request_table() {
+    let cases: &[(Vec<(axum::http::HeaderName, &str)>, Expected)] = &[
+        (vec![], Expected::Full),
+        // If-None-Match
+        (
+            vec![(IF_NONE_MATCH, "\"abc\"")],
+            Expected::Status(StatusCode::NOT_MODIFIED),
+        ),
+        (
+            vec![(IF_NONE_MATCH, "W/\"abc\"")],
+            Expected::Status(StatusCode::NOT_MODIFIED),
+        ),
+        (
+            vec![(IF_NONE_MATCH, "\"xyz\", \"abc\"")],
+            Expected::Status(StatusCode::NOT_MODIFIED),
+        ),
+        (
+            vec![(IF_NONE_MATCH, "*")],
+            Expected::Status(StatusCode::NOT_MODIFIED),
+        ),
+        (vec![(IF_NONE_MATCH, "\"xyz\"")], Expected::Full),
+        // If-Modified-Since
+        (
+            vec![(IF_MODIFIED_SINCE, "Wed, 01 May 2024 12:00:00 GMT")],
+            Expected::Status(StatusCode::NOT_MODIFIED),
+        ),
+        (
+            vec![(IF_MODIFIED_SINCE, "Thu, 02 May 2024 12:00:00 GMT")],
+            Expected::Status(StatusCode::NOT_MODIFIED),
+        ),
+        (
+            vec![(IF_MODIFIED_SINCE, "Tue, 30 Apr 2024 12:00:00 GMT")],
+            Expected::Full,
+        ),
+        (vec![(IF_MODIFIED_SINCE, "yesterday")], Expected::Full),
+        // If-Modified-Since is ignored if If-None-Match is present
+        (
+            vec![
+                (IF_NONE_MATCH, "\"xyz\""),
+                (IF_MODIFIED_SINCE, "Thu, 02 May 2024 12:00:00 GMT"),
+            ],
+            Expected::Full,
+        ),
+        // Range
+        (vec![(RANGE, "bytes=0-9")], Expected::Partial(0..10)),
+        (
+            vec![(RANGE, "bytes=1000-")],
+            Expected::Status(StatusCode::RANGE_NOT_SATISFIABLE),
+        ),
+        (vec![(RANGE, "bytes=0-1,5-10")], Expected::Full),
+        // If-Range
+        (
+            vec![(RANGE, "bytes=0-9"), (IF_RANGE, "\"abc\"")],
+            Expected::Partial(0..10),
+        ),
+        (
+            vec![(RANGE, "bytes=0-9"), (IF_RANGE, "\"old\"")],
+            Expected::Full,
+        ),
+        (
+            vec![(RANGE, "bytes=1000-"), (IF_RANGE, "\"old\"")],
+            Expected::Full,
+        ),
+        // not modified takes precedence over the range
+        (
+            vec![(RANGE, "bytes=0-9"), (IF_NONE_MATCH, "\"abc\"")],
+            Expected::Status(StatusCode::NOT_MODIFIED),
+        ),
+    ];
+    let meta = meta();
+    for (request_headers, expected) in cases {
+        let result = FileResponse::new(None).check_request(&meta, &headers(request_headers));
+        let actual = match &result {
+            Ok(None) => Expected::Full,
+            Ok(Some(range)) => Expected::Partial(range.clone()),
+            Err(response) => Expected::Status(response.status()),
+        };
+        assert_eq!(&actual, expected, "request headers: {:?}", request_headers);
+    }
+}
+
+#[test]
+fn not_modified_and_unsatisfiable_responses_have_headers() {
+    let meta = meta();
+    let not_modified = FileResponse::new(None)
+        .check_request(&meta, &headers(&[(IF_NONE_MATCH, "\"abc\"")]))
+        .unwrap_err();
+    assert_eq!(not_modified.headers().get(ETAG).unwrap(), "\"abc\"");
+
+    let unsatisfiable = FileResponse::new(None)
+        .check_request(&meta, &headers(&[(RANGE, "bytes=2000-")]))
+        .unwrap_err();
+    assert_eq!(
+        unsatisfiable.headers().get(CONTENT_RANGE).unwrap(),
+        "bytes */1000"
+    );
+}
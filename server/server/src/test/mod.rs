@@ -0,0 +1,4 @@
+pub mod album;
+pub mod archive;
+pub mod auth;
+pub mod file_response;